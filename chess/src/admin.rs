@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+use tokio_tungstenite::accept_async_with_config;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{GameMessage, IntegrityIssue, PlayerRole, RoomId, RoomManager, UserManager};
+
+// 没写理由时发给被踢玩家的说明
+const DEFAULT_KICK_REASON: &str = "管理员已将你移出对局";
+
+// 管理通道的命令，连接后第一条必须是 Auth
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AdminCommand {
+    Auth {
+        token: String,
+    },
+    ListGames,
+    // 把玩家移出对局，对局没下完时判负
+    Kick {
+        username: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    // 维护通知，发给所有在座的玩家和观战者
+    Broadcast {
+        message: String,
+    },
+    // 不再开始新的对局，进行中的对局照常下完
+    Drain,
+    // 载入时没通过完整性校验、没有恢复的对局
+    ListFlagged,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AdminReply {
+    Authenticated,
+    Games { rooms: Vec<RoomInfo> },
+    Kicked { username: String, room: RoomId },
+    Broadcasted { recipients: usize },
+    // 还没下完的对局数，降到 0 时可以停服
+    Draining { active_games: usize },
+    Flagged { games: Vec<IntegrityIssue> },
+    Error(String),
+}
+
+// 一个房间的概况，和公开的对局列表不同，空房间和已结束的也列出
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub room: RoomId,
+    pub game_id: String,
+    pub black: Option<String>,
+    pub white: Option<String>,
+    pub move_count: usize,
+    pub spectators: usize,
+    pub paused: bool,
+    pub finished: bool,
+}
+
+// 管理通道：单独的 WebSocket 端口，只接受管理员令牌
+pub async fn serve(
+    listener: TcpListener,
+    rooms: Arc<Mutex<RoomManager>>,
+    users: Arc<RwLock<UserManager>>,
+) {
+    while let Ok((stream, addr)) = listener.accept().await {
+        let rooms = rooms.clone();
+        let users = users.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &rooms, &users).await {
+                println!("管理连接 {} 处理失败: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    stream: TcpStream,
+    rooms: &Mutex<RoomManager>,
+    users: &RwLock<UserManager>,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let limits = rooms.lock().await.config().limits;
+    let mut ws = accept_async_with_config(stream, Some(limits.websocket_config())).await?;
+    let mut authenticated = false;
+    while let Some(frame) = ws.next().await {
+        let Message::Text(text) = frame? else {
+            continue;
+        };
+        let reply = match serde_json::from_str::<AdminCommand>(&text) {
+            Ok(AdminCommand::Auth { token }) => {
+                authenticated = users.read().await.is_admin(&token);
+                if authenticated {
+                    AdminReply::Authenticated
+                } else {
+                    AdminReply::Error("需要管理员权限".to_string())
+                }
+            }
+            Ok(_) if !authenticated => AdminReply::Error("请先发送管理员令牌".to_string()),
+            Ok(command) => execute(rooms, command).await,
+            Err(e) => AdminReply::Error(format!("无法解析管理命令: {}", e)),
+        };
+        // 没通过认证就断开，不给反复试令牌的机会
+        let rejected = !authenticated;
+        ws.send(Message::Text(serde_json::to_string(&reply).unwrap()))
+            .await?;
+        if rejected {
+            ws.close(None).await?;
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn execute(rooms: &Mutex<RoomManager>, command: AdminCommand) -> AdminReply {
+    match command {
+        AdminCommand::Auth { .. } => AdminReply::Authenticated,
+        AdminCommand::ListGames => AdminReply::Games {
+            rooms: list_rooms(rooms).await,
+        },
+        AdminCommand::Kick { username, reason } => {
+            let reason = reason.unwrap_or_else(|| DEFAULT_KICK_REASON.to_string());
+            let games = rooms.lock().await.games();
+            for (room, game) in games.into_iter().enumerate() {
+                if game.lock().await.kick(&username, &reason).await.is_some() {
+                    println!("管理员将 {} 移出房间 {}", username, room);
+                    return AdminReply::Kicked { username, room };
+                }
+            }
+            AdminReply::Error(format!("{} 不在任何对局中", username))
+        }
+        AdminCommand::Broadcast { message } => {
+            println!("管理员广播: {}", message);
+            let games = rooms.lock().await.games();
+            let mut recipients = 0;
+            for game in games {
+                recipients += game
+                    .lock()
+                    .await
+                    .notify_all(GameMessage::ServerNotice {
+                        message: message.clone(),
+                    })
+                    .await;
+            }
+            AdminReply::Broadcasted { recipients }
+        }
+        AdminCommand::Drain => {
+            let mut rooms = rooms.lock().await;
+            rooms.set_draining();
+            let active_games = rooms.active_games().await;
+            println!("服务器进入维护准备，还有 {} 盘对局进行中", active_games);
+            AdminReply::Draining { active_games }
+        }
+        AdminCommand::ListFlagged => AdminReply::Flagged {
+            games: rooms.lock().await.flagged().await,
+        },
+    }
+}
+
+async fn list_rooms(rooms: &Mutex<RoomManager>) -> Vec<RoomInfo> {
+    let games = rooms.lock().await.games();
+    let mut infos = Vec::with_capacity(games.len());
+    for (room, game) in games.into_iter().enumerate() {
+        let game = game.lock().await;
+        infos.push(RoomInfo {
+            room,
+            game_id: game.id().to_string(),
+            black: game.player_name(PlayerRole::Black).map(str::to_string),
+            white: game.player_name(PlayerRole::White).map(str::to_string),
+            move_count: game.moves().len(),
+            spectators: game.spectator_count(),
+            paused: game.is_paused(),
+            finished: game.is_finished(),
+        });
+    }
+    infos
+}
@@ -1,34 +1,310 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(feature = "server")]
 use std::sync::Arc;
 
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
 use tokio::{
     sync::{mpsc, Mutex},
     task::JoinHandle,
 };
 
-use crate::{Board, Game, GameError, GameMessage, PlayerRole};
+use crate::mcts::MctsEngine;
+use crate::movegen::{self, DIRECTIONS};
+use crate::{zobrist, Board, MoveRecord, PlayerRole, RulesConfig};
+#[cfg(feature = "server")]
+use crate::{AiThrottle, Game, GameError, GameMessage, MoveOutcome};
 
+type Grid = [[Option<PlayerRole>; 15]; 15];
+
+// 连成一线的分值，比任何局面评估都大
+const WIN_SCORE: i32 = 1_000_000;
+const INFINITY: i32 = i32::MAX / 2;
+// 每层只搜索启发分最高的这些候选点
+const MAX_CANDIDATES: usize = 12;
+// 六子棋成对搜索时，两两组合的候选点数
+const PAIR_CANDIDATES: usize = 16;
+
+// 服务器内置电脑对手的难度，对应搜索层数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn depth(self) -> usize {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 5,
+        }
+    }
+
+    // 搜索前先找连续冲四杀棋时，进攻方最多冲四的次数
+    pub fn vcf_depth(self) -> usize {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Medium => 4,
+            Difficulty::Hard => 8,
+        }
+    }
+
+    // 蒙特卡洛树搜索每步的模拟局数
+    pub fn playouts(self) -> usize {
+        match self {
+            Difficulty::Easy => 200,
+            Difficulty::Medium => 1_000,
+            Difficulty::Hard => 3_000,
+        }
+    }
+
+    pub fn budget(self) -> Budget {
+        Budget {
+            depth: self.depth(),
+            vcf_depth: self.vcf_depth(),
+            playouts: self.playouts(),
+        }
+    }
+}
+
+// 每步棋允许的计算量，各引擎只看自己用得到的部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub depth: usize,
+    pub vcf_depth: usize,
+    pub playouts: usize,
+}
+
+// 电脑对手的选点算法，棋盘下满时返回 None
+pub trait Engine: Send + Sync {
+    fn choose_move(
+        &self,
+        board: &Board,
+        player: PlayerRole,
+        budget: Budget,
+    ) -> Option<(usize, usize)>;
+
+    // 这一回合要下的所有子：六子棋一回合两子时成对搜索，否则就是 choose_move 的一步
+    fn choose_turn(
+        &self,
+        board: &Board,
+        player: PlayerRole,
+        budget: Budget,
+    ) -> Vec<(usize, usize)> {
+        match board.stones_left() {
+            1 => self
+                .choose_move(board, player, budget)
+                .into_iter()
+                .collect(),
+            _ => choose_pair(board, player),
+        }
+    }
+}
+
+// 服务器配置里选择的引擎
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineKind {
+    // 连续冲四 + alpha-beta 搜索
+    #[default]
+    Search,
+    // 蒙特卡洛树搜索
+    Mcts,
+}
+
+impl EngineKind {
+    pub fn engine(self) -> Box<dyn Engine> {
+        match self {
+            EngineKind::Search => Box::new(SearchEngine),
+            EngineKind::Mcts => Box::new(MctsEngine::default()),
+        }
+    }
+
+    // 随机选择由种子决定的引擎，同一盘棋重放时走法完全一样
+    pub fn seeded(self, seed: u64) -> Box<dyn Engine> {
+        match self {
+            EngineKind::Search => Box::new(SearchEngine),
+            EngineKind::Mcts => Box::new(MctsEngine::seeded(seed)),
+        }
+    }
+}
+
+pub struct SearchEngine;
+
+impl Engine for SearchEngine {
+    fn choose_move(
+        &self,
+        board: &Board,
+        player: PlayerRole,
+        budget: Budget,
+    ) -> Option<(usize, usize)> {
+        // 有连续冲四的杀棋就直接走，不用再搜索
+        if let Some(line) = solve_vcf(board, player, budget.vcf_depth) {
+            return Some(line[0]);
+        }
+        Search::new(board, player)
+            .root(budget.depth.max(1))
+            .map(|(mv, _)| mv)
+    }
+}
+
+// 局面的静态评估，从 player 一方看，正数表示 player 占优；用于评估条和评估曲线
+pub fn evaluate(board: &Board, player: PlayerRole) -> i32 {
+    evaluate_board(&board.cells, board.rules, player)
+}
+
+// 一盘棋每一步之后的评估，从黑方看
+pub fn evaluation_graph(moves: &[MoveRecord], rules: RulesConfig) -> Vec<i32> {
+    let mut cells: Grid = [[None; 15]; 15];
+    moves
+        .iter()
+        .map(|m| {
+            cells[m.row][m.col] = Some(m.player);
+            evaluate_board(&cells, rules, PlayerRole::Black)
+        })
+        .collect()
+}
+
+// 六子棋一回合的两子：候选点两两组合，能连成一线就直接走，否则选落下两子后
+// 对手下一回合不能直接取胜、局面评估最好的一对。只有一个空位时返回一子
+pub fn choose_pair(board: &Board, player: PlayerRole) -> Vec<(usize, usize)> {
+    let rules = board.rules;
+    let mut cells = board.cells;
+    let candidates = movegen::candidate_moves(&cells, rules, player, PAIR_CANDIDATES);
+    let mut best: Option<(i32, Vec<(usize, usize)>)> = None;
+    for (i, &first) in candidates.iter().enumerate() {
+        cells[first.0][first.1] = Some(player);
+        if is_win(&cells, rules, first.0, first.1, player) {
+            return vec![first];
+        }
+        for &second in &candidates[i + 1..] {
+            cells[second.0][second.1] = Some(player);
+            if is_win(&cells, rules, second.0, second.1, player) {
+                return vec![first, second];
+            }
+            let mut score = evaluate_board(&cells, rules, player);
+            if wins_next_turn(&cells, rules, player.other()) {
+                score -= WIN_SCORE;
+            }
+            cells[second.0][second.1] = None;
+            if best.as_ref().is_none_or(|&(best, _)| score > best) {
+                best = Some((score, vec![first, second]));
+            }
+        }
+        cells[first.0][first.1] = None;
+    }
+    match best {
+        Some((_, pair)) => pair,
+        None => candidates.into_iter().take(1).collect(),
+    }
+}
+
+// win_length 格的窗口里只有一方的 n 颗棋子时的分值：差一颗成线时 1000，每少一颗除以 10
+fn window_score(stones: usize, win_length: usize) -> i32 {
+    match win_length - stones {
+        0 => WIN_SCORE,
+        missing => 10_000 / 10i32.pow(missing as u32),
+    }
+}
+
+// 给人类玩家的提示：建议的落子，以及从 player 一方看的局面分数，
+// 找到连续冲四杀棋时为 WIN_SCORE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suggestion {
+    pub row: usize,
+    pub col: usize,
+    pub score: i32,
+}
+
+pub fn suggest_move(board: &Board, player: PlayerRole, budget: Budget) -> Option<Suggestion> {
+    let ((row, col), score) = match solve_vcf(board, player, budget.vcf_depth) {
+        Some(line) => (line[0], WIN_SCORE),
+        None => Search::new(board, player).root(budget.depth.max(1))?,
+    };
+    Some(Suggestion { row, col, score })
+}
+
+#[cfg(feature = "server")]
 pub struct AIPlayer {
     pub player: PlayerRole,
-    depth: usize,
+    budget: Budget,
+    engine: Box<dyn Engine>,
     game: Arc<Mutex<Game>>,
+    // 服务器上的电脑对手共用，忙的时候缩小计算量；不设置时总按难度的计算量搜索
+    throttle: Option<Arc<AiThrottle>>,
 }
 
+#[cfg(feature = "server")]
 impl AIPlayer {
     pub fn new(player: PlayerRole, game: Arc<Mutex<Game>>) -> Self {
         Self {
             player,
-            depth: 3, // 增加搜索深度
+            budget: Budget {
+                depth: 3, // 增加搜索深度
+                vcf_depth: 8,
+                playouts: 1_000,
+            },
+            engine: Box::new(SearchEngine),
             game,
+            throttle: None,
         }
     }
 
-    pub async fn start(&mut self, mut rx: mpsc::Receiver<GameMessage>) -> JoinHandle<()> {
+    pub fn with_difficulty(
+        player: PlayerRole,
+        game: Arc<Mutex<Game>>,
+        difficulty: Difficulty,
+    ) -> Self {
+        Self {
+            player,
+            budget: difficulty.budget(),
+            engine: Box::new(SearchEngine),
+            game,
+            throttle: None,
+        }
+    }
+
+    pub fn with_engine(mut self, engine: Box<dyn Engine>) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn with_throttle(mut self, throttle: Arc<AiThrottle>) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    // 坐在游戏里的电脑对手：轮到自己时落子，对局结束后离开座位。
+    // 落子和离开都另起任务，收消息的循环不会在等游戏锁时堵住游戏的发送；
+    // 离开后继续收消息直到游戏丢掉发送端，避免游戏往已关闭的通道发送
+    pub fn start(self, mut rx: mpsc::Receiver<GameMessage>) -> JoinHandle<()> {
+        let ai = Arc::new(self);
         tokio::spawn(async move {
+            let mut left = false;
             while let Some(message) = rx.recv().await {
-                println!("AI 收到消息: {:?}", message);
                 match message {
-                    GameMessage::Move { row, col } => {
-                        println!("AI 收到移动消息: ({}, {})", row, col);
+                    GameMessage::TurnNotification { player } if player == ai.player && !left => {
+                        let ai = ai.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = ai.action().await {
+                                println!("AI 落子失败: {}", e);
+                            }
+                        });
+                    }
+                    GameMessage::GameOver { .. } | GameMessage::PlayerDisconnected { .. }
+                        if !left =>
+                    {
+                        println!("AI 玩家 {:?} 离开对局", ai.player);
+                        left = true;
+                        let ai = ai.clone();
+                        tokio::spawn(async move {
+                            ai.game.lock().await.remove_player(ai.player).await;
+                        });
                     }
                     _ => {}
                 }
@@ -36,193 +312,448 @@ impl AIPlayer {
         })
     }
 
-    fn evaluate_position(&self, board: &Board, row: usize, col: usize, player: PlayerRole) -> i32 {
-        let mut score = 0;
-        let directions = [
-            (0, 1),  // 水平
-            (1, 0),  // 垂直
-            (1, 1),  // 对角线
-            (1, -1), // 反对角线
-        ];
-
-        // 位置评分：中心位置更有价值
-        let center = 7;
-        let distance_to_center =
-            ((row as i32 - center as i32).abs() + (col as i32 - center as i32).abs()) as i32;
-        score += (10 - distance_to_center) * 10;
-
-        // 评估周围棋子
-        let mut adjacent_own = 0;
-        let mut adjacent_opponent = 0;
-        for &(dr, dc) in &directions {
-            let r = row as i32 + dr;
-            let c = col as i32 + dc;
-            if r >= 0 && r < 15 && c >= 0 && c < 15 {
-                match board.cells[r as usize][c as usize] {
-                    Some(p) if p == player => adjacent_own += 1,
-                    Some(_) => adjacent_opponent += 1,
-                    None => {}
+    // 复制局面后放开对局锁，搜索放到阻塞线程里，只在提交落子时再短暂加锁，
+    // 搜索期间对局照常收发消息
+    pub async fn action(self: Arc<Self>) -> Result<(), GameError> {
+        let (board, game_id, moves) = {
+            let game = self.game.lock().await;
+            // 六子棋一回合的两子一起下，第一子之后的轮到通知已经过时
+            if game.board.current_player != self.player {
+                return Ok(());
+            }
+            (
+                game.position(),
+                game.id().to_string(),
+                game.board.moves.len(),
+            )
+        };
+        let (budget, permit) = match &self.throttle {
+            Some(throttle) => {
+                let (budget, permit) = throttle.acquire(self.budget);
+                if budget != self.budget {
+                    println!(
+                        "服务器繁忙（{} 个电脑对手同时搜索），{:?} 这一步的计算量降为 {:?}",
+                        throttle.active_searches(),
+                        self.player,
+                        budget
+                    );
                 }
+                (budget, Some(permit))
             }
+            None => (self.budget, None),
+        };
+        let ai = self.clone();
+        let stones = tokio::task::spawn_blocking(move || {
+            // 搜索结束才算空出一个名额
+            let _permit = permit;
+            ai.search_turn(&board, budget)
+        })
+        .await
+        .map_err(|e| GameError::InvalidMove(format!("搜索任务异常退出: {}", e)))??;
+
+        let mut game = self.game.lock().await;
+        // 搜索期间对局已经结束或换了新的一盘，这一步作废
+        if game.id() != game_id || game.board.moves.len() != moves {
+            return Ok(());
         }
-        score += adjacent_own * 50; // 靠近自己的棋子加分
-        score -= adjacent_opponent * 30; // 靠近对手的棋子减分
-
-        for &(dr, dc) in &directions {
-            let mut count = 0;
-            let mut empty = 0;
-            let mut blocked = 0;
-            let mut consecutive = true;
-
-            // 正向检查
-            for i in 1..5 {
-                let r = row as i32 + dr * i;
-                let c = col as i32 + dc * i;
-                if r < 0 || r >= 15 || c < 0 || c >= 15 {
-                    blocked += 1;
-                    break;
-                }
-                match board.cells[r as usize][c as usize] {
-                    Some(p) if p == player => {
-                        if consecutive {
-                            count += 1;
-                        }
-                    }
-                    None => {
-                        empty += 1;
-                        consecutive = true;
-                    }
-                    _ => {
-                        blocked += 1;
-                        consecutive = false;
-                    }
-                }
+        for (row, col) in stones {
+            if game.make_move(self.player, row, col).await? != MoveOutcome::Continue {
+                break;
             }
+        }
+        Ok(())
+    }
 
-            // 反向检查
-            consecutive = true;
-            for i in 1..5 {
-                let r = row as i32 - dr * i;
-                let c = col as i32 - dc * i;
-                if r < 0 || r >= 15 || c < 0 || c >= 15 {
-                    blocked += 1;
-                    break;
-                }
-                match board.cells[r as usize][c as usize] {
-                    Some(p) if p == player => {
-                        if consecutive {
-                            count += 1;
-                        }
-                    }
-                    None => {
-                        empty += 1;
-                        consecutive = true;
-                    }
-                    _ => {
-                        blocked += 1;
-                        consecutive = false;
+    // 这一回合要下的所有子
+    pub fn make_turn(&self, board: &Board) -> Result<Vec<(usize, usize)>, GameError> {
+        self.search_turn(board, self.budget)
+    }
+
+    fn search_turn(&self, board: &Board, budget: Budget) -> Result<Vec<(usize, usize)>, GameError> {
+        let stones = self.engine.choose_turn(board, self.player, budget);
+        if stones.is_empty() {
+            return Err(GameError::InvalidMove("没有可用的位置".to_string()));
+        }
+        Ok(stones)
+    }
+
+    pub fn make_move(&self, board: &Board) -> Result<(usize, usize), GameError> {
+        self.engine
+            .choose_move(board, self.player, self.budget)
+            .ok_or_else(|| GameError::InvalidMove("没有可用的位置".to_string()))
+    }
+}
+
+// 棋盘上所有 win_length 格的窗口里 player 和对方各有几颗子
+fn windows(
+    cells: &Grid,
+    rules: RulesConfig,
+    player: PlayerRole,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let span = rules.win_length as i32 - 1;
+    rules.points().flat_map(move |(row, col)| {
+        let (row, col) = (row as i32, col as i32);
+        DIRECTIONS
+            .iter()
+            .filter(move |&&(dr, dc)| rules.contains(row + dr * span, col + dc * span))
+            .map(move |&(dr, dc)| {
+                let (mut own, mut other) = (0, 0);
+                for i in 0..=span {
+                    match cells[(row + dr * i) as usize][(col + dc * i) as usize] {
+                        Some(p) if p == player => own += 1,
+                        Some(_) => other += 1,
+                        None => {}
                     }
                 }
-            }
+                (own, other)
+            })
+    })
+}
+
+// 整个棋盘的局面评估：统计所有 win_length 格的窗口，站在 player 一方
+fn evaluate_board(cells: &Grid, rules: RulesConfig, player: PlayerRole) -> i32 {
+    let mut score = 0;
+    for (own, other) in windows(cells, rules, player) {
+        if other == 0 {
+            score += window_score(own, rules.win_length);
+        } else if own == 0 {
+            score -= window_score(other, rules.win_length);
+        }
+    }
+    score
+}
 
-            // 计算棋型分数
-            if count >= 4 {
-                score += 100000; // 必胜
-            } else if count == 3 && empty >= 1 {
-                score += 10000; // 活四
-            } else if count == 2 && empty >= 2 {
-                score += 1000; // 活三
+// player 下一回合能否直接连成一线：某个窗口里没有对方的子，空位不超过一回合的落子数
+fn wins_next_turn(cells: &Grid, rules: RulesConfig, player: PlayerRole) -> bool {
+    windows(cells, rules, player)
+        .any(|(own, other)| other == 0 && own + rules.stones_per_turn >= rules.win_length)
+}
+
+// 刚落下的子是否连成 win_length 子
+pub(crate) fn is_win(
+    cells: &Grid,
+    rules: RulesConfig,
+    row: usize,
+    col: usize,
+    player: PlayerRole,
+) -> bool {
+    DIRECTIONS.iter().any(|&(dr, dc)| {
+        let mut count = 1;
+        for sign in [1, -1] {
+            let (mut r, mut c) = (row as i32 + dr * sign, col as i32 + dc * sign);
+            while rules.contains(r, c) && cells[r as usize][c as usize] == Some(player) {
+                count += 1;
+                r += dr * sign;
+                c += dc * sign;
             }
         }
+        count >= rules.win_length
+    })
+}
 
-        score
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    // 发生了剪枝，真实分数不低于 score
+    Lower,
+    // 所有走法都没超过 alpha，真实分数不高于 score
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct TableEntry {
+    depth: usize,
+    score: i32,
+    bound: Bound,
+    best: (usize, usize),
+}
+
+// 一次搜索的状态：在同一个棋盘上落子再撤回，Zobrist 哈希随之增量更新，
+// 置换表记住已经搜过的局面，换个顺序走到同一局面时不再重复搜索
+struct Search {
+    player: PlayerRole,
+    cells: Grid,
+    rules: RulesConfig,
+    hash: u64,
+    table: HashMap<u64, TableEntry>,
+}
+
+impl Search {
+    fn new(board: &Board, player: PlayerRole) -> Self {
+        Self {
+            player,
+            cells: board.cells,
+            rules: board.rules,
+            // 从棋子重新计算，调用方直接改过 cells 时也是对的
+            hash: zobrist::position_hash(&board.cells, player),
+            table: HashMap::new(),
+        }
     }
 
-    // 模拟下一步
-    fn simulate_move(
+    fn place(&mut self, row: usize, col: usize, player: PlayerRole) {
+        self.cells[row][col] = Some(player);
+        self.hash ^= zobrist::stone_key(row, col, player) ^ zobrist::WHITE_TO_MOVE;
+    }
+
+    fn undo(&mut self, row: usize, col: usize, player: PlayerRole) {
+        self.cells[row][col] = None;
+        self.hash ^= zobrist::stone_key(row, col, player) ^ zobrist::WHITE_TO_MOVE;
+    }
+
+    // 候选点，置换表里记录的最佳走法排在最前面
+    fn ordered_moves(
         &self,
-        board: &Board,
+        player: PlayerRole,
+        best: Option<(usize, usize)>,
+    ) -> Vec<(usize, usize)> {
+        let mut moves = movegen::candidate_moves(&self.cells, self.rules, player, MAX_CANDIDATES);
+        if let Some(index) = best.and_then(|best| moves.iter().position(|&m| m == best)) {
+            moves[..=index].rotate_right(1);
+        }
+        moves
+    }
+
+    // 落子后的分数：连成一线直接取胜，越早取胜分越高，否则继续向下搜索
+    fn score_move(
+        &mut self,
         row: usize,
         col: usize,
         player: PlayerRole,
         depth: usize,
+        alpha: i32,
+        beta: i32,
     ) -> i32 {
+        self.place(row, col, player);
+        let score = if is_win(&self.cells, self.rules, row, col, player) {
+            WIN_SCORE + depth as i32
+        } else {
+            -self.negamax(player.other(), depth - 1, -beta, -alpha)
+        };
+        self.undo(row, col, player);
+        score
+    }
+
+    // 带 alpha-beta 剪枝的负极大值搜索
+    fn negamax(&mut self, player: PlayerRole, depth: usize, mut alpha: i32, mut beta: i32) -> i32 {
         if depth == 0 {
-            return self.evaluate_position(board, row, col, player);
+            return evaluate_board(&self.cells, self.rules, player);
+        }
+        let original_alpha = alpha;
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(&self.hash) {
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower => alpha = alpha.max(entry.score),
+                    Bound::Upper => beta = beta.min(entry.score),
+                }
+                if alpha >= beta {
+                    return entry.score;
+                }
+            }
+            table_move = Some(entry.best);
+        }
+
+        let moves = self.ordered_moves(player, table_move);
+        let Some(&first) = moves.first() else {
+            return 0; // 棋盘下满，平局
+        };
+        let (mut best, mut best_move) = (-INFINITY, first);
+        for (row, col) in moves {
+            let score = self.score_move(row, col, player, depth, alpha, beta);
+            if score > best {
+                best = score;
+                best_move = (row, col);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
         }
 
-        let mut score = 0;
-        let opponent = player.other();
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(
+            self.hash,
+            TableEntry {
+                depth,
+                score: best,
+                bound,
+                best: best_move,
+            },
+        );
+        best
+    }
+
+    // 第一个候选点先单独搜出一个下界，其余候选点在线程池里并行搜索，
+    // 各自带一张置换表，共享目前最好的分数用来剪枝
+    // 最佳走法和它的分数
+    fn root(&mut self, depth: usize) -> Option<((usize, usize), i32)> {
+        let player = self.player;
+        let moves = self.ordered_moves(player, None);
+        let (&first, rest) = moves.split_first()?;
+        let first_score = self.score_move(first.0, first.1, player, depth, -INFINITY, INFINITY);
 
-        // 评估当前移动
-        score += self.evaluate_position(board, row, col, player);
+        let alpha = AtomicI32::new(first_score);
+        let results: Vec<Option<i32>> = rest
+            .par_iter()
+            .map(|&(row, col)| {
+                let mut search = Search {
+                    player,
+                    cells: self.cells,
+                    rules: self.rules,
+                    hash: self.hash,
+                    table: HashMap::new(),
+                };
+                let floor = alpha.load(Ordering::Relaxed);
+                let score = search.score_move(row, col, player, depth, floor, INFINITY);
+                // 不超过下界时只是个上界，不能拿来比较
+                (score > floor).then(|| {
+                    alpha.fetch_max(score, Ordering::Relaxed);
+                    score
+                })
+            })
+            .collect();
 
-        // 评估对手可能的回应
-        let mut best_opponent_score = 0;
-        for r in 0..15 {
-            for c in 0..15 {
-                if board.cells[r][c].is_none() {
-                    let opponent_score = self.simulate_move(board, r, c, opponent, depth - 1);
-                    best_opponent_score = best_opponent_score.max(opponent_score);
-                }
+        // 分数相同时取排在前面的候选点
+        let mut best = (first_score, first);
+        for (&mv, score) in rest.iter().zip(results) {
+            if let Some(score) = score.filter(|&score| score > best.0) {
+                best = (score, mv);
             }
         }
-        score -= best_opponent_score / 2; // 考虑对手的最佳回应
+        Some((best.1, best.0))
+    }
+}
 
-        score
+// 找 player 连续冲四（VCF）取胜的着法：进攻方每一步都走成四，防守方只能去堵，
+// 直到进攻方同时有两个成五点或者直接成五。返回双方交替的落子，最后一步是进攻方成五；
+// max_depth 是进攻方最多冲四的次数，找不到时返回 None
+pub fn solve_vcf(
+    board: &Board,
+    player: PlayerRole,
+    max_depth: usize,
+) -> Option<Vec<(usize, usize)>> {
+    let mut solver = VcfSolver {
+        cells: board.cells,
+        rules: board.rules,
+        hash: zobrist::position_hash(&board.cells, player),
+        failed: HashMap::new(),
+    };
+    let mut line = Vec::new();
+    solver.attack(player, max_depth, &mut line).then_some(line)
+}
+
+struct VcfSolver {
+    cells: Grid,
+    rules: RulesConfig,
+    hash: u64,
+    // 已经证明在这么多步以内冲不出来的局面
+    failed: HashMap<u64, usize>,
+}
+
+impl VcfSolver {
+    fn place(&mut self, row: usize, col: usize, player: PlayerRole) {
+        self.cells[row][col] = Some(player);
+        self.hash ^= zobrist::stone_key(row, col, player);
     }
 
-    pub async fn action(&self) -> Result<(), GameError> {
-        let mut game = self.game.lock().await;
-        let (row, col) = self.make_move(&game.board).unwrap();
-        game.make_move(self.player, row, col).await
+    fn undo(&mut self, row: usize, col: usize, player: PlayerRole) {
+        self.cells[row][col] = None;
+        self.hash ^= zobrist::stone_key(row, col, player);
     }
-    pub fn make_move(&self, board: &Board) -> Result<(usize, usize), GameError> {
-        let mut best_score = -1;
-        let mut best_move = None;
-        let opponent = self.player.other();
-
-        // 首先检查是否有必胜的位置
-        for row in 0..15 {
-            for col in 0..15 {
-                if board.cells[row][col].is_none() {
-                    let attack_score = self.evaluate_position(board, row, col, self.player);
-                    if attack_score >= 100000 {
-                        return Ok((row, col));
-                    }
-                }
+
+    fn completes_five(&mut self, row: usize, col: usize, player: PlayerRole) -> bool {
+        if self.cells[row][col].is_some() {
+            return false;
+        }
+        self.cells[row][col] = Some(player);
+        let five = is_win(&self.cells, self.rules, row, col, player);
+        self.cells[row][col] = None;
+        five
+    }
+
+    // player 下一步就能成五的所有空位
+    fn five_points(&mut self, player: PlayerRole) -> Vec<(usize, usize)> {
+        let mut points = Vec::new();
+        for (row, col) in self.rules.points() {
+            if self.completes_five(row, col, player) {
+                points.push((row, col));
             }
         }
+        points
+    }
 
-        // 检查是否需要防守对手的必胜位置或活四
-        for row in 0..15 {
-            for col in 0..15 {
-                if board.cells[row][col].is_none() {
-                    let defense_score = self.evaluate_position(board, row, col, opponent);
-                    if defense_score >= 100000 || defense_score >= 10000 {
-                        return Ok((row, col));
-                    }
+    // 刚在 (row, col) 落子后，经过这一点的四条线上的成五点
+    fn fives_through(&mut self, row: usize, col: usize, player: PlayerRole) -> Vec<(usize, usize)> {
+        let mut points = Vec::new();
+        let span = self.rules.win_length as i32 - 1;
+        for &(dr, dc) in &DIRECTIONS {
+            for step in (-span..=span).filter(|&step| step != 0) {
+                let (r, c) = (row as i32 + dr * step, col as i32 + dc * step);
+                if !self.rules.contains(r, c) {
+                    continue;
+                }
+                let point = (r as usize, c as usize);
+                if !points.contains(&point) && self.completes_five(point.0, point.1, player) {
+                    points.push(point);
                 }
             }
         }
+        points
+    }
+
+    fn attack(&mut self, player: PlayerRole, depth: usize, line: &mut Vec<(usize, usize)>) -> bool {
+        if let Some(&point) = self.five_points(player).first() {
+            line.push(point);
+            return true;
+        }
+        if depth == 0 || self.failed.get(&self.hash).is_some_and(|&d| d >= depth) {
+            return false;
+        }
 
-        // 寻找最佳进攻位置，考虑对手的回应
-        for row in 0..15 {
-            for col in 0..15 {
-                if board.cells[row][col].is_none() {
-                    let total_score = self.simulate_move(board, row, col, self.player, self.depth);
-                    if total_score > best_score {
-                        best_score = total_score;
-                        best_move = Some((row, col));
+        // 对手已经有成五点时，只能在那一点上冲四；有两个就挡不住了
+        let moves: Vec<(usize, usize)> = match self.five_points(player.other())[..] {
+            [] => self
+                .rules
+                .points()
+                .filter(|&(row, col)| self.cells[row][col].is_none())
+                .collect(),
+            [point] => vec![point],
+            _ => return false,
+        };
+        for (row, col) in moves {
+            self.place(row, col, player);
+            let found = match self.fives_through(row, col, player)[..] {
+                [] => false,
+                // 冲四，对手必须堵在唯一的成五点上
+                [block] => {
+                    self.place(block.0, block.1, player.other());
+                    line.extend([(row, col), block]);
+                    let found = self.attack(player, depth - 1, line);
+                    if !found {
+                        line.truncate(line.len() - 2);
                     }
+                    self.undo(block.0, block.1, player.other());
+                    found
+                }
+                // 四四或活四，堵住一个还有另一个
+                [block, win, ..] => {
+                    line.extend([(row, col), block, win]);
+                    true
                 }
+            };
+            self.undo(row, col, player);
+            if found {
+                return true;
             }
         }
-
-        if let Some((row, col)) = best_move {
-            Ok((row, col))
-        } else {
-            Err(GameError::InvalidMove("没有可用的位置".to_string()))
-        }
+        self.failed.insert(self.hash, depth);
+        false
     }
 }
@@ -0,0 +1,178 @@
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use crate::{zobrist, IntegrityIssue, SharedStore};
+use crate::{GameOverReason, MoveRecord, PlayerRole, RulesConfig};
+
+// 一盘已结束的对局。存储和传输的格式带版本号，见 record.rs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Value", into = "crate::record::RecordV2")]
+pub struct ArchivedGame {
+    pub id: String,
+    pub black: String,
+    pub white: String,
+    pub winner: Option<PlayerRole>,
+    // 旧存档里认输、超时的对局没有
+    pub reason: Option<GameOverReason>,
+    pub moves: Vec<MoveRecord>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    // 对局的随机种子，拿它和同样的引擎设置可以复现服务器电脑的每一步；旧存档没有
+    pub seed: Option<u64>,
+    // 旧存档都是 15 路连五
+    pub rules: RulesConfig,
+    // 终局的 Zobrist 哈希，载入时和重放的结果核对；旧存档没有
+    pub position_hash: Option<u64>,
+}
+
+// 已结束对局的存档，配置了存储后端时同时写入后端
+#[cfg(feature = "server")]
+#[derive(Default)]
+pub struct GameArchive {
+    games: Vec<ArchivedGame>,
+    // 用户名 -> 参与过的对局在 games 中的下标，按存档顺序
+    by_player: HashMap<String, Vec<usize>>,
+    // Zobrist 局面哈希 -> (对局下标, 走到这个局面时的步数)，按存档顺序；
+    // 同一盘棋里重复出现的局面只记第一次，空棋盘不记
+    by_position: HashMap<u64, Vec<(usize, usize)>>,
+    store: Option<SharedStore>,
+    // 载入时重放对不上的对局，不进索引
+    flagged: Vec<IntegrityIssue>,
+}
+
+#[cfg(feature = "server")]
+impl GameArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_store(store: SharedStore) -> Self {
+        let games = store.lock().unwrap().load_games().unwrap_or_else(|e| {
+            println!("加载历史对局失败: {}", e);
+            Vec::new()
+        });
+        let mut archive = Self {
+            store: Some(store),
+            ..Self::default()
+        };
+        for game in games {
+            match game.verify() {
+                Ok(()) => archive.push(game),
+                Err(e) => {
+                    println!("历史对局 {} 没有通过校验，不再加载: {}", game.id, e);
+                    archive.flagged.push(IntegrityIssue::new(&game.id, &e));
+                }
+            }
+        }
+        println!("已加载 {} 盘历史对局", archive.len());
+        archive
+    }
+
+    pub fn flagged(&self) -> &[IntegrityIssue] {
+        &self.flagged
+    }
+
+    fn push(&mut self, game: ArchivedGame) {
+        let index = self.games.len();
+        for name in [&game.black, &game.white] {
+            let indices = self.by_player.entry(name.clone()).or_default();
+            if indices.last() != Some(&index) {
+                indices.push(index);
+            }
+        }
+        // 和 Board 落子时的增量哈希一致
+        let mut hash = 0;
+        for (ply, m) in game.moves.iter().enumerate() {
+            hash ^= zobrist::stone_key(m.row, m.col, m.player) ^ zobrist::WHITE_TO_MOVE;
+            let entries = self.by_position.entry(hash).or_default();
+            if entries.last().is_none_or(|&(last, _)| last != index) {
+                entries.push((index, ply + 1));
+            }
+        }
+        self.games.push(game);
+    }
+
+    pub fn save(&mut self, game: ArchivedGame) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.lock().unwrap().save_game(&game) {
+                println!("写入对局存档失败: {}", e);
+            }
+        }
+        println!("对局 {} 已存档", game.id);
+        self.push(game);
+    }
+
+    // 停服前存下的对局已经恢复到房间里，之后以房间里的为准
+    pub fn forget_adjourned(&self, game_id: &str) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.lock().unwrap().delete_adjourned(game_id) {
+                println!("删除未完成的对局 {} 失败: {}", game_id, e);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ArchivedGame> {
+        self.games.iter().find(|game| game.id == id)
+    }
+
+    // 从新到旧遍历存档，指定用户名时只走该用户的索引
+    pub fn recent<'a>(
+        &'a self,
+        player: Option<&str>,
+    ) -> Box<dyn Iterator<Item = &'a ArchivedGame> + 'a> {
+        match player {
+            Some(name) => Box::new(
+                self.by_player
+                    .get(name)
+                    .into_iter()
+                    .flat_map(|indices| indices.iter().rev())
+                    .map(|&index| &self.games[index]),
+            ),
+            None => Box::new(self.games.iter().rev()),
+        }
+    }
+
+    // 某个用户参与过的对局数，不指定用户名时为全部
+    pub fn count(&self, player: Option<&str>) -> usize {
+        match player {
+            Some(name) => self.by_player.get(name).map_or(0, Vec::len),
+            None => self.games.len(),
+        }
+    }
+
+    // 下出过这个局面的对局，从新到旧，附带走到该局面时的步数
+    pub fn with_position(&self, hash: u64) -> impl Iterator<Item = (&ArchivedGame, usize)> {
+        self.by_position
+            .get(&hash)
+            .into_iter()
+            .flat_map(|entries| entries.iter().rev())
+            .map(|&(index, ply)| (&self.games[index], ply))
+    }
+
+    // 最近 n 盘对局的平均时长，没有存档时返回 None
+    pub fn average_duration(&self, n: usize) -> Option<Duration> {
+        let recent = &self.games[self.games.len().saturating_sub(n)..];
+        if recent.is_empty() {
+            return None;
+        }
+        let total: i64 = recent
+            .iter()
+            .map(|game| (game.ended_at - game.started_at).num_milliseconds().max(0))
+            .sum();
+        Some(Duration::from_millis((total / recent.len() as i64) as u64))
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+}
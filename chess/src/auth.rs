@@ -0,0 +1,154 @@
+#[cfg(feature = "server")]
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+#[cfg(feature = "server")]
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+#[cfg(feature = "server")]
+use argon2::Argon2;
+#[cfg(feature = "server")]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+#[cfg(feature = "server")]
+use base64::Engine;
+#[cfg(feature = "server")]
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use sha2::Sha256;
+
+use crate::PlayerRole;
+#[cfg(feature = "server")]
+use crate::RoomId;
+
+// 签名密钥也可以通过环境变量提供，优先于配置文件
+pub const TOKEN_SECRET_ENV: &str = "GOMOKU_TOKEN_SECRET";
+
+pub const MIN_PASSWORD_LEN: usize = 6;
+pub const TOKEN_TTL_HOURS: i64 = 24;
+pub const DEFAULT_INVITE_TTL_SECS: u64 = 60 * 60;
+pub const MAX_INVITE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+// 邀请令牌的前缀，和会话令牌区分开
+#[cfg(feature = "server")]
+const INVITE_PREFIX: &str = "inv";
+
+// 邀请持有者的身份：坐到指定颜色的空位上，或者观战
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteRole {
+    Seat(PlayerRole),
+    Spectator,
+}
+
+// 邀请绑定到某个房间里的某一盘棋，房间换了新对局后旧邀请失效
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invite {
+    pub room: RoomId,
+    pub game_id: String,
+    pub role: InviteRole,
+    // Unix 时间戳（秒）
+    pub expires_at: i64,
+}
+
+#[cfg(feature = "server")]
+impl Invite {
+    // 有效期超过上限时按上限算
+    pub fn new(room: RoomId, game_id: String, role: InviteRole, ttl_secs: u64) -> Self {
+        let ttl = ttl_secs.min(MAX_INVITE_TTL_SECS) as i64;
+        Self {
+            room,
+            game_id,
+            role,
+            expires_at: chrono::Utc::now().timestamp() + ttl,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 哈希失败")
+        .to_string()
+}
+
+#[cfg(feature = "server")]
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// 会话令牌：<用户ID>.<过期时间戳>.<HMAC-SHA256 签名>
+#[cfg(feature = "server")]
+pub struct TokenSigner {
+    secret: Vec<u8>,
+}
+
+#[cfg(feature = "server")]
+impl TokenSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    // 没有配置密钥时随机生成，服务器重启后旧令牌失效
+    pub fn random() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        Self::new(&secret)
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC 接受任意长度密钥");
+        mac.update(payload.as_bytes());
+        URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+    }
+
+    pub fn issue(&self, user_id: &str) -> String {
+        let expires = (chrono::Utc::now() + chrono::Duration::hours(TOKEN_TTL_HOURS)).timestamp();
+        let payload = format!("{}.{}", user_id, expires);
+        let signature = self.sign(&payload);
+        format!("{}.{}", payload, signature)
+    }
+
+    // 邀请令牌：inv.<base64url(JSON)>.<签名>，可以直接放进链接
+    pub fn issue_invite(&self, invite: &Invite) -> String {
+        let json = serde_json::to_string(invite).expect("邀请可以序列化");
+        let payload = format!("{}.{}", INVITE_PREFIX, URL_SAFE_NO_PAD.encode(json));
+        let signature = self.sign(&payload);
+        format!("{}.{}", payload, signature)
+    }
+
+    pub fn verify_invite(&self, token: &str) -> Option<Invite> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let encoded = payload.strip_prefix(INVITE_PREFIX)?.strip_prefix('.')?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).ok()?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?)
+            .ok()?;
+        let invite: Invite = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded).ok()?).ok()?;
+        if invite.expires_at < chrono::Utc::now().timestamp() {
+            return None;
+        }
+        Some(invite)
+    }
+
+    // 签名正确且未过期时返回用户 ID
+    pub fn verify(&self, token: &str) -> Option<String> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let (user_id, expires) = payload.split_once('.')?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).ok()?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).ok()?)
+            .ok()?;
+        if expires.parse::<i64>().ok()? < chrono::Utc::now().timestamp() {
+            return None;
+        }
+        Some(user_id.to_string())
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{ArchivedGame, Rating, RoomDump, Store, StoreError, User, UserSession};
+
+// 备份格式版本，格式变化时递增，恢复时拒绝不认识的版本
+pub const BACKUP_FORMAT_VERSION: u32 = 2;
+// 还能恢复的最旧版本。版本 1 没有 adjourned，按没有封存的对局处理
+pub const MIN_BACKUP_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupData {
+    pub users: Vec<User>,
+    pub sessions: Vec<UserSession>,
+    pub ratings: Vec<(String, Rating)>,
+    pub games: Vec<ArchivedGame>,
+    // 停服时封存、还没下完的对局
+    #[serde(default)]
+    pub adjourned: Vec<RoomDump>,
+}
+
+// 版本 1 的 data 部分，只用来按当时的字段重算校验和
+#[derive(Serialize)]
+struct BackupDataV1<'a> {
+    users: &'a [User],
+    sessions: &'a [UserSession],
+    ratings: &'a [(String, Rating)],
+    games: &'a [ArchivedGame],
+}
+
+impl BackupData {
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+            && self.ratings.is_empty()
+            && self.games.is_empty()
+            && self.adjourned.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    // data 部分 JSON 的 SHA-256，用于发现文件损坏或被改动
+    pub checksum: String,
+    pub data: BackupData,
+}
+
+fn checksum(data: &BackupData) -> Result<String, StoreError> {
+    let json = serde_json::to_string(data)?;
+    Ok(format!("{:x}", Sha256::digest(json.as_bytes())))
+}
+
+fn checksum_v1(data: &BackupData) -> Result<String, StoreError> {
+    let json = serde_json::to_string(&BackupDataV1 {
+        users: &data.users,
+        sessions: &data.sessions,
+        ratings: &data.ratings,
+        games: &data.games,
+    })?;
+    Ok(format!("{:x}", Sha256::digest(json.as_bytes())))
+}
+
+fn load_all(store: &dyn Store) -> Result<BackupData, StoreError> {
+    Ok(BackupData {
+        users: store.load_users()?,
+        sessions: store.load_sessions()?,
+        ratings: store.load_ratings()?,
+        games: store.load_games()?,
+        adjourned: store.load_adjourned()?,
+    })
+}
+
+impl Backup {
+    pub fn create(store: &dyn Store) -> Result<Self, StoreError> {
+        let data = load_all(store)?;
+        Ok(Self {
+            version: BACKUP_FORMAT_VERSION,
+            created_at: Utc::now(),
+            checksum: checksum(&data)?,
+            data,
+        })
+    }
+
+    pub fn verify(&self) -> Result<(), StoreError> {
+        if !(MIN_BACKUP_FORMAT_VERSION..=BACKUP_FORMAT_VERSION).contains(&self.version) {
+            return Err(StoreError(format!(
+                "不支持的备份版本 {}，当前版本为 {}",
+                self.version, BACKUP_FORMAT_VERSION
+            )));
+        }
+        let expected = match self.version {
+            1 if self.data.adjourned.is_empty() => checksum_v1(&self.data)?,
+            1 => return Err(StoreError("版本 1 的备份不应包含封存的对局".to_string())),
+            _ => checksum(&self.data)?,
+        };
+        if expected != self.checksum {
+            return Err(StoreError("备份校验失败，文件可能已损坏".to_string()));
+        }
+        Ok(())
+    }
+
+    // 写入目标存储，force 为 false 时目标必须为空
+    pub fn restore(&self, store: &mut dyn Store, force: bool) -> Result<(), StoreError> {
+        self.verify()?;
+        if !force && !load_all(store)?.is_empty() {
+            return Err(StoreError(
+                "目标数据库已有数据，如需覆盖请使用 --force".to_string(),
+            ));
+        }
+        for user in &self.data.users {
+            store.save_user(user)?;
+        }
+        for session in &self.data.sessions {
+            store.save_session(session)?;
+        }
+        for (user_id, rating) in &self.data.ratings {
+            store.save_rating(user_id, rating)?;
+        }
+        for game in &self.data.games {
+            store.save_game(game)?;
+        }
+        for game in &self.data.adjourned {
+            store.save_adjourned(game)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<(), StoreError> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).map_err(|e| StoreError(e.to_string()))
+    }
+
+    pub fn read_from(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let text = std::fs::read_to_string(path).map_err(|e| StoreError(e.to_string()))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
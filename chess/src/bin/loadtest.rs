@@ -0,0 +1,176 @@
+use chess::{Capability, GameMessage, PROTOCOL_VERSION};
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::Barrier;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+const USAGE: &str = "用法: loadtest [--addr <ws://地址>] [--connections <连接数>]";
+// 单个请求等这么久还没回复就算失败
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Options {
+    addr: String,
+    connections: usize,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        addr: "ws://127.0.0.1:8080".to_string(),
+        connections: 200,
+    };
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} 缺少参数\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--addr" => options.addr = value.clone(),
+            "--connections" => {
+                options.connections = value
+                    .parse()
+                    .map_err(|_| format!("{} 必须是整数: {}", flag, value))?
+            }
+            _ => return Err(format!("未知的参数: {}\n{}", flag, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+// 一个连接测到的三段耗时：注册和入座要改用户表，登录只读
+struct Sample {
+    register: Duration,
+    login: Duration,
+    connect: Duration,
+}
+
+// 发一条消息，等到服务器的第一条回复
+async fn round_trip(
+    ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    msg: &GameMessage,
+) -> Result<Duration, String> {
+    let sent = Instant::now();
+    ws.send(Message::Text(serde_json::to_string(msg).unwrap()))
+        .await
+        .map_err(|e| format!("发送失败: {}", e))?;
+    match tokio::time::timeout(REPLY_TIMEOUT, ws.next()).await {
+        Ok(Some(Ok(_))) => Ok(sent.elapsed()),
+        Ok(_) => Err("连接被关闭".to_string()),
+        Err(_) => Err("等待回复超时".to_string()),
+    }
+}
+
+async fn run_client(addr: String, index: usize, start: Arc<Barrier>) -> Result<Sample, String> {
+    let (mut ws, _) = connect_async(&addr)
+        .await
+        .map_err(|e| format!("连接失败: {}", e))?;
+    // 每次运行用不同的用户名，服务器用持久化存储时不会撞上上次注册的用户
+    let username = format!("lt{}-{}", std::process::id(), index);
+    // 所有连接建好后同时发请求
+    start.wait().await;
+
+    let register = GameMessage::Register {
+        username: username.clone(),
+        password: "loadtest-password".to_string(),
+    };
+    let register = round_trip(&mut ws, &register).await?;
+    let login = GameMessage::Login {
+        username: username.clone(),
+        password: "loadtest-password".to_string(),
+    };
+    let login = round_trip(&mut ws, &login).await?;
+    let connect = GameMessage::ConnectRequest {
+        username,
+        token: None,
+        play_vs_ai: None,
+        invite: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: None,
+        resume: None,
+    };
+    let connect = round_trip(&mut ws, &connect).await?;
+    let _ = ws.close(None).await;
+    Ok(Sample {
+        register,
+        login,
+        connect,
+    })
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    sorted[(sorted.len() * p / 100).min(sorted.len() - 1)]
+}
+
+fn report(name: &str, mut times: Vec<Duration>) {
+    if times.is_empty() {
+        return;
+    }
+    times.sort();
+    println!(
+        "{}: p50 {:?}, p95 {:?}, p99 {:?}, 最慢 {:?}",
+        name,
+        percentile(&times, 50),
+        percentile(&times, 95),
+        percentile(&times, 99),
+        times[times.len() - 1]
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    println!(
+        "向 {} 发起 {} 个并发连接",
+        options.addr, options.connections
+    );
+
+    let start = Arc::new(Barrier::new(options.connections + 1));
+    let clients: Vec<_> = (0..options.connections)
+        .map(|index| tokio::spawn(run_client(options.addr.clone(), index, start.clone())))
+        .collect();
+    // 有连接建不起来时 Barrier 永远等不齐，超时就放弃
+    let began = tokio::time::timeout(REPLY_TIMEOUT, start.wait())
+        .await
+        .is_ok();
+    if !began {
+        eprintln!("部分连接没有建立，放弃测试");
+        std::process::exit(1);
+    }
+    let started = Instant::now();
+
+    let (mut registers, mut logins, mut connects) = (Vec::new(), Vec::new(), Vec::new());
+    let mut failures = 0;
+    for client in clients {
+        match client.await {
+            Ok(Ok(sample)) => {
+                registers.push(sample.register);
+                logins.push(sample.login);
+                connects.push(sample.connect);
+            }
+            Ok(Err(e)) => {
+                failures += 1;
+                eprintln!("{}", e);
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("连接任务异常: {}", e);
+            }
+        }
+    }
+    println!(
+        "完成 {} 个连接，失败 {} 个，用时 {:?}",
+        logins.len(),
+        failures,
+        started.elapsed()
+    );
+    report("注册", registers);
+    report("登录", logins);
+    report("入座", connects);
+}
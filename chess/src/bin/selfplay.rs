@@ -0,0 +1,87 @@
+use chess::{self_play, Contestant, SelfPlayConfig};
+use std::path::PathBuf;
+
+const USAGE: &str = "用法: selfplay [--games <盘数>] [--first <引擎:难度>] [--second <引擎:难度>] \
+                     [--max-moves <步数>] [--records <目录>]\n引擎: search | mcts，难度: easy | medium | hard";
+
+struct Options {
+    config: SelfPlayConfig,
+    records: Option<PathBuf>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut config = SelfPlayConfig::new(
+        Contestant::parse("search:medium")?,
+        Contestant::parse("mcts:medium")?,
+        10,
+    );
+    let mut records = None;
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} 缺少参数\n{}", flag, USAGE))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("{} 必须是整数: {}", flag, value))
+        };
+        match flag.as_str() {
+            "--games" => config.games = number()?,
+            "--max-moves" => config.max_moves = number()?,
+            "--first" => config.first = Contestant::parse(value)?,
+            "--second" => config.second = Contestant::parse(value)?,
+            "--records" => records = Some(PathBuf::from(value)),
+            _ => return Err(format!("未知的参数: {}\n{}", flag, USAGE)),
+        }
+    }
+    Ok(Options { config, records })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(dir) = &options.records {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("无法创建棋谱目录 {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    let config = options.config;
+    let (first, second) = (config.first.name(), config.second.name());
+    println!("{} 对 {}，共 {} 盘", first, second, config.games);
+    let report = self_play(&config, |index, game| {
+        let winner = match game.first_won() {
+            Some(true) => first.as_str(),
+            Some(false) => second.as_str(),
+            None => "和棋",
+        };
+        println!(
+            "第 {} 盘: {} 步，胜者 {}",
+            index + 1,
+            game.moves.len(),
+            winner
+        );
+        if let Some(dir) = &options.records {
+            let path = dir.join(format!("game-{:04}.sgf", index + 1));
+            if let Err(e) = std::fs::write(&path, &game.sgf) {
+                eprintln!("写入棋谱 {} 失败: {}", path.display(), e);
+            }
+        }
+    });
+
+    for (name, stats) in [(&first, &report.first), (&second, &report.second)] {
+        println!(
+            "{}: 胜 {} 盘，胜率 {:.1}%，平均每步 {:.1} 毫秒",
+            name,
+            stats.wins,
+            report.win_rate(stats) * 100.0,
+            stats.average_move_time().as_secs_f64() * 1000.0
+        );
+    }
+    println!("和棋 {} 盘", report.draws);
+}
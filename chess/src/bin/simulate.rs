@@ -0,0 +1,109 @@
+use chess::{simulate, Policy, RulesConfig, SimulationConfig};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+const USAGE: &str = "用法: simulate [--games <盘数>] [--black <下法>] [--white <下法>] \
+                     [--board-size <路数>] [--win-length <连子数>] [--stones-per-turn <子数>] \
+                     [--max-moves <步数>] [--seed <种子>] [--threads <线程数>] [--out <文件>]\n\
+                     下法: random | search[:难度] | mcts[:难度]，难度: easy | medium | hard\n\
+                     每盘一行 JSON 写到 --out 指定的文件，默认写到标准输出；进度写到标准错误";
+// 每完成这么多盘报告一次进度
+const PROGRESS_EVERY: usize = 10;
+
+struct Options {
+    config: SimulationConfig,
+    out: Option<String>,
+}
+
+// 盘数、路数这些按 usize 解析，种子按 u64 解析，32 位平台上也能用满范围
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} 必须是整数: {}", flag, value))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut config = SimulationConfig::new(Policy::Random, Policy::Random, 100);
+    let mut out = None;
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} 缺少参数\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--games" => config.games = parse_number(flag, value)?,
+            "--black" => config.black = Policy::parse(value)?,
+            "--white" => config.white = Policy::parse(value)?,
+            "--board-size" => config.rules.board_size = parse_number(flag, value)?,
+            "--win-length" => config.rules.win_length = parse_number(flag, value)?,
+            "--stones-per-turn" => config.rules.stones_per_turn = parse_number(flag, value)?,
+            "--max-moves" => config.max_moves = Some(parse_number(flag, value)?),
+            "--seed" => config.seed = parse_number(flag, value)?,
+            "--threads" => config.threads = parse_number(flag, value)?,
+            "--out" => out = Some(value.clone()),
+            _ => return Err(format!("未知的参数: {}\n{}", flag, USAGE)),
+        }
+    }
+    Ok(Options { config, out })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let writer: Box<dyn Write + Send> = match &options.out {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("无法创建 {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(std::io::stdout()),
+    };
+    let writer = Mutex::new(writer);
+
+    let config = options.config;
+    let RulesConfig {
+        board_size,
+        win_length,
+        stones_per_turn,
+        ..
+    } = config.rules;
+    eprintln!(
+        "{} 对 {}，{}×{} 棋盘连 {} 子，每回合 {} 子，共 {} 盘",
+        config.black.name(),
+        config.white.name(),
+        board_size,
+        board_size,
+        win_length,
+        stones_per_turn,
+        config.games
+    );
+    let result = simulate(&config, |game, done| {
+        let line = serde_json::to_string(game).unwrap();
+        if let Err(e) = writeln!(writer.lock().unwrap(), "{}", line) {
+            eprintln!("写入结果失败: {}", e);
+            std::process::exit(1);
+        }
+        if done % PROGRESS_EVERY == 0 || done == config.games {
+            eprintln!("已完成 {}/{} 盘", done, config.games);
+        }
+    });
+    if let Err(e) = writer.lock().unwrap().flush() {
+        eprintln!("写入结果失败: {}", e);
+    }
+    match result {
+        Ok(summary) => eprintln!(
+            "黑胜 {} 盘，白胜 {} 盘，和棋 {} 盘，平均 {:.1} 步",
+            summary.black_wins, summary.white_wins, summary.draws, summary.average_length
+        ),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
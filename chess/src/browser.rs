@@ -0,0 +1,490 @@
+#[cfg(feature = "server")]
+use std::sync::Arc;
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "server")]
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(feature = "server")]
+use tokio::sync::{Mutex, RwLock, Semaphore};
+
+use crate::{ArchivedGame, PlayerRole};
+#[cfg(feature = "server")]
+use crate::{Board, GameArchive, GameError, RoomManager, UserManager};
+
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: usize = 100;
+// 请求头的长度上限，超过直接断开
+#[cfg(feature = "server")]
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+// 请求头要在这么长时间内收完，慢速连接不能一直占着
+#[cfg(feature = "server")]
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// 同时处理的连接数上限，超过的新连接直接关闭
+#[cfg(feature = "server")]
+const MAX_CONNECTIONS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameStatus {
+    Live,
+    Finished,
+}
+
+// 对局结果，和棋是已结束但没有胜者
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult {
+    Black,
+    White,
+    Draw,
+}
+
+impl GameResult {
+    fn of(winner: Option<PlayerRole>) -> Self {
+        match winner {
+            Some(PlayerRole::Black) => GameResult::Black,
+            Some(PlayerRole::White) => GameResult::White,
+            None => GameResult::Draw,
+        }
+    }
+}
+
+// 对局列表的筛选条件，时间范围按开局时间计算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameFilter {
+    pub player: Option<String>,
+    pub status: Option<GameStatus>,
+    pub result: Option<GameResult>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // 从 1 开始
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl Default for GameFilter {
+    fn default() -> Self {
+        Self {
+            player: None,
+            status: None,
+            result: None,
+            from: None,
+            to: None,
+            page: 1,
+            per_page: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl GameFilter {
+    // 解析 URL 查询串，例如 player=alice&status=finished&result=draw&page=2
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            let parse_time = |value: &str| {
+                DateTime::parse_from_rfc3339(value)
+                    .map(|time| time.with_timezone(&Utc))
+                    .map_err(|_| format!("{} 不是 RFC 3339 时间: {}", key, value))
+            };
+            match key {
+                "player" => filter.player = Some(value),
+                "status" => {
+                    filter.status = Some(match value.as_str() {
+                        "live" => GameStatus::Live,
+                        "finished" => GameStatus::Finished,
+                        _ => return Err(format!("未知的 status: {}", value)),
+                    })
+                }
+                "result" => {
+                    filter.result = Some(match value.as_str() {
+                        "black" => GameResult::Black,
+                        "white" => GameResult::White,
+                        "draw" => GameResult::Draw,
+                        _ => return Err(format!("未知的 result: {}", value)),
+                    })
+                }
+                "from" => filter.from = Some(parse_time(&value)?),
+                "to" => filter.to = Some(parse_time(&value)?),
+                "page" | "per_page" => {
+                    let number = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("{} 必须是正整数: {}", key, value))?;
+                    if key == "page" {
+                        filter.page = number;
+                    } else {
+                        filter.per_page = number.min(MAX_PAGE_SIZE);
+                    }
+                }
+                _ => return Err(format!("未知的参数: {}", key)),
+            }
+        }
+        Ok(filter)
+    }
+
+    // 只看存档对局本身，不用先转成 GameSummary
+    #[cfg(feature = "server")]
+    fn matches_archived(&self, game: &ArchivedGame) -> bool {
+        let player_ok = self
+            .player
+            .as_ref()
+            .is_none_or(|name| &game.black == name || &game.white == name);
+        player_ok
+            && self
+                .status
+                .is_none_or(|status| status == GameStatus::Finished)
+            && self
+                .result
+                .is_none_or(|result| result == GameResult::of(game.winner))
+            && self.from.is_none_or(|from| game.started_at >= from)
+            && self.to.is_none_or(|to| game.started_at <= to)
+    }
+
+    // 按结果或时间筛选时要逐盘检查存档；只按用户名筛选时总数和每一页都能直接从索引得到
+    #[cfg(feature = "server")]
+    fn scans_archive(&self) -> bool {
+        self.result.is_some() || self.from.is_some() || self.to.is_some()
+    }
+
+    #[cfg(feature = "server")]
+    fn matches(&self, game: &GameSummary) -> bool {
+        let player_ok = self
+            .player
+            .as_ref()
+            .is_none_or(|name| &game.black == name || &game.white == name);
+        player_ok
+            && self.status.is_none_or(|status| status == game.status)
+            && self.result.is_none_or(|result| Some(result) == game.result)
+            && self.from.is_none_or(|from| game.started_at >= from)
+            && self.to.is_none_or(|to| game.started_at <= to)
+    }
+}
+
+// 列表里的一盘对局，不带棋谱
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub id: String,
+    pub black: String,
+    pub white: String,
+    pub status: GameStatus,
+    pub result: Option<GameResult>,
+    pub move_count: usize,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    // 双方当前的等级分，列表发出前按用户名查出，电脑和查不到的玩家为 None
+    #[serde(default)]
+    pub black_rating: Option<i32>,
+    #[serde(default)]
+    pub white_rating: Option<i32>,
+}
+
+impl From<&ArchivedGame> for GameSummary {
+    fn from(game: &ArchivedGame) -> Self {
+        Self {
+            id: game.id.clone(),
+            black: game.black.clone(),
+            white: game.white.clone(),
+            status: GameStatus::Finished,
+            result: Some(GameResult::of(game.winner)),
+            move_count: game.moves.len(),
+            started_at: game.started_at,
+            ended_at: Some(game.ended_at),
+            black_rating: None,
+            white_rating: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GamePage {
+    pub games: Vec<GameSummary>,
+    pub page: usize,
+    pub per_page: usize,
+    // 符合条件的对局总数
+    pub total: usize,
+}
+
+// 历史对局里出现过某个局面的一盘
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionMatch {
+    pub game: GameSummary,
+    // 这盘棋走到第几步时出现了这个局面
+    pub ply: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionGames {
+    // 最多 MAX_PAGE_SIZE 盘，新的在前
+    pub games: Vec<PositionMatch>,
+    pub total: usize,
+}
+
+// 查询下出过某个局面的历史对局。moves 是从空棋盘黑先走到这个局面的落子，
+// 顺序不同但棋子相同的局面算同一个
+#[cfg(feature = "server")]
+pub fn search_position(
+    archive: &GameArchive,
+    moves: &[(usize, usize)],
+) -> Result<PositionGames, GameError> {
+    let mut board = Board::new();
+    for &(row, col) in moves {
+        board.make_move(row, col)?;
+    }
+    let matches: Vec<_> = archive.with_position(board.hash()).collect();
+    Ok(PositionGames {
+        total: matches.len(),
+        games: matches
+            .into_iter()
+            .take(MAX_PAGE_SIZE)
+            .map(|(game, ply)| PositionMatch {
+                game: GameSummary::from(game),
+                ply,
+            })
+            .collect(),
+    })
+}
+
+// 解析 7-7,7-8,8-8 这样的落子列表
+#[cfg(feature = "server")]
+fn parse_moves(value: &str) -> Result<Vec<(usize, usize)>, String> {
+    value
+        .split(',')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (row, col) = pair
+                .split_once('-')
+                .ok_or_else(|| format!("落子格式应为 行-列: {}", pair))?;
+            let parse = |n: &str| n.parse().map_err(|_| format!("不是有效的坐标: {}", pair));
+            Ok((parse(row)?, parse(col)?))
+        })
+        .collect()
+}
+
+// 进行中的对局在前（新开的在前），之后是已结束的对局（新结束的在前）。
+// 存档只取当前页需要的那几盘，不整体复制
+#[cfg(feature = "server")]
+pub async fn list_games(
+    rooms: &Mutex<RoomManager>,
+    archive: &Mutex<GameArchive>,
+    filter: &GameFilter,
+) -> GamePage {
+    let mut games = Vec::new();
+    let (live, users) = {
+        let rooms = rooms.lock().await;
+        (rooms.games(), rooms.users())
+    };
+    if filter.status != Some(GameStatus::Finished) {
+        for room in live {
+            if let Some(summary) = room.lock().await.rated_summary().await {
+                if filter.matches(&summary) {
+                    games.push(summary);
+                }
+            }
+        }
+        games.sort_by_key(|game| std::cmp::Reverse(game.started_at));
+    }
+
+    let start = (filter.page - 1).saturating_mul(filter.per_page);
+    let live_total = games.len();
+    let mut games: Vec<_> = games
+        .into_iter()
+        .skip(start)
+        .take(filter.per_page)
+        .collect();
+    let mut total = live_total;
+    if filter.status != Some(GameStatus::Live) {
+        // 进行中的对局之后，存档里要跳过和要取的盘数
+        let skip = start.saturating_sub(live_total);
+        let take = filter.per_page - games.len();
+        let archive = archive.lock().await;
+        let recent = archive.recent(filter.player.as_deref());
+        if filter.scans_archive() {
+            let mut matched = 0;
+            for game in recent.filter(|game| filter.matches_archived(game)) {
+                if (skip..skip.saturating_add(take)).contains(&matched) {
+                    games.push(GameSummary::from(game));
+                }
+                matched += 1;
+            }
+            total += matched;
+        } else {
+            games.extend(recent.skip(skip).take(take).map(GameSummary::from));
+            total += archive.count(filter.player.as_deref());
+        }
+    }
+
+    let users = users.read().await;
+    GamePage {
+        games: games
+            .into_iter()
+            // 进行中的对局已经按入座时的认证填好了，存档里的按用户名查
+            .map(|mut game| {
+                if game.status == GameStatus::Finished {
+                    game.black_rating = users.rating_by_name(&game.black);
+                    game.white_rating = users.rating_by_name(&game.white);
+                }
+                game
+            })
+            .collect(),
+        page: filter.page,
+        per_page: filter.per_page,
+        total,
+    }
+}
+
+// 只读的 HTTP 接口，都返回 JSON：
+// GET /games?<筛选条件> 对局列表；GET /invites/<令牌> 查看邀请，链接落地页用来展示邀请内容；
+// GET /positions?moves=7-7,7-8 出现过这个局面的历史对局；GET /metrics 落子各阶段的延迟直方图和协议违规次数
+#[cfg(feature = "server")]
+pub async fn serve(
+    listener: TcpListener,
+    rooms: Arc<Mutex<RoomManager>>,
+    archive: Arc<Mutex<GameArchive>>,
+    users: Arc<RwLock<UserManager>>,
+) {
+    let permits = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    while let Ok((stream, _)) = listener.accept().await {
+        let Ok(permit) = permits.clone().try_acquire_owned() else {
+            continue;
+        };
+        let rooms = rooms.clone();
+        let archive = archive.clone();
+        let users = users.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &rooms, &archive, &users).await {
+                println!("对局列表请求处理失败: {}", e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(feature = "server")]
+async fn handle(
+    mut stream: TcpStream,
+    rooms: &Mutex<RoomManager>,
+    archive: &Mutex<GameArchive>,
+    users: &RwLock<UserManager>,
+) -> std::io::Result<()> {
+    let Ok(request) = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await else {
+        return Ok(());
+    };
+    let Some(request) = request? else {
+        return Ok(());
+    };
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, body) = match (method, path) {
+        ("GET", "/games") => match GameFilter::from_query(query) {
+            Ok(filter) => {
+                let page = list_games(rooms, archive, &filter).await;
+                ("200 OK", serde_json::to_string(&page).unwrap_or_default())
+            }
+            Err(e) => ("400 Bad Request", error_body(&e)),
+        },
+        ("GET", "/positions") => {
+            let moves = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("moves="))
+                .unwrap_or("");
+            let moves = percent_decode(moves).and_then(|moves| parse_moves(&moves));
+            let result = match moves {
+                Ok(moves) => {
+                    search_position(&*archive.lock().await, &moves).map_err(|e| e.to_string())
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(games) => ("200 OK", serde_json::to_string(&games).unwrap_or_default()),
+                Err(e) => ("400 Bad Request", error_body(&e)),
+            }
+        }
+        ("GET", path) if path.starts_with("/invites/") => {
+            let token = &path["/invites/".len()..];
+            match users.read().await.verify_invite(token) {
+                Some(invite) => ("200 OK", serde_json::to_string(&invite).unwrap_or_default()),
+                None => ("404 Not Found", error_body("邀请无效或已过期")),
+            }
+        }
+        ("GET", "/metrics") => {
+            let metrics = rooms.lock().await.metrics();
+            let body = serde_json::json!({
+                "move_latency": metrics.move_latency(),
+                "protocol_violations": metrics.violations(),
+                "strict_disconnects": metrics.strict_disconnects(),
+            });
+            ("200 OK", body.to_string())
+        }
+        (_, "/games") => ("405 Method Not Allowed", error_body("只支持 GET")),
+        _ => ("404 Not Found", error_body("没有这个接口")),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json; charset=utf-8\r\n\
+         Access-Control-Allow-Origin: *\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+// 读到请求头结束为止，连接提前关闭或请求头太长时返回 None
+#[cfg(feature = "server")]
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 || request.len() + n > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    Ok(Some(request))
+}
+
+#[cfg(feature = "server")]
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+// 查询串解码：%XX 按 UTF-8 字节还原，+ 表示空格
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("无效的转义: {}", value))?;
+                out.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| format!("不是有效的 UTF-8: {}", value))
+}
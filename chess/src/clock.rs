@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::PlayerRole;
+
+// 剩余时间提醒的阈值（秒），从大到小
+pub const WARNING_THRESHOLDS: [u64; 3] = [30, 10, 5];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub main_time_secs: u64, // 每位玩家的基本用时
+    #[serde(default)]
+    pub increment_secs: u64, // 每步加秒
+}
+
+// 服务器停机期间经过的时间怎么算
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DowntimePolicy {
+    // 不计入任何一方的用时
+    #[default]
+    Pause,
+    // 记到停机时正在计时的一方身上，可能因此超时
+    Charge,
+}
+
+// 随对局一起保存的时钟状态，Instant 不能跨进程，所以记录剩余毫秒数和保存时刻
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSnapshot {
+    pub control: TimeControl,
+    pub black_ms: u64,
+    pub white_ms: u64,
+    // 保存时正在计时的一方
+    pub running: Option<PlayerRole>,
+    pub saved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClockEvent {
+    Warning {
+        player: PlayerRole,
+        remaining_secs: u64,
+    },
+    Expired {
+        player: PlayerRole,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: HashMap<PlayerRole, Duration>,
+    running: Option<(PlayerRole, Instant)>,
+    // 当前这一步已经发出过的提醒
+    warned: Vec<u64>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let main_time = Duration::from_secs(control.main_time_secs);
+        Self {
+            control,
+            remaining: HashMap::from([
+                (PlayerRole::Black, main_time),
+                (PlayerRole::White, main_time),
+            ]),
+            running: None,
+            warned: Vec::new(),
+        }
+    }
+
+    // 从保存的状态恢复，正在计时的一方从 now 开始继续计时；
+    // 停机期间已经用完时间的一方，下一次 tick 就会超时
+    pub fn restore(
+        snapshot: &ClockSnapshot,
+        policy: DowntimePolicy,
+        restored_at: DateTime<Utc>,
+        now: Instant,
+    ) -> Self {
+        let mut clock = Self::new(snapshot.control);
+        clock.remaining = HashMap::from([
+            (PlayerRole::Black, Duration::from_millis(snapshot.black_ms)),
+            (PlayerRole::White, Duration::from_millis(snapshot.white_ms)),
+        ]);
+        if let Some(mover) = snapshot.running {
+            if policy == DowntimePolicy::Charge {
+                // 系统时间回拨时按没有停机处理
+                let downtime = (restored_at - snapshot.saved_at)
+                    .to_std()
+                    .unwrap_or_default();
+                if let Some(left) = clock.remaining.get_mut(&mover) {
+                    *left = left.saturating_sub(downtime);
+                }
+            }
+            clock.running = Some((mover, now));
+        }
+        clock
+    }
+
+    pub fn snapshot(&self, now: Instant, saved_at: DateTime<Utc>) -> ClockSnapshot {
+        ClockSnapshot {
+            control: self.control,
+            black_ms: self.remaining(PlayerRole::Black, now).as_millis() as u64,
+            white_ms: self.remaining(PlayerRole::White, now).as_millis() as u64,
+            running: self.running.map(|(mover, _)| mover),
+            saved_at,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn remaining(&self, player: PlayerRole, now: Instant) -> Duration {
+        let base = self.remaining[&player];
+        match self.running {
+            Some((mover, started)) if mover == player => {
+                base.saturating_sub(now.saturating_duration_since(started))
+            }
+            _ => base,
+        }
+    }
+
+    // 开始计时某位玩家
+    pub fn start(&mut self, player: PlayerRole, now: Instant) {
+        self.stop(now);
+        self.running = Some((player, now));
+        self.warned.clear();
+    }
+
+    // 停止计时，把已用时间记到当前玩家身上
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((mover, _)) = self.running {
+            let left = self.remaining(mover, now);
+            self.remaining.insert(mover, left);
+            self.running = None;
+        }
+    }
+
+    // 当前玩家落子后切换到对手，并给落子方加秒
+    pub fn switch(&mut self, now: Instant) {
+        if let Some((mover, _)) = self.running {
+            self.stop(now);
+            let increment = Duration::from_secs(self.control.increment_secs);
+            if let Some(left) = self.remaining.get_mut(&mover) {
+                *left += increment;
+            }
+            self.start(mover.other(), now);
+        }
+    }
+
+    // 由服务器时钟任务定期调用，返回需要广播的事件
+    pub fn tick(&mut self, now: Instant) -> Option<ClockEvent> {
+        let (mover, _) = self.running?;
+        let left = self.remaining(mover, now);
+        if left.is_zero() {
+            self.stop(now);
+            return Some(ClockEvent::Expired { player: mover });
+        }
+
+        // 向上取整，29.2 秒按 30 秒提醒
+        let left_secs = (left.as_millis() as u64).div_ceil(1000);
+        let threshold = WARNING_THRESHOLDS
+            .iter()
+            .rev()
+            .find(|&&t| left_secs <= t && !self.warned.contains(&t))
+            .copied()?;
+        // 跨过多个阈值时只提醒最紧急的一次
+        for &t in WARNING_THRESHOLDS.iter().filter(|&&t| t >= threshold) {
+            self.warned.push(t);
+        }
+        Some(ClockEvent::Warning {
+            player: mover,
+            remaining_secs: left_secs,
+        })
+    }
+}
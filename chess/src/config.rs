@@ -0,0 +1,258 @@
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AbandonPolicy, AiThrottleConfig, Difficulty, DowntimePolicy, Engine, EngineKind,
+    ExternalEngine, ExternalEngineConfig, FeatureFlags, MessageLimits, RoomQuotas, RulesConfig,
+    StrictMode, TelemetryConfig, TimeControl, STORAGE_KEY_ENV, TOKEN_SECRET_ENV,
+};
+
+// 配置文件路径可以通过环境变量覆盖
+pub const CONFIG_ENV: &str = "GOMOKU_CONFIG";
+pub const DEFAULT_CONFIG_PATH: &str = "server_config.json";
+pub const DEFAULT_MAX_ROOMS: usize = 16;
+pub const DEFAULT_MAX_DEMOS: usize = 8;
+pub const DEFAULT_MAX_DEMOS_PER_USER: usize = 1;
+pub const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 10;
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_RECONNECT_GRACE_SECS: u64 = 60;
+pub const DEFAULT_RESUME_HOLD_SECS: u64 = 600;
+pub const DEFAULT_MAX_GAMES_PER_USER: usize = 1;
+pub const DEFAULT_HINTS_PER_GAME: usize = 3;
+pub const DEFAULT_SHUTDOWN_DEADLINE_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    // 不设置则不限时
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    // SQLite 数据库文件，不设置则只保存在内存中
+    #[serde(default)]
+    pub database_path: Option<String>,
+    // 数据库加密密钥（32 字节 base64），环境变量 GOMOKU_STORAGE_KEY 优先
+    #[serde(default)]
+    pub encryption_key: Option<String>,
+    // 会话令牌的签名密钥，环境变量 GOMOKU_TOKEN_SECRET 优先；不设置则每次启动随机生成
+    #[serde(default)]
+    pub token_secret: Option<String>,
+    // 按游戏类型开关的功能，未列出的使用默认值
+    #[serde(default)]
+    pub features: FeatureFlags,
+    // 可以用令牌在线调整功能开关的用户名
+    #[serde(default)]
+    pub admins: Vec<String>,
+    // 同时进行的对局上限，满了之后新玩家排队
+    #[serde(default = "default_max_rooms")]
+    pub max_rooms: usize,
+    // 同时开放的演示房间上限，每个登录用户最多开 max_demos_per_user 个
+    #[serde(default = "default_max_demos")]
+    pub max_demos: usize,
+    #[serde(default = "default_max_demos_per_user")]
+    pub max_demos_per_user: usize,
+    // 服务器发送 Ping 的间隔
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    // 超过这么久收不到客户端任何消息（包括 Pong）就当作断线
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    // 对局中掉线的玩家多久内重连可以继续，超时判负
+    #[serde(default = "default_reconnect_grace_secs")]
+    pub reconnect_grace_secs: u64,
+    // 恢复停服前的对局后，另一方多久内不回来就判负；没人坐着时放弃这盘棋
+    #[serde(default = "default_resume_hold_secs")]
+    pub resume_hold_secs: u64,
+    // 恢复停服前的对局时，停机期间的时间怎么算，默认不计入任何一方
+    #[serde(default)]
+    pub downtime_policy: DowntimePolicy,
+    // 每个用户同时进行的对局数上限
+    #[serde(default = "default_max_games_per_user")]
+    pub max_games_per_user: usize,
+    // 多次中途弃局后暂停匹配的规则
+    #[serde(default)]
+    pub abandon_policy: AbandonPolicy,
+    // 只读对局列表 HTTP 接口的监听地址，例如 127.0.0.1:8081；不设置则不开启
+    #[serde(default)]
+    pub browser_addr: Option<String>,
+    // 管理通道的 WebSocket 监听地址，例如 127.0.0.1:8082；不设置则不开启，只接受管理员令牌
+    #[serde(default)]
+    pub admin_addr: Option<String>,
+    // 崩溃或收到终止信号时把进行中的对局写到这个目录；不设置则不写
+    #[serde(default)]
+    pub dump_dir: Option<String>,
+    // 停服时最多等多久让连接把消息发完，到时间还没发完的直接断开
+    #[serde(default = "default_shutdown_deadline_secs")]
+    pub shutdown_deadline_secs: u64,
+    // 电脑对手使用的引擎：search 或 mcts
+    #[serde(default)]
+    pub ai_engine: EngineKind,
+    // 电脑对手改用外部引擎（Gomocup 协议），进程出问题的那几步由 ai_engine 顶上；不设置则只用内置引擎
+    #[serde(default)]
+    pub external_engine: Option<ExternalEngineConfig>,
+    // 电脑对手多、机器忙时减小每步的计算量
+    #[serde(default)]
+    pub ai_throttle: AiThrottleConfig,
+    // 收发消息的大小和嵌套层数限制
+    #[serde(default)]
+    pub limits: MessageLimits,
+    // 严格模式，协议违规时断开连接，默认关闭
+    #[serde(default)]
+    pub strict: StrictMode,
+    // 每个房间往观战者广播的频率和流量配额，按对局和演示房间分别设置
+    #[serde(default)]
+    pub room_quotas: RoomQuotas,
+    // 匿名使用统计，默认关闭
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    // 每位玩家每盘棋可以请求几次提示，0 表示不开放
+    #[serde(default = "default_hints_per_game")]
+    pub hints_per_game: usize,
+    // 提示按这个难度的计算量搜索
+    #[serde(default = "default_hint_difficulty")]
+    pub hint_difficulty: Difficulty,
+    // 棋盘大小和连几子获胜，所有房间共用
+    #[serde(default)]
+    pub rules: RulesConfig,
+}
+
+fn default_max_rooms() -> usize {
+    DEFAULT_MAX_ROOMS
+}
+
+fn default_max_demos() -> usize {
+    DEFAULT_MAX_DEMOS
+}
+
+fn default_max_demos_per_user() -> usize {
+    DEFAULT_MAX_DEMOS_PER_USER
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    DEFAULT_HEARTBEAT_INTERVAL_SECS
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    DEFAULT_IDLE_TIMEOUT_SECS
+}
+
+fn default_reconnect_grace_secs() -> u64 {
+    DEFAULT_RECONNECT_GRACE_SECS
+}
+
+fn default_resume_hold_secs() -> u64 {
+    DEFAULT_RESUME_HOLD_SECS
+}
+
+fn default_max_games_per_user() -> usize {
+    DEFAULT_MAX_GAMES_PER_USER
+}
+
+fn default_shutdown_deadline_secs() -> u64 {
+    DEFAULT_SHUTDOWN_DEADLINE_SECS
+}
+
+fn default_hints_per_game() -> usize {
+    DEFAULT_HINTS_PER_GAME
+}
+
+fn default_hint_difficulty() -> Difficulty {
+    Difficulty::Medium
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            time_control: None,
+            database_path: None,
+            encryption_key: None,
+            token_secret: None,
+            features: FeatureFlags::default(),
+            admins: Vec::new(),
+            max_rooms: DEFAULT_MAX_ROOMS,
+            max_demos: DEFAULT_MAX_DEMOS,
+            max_demos_per_user: DEFAULT_MAX_DEMOS_PER_USER,
+            heartbeat_interval_secs: DEFAULT_HEARTBEAT_INTERVAL_SECS,
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            reconnect_grace_secs: DEFAULT_RECONNECT_GRACE_SECS,
+            resume_hold_secs: DEFAULT_RESUME_HOLD_SECS,
+            downtime_policy: DowntimePolicy::default(),
+            max_games_per_user: DEFAULT_MAX_GAMES_PER_USER,
+            abandon_policy: AbandonPolicy::default(),
+            browser_addr: None,
+            admin_addr: None,
+            dump_dir: None,
+            shutdown_deadline_secs: DEFAULT_SHUTDOWN_DEADLINE_SECS,
+            ai_engine: EngineKind::default(),
+            external_engine: None,
+            ai_throttle: AiThrottleConfig::default(),
+            limits: MessageLimits::default(),
+            strict: StrictMode::default(),
+            room_quotas: RoomQuotas::default(),
+            telemetry: TelemetryConfig::default(),
+            hints_per_game: DEFAULT_HINTS_PER_GAME,
+            hint_difficulty: default_hint_difficulty(),
+            rules: RulesConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn load() -> Self {
+        let path = std::env::var(CONFIG_ENV).unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+        if !Path::new(&path).exists() {
+            println!("未找到配置文件 {}，使用默认配置", path);
+            return Self::default();
+        }
+        match Self::from_file(&path) {
+            Ok(config) => {
+                println!("已加载配置文件 {}", path);
+                config
+            }
+            Err(e) => {
+                println!("读取配置文件 {} 失败: {}，使用默认配置", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn storage_key(&self) -> Option<String> {
+        std::env::var(STORAGE_KEY_ENV)
+            .ok()
+            .or_else(|| self.encryption_key.clone())
+    }
+
+    pub fn token_secret(&self) -> Option<String> {
+        std::env::var(TOKEN_SECRET_ENV)
+            .ok()
+            .or_else(|| self.token_secret.clone())
+    }
+
+    // (Ping 间隔, 断线超时)，都至少 1 秒
+    pub fn heartbeat(&self) -> (Duration, Duration) {
+        (
+            Duration::from_secs(self.heartbeat_interval_secs.max(1)),
+            Duration::from_secs(self.idle_timeout_secs.max(1)),
+        )
+    }
+
+    pub fn shutdown_deadline(&self) -> Duration {
+        Duration::from_secs(self.shutdown_deadline_secs)
+    }
+
+    // 电脑对手用的引擎，每盘棋一个，配置了外部引擎时每盘棋各起一个进程
+    pub fn opponent_engine(&self, seed: u64) -> Box<dyn Engine> {
+        let builtin = self.ai_engine.seeded(seed);
+        match &self.external_engine {
+            Some(external) => Box::new(ExternalEngine::new(external.clone(), builtin)),
+            None => builtin,
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let config: Self = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        config.rules.validate()?;
+        Ok(config)
+    }
+}
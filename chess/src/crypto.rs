@@ -0,0 +1,86 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::StoreError;
+
+// 密钥也可以通过环境变量提供，优先于配置文件
+pub const STORAGE_KEY_ENV: &str = "GOMOKU_STORAGE_KEY";
+
+// 加密后的值带上前缀，没有前缀的旧数据按明文读取
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+// 从存储密钥派生查找索引的密钥，不和加密直接共用同一个密钥
+const INDEX_KEY_LABEL: &[u8] = b"gomoku index key v1";
+
+// AES-256-GCM，密钥为 32 字节的 base64
+pub struct Cipher {
+    cipher: Aes256Gcm,
+    index_key: Vec<u8>,
+}
+
+impl Cipher {
+    pub fn from_base64(key: &str) -> Result<Self, StoreError> {
+        let bytes = STANDARD
+            .decode(key.trim())
+            .map_err(|e| StoreError(format!("密钥不是有效的 base64: {}", e)))?;
+        if bytes.len() != 32 {
+            return Err(StoreError(format!(
+                "密钥长度应为 32 字节，实际为 {} 字节",
+                bytes.len()
+            )));
+        }
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&bytes).expect("HMAC 接受任意长度密钥");
+        mac.update(INDEX_KEY_LABEL);
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+            index_key: mac.finalize().into_bytes().to_vec(),
+        })
+    }
+
+    // 生成一个新密钥，供运维初始化时使用
+    pub fn generate_key() -> String {
+        STANDARD.encode(Aes256Gcm::generate_key(OsRng))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("AES-GCM 加密失败");
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        format!("{}{}", PREFIX, STANDARD.encode(data))
+    }
+
+    // 加密每次用新的随机数，密文不能拿来比较。需要唯一约束或按值查找的列另存这个
+    // 带密钥的 HMAC：同一个值总是得到同一个结果，没有密钥看不出原文
+    pub fn blind_index(&self, value: &str) -> String {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("HMAC 接受任意长度密钥");
+        mac.update(value.as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, StoreError> {
+        let Some(encoded) = value.strip_prefix(PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let data = STANDARD
+            .decode(encoded)
+            .map_err(|e| StoreError(format!("密文格式错误: {}", e)))?;
+        if data.len() < NONCE_LEN {
+            return Err(StoreError("密文格式错误".to_string()));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| StoreError("解密失败，请检查存储密钥".to_string()))?;
+        String::from_utf8(plaintext).map_err(|e| StoreError(e.to_string()))
+    }
+}
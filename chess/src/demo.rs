@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use tokio::sync::oneshot;
+
+use crate::PlayerRole;
+#[cfg(feature = "server")]
+use crate::{
+    Board, Fanout, GameError, GameMessage, MessageLimits, RoomQuota, RulesConfig, Spectator,
+};
+
+// 讲解用的变化树：每个节点是一手棋，可以带一段讲解，children 是接下来的几种下法
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariationNode {
+    pub player: PlayerRole,
+    pub row: usize,
+    pub col: usize,
+    #[serde(default)]
+    pub comment: String,
+    #[serde(default)]
+    pub children: Vec<VariationNode>,
+}
+
+// 讲解人对演示棋盘的操作
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DemoAction {
+    // 不分先后，哪一方的子都可以摆
+    Place {
+        row: usize,
+        col: usize,
+        color: PlayerRole,
+    },
+    Remove {
+        row: usize,
+        col: usize,
+    },
+    Clear,
+    // 以当前局面为起点载入准备好的变化树，variations 是第一手的几种下法
+    LoadVariations {
+        variations: Vec<VariationNode>,
+    },
+    // 跳到变化树里的一个位置，path 是每一层选第几种下法，空表示起点
+    Goto {
+        path: Vec<usize>,
+    },
+    // 一段讲解，原样转给观众
+    Note {
+        text: String,
+    },
+}
+
+// 演示房间：没有胜负，讲解人随意摆子、在变化树里来回切换，观众通过广播任务收看。
+// 讲解人断开时房间关闭
+#[cfg(feature = "server")]
+pub struct DemoRoom {
+    id: String,
+    title: String,
+    board: Board,
+    // 载入变化树时的局面和落子记录，跳到树里的位置时从这里重新摆
+    base: Board,
+    variations: Vec<VariationNode>,
+    path: Vec<usize>,
+    viewers: Fanout,
+    next_viewer: u64,
+}
+
+#[cfg(feature = "server")]
+impl DemoRoom {
+    pub fn new(title: &str, rules: RulesConfig, limits: MessageLimits, quota: RoomQuota) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            board: Board::with_rules(rules),
+            base: Board::with_rules(rules),
+            variations: Vec::new(),
+            path: Vec::new(),
+            viewers: Fanout::spawn(limits, quota),
+            next_viewer: 0,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.spectator_count()
+    }
+
+    pub fn status(&self) -> GameMessage {
+        self.board.status()
+    }
+
+    // 变化树里的当前位置、这一手的讲解和接下来可选的下法
+    pub fn position(&self) -> GameMessage {
+        let mut comment = String::new();
+        let mut next = &self.variations;
+        for &i in &self.path {
+            comment.clone_from(&next[i].comment);
+            next = &next[i].children;
+        }
+        GameMessage::DemoPosition {
+            path: self.path.clone(),
+            comment,
+            next: next.iter().map(|node| (node.row, node.col)).collect(),
+        }
+    }
+
+    // 执行讲解人的操作并广播给观众，返回广播的消息，讲解人自己也收一份
+    pub fn apply(&mut self, action: DemoAction) -> Result<Vec<GameMessage>, GameError> {
+        let messages = match action {
+            DemoAction::Place { row, col, color } => {
+                self.board.validate_move(row, col)?;
+                self.board.cells[row][col] = Some(color);
+                self.board.current_player = color.other();
+                self.board.record_move(color, row, col);
+                self.board.rehash();
+                vec![self.status()]
+            }
+            DemoAction::Remove { row, col } => {
+                if !self.board.rules.contains(row as i32, col as i32)
+                    || self.board.cells[row][col].is_none()
+                {
+                    return Err(GameError::InvalidPosition(format!(
+                        "位置 ({}, {}) 上没有棋子",
+                        row, col
+                    )));
+                }
+                self.board.cells[row][col] = None;
+                self.board
+                    .moves
+                    .retain(|record| (record.row, record.col) != (row, col));
+                self.board.rehash();
+                vec![self.status()]
+            }
+            DemoAction::Clear => {
+                self.board = Board::with_rules(self.board.rules);
+                vec![self.status()]
+            }
+            DemoAction::LoadVariations { variations } => {
+                check_variations(&self.board, &variations, 1)?;
+                self.base = self.board.clone();
+                self.variations = variations;
+                self.path.clear();
+                vec![self.status(), self.position()]
+            }
+            DemoAction::Goto { path } => {
+                let mut board = self.base.clone();
+                board.current_player = PlayerRole::Black;
+                let mut level = &self.variations;
+                for &i in &path {
+                    let node = level.get(i).ok_or_else(|| {
+                        GameError::InvalidInput(format!("变化树里没有位置 {:?}", path))
+                    })?;
+                    board.cells[node.row][node.col] = Some(node.player);
+                    board.current_player = node.player.other();
+                    board.record_move(node.player, node.row, node.col);
+                    level = &node.children;
+                }
+                board.rehash();
+                self.board = board;
+                self.path = path;
+                vec![self.status(), self.position()]
+            }
+            DemoAction::Note { text } => vec![GameMessage::Narration { text }],
+        };
+        for msg in &messages {
+            self.viewers.broadcast(msg.clone());
+        }
+        Ok(messages)
+    }
+
+    // 观众入场先收到当前局面和变化树位置，房间关闭时从返回的通道交还连接
+    pub fn add_viewer(&mut self, viewer: Spectator) -> (u64, oneshot::Receiver<Spectator>) {
+        let id = self.next_viewer;
+        self.next_viewer += 1;
+        let released = self.viewers.join(id, viewer);
+        self.viewers.send(id, self.status());
+        self.viewers.send(id, self.position());
+        (id, released)
+    }
+
+    pub fn remove_viewer(&mut self, id: u64) {
+        self.viewers.leave(id);
+    }
+
+    pub fn send_to_viewer(&self, id: u64, msg: GameMessage) {
+        self.viewers.send(id, msg);
+    }
+
+    // 讲解人离开：通知观众后交还所有连接
+    pub fn close(&self) {
+        println!(
+            "演示房间 {} ({}) 关闭，{} 位观众离开",
+            self.id,
+            self.title,
+            self.viewer_count()
+        );
+        self.viewers.broadcast(GameMessage::DemoClosed {
+            demo_id: self.id.clone(),
+        });
+        self.viewers.release_all();
+    }
+}
+
+// 载入前检查整棵树：每一手都在棋盘内，并且不落在这条变化里已经有子的位置
+#[cfg(feature = "server")]
+fn check_variations(
+    board: &Board,
+    variations: &[VariationNode],
+    depth: usize,
+) -> Result<(), GameError> {
+    for node in variations {
+        board
+            .validate_move(node.row, node.col)
+            .map_err(|e| GameError::InvalidInput(format!("变化树第 {} 手不合法: {}", depth, e)))?;
+        let mut next = Board::with_rules(board.rules);
+        next.cells = board.cells;
+        next.cells[node.row][node.col] = Some(node.player);
+        check_variations(&next, &node.children, depth + 1)?;
+    }
+    Ok(())
+}
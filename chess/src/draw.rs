@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Board, LineDirection, PlayerRole};
+#[cfg(feature = "server")]
+use crate::{Game, GameError, GameMessage, GamePhase};
+
+// 对局为什么结束，随 GameOver 发给客户端。已经发布的值不改名
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameOverReason {
+    FiveInARow,
+    Timeout,
+    Resignation,
+    // 中途离开、超过宽限期没有重连或者被管理员移出
+    Disconnect,
+    DrawAgreed,
+    BoardFull,
+    // 达到规则里的步数上限
+    MoveLimit,
+    // 棋盘上已经没有哪一方还能连成线的位置
+    Blocked,
+    // 更新的服务器发来的、这个客户端不认识的原因
+    #[serde(other)]
+    Unknown,
+}
+
+impl Board {
+    // 按规则判和，没有连成线时在每一步之后检查
+    pub fn draw_reason(&self) -> Option<GameOverReason> {
+        if self.is_full() {
+            Some(GameOverReason::BoardFull)
+        } else if self.rules.move_limit > 0 && self.moves.len() >= self.rules.move_limit {
+            Some(GameOverReason::MoveLimit)
+        } else if self.rules.draw_when_blocked && self.is_blocked() {
+            Some(GameOverReason::Blocked)
+        } else {
+            None
+        }
+    }
+
+    // 每一段 win_length 长的线上都已经有双方的棋子，谁也不可能再赢
+    pub fn is_blocked(&self) -> bool {
+        let length = self.rules.win_length as i32;
+        !self.rules.points().any(|(row, col)| {
+            LineDirection::ALL.into_iter().any(|direction| {
+                let (dr, dc) = direction.step();
+                let (row, col) = (row as i32, col as i32);
+                if !self
+                    .rules
+                    .contains(row + dr * (length - 1), col + dc * (length - 1))
+                {
+                    return false;
+                }
+                let mut seen = [false; 2];
+                for i in 0..length {
+                    match self.cells[(row + dr * i) as usize][(col + dc * i) as usize] {
+                        Some(PlayerRole::Black) => seen[0] = true,
+                        Some(PlayerRole::White) => seen[1] = true,
+                        None => {}
+                    }
+                }
+                !(seen[0] && seen[1])
+            })
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+impl Game {
+    // 提出和棋。对手已经提出过时直接成和；提议在任何一方落子后作废
+    pub async fn offer_draw(&mut self, player: PlayerRole) -> Result<(), GameError> {
+        if self.phase != GamePhase::Playing {
+            return Err(GameError::InvalidPhase {
+                from: self.phase,
+                to: GamePhase::Finished,
+            });
+        }
+        if self.draw_offer == Some(player.other()) {
+            return self.answer_draw(player, true).await;
+        }
+        println!("玩家 {:?} 提出和棋", player);
+        self.draw_offer = Some(player);
+        self.notify_players(GameMessage::DrawOffered { player })
+            .await;
+        Ok(())
+    }
+
+    // 回应对手的和棋提议
+    pub async fn answer_draw(&mut self, player: PlayerRole, accept: bool) -> Result<(), GameError> {
+        if self.draw_offer != Some(player.other()) {
+            return Err(GameError::InvalidMove("对手没有提出和棋".to_string()));
+        }
+        self.draw_offer = None;
+        if accept {
+            println!("玩家 {:?} 同意和棋", player);
+            self.finish(None, GameOverReason::DrawAgreed).await;
+        } else {
+            self.notify_players(GameMessage::DrawDeclined { player })
+                .await;
+        }
+        Ok(())
+    }
+
+    pub fn draw_offer(&self) -> Option<PlayerRole> {
+        self.draw_offer
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    Clock, ClockSnapshot, DowntimePolicy, Game, GameError, GamePhase, GameType, MoveRecord,
+    PlayerRole, RoomId, RoomManager, RulesConfig,
+};
+
+// 每个房间保留的最近事件条数
+pub const RECENT_EVENTS: usize = 50;
+
+// 对局里发生的一件事，文字和朗读的描述相同
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameEvent {
+    pub at: DateTime<Utc>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeatDump {
+    pub role: PlayerRole,
+    pub username: String,
+    // 掉线等待重连的为 false
+    pub connected: bool,
+    pub authenticated: bool,
+}
+
+// 一个房间当时的状态，带上全部落子，重启后可以照着摆回去接着下
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomDump {
+    pub room: RoomId,
+    pub game_id: String,
+    pub game_type: GameType,
+    pub rules: RulesConfig,
+    pub seed: u64,
+    pub started_at: DateTime<Utc>,
+    pub finished: bool,
+    pub winner: Option<PlayerRole>,
+    pub seats: Vec<SeatDump>,
+    pub moves: Vec<MoveRecord>,
+    // 最终局面的 Zobrist 哈希，恢复时和重放的结果核对；旧的转储没有
+    #[serde(default)]
+    pub position_hash: Option<u64>,
+    // 保存时双方的剩余时间，不限时的对局和旧的转储没有
+    #[serde(default)]
+    pub clock: Option<ClockSnapshot>,
+    pub spectators: usize,
+    // 从旧到新
+    pub events: Vec<GameEvent>,
+}
+
+// 崩溃或收到终止信号时写出的诊断快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashDump {
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub rooms: Vec<RoomDump>,
+    // 当时被占用、读不到的房间，例如正在处理时出错的那一间
+    pub unavailable: Vec<RoomId>,
+}
+
+impl Game {
+    pub(crate) fn record_event(&mut self, text: &str) {
+        if self.events.len() == RECENT_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(GameEvent {
+            at: Utc::now(),
+            text: text.to_string(),
+        });
+    }
+
+    pub fn dump(&self, room: RoomId) -> RoomDump {
+        let seats = [PlayerRole::Black, PlayerRole::White]
+            .into_iter()
+            .filter_map(|role| {
+                let username = self.names.get(&role)?.clone();
+                let connected = self.players.contains_key(&role);
+                if !connected
+                    && !self.paused.contains_key(&role)
+                    && !self.reserved.contains_key(&role)
+                {
+                    return None;
+                }
+                Some(SeatDump {
+                    role,
+                    username,
+                    connected,
+                    authenticated: self.authenticated.contains(&role),
+                })
+            })
+            .collect();
+        RoomDump {
+            room,
+            game_id: self.id.clone(),
+            game_type: self.game_type,
+            rules: self.board.rules,
+            seed: self.seed,
+            started_at: self.started_at,
+            finished: self.is_finished(),
+            winner: self.winner,
+            seats,
+            moves: self.board.moves.clone(),
+            position_hash: Some(self.board.hash()),
+            clock: self
+                .clock
+                .as_ref()
+                .map(|clock| clock.snapshot(Instant::now(), Utc::now())),
+            spectators: self.spectator_count(),
+            events: self.events.iter().cloned().collect(),
+        }
+    }
+
+    // 从保存的记录重建对局：按记录重放全部落子，轮到谁、哈希都和保存时一样，
+    // 双方的剩余时间也照记录恢复。座位是空的，只留给原来的玩家，计时停着，
+    // 双方重新坐下后从轮到的一方接着计时；停机期间的时间按 downtime 的规则算。
+    // 重放结果和记录对不上时拒绝恢复
+    pub fn from_record(record: &RoomDump, downtime: DowntimePolicy) -> Result<Self, GameError> {
+        let mut board = record.verify()?;
+        // 重放时的落子时间是现在，换回记录里的时间
+        board.moves.clone_from(&record.moves);
+        let mut game = Self::new();
+        game.id.clone_from(&record.game_id);
+        game.seed = record.seed;
+        game.game_type = record.game_type;
+        game.started_at = record.started_at;
+        game.board = board;
+        game.winner = record.winner;
+        for seat in &record.seats {
+            game.names.insert(seat.role, seat.username.clone());
+            game.reserved.insert(seat.role, seat.username.clone());
+            if seat.authenticated {
+                game.authenticated.insert(seat.role);
+            }
+        }
+        if let Some(snapshot) = &record.clock {
+            let now = Instant::now();
+            let mut clock = Clock::restore(snapshot, downtime, Utc::now(), now);
+            clock.stop(now);
+            game.time_control = Some(snapshot.control);
+            game.clock = Some(clock);
+        }
+        if record.finished {
+            game.enter_phase(GamePhase::Playing)?;
+            game.enter_phase(GamePhase::Finished)?;
+        }
+        Ok(game)
+    }
+}
+
+impl CrashDump {
+    // 等待每个房间的锁，收到终止信号时用
+    pub async fn capture(rooms: &Mutex<RoomManager>, reason: &str) -> Self {
+        let games = rooms.lock().await.games();
+        let mut dumps = Vec::with_capacity(games.len());
+        for (room, game) in games.into_iter().enumerate() {
+            dumps.push(game.lock().await.dump(room));
+        }
+        Self::new(reason, dumps, Vec::new())
+    }
+
+    // 不等锁，panic 时用：出错的任务可能还拿着锁，等下去会卡死
+    pub fn try_capture(rooms: &Mutex<RoomManager>, reason: &str) -> Self {
+        let Ok(games) = rooms.try_lock().map(|rooms| rooms.games()) else {
+            return Self::new(reason, Vec::new(), Vec::new());
+        };
+        let mut dumps = Vec::with_capacity(games.len());
+        let mut unavailable = Vec::new();
+        for (room, game) in games.into_iter().enumerate() {
+            match game.try_lock() {
+                Ok(game) => dumps.push(game.dump(room)),
+                Err(_) => unavailable.push(room),
+            }
+        }
+        Self::new(reason, dumps, unavailable)
+    }
+
+    fn new(reason: &str, rooms: Vec<RoomDump>, unavailable: Vec<RoomId>) -> Self {
+        Self {
+            reason: reason.to_string(),
+            created_at: Utc::now(),
+            rooms,
+            unavailable,
+        }
+    }
+
+    // 写到目录下的 crash-<时间>.json，返回文件路径
+    pub fn write_to(&self, dir: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&dir)?;
+        let name = format!(
+            "crash-{}.json",
+            self.created_at.format("%Y%m%dT%H%M%S%.3fZ")
+        );
+        let path = dir.as_ref().join(name);
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    pub fn read_from(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(std::io::Error::other)
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{GameMessage, GamePhase, PlayerRole};
+
+// 随 GameMessage::Error 发给客户端的错误码，程序按它判断错误类型，不用去匹配提示文字。
+// 已经发布的错误码不改名
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidInput,
+    InvalidPosition,
+    PositionOccupied,
+    InvalidMove,
+    Disconnected,
+    // 对局当前的阶段不允许这个操作，例如对局已结束还要认输
+    InvalidPhase,
+    // 消息解析失败、协议版本太旧或者这时候不该发的消息
+    Protocol,
+    // 令牌、密码、邀请不对，或者需要管理员权限
+    Unauthorized,
+    // 要找的对局、用户不存在
+    NotFound,
+    // 功能没开、座位已满、服务器准备维护，稍后或者换个方式可能就行
+    Unavailable,
+    Internal,
+    // 更新的服务器发来的、这个客户端不认识的错误码
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GameError {
+    #[error("输入错误: {0}")]
+    InvalidInput(String),
+    #[error("位置错误: {0}")]
+    InvalidPosition(String),
+    #[error("位置已被占用: {0}")]
+    PositionOccupied(String),
+    #[error("移动错误: {0}")]
+    InvalidMove(String),
+    // 玩家连接的发送任务已经结束，消息发不出去
+    #[error("玩家 {0:?} 的连接已断开")]
+    Disconnected(PlayerRole),
+    #[error("对局处于 {from:?} 阶段，不能转为 {to:?}")]
+    InvalidPhase { from: GamePhase, to: GamePhase },
+    #[error("{0}")]
+    Protocol(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Unavailable(String),
+    // 存下的对局重放后和记录对不上
+    #[error("对局记录校验失败: {0}")]
+    Corrupted(String),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+impl GameError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            GameError::InvalidInput(_) => ErrorCode::InvalidInput,
+            GameError::InvalidPosition(_) => ErrorCode::InvalidPosition,
+            GameError::PositionOccupied(_) => ErrorCode::PositionOccupied,
+            GameError::InvalidMove(_) => ErrorCode::InvalidMove,
+            GameError::Disconnected(_) => ErrorCode::Disconnected,
+            GameError::InvalidPhase { .. } => ErrorCode::InvalidPhase,
+            GameError::Protocol(_) => ErrorCode::Protocol,
+            GameError::Unauthorized(_) => ErrorCode::Unauthorized,
+            GameError::NotFound(_) => ErrorCode::NotFound,
+            GameError::Unavailable(_) => ErrorCode::Unavailable,
+            GameError::Corrupted(_) | GameError::Store(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("存储错误: {0}")]
+pub struct StoreError(pub String);
+
+#[cfg(feature = "server")]
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError(e.to_string())
+    }
+}
+
+// GameMessage::Error 的内容。没有错误码时按旧格式发成一个字符串，
+// 有错误码时发成 {"code": ..., "message": ...}，两种格式都能解析
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[serde(from = "ErrorWire", into = "ErrorWire")]
+#[error("{message}")]
+pub struct ErrorReply {
+    pub code: Option<ErrorCode>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ErrorWire {
+    Message(String),
+    Coded { code: ErrorCode, message: String },
+}
+
+impl From<ErrorWire> for ErrorReply {
+    fn from(wire: ErrorWire) -> Self {
+        match wire {
+            ErrorWire::Message(message) => Self {
+                code: None,
+                message,
+            },
+            ErrorWire::Coded { code, message } => Self {
+                code: Some(code),
+                message,
+            },
+        }
+    }
+}
+
+impl From<ErrorReply> for ErrorWire {
+    fn from(reply: ErrorReply) -> Self {
+        match reply.code {
+            Some(code) => ErrorWire::Coded {
+                code,
+                message: reply.message,
+            },
+            None => ErrorWire::Message(reply.message),
+        }
+    }
+}
+
+impl From<GameError> for ErrorReply {
+    fn from(e: GameError) -> Self {
+        Self {
+            code: Some(e.code()),
+            message: e.to_string(),
+        }
+    }
+}
+
+// 没有错误码的提示，例如发给还没声明能力的连接
+impl From<&str> for ErrorReply {
+    fn from(message: &str) -> Self {
+        Self {
+            code: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<GameError> for GameMessage {
+    fn from(e: GameError) -> Self {
+        GameMessage::Error(e.into())
+    }
+}
+
+impl GameMessage {
+    // 去掉错误码，发给没有声明 ErrorCodes 能力的客户端
+    pub fn without_error_code(self) -> Self {
+        match self {
+            GameMessage::Error(reply) => GameMessage::Error(ErrorReply {
+                code: None,
+                ..reply
+            }),
+            msg => msg,
+        }
+    }
+}
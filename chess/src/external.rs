@@ -0,0 +1,314 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Board, Budget, Engine, PlayerRole};
+
+pub const DEFAULT_ENGINE_MOVE_TIMEOUT_MS: u64 = 5_000;
+pub const DEFAULT_ENGINE_CPU_SECS: u64 = 300;
+pub const DEFAULT_ENGINE_MEMORY_MB: u64 = 512;
+pub const DEFAULT_ENGINE_MAX_RESTARTS: u32 = 3;
+// 启动后等 START 回复 OK 的时间
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 服务器上运行的外部引擎，按 Gomocup（piskvork）协议通过标准输入输出对话。配置示例:
+// "external_engine": { "command": "/opt/engines/pbrain-rapfi", "move_timeout_ms": 3000 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalEngineConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // 每步最多等这么久，超时直接结束进程
+    #[serde(default = "default_move_timeout_ms")]
+    pub move_timeout_ms: u64,
+    // 进程累计的 CPU 时间和地址空间上限，只在 Unix 上生效
+    #[serde(default = "default_cpu_secs")]
+    pub cpu_secs: u64,
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: u64,
+    // 一盘棋里进程崩溃、超时或者乱下之后最多重启几次，用完后这盘棋只用内置引擎
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+}
+
+fn default_move_timeout_ms() -> u64 {
+    DEFAULT_ENGINE_MOVE_TIMEOUT_MS
+}
+
+fn default_cpu_secs() -> u64 {
+    DEFAULT_ENGINE_CPU_SECS
+}
+
+fn default_memory_mb() -> u64 {
+    DEFAULT_ENGINE_MEMORY_MB
+}
+
+fn default_max_restarts() -> u32 {
+    DEFAULT_ENGINE_MAX_RESTARTS
+}
+
+impl ExternalEngineConfig {
+    pub fn new(command: &str, args: &[&str]) -> Self {
+        Self {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            move_timeout_ms: DEFAULT_ENGINE_MOVE_TIMEOUT_MS,
+            cpu_secs: DEFAULT_ENGINE_CPU_SECS,
+            memory_mb: DEFAULT_ENGINE_MEMORY_MB,
+            max_restarts: DEFAULT_ENGINE_MAX_RESTARTS,
+        }
+    }
+
+    pub fn move_timeout(&self) -> Duration {
+        Duration::from_millis(self.move_timeout_ms)
+    }
+}
+
+// 外部引擎：进程在第一次落子时启动，整盘棋复用，电脑对手离开时结束。
+// 进程出任何问题（启动失败、超时、退出、给出不合法的落子）都先结束它，
+// 这一步改由内置引擎下，下一步再重新启动，对局本身不受影响
+pub struct ExternalEngine {
+    config: ExternalEngineConfig,
+    fallback: Box<dyn Engine>,
+    supervisor: Mutex<Supervisor>,
+}
+
+impl ExternalEngine {
+    pub fn new(config: ExternalEngineConfig, fallback: Box<dyn Engine>) -> Self {
+        Self {
+            config,
+            fallback,
+            supervisor: Mutex::new(Supervisor::default()),
+        }
+    }
+
+    // 出过几次问题，测试和日志用
+    pub fn failures(&self) -> u32 {
+        self.supervisor.lock().unwrap().failures
+    }
+}
+
+impl Engine for ExternalEngine {
+    fn choose_move(
+        &self,
+        board: &Board,
+        player: PlayerRole,
+        budget: Budget,
+    ) -> Option<(usize, usize)> {
+        let result = self
+            .supervisor
+            .lock()
+            .unwrap()
+            .next_move(&self.config, board, player);
+        match result {
+            Ok(mv) => Some(mv),
+            Err(e) => {
+                println!(
+                    "外部引擎 {} 出错，这一步改用内置引擎: {}",
+                    self.config.command, e
+                );
+                self.fallback.choose_move(board, player, budget)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Supervisor {
+    process: Option<EngineProcess>,
+    failures: u32,
+}
+
+impl Supervisor {
+    fn next_move(
+        &mut self,
+        config: &ExternalEngineConfig,
+        board: &Board,
+        player: PlayerRole,
+    ) -> Result<(usize, usize), String> {
+        if self.failures > config.max_restarts {
+            return Err("重启次数已用完".to_string());
+        }
+        let result = match self.process.take() {
+            Some(process) => Ok(process),
+            None => EngineProcess::spawn(config, board.rules.board_size),
+        }
+        .and_then(|mut process| {
+            let mv = process.turn(board, player, config.move_timeout())?;
+            Ok((process, mv))
+        });
+        // 出错的进程在这里已经随 process 一起销毁
+        match result {
+            Ok((process, mv)) => {
+                self.process = Some(process);
+                Ok(mv)
+            }
+            Err(e) => {
+                self.failures += 1;
+                Err(e)
+            }
+        }
+    }
+}
+
+// 一个正在运行的引擎进程。工作目录是单独建的临时目录，环境变量只保留 PATH，
+// 标准错误直接丢弃；销毁时结束进程并删掉工作目录
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    // 读标准输出的线程逐行转发，等回复时可以设超时
+    lines: Receiver<String>,
+    workdir: PathBuf,
+}
+
+impl EngineProcess {
+    fn spawn(config: &ExternalEngineConfig, board_size: usize) -> Result<Self, String> {
+        let workdir = std::env::temp_dir().join(format!("gomoku-engine-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&workdir).map_err(|e| format!("创建工作目录失败: {}", e))?;
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .current_dir(&workdir)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        limit_resources(&mut command, config);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&workdir);
+                return Err(format!("启动失败: {}", e));
+            }
+        };
+        let stdin = child.stdin.take().expect("标准输入已设为管道");
+        let stdout = child.stdout.take().expect("标准输出已设为管道");
+        let (tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut process = Self {
+            child,
+            stdin,
+            lines,
+            workdir,
+        };
+
+        process.send(&format!("START {}", board_size))?;
+        let reply = process.read_reply(START_TIMEOUT)?;
+        if reply != "OK" {
+            return Err(format!("START 的回复不是 OK: {}", reply));
+        }
+        process.send(&format!("INFO timeout_turn {}", config.move_timeout_ms))?;
+        process.send(&format!(
+            "INFO max_memory {}",
+            config.memory_mb * 1024 * 1024
+        ))?;
+        println!("外部引擎 {} 已启动", config.command);
+        Ok(process)
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("写入失败: {}", e))
+    }
+
+    // 跳过 MESSAGE、DEBUG 之类的说明行，返回第一行回复
+    fn read_reply(&self, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(left) {
+                Ok(line) => {
+                    let line = line.trim();
+                    if ["MESSAGE", "DEBUG"]
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix))
+                    {
+                        continue;
+                    }
+                    if line.starts_with("ERROR") || line.starts_with("UNKNOWN") {
+                        return Err(line.to_string());
+                    }
+                    return Ok(line.to_string());
+                }
+                Err(RecvTimeoutError::Timeout) => return Err("等待回复超时".to_string()),
+                Err(RecvTimeoutError::Disconnected) => return Err("进程已退出".to_string()),
+            }
+        }
+    }
+
+    // 每步都用 BOARD 发整盘局面，引擎不用自己记棋谱。1 是引擎自己的子，2 是对方的
+    fn turn(
+        &mut self,
+        board: &Board,
+        player: PlayerRole,
+        timeout: Duration,
+    ) -> Result<(usize, usize), String> {
+        self.send("BOARD")?;
+        for (row, col) in board.rules.points() {
+            if let Some(stone) = board.cells[row][col] {
+                let field = if stone == player { 1 } else { 2 };
+                // 协议里的坐标是 x,y，也就是先列后行
+                self.send(&format!("{},{},{}", col, row, field))?;
+            }
+        }
+        self.send("DONE")?;
+        let reply = self.read_reply(timeout)?;
+        let parsed = reply
+            .split_once(',')
+            .and_then(|(x, y)| Some((y.trim().parse().ok()?, x.trim().parse().ok()?)));
+        match parsed {
+            Some((row, col)) if board.validate_move(row, col).is_ok() => Ok((row, col)),
+            _ => Err(format!("不合法的落子: {}", reply)),
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.send("END");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.workdir);
+    }
+}
+
+// 在子进程 exec 之前设置 CPU 时间和内存上限，超出 CPU 时间时进程被系统结束
+#[cfg(unix)]
+fn limit_resources(command: &mut Command, config: &ExternalEngineConfig) {
+    use std::os::unix::process::CommandExt;
+
+    let cpu = config.cpu_secs as libc::rlim_t;
+    let memory = (config.memory_mb * 1024 * 1024) as libc::rlim_t;
+    // pre_exec 的闭包运行在 fork 出来的子进程里，只调用 setrlimit
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, value) in [(libc::RLIMIT_CPU, cpu), (libc::RLIMIT_AS, memory)] {
+                let limit = libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                };
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_resources(_command: &mut Command, _config: &ExternalEngineConfig) {}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, SplitSink, StreamExt};
+use futures_util::SinkExt;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::quota::QuotaGate;
+use crate::{GameMessage, MessageLimits, Region, RegionView, RoomQuota};
+
+// 同时写入的观战连接数上限
+const MAX_CONCURRENT_WRITES: usize = 32;
+// 一条消息迟迟写不出去的观战者直接断开，不拖慢其他人
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub type SpectatorSink = SplitSink<WebSocketStream<TcpStream>, Message>;
+
+// 观战连接的发送端和窗口订阅。观战时交给房间的广播任务，离开或者对局重置时交还
+pub struct Spectator {
+    pub sink: SpectatorSink,
+    // 整盘局面放在堆上，连接在任务之间移交时不用整块拷贝
+    pub view: Box<RegionView>,
+}
+
+impl Spectator {
+    pub fn new(sink: SpectatorSink) -> Self {
+        Self {
+            sink,
+            view: Box::new(RegionView::new()),
+        }
+    }
+}
+
+enum Command {
+    Join {
+        id: u64,
+        spectator: Spectator,
+        released: oneshot::Sender<Spectator>,
+    },
+    Leave(u64),
+    Send(u64, GameMessage),
+    // status 是订阅时的局面，窗口内容从它开始维护
+    Subscribe {
+        id: u64,
+        region: Option<Region>,
+        status: GameMessage,
+    },
+    Broadcast(GameMessage),
+    ReleaseAll,
+    // 配额窗口结束，发出扣下的消息的摘要
+    Flush,
+}
+
+struct Entry {
+    spectator: Spectator,
+    released: oneshot::Sender<Spectator>,
+}
+
+// 每个房间一个广播任务，持有全部观战连接：一条消息只序列化一次，
+// 再以有限的并发写给所有观战者。对局只往任务里投递，不用等观战者。
+// 广播超出房间配额时合并成摘要，刷屏的房间拖不垮观战者
+pub struct Fanout {
+    tx: mpsc::UnboundedSender<Command>,
+    count: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl Fanout {
+    pub fn spawn(limits: MessageLimits, quota: RoomQuota) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let count = Arc::new(AtomicUsize::new(0));
+        let task = tokio::spawn(run(rx, limits, quota, count.clone()));
+        Self { tx, count, task }
+    }
+
+    // 交出连接开始观战，离开或者对局重置时从返回的通道拿回；通道关闭说明连接已经断了
+    pub fn join(&self, id: u64, spectator: Spectator) -> oneshot::Receiver<Spectator> {
+        let (released, rx) = oneshot::channel();
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.submit(Command::Join {
+            id,
+            spectator,
+            released,
+        });
+        rx
+    }
+
+    pub fn leave(&self, id: u64) {
+        self.submit(Command::Leave(id));
+    }
+
+    // 只发给一位观战者，例如入场时的局面和查询的回复
+    pub fn send(&self, id: u64, msg: GameMessage) {
+        self.submit(Command::Send(id, msg));
+    }
+
+    pub fn subscribe(&self, id: u64, region: Option<Region>, status: GameMessage) {
+        self.submit(Command::Subscribe { id, region, status });
+    }
+
+    pub fn broadcast(&self, msg: GameMessage) {
+        self.submit(Command::Broadcast(msg));
+    }
+
+    // 对局重置，所有观战者回到列表
+    pub fn release_all(&self) {
+        self.submit(Command::ReleaseAll);
+    }
+
+    // 不再接收新的消息，返回的任务把已经投递的发完后结束，观战连接随之断开
+    pub fn close(self) -> JoinHandle<()> {
+        self.task
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn submit(&self, command: Command) {
+        // 任务只会在 Fanout 销毁后退出
+        let _ = self.tx.send(command);
+    }
+}
+
+async fn run(
+    mut rx: mpsc::UnboundedReceiver<Command>,
+    limits: MessageLimits,
+    quota: RoomQuota,
+    count: Arc<AtomicUsize>,
+) {
+    let mut spectators: HashMap<u64, Entry> = HashMap::new();
+    let mut gate = QuotaGate::new(quota);
+    loop {
+        let command = tokio::select! {
+            command = rx.recv() => command,
+            _ = tokio::time::sleep_until(gate.deadline().into()), if gate.is_holding() => {
+                Some(Command::Flush)
+            }
+        };
+        // 任务结束前把扣下的消息的摘要发出去
+        let Some(command) = command else {
+            flush(&mut spectators, &limits, &mut gate).await;
+            break;
+        };
+        // 写失败或超时的观战者直接丢掉，交还通道随之关闭，观战连接跟着结束
+        let mut failed = Vec::new();
+        match command {
+            Command::Join {
+                id,
+                spectator,
+                released,
+            } => {
+                spectators.insert(
+                    id,
+                    Entry {
+                        spectator,
+                        released,
+                    },
+                );
+            }
+            Command::Leave(id) => {
+                if let Some(entry) = spectators.remove(&id) {
+                    count.fetch_sub(1, Ordering::Relaxed);
+                    let _ = entry.released.send(entry.spectator);
+                }
+            }
+            Command::Send(id, msg) => {
+                if let Some(entry) = spectators.get_mut(&id) {
+                    let spectator = &mut entry.spectator;
+                    let frames = limits.encode(&spectator.view.filter(msg));
+                    if !write(&mut spectator.sink, &frames).await {
+                        failed.push(id);
+                    }
+                }
+            }
+            Command::Subscribe { id, region, status } => {
+                if let Some(entry) = spectators.get_mut(&id) {
+                    let spectator = &mut entry.spectator;
+                    spectator.view.filter(status);
+                    // 观战连接没有协商过能力，错误按旧格式发
+                    let reply = spectator
+                        .view
+                        .subscribe(region)
+                        .unwrap_or_else(|e| GameMessage::from(e).without_error_code());
+                    if !write(&mut spectator.sink, &limits.encode(&reply)).await {
+                        failed.push(id);
+                    }
+                }
+            }
+            Command::Broadcast(msg) => {
+                let frames = limits.encode(&msg);
+                let bytes = frames.iter().map(String::len).sum();
+                if gate.hold(Instant::now(), &msg, bytes) {
+                    // 先不发，观战者的局面照样跟着走，摘要之后从这里补发
+                    for entry in spectators.values_mut() {
+                        entry.spectator.view.filter(msg.clone());
+                    }
+                } else {
+                    if gate.is_holding() {
+                        failed = flush(&mut spectators, &limits, &mut gate).await;
+                    }
+                    failed.extend(broadcast(&mut spectators, &limits, &msg, frames).await);
+                }
+            }
+            Command::Flush => {
+                failed = flush(&mut spectators, &limits, &mut gate).await;
+            }
+            Command::ReleaseAll => {
+                for (_, entry) in spectators.drain() {
+                    count.fetch_sub(1, Ordering::Relaxed);
+                    let _ = entry.released.send(entry.spectator);
+                }
+            }
+        }
+        for id in failed {
+            if spectators.remove(&id).is_some() {
+                count.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// frames 是整盘棋的观战者共用的序列化结果，返回写失败的观战者
+async fn broadcast(
+    spectators: &mut HashMap<u64, Entry>,
+    limits: &MessageLimits,
+    msg: &GameMessage,
+    frames: Vec<String>,
+) -> Vec<u64> {
+    let shared = Arc::new(frames);
+    let writes: Vec<_> = spectators
+        .iter_mut()
+        .map(|(&id, entry)| {
+            let spectator = &mut entry.spectator;
+            // 订阅了窗口的观战者看到的内容各不相同，只能单独序列化
+            let frames = if spectator.view.is_subscribed() {
+                Arc::new(limits.encode(&spectator.view.filter(msg.clone())))
+            } else {
+                shared.clone()
+            };
+            (id, &mut spectator.sink, frames)
+        })
+        .collect();
+    write_all(writes).await
+}
+
+// 发出扣下的消息的摘要，再给每位观战者补发当前局面和最新的状态，返回写失败的观战者
+async fn flush(
+    spectators: &mut HashMap<u64, Entry>,
+    limits: &MessageLimits,
+    gate: &mut QuotaGate,
+) -> Vec<u64> {
+    let Some(held) = gate.release() else {
+        return Vec::new();
+    };
+    println!(
+        "观战广播超出配额，{} 条消息（{} 字节）合并为摘要",
+        held.skipped, held.bytes
+    );
+    let summary = GameMessage::EventsSummarized {
+        skipped: held.skipped,
+    };
+    let writes: Vec<_> = spectators
+        .iter_mut()
+        .map(|(&id, entry)| {
+            let spectator = &mut entry.spectator;
+            let mut messages = vec![summary.clone()];
+            if held.board_changed {
+                messages.push(spectator.view.snapshot());
+            }
+            messages.extend(held.latest.iter().cloned());
+            let frames: Vec<String> = messages.iter().flat_map(|msg| limits.encode(msg)).collect();
+            (id, &mut spectator.sink, Arc::new(frames))
+        })
+        .collect();
+    write_all(writes).await
+}
+
+async fn write_all(writes: Vec<(u64, &mut SpectatorSink, Arc<Vec<String>>)>) -> Vec<u64> {
+    let results: Vec<(u64, bool)> = stream::iter(writes)
+        .map(write_to)
+        .buffer_unordered(MAX_CONCURRENT_WRITES)
+        .collect()
+        .await;
+    results
+        .into_iter()
+        .filter(|&(_, ok)| !ok)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+async fn write_to((id, sink, frames): (u64, &mut SpectatorSink, Arc<Vec<String>>)) -> (u64, bool) {
+    (id, write(sink, &frames).await)
+}
+
+async fn write(sink: &mut SpectatorSink, frames: &[String]) -> bool {
+    let send = async {
+        for frame in frames {
+            sink.send(Message::Text(frame.clone())).await?;
+        }
+        Ok::<_, tokio_tungstenite::tungstenite::Error>(())
+    };
+    matches!(tokio::time::timeout(WRITE_TIMEOUT, send).await, Ok(Ok(())))
+}
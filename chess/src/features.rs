@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameType {
+    Gomoku,
+    Connect6,
+}
+
+impl GameType {
+    pub const ALL: [GameType; 2] = [GameType::Gomoku, GameType::Connect6];
+}
+
+// 可以按游戏类型单独开关的功能，在协议处理处检查
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Narration, // 文字解说 SetNarration
+    Replay,    // 历史对局回放 ReplayRequest
+    Analysis,  // 导出棋谱 ExportGame，评估曲线 EvaluateRequest，历史局面查询 PositionSearch
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Narration, Feature::Replay, Feature::Analysis];
+
+    // 已经上线的功能默认开启，新加入的实验功能应默认关闭
+    pub fn default_enabled(self) -> bool {
+        match self {
+            Feature::Narration | Feature::Replay | Feature::Analysis => true,
+        }
+    }
+}
+
+// 配置文件示例: "features": { "gomoku": { "replay": false } }
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FeatureFlags {
+    overrides: HashMap<GameType, HashMap<Feature, bool>>,
+}
+
+impl FeatureFlags {
+    pub fn is_enabled(&self, game_type: GameType, feature: Feature) -> bool {
+        self.overrides
+            .get(&game_type)
+            .and_then(|flags| flags.get(&feature))
+            .copied()
+            .unwrap_or_else(|| feature.default_enabled())
+    }
+
+    pub fn set(&mut self, game_type: GameType, feature: Feature, enabled: bool) {
+        self.overrides
+            .entry(game_type)
+            .or_default()
+            .insert(feature, enabled);
+    }
+
+    pub fn enabled(&self, game_type: GameType) -> Vec<Feature> {
+        Feature::ALL
+            .into_iter()
+            .filter(|&feature| self.is_enabled(game_type, feature))
+            .collect()
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use crate::ServerConfig;
+use crate::{
+    Feature, GameError, GameMessage, GameType, MessageLimits, PlayerRole, TimeControl, BOARD_SIZE,
+};
+
+// 协议版本，GameMessage 有不兼容的改动时加一
+pub const PROTOCOL_VERSION: u32 = 1;
+// 比这更旧的客户端直接拒绝连接
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// 客户端在 ConnectRequest 里声明自己能处理的可选消息，服务器只对声明过的能力
+// 发送对应的消息，没声明的按旧的方式发
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Chunks,     // 能拼回 Chunk 分段，否则大消息整条发送
+    MoveDeltas, // 认识 MoveApplied 和 Resync，否则每步之后发整盘 Status
+    ErrorCodes, // 能解析带错误码的 Error，否则只发提示文字
+    // 更新的客户端声明的、这个服务器不认识的能力
+    #[serde(other)]
+    Unknown,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [
+        Capability::Chunks,
+        Capability::MoveDeltas,
+        Capability::ErrorCodes,
+    ];
+}
+
+// 一种游戏类型的规则和当前开启的功能
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariantInfo {
+    pub game_type: GameType,
+    pub board_size: usize,
+    pub win_length: usize,
+    pub stones_per_turn: usize,
+    pub features: Vec<Feature>,
+}
+
+// 回复 ServerInfoRequest，客户端和机器人据此调整行为，不用写死这些假设
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub version: String,
+    pub protocol_version: u32,
+    pub capabilities: Vec<Capability>,
+    pub variants: Vec<VariantInfo>,
+    // 不限时为 None
+    pub time_control: Option<TimeControl>,
+    pub limits: MessageLimits,
+    pub max_rooms: usize,
+    pub hints_per_game: usize,
+    pub reconnect_grace_secs: u64,
+}
+
+#[cfg(feature = "server")]
+impl ServerInfo {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: Capability::ALL.to_vec(),
+            // 规则是整个服务器共用的，所有房间都下同一种棋
+            variants: vec![VariantInfo {
+                game_type: config.rules.game_type(),
+                board_size: config.rules.board_size,
+                win_length: config.rules.win_length,
+                stones_per_turn: config.rules.stones_per_turn,
+                features: config.features.enabled(config.rules.game_type()),
+            }],
+            time_control: config.time_control,
+            limits: config.limits,
+            max_rooms: config.max_rooms,
+            hints_per_game: config.hints_per_game,
+            reconnect_grace_secs: config.reconnect_grace_secs,
+        }
+    }
+}
+
+// 一个连接协商出的协议版本和能力，发出消息前按客户端的能力降级
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub protocol_version: u32,
+    pub capabilities: Vec<Capability>,
+    // 不认识 MoveApplied 的客户端改发整盘局面，这里跟着转发的消息维护局面
+    cells: [[Option<PlayerRole>; BOARD_SIZE]; BOARD_SIZE],
+}
+
+impl Session {
+    // 不带版本号的旧客户端按最低版本处理；服务器不认识的能力直接忽略
+    pub fn negotiate(
+        protocol_version: Option<u32>,
+        requested: &[Capability],
+    ) -> Result<Self, GameError> {
+        let version = protocol_version.unwrap_or(MIN_PROTOCOL_VERSION);
+        if version < MIN_PROTOCOL_VERSION {
+            return Err(GameError::Protocol(format!(
+                "客户端协议版本 {} 太旧，服务器至少需要 {}，请升级客户端",
+                version, MIN_PROTOCOL_VERSION
+            )));
+        }
+        let capabilities = Capability::ALL
+            .into_iter()
+            .filter(|capability| requested.contains(capability))
+            .collect();
+        Ok(Self {
+            protocol_version: version.min(PROTOCOL_VERSION),
+            capabilities,
+            cells: [[None; BOARD_SIZE]; BOARD_SIZE],
+        })
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    // 处理一条要发给这个客户端的消息，返回实际发出的消息
+    pub fn filter(&mut self, msg: GameMessage) -> GameMessage {
+        let msg = if self.supports(Capability::ErrorCodes) {
+            msg
+        } else {
+            msg.without_error_code()
+        };
+        if self.supports(Capability::MoveDeltas) {
+            return msg;
+        }
+        match msg {
+            GameMessage::Status { ref board, .. } => {
+                self.cells = **board;
+                msg
+            }
+            GameMessage::MoveApplied {
+                row,
+                col,
+                by,
+                next_player,
+                move_number,
+            } if row < BOARD_SIZE && col < BOARD_SIZE => {
+                self.cells[row][col] = Some(by);
+                GameMessage::Status {
+                    board: Box::new(self.cells),
+                    current_player: next_player,
+                    move_number,
+                    last_move: Some((row, col)),
+                    moves: Vec::new(),
+                }
+            }
+            msg => msg,
+        }
+    }
+
+    // 序列化要发出的消息，不支持分段的客户端整条发送
+    pub fn encode(&self, limits: &MessageLimits, msg: &GameMessage) -> Vec<String> {
+        if self.supports(Capability::Chunks) {
+            limits.encode(msg)
+        } else {
+            vec![serde_json::to_string(msg).unwrap()]
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ArchivedGame, Board, GameError, MoveRecord, PlayerRole, RoomDump, RulesConfig};
+
+// 载入时没通过校验的对局：不恢复也不进存档索引，留给管理员查看
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    pub game_id: String,
+    pub reason: String,
+    pub found_at: DateTime<Utc>,
+}
+
+impl IntegrityIssue {
+    pub fn new(game_id: &str, error: &GameError) -> Self {
+        Self {
+            game_id: game_id.to_string(),
+            reason: error.to_string(),
+            found_at: Utc::now(),
+        }
+    }
+}
+
+// 存下来的结果，和重放得到的比较
+struct Expected {
+    winner: Option<PlayerRole>,
+    finished: bool,
+    // 旧记录没有校验和，不比较
+    position_hash: Option<u64>,
+}
+
+// 按规则重放存下的落子，再和存下的结局、校验和比较。存储损坏或者新旧版本的规则不一致时
+// 会在这里发现，这样的记录不能接着下
+fn replay(
+    rules: RulesConfig,
+    moves: &[MoveRecord],
+    expected: Expected,
+) -> Result<Board, GameError> {
+    rules.validate().map_err(GameError::Corrupted)?;
+    let mut board = Board::with_rules(rules);
+    let stones: Vec<_> = moves.iter().map(|m| (m.player, m.row, m.col)).collect();
+    board
+        .apply_moves(&stones)
+        .map_err(|e| GameError::Corrupted(e.to_string()))?;
+    if let Some(hash) = expected.position_hash {
+        if hash != board.hash() {
+            return Err(GameError::Corrupted(format!(
+                "局面校验和不符：记录是 {:016x}，重放得到 {:016x}",
+                hash,
+                board.hash()
+            )));
+        }
+    }
+    // 认输、超时的对局棋盘上没有连线，只有连成线的才能核对胜方
+    match board.check_winner() {
+        Some(player) if !expected.finished || expected.winner != Some(player) => {
+            return Err(GameError::Corrupted(format!(
+                "{:?} 已经连成一线，记录的结果对不上",
+                player
+            )));
+        }
+        _ => {}
+    }
+    if !expected.finished && expected.winner.is_some() {
+        return Err(GameError::Corrupted("对局没结束却记了胜方".to_string()));
+    }
+    Ok(board)
+}
+
+impl RoomDump {
+    // 通过校验时返回重放得到的局面
+    pub fn verify(&self) -> Result<Board, GameError> {
+        replay(
+            self.rules,
+            &self.moves,
+            Expected {
+                winner: self.winner,
+                finished: self.finished,
+                position_hash: self.position_hash,
+            },
+        )
+    }
+}
+
+impl ArchivedGame {
+    pub fn verify(&self) -> Result<(), GameError> {
+        replay(
+            self.rules,
+            &self.moves,
+            Expected {
+                winner: self.winner,
+                finished: true,
+                position_hash: self.position_hash,
+            },
+        )
+        .map(|_| ())
+    }
+}
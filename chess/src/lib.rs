@@ -1,43 +1,264 @@
-use std::clone;
-use std::{collections::HashMap, sync::Arc};
+// 下面的对局状态 Game 只在服务器上用到，连接处理见 server.rs
+#[cfg(feature = "server")]
+use {
+    std::collections::{HashMap, HashSet, VecDeque},
+    std::sync::Arc,
+    std::time::{Duration, Instant},
+    tokio::sync::{mpsc, oneshot, Mutex, RwLock},
+    tokio::task::JoinHandle,
+};
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "server")]
+pub mod admin;
 pub mod ai;
+pub mod archive;
+pub mod auth;
+#[cfg(feature = "server")]
+pub mod backup;
+pub mod browser;
+pub mod clock;
+#[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "server")]
+pub mod crypto;
+pub mod demo;
+pub mod draw;
+#[cfg(feature = "server")]
+pub mod dump;
+pub mod error;
+#[cfg(feature = "server")]
+pub mod external;
+#[cfg(feature = "server")]
+pub mod fanout;
+pub mod features;
+pub mod info;
+#[cfg(feature = "server")]
+pub mod integrity;
+pub mod limits;
+pub mod mcts;
+pub mod metrics;
+pub mod movegen;
+pub mod names;
+pub mod narrate;
+pub mod outbox;
+pub mod phase;
+pub mod preview;
+#[cfg(feature = "server")]
+pub mod quota;
+pub mod rating;
+pub mod record;
+pub mod region;
+pub mod resume;
+#[cfg(feature = "server")]
+pub mod room;
+pub mod rules;
+pub mod selfplay;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sgf;
+#[cfg(feature = "server")]
+pub mod shutdown;
+#[cfg(feature = "server")]
+pub mod simulate;
+#[cfg(feature = "server")]
+pub mod store;
+pub mod strict;
+#[cfg(feature = "server")]
+pub mod telemetry;
+#[cfg(feature = "server")]
+pub mod throttle;
 pub mod user;
+pub mod zobrist;
 
 pub use ai::*;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
-use tokio::sync::Mutex;
+pub use archive::*;
+pub use auth::*;
+#[cfg(feature = "server")]
+pub use backup::*;
+pub use browser::*;
+pub use clock::*;
+#[cfg(feature = "server")]
+pub use config::*;
+#[cfg(feature = "server")]
+pub use crypto::*;
+pub use demo::*;
+pub use draw::*;
+#[cfg(feature = "server")]
+pub use dump::*;
+pub use error::*;
+#[cfg(feature = "server")]
+pub use external::*;
+#[cfg(feature = "server")]
+pub use fanout::*;
+pub use features::*;
+pub use info::*;
+#[cfg(feature = "server")]
+pub use integrity::*;
+pub use limits::*;
+pub use mcts::*;
+pub use metrics::*;
+pub use outbox::*;
+pub use phase::*;
+pub use preview::*;
+#[cfg(feature = "server")]
+pub use quota::*;
+pub use rating::*;
+pub use record::*;
+pub use region::*;
+pub use resume::*;
+#[cfg(feature = "server")]
+pub use room::*;
+pub use rules::*;
+pub use selfplay::*;
+#[cfg(feature = "server")]
+pub use server::*;
+#[cfg(feature = "server")]
+pub use shutdown::*;
+#[cfg(feature = "server")]
+pub use simulate::*;
+#[cfg(feature = "server")]
+pub use store::*;
+pub use strict::*;
+#[cfg(feature = "server")]
+pub use telemetry::*;
+#[cfg(feature = "server")]
+pub use throttle::*;
 pub use user::*;
 
-use futures_util::{SinkExt, StreamExt};
-
-use tokio_tungstenite::accept_async;
-use tokio_tungstenite::tungstenite::Message;
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum GameMessage {
     ConnectRequest {
         username: String,
+        // 登录后拿到的令牌，不带则以游客身份进入，游客对局不计等级分
+        #[serde(default)]
+        token: Option<String>,
+        // 和服务器上的电脑对弈，不需要第二位玩家
+        #[serde(default)]
+        play_vs_ai: Option<Difficulty>,
+        // 邀请令牌：直接坐到邀请指定的座位上，或者开始观战
+        #[serde(default)]
+        invite: Option<String>,
+        // 客户端实现的协议版本，不带则按最低版本处理
+        #[serde(default)]
+        protocol_version: Option<u32>,
+        // 客户端能处理的可选消息
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+        // 想执的颜色，有人坐了就分到另一边；持邀请入座时不看
+        #[serde(default)]
+        preferred_role: Option<PlayerRole>,
+        // 登录时列出的停服前没下完的对局，坐回原来的座位接着下
+        #[serde(default)]
+        resume: Option<String>,
+    },
+    Register {
+        username: String,
+        password: String,
+    },
+    Login {
+        username: String,
+        password: String,
+    },
+    AuthToken {
+        token: String,
+        // 登录时附上这个用户没下完的对局，注册时为空
+        #[serde(default)]
+        unfinished: Vec<UnfinishedGame>,
+    },
+    // 管理员在线调整功能开关，在 ConnectRequest 之前发送
+    SetFeature {
+        token: String,
+        game_type: GameType,
+        feature: Feature,
+        enabled: bool,
+    },
+    Features {
+        game_type: GameType,
+        enabled: Vec<Feature>,
+    },
+    // 管理员在线开关匿名使用统计，同样在 ConnectRequest 之前发送
+    SetTelemetry {
+        token: String,
+        enabled: bool,
+    },
+    TelemetryStatus {
+        enabled: bool,
     },
     ConnectResponse {
         username: String,
         player_role: PlayerRole,
+        #[serde(default)]
+        rating: i32,
+        #[serde(default)]
+        user_id: String,
+        // 对局编号，同一盘棋里不变，断线重连、换设备后也一样
+        #[serde(default)]
+        game_id: String,
+        // 双方都支持的协议版本和能力，之后的消息按这个发送
+        #[serde(default)]
+        protocol_version: u32,
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+        // 棋盘大小和连几子获胜，旧服务器不发时就是 15 路五子棋
+        #[serde(default)]
+        rules: RulesConfig,
     },
+    // 客户端落子，必须带上对局编号、这是第几步（从 0 开始）和客户端生成的随机数，
+    // 重连后重发的同一步按随机数去重。服务器广播落子用 MoveApplied。
+    // 六子棋可以用 second 把一回合的两子一起提交，两子都合法才会落下
     Move {
         row: usize,
         col: usize,
+        #[serde(default)]
+        game_id: String,
+        #[serde(default)]
+        move_seq: usize,
+        #[serde(default)]
+        client_nonce: u64,
+        #[serde(default)]
+        second: Option<(usize, usize)>,
+    },
+    // 服务器已接受这一步（包括重发的）
+    MoveAck {
+        game_id: String,
+        move_seq: usize,
     },
-    Error(String),
+    Error(ErrorReply),
     GameOver {
         winner: Option<PlayerRole>,
+        // 连成五子的棋子坐标 (行, 列)，从一端排到另一端；和棋、超时、弃权时为空
+        #[serde(default)]
+        winning_line: Vec<(usize, usize)>,
+        // 旧服务器不发
+        #[serde(default)]
+        reason: Option<GameOverReason>,
     },
+    // 整盘局面，只在入座、重连、开始观战和客户端请求 Resync 时发送。
+    // 带上棋盘上的子数、最后一手和全部落子，客户端据此标出最后一手；
+    // 不支持逐步消息的旧客户端每步都收到整盘局面，那时不带落子列表
     Status {
-        board: [[Option<PlayerRole>; 15]; 15],
+        board: Box<[[Option<PlayerRole>; 15]; 15]>,
         current_player: PlayerRole,
+        #[serde(default)]
+        move_number: usize,
+        #[serde(default)]
+        last_move: Option<(usize, usize)>,
+        #[serde(default)]
+        moves: Vec<MoveRecord>,
+    },
+    // 对局中每一步只广播这一条。move_number 从 1 开始，等于落子后棋盘上的棋子数，
+    // 客户端据此跳过重复的消息、发现漏收的消息
+    MoveApplied {
+        row: usize,
+        col: usize,
+        by: PlayerRole,
+        next_player: PlayerRole,
+        move_number: usize,
     },
+    // 客户端发现本地局面和服务器对不上时请求整盘局面，服务器回复 Status
+    Resync,
     TurnNotification {
         player: PlayerRole,
     },
@@ -49,6 +270,262 @@ pub enum GameMessage {
         username: String,
     },
     ServerShutdown,
+    TimeUpdate {
+        black_ms: u64,
+        white_ms: u64,
+    },
+    TimeWarning {
+        player: PlayerRole,
+        remaining_secs: u64,
+    },
+    // 客户端上报自己的状态
+    SetPresence {
+        state: PresenceState,
+    },
+    // 服务器转发给对手
+    Presence {
+        player: PlayerRole,
+        state: PresenceState,
+    },
+    // 对局中说一句话
+    SendChat {
+        text: String,
+    },
+    // 服务器转发给双方和所有观战者，包括说话的人自己
+    Chat {
+        from: String,
+        text: String,
+    },
+    // 请求当前对局的棋谱
+    ExportGame,
+    GameRecord {
+        sgf: String,
+    },
+    // 对局结束并已存档，在 GameOver 之前发送
+    GameArchived {
+        game_id: String,
+    },
+    ReplayRequest {
+        game_id: String,
+    },
+    Replay {
+        game: ArchivedGame,
+    },
+    // 开启后服务器为每次变化附带一句文字描述
+    SetNarration {
+        enabled: bool,
+    },
+    Narration {
+        text: String,
+    },
+    LeaderboardRequest {
+        limit: usize,
+    },
+    Leaderboard {
+        entries: Vec<LeaderboardEntry>,
+    },
+    GetStats {
+        user_id: String,
+    },
+    Stats {
+        username: String,
+        stats: PlayerStats,
+    },
+    // 同一用户在其他设备上接管了对局，旧连接随后关闭
+    SessionTransferred,
+    // 管理员发出的通知，例如维护预告
+    ServerNotice {
+        message: String,
+    },
+    // 被管理员移出对局，随后连接关闭
+    Kicked {
+        reason: String,
+    },
+    // 严格模式下协议违规，随后连接关闭
+    ProtocolViolation {
+        violation: Violation,
+        message: String,
+    },
+    // 认输，对方获胜
+    Resign,
+    // 提出和棋，对手已经提出过时直接成和
+    OfferDraw,
+    // 转发给对手
+    DrawOffered {
+        player: PlayerRole,
+    },
+    // 回应对手的和棋提议，同意时对局以 DrawAgreed 结束
+    AnswerDraw {
+        accept: bool,
+    },
+    DrawDeclined {
+        player: PlayerRole,
+    },
+    // 客户端正常退出前发送，随后关闭连接。服务器不保留座位也不等重连，
+    // 对局没下完时按弃局判负
+    Goodbye,
+    // 对局结束后换个对手再来一盘：让出座位回到匹配队列，连接和会话保留，
+    // 入座后和刚连接时一样收到 ConnectResponse
+    FindOpponent,
+    // 对手掉线，对局暂停，宽限期内不重连判负
+    GamePaused {
+        player: PlayerRole,
+        grace_secs: u64,
+    },
+    GameResumed {
+        player: PlayerRole,
+    },
+    // 房间已满时告诉排队的玩家当前位置和预计等待时间
+    QueueStatus {
+        position: usize,
+        estimated_wait_secs: u64,
+    },
+    // 超过单帧上限的消息拆成多段，data 是原消息 JSON 的 base64 片段
+    Chunk {
+        id: u64,
+        index: usize,
+        total: usize,
+        data: String,
+    },
+    // 查询对局列表，在 ConnectRequest 之前或观战时发送
+    ListGames {
+        #[serde(default)]
+        filter: GameFilter,
+    },
+    GameList {
+        page: GamePage,
+    },
+    // 观战一盘进行中的对局，观战时再发一次即切换到另一盘
+    Watch {
+        game_id: String,
+    },
+    // 开始观战，随后是当前局面的 Status
+    Watching {
+        game: GameSummary,
+    },
+    StopWatching,
+    // 入座的玩家为当前对局生成邀请，ttl_secs 不填时 1 小时有效
+    CreateInvite {
+        role: InviteRole,
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    },
+    InviteCreated {
+        token: String,
+        role: InviteRole,
+        expires_at: i64,
+    },
+    // 房主允许或禁止观战者接替对局中让出的座位
+    AllowSubstitutes {
+        allowed: bool,
+    },
+    // 允许替补时有玩家中途离开，座位空出来等观战者接替，发给留下的玩家和观战者
+    SeatOpen {
+        game_id: String,
+        role: PlayerRole,
+    },
+    // 观战者申请接替所观战对局的空座位，成功时回复 SeatClaimed
+    ClaimSeat {
+        role: PlayerRole,
+    },
+    // 接替成功：这条观战连接随即以游客身份坐到这个座位上，接着收到 ConnectResponse
+    SeatClaimed {
+        game_id: String,
+        role: PlayerRole,
+    },
+    // 对局中请求电脑给出建议，只能在自己的回合使用，每盘次数有限
+    HintRequest,
+    Hint {
+        row: usize,
+        col: usize,
+        // 从请求方看的局面分数，越大越有利
+        score: i32,
+        hints_left: usize,
+    },
+    // 观战者只订阅棋盘的一块窗口，None 表示恢复整盘
+    SubscribeRegion {
+        #[serde(default)]
+        region: Option<Region>,
+    },
+    // 订阅窗口后代替 Status：窗口内的棋子加上窗口外的概况
+    RegionUpdate {
+        region: Region,
+        cells: Vec<Vec<Option<PlayerRole>>>,
+        current_player: PlayerRole,
+        outside: OutsideSummary,
+    },
+    // 窗口外有人落子
+    RegionSummary {
+        current_player: PlayerRole,
+        outside: OutsideSummary,
+    },
+    // 房间广播超出配额，这段时间里有 skipped 条消息没有单独发出，随后补发当前局面
+    EventsSummarized {
+        skipped: usize,
+    },
+    // 每步之后推给观战者的局面评估，从黑方看，负数表示白方占优
+    Evaluation {
+        move_seq: usize,
+        score: i32,
+    },
+    // 请求一盘已结束对局的评估曲线，进行中的对局不提供，以免变相给玩家提示
+    EvaluateRequest {
+        game_id: String,
+    },
+    EvaluationGraph {
+        game_id: String,
+        // 第 i 项是第 i 步之后的评估，从黑方看
+        scores: Vec<i32>,
+    },
+    // 查询下出过某个局面的历史对局，moves 是从空棋盘黑先走到这个局面的落子
+    PositionSearch {
+        moves: Vec<(usize, usize)>,
+    },
+    PositionGames(PositionGames),
+    // 查询服务器版本、协议版本、支持的规则和各项限制，连接前后都可以发
+    ServerInfoRequest,
+    ServerInfo(ServerInfo),
+    // 在 ConnectRequest 之前发送，开一个演示房间并成为讲解人，之后这个连接只发 DemoEdit。
+    // 需要登录拿到的令牌
+    CreateDemo {
+        title: String,
+        token: String,
+    },
+    // 观众用 Watch 带上这个编号收看
+    DemoCreated {
+        demo_id: String,
+    },
+    DemoEdit {
+        action: DemoAction,
+    },
+    // 演示棋盘在变化树里的位置，跟在 Status 之后发送
+    DemoPosition {
+        path: Vec<usize>,
+        comment: String,
+        // 接下来可选的几种下法 (行, 列)
+        next: Vec<(usize, usize)>,
+    },
+    // 讲解人已离开，随后连接关闭
+    DemoClosed {
+        demo_id: String,
+    },
+}
+
+// 排行榜一次最多返回的条数
+pub const MAX_LEADERBOARD_SIZE: usize = 100;
+// 一句聊天最多的字数
+pub const MAX_CHAT_CHARS: usize = 200;
+// 每人连着最多能发的聊天条数，之后每隔 CHAT_REFILL 恢复一条
+#[cfg(feature = "server")]
+pub const CHAT_BURST: u32 = 5;
+#[cfg(feature = "server")]
+pub const CHAT_REFILL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceState {
+    Idle,
+    Thinking,
+    ConnectionDegraded,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -66,57 +543,170 @@ impl PlayerRole {
     }
 }
 
-#[derive(Debug)]
-pub enum GameError {
-    InvalidInput(String),
-    InvalidPosition(String),
-    PositionOccupied(String),
-    InvalidMove(String),
+// 入座请求：邀请、电脑对手指定了座位；普通玩家只给出想执的颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeatRequest {
+    Exact(PlayerRole),
+    // 想执的颜色有人时坐另一边；不挑颜色的先到先坐黑方
+    Prefer(Option<PlayerRole>),
+}
+
+impl From<PlayerRole> for SeatRequest {
+    fn from(role: PlayerRole) -> Self {
+        SeatRequest::Exact(role)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub player: PlayerRole,
+    pub row: usize,
+    pub col: usize,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+// 连成五子的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineDirection {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
 }
 
-impl std::fmt::Display for GameError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl LineDirection {
+    pub const ALL: [LineDirection; 4] = [
+        LineDirection::Horizontal,
+        LineDirection::Vertical,
+        LineDirection::Diagonal,
+        LineDirection::AntiDiagonal,
+    ];
+
+    // 沿这个方向走一步时行、列的变化
+    pub fn step(self) -> (i32, i32) {
         match self {
-            GameError::InvalidInput(msg) => write!(f, "输入错误: {}", msg),
-            GameError::InvalidPosition(msg) => write!(f, "位置错误: {}", msg),
-            GameError::PositionOccupied(msg) => write!(f, "位置已被占用: {}", msg),
-            GameError::InvalidMove(msg) => write!(f, "移动错误: {}", msg),
+            LineDirection::Horizontal => (0, 1),
+            LineDirection::Vertical => (1, 0),
+            LineDirection::Diagonal => (1, 1),
+            LineDirection::AntiDiagonal => (1, -1),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinningLine {
+    pub player: PlayerRole,
+    pub direction: LineDirection,
+    // 按方向从一端排到另一端
+    pub cells: Vec<(usize, usize)>,
+}
+
+// 一步棋下完之后的局面
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveOutcome {
+    Continue,
+    Win(WinningLine),
+    Draw(GameOverReason),
+}
+
+#[derive(Clone)]
 pub struct Board {
     pub cells: [[Option<PlayerRole>; 15]; 15],
     pub current_player: PlayerRole,
+    pub moves: Vec<MoveRecord>,
+    // 小于 15 路的棋盘只用 cells 的左上角
+    pub rules: RulesConfig,
+    // Zobrist 哈希，落子和悔棋时增量更新
+    hash: u64,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
     pub fn new() -> Self {
+        Self::with_rules(RulesConfig::default())
+    }
+
+    pub fn with_rules(rules: RulesConfig) -> Self {
         Board {
             cells: [[None; 15]; 15],
             current_player: PlayerRole::Black,
+            moves: Vec::new(),
+            rules,
+            hash: 0,
         }
     }
 
-    pub fn display(&self) {
-        println!("\n当前棋盘：");
-        for row in self.cells {
-            for cell in row {
-                match cell {
-                    None => print!(" - "),
-                    Some(PlayerRole::Black) => print!(" X "),
-                    Some(PlayerRole::White) => print!(" O "),
-                }
-            }
-            println!();
+    // 当前局面（棋子加轮到谁）的 Zobrist 哈希，相同局面哈希相同，与落子顺序无关
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // 直接改了 cells 或 current_player 之后重新计算哈希
+    pub fn rehash(&mut self) {
+        self.hash = zobrist::position_hash(&self.cells, self.current_player);
+    }
+
+    // 棋盘上已有的子数，和 MoveApplied 的 move_number 一致
+    pub fn move_number(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn last_move(&self) -> Option<(usize, usize)> {
+        self.moves.last().map(|record| (record.row, record.col))
+    }
+
+    // 棋子已经直接摆到 cells 上时补记这一步，不检查也不换手
+    pub fn record_move(&mut self, player: PlayerRole, row: usize, col: usize) {
+        self.moves.push(MoveRecord {
+            player,
+            row,
+            col,
+            timestamp: chrono::Utc::now(),
+        });
+    }
+
+    pub fn status(&self) -> GameMessage {
+        GameMessage::Status {
+            board: Box::new(self.cells),
+            current_player: self.current_player,
+            move_number: self.move_number(),
+            last_move: self.last_move(),
+            moves: self.moves.clone(),
         }
     }
 
-    pub fn make_move(&mut self, row: usize, col: usize) -> Result<(), GameError> {
-        if row >= 15 || col >= 15 {
+    // 撤回最后一步，返回被撤回的落子
+    pub fn undo_move(&mut self) -> Option<MoveRecord> {
+        let record = self.moves.pop()?;
+        self.cells[record.row][record.col] = None;
+        self.hash ^= zobrist::stone_key(record.row, record.col, record.player);
+        if self.current_player != record.player {
+            self.hash ^= zobrist::WHITE_TO_MOVE;
+        }
+        self.current_player = record.player;
+        Some(record)
+    }
+
+    // 这一回合还要下几子，每回合一子时总是 1
+    pub fn stones_left(&self) -> usize {
+        let placed = self.cells.iter().flatten().flatten().count();
+        self.rules.stones_left(placed)
+    }
+
+    // 检查能否在 (row, col) 落子，不改变棋盘
+    pub fn validate_move(&self, row: usize, col: usize) -> Result<(), GameError> {
+        let size = self.rules.board_size;
+        if row >= size || col >= size {
             return Err(GameError::InvalidPosition(format!(
-                "行和列必须在 0-14 之间，你输入的是 ({}, {})",
-                row, col
+                "行和列必须在 0-{} 之间，你输入的是 ({}, {})",
+                size - 1,
+                row,
+                col
             )));
         }
         if self.cells[row][col].is_some() {
@@ -125,65 +715,78 @@ impl Board {
                 row, col
             )));
         }
+        Ok(())
+    }
+
+    pub fn make_move(&mut self, row: usize, col: usize) -> Result<(), GameError> {
+        self.validate_move(row, col)?;
+        // 六子棋一回合的第一子下完还轮到自己
+        let ends_turn = self.stones_left() == 1;
         self.cells[row][col] = Some(self.current_player);
-        self.current_player = self.current_player.other();
+        self.hash ^= zobrist::stone_key(row, col, self.current_player);
+        self.moves.push(MoveRecord {
+            player: self.current_player,
+            row,
+            col,
+            timestamp: chrono::Utc::now(),
+        });
+        if ends_turn {
+            self.hash ^= zobrist::WHITE_TO_MOVE;
+            self.current_player = self.current_player.other();
+        }
+        Ok(())
+    }
+
+    // 按顺序重放一串落子，每一步都和正常落子一样检查，并且必须轮到记录里的那一方。
+    // 同样的落子总是得到同样的局面、轮次和哈希，服务器重启后据此恢复对局
+    pub fn apply_moves(&mut self, moves: &[(PlayerRole, usize, usize)]) -> Result<(), GameError> {
+        for (i, &(player, row, col)) in moves.iter().enumerate() {
+            if player != self.current_player {
+                return Err(GameError::InvalidMove(format!(
+                    "第 {} 步应由 {:?} 落子，记录里是 {:?}",
+                    i + 1,
+                    self.current_player,
+                    player
+                )));
+            }
+            self.make_move(row, col)?;
+        }
         Ok(())
     }
 
+    // 只需要胜者时用；连成线的坐标由 winning_line 给出，GameOver 里带的就是它
     pub fn check_winner(&self) -> Option<PlayerRole> {
-        let directions = [
-            (0, 1, "水平"),      // 水平
-            (1, 0, "垂直"),      // 垂直
-            (1, 1, "对角线"),    // 对角线
-            (1, -1, "反对角线"), // 反对角线
-        ];
-
-        for row in 0..15 {
-            for col in 0..15 {
-                if let Some(player) = self.cells[row][col] {
-                    for &(dr, dc, direction) in &directions {
-                        let mut count = 1;
-                        let mut r = row as i32;
-                        let mut c = col as i32;
-
-                        // 正向检查
-                        for _ in 0..4 {
-                            r += dr;
-                            c += dc;
-                            if r < 0 || r >= 15 || c < 0 || c >= 15 {
-                                break;
-                            }
-                            if self.cells[r as usize][c as usize] == Some(player) {
-                                count += 1;
-                            } else {
-                                break;
-                            }
-                        }
-
-                        // 反向检查
-                        r = row as i32;
-                        c = col as i32;
-                        for _ in 0..4 {
-                            r -= dr;
-                            c -= dc;
-                            if r < 0 || r >= 15 || c < 0 || c >= 15 {
-                                break;
-                            }
-                            if self.cells[r as usize][c as usize] == Some(player) {
-                                count += 1;
-                            } else {
-                                break;
-                            }
-                        }
-
-                        if count >= 5 {
-                            println!(
-                                "玩家 {:?} 在 ({}, {}) 位置通过 {} 方向获胜，连续 {} 子",
-                                player, row, col, direction, count
-                            );
-                            return Some(player);
-                        }
-                    }
+        self.winning_line().map(|line| line.player)
+    }
+
+    // 找出连成 win_length 子（或更长）的一条线，从一端到另一端列出所有棋子
+    pub fn winning_line(&self) -> Option<WinningLine> {
+        for (row, col) in self.rules.points() {
+            let Some(player) = self.cells[row][col] else {
+                continue;
+            };
+            for direction in LineDirection::ALL {
+                let (dr, dc) = direction.step();
+                let stone = |r: i32, c: i32| {
+                    self.rules.contains(r, c) && self.cells[r as usize][c as usize] == Some(player)
+                };
+                // 只从线的起点开始数，避免同一条线数多次
+                if stone(row as i32 - dr, col as i32 - dc) {
+                    continue;
+                }
+                let (mut r, mut c) = (row as i32, col as i32);
+                let mut cells = Vec::new();
+                while stone(r, c) {
+                    cells.push((r as usize, c as usize));
+                    r += dr;
+                    c += dc;
+                }
+                if cells.len() >= self.rules.win_length {
+                    return Some(WinningLine {
+                        player,
+                        direction,
+                        cells,
+                    });
                 }
             }
         }
@@ -191,344 +794,1155 @@ impl Board {
     }
 
     pub fn is_full(&self) -> bool {
-        self.cells
-            .iter()
-            .all(|row| row.iter().all(|&cell| cell.is_some()))
+        self.rules
+            .points()
+            .all(|(row, col)| self.cells[row][col].is_some())
     }
 }
 
+#[cfg(feature = "server")]
 pub struct Game {
+    id: String,
+    // 这盘棋所有随机选择（目前是服务器电脑的蒙特卡洛搜索）都由它派生，随存档保存
+    seed: u64,
+    game_type: GameType,
+    features: FeatureFlags,
     board: Board,
     players: HashMap<PlayerRole, mpsc::Sender<GameMessage>>,
+    names: HashMap<PlayerRole, String>,
+    time_control: Option<TimeControl>,
+    clock: Option<Clock>,
+    phase: GamePhase,
+    winner: Option<PlayerRole>,
+    started_at: chrono::DateTime<chrono::Utc>,
+    archive: Option<Arc<Mutex<GameArchive>>>,
+    users: Option<Arc<RwLock<UserManager>>>,
+    telemetry: Option<Arc<Telemetry>>,
+    metrics: Option<Arc<Metrics>>,
+    // 订阅了文字描述的玩家
+    narrated: HashSet<PlayerRole>,
+    // 带有效令牌进入的玩家，双方都认证过才是排位赛
+    authenticated: HashSet<PlayerRole>,
+    // 对局中掉线的玩家，重连时通过通道通知等待中的旧连接
+    paused: HashMap<PlayerRole, oneshot::Sender<()>>,
+    reconnect_grace: Duration,
+    // 已经接受的 (玩家, 客户端随机数) -> 步数，用来识别重发的落子
+    nonces: HashMap<(PlayerRole, u64), usize>,
+    // 观战者的广播任务，第一位观战者入场时启动
+    spectators: Option<Fanout>,
+    next_spectator: u64,
+    // 发给观战者的消息按这个上限分段
+    limits: MessageLimits,
+    quota: RoomQuota,
+    hints_per_game: usize,
+    hint_budget: Budget,
+    // 每位玩家本局已用的提示次数
+    hints_used: HashMap<PlayerRole, usize>,
+    // 每位发言人剩下的聊天条数和上次结算的时刻，离座后保留，换座重进不能绕过限制
+    chat_budget: HashMap<String, (f64, Instant)>,
+    // 房主：先入座的玩家，房主离开后由留下的一方接任
+    owner: Option<PlayerRole>,
+    // 房主允许后，中途离开或超时未重连的座位留给观战者接替，不判负
+    substitutes_allowed: bool,
+    // 提出和棋、还没得到回应的一方
+    draw_offer: Option<PlayerRole>,
+    // 对局结束的原因，结束前为 None
+    reason: Option<GameOverReason>,
+    // 最近发生的事，写崩溃转储用
+    events: VecDeque<GameEvent>,
+    // 从停服前的记录恢复、还没重新开始的对局，座位只留给原来的玩家
+    reserved: HashMap<PlayerRole, String>,
+}
+
+#[cfg(feature = "server")]
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+#[cfg(feature = "server")]
 impl Game {
     pub fn new() -> Self {
         Game {
+            id: uuid::Uuid::new_v4().to_string(),
+            seed: rand::random(),
+            game_type: GameType::Gomoku,
+            features: FeatureFlags::default(),
             board: Board::new(),
             players: HashMap::new(),
+            names: HashMap::new(),
+            time_control: None,
+            clock: None,
+            phase: GamePhase::Waiting,
+            winner: None,
+            started_at: chrono::Utc::now(),
+            archive: None,
+            users: None,
+            telemetry: None,
+            metrics: None,
+            narrated: HashSet::new(),
+            authenticated: HashSet::new(),
+            paused: HashMap::new(),
+            reconnect_grace: Duration::from_secs(DEFAULT_RECONNECT_GRACE_SECS),
+            nonces: HashMap::new(),
+            spectators: None,
+            next_spectator: 0,
+            limits: MessageLimits::default(),
+            quota: RoomQuota::default(),
+            hints_per_game: DEFAULT_HINTS_PER_GAME,
+            hint_budget: Difficulty::Medium.budget(),
+            hints_used: HashMap::new(),
+            chat_budget: HashMap::new(),
+            owner: None,
+            substitutes_allowed: false,
+            draw_offer: None,
+            reason: None,
+            events: VecDeque::new(),
+            reserved: HashMap::new(),
+        }
+    }
+
+    pub fn with_config(
+        config: &ServerConfig,
+        archive: Arc<Mutex<GameArchive>>,
+        users: Arc<RwLock<UserManager>>,
+    ) -> Self {
+        let mut game = Self::new();
+        game.configure(config, archive, users);
+        game.time_control = config.time_control;
+        game.clock = config.time_control.map(Clock::new);
+        game.board = Board::with_rules(config.rules);
+        game.game_type = config.rules.game_type();
+        game
+    }
+
+    // 服务器配置里和局面、计时无关的部分，从记录恢复的对局也按它设置
+    pub(crate) fn configure(
+        &mut self,
+        config: &ServerConfig,
+        archive: Arc<Mutex<GameArchive>>,
+        users: Arc<RwLock<UserManager>>,
+    ) {
+        self.archive = Some(archive);
+        self.users = Some(users);
+        self.features = config.features.clone();
+        self.reconnect_grace = Duration::from_secs(config.reconnect_grace_secs);
+        self.hints_per_game = config.hints_per_game;
+        self.hint_budget = config.hint_difficulty.budget();
+        self.limits = config.limits;
+        self.quota = config.room_quotas.game;
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn record_latency(&self, stage: MoveStage, elapsed: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(stage, elapsed);
+        }
+    }
+
+    pub fn reconnect_grace(&self) -> Duration {
+        self.reconnect_grace
+    }
+
+    // 试下一子：和真正落子一样检查，但不改变对局，返回这一子形成的棋形
+    pub fn preview_move(
+        &self,
+        player: PlayerRole,
+        row: usize,
+        col: usize,
+    ) -> Result<MovePreview, GameError> {
+        if self.phase == GamePhase::Finished {
+            return Err(GameError::InvalidInput("游戏已结束".to_string()));
+        }
+        if self.board.current_player != player {
+            return Err(GameError::InvalidInput("不是你的回合".to_string()));
+        }
+        self.board.preview_move(row, col)
+    }
+
+    // 玩家请求提示：只能在对局进行中、自己的回合，每盘有次数上限。返回局面副本、计算量和
+    // 剩余次数，搜索由调用方在锁外进行，没搜出结果时用 refund_hint 退回这一次
+    pub fn take_hint(&mut self, player: PlayerRole) -> Result<(Board, Budget, usize), GameError> {
+        if self.hints_per_game == 0 {
+            return Err(GameError::InvalidInput("服务器未开放提示".to_string()));
+        }
+        match self.phase {
+            GamePhase::Playing => {}
+            GamePhase::Finished => {
+                return Err(GameError::InvalidInput("游戏已结束".to_string()));
+            }
+            GamePhase::Paused => {
+                return Err(GameError::InvalidInput(
+                    "对局暂停中，等待对手重连".to_string(),
+                ));
+            }
+            GamePhase::Waiting => {
+                return Err(GameError::InvalidInput("等待另一个玩家加入".to_string()));
+            }
+        }
+        if self.board.current_player != player {
+            return Err(GameError::InvalidInput(
+                "只能在自己的回合请求提示".to_string(),
+            ));
+        }
+        let used = self.hints_used.entry(player).or_default();
+        if *used >= self.hints_per_game {
+            return Err(GameError::InvalidInput(format!(
+                "本局的 {} 次提示已经用完",
+                self.hints_per_game
+            )));
+        }
+        *used += 1;
+        let hints_left = self.hints_per_game - *used;
+        Ok((self.position(), self.hint_budget, hints_left))
+    }
+
+    // 提示没有给出落点，不算用掉次数
+    pub fn refund_hint(&mut self, player: PlayerRole) {
+        if let Some(used) = self.hints_used.get_mut(&player) {
+            *used = used.saturating_sub(1);
+        }
+    }
+
+    // 当前局面的副本（不带落子记录），给在锁外运行的搜索使用
+    pub fn position(&self) -> Board {
+        let mut board = Board::with_rules(self.board.rules);
+        board.cells = self.board.cells;
+        board.current_player = self.board.current_player;
+        board.rehash();
+        board
+    }
+
+    async fn broadcast_time(&self) {
+        if let Some(clock) = &self.clock {
+            let now = Instant::now();
+            let msg = GameMessage::TimeUpdate {
+                black_ms: clock.remaining(PlayerRole::Black, now).as_millis() as u64,
+                white_ms: clock.remaining(PlayerRole::White, now).as_millis() as u64,
+            };
+            self.notify_players(msg).await;
+        }
+    }
+
+    // 发给所有在座的玩家。发送任务已经结束的连接只记日志，不影响其他人收到，
+    // 那个连接的读循环看到通道关闭后按掉线处理
+    async fn notify_players(&self, msg: GameMessage) {
+        for (&player, tx) in &self.players {
+            if tx.send(msg.clone()).await.is_err() {
+                log::warn!("{}", GameError::Disconnected(player));
+            }
+        }
+    }
+
+    // 由服务器时钟任务定期调用：发送倒计时提醒，超时判负
+    pub async fn tick_clock(&mut self) {
+        let event = match self.clock.as_mut() {
+            Some(clock) => clock.tick(Instant::now()),
+            None => return,
+        };
+        match event {
+            Some(ClockEvent::Warning {
+                player,
+                remaining_secs,
+            }) => {
+                log::info!("玩家 {:?} 剩余时间 {} 秒", player, remaining_secs);
+                self.notify_players(GameMessage::TimeWarning {
+                    player,
+                    remaining_secs,
+                })
+                .await;
+            }
+            Some(ClockEvent::Expired { player }) => self.end_on_time(player).await,
+            None => {}
+        }
+    }
+
+    // 掉线等待重连的玩家也占着座位
+    pub fn player_count(&self) -> usize {
+        self.players.len() + self.paused.len()
+    }
+
+    pub fn player_name(&self, player: PlayerRole) -> Option<&str> {
+        self.names.get(&player).map(String::as_str)
+    }
+
+    pub fn phase(&self) -> GamePhase {
+        self.phase
+    }
+
+    // 阶段只能经过这里改变，不合法的转换原样拒绝
+    fn enter_phase(&mut self, next: GamePhase) -> Result<(), GameError> {
+        if !self.phase.can_enter(next) {
+            return Err(GameError::InvalidPhase {
+                from: self.phase,
+                to: next,
+            });
+        }
+        if self.phase != next {
+            log::info!("对局 {} 进入 {:?} 阶段", self.id, next);
+        }
+        self.phase = next;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.phase == GamePhase::Paused
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == GamePhase::Finished
+    }
+
+    // 有人在座且没下完，掉线等待重连的也算
+    pub fn is_active(&self) -> bool {
+        !self.is_finished() && self.player_count() > 0
+    }
+
+    pub fn moves(&self) -> &[MoveRecord] {
+        &self.board.moves
+    }
+
+    // 导出当前对局的 SGF 棋谱，对局进行中也可以导出
+    pub fn export_sgf(&self) -> String {
+        let name = |player| self.names.get(&player).map(String::as_str).unwrap_or("?");
+        let result = self.is_finished().then_some(self.winner);
+        sgf::to_sgf(
+            &self.board.moves,
+            name(PlayerRole::Black),
+            name(PlayerRole::White),
+            self.board.rules.board_size,
+            result,
+        )
+    }
+
+    pub fn game_type(&self) -> GameType {
+        self.game_type
+    }
+
+    pub fn feature_enabled(&self, feature: Feature) -> bool {
+        self.features.is_enabled(self.game_type, feature)
+    }
+
+    pub fn set_feature(&mut self, game_type: GameType, feature: Feature, enabled: bool) {
+        log::info!("功能开关 {:?}/{:?} 设为 {}", game_type, feature, enabled);
+        self.features.set(game_type, feature, enabled);
+    }
+
+    pub fn features(&self) -> &FeatureFlags {
+        &self.features
+    }
+
+    pub fn set_narration(&mut self, player: PlayerRole, enabled: bool) {
+        if enabled {
+            self.narrated.insert(player);
+        } else {
+            self.narrated.remove(&player);
+        }
+    }
+
+    pub fn set_authenticated(&mut self, player: PlayerRole, authenticated: bool) {
+        if authenticated {
+            self.authenticated.insert(player);
+        } else {
+            self.authenticated.remove(&player);
+        }
+    }
+
+    pub fn is_ranked(&self) -> bool {
+        self.authenticated.len() == 2
+    }
+
+    // 同时记进最近事件，没人订阅也记
+    async fn narrate(&mut self, text: String) {
+        self.record_event(&text);
+        for role in &self.narrated {
+            if let Some(tx) = self.players.get(role) {
+                let _ = tx.send(GameMessage::Narration { text: text.clone() }).await;
+            }
+        }
+    }
+
+    // 玩家超时判负
+    async fn end_on_time(&mut self, player: PlayerRole) {
+        log::info!("玩家 {:?} 超时，判负", player);
+        self.narrate(narrate::narrate_timeout(player)).await;
+        self.finish(Some(player.other()), GameOverReason::Timeout)
+            .await;
+    }
+
+    // 结束对局：停表、存档并通知所有玩家。已经结束的对局不会再结束一次
+    async fn finish(&mut self, winner: Option<PlayerRole>, reason: GameOverReason) {
+        if let Err(e) = self.enter_phase(GamePhase::Finished) {
+            log::warn!("{}", e);
+            return;
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
+        }
+        self.winner = winner;
+        self.reason = Some(reason);
+        self.broadcast_time().await;
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.record_game(self.game_type, self.board.moves.len());
+        }
+
+        if let Some(archive) = &self.archive {
+            archive.lock().await.save(self.to_archived());
+            self.notify_players(GameMessage::GameArchived {
+                game_id: self.id.clone(),
+            })
+            .await;
+        }
+        if let Some(users) = &self.users {
+            let black = self
+                .names
+                .get(&PlayerRole::Black)
+                .cloned()
+                .unwrap_or_default();
+            let white = self
+                .names
+                .get(&PlayerRole::White)
+                .cloned()
+                .unwrap_or_default();
+            let mut users = users.write().await;
+            for (player, name) in [(PlayerRole::Black, &black), (PlayerRole::White, &white)] {
+                let outcome = match winner {
+                    Some(winner) if winner == player => GameOutcome::Win,
+                    Some(_) => GameOutcome::Loss,
+                    None => GameOutcome::Draw,
+                };
+                let (move_ms, moves) = self.move_time(player);
+                users.record_stats(name, outcome, move_ms, moves);
+            }
+            if !self.is_ranked() {
+                log::info!("非排位对局，不计算等级分");
+            } else if let Some((black_rating, white_rating)) =
+                users.record_result(&black, &white, winner)
+            {
+                log::info!(
+                    "等级分更新: {} {:.0}, {} {:.0}",
+                    black,
+                    black_rating.rating,
+                    white,
+                    white_rating.rating
+                );
+            }
+        }
+        self.narrate(narrate::narrate_game_over(winner, reason))
+            .await;
+        let winning_line = self
+            .board
+            .winning_line()
+            .filter(|line| Some(line.player) == winner)
+            .map(|line| line.cells)
+            .unwrap_or_default();
+        let game_over = GameMessage::GameOver {
+            winner,
+            winning_line,
+            reason: Some(reason),
+        };
+        self.notify_players(game_over.clone()).await;
+        self.notify_spectators(game_over);
+    }
+
+    // 某位玩家本局落子的总用时（毫秒）和步数，第一手从开局算起
+    fn move_time(&self, player: PlayerRole) -> (u64, u32) {
+        let mut previous = self.started_at;
+        let mut total_ms = 0;
+        let mut moves = 0;
+        for record in &self.board.moves {
+            if record.player == player {
+                total_ms += (record.timestamp - previous).num_milliseconds().max(0) as u64;
+                moves += 1;
+            }
+            previous = record.timestamp;
+        }
+        (total_ms, moves)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // 双方都已入座、尚未结束的对局才出现在公开列表里
+    pub fn summary(&self) -> Option<GameSummary> {
+        if self.is_finished() || self.names.len() < 2 {
+            return None;
+        }
+        let name = |player| self.names.get(&player).cloned().unwrap_or_default();
+        Some(GameSummary {
+            id: self.id.clone(),
+            black: name(PlayerRole::Black),
+            white: name(PlayerRole::White),
+            status: GameStatus::Live,
+            result: None,
+            move_count: self.board.moves.len(),
+            started_at: self.started_at,
+            ended_at: None,
+            black_rating: None,
+            white_rating: None,
+        })
+    }
+
+    // 带上等级分的对局信息：只有带令牌入座的一方有，同名的游客不算
+    pub async fn rated_summary(&self) -> Option<GameSummary> {
+        let mut game = self.summary()?;
+        if let Some(users) = &self.users {
+            let users = users.read().await;
+            let rating = |player, name: &str| {
+                self.authenticated
+                    .contains(&player)
+                    .then(|| users.rating_by_name(name))
+                    .flatten()
+            };
+            game.black_rating = rating(PlayerRole::Black, &game.black);
+            game.white_rating = rating(PlayerRole::White, &game.white);
+        }
+        Some(game)
+    }
+
+    // 整盘局面，回复 Resync
+    pub fn status(&self) -> GameMessage {
+        self.board.status()
+    }
+
+    // 观战者入场，先收到对局信息和当前局面。连接交给房间的广播任务，
+    // 离开或者对局重置时从返回的通道交还；对局没在进行时原样退回
+    pub async fn add_spectator(
+        &mut self,
+        spectator: Spectator,
+    ) -> Result<(u64, oneshot::Receiver<Spectator>), Spectator> {
+        let Some(game) = self.rated_summary().await else {
+            return Err(spectator);
+        };
+        let status = self.status();
+        let id = self.next_spectator;
+        self.next_spectator += 1;
+        let (limits, quota) = (self.limits, self.quota);
+        let fanout = self
+            .spectators
+            .get_or_insert_with(|| Fanout::spawn(limits, quota));
+        let released = fanout.join(id, spectator);
+        fanout.send(id, GameMessage::Watching { game });
+        fanout.send(id, status);
+        Ok((id, released))
+    }
+
+    pub fn remove_spectator(&mut self, id: u64) {
+        if let Some(fanout) = &self.spectators {
+            fanout.leave(id);
+        }
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators
+            .as_ref()
+            .map_or(0, |fanout| fanout.spectator_count())
+    }
+
+    // 观战连接在广播任务手里，回复也经它发出，和对局消息保持顺序
+    pub fn send_to_spectator(&self, id: u64, msg: GameMessage) {
+        if let Some(fanout) = &self.spectators {
+            fanout.send(id, msg);
+        }
+    }
+
+    // 窗口从当前局面开始维护，之后的落子按顺序排在后面
+    pub fn subscribe_region(&self, id: u64, region: Option<Region>) {
+        if let Some(fanout) = &self.spectators {
+            fanout.subscribe(id, region, self.status());
+        }
+    }
+
+    // 观战消息只投递给广播任务，不等观战者，读得太慢的由广播任务断开
+    fn notify_spectators(&self, msg: GameMessage) {
+        if let Some(fanout) = &self.spectators {
+            fanout.broadcast(msg);
+        }
+    }
+
+    pub fn to_archived(&self) -> ArchivedGame {
+        let name = |player| self.names.get(&player).cloned().unwrap_or_default();
+        ArchivedGame {
+            id: self.id.clone(),
+            black: name(PlayerRole::Black),
+            white: name(PlayerRole::White),
+            winner: self.winner,
+            reason: self.reason,
+            moves: self.board.moves.clone(),
+            started_at: self.started_at,
+            ended_at: chrono::Utc::now(),
+            seed: Some(self.seed),
+            rules: self.board.rules,
+            position_hash: Some(self.board.hash()),
         }
     }
 
     async fn send_turn_notification(&self, player: PlayerRole) {
         if let Some(tx) = self.players.get(&player) {
             let _ = tx.send(GameMessage::TurnNotification { player }).await;
-            println!("通知玩家 {:?} 轮到你了", player);
+            log::debug!("通知玩家 {:?} 轮到你了", player);
         }
     }
 
-    async fn add_player(
+    // 选座位和入座在同一次调用里完成，两个同时连进来的玩家不会分到同一边。返回坐下的座位
+    pub async fn add_player(
         &mut self,
-        player: PlayerRole,
+        seat: impl Into<SeatRequest>,
         username: String,
         tx: mpsc::Sender<GameMessage>,
-    ) -> Result<(), GameError> {
-        if self.players.len() >= 2 {
-            return Err(GameError::InvalidInput("游戏已满".to_string()));
+    ) -> Result<PlayerRole, GameError> {
+        if self.player_count() >= 2 {
+            return Err(GameError::Unavailable("游戏已满".to_string()));
         }
+        let taken =
+            |role: &PlayerRole| self.players.contains_key(role) || self.paused.contains_key(role);
+        let player = match seat.into() {
+            SeatRequest::Exact(role) if taken(&role) => {
+                return Err(GameError::Unavailable(format!("{:?} 的座位已有人", role)));
+            }
+            SeatRequest::Exact(role) => role,
+            SeatRequest::Prefer(preferred) => {
+                let first = preferred.unwrap_or(PlayerRole::Black);
+                // 人数不满两人，总有一边空着
+                [first, first.other()]
+                    .into_iter()
+                    .find(|role| !taken(role))
+                    .unwrap_or(first)
+            }
+        };
+        log::debug!("分配玩家角色: {:?}", player);
 
-        // 发送当前游戏状态给新玩家
-        tx.send(GameMessage::Status {
-            board: self.board.cells,
-            current_player: self.board.current_player,
-        })
-        .await
-        .unwrap();
+        // 发送当前游戏状态给新玩家，连接已经断了就不入座
+        tx.send(self.status())
+            .await
+            .map_err(|_| GameError::Disconnected(player))?;
 
         self.players.insert(player, tx);
+        self.names.insert(player, username.clone());
+        self.owner.get_or_insert(player);
 
         // 通知其他玩家有新玩家加入
-        for (_, other_tx) in &self.players {
-            other_tx
-                .send(GameMessage::PlayerConnected {
-                    player,
-                    username: username.clone(),
-                })
-                .await
-                .unwrap();
-        }
-        println!("通知其他玩家 {} ({:?}) 已加入", username, player);
+        self.notify_players(GameMessage::PlayerConnected {
+            player,
+            username: username.clone(),
+        })
+        .await;
+        log::debug!("通知其他玩家 {} ({:?}) 已加入", username, player);
+        self.narrate(narrate::narrate_joined(player, &username))
+            .await;
 
-        // 如果这是第二个玩家，游戏开始，通知当前玩家轮到他了
-        if self.players.len() == 2 {
+        // 如果这是第二个玩家，游戏开始，通知当前玩家轮到他了；接替空座位的从暂停处接着下
+        let substitute = self.phase == GamePhase::Paused;
+        if self.players.len() == 2 && self.enter_phase(GamePhase::Playing).is_ok() {
+            self.reserved.clear();
+            if substitute {
+                log::info!("{} 接替 {:?} 的座位，对局继续", username, player);
+                self.notify_spectators(GameMessage::GameResumed { player });
+            }
+            if self.board.moves.is_empty() {
+                self.started_at = chrono::Utc::now();
+            }
+            if let Some(clock) = self.clock.as_mut() {
+                clock.start(self.board.current_player, Instant::now());
+            }
+            self.broadcast_time().await;
             self.send_turn_notification(self.board.current_player).await;
         }
 
-        Ok(())
+        Ok(player)
+    }
+
+    // 同一用户从新设备接入时换掉座位上的连接，返回旧连接的发送端
+    pub async fn replace_player(
+        &mut self,
+        player: PlayerRole,
+        tx: mpsc::Sender<GameMessage>,
+    ) -> Option<mpsc::Sender<GameMessage>> {
+        let seat = self.players.get_mut(&player)?;
+        let old = std::mem::replace(seat, tx.clone());
+        // 新设备需要重新开启朗读
+        self.narrated.remove(&player);
+        let _ = tx.send(self.status()).await;
+        self.broadcast_time().await;
+        if !self.is_finished() && self.board.current_player == player {
+            self.send_turn_notification(player).await;
+        }
+        Some(old)
+    }
+
+    // 判断这个连接是否仍然占着座位
+    pub fn is_seated(&self, player: PlayerRole, tx: &mpsc::Sender<GameMessage>) -> bool {
+        self.players
+            .get(&player)
+            .is_some_and(|seat| seat.same_channel(tx))
+    }
+
+    // 客户端提交的落子：先核对对局编号和步数，重发的同一步只回确认，不当作新的一步，
+    // 这时返回 None。六子棋一次提交的两子按顺序落下，move_seq 是第一子的步数
+    pub async fn submit_move(
+        &mut self,
+        player: PlayerRole,
+        stones: &[(usize, usize)],
+        game_id: &str,
+        move_seq: usize,
+        client_nonce: u64,
+    ) -> Result<Option<MoveOutcome>, GameError> {
+        if game_id != self.id {
+            return Err(GameError::InvalidInput(
+                "对局编号不符，对局可能已经结束".to_string(),
+            ));
+        }
+        if let Some(&seq) = self.nonces.get(&(player, client_nonce)) {
+            log::info!("玩家 {:?} 重发了第 {} 步，直接确认", player, seq);
+            self.ack_move(player, seq).await;
+            return Ok(None);
+        }
+        if move_seq != self.board.moves.len() {
+            return Err(GameError::InvalidInput(format!(
+                "步数不符：提交的是第 {} 步，当前应为第 {} 步",
+                move_seq,
+                self.board.moves.len()
+            )));
+        }
+        // 一起提交的几子先全部检查，不能落下一子之后才发现另一子不合法
+        if stones.len() > 1 {
+            if self.board.current_player == player && stones.len() > self.board.stones_left() {
+                return Err(GameError::InvalidInput(format!(
+                    "这一回合只能再下 {} 子",
+                    self.board.stones_left()
+                )));
+            }
+            for (i, &(row, col)) in stones.iter().enumerate() {
+                self.board.validate_move(row, col)?;
+                if stones[..i].contains(&(row, col)) {
+                    return Err(GameError::PositionOccupied(format!(
+                        "同一回合的两子不能下在同一位置 ({}, {})",
+                        row, col
+                    )));
+                }
+            }
+        }
+        let mut outcome = MoveOutcome::Continue;
+        for &(row, col) in stones {
+            outcome = self.make_move(player, row, col).await?;
+            if outcome != MoveOutcome::Continue {
+                break;
+            }
+        }
+        self.nonces.insert((player, client_nonce), move_seq);
+        self.ack_move(player, move_seq).await;
+        Ok(Some(outcome))
+    }
+
+    async fn ack_move(&self, player: PlayerRole, move_seq: usize) {
+        if let Some(tx) = self.players.get(&player) {
+            let _ = tx
+                .send(GameMessage::MoveAck {
+                    game_id: self.id.clone(),
+                    move_seq,
+                })
+                .await;
+        }
     }
 
-    async fn make_move(
+    pub async fn make_move(
         &mut self,
         player: PlayerRole,
         row: usize,
         col: usize,
-    ) -> Result<(), GameError> {
-        println!("处理移动请求: 玩家 {:?} 移动到 ({}, {})", player, row, col);
-
-        if self.players.len() < 2 {
-            println!("移动失败: 等待另一个玩家加入");
-            return Err(GameError::InvalidInput("等待另一个玩家加入".to_string()));
+    ) -> Result<MoveOutcome, GameError> {
+        let started = Instant::now();
+        match self.phase {
+            GamePhase::Playing => {}
+            GamePhase::Finished => {
+                return Err(GameError::InvalidInput("游戏已结束".to_string()));
+            }
+            GamePhase::Paused => {
+                return Err(GameError::InvalidInput(
+                    "对局暂停中，等待对手重连".to_string(),
+                ));
+            }
+            GamePhase::Waiting => {
+                return Err(GameError::InvalidInput("等待另一个玩家加入".to_string()));
+            }
         }
         if self.board.current_player != player {
-            println!("移动失败: 不是玩家 {:?} 的回合", player);
             return Err(GameError::InvalidInput("不是你的回合".to_string()));
         }
+        // 时钟任务可能还没来得及处理到期，这里再检查一次
+        let timed_out = self
+            .clock
+            .as_ref()
+            .is_some_and(|clock| clock.remaining(player, Instant::now()).is_zero());
+        if timed_out {
+            self.end_on_time(player).await;
+            return Err(GameError::InvalidInput("已超时".to_string()));
+        }
 
-        println!("执行移动: ({}, {})", row, col);
         if let Err(e) = self.board.make_move(row, col) {
             // 移动失败，通知当前玩家继续尝试
             self.send_turn_notification(player).await;
             return Err(e);
         }
+        let validated = Instant::now();
+        self.record_latency(MoveStage::Validate, validated - started);
+        self.draw_offer = None;
 
-        // 通知所有玩家移动和新的游戏状态
-        println!("通知所有玩家移动和新的游戏状态");
-        for (_, tx) in &self.players {
-            tx.send(GameMessage::Move { row, col }).await.unwrap();
-            tx.send(GameMessage::Status {
-                board: self.board.cells,
-                current_player: self.board.current_player,
-            })
-            .await
-            .unwrap();
+        // 通知所有玩家和观战者这一步
+        let applied = GameMessage::MoveApplied {
+            row,
+            col,
+            by: player,
+            next_player: self.board.current_player,
+            move_number: self.board.moves.len(),
+        };
+        self.notify_players(applied.clone()).await;
+        self.notify_spectators(applied);
+        if self.spectator_count() > 0 {
+            self.notify_spectators(GameMessage::Evaluation {
+                move_seq: self.board.moves.len() - 1,
+                score: evaluate(&self.board, PlayerRole::Black),
+            });
         }
+        self.narrate(narrate::narrate_move(&self.board, player, row, col))
+            .await;
+
+        // 六子棋一回合的第一子下完不换边
+        if self.board.current_player != player {
+            if let Some(clock) = self.clock.as_mut() {
+                clock.switch(Instant::now());
+            }
+        }
+        self.broadcast_time().await;
 
         // 通知下一个玩家轮到他们了
         self.send_turn_notification(self.board.current_player).await;
+        self.record_latency(MoveStage::Broadcast, validated.elapsed());
 
-        if let Some(winner) = self.board.check_winner() {
-            println!("游戏结束！胜利者是: {:?}", winner);
-            for (_, tx) in &self.players {
-                tx.send(GameMessage::GameOver {
-                    winner: Some(winner),
-                })
-                .await
-                .unwrap();
+        if let Some(line) = self.board.winning_line() {
+            self.finish(Some(line.player), GameOverReason::FiveInARow)
+                .await;
+            Ok(MoveOutcome::Win(line))
+        } else if let Some(reason) = self.board.draw_reason() {
+            self.finish(None, reason).await;
+            Ok(MoveOutcome::Draw(reason))
+        } else {
+            Ok(MoveOutcome::Continue)
+        }
+    }
+
+    // 玩家断开连接：对局进行中时保留座位并暂停，返回重连时会收到通知的通道；
+    // 否则直接移出
+    pub async fn disconnect(&mut self, player: PlayerRole) -> Option<oneshot::Receiver<()>> {
+        if self.phase != GamePhase::Playing || self.enter_phase(GamePhase::Paused).is_err() {
+            self.remove_player(player).await;
+            return None;
+        }
+        self.players.remove(&player);
+        self.narrated.remove(&player);
+        let (resumed_tx, resumed_rx) = oneshot::channel();
+        self.paused.insert(player, resumed_tx);
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
+        }
+        log::info!("玩家 {:?} 掉线，对局暂停", player);
+        self.notify_players(GameMessage::GamePaused {
+            player,
+            grace_secs: self.reconnect_grace.as_secs(),
+        })
+        .await;
+        self.notify_spectators(GameMessage::GamePaused {
+            player,
+            grace_secs: self.reconnect_grace.as_secs(),
+        });
+        self.broadcast_time().await;
+        self.narrate(narrate::narrate_left(player)).await;
+        Some(resumed_rx)
+    }
+
+    pub fn is_paused_for(&self, player: PlayerRole) -> bool {
+        self.paused.contains_key(&player)
+    }
+
+    // 掉线的玩家在宽限期内重连，对局继续
+    pub async fn resume(&mut self, player: PlayerRole, tx: mpsc::Sender<GameMessage>) {
+        let Some(resumed) = self.paused.remove(&player) else {
+            return;
+        };
+        let _ = resumed.send(());
+        let _ = tx.send(self.status()).await;
+        self.players.insert(player, tx);
+        log::info!("玩家 {:?} 已重连，对局继续", player);
+        for (&role, other_tx) in &self.players {
+            if role != player {
+                let _ = other_tx.send(GameMessage::GameResumed { player }).await;
             }
-        } else if self.board.is_full() {
-            println!("游戏结束！平局！");
-            for (_, tx) in &self.players {
-                tx.send(GameMessage::GameOver { winner: None })
-                    .await
-                    .unwrap();
+        }
+        self.notify_spectators(GameMessage::GameResumed { player });
+        if self.paused.is_empty() && self.enter_phase(GamePhase::Playing).is_ok() {
+            if let Some(clock) = self.clock.as_mut() {
+                clock.start(self.board.current_player, Instant::now());
             }
+            self.broadcast_time().await;
+            self.send_turn_notification(self.board.current_player).await;
         }
+    }
 
-        println!("移动处理完成");
+    // 房主开关替补，已经空出来的座位不受影响
+    pub fn allow_substitutes(
+        &mut self,
+        player: PlayerRole,
+        allowed: bool,
+    ) -> Result<(), GameError> {
+        if self.owner != Some(player) {
+            return Err(GameError::Unauthorized("只有房主可以设置替补".to_string()));
+        }
+        log::info!("房主 {:?} 将替补设为 {}", player, allowed);
+        self.substitutes_allowed = allowed;
         Ok(())
     }
 
-    async fn remove_player(&mut self, player: PlayerRole) {
+    // 对局中让出、可以由观战者接替的座位
+    pub fn seat_is_open(&self, role: PlayerRole) -> bool {
+        self.substitutes_allowed && self.phase.in_progress() && self.seat_is_free(role)
+    }
+
+    // 允许替补且对手还在座时，离开的玩家让出座位，对局停在原处等人接替。
+    // 这个座位之前提交的落子随机数一并作废，免得接替的人被当成重发
+    async fn vacate(&mut self, player: PlayerRole) -> bool {
+        let opponent = player.other();
+        if !self.substitutes_allowed
+            || !self.phase.in_progress()
+            || !self.players.contains_key(&opponent)
+        {
+            return false;
+        }
         self.players.remove(&player);
-        // 通知其他玩家
-        for (_, tx) in &self.players {
-            tx.send(GameMessage::PlayerDisconnected { player })
-                .await
-                .unwrap();
+        self.paused.remove(&player);
+        self.names.remove(&player);
+        self.narrated.remove(&player);
+        self.authenticated.remove(&player);
+        self.nonces.retain(|&(role, _), _| role != player);
+        // 接替的人从满额的提示开始
+        self.hints_used.remove(&player);
+        if self.phase == GamePhase::Playing {
+            let _ = self.enter_phase(GamePhase::Paused);
         }
-        // 如果所有玩家都断开，重置游戏状态
-        if self.players.is_empty() {
-            self.board = Board::new();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
         }
+        self.owner = Some(opponent);
+        log::info!("玩家 {:?} 让出座位，等待观战者接替", player);
+        let open = GameMessage::SeatOpen {
+            game_id: self.id.clone(),
+            role: player,
+        };
+        self.notify_players(open.clone()).await;
+        self.notify_spectators(open);
+        self.broadcast_time().await;
+        self.narrate(narrate::narrate_left(player)).await;
+        true
     }
-    pub async fn shutdown(&mut self) {
-        println!("服务器正在关闭...");
-        // 通知所有玩家服务器关闭
-        for (_, tx) in &self.players {
-            let _ = tx.send(GameMessage::ServerShutdown).await;
+
+    // 玩家认输，对方获胜
+    pub async fn resign(&mut self, player: PlayerRole) -> Result<(), GameError> {
+        if !self.phase.in_progress() {
+            return Err(GameError::InvalidMove(
+                "对局没有在进行，不能认输".to_string(),
+            ));
         }
+        log::info!("玩家 {:?} 认输", player);
+        self.finish(Some(player.other()), GameOverReason::Resignation)
+            .await;
+        Ok(())
     }
 
-    pub fn get_player_role(&self) -> Option<PlayerRole> {
-        if self.players.len() >= 2 {
-            println!("游戏已满，拒绝连接");
-            return None;
+    // 玩家主动离开：让出座位，对局没下完时判负（允许替补时留给观战者接替），
+    // 返回是否中途弃局。对手的座位已经空着等替补时不算弃局
+    pub async fn leave(&mut self, player: PlayerRole) -> bool {
+        // 恢复的对局还没重新开始就主动离开，等于放弃这盘棋，不再给双方留座位
+        self.reserved.clear();
+        let abandoned = self.phase.in_progress() && !self.seat_is_free(player.other());
+        if abandoned && self.vacate(player).await {
+            return true;
         }
-        if self.players.len() == 0 {
-            println!("分配玩家角色: Black");
-            Some(PlayerRole::Black)
-        } else {
-            println!("分配玩家角色: White");
-            Some(self.players.keys().next().unwrap().other())
+        if abandoned {
+            log::info!("玩家 {:?} 中途离开，判负", player);
+            self.finish(Some(player.other()), GameOverReason::Disconnect)
+                .await;
         }
+        self.remove_player(player).await;
+        abandoned
     }
-    // })
-}
 
-pub struct NetworkPlayer {
-    stream: TcpStream,
-    game: Arc<Mutex<Game>>,
-    user_manager: Arc<Mutex<UserManager>>,
-}
-impl NetworkPlayer {
-    pub fn new(
-        stream: TcpStream,
-        game: Arc<Mutex<Game>>,
-        user_manager: Arc<Mutex<UserManager>>,
-    ) -> Self {
-        Self {
-            stream,
-            game,
-            user_manager,
-        }
-    }
-    pub async fn play(self) {
-        let ws_stream = accept_async(self.stream).await.unwrap();
-        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
-
-        let (tx, mut rx) = mpsc::channel(32);
-
-        // 等待客户端发送用户名
-        let username = match ws_receiver.next().await {
-            Some(Ok(Message::Text(text))) => {
-                println!("收到连接消息: {}", text);
-                match serde_json::from_str::<GameMessage>(&text) {
-                    Ok(GameMessage::ConnectRequest { username }) => {
-                        println!("新玩家 {} 正在连接...", username);
-                        username
-                    }
-                    Ok(_) => {
-                        println!("无效的连接消息类型");
-                        let _ = ws_sender
-                            .send(Message::Text(
-                                serde_json::to_string(&GameMessage::Error(
-                                    "无效的连接消息类型".to_string(),
-                                ))
-                                .unwrap(),
-                            ))
-                            .await;
-                        return;
-                    }
-                    Err(e) => {
-                        println!("解析连接消息失败: {}", e);
-                        let _ = ws_sender
-                            .send(Message::Text(
-                                serde_json::to_string(&GameMessage::Error(
-                                    "解析连接消息失败".to_string(),
-                                ))
-                                .unwrap(),
-                            ))
-                            .await;
-                        return;
-                    }
-                }
-            }
-            _ => {
-                println!("连接失败：无法读取用户名");
-                let _ = ws_sender
-                    .send(Message::Text(
-                        serde_json::to_string(&GameMessage::Error("连接失败".to_string())).unwrap(),
-                    ))
-                    .await;
-                return;
-            }
-        };
+    // 宽限期内没有重连，判负并让出座位
+    pub async fn forfeit(&mut self, player: PlayerRole) {
+        if self.paused.remove(&player).is_none() {
+            return;
+        }
+        self.authenticated.remove(&player);
+        if self.vacate(player).await {
+            return;
+        }
+        log::info!("玩家 {:?} 未在宽限期内重连，判负", player);
+        self.finish(Some(player.other()), GameOverReason::Disconnect)
+            .await;
+        if self.players.is_empty() {
+            self.reset();
+        }
+    }
 
-        // 创建用户
-        let user = {
-            let mut user_manager = self.user_manager.lock().await;
-            let user = user_manager.create_user(username.clone());
-            println!("创建用户: {:?}", user);
-            user
-        };
+    fn reset(&mut self) {
+        self.board = Board::with_rules(self.board.rules);
+        self.clock = self.time_control.map(Clock::new);
+        // 回到 Waiting 总是允许的
+        let _ = self.enter_phase(GamePhase::Waiting);
+        self.winner = None;
+        self.names.clear();
+        self.paused.clear();
+        self.nonces.clear();
+        self.hints_used.clear();
+        self.reserved.clear();
+        self.owner = None;
+        self.substitutes_allowed = false;
+        self.draw_offer = None;
+        self.reason = None;
+        self.events.clear();
+        // 观战的是上一盘，观战者拿回连接后回到列表
+        if let Some(fanout) = &self.spectators {
+            fanout.release_all();
+        }
+        self.id = uuid::Uuid::new_v4().to_string();
+        self.seed = rand::random();
+    }
 
-        // 获取当前游戏状态
-        let mut game_guard = self.game.lock().await;
-        let player = game_guard.get_player_role();
-        if player.is_none() {
-            println!("游戏已满，拒绝连接");
-            let _ = ws_sender
-                .send(Message::Text(
-                    serde_json::to_string(&GameMessage::Error("游戏已满".to_string())).unwrap(),
-                ))
-                .await;
-            return;
+    pub async fn remove_player(&mut self, player: PlayerRole) {
+        self.players.remove(&player);
+        self.narrated.remove(&player);
+        // 恢复的对局还没重新开始，座位连同认证一起留着
+        if !self.reserved.contains_key(&player) {
+            self.authenticated.remove(&player);
         }
-        let player = player.unwrap();
-        // 分配玩家角色给用户
-        {
-            let mut user_manager = self.user_manager.lock().await;
-            if let Err(e) = user_manager.assign_player(&user.id, player) {
-                println!("分配玩家角色失败: {}", e);
-                let _ = ws_sender
-                    .send(Message::Text(
-                        serde_json::to_string(&GameMessage::Error(e.to_string())).unwrap(),
-                    ))
-                    .await;
-                return;
-            }
-            println!("成功分配玩家角色: {:?} 给用户 {}", player, user.name);
+        // 对局无法继续，暂停计时，等新的玩家坐下再接着下
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
+        }
+        if self.phase.in_progress() {
+            let _ = self.enter_phase(GamePhase::Waiting);
         }
+        // 通知其他玩家
+        self.notify_players(GameMessage::PlayerDisconnected { player })
+            .await;
+        self.narrate(narrate::narrate_left(player)).await;
+        // 如果所有玩家都断开，重置游戏状态，等待重连的玩家也不再等待；
+        // 恢复的对局还没重新开始时继续给双方留着
+        if self.players.is_empty() && self.reserved.is_empty() {
+            self.reset();
+        }
+    }
 
-        // 添加玩家到游戏
-        if let Err(e) = game_guard
-            .add_player(player, username.clone(), tx.clone())
-            .await
-        {
-            println!("添加玩家到游戏失败: {}", e);
-            let _ = ws_sender
-                .send(Message::Text(
-                    serde_json::to_string(&GameMessage::Error(e.to_string())).unwrap(),
-                ))
+    // 管理员踢人：对局没下完时判负。被踢的连接收到通知后关闭，座位按正常断开的流程让出
+    pub async fn kick(&mut self, username: &str, reason: &str) -> Option<PlayerRole> {
+        let player = self
+            .names
+            .iter()
+            .find(|(role, name)| {
+                name.as_str() == username
+                    && (self.players.contains_key(role) || self.paused.contains_key(role))
+            })
+            .map(|(&role, _)| role)?;
+        if self.phase.in_progress() {
+            log::info!("玩家 {:?} 被管理员移出，判负", player);
+            self.finish(Some(player.other()), GameOverReason::Disconnect)
                 .await;
-            return;
         }
-        println!("成功添加玩家 {} ({:?}) 到游戏", user.name, player);
-
-        // 发送连接成功消息
-        let _ = ws_sender
-            .send(Message::Text(
-                serde_json::to_string(&GameMessage::ConnectResponse {
-                    username: user.name.clone(),
-                    player_role: player,
+        if let Some(tx) = self.players.get(&player) {
+            let _ = tx
+                .send(GameMessage::Kicked {
+                    reason: reason.to_string(),
                 })
-                .unwrap(),
-            ))
-            .await;
-        println!("发送连接成功消息给玩家 {}", user.name);
-
-        drop(game_guard); // 释放锁
-
-        // 处理游戏消息
-        let game_clone = self.game.clone();
-        let user_manager_clone = self.user_manager.clone();
-        let username_clone = username.clone(); // 克隆 username 用于消息处理
-        tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                println!("发送消息给玩家 {}: {:?}", username_clone, msg);
-                let _ = ws_sender
-                    .send(Message::Text(serde_json::to_string(&msg).unwrap()))
-                    .await;
-            }
-        });
+                .await;
+        }
+        Some(player)
+    }
 
-        // 接收玩家移动
-        while let Some(Ok(msg)) = ws_receiver.next().await {
-            if let Message::Text(text) = msg {
-                println!("收到玩家 {} 的消息: {}", username, text);
-                if let Ok(GameMessage::Move { row, col }) = serde_json::from_str(&text) {
-                    println!(
-                        "玩家 {} ({:?}) 尝试移动: ({}, {})",
-                        username, player, row, col
-                    );
-                    let mut game = game_clone.lock().await;
-                    if let Err(e) = game.make_move(player, row, col).await {
-                        println!("移动失败: {}", e);
-                        tx.send(GameMessage::Error(e.to_string())).await.unwrap();
-                    } else {
-                        println!("移动成功: ({}, {})", row, col);
-                    }
-                }
+    // 发给双方和所有观战者，返回收件人数
+    pub async fn notify_all(&mut self, msg: GameMessage) -> usize {
+        self.notify_players(msg.clone()).await;
+        self.notify_spectators(msg);
+        self.players.len() + self.spectator_count()
+    }
+
+    // 通知所有玩家和观战者服务器关闭，返回观战广播任务，发完后结束
+    pub async fn shutdown(&mut self) -> Option<JoinHandle<()>> {
+        log::info!("服务器正在关闭...");
+        self.notify_players(GameMessage::ServerShutdown).await;
+        self.notify_spectators(GameMessage::ServerShutdown);
+        self.spectators.take().map(Fanout::close)
+    }
+
+    // 把玩家状态转发给对手，对局未开始时不转发
+    pub async fn relay_presence(&self, player: PlayerRole, state: PresenceState) {
+        if self.players.len() < 2 {
+            return;
+        }
+        for (&role, tx) in &self.players {
+            if role != player {
+                let _ = tx.send(GameMessage::Presence { player, state }).await;
             }
         }
+    }
 
-        // 处理断开连接
-        {
-            println!("玩家 {} ({:?}) 断开连接", user.name, player);
-            let mut game = game_clone.lock().await;
-            game.remove_player(player).await;
-            let mut user_manager = user_manager_clone.lock().await;
-            user_manager.remove_user(&user.id);
+    // 把聊天转发给双方和所有观战者，空话和太长的话直接拒绝
+    pub async fn relay_chat(&mut self, from: &str, text: &str) -> Result<(), GameError> {
+        let text = text.trim();
+        if text.is_empty() || text.chars().count() > MAX_CHAT_CHARS {
+            return Err(GameError::InvalidInput(format!(
+                "聊天内容不能为空，最多 {} 个字",
+                MAX_CHAT_CHARS
+            )));
         }
+        let now = Instant::now();
+        let (left, settled) = self
+            .chat_budget
+            .entry(from.to_string())
+            .or_insert((CHAT_BURST as f64, now));
+        let refilled = now.duration_since(*settled).as_secs_f64() / CHAT_REFILL.as_secs_f64();
+        *left = (*left + refilled).min(CHAT_BURST as f64);
+        *settled = now;
+        if *left < 1.0 {
+            return Err(GameError::Unavailable("发言太频繁，请稍后再说".to_string()));
+        }
+        *left -= 1.0;
+        self.notify_all(GameMessage::Chat {
+            from: from.to_string(),
+            text: text.to_string(),
+        })
+        .await;
+        Ok(())
     }
+
+    // 对局还没结束，这个颜色也没有人坐（包括掉线等待重连的）
+    pub fn seat_is_free(&self, player: PlayerRole) -> bool {
+        !self.is_finished()
+            && !self.players.contains_key(&player)
+            && !self.paused.contains_key(&player)
+    }
+
+    // })
 }
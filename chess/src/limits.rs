@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+
+use crate::{GameError, GameMessage};
+
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 16 * 1024;
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 16;
+pub const DEFAULT_MAX_USERNAME_CHARS: usize = 32;
+pub const DEFAULT_MAX_OUTBOUND_FRAME_BYTES: usize = 32 * 1024;
+// 分段消息外层 JSON 的长度余量
+const CHUNK_OVERHEAD: usize = 128;
+// 一条消息最多拆成这么多段，收到更多的直接丢弃
+const MAX_CHUNKS: usize = 4096;
+
+// 区分不同分段消息的编号
+static NEXT_CHUNK_ID: AtomicU64 = AtomicU64::new(1);
+
+// 收发消息的大小限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageLimits {
+    // 收到的单条消息最大字节数，超过的在 WebSocket 层直接拒绝
+    pub max_frame_bytes: usize,
+    // 收到的 JSON 最多嵌套这么多层
+    pub max_json_depth: usize,
+    pub max_username_chars: usize,
+    // 发出的单帧最大字节数，更大的消息（回放、棋谱、对局列表）拆成 Chunk 分段发送
+    pub max_outbound_frame_bytes: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_username_chars: DEFAULT_MAX_USERNAME_CHARS,
+            max_outbound_frame_bytes: DEFAULT_MAX_OUTBOUND_FRAME_BYTES,
+        }
+    }
+}
+
+impl MessageLimits {
+    #[cfg(feature = "server")]
+    pub fn websocket_config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: Some(self.max_frame_bytes),
+            max_frame_size: Some(self.max_frame_bytes),
+            ..WebSocketConfig::default()
+        }
+    }
+
+    // 解析客户端消息：先查长度和嵌套层数再交给 serde，超长的用户名也在这里拒绝
+    pub fn decode(&self, text: &str) -> Result<GameMessage, GameError> {
+        if text.len() > self.max_frame_bytes {
+            return Err(GameError::Protocol(format!(
+                "消息超过 {} 字节",
+                self.max_frame_bytes
+            )));
+        }
+        if json_depth(text) > self.max_json_depth {
+            return Err(GameError::Protocol(format!(
+                "消息嵌套超过 {} 层",
+                self.max_json_depth
+            )));
+        }
+        let msg: GameMessage = serde_json::from_str(text)
+            .map_err(|e| GameError::Protocol(format!("解析消息失败: {}", e)))?;
+        match &msg {
+            GameMessage::ConnectRequest { username, .. }
+            | GameMessage::Register { username, .. }
+            | GameMessage::Login { username, .. }
+                if username.chars().count() > self.max_username_chars =>
+            {
+                Err(GameError::InvalidInput(format!(
+                    "用户名不能超过 {} 个字符",
+                    self.max_username_chars
+                )))
+            }
+            _ => Ok(msg),
+        }
+    }
+
+    // 序列化要发出的消息，超过单帧上限时拆成多个 Chunk
+    pub fn encode(&self, msg: &GameMessage) -> Vec<String> {
+        let json = serde_json::to_string(msg).unwrap();
+        if json.len() <= self.max_outbound_frame_bytes {
+            return vec![json];
+        }
+        let encoded = URL_SAFE_NO_PAD.encode(json);
+        let size = self
+            .max_outbound_frame_bytes
+            .saturating_sub(CHUNK_OVERHEAD)
+            .max(1);
+        let id = NEXT_CHUNK_ID.fetch_add(1, Ordering::Relaxed);
+        let total = encoded.len().div_ceil(size);
+        // base64 只有 ASCII，按字节切分不会切断字符
+        (0..total)
+            .map(|index| {
+                let end = ((index + 1) * size).min(encoded.len());
+                let chunk = GameMessage::Chunk {
+                    id,
+                    index,
+                    total,
+                    data: encoded[index * size..end].to_string(),
+                };
+                serde_json::to_string(&chunk).unwrap()
+            })
+            .collect()
+    }
+}
+
+// 数一下 JSON 最深嵌套了几层对象和数组，字符串里的括号不算
+fn json_depth(text: &str) -> usize {
+    let (mut depth, mut max_depth) = (0usize, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in text.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max_depth
+}
+
+// 客户端把 Chunk 分段拼回原来的消息
+#[derive(Debug, Default)]
+pub struct ChunkAssembler {
+    pending: HashMap<u64, Vec<Option<String>>>,
+}
+
+impl ChunkAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 收齐最后一段时返回拼好的消息，还没收齐时返回 None
+    pub fn push(
+        &mut self,
+        id: u64,
+        index: usize,
+        total: usize,
+        data: String,
+    ) -> Option<Result<GameMessage, String>> {
+        if total > MAX_CHUNKS {
+            return Some(Err(format!("分段数 {} 超过上限", total)));
+        }
+        let parts = self.pending.entry(id).or_insert_with(|| vec![None; total]);
+        if index >= parts.len() {
+            self.pending.remove(&id);
+            return Some(Err(format!("分段编号 {} 超出范围", index)));
+        }
+        parts[index] = Some(data);
+        if parts.iter().any(Option::is_none) {
+            return None;
+        }
+        let encoded: String = self.pending.remove(&id)?.into_iter().flatten().collect();
+        let decoded = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|e| format!("分段消息解码失败: {}", e))
+            .and_then(|bytes| {
+                serde_json::from_slice(&bytes).map_err(|e| format!("分段消息解析失败: {}", e))
+            });
+        Some(decoded)
+    }
+}
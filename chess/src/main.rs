@@ -1,35 +1,281 @@
-use chess::{Game, NetworkPlayer, UserManager};
+use chess::{
+    admin, browser, graceful_shutdown, read_ratings_csv, shared, write_ratings_csv, Backup, Cipher,
+    CrashDump, GameArchive, MemoryStore, NetworkPlayer, RoomManager, ServerConfig, SharedStore,
+    SqliteStore, UserManager,
+};
 
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::signal;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, RwLock};
+
+// 对局里的事件走 log，和服务器其他输出一样打到标准输出，逐步的提示（debug）不打
+struct StdoutLogger;
+
+impl log::Log for StdoutLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            println!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+// 根据配置打开存储后端，配置了数据库但打不开时直接退出
+fn open_store(config: &ServerConfig) -> SharedStore {
+    match &config.database_path {
+        Some(path) => match SqliteStore::open(path) {
+            Ok(store) => {
+                println!("使用数据库 {}", path);
+                match config.storage_key() {
+                    Some(key) => {
+                        let cipher = Cipher::from_base64(&key)
+                            .unwrap_or_else(|e| panic!("存储密钥无效: {}", e));
+                        println!("数据库已启用加密");
+                        shared(store.with_cipher(cipher))
+                    }
+                    None => shared(store),
+                }
+            }
+            Err(e) => panic!("打开数据库 {} 失败: {}", path, e),
+        },
+        None => {
+            println!("未配置数据库，数据只保存在内存中");
+            shared(MemoryStore::new())
+        }
+    }
+}
+
+// 运维子命令：备份恢复、导入导出等级分
+fn run_command(config: &ServerConfig, args: &[String]) -> Result<(), String> {
+    if config.database_path.is_none() {
+        return Err("未配置 database_path，没有可以操作的数据".to_string());
+    }
+    let store = open_store(config);
+    match args {
+        [cmd, path] if cmd == "import-ratings" => {
+            let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+            let records = read_ratings_csv(file)?;
+            let count = UserManager::with_store(store).import_ratings(&records);
+            println!("已从 {} 导入 {} 条等级分", path, count);
+            Ok(())
+        }
+        [cmd, path] if cmd == "export-ratings" => {
+            let records = UserManager::with_store(store).export_ratings();
+            let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+            write_ratings_csv(file, &records)?;
+            println!("已导出 {} 条等级分到 {}", records.len(), path);
+            Ok(())
+        }
+        [cmd, path] if cmd == "backup" => {
+            let backup = Backup::create(&*store.lock().unwrap()).map_err(|e| e.to_string())?;
+            backup.write_to(path).map_err(|e| e.to_string())?;
+            println!(
+                "已备份 {} 个用户、{} 条等级分、{} 盘对局到 {}",
+                backup.data.users.len(),
+                backup.data.ratings.len(),
+                backup.data.games.len(),
+                path
+            );
+            Ok(())
+        }
+        [cmd, path, rest @ ..] if cmd == "restore" && rest.iter().all(|arg| arg == "--force") => {
+            let backup = Backup::read_from(path).map_err(|e| e.to_string())?;
+            backup
+                .restore(&mut *store.lock().unwrap(), !rest.is_empty())
+                .map_err(|e| e.to_string())?;
+            println!(
+                "已从 {} 恢复 (备份时间 {})",
+                path,
+                backup.created_at.to_rfc3339()
+            );
+            Ok(())
+        }
+        // 密码从标准输入读一行，不出现在命令行和 shell 历史里
+        [cmd, name] if cmd == "create-admin" => {
+            let mut password = String::new();
+            std::io::stdin()
+                .read_line(&mut password)
+                .map_err(|e| e.to_string())?;
+            let mut users = UserManager::with_store(store);
+            users.set_admins(config.admins.clone());
+            users
+                .register_admin(name, password.trim_end_matches(['\r', '\n']))
+                .map_err(|e| e.to_string())?;
+            println!("已创建管理员账号 {}", name);
+            Ok(())
+        }
+        _ => Err(
+            "用法: chess_server [backup <文件> | restore <文件> [--force] \
+                  | import-ratings <CSV> | export-ratings <CSV> | create-admin <用户名>]"
+                .to_string(),
+        ),
+    }
+}
+
+// 写崩溃转储，写不出来也只打印，不影响退出流程
+fn write_dump(dump: &CrashDump, dir: &str) {
+    match dump.write_to(dir) {
+        Ok(path) => println!(
+            "已把 {} 个房间的状态写到 {}",
+            dump.rooms.len(),
+            path.display()
+        ),
+        Err(e) => println!("写崩溃转储到 {} 失败: {}", dir, e),
+    }
+}
+
+// Ctrl+C 或者 SIGTERM（例如停止容器、systemd 停服）
+async fn wait_for_termination() -> &'static str {
+    #[cfg(unix)]
+    {
+        use signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = signal::ctrl_c() => "收到 Ctrl+C",
+            _ = terminate.recv() => "收到 SIGTERM",
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        signal::ctrl_c().await.unwrap();
+        "收到 Ctrl+C"
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+    let config = ServerConfig::load();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = run_command(&config, &args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let listener = TcpListener::bind("127.0.0.1:8080").await.unwrap();
     println!("服务器启动在 127.0.0.1:8080");
 
-    let game = Arc::new(Mutex::new(Game::new()));
-    let user_manager = Arc::new(Mutex::new(UserManager::new()));
+    let store = open_store(&config);
+    let archive = Arc::new(Mutex::new(GameArchive::with_store(store.clone())));
+    let mut users = UserManager::with_store(store.clone());
+    users.set_admins(config.admins.clone());
+    users.set_play_limits(config.max_games_per_user, config.abandon_policy.clone());
+    match config.token_secret() {
+        Some(secret) => users.set_token_secret(secret.as_bytes()),
+        None => println!("未配置令牌密钥，重启后需要重新登录"),
+    }
+    // 每个连接都要查用户表，大多是只读的查询，用读写锁让它们并发
+    let user_manager = Arc::new(RwLock::new(users));
+    println!("最多同时进行 {} 盘对局", config.max_rooms);
+    let browser_addr = config.browser_addr.clone();
+    let admin_addr = config.admin_addr.clone();
+    let dump_dir = config.dump_dir.clone();
+    let shutdown_deadline = config.shutdown_deadline();
+    let mut room_manager = RoomManager::new(config, archive.clone(), user_manager.clone());
+    let adjourned = store.lock().unwrap().load_adjourned();
+    match adjourned {
+        Ok(records) => room_manager.load_adjourned(records),
+        Err(e) => println!("读取未完成的对局失败: {}", e),
+    }
+    let rooms = Arc::new(Mutex::new(room_manager));
 
-    // 处理 Ctrl+C 信号
-    let game_clone = game.clone();
+    if let Some(addr) = browser_addr {
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                println!("对局列表接口启动在 http://{}/games", addr);
+                tokio::spawn(browser::serve(
+                    listener,
+                    rooms.clone(),
+                    archive.clone(),
+                    user_manager.clone(),
+                ));
+            }
+            Err(e) => println!("对局列表接口监听 {} 失败: {}", addr, e),
+        }
+    }
+
+    if let Some(addr) = admin_addr {
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                println!("管理通道启动在 ws://{}", addr);
+                tokio::spawn(admin::serve(listener, rooms.clone(), user_manager.clone()));
+            }
+            Err(e) => println!("管理通道监听 {} 失败: {}", addr, e),
+        }
+    }
+
+    let telemetry = rooms.lock().await.telemetry();
+    if telemetry.is_enabled() {
+        println!("已开启匿名使用统计，只上报对局数、游戏类型和平均步数");
+    }
+    tokio::spawn(async move { telemetry.run().await });
+
+    // 服务器时钟任务：驱动所有房间的倒计时提醒和超时判负
+    let rooms_clone = rooms.clone();
     tokio::spawn(async move {
-        signal::ctrl_c().await.unwrap();
-        game_clone.lock().await.shutdown().await;
-        // 等待一小段时间确保消息被发送
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        std::process::exit(0);
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(200));
+        loop {
+            interval.tick().await;
+            let games = rooms_clone.lock().await.games();
+            for game in games {
+                game.lock().await.tick_clock().await;
+            }
+        }
     });
 
-    while let Ok((stream, _)) = listener.accept().await {
-        let game = game.clone();
-        let user_manager = user_manager.clone();
+    // 任务 panic 时写一份转储。运行时会接住 panic，服务器不一定退出，但出错时的局面留下来了；
+    // 出错的任务可能还拿着房间锁，读不到的房间只记编号
+    if let Some(dir) = dump_dir.clone() {
+        let rooms = rooms.clone();
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            write_dump(&CrashDump::try_capture(&rooms, &info.to_string()), &dir);
+            default_hook(info);
+        }));
+    }
+
+    // 收到 Ctrl+C 或 SIGTERM 之前一直接受新连接
+    let termination = wait_for_termination();
+    tokio::pin!(termination);
+    let reason = loop {
+        tokio::select! {
+            reason = &mut termination => break reason,
+            accepted = listener.accept() => {
+                let Ok((stream, _)) = accepted else {
+                    return;
+                };
+                let rooms = rooms.clone();
+                let user_manager = user_manager.clone();
+                let archive = archive.clone();
+
+                tokio::spawn(async move {
+                    let network_player = NetworkPlayer::new(stream, rooms, user_manager, archive);
+                    network_player.play().await;
+                });
+            }
+        }
+    };
 
-        tokio::spawn(async move {
-            let network_player = NetworkPlayer::new(stream, game, user_manager);
-            network_player.play().await;
-        });
+    // 先停止接受新连接，写转储，再等已有的连接把消息发完、存下没下完的对局
+    drop(listener);
+    println!("{}，准备退出", reason);
+    if let Some(dir) = &dump_dir {
+        write_dump(&CrashDump::capture(&rooms, reason).await, dir);
     }
+    let report = graceful_shutdown(&rooms, &store, shutdown_deadline).await;
+    println!("已保存 {} 盘未完成的对局，服务器退出", report.adjourned);
+    std::process::exit(0);
 }
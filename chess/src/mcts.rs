@@ -0,0 +1,229 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::ai::is_win;
+use crate::movegen;
+use crate::{solve_vcf, Board, Budget, Engine, PlayerRole, RulesConfig};
+
+type Grid = [[Option<PlayerRole>; 15]; 15];
+
+// 每个节点只展开启发分最高的这些候选点
+const MAX_CHILDREN: usize = 8;
+// 模拟对局最多走这么多步，走完还没分出胜负按和棋算
+const MAX_ROLLOUT_PLIES: usize = 60;
+// UCT 公式里的探索系数
+const EXPLORATION: f64 = 1.4;
+
+struct Node {
+    // 走到这个节点的落子，根节点没有
+    mv: Option<(usize, usize)>,
+    // 走这一步的一方，胜率都站在这一方统计
+    mover: PlayerRole,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<(usize, usize)>,
+    visits: u32,
+    wins: f64,
+    // 这一步已经连成五子
+    terminal: bool,
+}
+
+// 蒙特卡洛树搜索：按 UCT 选择节点，每次展开一个候选点，随机下完一盘后回传结果。
+// 模拟不看棋型，所以先处理一步成五和必须堵的点。
+// 带种子时随机数由种子和局面决定，同一局面总是走同一步
+#[derive(Default)]
+pub struct MctsEngine {
+    seed: Option<u64>,
+}
+
+impl MctsEngine {
+    pub fn seeded(seed: u64) -> Self {
+        Self { seed: Some(seed) }
+    }
+}
+
+impl Engine for MctsEngine {
+    fn choose_move(
+        &self,
+        board: &Board,
+        player: PlayerRole,
+        budget: Budget,
+    ) -> Option<(usize, usize)> {
+        if let Some(line) = solve_vcf(board, player, 0) {
+            return Some(line[0]);
+        }
+        if let Some(line) = solve_vcf(board, player.other(), 0) {
+            return Some(line[0]);
+        }
+        let mut tree = Tree::new(board.cells, board.rules, player);
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ board.hash()),
+            None => StdRng::from_entropy(),
+        };
+        for _ in 0..budget.playouts.max(1) {
+            tree.playout(&mut rng);
+        }
+        tree.best_move()
+    }
+}
+
+struct Tree {
+    root_cells: Grid,
+    rules: RulesConfig,
+    nodes: Vec<Node>,
+}
+
+impl Tree {
+    fn new(cells: Grid, rules: RulesConfig, player: PlayerRole) -> Self {
+        let root = Node {
+            mv: None,
+            mover: player.other(),
+            parent: None,
+            children: Vec::new(),
+            untried: untried_moves(&cells, rules, player),
+            visits: 0,
+            wins: 0.0,
+            terminal: false,
+        };
+        Self {
+            root_cells: cells,
+            rules,
+            nodes: vec![root],
+        }
+    }
+
+    // 选择、展开、模拟、回传各一次
+    fn playout(&mut self, rng: &mut impl Rng) {
+        let mut cells = self.root_cells;
+        let mut node = 0;
+        while self.nodes[node].untried.is_empty()
+            && !self.nodes[node].children.is_empty()
+            && !self.nodes[node].terminal
+        {
+            node = self.select_child(node);
+            let (row, col) = self.nodes[node].mv.unwrap_or_default();
+            cells[row][col] = Some(self.nodes[node].mover);
+        }
+
+        if !self.nodes[node].terminal {
+            if let Some((row, col)) = self.nodes[node].untried.pop() {
+                let mover = self.nodes[node].mover.other();
+                cells[row][col] = Some(mover);
+                let terminal = is_win(&cells, self.rules, row, col, mover);
+                let untried = if terminal {
+                    Vec::new()
+                } else {
+                    untried_moves(&cells, self.rules, mover.other())
+                };
+                self.nodes.push(Node {
+                    mv: Some((row, col)),
+                    mover,
+                    parent: Some(node),
+                    children: Vec::new(),
+                    untried,
+                    visits: 0,
+                    wins: 0.0,
+                    terminal,
+                });
+                let child = self.nodes.len() - 1;
+                self.nodes[node].children.push(child);
+                node = child;
+            }
+        }
+
+        let winner = if self.nodes[node].terminal {
+            Some(self.nodes[node].mover)
+        } else {
+            rollout(&mut cells, self.rules, self.nodes[node].mover.other(), rng)
+        };
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.wins += match winner {
+                Some(winner) if winner == node.mover => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = node.parent;
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let parent_visits = (self.nodes[node].visits.max(1) as f64).ln();
+        let uct = |&child: &usize| {
+            let child = &self.nodes[child];
+            let visits = child.visits.max(1) as f64;
+            child.wins / visits + EXPLORATION * (parent_visits / visits).sqrt()
+        };
+        self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by(|a, b| uct(a).total_cmp(&uct(b)))
+            .unwrap_or(node)
+    }
+
+    // 访问次数最多的一步最可靠
+    fn best_move(&self) -> Option<(usize, usize)> {
+        let root = &self.nodes[0];
+        root.children
+            .iter()
+            .max_by_key(|&&child| self.nodes[child].visits)
+            .and_then(|&child| self.nodes[child].mv)
+            .or_else(|| root.untried.last().copied())
+    }
+}
+
+// 待展开的候选点，启发分高的放在末尾先展开
+fn untried_moves(cells: &Grid, rules: RulesConfig, player: PlayerRole) -> Vec<(usize, usize)> {
+    let mut moves = movegen::candidate_moves(cells, rules, player, MAX_CHILDREN);
+    moves.reverse();
+    moves
+}
+
+// 随机下到分出胜负，落子只挑已有棋子旁边的空位
+fn rollout(
+    cells: &mut Grid,
+    rules: RulesConfig,
+    mut player: PlayerRole,
+    rng: &mut impl Rng,
+) -> Option<PlayerRole> {
+    let mut stones: Vec<(usize, usize)> = rules
+        .points()
+        .filter(|&(row, col)| cells[row][col].is_some())
+        .collect();
+    for _ in 0..MAX_ROLLOUT_PLIES {
+        let (row, col) = random_neighbour(cells, rules, &stones, rng)?;
+        cells[row][col] = Some(player);
+        if is_win(cells, rules, row, col, player) {
+            return Some(player);
+        }
+        stones.push((row, col));
+        player = player.other();
+    }
+    None
+}
+
+fn random_neighbour(
+    cells: &Grid,
+    rules: RulesConfig,
+    stones: &[(usize, usize)],
+    rng: &mut impl Rng,
+) -> Option<(usize, usize)> {
+    for _ in 0..32 {
+        let &(row, col) = stones.choose(rng)?;
+        let r = row as i32 + rng.gen_range(-1..=1);
+        let c = col as i32 + rng.gen_range(-1..=1);
+        if rules.contains(r, c) && cells[r as usize][c as usize].is_none() {
+            return Some((r as usize, c as usize));
+        }
+    }
+    // 附近很难找到空位时退回到整个棋盘
+    let empty: Vec<(usize, usize)> = rules
+        .points()
+        .filter(|&(row, col)| cells[row][col].is_none())
+        .collect();
+    empty.choose(rng).copied()
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Violation;
+
+// 直方图各个桶的上限（微秒），超过最后一个的计入溢出桶
+const BUCKET_BOUNDS_MICROS: [u64; 12] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+
+// 服务器处理一步棋的各个阶段，total 从收到消息算到通知发出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveStage {
+    // 解析消息
+    Parse,
+    // 等对局锁，锁竞争变严重时先体现在这里
+    LockWait,
+    // 检查轮次、时钟和落子规则
+    Validate,
+    // 把这一步投递给双方和观战者
+    Broadcast,
+    Total,
+}
+
+// counts 比 bounds_micros 多一个溢出桶，各桶单独计数，不累加
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub bounds_micros: Vec<u64>,
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum_micros: u64,
+    pub max_micros: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            bounds_micros: BUCKET_BOUNDS_MICROS.to_vec(),
+            counts: vec![0; BUCKET_BOUNDS_MICROS.len() + 1],
+            count: 0,
+            sum_micros: 0,
+            max_micros: 0,
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        let micros = elapsed.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = self.bounds_micros.partition_point(|&bound| bound < micros);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+}
+
+// 落子延迟和协议违规的统计，从服务器启动起累计，对局列表接口的 /metrics 导出
+#[derive(Default)]
+pub struct Metrics {
+    moves: Mutex<BTreeMap<MoveStage, Histogram>>,
+    violations: Mutex<BTreeMap<Violation, u64>>,
+    // 严格模式下因为违规断开的连接数
+    strict_disconnects: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, stage: MoveStage, elapsed: Duration) {
+        self.moves
+            .lock()
+            .unwrap()
+            .entry(stage)
+            .or_insert_with(Histogram::new)
+            .record(elapsed);
+    }
+
+    // 还没记录过的阶段不出现
+    pub fn move_latency(&self) -> BTreeMap<MoveStage, Histogram> {
+        self.moves.lock().unwrap().clone()
+    }
+
+    pub fn record_violation(&self, violation: Violation) {
+        *self
+            .violations
+            .lock()
+            .unwrap()
+            .entry(violation)
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_strict_disconnect(&self) {
+        self.strict_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 还没发生过的违规不出现
+    pub fn violations(&self) -> BTreeMap<Violation, u64> {
+        self.violations.lock().unwrap().clone()
+    }
+
+    pub fn strict_disconnects(&self) -> u64 {
+        self.strict_disconnects.load(Ordering::Relaxed)
+    }
+}
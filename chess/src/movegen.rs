@@ -0,0 +1,145 @@
+use crate::{PlayerRole, RulesConfig};
+
+type Cells = [[Option<PlayerRole>; 15]; 15];
+
+pub const DIRECTIONS: [(i32, i32); 4] = [
+    (0, 1),  // 水平
+    (1, 0),  // 垂直
+    (1, 1),  // 对角线
+    (1, -1), // 反对角线
+];
+// 只考虑离已有棋子这么多格以内的空位
+pub const CANDIDATE_RADIUS: usize = 2;
+
+// 单个空位的启发分：靠近中心、挨着己方棋子、能连成棋型的位置分高
+pub fn score_cell(
+    cells: &Cells,
+    rules: RulesConfig,
+    row: usize,
+    col: usize,
+    player: PlayerRole,
+) -> i32 {
+    let mut score = 0;
+    let win_length = rules.win_length as i32;
+    // 位置评分：中心位置更有价值
+    let (center, _) = rules.center();
+    let center = center as i32;
+    let distance_to_center = (row as i32 - center).abs() + (col as i32 - center).abs();
+    score += (10 - distance_to_center) * 10;
+
+    // 评估周围棋子
+    let mut adjacent_own = 0;
+    let mut adjacent_opponent = 0;
+    for &(dr, dc) in &DIRECTIONS {
+        let r = row as i32 + dr;
+        let c = col as i32 + dc;
+        if rules.contains(r, c) {
+            match cells[r as usize][c as usize] {
+                Some(p) if p == player => adjacent_own += 1,
+                Some(_) => adjacent_opponent += 1,
+                None => {}
+            }
+        }
+    }
+    score += adjacent_own * 50; // 靠近自己的棋子加分
+    score -= adjacent_opponent * 30; // 靠近对手的棋子减分
+
+    for &(dr, dc) in &DIRECTIONS {
+        let mut count = 0;
+        let mut empty = 0;
+        let mut consecutive = true;
+
+        // 正向检查
+        for i in 1..win_length {
+            let r = row as i32 + dr * i;
+            let c = col as i32 + dc * i;
+            if !rules.contains(r, c) {
+                break;
+            }
+            match cells[r as usize][c as usize] {
+                Some(p) if p == player => {
+                    if consecutive {
+                        count += 1;
+                    }
+                }
+                None => {
+                    empty += 1;
+                    consecutive = true;
+                }
+                _ => {
+                    consecutive = false;
+                }
+            }
+        }
+
+        // 反向检查
+        consecutive = true;
+        for i in 1..win_length {
+            let r = row as i32 - dr * i;
+            let c = col as i32 - dc * i;
+            if !rules.contains(r, c) {
+                break;
+            }
+            match cells[r as usize][c as usize] {
+                Some(p) if p == player => {
+                    if consecutive {
+                        count += 1;
+                    }
+                }
+                None => {
+                    empty += 1;
+                    consecutive = true;
+                }
+                _ => {
+                    consecutive = false;
+                }
+            }
+        }
+
+        // 计算棋型分数，以连五为例
+        if count >= win_length - 1 {
+            score += 100000; // 必胜
+        } else if count == win_length - 2 && empty >= 1 {
+            score += 10000; // 活四
+        } else if count == win_length - 3 && empty >= 2 {
+            score += 1000; // 活三
+        }
+    }
+
+    score
+}
+
+// 候选点：已有棋子周围 CANDIDATE_RADIUS 格内的空位，按进攻加防守的启发分从高到低排序，
+// 最多返回 limit 个；空棋盘只有天元
+pub fn candidate_moves(
+    cells: &Cells,
+    rules: RulesConfig,
+    player: PlayerRole,
+    limit: usize,
+) -> Vec<(usize, usize)> {
+    let mut scored = Vec::new();
+    let mut has_stone = false;
+    let last = rules.board_size - 1;
+    for (row, col) in rules.points() {
+        if cells[row][col].is_some() {
+            has_stone = true;
+            continue;
+        }
+        let rows = row.saturating_sub(CANDIDATE_RADIUS)..=(row + CANDIDATE_RADIUS).min(last);
+        let near = rows.into_iter().any(|r| {
+            (col.saturating_sub(CANDIDATE_RADIUS)..=(col + CANDIDATE_RADIUS).min(last))
+                .any(|c| cells[r][c].is_some())
+        });
+        if near {
+            let score = score_cell(cells, rules, row, col, player)
+                + score_cell(cells, rules, row, col, player.other());
+            scored.push((score, row, col));
+        }
+    }
+    if !has_stone {
+        return vec![rules.center()];
+    }
+    scored.sort_by_key(|&(score, _, _)| std::cmp::Reverse(score));
+    scored.truncate(limit);
+    scored.into_iter().map(|(_, row, col)| (row, col)).collect()
+}
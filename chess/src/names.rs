@@ -0,0 +1,26 @@
+use rand::Rng;
+
+// 词表只收常见、中性的词，组合起来也不会冒犯人
+const ADJECTIVES: &[&str] = &[
+    "amber", "bold", "brave", "bright", "calm", "clever", "cosy", "curious", "daring", "eager",
+    "gentle", "golden", "happy", "humble", "jolly", "keen", "kind", "lively", "lucky", "merry",
+    "mighty", "nimble", "patient", "polite", "quick", "quiet", "rapid", "silver", "steady",
+    "sunny", "swift", "witty",
+];
+
+const NOUNS: &[&str] = &[
+    "badger", "beaver", "comet", "crane", "falcon", "finch", "fox", "gecko", "heron", "koala",
+    "lark", "lynx", "maple", "meteor", "otter", "owl", "panda", "pebble", "pine", "puffin",
+    "rabbit", "raven", "river", "robin", "sparrow", "squirrel", "stone", "tiger", "turtle",
+    "walrus", "willow", "wren",
+];
+
+// 形如 brave-otter-42 的用户名，数字小于 max_number
+pub fn random_username(rng: &mut impl Rng, max_number: u32) -> String {
+    format!(
+        "{}-{}-{}",
+        ADJECTIVES[rng.gen_range(0..ADJECTIVES.len())],
+        NOUNS[rng.gen_range(0..NOUNS.len())],
+        rng.gen_range(0..max_number.max(1))
+    )
+}
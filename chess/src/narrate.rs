@@ -0,0 +1,100 @@
+use crate::{Board, GameOverReason, PlayerRole};
+
+// 给纯文本客户端（IRC 桥接、短信等）用的简短描述，客户端原样转发即可
+
+fn role_name(player: PlayerRole) -> &'static str {
+    match player {
+        PlayerRole::Black => "黑方",
+        PlayerRole::White => "白方",
+    }
+}
+
+// 穿过 (row, col) 的最长连子数
+fn longest_line(board: &Board, row: usize, col: usize) -> usize {
+    let Some(player) = board.cells[row][col] else {
+        return 0;
+    };
+    let mut longest = 0;
+    for (dr, dc) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+        let mut count = 1;
+        for sign in [1, -1] {
+            let (mut r, mut c) = (row as i32, col as i32);
+            loop {
+                r += dr * sign;
+                c += dc * sign;
+                if !board.rules.contains(r, c) {
+                    break;
+                }
+                if board.cells[r as usize][c as usize] != Some(player) {
+                    break;
+                }
+                count += 1;
+            }
+        }
+        longest = longest.max(count);
+    }
+    longest
+}
+
+// 落子之后调用，board 已包含这一手
+pub fn narrate_move(board: &Board, player: PlayerRole, row: usize, col: usize) -> String {
+    let mut text = format!(
+        "第 {} 手：{}落子于 ({}, {})",
+        board.moves.len(),
+        role_name(player),
+        row,
+        col
+    );
+    let line = longest_line(board, row, col);
+    let win_length = board.rules.win_length;
+    if (3..win_length).contains(&line) {
+        text.push_str(&format!("，形成 {} 连", line));
+    }
+    if line < win_length {
+        text.push_str(&format!("，轮到{}", role_name(board.current_player)));
+    }
+    text
+}
+
+pub fn narrate_game_over(winner: Option<PlayerRole>, reason: GameOverReason) -> String {
+    let Some(winner) = winner else {
+        let why = match reason {
+            GameOverReason::DrawAgreed => "双方同意和棋",
+            GameOverReason::BoardFull => "棋盘已下满，平局",
+            GameOverReason::MoveLimit => "达到步数上限，平局",
+            GameOverReason::Blocked => "双方都已无法连成一线，平局",
+            // 这些结局总有胜者，不认识的原因也只报平局
+            GameOverReason::FiveInARow
+            | GameOverReason::Timeout
+            | GameOverReason::Resignation
+            | GameOverReason::Disconnect
+            | GameOverReason::Unknown => "平局",
+        };
+        return format!("对局结束，{}", why);
+    };
+    let how = match reason {
+        GameOverReason::FiveInARow => "连成一线",
+        GameOverReason::Timeout => "因对方超时",
+        GameOverReason::Resignation => "因对方认输",
+        GameOverReason::Disconnect => "因对方离开",
+        // 和棋的原因不会有胜者，不认识的原因不说怎么赢的
+        GameOverReason::DrawAgreed
+        | GameOverReason::BoardFull
+        | GameOverReason::MoveLimit
+        | GameOverReason::Blocked
+        | GameOverReason::Unknown => "",
+    };
+    format!("对局结束，{}{}获胜", role_name(winner), how)
+}
+
+pub fn narrate_timeout(player: PlayerRole) -> String {
+    format!("{}超时", role_name(player))
+}
+
+pub fn narrate_joined(player: PlayerRole, username: &str) -> String {
+    format!("{} 执{}加入对局", username, role_name(player))
+}
+
+pub fn narrate_left(player: PlayerRole) -> String {
+    format!("{}离开了对局", role_name(player))
+}
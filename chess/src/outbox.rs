@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use crate::GameMessage;
+
+// 积压的后台消息超过这么多条就丢掉最早的，朗读、网络状态这类消息过时了也没用
+pub const MAX_BACKGROUND_BACKLOG: usize = 256;
+// 关键消息不能丢，积压到这么多条说明客户端已经跟不上，发送任务断开连接
+pub const MAX_CRITICAL_BACKLOG: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    // 落子、局面、时钟、对局结束等，影响对局进行的消息
+    Critical,
+    // 朗读、网络状态、棋谱、分析、查询回复等，晚一点送达也没关系
+    Background,
+}
+
+impl GameMessage {
+    pub fn priority(&self) -> Priority {
+        match self {
+            // 落子和局面
+            GameMessage::ConnectResponse { .. }
+            | GameMessage::Move { .. }
+            | GameMessage::MoveAck { .. }
+            | GameMessage::MoveApplied { .. }
+            | GameMessage::Status { .. }
+            | GameMessage::TurnNotification { .. }
+            | GameMessage::Watching { .. }
+            | GameMessage::RegionUpdate { .. }
+            | GameMessage::RegionSummary { .. }
+            | GameMessage::EventsSummarized { .. }
+            | GameMessage::DemoPosition { .. }
+            // 时钟和对局的暂停、继续
+            | GameMessage::TimeUpdate { .. }
+            | GameMessage::TimeWarning { .. }
+            | GameMessage::PlayerConnected { .. }
+            | GameMessage::PlayerDisconnected { .. }
+            | GameMessage::GamePaused { .. }
+            | GameMessage::GameResumed { .. }
+            | GameMessage::SeatOpen { .. }
+            | GameMessage::DrawOffered { .. }
+            | GameMessage::DrawDeclined { .. }
+            // 对局结束，GameArchived 在 GameOver 之前发送
+            | GameMessage::GameArchived { .. }
+            | GameMessage::GameOver { .. }
+            // 错误和随后就要关闭连接的通知
+            | GameMessage::Error(_)
+            | GameMessage::ServerShutdown
+            | GameMessage::SessionTransferred
+            | GameMessage::Kicked { .. }
+            | GameMessage::ProtocolViolation { .. }
+            | GameMessage::DemoClosed { .. } => Priority::Critical,
+            // 其余的查询回复、分析结果、聊天和朗读都可以晚一点到
+            _ => Priority::Background,
+        }
+    }
+}
+
+// 每个连接的发送队列：积压时关键消息先发，同一优先级内保持原来的顺序
+#[derive(Debug, Default)]
+pub struct Outbox {
+    critical: VecDeque<GameMessage>,
+    background: VecDeque<GameMessage>,
+    // 因为积压丢掉的后台消息数
+    dropped: usize,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, msg: GameMessage) {
+        match msg.priority() {
+            Priority::Critical => self.critical.push_back(msg),
+            Priority::Background => {
+                if self.background.len() >= MAX_BACKGROUND_BACKLOG {
+                    self.background.pop_front();
+                    self.dropped += 1;
+                }
+                self.background.push_back(msg);
+            }
+        }
+    }
+
+    // 关键消息积压超过上限，这个连接应该断开
+    pub fn overflowed(&self) -> bool {
+        self.critical.len() > MAX_CRITICAL_BACKLOG
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn pop(&mut self) -> Option<GameMessage> {
+        self.critical
+            .pop_front()
+            .or_else(|| self.background.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.critical.len() + self.background.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.critical.is_empty() && self.background.is_empty()
+    }
+}
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+// 一盘棋所处的阶段。阶段只能按 can_enter 列出的方向变化，
+// 落子、认输、暂停等操作先看阶段，不再各自拿座位数和结束标志拼判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    // 座位还没坐满，或者坐满了还没开始
+    Waiting,
+    Playing,
+    // 有玩家掉线，等待重连，计时停止
+    Paused,
+    // 已分出胜负或和棋，不再接受落子
+    Finished,
+}
+
+impl GamePhase {
+    // 重置对局（回到 Waiting）任何时候都可以，其余只能按对局流程往前走
+    pub fn can_enter(self, next: GamePhase) -> bool {
+        use GamePhase::*;
+        matches!(
+            (self, next),
+            (_, Waiting)
+                | (Waiting, Playing)
+                | (Playing, Paused)
+                | (Paused, Playing)
+                | (Playing, Finished)
+                | (Paused, Finished)
+        )
+    }
+
+    // 对局已经开始还没结束，掉线等待重连的也算
+    pub fn in_progress(self) -> bool {
+        matches!(self, GamePhase::Playing | GamePhase::Paused)
+    }
+}
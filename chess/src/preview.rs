@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Board, GameError, LineDirection, PlayerRole};
+
+// 落子后穿过这一子形成的棋形，按五子棋的叫法：连 k 子获胜时「四」指差一子、「三」指差两子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreatKind {
+    Win,
+    // 两头都空，对方堵不住
+    OpenFour,
+    // 只有一头空
+    Four,
+    OpenThree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threat {
+    pub kind: ThreatKind,
+    pub direction: LineDirection,
+    // 这条线上的棋子，包括新落的一子，按方向从一端排到另一端
+    pub cells: Vec<(usize, usize)>,
+}
+
+// 试下一子的结果，不改变棋盘
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovePreview {
+    pub player: PlayerRole,
+    pub row: usize,
+    pub col: usize,
+    // 从强到弱排列
+    pub threats: Vec<Threat>,
+}
+
+impl MovePreview {
+    pub fn is_win(&self) -> bool {
+        self.threats
+            .first()
+            .is_some_and(|threat| threat.kind == ThreatKind::Win)
+    }
+}
+
+impl Board {
+    // 检查当前一方能否在 (row, col) 落子，并列出这一子形成的棋形。
+    // 只看连续的棋子，跳一格的棋形（如 X_XX）不算
+    pub fn preview_move(&self, row: usize, col: usize) -> Result<MovePreview, GameError> {
+        self.validate_move(row, col)?;
+        let player = self.current_player;
+        let stone = |r: i32, c: i32| {
+            (r, c) == (row as i32, col as i32)
+                || self.rules.contains(r, c) && self.cells[r as usize][c as usize] == Some(player)
+        };
+        let empty = |r: i32, c: i32| {
+            (r, c) != (row as i32, col as i32)
+                && self.rules.contains(r, c)
+                && self.cells[r as usize][c as usize].is_none()
+        };
+
+        let k = self.rules.win_length;
+        let mut threats = Vec::new();
+        for direction in LineDirection::ALL {
+            let (dr, dc) = direction.step();
+            let (mut r, mut c) = (row as i32, col as i32);
+            while stone(r - dr, c - dc) {
+                r -= dr;
+                c -= dc;
+            }
+            let open_start = empty(r - dr, c - dc);
+            let mut cells = Vec::new();
+            while stone(r, c) {
+                cells.push((r as usize, c as usize));
+                r += dr;
+                c += dc;
+            }
+            let open_ends = open_start as usize + empty(r, c) as usize;
+            let kind = match (cells.len(), open_ends) {
+                (len, _) if len >= k => ThreatKind::Win,
+                (len, 2) if len + 1 == k => ThreatKind::OpenFour,
+                (len, 1) if len + 1 == k => ThreatKind::Four,
+                (len, 2) if len + 2 == k => ThreatKind::OpenThree,
+                _ => continue,
+            };
+            threats.push(Threat {
+                kind,
+                direction,
+                cells,
+            });
+        }
+        threats.sort_by_key(|threat| threat.kind as u8);
+        Ok(MovePreview {
+            player,
+            row,
+            col,
+            threats,
+        })
+    }
+}
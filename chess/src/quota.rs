@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::GameMessage;
+
+// 一个房间往观战者广播的配额：每个时间窗口内最多这么多条、这么多字节，0 表示不限。
+// 超出的消息合并成一条摘要，在窗口结束时发出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomQuota {
+    pub window_ms: u64,
+    pub max_events: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for RoomQuota {
+    fn default() -> Self {
+        Self {
+            window_ms: 1_000,
+            max_events: 20,
+            max_bytes: 64 * 1024,
+        }
+    }
+}
+
+// 按房间类型分别配置。配置示例:
+// "room_quotas": { "demo": { "window_ms": 1000, "max_events": 10, "max_bytes": 32768 } }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomQuotas {
+    pub game: RoomQuota,
+    // 演示房间由讲解人随手操作，连续摆子和讲解比对局密得多
+    pub demo: RoomQuota,
+}
+
+impl Default for RoomQuotas {
+    fn default() -> Self {
+        Self {
+            game: RoomQuota::default(),
+            demo: RoomQuota {
+                max_events: 10,
+                max_bytes: 32 * 1024,
+                ..RoomQuota::default()
+            },
+        }
+    }
+}
+
+// 窗口结束时要补发的内容
+pub(crate) struct Held {
+    pub skipped: usize,
+    pub bytes: usize,
+    // 局面变过，观战者各自补一份当前局面
+    pub board_changed: bool,
+    // 每种状态消息最新的一条
+    pub latest: Vec<GameMessage>,
+}
+
+// 广播任务里的配额计数。超出配额后这个窗口里剩下的消息都先扣下，
+// 结束对局和关闭房间的消息照发，发之前先把扣下的摘要发出去
+pub(crate) struct QuotaGate {
+    quota: RoomQuota,
+    window_start: Instant,
+    events: usize,
+    bytes: usize,
+    held: Option<Held>,
+}
+
+impl QuotaGate {
+    pub fn new(quota: RoomQuota) -> Self {
+        Self {
+            quota,
+            window_start: Instant::now(),
+            events: 0,
+            bytes: 0,
+            held: None,
+        }
+    }
+
+    // 有扣下的消息时，到这个时间发摘要
+    pub fn deadline(&self) -> Instant {
+        self.window_start + Duration::from_millis(self.quota.window_ms)
+    }
+
+    pub fn is_holding(&self) -> bool {
+        self.held.is_some()
+    }
+
+    // 记一条要广播的消息，返回 true 表示这条先扣下不发
+    pub fn hold(&mut self, now: Instant, msg: &GameMessage, bytes: usize) -> bool {
+        if now >= self.deadline() {
+            self.window_start = now;
+            self.events = 0;
+            self.bytes = 0;
+        }
+        if essential(msg) {
+            return false;
+        }
+        // 窗口里的第一条总是放行，单条超过字节配额的消息不会一直发不出去
+        let over = self.held.is_some()
+            || (self.events > 0
+                && ((self.quota.max_events > 0 && self.events >= self.quota.max_events)
+                    || (self.quota.max_bytes > 0 && self.bytes + bytes > self.quota.max_bytes)));
+        if !over {
+            self.events += 1;
+            self.bytes += bytes;
+            return false;
+        }
+        let held = self.held.get_or_insert_with(|| Held {
+            skipped: 0,
+            bytes: 0,
+            board_changed: false,
+            latest: Vec::new(),
+        });
+        held.skipped += 1;
+        held.bytes += bytes;
+        match msg {
+            GameMessage::Status { .. } | GameMessage::MoveApplied { .. } => {
+                held.board_changed = true;
+            }
+            GameMessage::DemoPosition { .. }
+            | GameMessage::TimeUpdate { .. }
+            | GameMessage::Evaluation { .. } => {
+                let kind = std::mem::discriminant(msg);
+                held.latest
+                    .retain(|latest| std::mem::discriminant(latest) != kind);
+                held.latest.push(msg.clone());
+            }
+            // 讲解、描述之类的事件只计数
+            _ => {}
+        }
+        true
+    }
+
+    pub fn release(&mut self) -> Option<Held> {
+        self.held.take()
+    }
+}
+
+// 观战者不能错过的消息：对局结束、房间关闭和暂停恢复
+fn essential(msg: &GameMessage) -> bool {
+    matches!(
+        msg,
+        GameMessage::GameOver { .. }
+            | GameMessage::ServerShutdown
+            | GameMessage::DemoClosed { .. }
+            | GameMessage::GamePaused { .. }
+            | GameMessage::GameResumed { .. }
+    )
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::PlayerRole;
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+
+// 新玩家前几盘变化更快，尽快接近真实水平
+const PROVISIONAL_GAMES: u32 = 30;
+const PROVISIONAL_K: f64 = 40.0;
+const K_FACTOR: f64 = 20.0;
+
+// 其他平台导出的评分偏差（Glicko RD）：新玩家 350，稳定后约 50
+const NEW_PLAYER_DEVIATION: f64 = 350.0;
+const SETTLED_DEVIATION: f64 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            games: 0,
+        }
+    }
+}
+
+impl Rating {
+    fn k_factor(&self) -> f64 {
+        if self.games < PROVISIONAL_GAMES {
+            PROVISIONAL_K
+        } else {
+            K_FACTOR
+        }
+    }
+
+    // score: 胜 1，平 0.5，负 0
+    pub fn updated(&self, opponent: &Rating, score: f64) -> Rating {
+        Rating {
+            rating: self.rating + self.k_factor() * (score - expected_score(self, opponent)),
+            games: self.games + 1,
+        }
+    }
+
+    // 用偏差估算相当于下过多少盘，决定导入后的 K 值；超过 350 按新玩家处理
+    pub fn from_deviation(rating: f64, deviation: f64) -> Rating {
+        let settled =
+            (NEW_PLAYER_DEVIATION - deviation) / (NEW_PLAYER_DEVIATION - SETTLED_DEVIATION);
+        Rating {
+            rating,
+            games: (settled.clamp(0.0, 1.0) * PROVISIONAL_GAMES as f64).round() as u32,
+        }
+    }
+
+    pub fn deviation(&self) -> f64 {
+        let settled = self.games.min(PROVISIONAL_GAMES) as f64 / PROVISIONAL_GAMES as f64;
+        NEW_PLAYER_DEVIATION - settled * (NEW_PLAYER_DEVIATION - SETTLED_DEVIATION)
+    }
+}
+
+pub fn expected_score(player: &Rating, opponent: &Rating) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent.rating - player.rating) / 400.0))
+}
+
+// 返回对局后黑白双方的新等级分
+pub fn rate_game(black: &Rating, white: &Rating, winner: Option<PlayerRole>) -> (Rating, Rating) {
+    let black_score = match winner {
+        Some(PlayerRole::Black) => 1.0,
+        Some(PlayerRole::White) => 0.0,
+        None => 0.5,
+    };
+    (
+        black.updated(white, black_score),
+        white.updated(black, 1.0 - black_score),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub username: String,
+    pub rating: i32,
+    pub games: u32,
+}
+
+// 等级分 CSV 的一行：username,rating,deviation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RatingRecord {
+    pub username: String,
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+#[cfg(feature = "server")]
+pub fn read_ratings_csv(reader: impl std::io::Read) -> Result<Vec<RatingRecord>, String> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .enumerate()
+        .map(|(i, record)| {
+            let record: RatingRecord =
+                record.map_err(|e| format!("第 {} 行格式错误: {}", i + 2, e))?;
+            // NaN 和无穷大能解析成 f64，但写进等级分后排名和对局计算都会出错
+            if !record.rating.is_finite() || !record.deviation.is_finite() {
+                return Err(format!("第 {} 行的等级分不是有限的数", i + 2));
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+#[cfg(feature = "server")]
+pub fn write_ratings_csv(
+    writer: impl std::io::Write,
+    records: &[RatingRecord],
+) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for record in records {
+        writer.serialize(record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ArchivedGame, Board, GameOverReason, MoveRecord, PlayerRole, RulesConfig};
+
+// 存储和 Replay 消息里对局记录的格式版本。改动记录的结构时加一个版本，
+// 保留旧版本的结构和升级函数，已经存下的对局总能读出来
+pub const REPLAY_FORMAT_VERSION: u32 = 2;
+
+// 版本 1：没有 version 字段，种子、规则和校验和是后来加的，可能没有
+#[derive(Deserialize)]
+struct RecordV1 {
+    id: String,
+    black: String,
+    white: String,
+    winner: Option<PlayerRole>,
+    moves: Vec<MoveRecord>,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default)]
+    rules: RulesConfig,
+    #[serde(default)]
+    position_hash: Option<u64>,
+}
+
+// 版本 2：带上 version 和结束原因
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RecordV2 {
+    version: u32,
+    id: String,
+    black: String,
+    white: String,
+    winner: Option<PlayerRole>,
+    reason: Option<GameOverReason>,
+    moves: Vec<MoveRecord>,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+    seed: Option<u64>,
+    rules: RulesConfig,
+    position_hash: Option<u64>,
+}
+
+// 旧记录没有结束原因，能从棋盘上看出来的（连成线、和棋）补上，
+// 认输、超时和离开在棋盘上没有痕迹，留空
+fn upgrade_v1(record: RecordV1) -> RecordV2 {
+    let mut board = Board::with_rules(record.rules);
+    let stones: Vec<_> = record
+        .moves
+        .iter()
+        .map(|m| (m.player, m.row, m.col))
+        .collect();
+    // 重放失败的记录载入时会被校验挡下，这里不报错
+    let reason = match board.apply_moves(&stones) {
+        Ok(()) if board.winning_line().is_some() => Some(GameOverReason::FiveInARow),
+        Ok(()) if record.winner.is_none() => board.draw_reason(),
+        _ => None,
+    };
+    RecordV2 {
+        version: 2,
+        id: record.id,
+        black: record.black,
+        white: record.white,
+        winner: record.winner,
+        reason,
+        moves: record.moves,
+        started_at: record.started_at,
+        ended_at: record.ended_at,
+        seed: record.seed,
+        rules: record.rules,
+        position_hash: record.position_hash,
+    }
+}
+
+impl TryFrom<serde_json::Value> for ArchivedGame {
+    type Error = String;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let version = value.get("version").map_or(Some(1), |v| v.as_u64());
+        let record = match version {
+            Some(1) => upgrade_v1(serde_json::from_value(value).map_err(|e| e.to_string())?),
+            Some(2) => serde_json::from_value(value).map_err(|e| e.to_string())?,
+            _ => {
+                return Err(format!(
+                    "不支持的对局记录版本 {}，最高支持 {}",
+                    value["version"], REPLAY_FORMAT_VERSION
+                ))
+            }
+        };
+        Ok(record.into())
+    }
+}
+
+impl From<RecordV2> for ArchivedGame {
+    fn from(record: RecordV2) -> Self {
+        Self {
+            id: record.id,
+            black: record.black,
+            white: record.white,
+            winner: record.winner,
+            reason: record.reason,
+            moves: record.moves,
+            started_at: record.started_at,
+            ended_at: record.ended_at,
+            seed: record.seed,
+            rules: record.rules,
+            position_hash: record.position_hash,
+        }
+    }
+}
+
+// 总是按最新版本写出
+impl From<ArchivedGame> for RecordV2 {
+    fn from(game: ArchivedGame) -> Self {
+        Self {
+            version: REPLAY_FORMAT_VERSION,
+            id: game.id,
+            black: game.black,
+            white: game.white,
+            winner: game.winner,
+            reason: game.reason,
+            moves: game.moves,
+            started_at: game.started_at,
+            ended_at: game.ended_at,
+            seed: game.seed,
+            rules: game.rules,
+            position_hash: game.position_hash,
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{GameError, GameMessage, PlayerRole, BOARD_SIZE};
+
+type Grid = [[Option<PlayerRole>; 15]; 15];
+
+// 观战者订阅的一块棋盘窗口，小屏幕或嵌入式设备只关心这一块
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub top: usize,
+    pub left: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Region {
+    // 坐标来自客户端，先比较再相减，避免加法溢出
+    pub fn validate(&self, board_size: usize) -> Result<(), GameError> {
+        if self.rows == 0 || self.cols == 0 {
+            return Err(GameError::InvalidPosition("窗口不能为空".to_string()));
+        }
+        let fits = |start: usize, len: usize| start < board_size && len <= board_size - start;
+        if !fits(self.top, self.rows) || !fits(self.left, self.cols) {
+            return Err(GameError::InvalidPosition("窗口超出棋盘".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.top..self.top + self.rows).contains(&row)
+            && (self.left..self.left + self.cols).contains(&col)
+    }
+
+    // 窗口内的棋子，按行排列
+    pub fn crop(&self, cells: &Grid) -> Vec<Vec<Option<PlayerRole>>> {
+        cells[self.top..self.top + self.rows]
+            .iter()
+            .map(|row| row[self.left..self.left + self.cols].to_vec())
+            .collect()
+    }
+}
+
+// 窗口外的概况
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutsideSummary {
+    pub black_stones: usize,
+    pub white_stones: usize,
+    // 窗口外最近的一步
+    pub last_move: Option<(usize, usize)>,
+}
+
+// 一个观战连接上的窗口订阅：订阅了窗口时跟着转发的消息维护整盘局面，
+// 把整盘局面换成窗口内容，窗口外的落子只发概况
+#[derive(Debug, Clone)]
+pub struct RegionView {
+    region: Option<Region>,
+    cells: Grid,
+    current_player: PlayerRole,
+    move_number: usize,
+    last_move: Option<(usize, usize)>,
+    last_outside: Option<(usize, usize)>,
+}
+
+impl Default for RegionView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegionView {
+    pub fn new() -> Self {
+        Self {
+            region: None,
+            cells: [[None; 15]; 15],
+            current_player: PlayerRole::Black,
+            move_number: 0,
+            last_move: None,
+            last_outside: None,
+        }
+    }
+
+    // 订阅新窗口或取消订阅（None），返回要立即发给观战者的局面
+    pub fn subscribe(&mut self, region: Option<Region>) -> Result<GameMessage, GameError> {
+        if let Some(region) = &region {
+            region.validate(BOARD_SIZE)?;
+        }
+        self.region = region;
+        Ok(self.snapshot())
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        self.region.is_some()
+    }
+
+    // 处理一条要转发给观战者的消息，返回实际发出的消息
+    pub fn filter(&mut self, msg: GameMessage) -> GameMessage {
+        match msg {
+            GameMessage::Watching { .. } => {
+                // 换了一盘棋，随后会收到新对局的局面
+                self.cells = [[None; 15]; 15];
+                self.current_player = PlayerRole::Black;
+                self.move_number = 0;
+                self.last_move = None;
+                self.last_outside = None;
+                msg
+            }
+            GameMessage::Status {
+                ref board,
+                current_player,
+                move_number,
+                last_move,
+                ..
+            } => {
+                self.cells = **board;
+                self.current_player = current_player;
+                self.move_number = move_number;
+                self.last_move = last_move;
+                // 没有订阅窗口时原样转发，落子列表也一起带上
+                match self.region {
+                    Some(_) => self.snapshot(),
+                    None => msg,
+                }
+            }
+            GameMessage::MoveApplied {
+                row,
+                col,
+                by,
+                next_player,
+                move_number,
+            } if row < 15 && col < 15 => {
+                self.cells[row][col] = Some(by);
+                self.current_player = next_player;
+                self.move_number = move_number;
+                self.last_move = Some((row, col));
+                match self.region {
+                    Some(region) if !region.contains(row, col) => {
+                        self.last_outside = Some((row, col));
+                        GameMessage::RegionSummary {
+                            current_player: self.current_player,
+                            outside: self.outside(region),
+                        }
+                    }
+                    _ => msg,
+                }
+            }
+            msg => msg,
+        }
+    }
+
+    // 当前局面：没有订阅窗口时是整盘棋
+    pub(crate) fn snapshot(&self) -> GameMessage {
+        match self.region {
+            Some(region) => GameMessage::RegionUpdate {
+                region,
+                cells: region.crop(&self.cells),
+                current_player: self.current_player,
+                outside: self.outside(region),
+            },
+            // 窗口里只跟着局面，没有落子列表
+            None => GameMessage::Status {
+                board: Box::new(self.cells),
+                current_player: self.current_player,
+                move_number: self.move_number,
+                last_move: self.last_move,
+                moves: Vec::new(),
+            },
+        }
+    }
+
+    fn outside(&self, region: Region) -> OutsideSummary {
+        let mut summary = OutsideSummary {
+            last_move: self.last_outside,
+            ..OutsideSummary::default()
+        };
+        for (row, line) in self.cells.iter().enumerate() {
+            for (col, cell) in line.iter().enumerate() {
+                match cell {
+                    _ if region.contains(row, col) => {}
+                    Some(PlayerRole::Black) => summary.black_stones += 1,
+                    Some(PlayerRole::White) => summary.white_stones += 1,
+                    None => {}
+                }
+            }
+        }
+        summary
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(feature = "server")]
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::PlayerRole;
+#[cfg(feature = "server")]
+use crate::{Clock, Game, GameOverReason, GamePhase, RoomDump};
+
+// 登录时告诉用户的一盘没下完的对局
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnfinishedGame {
+    pub game_id: String,
+    pub opponent: String,
+    pub color: PlayerRole,
+    // 轮到哪一方
+    pub to_move: PlayerRole,
+    pub move_number: usize,
+    // 黑白双方剩余时间（毫秒），不限时的对局没有
+    pub clock: Option<(u64, u64)>,
+    // 停服前存下的对局，连接时带上编号才会恢复；进行中的对局连接时直接回到座位
+    pub adjourned: bool,
+}
+
+#[cfg(feature = "server")]
+impl Game {
+    pub fn is_reserved(&self) -> bool {
+        !self.reserved.is_empty()
+    }
+
+    // 恢复的对局里留给这位用户、还空着的座位
+    pub(crate) fn reserved_seat(&self, username: &str) -> Option<PlayerRole> {
+        self.reserved
+            .iter()
+            .find(|&(&role, name)| name == username && self.seat_is_free(role))
+            .map(|(&role, _)| role)
+    }
+
+    // 恢复的对局等了 resume_hold_secs 还没重新开始：坐着的一方获胜，
+    // 没人坐着时放弃这盘棋，房间空出来
+    pub async fn expire_reservation(&mut self, game_id: &str) {
+        if self.id != game_id || !self.is_reserved() {
+            return;
+        }
+        self.reserved.clear();
+        let seated: Vec<PlayerRole> = self.players.keys().copied().collect();
+        match seated[..] {
+            [player] => {
+                println!("对局 {} 的另一方没有回来，{:?} 获胜", game_id, player);
+                // 恢复的对局停在 Waiting，先回到对局中才能结束
+                let _ = self.enter_phase(GamePhase::Playing);
+                self.finish(Some(player), GameOverReason::Disconnect).await;
+            }
+            _ => {
+                println!("对局 {} 没有人回来，放弃恢复", game_id);
+                self.reset();
+            }
+        }
+    }
+
+    // 登录用户坐着、掉线等重连或者恢复后还没回来的对局
+    pub(crate) fn unfinished_for(&self, username: &str) -> Option<UnfinishedGame> {
+        if self.is_finished() {
+            return None;
+        }
+        let color = [PlayerRole::Black, PlayerRole::White]
+            .into_iter()
+            .find(|role| {
+                self.names.get(role).map(String::as_str) == Some(username)
+                    && self.authenticated.contains(role)
+                    && (self.players.contains_key(role)
+                        || self.paused.contains_key(role)
+                        || self.reserved.contains_key(role))
+            })?;
+        let now = Instant::now();
+        let remaining = |clock: &Clock, role| clock.remaining(role, now).as_millis() as u64;
+        Some(UnfinishedGame {
+            game_id: self.id.clone(),
+            opponent: self.names.get(&color.other()).cloned().unwrap_or_default(),
+            color,
+            to_move: self.board.current_player,
+            move_number: self.board.move_number(),
+            clock: self.clock.as_ref().map(|clock| {
+                (
+                    remaining(clock, PlayerRole::Black),
+                    remaining(clock, PlayerRole::White),
+                )
+            }),
+            adjourned: self.is_reserved(),
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+impl RoomDump {
+    // 双方都是登录用户的对局才能恢复，游客下次来不一定还是同一个人
+    pub(crate) fn unfinished_for(&self, username: &str) -> Option<UnfinishedGame> {
+        if self.seats.len() < 2 || self.seats.iter().any(|seat| !seat.authenticated) {
+            return None;
+        }
+        let seat = self.seats.iter().find(|seat| seat.username == username)?;
+        let opponent = self.seats.iter().find(|other| other.role != seat.role)?;
+        // 载入时已经校验过，这里重放只为知道轮到谁
+        let board = self.verify().ok()?;
+        Some(UnfinishedGame {
+            game_id: self.game_id.clone(),
+            opponent: opponent.username.clone(),
+            color: seat.role,
+            to_move: board.current_player,
+            move_number: board.move_number(),
+            clock: self
+                .clock
+                .as_ref()
+                .map(|clock| (clock.black_ms, clock.white_ms)),
+            adjourned: true,
+        })
+    }
+}
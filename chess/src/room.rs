@@ -0,0 +1,412 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, Mutex, RwLock};
+
+use crate::{
+    AiThrottle, DemoRoom, Feature, Game, GameArchive, GameError, GamePhase, GameType,
+    IntegrityIssue, Invite, InviteRole, Metrics, PlayerRole, RoomDump, ServerConfig, Telemetry,
+    UnfinishedGame, UserManager,
+};
+
+pub type RoomId = usize;
+
+// 没有历史对局可参考时，按每盘 5 分钟估算排队时间
+const DEFAULT_GAME_DURATION: Duration = Duration::from_secs(300);
+// 估算排队时间时参考的最近对局数
+const DURATION_SAMPLE: usize = 20;
+// 观战者接替空座位时拿到的邀请的有效期，连接随即入座，只在入座前的一刻有用
+const SEAT_CLAIM_TTL_SECS: u64 = 60;
+
+// 房间和排队：房间数达到上限时新玩家排队，按先来后到入座
+pub struct RoomManager {
+    rooms: Vec<Arc<Mutex<Game>>>,
+    max_rooms: usize,
+    queue: VecDeque<u64>,
+    next_ticket: u64,
+    config: ServerConfig,
+    archive: Arc<Mutex<GameArchive>>,
+    users: Arc<RwLock<UserManager>>,
+    telemetry: Arc<Telemetry>,
+    metrics: Arc<Metrics>,
+    ai_throttle: Arc<AiThrottle>,
+    // 管理员要求停服前排空：不再开始新的对局
+    draining: bool,
+    // 每个连接的发送任务持有一份，停服时丢掉这里的一份，接收端读到关闭说明都发完了
+    connections: Option<mpsc::Sender<()>>,
+    connections_closed: Option<mpsc::Receiver<()>>,
+    // 上次停服时存下、启动时通过校验的未完成对局
+    adjourned: Vec<RoomDump>,
+    // 启动时没通过校验的未完成对局，不能恢复，等管理员处理
+    flagged: Vec<IntegrityIssue>,
+    // 演示房间编号 -> (讲解人的用户ID, 房间)，不占对局房间的名额，另有自己的上限
+    demos: HashMap<String, (String, Arc<Mutex<DemoRoom>>)>,
+}
+
+impl RoomManager {
+    pub fn new(
+        config: ServerConfig,
+        archive: Arc<Mutex<GameArchive>>,
+        users: Arc<RwLock<UserManager>>,
+    ) -> Self {
+        let (connections, connections_closed) = mpsc::channel(1);
+        Self {
+            rooms: Vec::new(),
+            connections: Some(connections),
+            connections_closed: Some(connections_closed),
+            max_rooms: config.max_rooms.max(1),
+            queue: VecDeque::new(),
+            next_ticket: 0,
+            draining: false,
+            telemetry: Arc::new(Telemetry::new(&config.telemetry)),
+            metrics: Arc::new(Metrics::new()),
+            ai_throttle: Arc::new(AiThrottle::new(config.ai_throttle)),
+            config,
+            archive,
+            users,
+            adjourned: Vec::new(),
+            flagged: Vec::new(),
+            demos: HashMap::new(),
+        }
+    }
+
+    // 启动时载入上次停服存下的未完成对局，逐盘重放校验，对不上的只记下来不恢复
+    pub fn load_adjourned(&mut self, records: Vec<RoomDump>) {
+        for record in records {
+            match record.verify() {
+                Ok(_) => self.adjourned.push(record),
+                Err(e) => {
+                    println!(
+                        "未完成的对局 {} 没有通过校验，不再恢复: {}",
+                        record.game_id, e
+                    );
+                    self.flagged.push(IntegrityIssue::new(&record.game_id, &e));
+                }
+            }
+        }
+        println!(
+            "载入 {} 盘未完成的对局，{} 盘校验失败",
+            self.adjourned.len(),
+            self.flagged.len()
+        );
+    }
+
+    pub fn adjourned(&self) -> &[RoomDump] {
+        &self.adjourned
+    }
+
+    // 未完成的和已存档的对局里，载入时没通过校验的
+    pub async fn flagged(&self) -> Vec<IntegrityIssue> {
+        let mut flagged = self.flagged.clone();
+        flagged.extend_from_slice(self.archive.lock().await.flagged());
+        flagged
+    }
+
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    pub fn telemetry(&self) -> Arc<Telemetry> {
+        self.telemetry.clone()
+    }
+
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn ai_throttle(&self) -> Arc<AiThrottle> {
+        self.ai_throttle.clone()
+    }
+
+    pub fn users(&self) -> Arc<RwLock<UserManager>> {
+        self.users.clone()
+    }
+
+    pub fn room(&self, id: RoomId) -> Option<Arc<Mutex<Game>>> {
+        self.rooms.get(id).cloned()
+    }
+
+    pub fn games(&self) -> Vec<Arc<Mutex<Game>>> {
+        self.rooms.clone()
+    }
+
+    pub fn room_count(&self) -> usize {
+        self.rooms.len()
+    }
+
+    pub fn set_draining(&mut self) {
+        self.draining = true;
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    // 有人在座且没下完的对局，掉线等待重连的也算
+    pub async fn active_games(&self) -> usize {
+        let mut active = 0;
+        for room in &self.rooms {
+            if room.lock().await.is_active() {
+                active += 1;
+            }
+        }
+        active
+    }
+
+    // 开始停服之后返回 None
+    pub fn track_connection(&self) -> Option<mpsc::Sender<()>> {
+        self.connections.clone()
+    }
+
+    // 和排空一样不再开始新的对局；返回的接收端在所有连接的发送任务结束后关闭
+    pub fn begin_shutdown(&mut self) -> Option<mpsc::Receiver<()>> {
+        self.draining = true;
+        self.connections = None;
+        self.connections_closed.take()
+    }
+
+    // 用户没下完的对局：房间里进行中的，和停服前存下还没恢复的
+    pub async fn unfinished_games(&self, username: &str) -> Vec<UnfinishedGame> {
+        let mut games = Vec::new();
+        for room in &self.rooms {
+            games.extend(room.lock().await.unfinished_for(username));
+        }
+        games.extend(
+            self.adjourned
+                .iter()
+                .filter_map(|record| record.unfinished_for(username)),
+        );
+        games
+    }
+
+    // 恢复停服前没下完的对局，返回房间和留给这位用户的座位。已经有一方回来过的
+    // 在原来的房间里等另一方；还没恢复的照记录摆进一个空房间
+    pub async fn resume(
+        &mut self,
+        game_id: &str,
+        username: &str,
+    ) -> Result<(RoomId, Arc<Mutex<Game>>, PlayerRole), GameError> {
+        for (id, room) in self.rooms.iter().enumerate() {
+            let game = room.lock().await;
+            if game.id() != game_id {
+                continue;
+            }
+            let role = game.reserved_seat(username).ok_or_else(|| {
+                GameError::Unavailable(format!("对局 {} 里没有留给你的座位", game_id))
+            })?;
+            return Ok((id, room.clone(), role));
+        }
+        if self.draining {
+            return Err(GameError::Unavailable(
+                "服务器准备维护，暂不恢复对局".to_string(),
+            ));
+        }
+        let index = self
+            .adjourned
+            .iter()
+            .position(|record| {
+                record.game_id == game_id && record.unfinished_for(username).is_some()
+            })
+            .ok_or_else(|| GameError::NotFound(format!("找不到可以恢复的对局 {}", game_id)))?;
+        let mut game = Game::from_record(&self.adjourned[index], self.config.downtime_policy)?;
+        game.configure(&self.config, self.archive.clone(), self.users.clone());
+        let game = game
+            .with_telemetry(self.telemetry.clone())
+            .with_metrics(self.metrics.clone());
+        let role = game
+            .reserved_seat(username)
+            .ok_or_else(|| GameError::NotFound(format!("找不到可以恢复的对局 {}", game_id)))?;
+        let (id, room) = self
+            .free_room()
+            .await
+            .ok_or_else(|| GameError::Unavailable("房间已满，稍后再恢复对局".to_string()))?;
+        *room.lock().await = game;
+        self.adjourned.remove(index);
+        self.archive.lock().await.forget_adjourned(game_id);
+        println!("对局 {} 恢复到房间 {}", game_id, id);
+        // 另一方迟迟不回来时不能一直占着房间
+        let hold = Duration::from_secs(self.config.resume_hold_secs);
+        let held = room.clone();
+        let game_id = game_id.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(hold).await;
+            held.lock().await.expire_reservation(&game_id).await;
+        });
+        Ok((id, room, role))
+    }
+
+    // 找一个有空位的房间：优先等待对手的房间，其次空房间，最后在上限内新开房间；
+    // solo 表示和电脑对弈，只要空房间
+    async fn find_room(&mut self, solo: bool) -> Option<(RoomId, Arc<Mutex<Game>>)> {
+        if !solo {
+            for (id, room) in self.rooms.iter().enumerate() {
+                let game = room.lock().await;
+                // 下到一半让出来的座位留给观战者接替，恢复的对局留给原来的双方，
+                // 都不分给排队的玩家
+                if game.player_count() == 1
+                    && game.phase() == GamePhase::Waiting
+                    && !game.is_reserved()
+                {
+                    return Some((id, room.clone()));
+                }
+            }
+        }
+        self.free_room().await
+    }
+
+    // 空房间，没有就在上限内新开一间
+    pub(crate) async fn free_room(&mut self) -> Option<(RoomId, Arc<Mutex<Game>>)> {
+        for (id, room) in self.rooms.iter().enumerate() {
+            let game = room.lock().await;
+            if game.player_count() == 0 && !game.is_reserved() {
+                return Some((id, room.clone()));
+            }
+        }
+        if self.rooms.len() < self.max_rooms {
+            let game = Game::with_config(&self.config, self.archive.clone(), self.users.clone())
+                .with_telemetry(self.telemetry.clone())
+                .with_metrics(self.metrics.clone());
+            let room = Arc::new(Mutex::new(game));
+            self.rooms.push(room.clone());
+            println!(
+                "新开房间 {}，当前共 {} 个房间",
+                self.rooms.len() - 1,
+                self.rooms.len()
+            );
+            return Some((self.rooms.len() - 1, room));
+        }
+        None
+    }
+
+    // 观战者接替对局中让出的座位：房主允许替补、座位确实空着时给一张这个座位的邀请，
+    // 观战连接凭它像普通邀请一样直接入座。几个人同时申请时先入座的得到座位
+    pub async fn claim_seat(&self, game_id: &str, role: PlayerRole) -> Result<Invite, GameError> {
+        for (room, game) in self.rooms.iter().enumerate() {
+            let game = game.lock().await;
+            if game.id() != game_id {
+                continue;
+            }
+            if !game.seat_is_open(role) {
+                return Err(GameError::Unavailable(format!(
+                    "{:?} 的座位没有开放替补",
+                    role
+                )));
+            }
+            let invite = Invite::new(
+                room,
+                game_id.to_string(),
+                InviteRole::Seat(role),
+                SEAT_CLAIM_TTL_SECS,
+            );
+            println!("观战者申请接替房间 {} 的 {:?} 座位", room, role);
+            return Ok(invite);
+        }
+        Err(GameError::NotFound(format!("找不到对局 {}", game_id)))
+    }
+
+    // ticket 为 None 表示新来的玩家，有人排队时不能插队
+    pub async fn try_seat(
+        &mut self,
+        ticket: Option<u64>,
+        solo: bool,
+    ) -> Option<(RoomId, Arc<Mutex<Game>>)> {
+        let allowed = match ticket {
+            None => self.queue.is_empty(),
+            Some(ticket) => self.queue.front() == Some(&ticket),
+        };
+        if !allowed {
+            return None;
+        }
+        let room = self.find_room(solo).await?;
+        if ticket.is_some() {
+            self.queue.pop_front();
+        }
+        Some(room)
+    }
+
+    pub fn enqueue(&mut self) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.queue.push_back(ticket);
+        ticket
+    }
+
+    pub fn leave_queue(&mut self, ticket: u64) {
+        self.queue.retain(|&t| t != ticket);
+    }
+
+    // 排队位置，从 1 开始
+    pub fn position(&self, ticket: u64) -> Option<usize> {
+        self.queue.iter().position(|&t| t == ticket).map(|i| i + 1)
+    }
+
+    // 前面每轮 max_rooms 个人需要等一盘棋结束
+    pub async fn estimated_wait(&self, position: usize) -> Duration {
+        let per_game = self
+            .archive
+            .lock()
+            .await
+            .average_duration(DURATION_SAMPLE)
+            .unwrap_or(DEFAULT_GAME_DURATION);
+        per_game * position.div_ceil(self.max_rooms) as u32
+    }
+
+    // 管理员调整的开关对所有房间和之后新开的房间生效
+    pub async fn set_feature(&mut self, game_type: GameType, feature: Feature, enabled: bool) {
+        self.config.features.set(game_type, feature, enabled);
+        for room in &self.rooms {
+            room.lock().await.set_feature(game_type, feature, enabled);
+        }
+    }
+
+    pub fn enabled_features(&self, game_type: GameType) -> Vec<Feature> {
+        self.config.features.enabled(game_type)
+    }
+
+    // 按服务器的规则为登录用户开一个演示房间，准备停服时不再开新的
+    pub fn open_demo(
+        &mut self,
+        title: &str,
+        owner: &str,
+    ) -> Result<Arc<Mutex<DemoRoom>>, GameError> {
+        if self.draining {
+            return Err(GameError::Unavailable(
+                "服务器准备维护，暂不开放新的演示".to_string(),
+            ));
+        }
+        if self.demos.len() >= self.config.max_demos {
+            return Err(GameError::Unavailable(
+                "演示房间已满，请稍后再试".to_string(),
+            ));
+        }
+        let owned = self.demos.values().filter(|(id, _)| id == owner).count();
+        if owned >= self.config.max_demos_per_user {
+            return Err(GameError::Unavailable(format!(
+                "每个用户最多同时开 {} 个演示房间",
+                self.config.max_demos_per_user
+            )));
+        }
+        let demo = DemoRoom::new(
+            title,
+            self.config.rules,
+            self.config.limits,
+            self.config.room_quotas.demo,
+        );
+        let id = demo.id().to_string();
+        let demo = Arc::new(Mutex::new(demo));
+        self.demos.insert(id, (owner.to_string(), demo.clone()));
+        Ok(demo)
+    }
+
+    pub fn demo(&self, id: &str) -> Option<Arc<Mutex<DemoRoom>>> {
+        self.demos.get(id).map(|(_, demo)| demo.clone())
+    }
+
+    // 讲解人离开后关闭房间，观众收到 DemoClosed
+    pub async fn close_demo(&mut self, id: &str) {
+        if let Some((_, demo)) = self.demos.remove(id) {
+            demo.lock().await.close();
+        }
+    }
+}
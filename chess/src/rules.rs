@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{GameType, PlayerRole};
+
+// 棋盘数组的大小，也是最大的棋盘；小棋盘只用左上角
+pub const BOARD_SIZE: usize = 15;
+pub const DEFAULT_WIN_LENGTH: usize = 5;
+// 可以配置的连子数
+pub const MIN_WIN_LENGTH: usize = 4;
+pub const MAX_WIN_LENGTH: usize = 6;
+pub const MAX_STONES_PER_TURN: usize = 2;
+
+// 棋盘大小、连几子获胜和每回合落几子，配置示例: "rules": { "board_size": 9, "win_length": 4 }
+// 六子棋: "rules": { "win_length": 6, "stones_per_turn": 2 }
+// 和棋条件: "rules": { "move_limit": 120, "draw_when_blocked": true }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesConfig {
+    pub board_size: usize,
+    pub win_length: usize,
+    // 大于 1 时黑方第一手只下一子，之后双方每回合各下这么多子
+    pub stones_per_turn: usize,
+    // 棋盘上的棋子数达到这么多时判和，0 表示不限
+    pub move_limit: usize,
+    // 所有能连成线的位置都被双方堵死时提前判和，不用等到下满
+    pub draw_when_blocked: bool,
+}
+
+impl Default for RulesConfig {
+    fn default() -> Self {
+        Self {
+            board_size: BOARD_SIZE,
+            win_length: DEFAULT_WIN_LENGTH,
+            stones_per_turn: 1,
+            move_limit: 0,
+            draw_when_blocked: false,
+        }
+    }
+}
+
+impl RulesConfig {
+    pub fn connect6() -> Self {
+        Self {
+            board_size: BOARD_SIZE,
+            win_length: 6,
+            stones_per_turn: 2,
+            ..Self::default()
+        }
+    }
+
+    // 统计和功能开关按游戏类型区分，每回合多子的算六子棋
+    pub fn game_type(&self) -> GameType {
+        match self.stones_per_turn {
+            1 => GameType::Gomoku,
+            _ => GameType::Connect6,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_STONES_PER_TURN).contains(&self.stones_per_turn) {
+            return Err(format!("每回合落子数必须在 1-{} 之间", MAX_STONES_PER_TURN));
+        }
+        if !(MIN_WIN_LENGTH..=MAX_WIN_LENGTH).contains(&self.win_length) {
+            return Err(format!(
+                "连子数必须在 {}-{} 之间",
+                MIN_WIN_LENGTH, MAX_WIN_LENGTH
+            ));
+        }
+        if !(self.win_length..=BOARD_SIZE).contains(&self.board_size) {
+            return Err(format!(
+                "棋盘大小必须在 {}-{} 之间",
+                self.win_length, BOARD_SIZE
+            ));
+        }
+        Ok(())
+    }
+
+    // 坐标在棋盘内，搜索时常常先走出去再判断，所以用 i32
+    pub fn contains(&self, row: i32, col: i32) -> bool {
+        let size = self.board_size as i32;
+        (0..size).contains(&row) && (0..size).contains(&col)
+    }
+
+    // 棋盘上所有的点，按行排列
+    pub fn points(&self) -> impl Iterator<Item = (usize, usize)> {
+        let size = self.board_size;
+        (0..size).flat_map(move |row| (0..size).map(move |col| (row, col)))
+    }
+
+    pub fn center(&self) -> (usize, usize) {
+        (self.board_size / 2, self.board_size / 2)
+    }
+
+    // 棋盘上已有 placed 颗子时是第几回合，黑方第一手单独算一回合
+    fn turn(&self, placed: usize) -> usize {
+        placed.div_ceil(self.stones_per_turn)
+    }
+
+    // 下第 placed 颗子（从 0 开始）的一方
+    pub fn mover(&self, placed: usize) -> PlayerRole {
+        match self.turn(placed) % 2 {
+            0 => PlayerRole::Black,
+            _ => PlayerRole::White,
+        }
+    }
+
+    // 棋盘上已有 placed 颗子时，这一回合还要下几子（包括正要下的这一子）
+    pub fn stones_left(&self, placed: usize) -> usize {
+        self.turn(placed) * self.stones_per_turn + 1 - placed
+    }
+}
@@ -0,0 +1,204 @@
+use std::time::{Duration, Instant};
+
+use crate::ai::is_win;
+use crate::{sgf, Board, Difficulty, EngineKind, MoveRecord, PlayerRole};
+
+// 自对弈的一方：用哪个引擎、按哪个难度分配计算量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contestant {
+    pub engine: EngineKind,
+    pub difficulty: Difficulty,
+}
+
+impl Contestant {
+    // 解析 "search:hard"、"mcts:easy" 这样的写法，省略难度时为 medium
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (engine, difficulty) = text.split_once(':').unwrap_or((text, "medium"));
+        let engine = match engine {
+            "search" => EngineKind::Search,
+            "mcts" => EngineKind::Mcts,
+            _ => return Err(format!("未知的引擎: {}", engine)),
+        };
+        let difficulty = match difficulty {
+            "easy" => Difficulty::Easy,
+            "medium" => Difficulty::Medium,
+            "hard" => Difficulty::Hard,
+            _ => return Err(format!("未知的难度: {}", difficulty)),
+        };
+        Ok(Self { engine, difficulty })
+    }
+
+    // 写进棋谱的对局者名字
+    pub fn name(&self) -> String {
+        let engine = match self.engine {
+            EngineKind::Search => "search",
+            EngineKind::Mcts => "mcts",
+        };
+        let difficulty = match self.difficulty {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        };
+        format!("{}:{}", engine, difficulty)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfPlayConfig {
+    pub first: Contestant,
+    pub second: Contestant,
+    pub games: usize,
+    // 超过这么多步仍未分胜负按和棋计
+    pub max_moves: usize,
+}
+
+impl SelfPlayConfig {
+    pub fn new(first: Contestant, second: Contestant, games: usize) -> Self {
+        Self {
+            first,
+            second,
+            games,
+            max_moves: 15 * 15,
+        }
+    }
+}
+
+// 自对弈的一盘棋，双方轮流执黑：第 0、2、4… 盘 first 执黑
+#[derive(Debug, Clone)]
+pub struct SelfPlayGame {
+    pub first_is_black: bool,
+    pub winner: Option<PlayerRole>,
+    pub moves: Vec<MoveRecord>,
+    // 黑、白双方的总思考时间
+    pub black_time: Duration,
+    pub white_time: Duration,
+    pub sgf: String,
+}
+
+impl SelfPlayGame {
+    // first 赢了返回 Some(true)，second 赢了返回 Some(false)，和棋返回 None
+    pub fn first_won(&self) -> Option<bool> {
+        self.winner
+            .map(|winner| (winner == PlayerRole::Black) == self.first_is_black)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContestantStats {
+    pub wins: usize,
+    pub moves: usize,
+    pub think_time: Duration,
+}
+
+impl ContestantStats {
+    fn record(&mut self, (moves, time): (usize, Duration)) {
+        self.moves += moves;
+        self.think_time += time;
+    }
+
+    pub fn average_move_time(&self) -> Duration {
+        self.think_time
+            .checked_div(self.moves as u32)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SelfPlayReport {
+    pub first: ContestantStats,
+    pub second: ContestantStats,
+    pub draws: usize,
+    pub games: Vec<SelfPlayGame>,
+}
+
+impl SelfPlayReport {
+    // 胜率按总盘数计算，和棋不算胜
+    pub fn win_rate(&self, stats: &ContestantStats) -> f64 {
+        if self.games.is_empty() {
+            return 0.0;
+        }
+        stats.wins as f64 / self.games.len() as f64
+    }
+}
+
+// 不经过网络，让两个引擎配置下 games 盘棋。每下完一盘调用一次 on_game，方便显示进度
+pub fn self_play(
+    config: &SelfPlayConfig,
+    mut on_game: impl FnMut(usize, &SelfPlayGame),
+) -> SelfPlayReport {
+    let mut report = SelfPlayReport::default();
+    for index in 0..config.games {
+        let first_is_black = index % 2 == 0;
+        let (black, white) = if first_is_black {
+            (config.first, config.second)
+        } else {
+            (config.second, config.first)
+        };
+        let game = play_game(black, white, config.max_moves, first_is_black);
+
+        let black_side = (game.moves.len().div_ceil(2), game.black_time);
+        let white_side = (game.moves.len() / 2, game.white_time);
+        let (first_side, second_side) = if first_is_black {
+            (black_side, white_side)
+        } else {
+            (white_side, black_side)
+        };
+        report.first.record(first_side);
+        report.second.record(second_side);
+        match game.first_won() {
+            Some(true) => report.first.wins += 1,
+            Some(false) => report.second.wins += 1,
+            None => report.draws += 1,
+        }
+        on_game(index, &game);
+        report.games.push(game);
+    }
+    report
+}
+
+fn play_game(
+    black: Contestant,
+    white: Contestant,
+    max_moves: usize,
+    first_is_black: bool,
+) -> SelfPlayGame {
+    let engines = [black.engine.engine(), white.engine.engine()];
+    let budgets = [black.difficulty.budget(), white.difficulty.budget()];
+    let mut times = [Duration::ZERO; 2];
+    let mut board = Board::new();
+    let mut winner = None;
+
+    while board.moves.len() < max_moves {
+        let player = board.current_player;
+        let side = match player {
+            PlayerRole::Black => 0,
+            PlayerRole::White => 1,
+        };
+        let started = Instant::now();
+        let choice = engines[side].choose_move(&board, player, budgets[side]);
+        times[side] += started.elapsed();
+
+        let Some((row, col)) = choice else {
+            break;
+        };
+        // 引擎走出非法的棋直接判负，回归测试里这本身就是要发现的问题
+        if board.make_move(row, col).is_err() {
+            winner = Some(player.other());
+            break;
+        }
+        if is_win(&board.cells, board.rules, row, col, player) {
+            winner = Some(player);
+            break;
+        }
+    }
+
+    let sgf = sgf::to_sgf(&board.moves, &black.name(), &white.name(), 15, Some(winner));
+    SelfPlayGame {
+        first_is_black,
+        winner,
+        moves: board.moves,
+        black_time: times[0],
+        white_time: times[1],
+        sgf,
+    }
+}
@@ -0,0 +1,1451 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, watch, Mutex, RwLock};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_async_with_config, WebSocketStream};
+
+use crate::{
+    authenticate_user, evaluation_graph, list_games, register_user, search_position, suggest_move,
+    AIPlayer, DemoRoom, Difficulty, Feature, Game, GameArchive, GameError, GameMessage, Invite,
+    InviteRole, LineDirection, MessageLimits, MoveOutcome, MoveStage, Outbox, PlayerRole,
+    PresenceState, RoomId, RoomManager, SeatRequest, ServerInfo, Session, Spectator, User,
+    UserManager, Violation, ViolationTracker, BOARD_SIZE, DEFAULT_INVITE_TTL_SECS,
+    MAX_LEADERBOARD_SIZE,
+};
+
+fn feature_disabled(feature: Feature) -> GameMessage {
+    GameError::Unavailable(format!("功能 {:?} 暂未开放", feature)).into()
+}
+
+// 用户还坐在某个房间里时接回座位：掉线的玩家在宽限期内重连，
+// 或者已认证用户从其他设备接管，旧连接收到通知后关闭
+async fn reclaim_seat(
+    rooms: &Mutex<RoomManager>,
+    user_manager: &RwLock<UserManager>,
+    user_id: &str,
+    authenticated: bool,
+    tx: &mpsc::Sender<GameMessage>,
+) -> Option<(RoomId, Arc<Mutex<Game>>, PlayerRole)> {
+    let (room, player) = user_manager.read().await.seat_of(user_id)?;
+    let game = rooms.lock().await.room(room)?;
+    let mut guard = game.lock().await;
+    if guard.is_paused_for(player) {
+        guard.resume(player, tx.clone()).await;
+    } else if authenticated {
+        let old = guard.replace_player(player, tx.clone()).await?;
+        println!(
+            "用户 {} 的会话转移到新连接 ({:?}, 房间 {})",
+            user_id, player, room
+        );
+        let _ = old.send(GameMessage::SessionTransferred).await;
+    } else {
+        return None;
+    }
+    drop(guard);
+    Some((room, game, player))
+}
+
+// 排队等空位时每一轮的结果
+enum QueueStep<'a> {
+    // 入座完成前一直持有房间锁，避免两个人抢到同一个空位
+    Seated(
+        (RoomId, Arc<Mutex<Game>>),
+        tokio::sync::MutexGuard<'a, RoomManager>,
+    ),
+    Draining,
+    // 还在排队，位置变了或者隔了一段时间时带上要推送的排队状态
+    Waiting(Option<GameMessage>),
+}
+
+// 房间都满时排队，按先来后到等待空位。连接时和对局结束后换对手时都走这里
+struct Matchmaking {
+    ticket: Option<u64>,
+    reported: Option<usize>,
+    last_report: Instant,
+}
+
+impl Matchmaking {
+    fn new() -> Self {
+        Self {
+            ticket: None,
+            reported: None,
+            last_report: Instant::now(),
+        }
+    }
+
+    async fn step<'a>(
+        &mut self,
+        rooms: &'a Mutex<RoomManager>,
+        solo: bool,
+        username: &str,
+    ) -> QueueStep<'a> {
+        let mut guard = rooms.lock().await;
+        if guard.is_draining() {
+            if let Some(queued) = self.ticket {
+                guard.leave_queue(queued);
+            }
+            return QueueStep::Draining;
+        }
+        if let Some(seat) = guard.try_seat(self.ticket, solo).await {
+            return QueueStep::Seated(seat, guard);
+        }
+        let queued = *self.ticket.get_or_insert_with(|| guard.enqueue());
+        let position = guard.position(queued).unwrap_or(1);
+        let wait = guard.estimated_wait(position).await;
+        drop(guard);
+
+        if self.reported == Some(position) && self.last_report.elapsed() < QUEUE_STATUS_INTERVAL {
+            return QueueStep::Waiting(None);
+        }
+        println!("玩家 {} 排队中，位置 {}", username, position);
+        self.reported = Some(position);
+        self.last_report = Instant::now();
+        QueueStep::Waiting(Some(GameMessage::QueueStatus {
+            position,
+            estimated_wait_secs: wait.as_secs(),
+        }))
+    }
+
+    async fn leave(&self, rooms: &Mutex<RoomManager>) {
+        if let Some(queued) = self.ticket {
+            rooms.lock().await.leave_queue(queued);
+        }
+    }
+}
+
+// 排队期间只关心断开，其他消息忽略。连接断开时返回 false
+async fn wait_in_queue(ws_receiver: &mut SplitStream<WebSocketStream<TcpStream>>) -> bool {
+    tokio::select! {
+        msg = ws_receiver.next() => matches!(
+            msg,
+            Some(Ok(Message::Text(_) | Message::Ping(_) | Message::Pong(_)))
+        ),
+        _ = tokio::time::sleep(QUEUE_POLL_INTERVAL) => true,
+    }
+}
+
+// 同时对局数和弃局冷却不满足时不让排队，准备维护时也不再开新局
+async fn check_can_play(
+    rooms: &Mutex<RoomManager>,
+    user_manager: &RwLock<UserManager>,
+    user_id: &str,
+) -> Result<(), GameError> {
+    if rooms.lock().await.is_draining() {
+        return Err(GameError::Unavailable(DRAINING_MESSAGE.to_string()));
+    }
+    user_manager.read().await.check_can_play(user_id)
+}
+
+// 坐到选好的房间里并把座位登记到用户名下，登记失败时让出座位
+async fn take_seat(
+    user_manager: &RwLock<UserManager>,
+    game: &mut Game,
+    room: RoomId,
+    seat: SeatRequest,
+    user: &User,
+    tx: &mpsc::Sender<GameMessage>,
+) -> Result<PlayerRole, GameError> {
+    // 添加玩家到游戏，座位在这里选定
+    let player = match game.add_player(seat, user.name.clone(), tx.clone()).await {
+        Ok(player) => player,
+        Err(e) => {
+            println!("添加玩家到游戏失败: {}", e);
+            return Err(e);
+        }
+    };
+    // 分配玩家角色给用户
+    let assigned = user_manager
+        .write()
+        .await
+        .assign_player(&user.id, room, player);
+    if let Err(e) = assigned {
+        println!("分配玩家角色失败: {}", e);
+        game.remove_player(player).await;
+        return Err(e);
+    }
+    println!("成功分配玩家角色: {:?} 给用户 {}", player, user.name);
+    println!("成功添加玩家 {} ({:?}) 到房间 {}", user.name, player, room);
+    Ok(player)
+}
+
+// 对局结束后换对手：回到匹配队列等新的座位，不再配电脑对手。排队时断开返回 None
+async fn find_opponent(
+    rooms: &Mutex<RoomManager>,
+    user_manager: &RwLock<UserManager>,
+    user: &User,
+    authenticated: bool,
+    preferred_role: Option<PlayerRole>,
+    tx: &mpsc::Sender<GameMessage>,
+    ws_receiver: &mut SplitStream<WebSocketStream<TcpStream>>,
+) -> Result<Option<(RoomId, Arc<Mutex<Game>>, PlayerRole)>, GameError> {
+    check_can_play(rooms, user_manager, &user.id).await?;
+    let mut queue = Matchmaking::new();
+    loop {
+        match queue.step(rooms, false, &user.name).await {
+            QueueStep::Seated((room, game), rooms) => {
+                let mut guard = game.lock().await;
+                let seat = SeatRequest::Prefer(preferred_role);
+                let player = take_seat(user_manager, &mut guard, room, seat, user, tx).await?;
+                drop(rooms);
+                guard.set_authenticated(player, authenticated);
+                drop(guard);
+                return Ok(Some((room, game, player)));
+            }
+            QueueStep::Draining => {
+                return Err(GameError::Unavailable(DRAINING_MESSAGE.to_string()));
+            }
+            QueueStep::Waiting(status) => {
+                if let Some(status) = status {
+                    let _ = tx.send(status).await;
+                }
+                if !wait_in_queue(ws_receiver).await {
+                    queue.leave(rooms).await;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+async fn connect_response(
+    user_manager: &RwLock<UserManager>,
+    user: &User,
+    game: &Mutex<Game>,
+    player: PlayerRole,
+    session: &Session,
+) -> GameMessage {
+    let rating = user_manager.read().await.rating(&user.id).rating;
+    let game = game.lock().await;
+    GameMessage::ConnectResponse {
+        username: user.name.clone(),
+        player_role: player,
+        rating: rating.round() as i32,
+        user_id: user.id.clone(),
+        game_id: game.id().to_string(),
+        protocol_version: session.protocol_version,
+        capabilities: session.capabilities.clone(),
+        rules: game.board.rules,
+    }
+}
+
+// 按编号找到进行中的对局并作为观战者入场，找不到时交还连接
+async fn watch_game(
+    rooms: &Mutex<RoomManager>,
+    game_id: &str,
+    spectator: Spectator,
+) -> Result<(Arc<Mutex<Game>>, u64, oneshot::Receiver<Spectator>), Spectator> {
+    let games = rooms.lock().await.games();
+    for game in games {
+        let mut guard = game.lock().await;
+        if guard.id() != game_id {
+            continue;
+        }
+        let (id, released) = guard.add_spectator(spectator).await?;
+        drop(guard);
+        return Ok((game, id, released));
+    }
+    Err(spectator)
+}
+
+// 按单帧上限发送一条消息，太大的拆成多个 Chunk
+async fn send_frames(
+    ws_sender: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+    frames: Vec<String>,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    for frame in frames {
+        ws_sender.send(Message::Text(frame)).await?;
+    }
+    Ok(())
+}
+
+// 入座前拒绝连接，错误按协商出的能力发出，调用方随后关闭连接
+async fn reject(
+    ws_sender: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+    limits: &MessageLimits,
+    session: &mut Session,
+    error: GameError,
+) {
+    let reply = session.filter(error.into());
+    let _ = send_frames(ws_sender, session.encode(limits, &reply)).await;
+}
+
+// 观战者接替了空座位，连接交还给调用方按持邀请的玩家入座
+struct SeatClaim {
+    ws_sender: SplitSink<WebSocketStream<TcpStream>, Message>,
+    ws_receiver: SplitStream<WebSocketStream<TcpStream>>,
+    invite: Invite,
+}
+
+// 入座前的握手结果：用户名、令牌、电脑对手难度、邀请、要恢复的对局、想坐的颜色和协商结果
+type Handshake = (
+    String,
+    Option<String>,
+    Option<Difficulty>,
+    Option<Invite>,
+    Option<String>,
+    Option<PlayerRole>,
+    Session,
+);
+
+// 接替座位的观战者以服务器生成名字的游客身份入座。观战连接没有协商过协议版本和能力，
+// 按最旧的方式发
+fn claimed_seat(invite: Invite) -> Handshake {
+    println!("观战者接替房间 {} 的座位", invite.room);
+    let session = Session::negotiate(None, &[]).expect("最旧的协议版本总是支持的");
+    (String::new(), None, None, Some(invite), None, None, session)
+}
+
+// 观战连接：转发所观战对局的消息，可以随时查询列表、切换到另一盘或停止观战，
+// 连接关闭时离开对局。观战时连接交给房间的广播任务，回复也经它发出。
+// 接替到空座位时离开观战，把连接交还给调用方
+async fn spectate(
+    ws_sender: SplitSink<WebSocketStream<TcpStream>, Message>,
+    mut ws_receiver: SplitStream<WebSocketStream<TcpStream>>,
+    rooms: &Mutex<RoomManager>,
+    archive: &Mutex<GameArchive>,
+    game_id: String,
+) -> Option<SeatClaim> {
+    let limits = rooms.lock().await.config().limits;
+    // 没在观战时连接在自己手里，窗口订阅跟着连接走，切换到别的对局时保留
+    let mut idle = Some(Spectator::new(ws_sender));
+    let mut watching: Option<(Arc<Mutex<Game>>, u64, oneshot::Receiver<Spectator>)> = None;
+    let mut request = Some(GameMessage::Watch { game_id });
+    let claimed = loop {
+        if let Some(request) = request.take() {
+            // 切换、停止观战或者接替座位时先从广播任务拿回连接，拿不回说明连接已经断了
+            if matches!(
+                request,
+                GameMessage::Watch { .. } | GameMessage::StopWatching
+            ) {
+                if let Some((game, id, released)) = watching.take() {
+                    game.lock().await.remove_spectator(id);
+                    match released.await {
+                        Ok(spectator) => idle = Some(spectator),
+                        Err(_) => break None,
+                    }
+                }
+            }
+            let reply = match request {
+                GameMessage::ListGames { filter } => Some(GameMessage::GameList {
+                    page: list_games(rooms, archive, &filter).await,
+                }),
+                GameMessage::Watch { game_id } => {
+                    let Some(spectator) = idle.take() else {
+                        break None;
+                    };
+                    match watch_game(rooms, &game_id, spectator).await {
+                        Ok(watch) => {
+                            watching = Some(watch);
+                            None
+                        }
+                        Err(spectator) => {
+                            idle = Some(spectator);
+                            Some(
+                                GameError::NotFound(format!("对局 {} 不存在或已结束", game_id))
+                                    .into(),
+                            )
+                        }
+                    }
+                }
+                GameMessage::StopWatching => None,
+                GameMessage::ClaimSeat { role } => match watching.as_ref() {
+                    Some((game, _, _)) => {
+                        let game_id = game.lock().await.id().to_string();
+                        let claim = rooms.lock().await.claim_seat(&game_id, role).await;
+                        match claim {
+                            Ok(invite) => {
+                                let Some((game, id, released)) = watching.take() else {
+                                    break None;
+                                };
+                                game.lock().await.remove_spectator(id);
+                                let Ok(mut spectator) = released.await else {
+                                    break None;
+                                };
+                                let reply = GameMessage::SeatClaimed { game_id, role };
+                                if send_frames(&mut spectator.sink, limits.encode(&reply))
+                                    .await
+                                    .is_err()
+                                {
+                                    break None;
+                                }
+                                break Some((spectator.sink, invite));
+                            }
+                            Err(e) => Some(e.into()),
+                        }
+                    }
+                    None => Some(
+                        GameError::Protocol("只能接替正在观战的对局里的座位".to_string()).into(),
+                    ),
+                },
+                GameMessage::Resync => match watching.as_ref() {
+                    Some((game, _, _)) => Some(game.lock().await.status()),
+                    None => None,
+                },
+                GameMessage::SubscribeRegion { region } => {
+                    // 按这盘棋的实际大小检查，还没开始观战时按棋盘数组的大小
+                    let board_size = match watching.as_ref() {
+                        Some((game, _, _)) => game.lock().await.board.rules.board_size,
+                        None => BOARD_SIZE,
+                    };
+                    match region.map_or(Ok(()), |region| region.validate(board_size)) {
+                        Err(e) => Some(e.into()),
+                        Ok(()) => {
+                            if let Some((game, id, _)) = watching.as_ref() {
+                                game.lock().await.subscribe_region(*id, region);
+                            } else if let Some(spectator) = idle.as_mut() {
+                                let _ = spectator.view.subscribe(region);
+                            }
+                            None
+                        }
+                    }
+                }
+                GameMessage::Error(e) => Some(GameMessage::Error(e)),
+                _ => Some(
+                    GameError::Protocol(
+                        "观战中只能查询对局列表、切换、订阅窗口、接替空座位或停止观战".to_string(),
+                    )
+                    .into(),
+                ),
+            };
+            // 观战连接没有协商过能力，错误按旧格式发
+            let reply = reply.map(GameMessage::without_error_code);
+            let sent = match (reply, watching.as_ref(), idle.as_mut()) {
+                (Some(reply), Some((game, id, _)), _) => {
+                    game.lock().await.send_to_spectator(*id, reply);
+                    true
+                }
+                (Some(reply), None, Some(spectator)) => {
+                    send_frames(&mut spectator.sink, limits.encode(&reply))
+                        .await
+                        .is_ok()
+                }
+                _ => true,
+            };
+            if !sent {
+                break None;
+            }
+        }
+
+        let released = async {
+            match watching.as_mut() {
+                Some((_, _, released)) => released.await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            spectator = released => match spectator {
+                // 对局已重置，这盘棋的观战结束
+                Ok(spectator) => {
+                    watching = None;
+                    idle = Some(spectator);
+                }
+                // 写得太慢或者写失败，广播任务已经断开连接
+                Err(_) => break None,
+            },
+            frame = ws_receiver.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    request = Some(limits.decode(&text).unwrap_or_else(GameMessage::from));
+                }
+                Some(Ok(_)) => {}
+                _ => break None,
+            },
+        }
+    };
+    if let Some((game, id, _)) = watching {
+        game.lock().await.remove_spectator(id);
+    }
+    let Some((ws_sender, invite)) = claimed else {
+        println!("观战连接已关闭");
+        return None;
+    };
+    Some(SeatClaim {
+        ws_sender,
+        ws_receiver,
+        invite,
+    })
+}
+
+// 讲解连接：开一个演示房间，之后只接受 DemoEdit，操作结果同时回给讲解人。
+// 连接关闭时房间随之关闭
+async fn present(
+    mut ws_sender: SplitSink<WebSocketStream<TcpStream>, Message>,
+    mut ws_receiver: SplitStream<WebSocketStream<TcpStream>>,
+    rooms: &Mutex<RoomManager>,
+    title: String,
+    owner: String,
+) {
+    let limits = rooms.lock().await.config().limits;
+    let demo = match rooms.lock().await.open_demo(&title, &owner) {
+        Ok(demo) => demo,
+        Err(e) => {
+            let reply = GameMessage::from(e).without_error_code();
+            let _ = send_frames(&mut ws_sender, limits.encode(&reply)).await;
+            return;
+        }
+    };
+    let (demo_id, status, position) = {
+        let demo = demo.lock().await;
+        (demo.id().to_string(), demo.status(), demo.position())
+    };
+    println!("演示房间 {} 已开放: {}", demo_id, title);
+    let mut replies = vec![
+        GameMessage::DemoCreated {
+            demo_id: demo_id.clone(),
+        },
+        status,
+        position,
+    ];
+    loop {
+        let mut sent = true;
+        for reply in replies.drain(..) {
+            // 讲解连接没有协商过能力，错误按旧格式发
+            let frames = limits.encode(&reply.without_error_code());
+            sent = sent && send_frames(&mut ws_sender, frames).await.is_ok();
+        }
+        if !sent {
+            break;
+        }
+        match ws_receiver.next().await {
+            Some(Ok(Message::Text(text))) => match limits.decode(&text) {
+                Ok(GameMessage::DemoEdit { action }) => {
+                    replies = demo
+                        .lock()
+                        .await
+                        .apply(action)
+                        .unwrap_or_else(|e| vec![e.into()]);
+                }
+                Ok(_) => {
+                    replies.push(GameError::Protocol("讲解时只能发送 DemoEdit".to_string()).into())
+                }
+                Err(e) => replies.push(e.into()),
+            },
+            Some(Ok(_)) => {}
+            _ => break,
+        }
+    }
+    rooms.lock().await.close_demo(&demo_id).await;
+}
+
+// 演示房间的观众：连接交给房间的广播任务，可以请求 Resync，房间关闭或连接断开时结束
+async fn view_demo(
+    ws_sender: SplitSink<WebSocketStream<TcpStream>, Message>,
+    mut ws_receiver: SplitStream<WebSocketStream<TcpStream>>,
+    limits: &MessageLimits,
+    demo: Arc<Mutex<DemoRoom>>,
+) {
+    let (id, mut released) = demo.lock().await.add_viewer(Spectator::new(ws_sender));
+    loop {
+        tokio::select! {
+            // 房间已关闭，DemoClosed 已经发出
+            _ = &mut released => return,
+            frame = ws_receiver.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    let demo = demo.lock().await;
+                    let reply = match limits.decode(&text) {
+                        Ok(GameMessage::Resync) => demo.status(),
+                        _ => GameError::Protocol("观看演示时只能请求 Resync".to_string()).into(),
+                    };
+                    demo.send_to_viewer(id, reply.without_error_code());
+                }
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+        }
+    }
+    demo.lock().await.remove_viewer(id);
+}
+
+// 单条消息超过该时长仍未发出即认为连接变差
+const DEGRADED_SEND_THRESHOLD: Duration = Duration::from_secs(2);
+// 排队时检查空位的间隔
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 排队位置不变时也定期推送一次排队状态
+const QUEUE_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+// 管理员让服务器停止开新局后，回复新来的和排队中的玩家
+const DRAINING_MESSAGE: &str = "服务器即将维护，暂不开始新的对局";
+
+fn direction_name(direction: LineDirection) -> &'static str {
+    match direction {
+        LineDirection::Horizontal => "水平",
+        LineDirection::Vertical => "垂直",
+        LineDirection::Diagonal => "对角线",
+        LineDirection::AntiDiagonal => "反对角线",
+    }
+}
+
+pub struct NetworkPlayer {
+    stream: TcpStream,
+    rooms: Arc<Mutex<RoomManager>>,
+    user_manager: Arc<RwLock<UserManager>>,
+    archive: Arc<Mutex<GameArchive>>,
+}
+impl NetworkPlayer {
+    pub fn new(
+        stream: TcpStream,
+        rooms: Arc<Mutex<RoomManager>>,
+        user_manager: Arc<RwLock<UserManager>>,
+        archive: Arc<Mutex<GameArchive>>,
+    ) -> Self {
+        Self {
+            stream,
+            rooms,
+            user_manager,
+            archive,
+        }
+    }
+    pub async fn play(self) {
+        let (limits, strict) = {
+            let rooms = self.rooms.lock().await;
+            (rooms.config().limits, rooms.config().strict)
+        };
+        let metrics = self.rooms.lock().await.metrics();
+        let mut violations = ViolationTracker::new(strict);
+        let ws_stream = accept_async_with_config(self.stream, Some(limits.websocket_config()))
+            .await
+            .unwrap();
+        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+        let (tx, mut rx) = mpsc::channel(32);
+
+        // 等待客户端发送用户名，在此之前可以先注册或登录换取令牌
+        let (username, token, vs_ai, invite, resume, preferred_role, mut session) = loop {
+            let text = match ws_receiver.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                _ => {
+                    println!("连接失败：无法读取用户名");
+                    let _ = ws_sender
+                        .send(Message::Text(
+                            serde_json::to_string(&GameMessage::Error("连接失败".into())).unwrap(),
+                        ))
+                        .await;
+                    return;
+                }
+            };
+            let reply = match limits.decode(&text) {
+                Ok(GameMessage::ConnectRequest {
+                    username,
+                    token,
+                    play_vs_ai,
+                    invite,
+                    protocol_version,
+                    capabilities,
+                    preferred_role,
+                    resume,
+                }) => {
+                    let session = match Session::negotiate(protocol_version, &capabilities) {
+                        Ok(session) => session,
+                        Err(e) => {
+                            println!("拒绝玩家 {} 连接: {}", username, e);
+                            let _ = send_frames(
+                                &mut ws_sender,
+                                limits.encode(&GameMessage::from(e).without_error_code()),
+                            )
+                            .await;
+                            continue;
+                        }
+                    };
+                    let Some(invite) = invite else {
+                        println!("新玩家 {} 正在连接...", username);
+                        // 恢复的对局对手是原来的玩家，不再配电脑对手
+                        let play_vs_ai = play_vs_ai.filter(|_| resume.is_none());
+                        break (
+                            username,
+                            token,
+                            play_vs_ai,
+                            None,
+                            resume,
+                            preferred_role,
+                            session,
+                        );
+                    };
+                    match self.user_manager.read().await.verify_invite(&invite) {
+                        Some(Invite {
+                            role: InviteRole::Spectator,
+                            game_id,
+                            ..
+                        }) => {
+                            println!("观战邀请，观看对局 {}", game_id);
+                            let claim = spectate(
+                                ws_sender,
+                                ws_receiver,
+                                &self.rooms,
+                                &self.archive,
+                                game_id,
+                            )
+                            .await;
+                            let Some(claim) = claim else {
+                                return;
+                            };
+                            ws_sender = claim.ws_sender;
+                            ws_receiver = claim.ws_receiver;
+                            break claimed_seat(claim.invite);
+                        }
+                        // 按邀请入座时不再配电脑对手
+                        Some(invite) => {
+                            println!("新玩家 {} 持邀请连接房间 {}", username, invite.room);
+                            break (username, token, None, Some(invite), None, None, session);
+                        }
+                        None => GameError::Unauthorized("邀请无效或已过期".to_string()).into(),
+                    }
+                }
+                Ok(GameMessage::ListGames { filter }) => GameMessage::GameList {
+                    page: list_games(&self.rooms, &self.archive, &filter).await,
+                },
+                Ok(GameMessage::Watch { game_id }) => {
+                    let demo = self.rooms.lock().await.demo(&game_id);
+                    if let Some(demo) = demo {
+                        println!("观众进入演示房间 {}", game_id);
+                        view_demo(ws_sender, ws_receiver, &limits, demo).await;
+                        return;
+                    }
+                    println!("观战者请求观看对局 {}", game_id);
+                    let claim =
+                        spectate(ws_sender, ws_receiver, &self.rooms, &self.archive, game_id).await;
+                    let Some(claim) = claim else {
+                        return;
+                    };
+                    ws_sender = claim.ws_sender;
+                    ws_receiver = claim.ws_receiver;
+                    break claimed_seat(claim.invite);
+                }
+                Ok(GameMessage::CreateDemo { title, token }) => {
+                    let owner = self
+                        .user_manager
+                        .read()
+                        .await
+                        .token_user(&token)
+                        .map(|user| user.id.clone());
+                    match owner {
+                        Some(owner) => {
+                            present(ws_sender, ws_receiver, &self.rooms, title, owner).await;
+                            return;
+                        }
+                        None => GameError::Unauthorized("开演示房间需要先登录".to_string()).into(),
+                    }
+                }
+                Ok(GameMessage::Register { username, password }) => {
+                    println!("用户 {} 请求注册", username);
+                    match register_user(&self.user_manager, &username, &password).await {
+                        Ok(token) => GameMessage::AuthToken {
+                            token,
+                            unfinished: Vec::new(),
+                        },
+                        Err(e) => e.into(),
+                    }
+                }
+                Ok(GameMessage::SetFeature {
+                    token,
+                    game_type,
+                    feature,
+                    enabled,
+                }) => {
+                    if self.user_manager.read().await.is_admin(&token) {
+                        let mut rooms = self.rooms.lock().await;
+                        rooms.set_feature(game_type, feature, enabled).await;
+                        GameMessage::Features {
+                            game_type,
+                            enabled: rooms.enabled_features(game_type),
+                        }
+                    } else {
+                        GameError::Unauthorized("需要管理员权限".to_string()).into()
+                    }
+                }
+                Ok(GameMessage::SetTelemetry { token, enabled }) => {
+                    if self.user_manager.read().await.is_admin(&token) {
+                        let rooms = self.rooms.lock().await;
+                        rooms.telemetry().set_enabled(enabled);
+                        GameMessage::TelemetryStatus { enabled }
+                    } else {
+                        GameError::Unauthorized("需要管理员权限".to_string()).into()
+                    }
+                }
+                Ok(GameMessage::Login { username, password }) => {
+                    println!("用户 {} 请求登录", username);
+                    match authenticate_user(&self.user_manager, &username, &password).await {
+                        // 顺便告诉用户还有哪些对局没下完
+                        Ok(token) => GameMessage::AuthToken {
+                            token,
+                            unfinished: self.rooms.lock().await.unfinished_games(&username).await,
+                        },
+                        Err(e) => e.into(),
+                    }
+                }
+                Ok(GameMessage::ServerInfoRequest) => {
+                    GameMessage::ServerInfo(ServerInfo::new(self.rooms.lock().await.config()))
+                }
+                Ok(_) => {
+                    println!("无效的连接消息类型");
+                    let e = GameError::Protocol("无效的连接消息类型".to_string());
+                    violations
+                        .record(Violation::Unexpected, &e.to_string(), &metrics)
+                        .unwrap_or_else(|| e.into())
+                }
+                Err(e) => {
+                    println!("解析连接消息失败: {}", e);
+                    violations
+                        .record(Violation::Malformed, &e.to_string(), &metrics)
+                        .unwrap_or_else(|| e.into())
+                }
+            };
+            // 客户端在 ConnectRequest 里才声明能力，这之前的错误按旧格式发
+            let reply = reply.without_error_code();
+            let _ = send_frames(&mut ws_sender, limits.encode(&reply)).await;
+            if matches!(reply, GameMessage::ProtocolViolation { .. }) {
+                println!("连接违反协议，已断开");
+                let _ = ws_sender.close().await;
+                return;
+            }
+        };
+
+        // 创建用户
+        let (user, authenticated) = {
+            let mut user_manager = self.user_manager.write().await;
+            match user_manager.connect(&username, token.as_deref()) {
+                Ok((user, authenticated)) => {
+                    println!("用户登录: {} (已认证: {})", user.name, authenticated);
+                    (user, authenticated)
+                }
+                Err(e) => {
+                    println!("用户 {} 登录失败: {}", username, e);
+                    reject(&mut ws_sender, &limits, &mut session, e).await;
+                    return;
+                }
+            }
+        };
+        // 游客没填用户名时由服务器生成，之后都用实际的用户名
+        let username = user.name.clone();
+
+        // 掉线重连或者从其他设备接管时回到原来的座位
+        let reclaimed = reclaim_seat(
+            &self.rooms,
+            &self.user_manager,
+            &user.id,
+            authenticated,
+            &tx,
+        )
+        .await;
+        let (mut room, game, mut player) = match reclaimed {
+            Some(seat) => seat,
+            None => {
+                if let Err(e) = check_can_play(&self.rooms, &self.user_manager, &user.id).await {
+                    println!("用户 {} 暂时不能入座: {}", user.name, e);
+                    reject(&mut ws_sender, &limits, &mut session, e).await;
+                    return;
+                }
+
+                // 恢复停服前没下完的对局，只有登录用户可以
+                let resumed = match &resume {
+                    Some(_) if !authenticated => {
+                        Err(GameError::Unauthorized("恢复对局需要先登录".to_string()))
+                    }
+                    Some(game_id) => {
+                        let mut rooms = self.rooms.lock().await;
+                        rooms
+                            .resume(game_id, &username)
+                            .await
+                            .map(|(room, game, role)| Some(((room, game), rooms, role)))
+                    }
+                    None => Ok(None),
+                };
+                let resumed = match resumed {
+                    Ok(resumed) => resumed,
+                    Err(e) => {
+                        println!("用户 {} 恢复对局失败: {}", username, e);
+                        reject(&mut ws_sender, &limits, &mut session, e).await;
+                        self.user_manager.write().await.logout(&user.id);
+                        return;
+                    }
+                };
+                let mut resumed_role = None;
+
+                // 找房间入座，房间都满时排队，按先来后到等待空位；持邀请的直接去邀请的房间
+                let invited_room = match &invite {
+                    Some(invite) => {
+                        let rooms = self.rooms.lock().await;
+                        rooms
+                            .room(invite.room)
+                            .map(|game| ((invite.room, game), rooms))
+                    }
+                    None => None,
+                };
+                let ((room, game), rooms) = if let Some((seat, rooms, role)) = resumed {
+                    resumed_role = Some(role);
+                    (seat, rooms)
+                } else if let Some(seat) = invited_room {
+                    seat
+                } else if invite.is_some() {
+                    let expired = GameError::Unavailable("邀请已失效".to_string());
+                    reject(&mut ws_sender, &limits, &mut session, expired).await;
+                    self.user_manager.write().await.logout(&user.id);
+                    return;
+                } else {
+                    let mut queue = Matchmaking::new();
+                    loop {
+                        match queue.step(&self.rooms, vs_ai.is_some(), &username).await {
+                            QueueStep::Seated(seat, rooms) => break (seat, rooms),
+                            QueueStep::Draining => {
+                                println!("服务器准备维护，玩家 {} 离开队列", username);
+                                let draining = GameError::Unavailable(DRAINING_MESSAGE.to_string());
+                                reject(&mut ws_sender, &limits, &mut session, draining).await;
+                                self.user_manager.write().await.logout(&user.id);
+                                return;
+                            }
+                            QueueStep::Waiting(status) => {
+                                if let Some(status) = status {
+                                    let _ = ws_sender
+                                        .send(Message::Text(
+                                            serde_json::to_string(&status).unwrap(),
+                                        ))
+                                        .await;
+                                }
+                                if !wait_in_queue(&mut ws_receiver).await {
+                                    println!("玩家 {} 排队时断开连接", username);
+                                    queue.leave(&self.rooms).await;
+                                    self.user_manager.write().await.logout(&user.id);
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                };
+
+                // 获取当前游戏状态
+                let mut game_guard = game.lock().await;
+                let seat = match (&invite, resumed_role) {
+                    (_, Some(role)) => Some(SeatRequest::Exact(role)),
+                    (
+                        Some(Invite {
+                            role: InviteRole::Seat(role),
+                            game_id,
+                            ..
+                        }),
+                        None,
+                    ) => (game_guard.id() == game_id && game_guard.seat_is_free(*role))
+                        .then_some(SeatRequest::Exact(*role)),
+                    _ => Some(SeatRequest::Prefer(preferred_role)),
+                };
+                let Some(seat) = seat else {
+                    println!("邀请已失效，拒绝连接");
+                    let expired = GameError::Unavailable("邀请已失效".to_string());
+                    reject(&mut ws_sender, &limits, &mut session, expired).await;
+                    drop(game_guard);
+                    self.user_manager.write().await.logout(&user.id);
+                    return;
+                };
+
+                let player =
+                    match take_seat(&self.user_manager, &mut game_guard, room, seat, &user, &tx)
+                        .await
+                    {
+                        Ok(player) => player,
+                        Err(e) => {
+                            reject(&mut ws_sender, &limits, &mut session, e).await;
+                            return;
+                        }
+                    };
+                // 电脑对手坐到另一边，不需要第二个连接
+                if let Some(difficulty) = vs_ai {
+                    let (ai_tx, ai_rx) = mpsc::channel(32);
+                    let ai_role = player.other();
+                    let engine = rooms.config().opponent_engine(game_guard.seed());
+                    AIPlayer::with_difficulty(ai_role, game.clone(), difficulty)
+                        .with_engine(engine)
+                        .with_throttle(rooms.ai_throttle())
+                        .start(ai_rx);
+                    let ai_name = format!("电脑({:?})", difficulty);
+                    if let Err(e) = game_guard.add_player(ai_role, ai_name, ai_tx).await {
+                        println!("添加电脑对手失败: {}", e);
+                    }
+                }
+                drop(rooms);
+                game_guard.set_authenticated(player, authenticated);
+
+                drop(game_guard); // 释放锁
+                (room, game, player)
+            }
+        };
+
+        // 发送连接成功消息
+        let connected = connect_response(&self.user_manager, &user, &game, player, &session).await;
+        let _ = ws_sender
+            .send(Message::Text(serde_json::to_string(&connected).unwrap()))
+            .await;
+        println!("发送连接成功消息给玩家 {}", user.name);
+
+        // 处理游戏消息
+        let mut game_clone = game.clone();
+        let user_manager_clone = self.user_manager.clone();
+        // 克隆 username 用于消息处理
+        let username_clone = username.clone();
+        // 换对手后座位会变，网络状态发给当前所在的对局
+        let (seat_tx, seat_rx) = watch::channel((game.clone(), player));
+        // 发送任务拿走了 session，换对手后的连接成功消息还要用
+        let negotiated = session.clone();
+        let (heartbeat_interval, idle_timeout) = self.rooms.lock().await.config().heartbeat();
+        let connection = self.rooms.lock().await.track_connection();
+        tokio::spawn(async move {
+            // 任务结束时释放，停服时据此判断消息都发完了
+            let _connection = connection;
+            let mut degraded = false;
+            let mut outbox = Outbox::new();
+            let mut next_ping = tokio::time::Instant::now() + heartbeat_interval;
+            // 另起任务转发，避免和持有游戏锁的发送方互相等待
+            let relay = |state: PresenceState| {
+                println!("玩家 {} 网络状态变化: {:?}", username_clone, state);
+                let (game, player) = seat_rx.borrow().clone();
+                tokio::spawn(async move {
+                    game.lock().await.relay_presence(player, state).await;
+                });
+            };
+            loop {
+                // 空闲时等待新消息或者下一次 Ping
+                if outbox.is_empty() {
+                    tokio::select! {
+                        msg = rx.recv() => match msg {
+                            Some(msg) => outbox.push(msg),
+                            None => break,
+                        },
+                        _ = tokio::time::sleep_until(next_ping) => {}
+                    }
+                }
+                // 积压时也要按时发 Ping，客户端回复的 Pong 会刷新读循环的超时
+                if tokio::time::Instant::now() >= next_ping {
+                    next_ping = tokio::time::Instant::now() + heartbeat_interval;
+                    if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
+                }
+                // 收下已经到达的全部消息，关键消息优先发送
+                while let Ok(msg) = rx.try_recv() {
+                    outbox.push(msg);
+                }
+                // 客户端读得太慢，关键消息都积压到上限了：断开连接，按掉线处理
+                if outbox.overflowed() {
+                    println!(
+                        "玩家 {} 积压的消息过多，断开连接（已丢弃 {} 条后台消息）",
+                        username_clone,
+                        outbox.dropped()
+                    );
+                    let _ = ws_sender.close().await;
+                    break;
+                }
+                let Some(msg) = outbox.pop() else {
+                    continue;
+                };
+                let msg = session.filter(msg);
+                println!("发送消息给玩家 {}: {:?}", username_clone, msg);
+                // 分段发送时 send 借用着 ws_sender，放在单独的块里
+                let stalled = {
+                    let send = send_frames(&mut ws_sender, session.encode(&limits, &msg));
+                    tokio::pin!(send);
+
+                    // 发送迟迟完成不了时立即告诉对手该玩家网络不佳，连接完全卡住也能发现
+                    let stalled = tokio::select! {
+                        _ = &mut send => false,
+                        _ = tokio::time::sleep(DEGRADED_SEND_THRESHOLD) => true,
+                    };
+                    if stalled {
+                        if !degraded {
+                            degraded = true;
+                            relay(PresenceState::ConnectionDegraded);
+                        }
+                        let _ = send.await;
+                    }
+                    stalled
+                };
+                if !stalled && degraded {
+                    degraded = false;
+                    relay(PresenceState::Idle);
+                }
+                // 会话转移到新设备、被管理员移出或者停服后关闭连接
+                if matches!(
+                    msg,
+                    GameMessage::SessionTransferred
+                        | GameMessage::Kicked { .. }
+                        | GameMessage::ProtocolViolation { .. }
+                        | GameMessage::ServerShutdown
+                ) {
+                    let _ = ws_sender.close().await;
+                    break;
+                }
+            }
+        });
+
+        // 接收玩家移动，任何帧（包括 Pong）都说明连接还活着
+        let mut leaving = false;
+        loop {
+            let frame = tokio::select! {
+                frame = tokio::time::timeout(idle_timeout, ws_receiver.next()) => frame,
+                // 发送任务已经结束（写失败或者连接被关闭），不用等到读超时
+                _ = tx.closed() => {
+                    println!("玩家 {} 的发送任务已结束，视为断开", username);
+                    break;
+                }
+            };
+            let msg = match frame {
+                Ok(Some(Ok(msg))) => msg,
+                Ok(_) => break,
+                Err(_) => {
+                    println!(
+                        "玩家 {} 超过 {} 秒没有响应，视为断开",
+                        username,
+                        idle_timeout.as_secs()
+                    );
+                    break;
+                }
+            };
+            let received = Instant::now();
+            if let Message::Text(text) = msg {
+                println!("收到玩家 {} 的消息: {}", username, text);
+                match limits.decode(&text) {
+                    Ok(GameMessage::Move {
+                        row,
+                        col,
+                        game_id,
+                        move_seq,
+                        client_nonce,
+                        second,
+                    }) => {
+                        println!(
+                            "玩家 {} ({:?}) 尝试移动: ({}, {})",
+                            username, player, row, col
+                        );
+                        let stones: Vec<_> = std::iter::once((row, col)).chain(second).collect();
+                        let parsed = Instant::now();
+                        metrics.record(MoveStage::Parse, parsed - received);
+                        let mut game = game_clone.lock().await;
+                        metrics.record(MoveStage::LockWait, parsed.elapsed());
+                        let out_of_turn = game.out_of_turn(player);
+                        let result = game
+                            .submit_move(player, &stones, &game_id, move_seq, client_nonce)
+                            .await;
+                        drop(game);
+                        metrics.record(MoveStage::Total, received.elapsed());
+                        match result {
+                            // 重发已经确认过的一步不算违规，它不会出错
+                            Err(e) if out_of_turn => {
+                                println!("玩家 {} 不在自己的回合落子: {}", username, e);
+                                let violation = violations.record(
+                                    Violation::OutOfTurn,
+                                    &e.to_string(),
+                                    &metrics,
+                                );
+                                if let Some(violation) = violation {
+                                    let _ = tx.send(violation).await;
+                                    break;
+                                }
+                                let _ = tx.send(e.into()).await;
+                            }
+                            Err(e) => {
+                                println!("移动失败: {}", e);
+                                let _ = tx.send(e.into()).await;
+                            }
+                            Ok(Some(MoveOutcome::Win(line))) => println!(
+                                "游戏结束！玩家 {:?} 沿{}方向连成 {} 子获胜",
+                                line.player,
+                                direction_name(line.direction),
+                                line.cells.len()
+                            ),
+                            Ok(Some(MoveOutcome::Draw(reason))) => {
+                                println!("游戏结束！平局！({:?})", reason)
+                            }
+                            Ok(_) => println!("移动成功: ({}, {})", row, col),
+                        }
+                    }
+                    Ok(GameMessage::Resync) => {
+                        let status = game_clone.lock().await.status();
+                        let _ = tx.send(status).await;
+                    }
+                    Ok(GameMessage::Resign) => {
+                        let result = game_clone.lock().await.resign(player).await;
+                        if let Err(e) = result {
+                            let _ = tx.send(e.into()).await;
+                        }
+                    }
+                    Ok(GameMessage::OfferDraw) => {
+                        let result = game_clone.lock().await.offer_draw(player).await;
+                        if let Err(e) = result {
+                            let _ = tx.send(e.into()).await;
+                        }
+                    }
+                    Ok(GameMessage::AnswerDraw { accept }) => {
+                        let result = game_clone.lock().await.answer_draw(player, accept).await;
+                        if let Err(e) = result {
+                            let _ = tx.send(e.into()).await;
+                        }
+                    }
+                    Ok(GameMessage::Goodbye) => {
+                        leaving = true;
+                        break;
+                    }
+                    Ok(GameMessage::FindOpponent) => {
+                        if !game_clone.lock().await.is_finished() {
+                            let busy = GameError::Unavailable("对局还没有结束".to_string());
+                            let _ = tx.send(busy.into()).await;
+                            continue;
+                        }
+                        println!("玩家 {} ({:?}) 寻找新对手", username, player);
+                        game_clone.lock().await.leave(player).await;
+                        user_manager_clone
+                            .write()
+                            .await
+                            .release_seat(&user.id, room, player);
+                        let found = find_opponent(
+                            &self.rooms,
+                            &self.user_manager,
+                            &user,
+                            authenticated,
+                            preferred_role,
+                            &tx,
+                            &mut ws_receiver,
+                        )
+                        .await;
+                        match found {
+                            Ok(Some(seat)) => {
+                                (room, game_clone, player) = seat;
+                                let _ = seat_tx.send((game_clone.clone(), player));
+                                let connected = connect_response(
+                                    &self.user_manager,
+                                    &user,
+                                    &game_clone,
+                                    player,
+                                    &negotiated,
+                                )
+                                .await;
+                                let _ = tx.send(connected).await;
+                            }
+                            // 没有座位了，和连接时一样结束会话
+                            Ok(None) => {
+                                println!("玩家 {} 排队时断开连接", username);
+                                user_manager_clone.write().await.logout(&user.id);
+                                return;
+                            }
+                            Err(e) => {
+                                println!("用户 {} 暂时不能入座: {}", user.name, e);
+                                let _ = tx.send(e.into()).await;
+                                user_manager_clone.write().await.logout(&user.id);
+                                return;
+                            }
+                        }
+                    }
+                    Ok(GameMessage::HintRequest) => {
+                        let request = game_clone.lock().await.take_hint(player);
+                        let reply = match request {
+                            // 搜索放到阻塞线程里，不占着对局锁
+                            Ok((board, budget, hints_left)) => {
+                                let search = tokio::task::spawn_blocking(move || {
+                                    suggest_move(&board, player, budget)
+                                });
+                                match search.await {
+                                    Ok(Some(hint)) => GameMessage::Hint {
+                                        row: hint.row,
+                                        col: hint.col,
+                                        score: hint.score,
+                                        hints_left,
+                                    },
+                                    _ => {
+                                        game_clone.lock().await.refund_hint(player);
+                                        GameError::Unavailable("没有可以提示的位置".to_string())
+                                            .into()
+                                    }
+                                }
+                            }
+                            Err(e) => e.into(),
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::CreateInvite { role, ttl_secs }) => {
+                        let seat = user_manager_clone.read().await.seat_of(&user.id);
+                        let game = game_clone.lock().await;
+                        let reply = match (seat, role) {
+                            (Some(_), InviteRole::Seat(seat)) if !game.seat_is_free(seat) => {
+                                GameError::Unavailable(format!("{:?} 的座位已经有人了", seat))
+                                    .into()
+                            }
+                            (Some((room, _)), role) => {
+                                let ttl = ttl_secs.unwrap_or(DEFAULT_INVITE_TTL_SECS);
+                                let invite = Invite::new(room, game.id().to_string(), role, ttl);
+                                GameMessage::InviteCreated {
+                                    token: user_manager_clone.read().await.issue_invite(&invite),
+                                    role,
+                                    expires_at: invite.expires_at,
+                                }
+                            }
+                            (None, _) => GameError::NotFound("不在对局中".to_string()).into(),
+                        };
+                        drop(game);
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::AllowSubstitutes { allowed }) => {
+                        let result = game_clone.lock().await.allow_substitutes(player, allowed);
+                        if let Err(e) = result {
+                            let _ = tx.send(e.into()).await;
+                        }
+                    }
+                    Ok(GameMessage::LeaderboardRequest { limit }) => {
+                        let entries = user_manager_clone
+                            .read()
+                            .await
+                            .leaderboard(limit.min(MAX_LEADERBOARD_SIZE));
+                        let _ = tx.send(GameMessage::Leaderboard { entries }).await;
+                    }
+                    Ok(GameMessage::GetStats { user_id }) => {
+                        let reply = match user_manager_clone.read().await.get_user(&user_id) {
+                            Some(user) => GameMessage::Stats {
+                                username: user.name.clone(),
+                                stats: user.stats.clone(),
+                            },
+                            None => GameError::NotFound(format!("找不到用户 {}", user_id)).into(),
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::SetNarration { enabled }) => {
+                        let mut game = game_clone.lock().await;
+                        if enabled && !game.feature_enabled(Feature::Narration) {
+                            let _ = tx.send(feature_disabled(Feature::Narration)).await;
+                        } else {
+                            game.set_narration(player, enabled);
+                        }
+                    }
+                    Ok(GameMessage::SetPresence { state }) => {
+                        game_clone.lock().await.relay_presence(player, state).await;
+                    }
+                    Ok(GameMessage::SendChat { text }) => {
+                        let result = game_clone.lock().await.relay_chat(&username, &text).await;
+                        if let Err(e) = result {
+                            let _ = tx.send(e.into()).await;
+                        }
+                    }
+                    Ok(GameMessage::ExportGame) => {
+                        let game = game_clone.lock().await;
+                        let reply = if game.feature_enabled(Feature::Analysis) {
+                            GameMessage::GameRecord {
+                                sgf: game.export_sgf(),
+                            }
+                        } else {
+                            feature_disabled(Feature::Analysis)
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::ReplayRequest { game_id }) => {
+                        if !game_clone.lock().await.feature_enabled(Feature::Replay) {
+                            let _ = tx.send(feature_disabled(Feature::Replay)).await;
+                            continue;
+                        }
+                        let game = self.archive.lock().await.get(&game_id).cloned();
+                        let reply = match game {
+                            Some(game) => GameMessage::Replay { game },
+                            None => GameError::NotFound(format!("找不到对局 {}", game_id)).into(),
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::EvaluateRequest { game_id }) => {
+                        if !game_clone.lock().await.feature_enabled(Feature::Analysis) {
+                            let _ = tx.send(feature_disabled(Feature::Analysis)).await;
+                            continue;
+                        }
+                        // 存档里只有已结束的对局
+                        let moves = self
+                            .archive
+                            .lock()
+                            .await
+                            .get(&game_id)
+                            .map(|game| (game.moves.clone(), game.rules));
+                        let reply = match moves {
+                            Some((moves, rules)) => GameMessage::EvaluationGraph {
+                                scores: evaluation_graph(&moves, rules),
+                                game_id,
+                            },
+                            None => GameError::NotFound(format!("找不到已结束的对局 {}", game_id))
+                                .into(),
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::PositionSearch { moves }) => {
+                        if !game_clone.lock().await.feature_enabled(Feature::Analysis) {
+                            let _ = tx.send(feature_disabled(Feature::Analysis)).await;
+                            continue;
+                        }
+                        let reply = match search_position(&*self.archive.lock().await, &moves) {
+                            Ok(games) => GameMessage::PositionGames(games),
+                            Err(e) => e.into(),
+                        };
+                        let _ = tx.send(reply).await;
+                    }
+                    Ok(GameMessage::ServerInfoRequest) => {
+                        let info = ServerInfo::new(self.rooms.lock().await.config());
+                        let _ = tx.send(GameMessage::ServerInfo(info)).await;
+                    }
+                    Err(e) => {
+                        let violation =
+                            violations.record(Violation::Malformed, &e.to_string(), &metrics);
+                        if let Some(violation) = violation {
+                            let _ = tx.send(violation).await;
+                            break;
+                        }
+                        let _ = tx.send(e.into()).await;
+                    }
+                    // 对局中不处理的消息，严格模式下算违规
+                    Ok(msg) => {
+                        let message = format!("对局中不能发送 {}", msg.kind());
+                        if let Some(violation) =
+                            violations.record(Violation::Unexpected, &message, &metrics)
+                        {
+                            println!("玩家 {} {}", username, message);
+                            let _ = tx.send(violation).await;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        // 处理断开连接
+        let paused = {
+            let mut game = game_clone.lock().await;
+            // 座位已被新设备接管，不能把玩家移出对局
+            if !game.is_seated(player, &tx) {
+                println!("玩家 {} 的旧连接已关闭，会话已转移", user.name);
+                return;
+            }
+            // 主动离开的不等重连
+            if leaving {
+                println!("玩家 {} ({:?}) 主动离开", user.name, player);
+                if game.leave(player).await {
+                    user_manager_clone.write().await.record_abandon(&user.id);
+                }
+                drop(game);
+                user_manager_clone.write().await.logout(&user.id);
+                return;
+            }
+            println!("玩家 {} ({:?}) 断开连接", user.name, player);
+            let grace = game.reconnect_grace();
+            game.disconnect(player)
+                .await
+                .map(|resumed| (resumed, grace))
+        };
+
+        // 对局进行中掉线时保留座位，宽限期内重连可以继续
+        if let Some((mut resumed, grace)) = paused {
+            if let Ok(Ok(())) = tokio::time::timeout(grace, &mut resumed).await {
+                return;
+            }
+            let mut game = game_clone.lock().await;
+            match resumed.try_recv() {
+                // 超时的同时刚好重连上
+                Ok(()) => return,
+                Err(oneshot::error::TryRecvError::Empty) => {
+                    game.forfeit(player).await;
+                    user_manager_clone.write().await.record_abandon(&user.id);
+                }
+                // 对局已经重置
+                Err(oneshot::error::TryRecvError::Closed) => {}
+            }
+        }
+        user_manager_clone.write().await.logout(&user.id);
+    }
+}
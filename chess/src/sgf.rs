@@ -0,0 +1,56 @@
+use crate::{MoveRecord, PlayerRole};
+
+// 五子棋在 SGF 中的游戏类型编号
+const SGF_GAME_GOMOKU: u32 = 4;
+
+// SGF 坐标：先列后行，a 表示 0
+fn sgf_coord(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
+
+// 转义属性值中的 ] 和 \
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+fn color(player: PlayerRole) -> &'static str {
+    match player {
+        PlayerRole::Black => "B",
+        PlayerRole::White => "W",
+    }
+}
+
+// result: None 表示对局未结束，Some(None) 表示平局
+pub fn to_sgf(
+    moves: &[MoveRecord],
+    black: &str,
+    white: &str,
+    size: usize,
+    result: Option<Option<PlayerRole>>,
+) -> String {
+    let mut sgf = format!(
+        "(;FF[4]GM[{}]CA[UTF-8]SZ[{}]PB[{}]PW[{}]",
+        SGF_GAME_GOMOKU,
+        size,
+        escape(black),
+        escape(white)
+    );
+    if let Some(first) = moves.first() {
+        sgf.push_str(&format!("DT[{}]", first.timestamp.format("%Y-%m-%d")));
+    }
+    match result {
+        Some(Some(winner)) => sgf.push_str(&format!("RE[{}+]", color(winner))),
+        Some(None) => sgf.push_str("RE[0]"),
+        None => {}
+    }
+    for m in moves {
+        sgf.push_str(&format!(
+            ";{}[{}{}]",
+            color(m.player),
+            sgf_coord(m.col),
+            sgf_coord(m.row)
+        ));
+    }
+    sgf.push(')');
+    sgf
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use crate::{RoomManager, SharedStore};
+
+// 停服的结果，退出前打印
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    // 截止时间之前所有连接都把消息发完了
+    pub flushed: bool,
+    // 存下来的未完成对局数
+    pub adjourned: usize,
+}
+
+// 优雅停服，调用前应当已经停止接受新连接：不再开始新的对局，通知所有连接并等它们把消息发完，
+// 再把没下完的对局存起来。到了 deadline 还没发完的不再等
+pub async fn graceful_shutdown(
+    rooms: &Mutex<RoomManager>,
+    store: &SharedStore,
+    deadline: Duration,
+) -> ShutdownReport {
+    let (games, connections) = {
+        let mut rooms = rooms.lock().await;
+        (rooms.games(), rooms.begin_shutdown())
+    };
+    // 存的是通知停服那一刻的局面，之后断线引起的变化不算
+    let mut unfinished = Vec::new();
+    let mut fanouts = Vec::new();
+    for (room, game) in games.into_iter().enumerate() {
+        let mut game = game.lock().await;
+        // 恢复后还在等双方回来的对局原样再存一次
+        if game.is_active() || game.is_reserved() {
+            unfinished.push(game.dump(room));
+        }
+        fanouts.extend(game.shutdown().await);
+    }
+
+    let flush = async {
+        // 没有人往通道里发，读到 None 就是所有发送任务都结束了
+        if let Some(mut connections) = connections {
+            let _ = connections.recv().await;
+        }
+        for fanout in fanouts {
+            let _ = fanout.await;
+        }
+    };
+    let flushed = tokio::time::timeout(deadline, flush).await.is_ok();
+    if !flushed {
+        println!("{} 秒内没能发完所有消息，不再等待", deadline.as_secs());
+    }
+
+    let mut adjourned = 0;
+    let mut store = store.lock().unwrap();
+    for game in &unfinished {
+        match store.save_adjourned(game) {
+            Ok(()) => adjourned += 1,
+            Err(e) => println!("保存房间 {} 的未完成对局失败: {}", game.room, e),
+        }
+    }
+    ShutdownReport { flushed, adjourned }
+}
@@ -0,0 +1,186 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::ai::is_win;
+use crate::{movegen, Board, Contestant, PlayerRole, RulesConfig};
+
+// 批量模拟里一方的下法：在已有棋子附近随机落子，或者交给引擎
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Random,
+    Engine(Contestant),
+}
+
+impl Policy {
+    // "random"，或者 Contestant::parse 认识的 "search:hard"、"mcts" 等
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "random" => Ok(Policy::Random),
+            _ => Contestant::parse(text).map(Policy::Engine),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Policy::Random => "random".to_string(),
+            Policy::Engine(contestant) => contestant.name(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulationConfig {
+    pub black: Policy,
+    pub white: Policy,
+    pub games: usize,
+    pub rules: RulesConfig,
+    // 超过这么多子仍未分胜负按和棋计，None 时下到棋盘满
+    pub max_moves: Option<usize>,
+    // 第 i 盘的随机数种子是 seed + i，和线程怎么分配无关
+    pub seed: u64,
+    // 并行的线程数，0 表示按 CPU 核数
+    pub threads: usize,
+}
+
+impl SimulationConfig {
+    pub fn new(black: Policy, white: Policy, games: usize) -> Self {
+        Self {
+            black,
+            white,
+            games,
+            rules: RulesConfig::default(),
+            max_moves: None,
+            seed: 0,
+            threads: 0,
+        }
+    }
+}
+
+// 一盘模拟的结果，批量工具按行输出成 NDJSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SimulatedGame {
+    pub index: usize,
+    pub seed: u64,
+    pub black: String,
+    pub white: String,
+    pub winner: Option<PlayerRole>,
+    pub length: usize,
+    pub moves: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SimulationSummary {
+    pub games: usize,
+    pub black_wins: usize,
+    pub white_wins: usize,
+    pub draws: usize,
+    pub average_length: f64,
+}
+
+// 不经过网络并行下 games 盘棋。每下完一盘在工作线程上调用一次 on_game，
+// 参数是这一盘和目前已完成的盘数；完成的顺序不固定，按 index 区分
+pub fn simulate(
+    config: &SimulationConfig,
+    on_game: impl Fn(&SimulatedGame, usize) + Sync,
+) -> Result<SimulationSummary, String> {
+    config.rules.validate()?;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build()
+        .map_err(|e| format!("无法创建线程池: {}", e))?;
+    let done = AtomicUsize::new(0);
+    let results: Vec<(Option<PlayerRole>, usize)> = pool.install(|| {
+        (0..config.games)
+            .into_par_iter()
+            .map(|index| {
+                let game = play(config, index);
+                on_game(&game, done.fetch_add(1, Ordering::Relaxed) + 1);
+                (game.winner, game.length)
+            })
+            .collect()
+    });
+
+    let mut summary = SimulationSummary {
+        games: results.len(),
+        ..SimulationSummary::default()
+    };
+    for &(winner, _) in &results {
+        match winner {
+            Some(PlayerRole::Black) => summary.black_wins += 1,
+            Some(PlayerRole::White) => summary.white_wins += 1,
+            None => summary.draws += 1,
+        }
+    }
+    if !results.is_empty() {
+        let total: usize = results.iter().map(|&(_, length)| length).sum();
+        summary.average_length = total as f64 / results.len() as f64;
+    }
+    Ok(summary)
+}
+
+fn play(config: &SimulationConfig, index: usize) -> SimulatedGame {
+    let seed = config.seed.wrapping_add(index as u64);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let policies = [config.black, config.white];
+    let engines = policies.map(|policy| match policy {
+        Policy::Random => None,
+        Policy::Engine(contestant) => Some((
+            contestant.engine.seeded(seed),
+            contestant.difficulty.budget(),
+        )),
+    });
+    let rules = config.rules;
+    let max_moves = config
+        .max_moves
+        .unwrap_or(rules.board_size * rules.board_size);
+    let mut board = Board::with_rules(rules);
+    let mut winner = None;
+
+    'game: while board.moves.len() < max_moves {
+        let player = board.current_player;
+        let side = match player {
+            PlayerRole::Black => 0,
+            PlayerRole::White => 1,
+        };
+        let stones = match &engines[side] {
+            Some((engine, budget)) => engine.choose_turn(&board, player, *budget),
+            None => movegen::candidate_moves(&board.cells, rules, player, usize::MAX)
+                .choose(&mut rng)
+                .into_iter()
+                .copied()
+                .collect(),
+        };
+        if stones.is_empty() {
+            break;
+        }
+        for (row, col) in stones {
+            // 引擎走出非法的棋直接判负
+            if board.make_move(row, col).is_err() {
+                winner = Some(player.other());
+                break 'game;
+            }
+            if is_win(&board.cells, rules, row, col, player) {
+                winner = Some(player);
+                break 'game;
+            }
+            if board.moves.len() >= max_moves || board.draw_reason().is_some() {
+                break 'game;
+            }
+        }
+    }
+
+    SimulatedGame {
+        index,
+        seed,
+        black: config.black.name(),
+        white: config.white.name(),
+        winner,
+        length: board.moves.len(),
+        moves: board.moves.iter().map(|m| (m.row, m.col)).collect(),
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{ArchivedGame, Cipher, PlayerStats, Rating, RoomDump, StoreError, User, UserSession};
+
+// 持久化后端：启动时整体加载，运行中逐条写入
+pub trait Store: Send {
+    fn load_users(&self) -> Result<Vec<User>, StoreError>;
+    fn save_user(&mut self, user: &User) -> Result<(), StoreError>;
+    fn load_sessions(&self) -> Result<Vec<UserSession>, StoreError>;
+    fn save_session(&mut self, session: &UserSession) -> Result<(), StoreError>;
+    fn delete_session(&mut self, session_id: &str) -> Result<(), StoreError>;
+    fn load_games(&self) -> Result<Vec<ArchivedGame>, StoreError>;
+    fn save_game(&mut self, game: &ArchivedGame) -> Result<(), StoreError>;
+    // 等级分按用户 ID 保存
+    fn load_ratings(&self) -> Result<Vec<(String, Rating)>, StoreError>;
+    fn save_rating(&mut self, user_id: &str, rating: &Rating) -> Result<(), StoreError>;
+    // 停服时没下完的对局，按对局 ID 覆盖保存
+    fn load_adjourned(&self) -> Result<Vec<RoomDump>, StoreError>;
+    fn save_adjourned(&mut self, game: &RoomDump) -> Result<(), StoreError>;
+    fn delete_adjourned(&mut self, game_id: &str) -> Result<(), StoreError>;
+}
+
+// UserManager 和 GameArchive 共用同一个后端
+pub type SharedStore = Arc<std::sync::Mutex<dyn Store>>;
+
+pub fn shared(store: impl Store + 'static) -> SharedStore {
+    Arc::new(std::sync::Mutex::new(store))
+}
+
+// 默认后端，重启后数据丢失
+#[derive(Default)]
+pub struct MemoryStore {
+    users: HashMap<String, User>,
+    sessions: HashMap<String, UserSession>,
+    games: Vec<ArchivedGame>,
+    ratings: HashMap<String, Rating>,
+    adjourned: Vec<RoomDump>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn load_users(&self) -> Result<Vec<User>, StoreError> {
+        Ok(self.users.values().cloned().collect())
+    }
+
+    fn save_user(&mut self, user: &User) -> Result<(), StoreError> {
+        self.users.insert(user.id.clone(), user.clone());
+        Ok(())
+    }
+
+    fn load_sessions(&self) -> Result<Vec<UserSession>, StoreError> {
+        Ok(self.sessions.values().cloned().collect())
+    }
+
+    fn save_session(&mut self, session: &UserSession) -> Result<(), StoreError> {
+        self.sessions
+            .insert(session.session_id.clone(), session.clone());
+        Ok(())
+    }
+
+    fn delete_session(&mut self, session_id: &str) -> Result<(), StoreError> {
+        self.sessions.remove(session_id);
+        Ok(())
+    }
+
+    fn load_games(&self) -> Result<Vec<ArchivedGame>, StoreError> {
+        Ok(self.games.clone())
+    }
+
+    fn save_game(&mut self, game: &ArchivedGame) -> Result<(), StoreError> {
+        self.games.push(game.clone());
+        Ok(())
+    }
+
+    fn load_ratings(&self) -> Result<Vec<(String, Rating)>, StoreError> {
+        Ok(self
+            .ratings
+            .iter()
+            .map(|(id, rating)| (id.clone(), *rating))
+            .collect())
+    }
+
+    fn save_rating(&mut self, user_id: &str, rating: &Rating) -> Result<(), StoreError> {
+        self.ratings.insert(user_id.to_string(), *rating);
+        Ok(())
+    }
+
+    fn load_adjourned(&self) -> Result<Vec<RoomDump>, StoreError> {
+        Ok(self.adjourned.clone())
+    }
+
+    fn save_adjourned(&mut self, game: &RoomDump) -> Result<(), StoreError> {
+        self.adjourned.retain(|saved| saved.game_id != game.game_id);
+        self.adjourned.push(game.clone());
+        Ok(())
+    }
+
+    fn delete_adjourned(&mut self, game_id: &str) -> Result<(), StoreError> {
+        self.adjourned.retain(|saved| saved.game_id != game_id);
+        Ok(())
+    }
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+    // 设置后用户名和棋谱加密保存，ID 和时间仍为明文以便查询
+    cipher: Option<Cipher>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                name_key TEXT NOT NULL UNIQUE,
+                session_id TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS credentials (
+                user_id TEXT PRIMARY KEY,
+                password_hash TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS stats (
+                user_id TEXT PRIMARY KEY,
+                data TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS abandons (
+                user_id TEXT PRIMARY KEY,
+                data TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sessions (
+                session_id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS games (
+                id TEXT PRIMARY KEY,
+                black TEXT NOT NULL,
+                white TEXT NOT NULL,
+                ended_at TEXT NOT NULL,
+                record TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS ratings (
+                user_id TEXT PRIMARY KEY,
+                rating REAL NOT NULL,
+                games INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS adjourned_games (
+                id TEXT PRIMARY KEY,
+                started_at TEXT NOT NULL,
+                record TEXT NOT NULL
+            );",
+        )?;
+        // 旧数据库的 users 表没有 name_key，补上这一列，由 load_users 回填
+        if conn.prepare("SELECT name_key FROM users LIMIT 0").is_err() {
+            conn.execute_batch(
+                "ALTER TABLE users ADD COLUMN name_key TEXT;
+                 CREATE UNIQUE INDEX users_name_key ON users (name_key);",
+            )?;
+        }
+        Ok(Self { conn, cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    fn seal(&self, value: &str) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.encrypt(value),
+            None => value.to_string(),
+        }
+    }
+
+    fn unseal(&self, value: String) -> Result<String, StoreError> {
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(&value),
+            None => Ok(value),
+        }
+    }
+
+    // 用户名的查找键。加密后的用户名每次都不一样，唯一约束和查找都靠这一列
+    fn name_key(&self, name: &str) -> String {
+        match &self.cipher {
+            Some(cipher) => cipher.blind_index(name),
+            None => name.to_string(),
+        }
+    }
+
+    // 按用户名查用户 ID，不用解密整张表
+    pub fn user_id_by_name(&self, name: &str) -> Result<Option<String>, StoreError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id FROM users WHERE name_key = ?1",
+                params![self.name_key(name)],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+}
+
+impl Store for SqliteStore {
+    fn load_users(&self) -> Result<Vec<User>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT users.id, users.name, users.session_id, stats.data, credentials.password_hash,
+                    abandons.data, users.name_key
+             FROM users
+             LEFT JOIN stats ON stats.user_id = users.id
+             LEFT JOIN credentials ON credentials.user_id = users.id
+             LEFT JOIN abandons ON abandons.user_id = users.id",
+        )?;
+        type Row = (
+            String,
+            String,
+            String,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+        );
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ))
+            })?
+            .collect::<Result<Vec<Row>, _>>()?;
+        let mut users = Vec::with_capacity(rows.len());
+        for (id, name, session_id, stats, password_hash, abandons, name_key) in rows {
+            let name = self.unseal(name)?;
+            // 旧数据没有查找键，或者后来才开启加密，顺手改成当前的
+            let key = self.name_key(&name);
+            if name_key.as_ref() != Some(&key) {
+                let updated = self.conn.execute(
+                    "UPDATE users SET name_key = ?1 WHERE id = ?2",
+                    params![key, id],
+                );
+                match updated {
+                    Ok(_) => {}
+                    // 加唯一约束之前留下的同名用户：照常加载，查找键留空，等运维改名
+                    Err(rusqlite::Error::SqliteFailure(e, _))
+                        if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+                    {
+                        log::warn!("用户 {} ({}) 和其他用户同名，没有补上查找键", name, id);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            users.push(User {
+                id,
+                name,
+                session_id,
+                player: None,
+                stats: match stats {
+                    Some(stats) => serde_json::from_str(&stats)?,
+                    None => PlayerStats::default(),
+                },
+                password_hash,
+                abandons: match abandons {
+                    Some(abandons) => serde_json::from_str(&abandons)?,
+                    None => Vec::new(),
+                },
+            });
+        }
+        Ok(users)
+    }
+
+    fn save_user(&mut self, user: &User) -> Result<(), StoreError> {
+        // 按 ID 更新；同名的另一个用户会撞上 name_key 的唯一约束
+        self.conn.execute(
+            "INSERT INTO users (id, name, name_key, session_id) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (id) DO UPDATE
+             SET name = excluded.name, name_key = excluded.name_key, session_id = excluded.session_id",
+            params![
+                user.id,
+                self.seal(&user.name),
+                self.name_key(&user.name),
+                user.session_id
+            ],
+        )?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO stats (user_id, data) VALUES (?1, ?2)",
+            params![user.id, serde_json::to_string(&user.stats)?],
+        )?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO abandons (user_id, data) VALUES (?1, ?2)",
+            params![user.id, serde_json::to_string(&user.abandons)?],
+        )?;
+        if let Some(hash) = &user.password_hash {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO credentials (user_id, password_hash) VALUES (?1, ?2)",
+                params![user.id, hash],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn load_sessions(&self) -> Result<Vec<UserSession>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT session_id, user_id, created_at, expires_at FROM sessions")?;
+        let sessions = stmt
+            .query_map([], |row| {
+                Ok(UserSession {
+                    session_id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    created_at: row.get(2)?,
+                    expires_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(sessions)
+    }
+
+    fn save_session(&mut self, session: &UserSession) -> Result<(), StoreError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sessions (session_id, user_id, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                session.session_id,
+                session.user_id,
+                session.created_at,
+                session.expires_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_session(&mut self, session_id: &str) -> Result<(), StoreError> {
+        self.conn.execute(
+            "DELETE FROM sessions WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        Ok(())
+    }
+
+    fn load_games(&self) -> Result<Vec<ArchivedGame>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT record FROM games ORDER BY ended_at")?;
+        let records = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        records
+            .into_iter()
+            .map(|record| Ok(serde_json::from_str(&self.unseal(record)?)?))
+            .collect()
+    }
+
+    fn save_game(&mut self, game: &ArchivedGame) -> Result<(), StoreError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO games (id, black, white, ended_at, record)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                game.id,
+                self.seal(&game.black),
+                self.seal(&game.white),
+                game.ended_at,
+                self.seal(&serde_json::to_string(game)?)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_ratings(&self) -> Result<Vec<(String, Rating)>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT user_id, rating, games FROM ratings")?;
+        let ratings = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    Rating {
+                        rating: row.get(1)?,
+                        games: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(ratings)
+    }
+
+    fn save_rating(&mut self, user_id: &str, rating: &Rating) -> Result<(), StoreError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO ratings (user_id, rating, games) VALUES (?1, ?2, ?3)",
+            params![user_id, rating.rating, rating.games],
+        )?;
+        Ok(())
+    }
+
+    fn load_adjourned(&self) -> Result<Vec<RoomDump>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT record FROM adjourned_games ORDER BY started_at")?;
+        let records = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        records
+            .into_iter()
+            .map(|record| Ok(serde_json::from_str(&self.unseal(record)?)?))
+            .collect()
+    }
+
+    fn save_adjourned(&mut self, game: &RoomDump) -> Result<(), StoreError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO adjourned_games (id, started_at, record) VALUES (?1, ?2, ?3)",
+            params![
+                game.game_id,
+                game.started_at,
+                self.seal(&serde_json::to_string(game)?)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_adjourned(&mut self, game_id: &str) -> Result<(), StoreError> {
+        self.conn.execute(
+            "DELETE FROM adjourned_games WHERE id = ?1",
+            params![game_id],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use crate::{Game, GameMessage, GamePhase, Metrics, PlayerRole};
+
+pub const DEFAULT_MAX_OUT_OF_TURN: u32 = 3;
+
+// 严格模式：协议违规不再只回一条错误，而是发出 ProtocolViolation 后断开连接，
+// 方便机器人的开发者尽早发现问题。默认关闭
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrictMode {
+    pub enabled: bool,
+    // 一个连接上不是自己的回合还落子超过这么多次才断开，偶尔的竞争不算违规
+    pub max_out_of_turn: u32,
+}
+
+impl Default for StrictMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_out_of_turn: DEFAULT_MAX_OUT_OF_TURN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Violation {
+    // 解析不了的消息，包括这个服务器不认识的消息类型
+    Malformed,
+    // 认识的消息，但这时候不该发，例如对局中又发 ConnectRequest
+    Unexpected,
+    // 不是自己的回合还落子
+    OutOfTurn,
+}
+
+// 一个连接上的违规情况，每次违规都计入服务器的统计
+#[cfg(feature = "server")]
+pub struct ViolationTracker {
+    strict: StrictMode,
+    out_of_turn: u32,
+}
+
+#[cfg(feature = "server")]
+impl ViolationTracker {
+    pub fn new(strict: StrictMode) -> Self {
+        Self {
+            strict,
+            out_of_turn: 0,
+        }
+    }
+
+    // 记下一次违规。严格模式下该断开时返回发给客户端的 ProtocolViolation，否则照旧处理
+    pub fn record(
+        &mut self,
+        violation: Violation,
+        message: &str,
+        metrics: &Metrics,
+    ) -> Option<GameMessage> {
+        metrics.record_violation(violation);
+        if !self.strict.enabled {
+            return None;
+        }
+        if violation == Violation::OutOfTurn {
+            self.out_of_turn += 1;
+            if self.out_of_turn <= self.strict.max_out_of_turn {
+                return None;
+            }
+        }
+        metrics.record_strict_disconnect();
+        Some(GameMessage::ProtocolViolation {
+            violation,
+            message: message.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "server")]
+impl Game {
+    // 对局进行中，却不是这位玩家的回合
+    pub(crate) fn out_of_turn(&self, player: PlayerRole) -> bool {
+        self.phase == GamePhase::Playing && self.board.current_player != player
+    }
+}
+
+#[cfg(feature = "server")]
+impl GameMessage {
+    // 消息类型的名字，写进违规说明里。和序列化时的外层标签一致，新加的消息要在这里补上
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            GameMessage::ConnectRequest { .. } => "ConnectRequest",
+            GameMessage::Register { .. } => "Register",
+            GameMessage::Login { .. } => "Login",
+            GameMessage::AuthToken { .. } => "AuthToken",
+            GameMessage::SetFeature { .. } => "SetFeature",
+            GameMessage::Features { .. } => "Features",
+            GameMessage::SetTelemetry { .. } => "SetTelemetry",
+            GameMessage::TelemetryStatus { .. } => "TelemetryStatus",
+            GameMessage::ConnectResponse { .. } => "ConnectResponse",
+            GameMessage::Move { .. } => "Move",
+            GameMessage::MoveAck { .. } => "MoveAck",
+            GameMessage::Error(_) => "Error",
+            GameMessage::GameOver { .. } => "GameOver",
+            GameMessage::Status { .. } => "Status",
+            GameMessage::MoveApplied { .. } => "MoveApplied",
+            GameMessage::Resync => "Resync",
+            GameMessage::TurnNotification { .. } => "TurnNotification",
+            GameMessage::PlayerDisconnected { .. } => "PlayerDisconnected",
+            GameMessage::PlayerConnected { .. } => "PlayerConnected",
+            GameMessage::ServerShutdown => "ServerShutdown",
+            GameMessage::TimeUpdate { .. } => "TimeUpdate",
+            GameMessage::TimeWarning { .. } => "TimeWarning",
+            GameMessage::SetPresence { .. } => "SetPresence",
+            GameMessage::Presence { .. } => "Presence",
+            GameMessage::SendChat { .. } => "SendChat",
+            GameMessage::Chat { .. } => "Chat",
+            GameMessage::ExportGame => "ExportGame",
+            GameMessage::GameRecord { .. } => "GameRecord",
+            GameMessage::GameArchived { .. } => "GameArchived",
+            GameMessage::ReplayRequest { .. } => "ReplayRequest",
+            GameMessage::Replay { .. } => "Replay",
+            GameMessage::SetNarration { .. } => "SetNarration",
+            GameMessage::Narration { .. } => "Narration",
+            GameMessage::LeaderboardRequest { .. } => "LeaderboardRequest",
+            GameMessage::Leaderboard { .. } => "Leaderboard",
+            GameMessage::GetStats { .. } => "GetStats",
+            GameMessage::Stats { .. } => "Stats",
+            GameMessage::SessionTransferred => "SessionTransferred",
+            GameMessage::ServerNotice { .. } => "ServerNotice",
+            GameMessage::Kicked { .. } => "Kicked",
+            GameMessage::ProtocolViolation { .. } => "ProtocolViolation",
+            GameMessage::Resign => "Resign",
+            GameMessage::OfferDraw => "OfferDraw",
+            GameMessage::DrawOffered { .. } => "DrawOffered",
+            GameMessage::AnswerDraw { .. } => "AnswerDraw",
+            GameMessage::DrawDeclined { .. } => "DrawDeclined",
+            GameMessage::Goodbye => "Goodbye",
+            GameMessage::FindOpponent => "FindOpponent",
+            GameMessage::GamePaused { .. } => "GamePaused",
+            GameMessage::GameResumed { .. } => "GameResumed",
+            GameMessage::QueueStatus { .. } => "QueueStatus",
+            GameMessage::Chunk { .. } => "Chunk",
+            GameMessage::ListGames { .. } => "ListGames",
+            GameMessage::GameList { .. } => "GameList",
+            GameMessage::Watch { .. } => "Watch",
+            GameMessage::Watching { .. } => "Watching",
+            GameMessage::StopWatching => "StopWatching",
+            GameMessage::CreateInvite { .. } => "CreateInvite",
+            GameMessage::InviteCreated { .. } => "InviteCreated",
+            GameMessage::AllowSubstitutes { .. } => "AllowSubstitutes",
+            GameMessage::SeatOpen { .. } => "SeatOpen",
+            GameMessage::ClaimSeat { .. } => "ClaimSeat",
+            GameMessage::SeatClaimed { .. } => "SeatClaimed",
+            GameMessage::HintRequest => "HintRequest",
+            GameMessage::Hint { .. } => "Hint",
+            GameMessage::SubscribeRegion { .. } => "SubscribeRegion",
+            GameMessage::RegionUpdate { .. } => "RegionUpdate",
+            GameMessage::RegionSummary { .. } => "RegionSummary",
+            GameMessage::EventsSummarized { .. } => "EventsSummarized",
+            GameMessage::Evaluation { .. } => "Evaluation",
+            GameMessage::EvaluateRequest { .. } => "EvaluateRequest",
+            GameMessage::EvaluationGraph { .. } => "EvaluationGraph",
+            GameMessage::PositionSearch { .. } => "PositionSearch",
+            GameMessage::PositionGames(_) => "PositionGames",
+            GameMessage::ServerInfoRequest => "ServerInfoRequest",
+            GameMessage::ServerInfo(_) => "ServerInfo",
+            GameMessage::CreateDemo { .. } => "CreateDemo",
+            GameMessage::DemoCreated { .. } => "DemoCreated",
+            GameMessage::DemoEdit { .. } => "DemoEdit",
+            GameMessage::DemoPosition { .. } => "DemoPosition",
+            GameMessage::DemoClosed { .. } => "DemoClosed",
+        }
+    }
+}
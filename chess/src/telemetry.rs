@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::GameType;
+
+pub const DEFAULT_TELEMETRY_INTERVAL_SECS: u64 = 60 * 60;
+// 上报接口迟迟不响应时放弃这一批
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 匿名使用统计，默认关闭。配置示例:
+// "telemetry": { "enabled": true, "endpoint": "http://stats.example.com/gomoku" }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    // 只支持 http://，不设置则只统计不上报
+    pub endpoint: Option<String>,
+    pub interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_secs: DEFAULT_TELEMETRY_INTERVAL_SECS,
+        }
+    }
+}
+
+// 上报的全部内容。不含任何个人信息：这里只能记录游戏类型和步数，
+// 用户名、用户 ID、对局编号、IP 地址都不会进入这个模块
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub version: String,
+    pub games_played: u64,
+    pub average_game_length: f64,
+    pub variants: BTreeMap<GameType, u64>,
+}
+
+#[derive(Default)]
+struct Counters {
+    games_played: u64,
+    total_moves: u64,
+    variants: BTreeMap<GameType, u64>,
+}
+
+pub struct Telemetry {
+    enabled: AtomicBool,
+    endpoint: Option<String>,
+    interval: Duration,
+    counters: Mutex<Counters>,
+}
+
+impl Telemetry {
+    pub fn new(config: &TelemetryConfig) -> Self {
+        Self {
+            enabled: AtomicBool::new(config.enabled),
+            endpoint: config.endpoint.clone(),
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    // 运行时开关，关闭时丢掉还没上报的统计
+    pub fn set_enabled(&self, enabled: bool) {
+        println!("匿名使用统计已{}", if enabled { "开启" } else { "关闭" });
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *self.counters.lock().unwrap() = Counters::default();
+        }
+    }
+
+    // 一盘对局结束，关闭时什么都不记
+    pub fn record_game(&self, game_type: GameType, moves: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut counters = self.counters.lock().unwrap();
+        counters.games_played += 1;
+        counters.total_moves += moves as u64;
+        *counters.variants.entry(game_type).or_default() += 1;
+    }
+
+    // 取出上次上报以来的统计并清零，没有新对局时返回 None
+    pub fn take_report(&self) -> Option<UsageReport> {
+        let counters = std::mem::take(&mut *self.counters.lock().unwrap());
+        if counters.games_played == 0 {
+            return None;
+        }
+        Some(UsageReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            games_played: counters.games_played,
+            average_game_length: counters.total_moves as f64 / counters.games_played as f64,
+            variants: counters.variants,
+        })
+    }
+
+    // 定期上报，失败的这一批直接丢弃
+    pub async fn run(&self) {
+        let Some(endpoint) = self.endpoint.as_deref() else {
+            return;
+        };
+        loop {
+            tokio::time::sleep(self.interval).await;
+            if !self.is_enabled() {
+                continue;
+            }
+            if let Some(report) = self.take_report() {
+                match tokio::time::timeout(POST_TIMEOUT, post_json(endpoint, &report)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => println!("上报使用统计失败: {}", e),
+                    Err(_) => println!("上报使用统计超时"),
+                }
+            }
+        }
+    }
+}
+
+// 最简单的 HTTP/1.1 POST，只支持 http://host[:port]/path
+pub async fn post_json(endpoint: &str, report: &UsageReport) -> std::io::Result<()> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "只支持 http:// 地址");
+    let rest = endpoint.strip_prefix("http://").ok_or_else(invalid)?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+
+    let body = serde_json::to_string(report)?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut status = [0u8; 12];
+    stream.read_exact(&mut status).await?;
+    // "HTTP/1.1 200"
+    match &status[9..10] {
+        b"2" => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "服务器返回 {}",
+            String::from_utf8_lossy(&status[9..])
+        ))),
+    }
+}
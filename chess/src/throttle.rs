@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Budget;
+
+const DEFAULT_MIN_BUDGET_PERCENT: u32 = 25;
+
+// 电脑对手限流：电脑座位多、机器忙的时候按比例减小每一步的计算量，
+// 真人对局的消息处理不会被搜索挤占。配置示例:
+// "ai_throttle": { "enabled": true, "min_budget_percent": 25 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiThrottleConfig {
+    pub enabled: bool,
+    // 再忙也至少保留这个比例的计算量，每个电脑对手都分得到，不会弱到没法下
+    pub min_budget_percent: u32,
+    // 同时搜索数和系统负载都不超过它时不限流，0 表示按 CPU 核数
+    pub capacity: usize,
+}
+
+impl Default for AiThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_budget_percent: DEFAULT_MIN_BUDGET_PERCENT,
+            capacity: 0,
+        }
+    }
+}
+
+// 所有电脑对手共用一份，记着正在进行的搜索数
+pub struct AiThrottle {
+    config: AiThrottleConfig,
+    capacity: usize,
+    active: Arc<AtomicUsize>,
+    load: fn() -> Option<f64>,
+}
+
+// 搜索期间持有，放下时正在进行的搜索数减一
+pub struct SearchPermit(Arc<AtomicUsize>);
+
+impl Drop for SearchPermit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl AiThrottle {
+    pub fn new(config: AiThrottleConfig) -> Self {
+        let capacity = match config.capacity {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        Self {
+            config,
+            capacity,
+            active: Arc::new(AtomicUsize::new(0)),
+            load: load_average,
+        }
+    }
+
+    // 换掉系统负载的来源，取不到负载时只按同时搜索数算
+    pub fn with_load(mut self, load: fn() -> Option<f64>) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn active_searches(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    // 开始一次搜索：同时搜索数或者系统负载超过容量时，计算量按超出的比例缩小，
+    // 不低于配置的下限。返回这一步实际可用的计算量
+    pub fn acquire(&self, budget: Budget) -> (Budget, SearchPermit) {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        let permit = SearchPermit(self.active.clone());
+        if !self.config.enabled {
+            return (budget, permit);
+        }
+        let load = (self.load)().unwrap_or(0.0);
+        let pressure = (active as f64).max(load) / self.capacity as f64;
+        if pressure <= 1.0 {
+            return (budget, permit);
+        }
+        let floor = self.config.min_budget_percent.min(100) as f64 / 100.0;
+        (budget.scaled((1.0 / pressure).max(floor)), permit)
+    }
+}
+
+impl Budget {
+    // 按比例缩小，至少还搜一层、模拟一局
+    fn scaled(self, scale: f64) -> Self {
+        let scale = |value: usize| (value as f64 * scale).round() as usize;
+        Self {
+            depth: scale(self.depth).max(1),
+            vcf_depth: scale(self.vcf_depth),
+            playouts: scale(self.playouts).max(1),
+        }
+    }
+}
+
+// 最近一分钟的平均负载
+fn load_average() -> Option<f64> {
+    let mut loads = [0.0];
+    // getloadavg 最多写入请求的个数，返回实际写入的个数
+    let written = unsafe { libc::getloadavg(loads.as_mut_ptr(), 1) };
+    (written == 1).then_some(loads[0])
+}
@@ -1,7 +1,25 @@
-use crate::GameError;
-use crate::PlayerRole;
 use serde::{Deserialize, Serialize};
+
+use crate::PlayerRole;
+#[cfg(feature = "server")]
+use crate::{
+    hash_password, names::random_username, rate_game, shared, verify_password, GameError, Invite,
+    LeaderboardEntry, MemoryStore, Rating, RatingRecord, RoomId, SharedStore, Store, StoreError,
+    TokenSigner, MIN_PASSWORD_LEN,
+};
+#[cfg(feature = "server")]
 use std::collections::HashMap;
+#[cfg(feature = "server")]
+use tokio::sync::{RwLock, Semaphore};
+
+// 随机用户名先在小范围里试这么多次
+#[cfg(feature = "server")]
+const GUEST_NAME_ATTEMPTS: usize = 10;
+// 同时计算的密码哈希数。argon2 每次要占约 19 MiB 内存，大量连接同时注册登录时不能不限
+#[cfg(feature = "server")]
+const MAX_CONCURRENT_HASHES: usize = 4;
+#[cfg(feature = "server")]
+static HASH_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_HASHES);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -9,9 +27,79 @@ pub struct User {
     pub name: String,           // 用户名
     pub session_id: String,     // 会话ID
     pub player: Option<PlayerRole>, // 当前游戏中的角色
+    #[serde(default)]
+    pub stats: PlayerStats,         // 历史战绩
+    #[serde(default)]
+    pub password_hash: Option<String>, // 注册用户的密码哈希，游客为空
+    #[serde(default)]
+    pub abandons: Vec<chrono::DateTime<chrono::Utc>>, // 最近中途弃局的时间
+}
+
+// 弃局惩罚：统计窗口内弃局达到 limit 次后暂停匹配，之后每多一次冷却时间再加一倍基数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbandonPolicy {
+    pub limit: usize,
+    pub window_hours: i64,
+    pub cooldown_secs: i64,
+}
+
+impl Default for AbandonPolicy {
+    fn default() -> Self {
+        Self {
+            limit: 3,
+            window_hours: 24,
+            cooldown_secs: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub current_streak: i32, // 正数为连胜，负数为连败，平局清零
+    pub best_streak: u32,    // 最长连胜
+    pub total_move_ms: u64,  // 所有对局中自己落子的总用时
+    pub moves: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl PlayerStats {
+    pub fn record(&mut self, outcome: GameOutcome, move_ms: u64, moves: u32) {
+        self.games += 1;
+        self.total_move_ms += move_ms;
+        self.moves += moves;
+        match outcome {
+            GameOutcome::Win => {
+                self.wins += 1;
+                self.current_streak = self.current_streak.max(0) + 1;
+                self.best_streak = self.best_streak.max(self.current_streak as u32);
+            }
+            GameOutcome::Loss => {
+                self.losses += 1;
+                self.current_streak = self.current_streak.min(0) - 1;
+            }
+            GameOutcome::Draw => {
+                self.draws += 1;
+                self.current_streak = 0;
+            }
+        }
+    }
+
+    pub fn average_move_ms(&self) -> Option<u64> {
+        (self.moves > 0).then(|| self.total_move_ms / self.moves as u64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSession {
     pub user_id: String,
     pub session_id: String,
@@ -19,40 +107,393 @@ pub struct UserSession {
     pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[cfg(feature = "server")]
 pub struct UserManager {
     users: HashMap<String, User>,                // 用户ID -> 用户信息
     sessions: HashMap<String, UserSession>,      // 会话ID -> 会话信息
-    player_assignments: HashMap<PlayerRole, String>, // 玩家 -> 用户ID
+    player_assignments: HashMap<(RoomId, PlayerRole), String>, // (房间, 玩家) -> 用户ID
+    ratings: HashMap<String, Rating>,            // 用户ID -> 等级分
+    store: SharedStore,
+    tokens: TokenSigner,
+    admins: Vec<String>, // 管理员用户名
+    max_games_per_user: usize, // 每个用户同时进行的对局数上限
+    abandon_policy: AbandonPolicy,
 }
 
+#[cfg(feature = "server")]
+impl Default for UserManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "server")]
 impl UserManager {
     pub fn new() -> Self {
+        Self::with_store(shared(MemoryStore::new()))
+    }
+
+    // 从存储后端加载用户和未过期的会话
+    pub fn with_store(store: SharedStore) -> Self {
+        let (users, sessions, ratings) = {
+            let store = store.lock().unwrap();
+            let users = store.load_users().unwrap_or_else(|e| {
+                println!("加载用户失败: {}", e);
+                Vec::new()
+            });
+            let sessions = store.load_sessions().unwrap_or_else(|e| {
+                println!("加载会话失败: {}", e);
+                Vec::new()
+            });
+            let ratings = store.load_ratings().unwrap_or_else(|e| {
+                println!("加载等级分失败: {}", e);
+                Vec::new()
+            });
+            (users, sessions, ratings)
+        };
+        let now = chrono::Utc::now();
+        println!("已加载 {} 个用户", users.len());
         Self {
-            users: HashMap::new(),
-            sessions: HashMap::new(),
+            users: users.into_iter().map(|user| (user.id.clone(), user)).collect(),
+            sessions: sessions
+                .into_iter()
+                .filter(|session| session.expires_at > now)
+                .map(|session| (session.session_id.clone(), session))
+                .collect(),
             player_assignments: HashMap::new(),
+            ratings: ratings.into_iter().collect(),
+            store,
+            tokens: TokenSigner::random(),
+            admins: Vec::new(),
+            max_games_per_user: 1,
+            abandon_policy: AbandonPolicy::default(),
+        }
+    }
+
+    // 写入失败只记录日志，不影响对局
+    fn persist(&self, write: impl FnOnce(&mut dyn Store) -> Result<(), StoreError>) {
+        if let Err(e) = write(&mut *self.store.lock().unwrap()) {
+            println!("{}", e);
+        }
+    }
+
+    fn new_session(&mut self, user_id: &str) -> String {
+        let session = UserSession {
+            user_id: user_id.to_string(),
+            session_id: uuid::Uuid::new_v4().to_string(),
+            created_at: chrono::Utc::now(),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(24),
+        };
+        self.persist(|store| store.save_session(&session));
+        let session_id = session.session_id.clone();
+        self.sessions.insert(session_id.clone(), session);
+        session_id
+    }
+
+    // 按用户名登录，已有的用户沿用原来的 ID，每次登录开一个新会话
+    pub fn login(&mut self, name: String) -> User {
+        let existing = self.users.values().find(|user| user.name == name).cloned();
+        let Some(mut user) = existing else {
+            return self.create_user(name);
+        };
+        self.sessions.remove(&user.session_id);
+        let old_session = user.session_id.clone();
+        self.persist(|store| store.delete_session(&old_session));
+        user.session_id = self.new_session(&user.id);
+        user.player = None;
+        self.persist(|store| store.save_user(&user));
+        self.users.insert(user.id.clone(), user.clone());
+        user
+    }
+
+    pub fn set_token_secret(&mut self, secret: &[u8]) {
+        self.tokens = TokenSigner::new(secret);
+    }
+
+    pub fn set_admins(&mut self, admins: Vec<String>) {
+        self.admins = admins;
+    }
+
+    pub fn set_play_limits(&mut self, max_games_per_user: usize, abandon_policy: AbandonPolicy) {
+        self.max_games_per_user = max_games_per_user.max(1);
+        self.abandon_policy = abandon_policy;
+    }
+
+    // 令牌有效且属于管理员
+    pub fn is_admin(&self, token: &str) -> bool {
+        self.token_user(token)
+            .is_some_and(|user| self.admins.contains(&user.name))
+    }
+
+    // 令牌有效且用户还在时返回这个用户
+    pub fn token_user(&self, token: &str) -> Option<&User> {
+        self.tokens
+            .verify(token)
+            .and_then(|user_id| self.users.get(&user_id))
+    }
+
+    // 注册账号并返回令牌；同名的游客用户会被认领，保留原来的战绩
+    pub fn register(&mut self, name: &str, password: &str) -> Result<String, GameError> {
+        self.check_registration(name, password)?;
+        self.register_hashed(name, hash_password(password))
+    }
+
+    // 配置里的管理员用户名不能通过连接注册，只能由运维用 create-admin 子命令开户
+    pub fn register_admin(&mut self, name: &str, password: &str) -> Result<String, GameError> {
+        if !self.admins.iter().any(|admin| admin == name) {
+            return Err(GameError::InvalidInput(format!(
+                "{} 不在配置的管理员列表里",
+                name
+            )));
+        }
+        self.check_password(name, password)?;
+        self.create_account(name, hash_password(password))
+    }
+
+    fn check_reserved(&self, name: &str) -> Result<(), GameError> {
+        if self.admins.iter().any(|admin| admin == name) {
+            return Err(GameError::InvalidInput(
+                "这个用户名保留给管理员".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // 哈希密码之前先检查，免得白算
+    fn check_registration(&self, name: &str, password: &str) -> Result<(), GameError> {
+        self.check_reserved(name)?;
+        self.check_password(name, password)
+    }
+
+    fn check_password(&self, name: &str, password: &str) -> Result<(), GameError> {
+        if name.trim().is_empty() {
+            return Err(GameError::InvalidInput("用户名不能为空".to_string()));
+        }
+        if password.chars().count() < MIN_PASSWORD_LEN {
+            return Err(GameError::InvalidInput(format!(
+                "密码至少需要 {} 个字符",
+                MIN_PASSWORD_LEN
+            )));
+        }
+        if self.get_user_by_name(name).is_some_and(|user| user.password_hash.is_some()) {
+            return Err(GameError::InvalidInput("用户名已被注册".to_string()));
+        }
+        Ok(())
+    }
+
+    // 用算好的密码哈希注册。哈希期间可能有人抢先注册了同名账号，这里要再查一次
+    fn register_hashed(&mut self, name: &str, password_hash: String) -> Result<String, GameError> {
+        self.check_reserved(name)?;
+        self.create_account(name, password_hash)
+    }
+
+    fn create_account(&mut self, name: &str, password_hash: String) -> Result<String, GameError> {
+        let existing = self.get_user_by_name(name).cloned();
+        let mut user = match existing {
+            Some(user) if user.password_hash.is_some() => {
+                return Err(GameError::InvalidInput("用户名已被注册".to_string()));
+            }
+            Some(user) => user,
+            None => self.create_user(name.to_string()),
+        };
+        user.password_hash = Some(password_hash);
+        self.persist(|store| store.save_user(&user));
+        let token = self.tokens.issue(&user.id);
+        self.users.insert(user.id.clone(), user);
+        Ok(token)
+    }
+
+    // 用户名密码登录，成功返回令牌
+    pub fn authenticate(&self, name: &str, password: &str) -> Result<String, GameError> {
+        let user = self
+            .get_user_by_name(name)
+            .filter(|user| {
+                user.password_hash
+                    .as_deref()
+                    .is_some_and(|hash| verify_password(password, hash))
+            })
+            .ok_or_else(|| GameError::Unauthorized("用户名或密码错误".to_string()))?;
+        Ok(self.tokens.issue(&user.id))
+    }
+
+    // 进入对局：带令牌时校验身份，不带令牌以游客身份进入，已注册的用户名必须带令牌
+    // 返回的布尔值表示是否通过了认证
+    // 用户名留空的游客由服务器分配一个随机用户名
+    pub fn connect(&mut self, name: &str, token: Option<&str>) -> Result<(User, bool), GameError> {
+        if name.trim().is_empty() && token.is_none() {
+            let name = self.generate_username();
+            return Ok((self.login(name), false));
+        }
+        match token {
+            Some(token) => {
+                let user_id = self
+                    .tokens
+                    .verify(token)
+                    .ok_or_else(|| GameError::Unauthorized("令牌无效或已过期".to_string()))?;
+                if self.users.get(&user_id).map(|user| user.name.as_str()) != Some(name) {
+                    return Err(GameError::Unauthorized("令牌与用户名不匹配".to_string()));
+                }
+                Ok((self.login(name.to_string()), true))
+            }
+            None => {
+                if self.get_user_by_name(name).is_some_and(|user| user.password_hash.is_some()) {
+                    return Err(GameError::Unauthorized(
+                        "该用户名已注册，请先登录".to_string(),
+                    ));
+                }
+                Ok((self.login(name.to_string()), false))
+            }
+        }
+    }
+
+    // 断开连接：释放角色并结束会话，用户本身保留
+    pub fn logout(&mut self, user_id: &str) {
+        let Some(user) = self.users.get_mut(user_id) else {
+            return;
+        };
+        user.player = None;
+        self.player_assignments.retain(|_, id| id != user_id);
+        let session_id = user.session_id.clone();
+        self.sessions.remove(&session_id);
+        self.persist(|store| store.delete_session(&session_id));
+    }
+
+    pub fn issue_invite(&self, invite: &Invite) -> String {
+        self.tokens.issue_invite(invite)
+    }
+
+    // 签名正确且未过期的邀请，是否还能用由房间判断
+    pub fn verify_invite(&self, token: &str) -> Option<Invite> {
+        self.tokens.verify_invite(token)
+    }
+
+    // 生成一个没人用过的用户名，同名的多了就放大数字范围
+    pub fn generate_username(&self) -> String {
+        let mut rng = rand::thread_rng();
+        let mut attempt = 0;
+        loop {
+            let max_number = if attempt < GUEST_NAME_ATTEMPTS { 1_000 } else { 1_000_000 };
+            let name = random_username(&mut rng, max_number);
+            if self.get_user_by_name(&name).is_none() {
+                return name;
+            }
+            attempt += 1;
+        }
+    }
+
+    pub fn get_user_by_name(&self, name: &str) -> Option<&User> {
+        self.users.values().find(|user| user.name == name)
+    }
+
+    pub fn rating(&self, user_id: &str) -> Rating {
+        self.ratings.get(user_id).copied().unwrap_or_default()
+    }
+
+    // 按用户名查注册用户的等级分，取整后和 ConnectResponse 里的一致；游客没有等级分
+    pub fn rating_by_name(&self, name: &str) -> Option<i32> {
+        let user = self
+            .get_user_by_name(name)
+            .filter(|user| user.password_hash.is_some())?;
+        Some(self.rating(&user.id).rating.round() as i32)
+    }
+
+    fn set_rating(&mut self, user_id: &str, rating: Rating) {
+        self.persist(|store| store.save_rating(user_id, &rating));
+        self.ratings.insert(user_id.to_string(), rating);
+    }
+
+    // 对局结束后按用户名更新双方等级分，返回新的 (黑, 白)
+    pub fn record_result(
+        &mut self,
+        black: &str,
+        white: &str,
+        winner: Option<PlayerRole>,
+    ) -> Option<(Rating, Rating)> {
+        let black_id = self.get_user_by_name(black)?.id.clone();
+        let white_id = self.get_user_by_name(white)?.id.clone();
+        let (new_black, new_white) =
+            rate_game(&self.rating(&black_id), &self.rating(&white_id), winner);
+        self.set_rating(&black_id, new_black);
+        self.set_rating(&white_id, new_white);
+        Some((new_black, new_white))
+    }
+
+    pub fn get_user(&self, user_id: &str) -> Option<&User> {
+        self.users.get(user_id)
+    }
+
+    // 对局结束后更新战绩，move_ms 和 moves 是该玩家本局的落子用时和步数
+    pub fn record_stats(&mut self, name: &str, outcome: GameOutcome, move_ms: u64, moves: u32) {
+        let Some(user) = self.users.values_mut().find(|user| user.name == name) else {
+            return;
+        };
+        user.stats.record(outcome, move_ms, moves);
+        let user = user.clone();
+        self.persist(|store| store.save_user(&user));
+    }
+
+    // 从其他平台导入等级分，不存在的用户以游客身份创建，之后可以注册认领
+    pub fn import_ratings(&mut self, records: &[RatingRecord]) -> usize {
+        for record in records {
+            let user_id = match self.get_user_by_name(&record.username) {
+                Some(user) => user.id.clone(),
+                None => self.create_user(record.username.clone()).id,
+            };
+            self.set_rating(&user_id, Rating::from_deviation(record.rating, record.deviation));
         }
+        records.len()
+    }
+
+    pub fn export_ratings(&self) -> Vec<RatingRecord> {
+        let mut records: Vec<RatingRecord> = self
+            .ratings
+            .iter()
+            .filter_map(|(id, rating)| {
+                Some(RatingRecord {
+                    username: self.users.get(id)?.name.clone(),
+                    rating: rating.rating,
+                    deviation: rating.deviation(),
+                })
+            })
+            .collect();
+        records.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        records
+    }
+
+    // 按等级分从高到低排列，只包含下过排位赛或导入过等级分的用户
+    pub fn leaderboard(&self, limit: usize) -> Vec<LeaderboardEntry> {
+        let mut entries: Vec<LeaderboardEntry> = self
+            .ratings
+            .iter()
+            .filter_map(|(id, rating)| {
+                let user = self.users.get(id)?;
+                Some(LeaderboardEntry {
+                    username: user.name.clone(),
+                    rating: rating.rating.round() as i32,
+                    games: rating.games,
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| b.rating.cmp(&a.rating).then(a.username.cmp(&b.username)));
+        entries.truncate(limit);
+        entries
     }
 
     pub fn create_user(&mut self, name: String) -> User {
         let user_id = uuid::Uuid::new_v4().to_string();
-        let session_id = uuid::Uuid::new_v4().to_string();
+        let session_id = self.new_session(&user_id);
         let user = User {
             id: user_id.clone(),
             name,
-            session_id: session_id.clone(),
-            player: None,
-        };
-
-        let session = UserSession {
-            user_id: user_id.clone(),
             session_id,
-            created_at: chrono::Utc::now(),
-            expires_at: chrono::Utc::now() + chrono::Duration::hours(24),
+            player: None,
+            stats: PlayerStats::default(),
+            password_hash: None,
+            abandons: Vec::new(),
         };
 
+        self.persist(|store| store.save_user(&user));
         self.users.insert(user_id.clone(), user.clone());
-        self.sessions.insert(session.session_id.clone(), session);
         user
     }
 
@@ -62,31 +503,150 @@ impl UserManager {
             .and_then(|session| self.users.get(&session.user_id))
     }
 
-    pub fn assign_player(&mut self, user_id: &str, player: PlayerRole) -> Result<(), GameError> {
-        if self.player_assignments.contains_key(&player) {
+    pub fn assign_player(
+        &mut self,
+        user_id: &str,
+        room: RoomId,
+        player: PlayerRole,
+    ) -> Result<(), GameError> {
+        if self.player_assignments.contains_key(&(room, player)) {
             return Err(GameError::InvalidInput(
                 "Player already assigned".to_string(),
             ));
         }
-        self.player_assignments.insert(player, user_id.to_string());
+        self.check_can_play(user_id)?;
+        self.player_assignments.insert((room, player), user_id.to_string());
         if let Some(user) = self.users.get_mut(user_id) {
             user.player = Some(player);
         }
         Ok(())
     }
 
-    pub fn get_user_by_player(&self, player: &PlayerRole) -> Option<&User> {
+    // 对局结束后换对手：只让出这个座位，会话保留
+    pub fn release_seat(&mut self, user_id: &str, room: RoomId, player: PlayerRole) {
+        if self.player_assignments.get(&(room, player)).map(String::as_str) != Some(user_id) {
+            return;
+        }
+        self.player_assignments.remove(&(room, player));
+        if let Some(user) = self.users.get_mut(user_id) {
+            user.player = None;
+        }
+    }
+
+    pub fn get_user_by_player(&self, room: RoomId, player: PlayerRole) -> Option<&User> {
         self.player_assignments
-            .get(player)
+            .get(&(room, player))
             .and_then(|user_id| self.users.get(user_id))
     }
 
+    fn active_games(&self, user_id: &str) -> usize {
+        self.player_assignments
+            .values()
+            .filter(|id| *id == user_id)
+            .count()
+    }
+
+    // 记录一次中途弃局，只保留统计窗口内的记录
+    pub fn record_abandon(&mut self, user_id: &str) {
+        let now = chrono::Utc::now();
+        let window = chrono::Duration::hours(self.abandon_policy.window_hours);
+        let Some(user) = self.users.get_mut(user_id) else {
+            return;
+        };
+        user.abandons.retain(|at| now - *at < window);
+        user.abandons.push(now);
+        println!("用户 {} 中途弃局，窗口内共 {} 次", user.name, user.abandons.len());
+        let user = user.clone();
+        self.persist(|store| store.save_user(&user));
+    }
+
+    // 弃局太多时剩余的匹配冷却时间
+    pub fn cooldown_remaining(
+        &self,
+        user_id: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<chrono::Duration> {
+        let policy = &self.abandon_policy;
+        let user = self.users.get(user_id)?;
+        let window = chrono::Duration::hours(policy.window_hours);
+        let recent: Vec<_> = user.abandons.iter().filter(|at| now - **at < window).collect();
+        if policy.limit == 0 || recent.len() < policy.limit {
+            return None;
+        }
+        let excess = (recent.len() - policy.limit + 1) as i32;
+        let until = **recent.iter().max()? + chrono::Duration::seconds(policy.cooldown_secs) * excess;
+        (until > now).then(|| until - now)
+    }
+
+    // 入座前检查同时对局数和弃局冷却
+    pub fn check_can_play(&self, user_id: &str) -> Result<(), GameError> {
+        if self.active_games(user_id) >= self.max_games_per_user {
+            return Err(GameError::Unavailable(format!(
+                "同时进行的对局已达上限 ({})",
+                self.max_games_per_user
+            )));
+        }
+        if let Some(left) = self.cooldown_remaining(user_id, chrono::Utc::now()) {
+            return Err(GameError::Unavailable(format!(
+                "你最近多次中途离开对局，请 {} 分钟后再匹配",
+                (left.num_seconds() + 59) / 60
+            )));
+        }
+        Ok(())
+    }
+
+    // 用户当前坐在哪个房间的哪个位置
+    pub fn seat_of(&self, user_id: &str) -> Option<(RoomId, PlayerRole)> {
+        self.player_assignments
+            .iter()
+            .find(|(_, id)| *id == user_id)
+            .map(|(&seat, _)| seat)
+    }
+
     pub fn remove_user(&mut self, user_id: &str) {
         if let Some(user) = self.users.remove(user_id) {
             self.sessions.remove(&user.session_id);
-            if let Some(player) = user.player {
-                self.player_assignments.remove(&player);
-            }
+            self.player_assignments.retain(|_, id| *id != user.id);
         }
     }
 }
+
+// 连接处理用下面两个函数注册和登录：argon2 哈希很慢，放到阻塞线程里算，
+// 算的时候不占着用户表的锁，其他连接查用户、入座不用等
+#[cfg(feature = "server")]
+pub async fn register_user(
+    users: &RwLock<UserManager>,
+    name: &str,
+    password: &str,
+) -> Result<String, GameError> {
+    users.read().await.check_registration(name, password)?;
+    let _permit = HASH_PERMITS.acquire().await;
+    let password = password.to_string();
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+        .await
+        .map_err(|e| GameError::InvalidInput(format!("注册失败: {}", e)))?;
+    users.write().await.register_hashed(name, password_hash)
+}
+
+#[cfg(feature = "server")]
+pub async fn authenticate_user(
+    users: &RwLock<UserManager>,
+    name: &str,
+    password: &str,
+) -> Result<String, GameError> {
+    let invalid = || GameError::Unauthorized("用户名或密码错误".to_string());
+    let (user_id, password_hash) = {
+        let users = users.read().await;
+        let user = users.get_user_by_name(name).ok_or_else(invalid)?;
+        (user.id.clone(), user.password_hash.clone().ok_or_else(invalid)?)
+    };
+    let _permit = HASH_PERMITS.acquire().await;
+    let password = password.to_string();
+    let valid = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .unwrap_or(false);
+    if !valid {
+        return Err(invalid());
+    }
+    Ok(users.read().await.tokens.issue(&user_id))
+}
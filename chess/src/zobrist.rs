@@ -0,0 +1,46 @@
+use crate::PlayerRole;
+
+type Cells = [[Option<PlayerRole>; 15]; 15];
+
+// 每个 (行, 列, 颜色) 一个随机键，编译期用固定种子生成，不同进程之间哈希值一致
+const KEYS: [[[u64; 2]; 15]; 15] = {
+    let mut keys = [[[0; 2]; 15]; 15];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 15 * 15 * 2 {
+        state = splitmix64(state);
+        keys[i / 30][(i / 2) % 15][i % 2] = state;
+        i += 1;
+    }
+    keys
+};
+
+// 轮到白方时异或上这个键
+pub const WHITE_TO_MOVE: u64 = splitmix64(0xD1B5_4A32_D192_ED03);
+
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn stone_key(row: usize, col: usize, player: PlayerRole) -> u64 {
+    KEYS[row][col][player as usize]
+}
+
+// 从头计算局面哈希，落子时增量更新的结果和它相同
+pub fn position_hash(cells: &Cells, to_move: PlayerRole) -> u64 {
+    let mut hash = match to_move {
+        PlayerRole::Black => 0,
+        PlayerRole::White => WHITE_TO_MOVE,
+    };
+    for (row, line) in cells.iter().enumerate() {
+        for (col, cell) in line.iter().enumerate() {
+            if let Some(player) = cell {
+                hash ^= stone_key(row, col, *player);
+            }
+        }
+    }
+    hash
+}
@@ -0,0 +1,532 @@
+use chess::{
+    evaluate, evaluation_graph, self_play, AIPlayer, AiThrottle, AiThrottleConfig, Board, Budget,
+    Contestant, Difficulty, Engine, EngineKind, ExternalEngine, ExternalEngineConfig, Game,
+    PlayerRole, RulesConfig, SelfPlayConfig,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+fn ai(player: PlayerRole, difficulty: Difficulty) -> AIPlayer {
+    AIPlayer::with_difficulty(player, Arc::new(Mutex::new(Game::new())), difficulty)
+}
+
+fn board(stones: &[(usize, usize, PlayerRole)]) -> Board {
+    let mut board = Board::new();
+    for &(row, col, player) in stones {
+        board.cells[row][col] = Some(player);
+    }
+    board
+}
+
+#[test]
+fn test_ai_opens_in_center() {
+    let ai = ai(PlayerRole::Black, Difficulty::Hard);
+    assert_eq!(ai.make_move(&Board::new()).unwrap(), (7, 7));
+}
+
+#[test]
+fn test_ai_takes_immediate_win_over_blocking() {
+    use PlayerRole::*;
+    // 双方都有活四，白方应该直接连五而不是去堵
+    let board = board(&[
+        (7, 3, White),
+        (7, 4, White),
+        (7, 5, White),
+        (7, 6, White),
+        (9, 3, Black),
+        (9, 4, Black),
+        (9, 5, Black),
+        (9, 6, Black),
+    ]);
+    let ai = ai(White, Difficulty::Medium);
+    let (row, col) = ai.make_move(&board).unwrap();
+    assert_eq!(row, 7);
+    assert!(col == 2 || col == 7);
+}
+
+#[test]
+fn test_ai_blocks_four() {
+    use PlayerRole::*;
+    let board = board(&[
+        (7, 7, Black),
+        (8, 8, Black),
+        (9, 9, Black),
+        (10, 10, Black),
+        (6, 6, White),
+        (7, 8, White),
+    ]);
+    let ai = ai(White, Difficulty::Hard);
+    assert_eq!(ai.make_move(&board).unwrap(), (11, 11));
+}
+
+#[test]
+fn test_ai_blocks_open_three_with_lookahead() {
+    use PlayerRole::*;
+    // 黑方活三，不堵住下一步就成活四
+    let board = board(&[
+        (7, 6, Black),
+        (7, 7, Black),
+        (7, 8, Black),
+        (8, 7, White),
+        (6, 7, White),
+    ]);
+    let ai = ai(White, Difficulty::Medium);
+    let (row, col) = ai.make_move(&board).unwrap();
+    assert_eq!(row, 7);
+    assert!(col == 5 || col == 9, "没有堵住活三: {:?}", (row, col));
+}
+
+#[test]
+fn test_ai_plays_to_the_configured_win_length() {
+    let rules = RulesConfig {
+        board_size: 9,
+        win_length: 4,
+        ..RulesConfig::default()
+    };
+    let mut board = Board::with_rules(rules);
+    assert_eq!(
+        chess::movegen::candidate_moves(&board.cells, rules, PlayerRole::Black, 10),
+        [(4, 4)]
+    );
+    // 黑棋已有三连，连四子就赢
+    for (row, col) in [(4, 2), (0, 0), (4, 3), (0, 8), (4, 4), (8, 0)] {
+        board.make_move(row, col).unwrap();
+    }
+    let ai = ai(PlayerRole::Black, Difficulty::Hard);
+    let (row, col) = ai.make_move(&board).unwrap();
+    assert!(row == 4 && (col == 1 || col == 5));
+}
+
+#[test]
+fn test_candidate_moves_stay_near_stones() {
+    use chess::movegen::{candidate_moves, CANDIDATE_RADIUS};
+    use PlayerRole::*;
+
+    assert_eq!(
+        candidate_moves(&Board::new().cells, RulesConfig::default(), Black, 10),
+        [(7, 7)]
+    );
+
+    let board = board(&[(0, 0, Black), (0, 1, Black), (0, 2, Black), (0, 3, Black)]);
+    let all = candidate_moves(&board.cells, board.rules, White, usize::MAX);
+    assert!(all
+        .iter()
+        .all(|&(row, col)| row <= CANDIDATE_RADIUS && col <= 3 + CANDIDATE_RADIUS));
+    // 堵四的位置排在最前面
+    assert_eq!(all[0], (0, 4));
+    assert_eq!(
+        candidate_moves(&board.cells, board.rules, White, 5).len(),
+        5
+    );
+}
+
+// 黑方连冲三个四：横向冲四、纵向冲四，最后在斜线上走成四四
+fn vcf_board() -> Board {
+    use PlayerRole::*;
+    board(&[
+        (7, 5, Black),
+        (7, 6, Black),
+        (7, 7, Black),
+        (7, 4, White),
+        (5, 9, Black),
+        (6, 9, Black),
+        (4, 9, White),
+        (9, 7, Black),
+        (10, 7, Black),
+        (11, 7, White),
+    ])
+}
+
+#[test]
+fn test_vcf_solver_finds_forced_win() {
+    let board = vcf_board();
+    assert_eq!(chess::solve_vcf(&board, PlayerRole::Black, 2), None);
+    assert_eq!(chess::solve_vcf(&board, PlayerRole::White, 8), None);
+
+    let line = chess::solve_vcf(&board, PlayerRole::Black, 3).unwrap();
+    assert_eq!(line.len(), 7);
+    // 按顺序摆出来，最后一步成五
+    let mut replay = vcf_board();
+    for &(row, col) in &line {
+        assert_eq!(replay.check_winner(), None);
+        replay.make_move(row, col).unwrap();
+    }
+    assert_eq!(replay.check_winner(), Some(PlayerRole::Black));
+
+    let ai = ai(PlayerRole::Black, Difficulty::Hard);
+    assert_eq!(ai.make_move(&board).unwrap(), line[0]);
+}
+
+#[test]
+fn test_mcts_engine_plays_forced_moves() {
+    use PlayerRole::*;
+    let engine = EngineKind::Mcts.engine();
+    let budget = Difficulty::Easy.budget();
+    assert_eq!(
+        engine.choose_move(&Board::new(), Black, budget),
+        Some((7, 7))
+    );
+
+    // 先连五，连不成时去堵对方的四
+    let four = board(&[
+        (7, 7, Black),
+        (8, 8, Black),
+        (9, 9, Black),
+        (10, 10, Black),
+        (6, 6, White),
+        (7, 8, White),
+    ]);
+    assert_eq!(engine.choose_move(&four, Black, budget), Some((11, 11)));
+    assert_eq!(engine.choose_move(&four, White, budget), Some((11, 11)));
+
+    // 普通局面下走在已有棋子附近
+    let open = board(&[(7, 7, Black), (8, 8, White), (7, 8, Black)]);
+    let chosen = engine.choose_move(&open, White, budget).unwrap();
+    let nearby = chess::movegen::candidate_moves(&open.cells, open.rules, White, usize::MAX);
+    assert!(nearby.contains(&chosen), "{:?}", chosen);
+
+    let ai = ai(White, Difficulty::Easy).with_engine(EngineKind::Mcts.engine());
+    assert_eq!(ai.make_move(&four).unwrap(), (11, 11));
+}
+
+#[test]
+fn test_seeded_mcts_replays_the_same_game() {
+    let play = |seed| {
+        let engine = EngineKind::Mcts.seeded(seed);
+        let mut board = Board::new();
+        for _ in 0..8 {
+            let player = board.current_player;
+            let (row, col) = engine
+                .choose_move(&board, player, Difficulty::Easy.budget())
+                .unwrap();
+            board.make_move(row, col).unwrap();
+        }
+        board
+            .moves
+            .iter()
+            .map(|m| (m.row, m.col))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(play(7), play(7));
+
+    // 种子随对局存档
+    let game = Game::new();
+    assert_eq!(game.to_archived().seed, Some(game.seed()));
+}
+
+#[test]
+fn test_self_play_alternates_colors_and_tallies_results() {
+    let easy = Contestant::parse("search:easy").unwrap();
+    assert_eq!(
+        Contestant::parse("mcts").unwrap(),
+        Contestant {
+            engine: EngineKind::Mcts,
+            difficulty: Difficulty::Medium
+        }
+    );
+    assert!(Contestant::parse("search:expert").is_err());
+
+    let config = SelfPlayConfig {
+        max_moves: 30,
+        ..SelfPlayConfig::new(easy, easy, 2)
+    };
+    let mut seen = 0;
+    let report = self_play(&config, |index, _| {
+        assert_eq!(index, seen);
+        seen += 1;
+    });
+    assert_eq!(seen, 2);
+    assert!(report.games[0].first_is_black);
+    assert!(!report.games[1].first_is_black);
+    assert_eq!(
+        report.first.wins + report.second.wins + report.draws,
+        report.games.len()
+    );
+    let total_moves: usize = report.games.iter().map(|game| game.moves.len()).sum();
+    assert_eq!(report.first.moves + report.second.moves, total_moves);
+    for game in &report.games {
+        assert!(game.moves.len() <= 30);
+        assert!(game.sgf.contains("PB[search:easy]"));
+        assert_eq!(game.winner.is_none(), game.first_won().is_none());
+    }
+}
+
+#[test]
+fn test_evaluation_graph_follows_the_game_from_blacks_side() {
+    let mut board = Board::new();
+    for (row, col) in [(7, 7), (0, 0), (7, 8), (0, 14), (7, 9), (14, 0)] {
+        board.make_move(row, col).unwrap();
+    }
+    let scores = evaluation_graph(&board.moves, board.rules);
+    assert_eq!(scores.len(), 6);
+    // 黑棋在中间连成三子，白棋散在角上
+    assert!(scores[5] > 0);
+    assert!(scores[4] > scores[0]);
+    assert_eq!(scores[5], evaluate(&board, PlayerRole::Black));
+    assert_eq!(
+        evaluate(&board, PlayerRole::White),
+        -evaluate(&board, PlayerRole::Black)
+    );
+}
+
+// 故意算得很慢的引擎，用来确认搜索期间对局锁是空闲的
+struct SlowEngine;
+
+impl Engine for SlowEngine {
+    fn choose_move(&self, _: &Board, _: PlayerRole, _: Budget) -> Option<(usize, usize)> {
+        std::thread::sleep(Duration::from_millis(500));
+        Some((0, 0))
+    }
+}
+
+#[tokio::test]
+async fn test_ai_searches_without_holding_the_game_lock() {
+    let game = Arc::new(Mutex::new(Game::new()));
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    {
+        let mut game = game.lock().await;
+        game.add_player(PlayerRole::Black, "alice".to_string(), black_tx)
+            .await
+            .unwrap();
+        game.add_player(PlayerRole::White, "computer".to_string(), white_tx)
+            .await
+            .unwrap();
+        game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+    }
+
+    let ai = Arc::new(
+        AIPlayer::with_difficulty(PlayerRole::White, game.clone(), Difficulty::Easy)
+            .with_engine(Box::new(SlowEngine)),
+    );
+    let action = tokio::spawn(ai.action());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // 引擎还在算，对局锁应该马上就能拿到
+    let guard = tokio::time::timeout(Duration::from_millis(100), game.lock())
+        .await
+        .expect("搜索期间对局锁被占用");
+    drop(guard);
+
+    action.await.unwrap().unwrap();
+    let position = game.lock().await.position();
+    assert_eq!(position.cells[0][0], Some(PlayerRole::White));
+    assert_eq!(position.current_player, PlayerRole::Black);
+}
+
+#[test]
+fn test_connect6_ai_plays_both_stones_of_a_turn() {
+    use PlayerRole::*;
+    let play = |moves: &[(usize, usize)]| {
+        let mut board = Board::with_rules(RulesConfig::connect6());
+        for &(row, col) in moves {
+            board.make_move(row, col).unwrap();
+        }
+        board
+    };
+
+    // 黑方第 7 行四连，一回合两子就能连成六子，白方必须两头都堵
+    let board = play(&[
+        (7, 3),
+        (0, 0),
+        (0, 2),
+        (7, 4),
+        (7, 5),
+        (0, 4),
+        (0, 6),
+        (7, 6),
+        (14, 14),
+    ]);
+    assert_eq!(board.current_player, White);
+    let pair = ai(White, Difficulty::Hard).make_turn(&board).unwrap();
+    assert_eq!(pair.len(), 2);
+    assert!(pair.iter().any(|&(row, col)| row == 7 && col < 3));
+    assert!(pair.iter().any(|&(row, col)| row == 7 && col > 6));
+
+    // 自己有四连时两子直接连成六子
+    let mut board = play(&[
+        (7, 7),
+        (0, 0),
+        (0, 1),
+        (14, 14),
+        (14, 12),
+        (0, 2),
+        (0, 3),
+        (14, 10),
+        (14, 8),
+    ]);
+    assert_eq!(board.current_player, White);
+    for (row, col) in ai(White, Difficulty::Hard).make_turn(&board).unwrap() {
+        board.make_move(row, col).unwrap();
+    }
+    assert_eq!(board.check_winner(), Some(White));
+}
+
+#[test]
+fn test_simulate_runs_seeded_games_in_parallel() {
+    use chess::{simulate, Policy, SimulationConfig};
+    use std::sync::Mutex;
+
+    assert_eq!(Policy::parse("random").unwrap(), Policy::Random);
+    assert_eq!(Policy::parse("search:easy").unwrap().name(), "search:easy");
+    assert!(Policy::parse("coinflip").is_err());
+
+    let config = SimulationConfig {
+        rules: RulesConfig {
+            board_size: 9,
+            win_length: 4,
+            ..RulesConfig::default()
+        },
+        seed: 42,
+        threads: 2,
+        ..SimulationConfig::new(Policy::Random, Policy::Random, 20)
+    };
+    let run = || {
+        let games = Mutex::new(Vec::new());
+        let progress = Mutex::new(Vec::new());
+        let summary = simulate(&config, |game, done| {
+            games.lock().unwrap().push(game.clone());
+            progress.lock().unwrap().push(done);
+        })
+        .unwrap();
+        let mut games = games.into_inner().unwrap();
+        games.sort_by_key(|game| game.index);
+        let mut progress = progress.into_inner().unwrap();
+        progress.sort();
+        assert_eq!(progress, (1..=20).collect::<Vec<_>>());
+        (summary, games)
+    };
+
+    let (summary, games) = run();
+    assert_eq!(summary.games, 20);
+    assert_eq!(summary.black_wins + summary.white_wins + summary.draws, 20);
+    for game in &games {
+        assert_eq!(game.seed, 42 + game.index as u64);
+        assert_eq!(game.length, game.moves.len());
+        assert!(game.moves.iter().all(|&(row, col)| row < 9 && col < 9));
+        let line = serde_json::to_value(game).unwrap();
+        assert_eq!(line["black"], "random");
+    }
+    // 同样的种子不管线程怎么分配，结果都一样
+    assert_eq!(run().1, games);
+
+    let bad = SimulationConfig {
+        rules: RulesConfig {
+            win_length: 9,
+            ..RulesConfig::default()
+        },
+        ..config
+    };
+    assert!(simulate(&bad, |_, _| {}).is_err());
+}
+
+// 用 sh 脚本充当外部引擎，按 Gomocup 协议回复
+#[cfg(unix)]
+fn script_engine(script: &str, move_timeout_ms: u64, max_restarts: u32) -> ExternalEngine {
+    let config = ExternalEngineConfig {
+        move_timeout_ms,
+        max_restarts,
+        ..ExternalEngineConfig::new("sh", &["-c", script])
+    };
+    ExternalEngine::new(config, EngineKind::Search.engine())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_external_engine_plays_the_reported_move() {
+    // 回复的坐标先列后行，工作目录是单独的临时目录
+    let engine = script_engine(
+        r#"case "$PWD" in *gomoku-engine-*) ;; *) exit 1;; esac
+        while read line; do
+            case "$line" in
+                START*) echo OK;;
+                DONE) echo "MESSAGE thinking"; echo 3,4;;
+                END) exit 0;;
+            esac
+        done"#,
+        5_000,
+        0,
+    );
+    let budget = Difficulty::Easy.budget();
+    assert_eq!(
+        engine.choose_move(&Board::new(), PlayerRole::Black, budget),
+        Some((4, 3))
+    );
+    assert_eq!(engine.failures(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_misbehaving_external_engine_is_killed_and_replaced() {
+    let budget = Difficulty::Easy.budget();
+    // 不回复落子：超时后结束进程，这一步由内置引擎下
+    let silent = script_engine(
+        r#"while read line; do case "$line" in START*) echo OK;; esac; done"#,
+        200,
+        1,
+    );
+    let started = std::time::Instant::now();
+    assert_eq!(
+        silent.choose_move(&Board::new(), PlayerRole::Black, budget),
+        Some((7, 7))
+    );
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert_eq!(silent.failures(), 1);
+    // 重启次数用完后不再启动进程，直接用内置引擎
+    silent.choose_move(&Board::new(), PlayerRole::Black, budget);
+    assert_eq!(silent.failures(), 2);
+    let started = std::time::Instant::now();
+    assert!(silent
+        .choose_move(&Board::new(), PlayerRole::Black, budget)
+        .is_some());
+    assert!(started.elapsed() < Duration::from_millis(200));
+
+    // 下在已有棋子上的回复不算数
+    let occupied = board(&[(7, 7, PlayerRole::White)]);
+    let cheater = script_engine(
+        r#"while read line; do case "$line" in START*) echo OK;; DONE) echo 7,7;; esac; done"#,
+        5_000,
+        0,
+    );
+    let chosen = cheater
+        .choose_move(&occupied, PlayerRole::Black, budget)
+        .unwrap();
+    assert_ne!(chosen, (7, 7));
+    assert_eq!(cheater.failures(), 1);
+}
+
+#[test]
+fn test_ai_budget_shrinks_under_load_but_not_below_the_floor() {
+    let config = AiThrottleConfig {
+        enabled: true,
+        min_budget_percent: 25,
+        capacity: 2,
+    };
+    let hard = Difficulty::Hard.budget();
+    let idle = AiThrottle::new(config).with_load(|| Some(0.0));
+
+    // 容量以内按难度的计算量搜索
+    let (first, _a) = idle.acquire(hard);
+    let (second, _b) = idle.acquire(hard);
+    assert_eq!((first, second), (hard, hard));
+    // 第四个同时搜索的只分到一半
+    let (_, _c) = idle.acquire(hard);
+    let (fourth, d) = idle.acquire(hard);
+    assert_eq!(fourth.playouts, hard.playouts / 2);
+    assert!(fourth.depth < hard.depth);
+    drop(d);
+    assert_eq!(idle.active_searches(), 3);
+
+    // 系统负载很高时也不低于下限
+    let busy = AiThrottle::new(config).with_load(|| Some(100.0));
+    let (budget, _permit) = busy.acquire(hard);
+    assert_eq!(budget.playouts, hard.playouts / 4);
+    assert!(budget.depth >= 1);
+
+    let disabled = AiThrottle::new(AiThrottleConfig {
+        enabled: false,
+        ..config
+    })
+    .with_load(|| Some(100.0));
+    assert_eq!(disabled.acquire(hard).0, hard);
+}
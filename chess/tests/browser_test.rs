@@ -0,0 +1,255 @@
+use chess::{
+    browser, list_games, search_position, ArchivedGame, GameArchive, GameFilter, GamePage,
+    GameResult, GameStatus, Histogram, MoveRecord, MoveStage, PlayerRole, PositionGames,
+    RoomManager, RulesConfig, ServerConfig, UserManager, Violation,
+};
+use chrono::{Duration, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex, RwLock};
+
+// 第 day 天的一盘已结束对局
+fn finished(
+    id: &str,
+    black: &str,
+    white: &str,
+    winner: Option<PlayerRole>,
+    day: u32,
+) -> ArchivedGame {
+    let started_at = Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap();
+    ArchivedGame {
+        id: id.to_string(),
+        black: black.to_string(),
+        white: white.to_string(),
+        winner,
+        reason: None,
+        moves: Vec::new(),
+        started_at,
+        ended_at: started_at + Duration::minutes(10),
+        seed: None,
+        rules: RulesConfig::default(),
+        position_hash: None,
+    }
+}
+
+async fn setup() -> (
+    Arc<Mutex<RoomManager>>,
+    Arc<Mutex<GameArchive>>,
+    Arc<RwLock<UserManager>>,
+) {
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    {
+        let mut archive = archive.lock().await;
+        archive.save(finished("g1", "alice", "bob", Some(PlayerRole::Black), 1));
+        archive.save(finished("g2", "carol", "alice", None, 2));
+        archive.save(finished("g3", "bob", "carol", Some(PlayerRole::White), 3));
+    }
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        ServerConfig::default(),
+        archive.clone(),
+        users.clone(),
+    )));
+    // 一盘进行中的对局
+    let (_, game) = rooms.lock().await.try_seat(None, false).await.unwrap();
+    let mut game = game.lock().await;
+    for (role, name) in [(PlayerRole::Black, "dave"), (PlayerRole::White, "alice")] {
+        let (tx, mut rx) = mpsc::channel(32);
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        game.add_player(role, name.to_string(), tx).await.unwrap();
+    }
+    drop(game);
+    (rooms, archive, users)
+}
+
+#[tokio::test]
+async fn test_list_games_filters_and_pages() {
+    let (rooms, archive, _) = setup().await;
+    let ids = |page: GamePage| page.games.into_iter().map(|g| g.id).collect::<Vec<_>>();
+
+    let all = list_games(&rooms, &archive, &GameFilter::default()).await;
+    assert_eq!(all.total, 4);
+    assert_eq!(all.games[0].status, GameStatus::Live);
+    assert_eq!(ids(all)[1..], ["g3", "g2", "g1"]);
+
+    let filter = GameFilter::from_query("player=alice&status=finished").unwrap();
+    assert_eq!(
+        ids(list_games(&rooms, &archive, &filter).await),
+        ["g2", "g1"]
+    );
+
+    let filter = GameFilter::from_query("result=draw").unwrap();
+    assert_eq!(ids(list_games(&rooms, &archive, &filter).await), ["g2"]);
+
+    let filter =
+        GameFilter::from_query("status=finished&from=2024-03-02T00:00:00Z&to=2024-03-03T23:59:59Z")
+            .unwrap();
+    assert_eq!(
+        ids(list_games(&rooms, &archive, &filter).await),
+        ["g3", "g2"]
+    );
+
+    let filter = GameFilter::from_query("status=finished&per_page=2&page=2").unwrap();
+    let page = list_games(&rooms, &archive, &filter).await;
+    assert_eq!(page.total, 3);
+    assert_eq!(ids(page), ["g1"]);
+
+    // 一页里先放进行中的对局，剩下的位置从存档里补
+    let filter = GameFilter::from_query("per_page=2").unwrap();
+    let page = list_games(&rooms, &archive, &filter).await;
+    assert_eq!((page.total, page.games[1].id.as_str()), (4, "g3"));
+    let filter = GameFilter::from_query("from=2024-03-01T00:00:00Z&per_page=2&page=2").unwrap();
+    let page = list_games(&rooms, &archive, &filter).await;
+    assert_eq!(page.total, 4);
+    assert_eq!(ids(page), ["g2", "g1"]);
+
+    assert_eq!(
+        GameFilter::from_query("result=black").unwrap().result,
+        Some(GameResult::Black)
+    );
+    assert!(GameFilter::from_query("page=0").is_err());
+    assert!(GameFilter::from_query("variant=renju").is_err());
+}
+
+async fn get(addr: &str, target: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, addr);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[tokio::test]
+async fn test_http_endpoint_serves_json() {
+    let (rooms, archive, users) = setup().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(browser::serve(listener, rooms, archive, users.clone()));
+
+    let (status, body) = get(&addr, "/games?status=live").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let page: GamePage = serde_json::from_str(&body).unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.games[0].black, "dave");
+    assert_eq!(page.games[0].result, None);
+
+    let (status, _) = get(&addr, "/games?status=paused").await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    let (status, _) = get(&addr, "/users").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
+
+// 黑先轮流落子的一盘已结束对局
+fn played(id: &str, day: u32, moves: &[(usize, usize)]) -> ArchivedGame {
+    let mut game = finished(id, "erin", "frank", Some(PlayerRole::Black), day);
+    game.moves = moves
+        .iter()
+        .enumerate()
+        .map(|(i, &(row, col))| MoveRecord {
+            player: if i % 2 == 0 {
+                PlayerRole::Black
+            } else {
+                PlayerRole::White
+            },
+            row,
+            col,
+            timestamp: game.started_at,
+        })
+        .collect();
+    game
+}
+
+#[tokio::test]
+async fn test_position_search_matches_transpositions() {
+    let (rooms, archive, users) = setup().await;
+    {
+        let mut archive = archive.lock().await;
+        archive.save(played("p1", 4, &[(7, 7), (7, 8), (8, 8)]));
+        // 换了走子顺序，局面相同
+        archive.save(played("p2", 5, &[(8, 8), (7, 8), (7, 7), (6, 6)]));
+        archive.save(played("p3", 6, &[(7, 7), (6, 6)]));
+    }
+    let found = |moves: &[(usize, usize)]| {
+        let archive = archive.clone();
+        let moves = moves.to_vec();
+        async move { search_position(&*archive.lock().await, &moves) }
+    };
+    let ids = |games: PositionGames| {
+        games
+            .games
+            .into_iter()
+            .map(|m| (m.game.id, m.ply))
+            .collect::<Vec<_>>()
+    };
+
+    let games = found(&[(7, 7), (7, 8), (8, 8)]).await.unwrap();
+    assert_eq!(games.total, 2);
+    assert_eq!(ids(games), [("p2".to_string(), 3), ("p1".to_string(), 3)]);
+    let games = found(&[(7, 7)]).await.unwrap();
+    assert_eq!(ids(games), [("p3".to_string(), 1), ("p1".to_string(), 1)]);
+    assert_eq!(found(&[(0, 0)]).await.unwrap().total, 0);
+    assert!(found(&[(7, 7), (7, 7)]).await.is_err());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(browser::serve(listener, rooms, archive, users));
+    let (status, body) = get(&addr, "/positions?moves=7-7,6-6").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let games: PositionGames = serde_json::from_str(&body).unwrap();
+    assert_eq!(ids(games), [("p3".to_string(), 2)]);
+    let (status, _) = get(&addr, "/positions?moves=7-7,99-0").await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    let (status, _) = get(&addr, "/positions?moves=7:7").await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_exports_latency_and_violation_counts() {
+    let (rooms, archive, users) = setup().await;
+    let game = rooms.lock().await.room(0).unwrap();
+    {
+        let mut game = game.lock().await;
+        game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+        game.make_move(PlayerRole::White, 7, 8).await.unwrap();
+        // 不合法的落子在检查阶段就被拒绝，不计入任何阶段
+        assert!(game.make_move(PlayerRole::Black, 7, 7).await.is_err());
+    }
+    let metrics = rooms.lock().await.metrics();
+    metrics.record(MoveStage::Total, std::time::Duration::from_millis(3));
+    metrics.record_violation(Violation::Malformed);
+    metrics.record_violation(Violation::Malformed);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(browser::serve(listener, rooms, archive, users));
+    let (status, body) = get(&addr, "/metrics").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let mut body: BTreeMap<String, serde_json::Value> = serde_json::from_str(&body).unwrap();
+    let latency: BTreeMap<MoveStage, Histogram> =
+        serde_json::from_value(body.remove("move_latency").unwrap()).unwrap();
+    let violations: BTreeMap<Violation, u64> =
+        serde_json::from_value(body.remove("protocol_violations").unwrap()).unwrap();
+    assert_eq!(violations, BTreeMap::from([(Violation::Malformed, 2)]));
+    assert_eq!(body["strict_disconnects"], 0);
+
+    assert_eq!(latency[&MoveStage::Validate].count, 2);
+    assert_eq!(latency[&MoveStage::Broadcast].count, 2);
+    assert!(!latency.contains_key(&MoveStage::Parse));
+    for histogram in latency.values() {
+        assert_eq!(histogram.counts.len(), histogram.bounds_micros.len() + 1);
+        assert_eq!(histogram.counts.iter().sum::<u64>(), histogram.count);
+    }
+    // 3 毫秒落在 (2.5ms, 5ms] 这个桶
+    let total = &latency[&MoveStage::Total];
+    let bucket = total
+        .bounds_micros
+        .iter()
+        .position(|&b| b == 5_000)
+        .unwrap();
+    assert_eq!(total.counts[bucket], 1);
+    assert_eq!(total.max_micros, 3_000);
+}
@@ -0,0 +1,187 @@
+use chess::{Clock, ClockEvent, ClockSnapshot, DowntimePolicy, PlayerRole, TimeControl};
+use chrono::Utc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_countdown_warnings() {
+    let mut clock = Clock::new(TimeControl {
+        main_time_secs: 60,
+        increment_secs: 0,
+    });
+    let start = Instant::now();
+    clock.start(PlayerRole::Black, start);
+
+    assert_eq!(clock.tick(start + Duration::from_secs(10)), None);
+    assert_eq!(
+        clock.tick(start + Duration::from_secs(31)),
+        Some(ClockEvent::Warning {
+            player: PlayerRole::Black,
+            remaining_secs: 29,
+        })
+    );
+    // 同一个阈值只提醒一次
+    assert_eq!(clock.tick(start + Duration::from_secs(32)), None);
+    // 直接跳过 10 秒阈值时只发最紧急的提醒
+    assert_eq!(
+        clock.tick(start + Duration::from_secs(56)),
+        Some(ClockEvent::Warning {
+            player: PlayerRole::Black,
+            remaining_secs: 4,
+        })
+    );
+    assert_eq!(clock.tick(start + Duration::from_secs(58)), None);
+    assert_eq!(
+        clock.tick(start + Duration::from_secs(60)),
+        Some(ClockEvent::Expired {
+            player: PlayerRole::Black,
+        })
+    );
+    assert!(!clock.is_running());
+}
+
+#[test]
+fn test_switch_adds_increment_and_resets_warnings() {
+    let mut clock = Clock::new(TimeControl {
+        main_time_secs: 20,
+        increment_secs: 5,
+    });
+    let start = Instant::now();
+    clock.start(PlayerRole::Black, start);
+    assert!(matches!(
+        clock.tick(start + Duration::from_secs(1)),
+        Some(ClockEvent::Warning { .. })
+    ));
+
+    let moved = start + Duration::from_secs(4);
+    clock.switch(moved);
+    assert_eq!(
+        clock.remaining(PlayerRole::Black, moved),
+        Duration::from_secs(21)
+    );
+    assert_eq!(
+        clock.tick(moved),
+        Some(ClockEvent::Warning {
+            player: PlayerRole::White,
+            remaining_secs: 20,
+        })
+    );
+}
+
+fn saved_clock(black_secs: u64, white_secs: u64) -> (Clock, Instant) {
+    let mut clock = Clock::new(TimeControl {
+        main_time_secs: 60,
+        increment_secs: 0,
+    });
+    let start = Instant::now();
+    clock.start(PlayerRole::Black, start);
+    clock.switch(start + Duration::from_secs(60 - black_secs));
+    let now = start + Duration::from_secs(60 - black_secs + 60 - white_secs);
+    (clock, now)
+}
+
+#[test]
+fn test_snapshot_survives_restart_with_downtime_paused() {
+    let (clock, now) = saved_clock(40, 25);
+    let saved_at = Utc::now();
+    let snapshot = clock.snapshot(now, saved_at);
+    assert_eq!(snapshot.running, Some(PlayerRole::White));
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: ClockSnapshot = serde_json::from_str(&json).unwrap();
+    let later = Instant::now();
+    let restored = Clock::restore(
+        &snapshot,
+        DowntimePolicy::Pause,
+        saved_at + chrono::Duration::hours(2),
+        later,
+    );
+    assert!(restored.is_running());
+    assert_eq!(
+        restored.remaining(PlayerRole::Black, later),
+        Duration::from_secs(40)
+    );
+    assert_eq!(
+        restored.remaining(PlayerRole::White, later + Duration::from_secs(5)),
+        Duration::from_secs(20)
+    );
+}
+
+#[test]
+fn test_downtime_is_charged_to_the_player_on_move() {
+    let (clock, now) = saved_clock(40, 25);
+    let saved_at = Utc::now();
+    let snapshot = clock.snapshot(now, saved_at);
+
+    let later = Instant::now();
+    let mut restored = Clock::restore(
+        &snapshot,
+        DowntimePolicy::Charge,
+        saved_at + chrono::Duration::seconds(10),
+        later,
+    );
+    assert_eq!(
+        restored.remaining(PlayerRole::White, later),
+        Duration::from_secs(15)
+    );
+    assert_eq!(
+        restored.remaining(PlayerRole::Black, later),
+        Duration::from_secs(40)
+    );
+    assert_eq!(
+        restored.tick(later),
+        Some(ClockEvent::Warning {
+            player: PlayerRole::White,
+            remaining_secs: 15,
+        })
+    );
+
+    // 系统时间回拨不会给任何一方加时
+    let restored = Clock::restore(
+        &snapshot,
+        DowntimePolicy::Charge,
+        saved_at - chrono::Duration::seconds(30),
+        later,
+    );
+    assert_eq!(
+        restored.remaining(PlayerRole::White, later),
+        Duration::from_secs(25)
+    );
+}
+
+#[test]
+fn test_clock_expired_during_downtime_ends_on_first_tick() {
+    let (clock, now) = saved_clock(40, 25);
+    let saved_at = Utc::now();
+    let snapshot = clock.snapshot(now, saved_at);
+
+    let later = Instant::now();
+    let mut restored = Clock::restore(
+        &snapshot,
+        DowntimePolicy::Charge,
+        saved_at + chrono::Duration::minutes(5),
+        later,
+    );
+    assert!(restored.remaining(PlayerRole::White, later).is_zero());
+    assert_eq!(
+        restored.tick(later),
+        Some(ClockEvent::Expired {
+            player: PlayerRole::White,
+        })
+    );
+
+    // 保存时没有在计时的时钟不受停机影响
+    let mut stopped = clock.clone();
+    stopped.stop(now);
+    let snapshot = stopped.snapshot(now, saved_at);
+    let restored = Clock::restore(
+        &snapshot,
+        DowntimePolicy::Charge,
+        saved_at + chrono::Duration::minutes(5),
+        later,
+    );
+    assert!(!restored.is_running());
+    assert_eq!(
+        restored.remaining(PlayerRole::White, later),
+        Duration::from_secs(25)
+    );
+}
@@ -0,0 +1,320 @@
+// 客户端和服务器共用的协议一致性测试，chess/tests/protocol_test.rs 和
+// client/tests/protocol_test.rs 都引入这个模块，两边对同一份样例做同样的检查
+use std::sync::Arc;
+
+use chess::{
+    ArchivedGame, Capability, DemoAction, Difficulty, Feature, GameArchive, GameError, GameFilter,
+    GameMessage, GameOverReason, GamePage, GameResult, GameStatus, GameSummary, GameType,
+    InviteRole, LeaderboardEntry, MoveRecord, NetworkPlayer, OutsideSummary, PlayerRole,
+    PlayerStats, PositionGames, PositionMatch, PresenceState, Region, RoomManager, RulesConfig,
+    ServerConfig, ServerInfo, UnfinishedGame, UserManager, VariationNode, Violation,
+    PROTOCOL_VERSION,
+};
+use chrono::{TimeZone, Utc};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, RwLock};
+
+// 列出每个协议变体的样例。覆盖检查的 match 没有通配分支，
+// 协议新增变体却没有补样例时，两个 crate 的测试都编译不过
+macro_rules! protocol_samples {
+    ($($variant:ident $(($($tuple:tt)*))? $({ $($fields:tt)* })?),* $(,)?) => {
+        pub fn samples() -> Vec<GameMessage> {
+            vec![$(GameMessage::$variant $(($($tuple)*))? $({ $($fields)* })?),*]
+        }
+
+        fn covered(msg: &GameMessage) {
+            match msg {
+                $(GameMessage::$variant { .. } => {})*
+            }
+        }
+    };
+}
+
+protocol_samples! {
+    ConnectRequest {
+        username: "alice".to_string(),
+        token: Some("token".to_string()),
+        play_vs_ai: Some(Difficulty::Hard),
+        invite: Some("invite".to_string()),
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: Some(PlayerRole::White),
+        resume: None,
+    },
+    Register { username: "alice".to_string(), password: "secret".to_string() },
+    Login { username: "alice".to_string(), password: "secret".to_string() },
+    AuthToken {
+        token: "token".to_string(),
+        unfinished: vec![UnfinishedGame {
+            game_id: "game".to_string(),
+            opponent: "bob".to_string(),
+            color: PlayerRole::Black,
+            to_move: PlayerRole::White,
+            move_number: 5,
+            clock: Some((60_000, 55_000)),
+            adjourned: true,
+        }],
+    },
+    SetFeature {
+        token: "admin".to_string(),
+        game_type: GameType::Gomoku,
+        feature: Feature::Replay,
+        enabled: false,
+    },
+    Features { game_type: GameType::Gomoku, enabled: Feature::ALL.to_vec() },
+    SetTelemetry { token: "admin".to_string(), enabled: true },
+    TelemetryStatus { enabled: true },
+    ConnectResponse {
+        username: "alice".to_string(),
+        player_role: PlayerRole::White,
+        rating: 1500,
+        user_id: "user-1".to_string(),
+        game_id: "game-1".to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        capabilities: Capability::ALL.to_vec(),
+        rules: RulesConfig::connect6(),
+    },
+    Move {
+        row: 7,
+        col: 7,
+        game_id: "game-1".to_string(),
+        move_seq: 3,
+        client_nonce: u64::MAX,
+        second: Some((7, 8)),
+    },
+    MoveAck { game_id: "game-1".to_string(), move_seq: 3 },
+    Error(GameError::InvalidMove("错误".to_string()).into()),
+    GameOver {
+        winner: Some(PlayerRole::Black),
+        winning_line: vec![(7, 3), (7, 4), (7, 5), (7, 6), (7, 7)],
+        reason: Some(GameOverReason::FiveInARow),
+    },
+    Status {
+        board: status_board(),
+        current_player: PlayerRole::White,
+        move_number: 2,
+        last_move: Some((8, 8)),
+        moves: [(PlayerRole::Black, 7, 7), (PlayerRole::White, 8, 8)]
+            .map(|(player, row, col)| MoveRecord {
+                player,
+                row,
+                col,
+                timestamp: archived_game().started_at,
+            })
+            .to_vec(),
+    },
+    TurnNotification { player: PlayerRole::Black },
+    PlayerDisconnected { player: PlayerRole::White },
+    PlayerConnected { player: PlayerRole::White, username: "bob".to_string() },
+    ServerShutdown,
+    TimeUpdate { black_ms: 60_000, white_ms: 59_500 },
+    TimeWarning { player: PlayerRole::Black, remaining_secs: 10 },
+    SetPresence { state: PresenceState::Thinking },
+    Presence { player: PlayerRole::Black, state: PresenceState::ConnectionDegraded },
+    ExportGame,
+    GameRecord { sgf: "(;GM[4]SZ[15];B[hh])".to_string() },
+    GameArchived { game_id: "game-1".to_string() },
+    ReplayRequest { game_id: "game-1".to_string() },
+    Replay { game: archived_game() },
+    SetNarration { enabled: true },
+    Narration { text: "黑棋落子 H8".to_string() },
+    LeaderboardRequest { limit: 10 },
+    Leaderboard {
+        entries: vec![LeaderboardEntry { username: "alice".to_string(), rating: 1520, games: 3 }],
+    },
+    GetStats { user_id: "user-1".to_string() },
+    Stats {
+        username: "alice".to_string(),
+        stats: PlayerStats { games: 3, wins: 2, losses: 1, current_streak: -1, ..PlayerStats::default() },
+    },
+    SessionTransferred,
+    ServerNotice { message: "服务器将在 10 分钟后维护".to_string() },
+    Kicked { reason: "管理员已将你移出对局".to_string() },
+    ProtocolViolation {
+        violation: Violation::OutOfTurn,
+        message: "不是你的回合".to_string(),
+    },
+    GamePaused { player: PlayerRole::White, grace_secs: 30 },
+    GameResumed { player: PlayerRole::White },
+    QueueStatus { position: 2, estimated_wait_secs: 45 },
+    Chunk { id: 1, index: 0, total: 2, data: "eyJFcnJvciI6".to_string() },
+    ListGames {
+        filter: GameFilter {
+            player: Some("alice".to_string()),
+            status: Some(GameStatus::Finished),
+            result: Some(GameResult::Black),
+            ..GameFilter::default()
+        },
+    },
+    GameList {
+        page: GamePage { games: vec![game_summary()], page: 1, per_page: 20, total: 1 },
+    },
+    Watch { game_id: "game-1".to_string() },
+    Watching { game: game_summary() },
+    StopWatching,
+    CreateInvite { role: InviteRole::Seat(PlayerRole::White), ttl_secs: Some(600) },
+    InviteCreated { token: "invite".to_string(), role: InviteRole::Spectator, expires_at: 1_700_000_600 },
+    AllowSubstitutes { allowed: true },
+    SeatOpen { game_id: "game".to_string(), role: PlayerRole::White },
+    ClaimSeat { role: PlayerRole::White },
+    SeatClaimed { game_id: "game".to_string(), role: PlayerRole::White },
+    HintRequest,
+    Hint { row: 7, col: 8, score: -120, hints_left: 2 },
+    SubscribeRegion { region: Some(REGION) },
+    RegionUpdate {
+        region: REGION,
+        cells: REGION.crop(&status_board()),
+        current_player: PlayerRole::White,
+        outside: OutsideSummary { black_stones: 0, white_stones: 1, last_move: Some((8, 8)) },
+    },
+    Evaluation { move_seq: 4, score: 230 },
+    EvaluateRequest { game_id: "game-1".to_string() },
+    EvaluationGraph { game_id: "game-1".to_string(), scores: vec![0, -12, 40, 1_000_000] },
+    PositionSearch { moves: vec![(7, 7), (7, 8)] },
+    Resign,
+    OfferDraw,
+    DrawOffered { player: PlayerRole::White },
+    AnswerDraw { accept: false },
+    DrawDeclined { player: PlayerRole::Black },
+    Goodbye,
+    FindOpponent,
+    MoveApplied {
+        row: 7,
+        col: 8,
+        by: PlayerRole::White,
+        next_player: PlayerRole::Black,
+        move_number: 2,
+    },
+    Resync,
+    PositionGames(PositionGames {
+        games: vec![PositionMatch { game: game_summary(), ply: 2 }],
+        total: 1,
+    }),
+    ServerInfoRequest,
+    ServerInfo(ServerInfo::new(&ServerConfig::default())),
+    CreateDemo { title: "活三的攻防".to_string(), token: "token".to_string() },
+    DemoCreated { demo_id: "demo-1".to_string() },
+    DemoEdit {
+        action: DemoAction::LoadVariations {
+            variations: vec![VariationNode {
+                player: PlayerRole::Black,
+                row: 6,
+                col: 6,
+                comment: "挡住上方".to_string(),
+                children: Vec::new(),
+            }],
+        },
+    },
+    DemoPosition { path: vec![0], comment: "挡住上方".to_string(), next: vec![(8, 8)] },
+    DemoClosed { demo_id: "demo-1".to_string() },
+    RegionSummary {
+        current_player: PlayerRole::Black,
+        outside: OutsideSummary { black_stones: 0, white_stones: 2, last_move: Some((0, 0)) },
+    },
+    EventsSummarized { skipped: 12 },
+    SendChat { text: "好棋".to_string() },
+    Chat { from: "alice".to_string(), text: "好棋".to_string() },
+}
+
+const REGION: Region = Region {
+    top: 5,
+    left: 5,
+    rows: 3,
+    cols: 3,
+};
+
+// 黑棋走完就赢的一盘：黑棋下第 7 行 3..=7 列，白棋在第 8 行陪着
+pub const SCRIPT: [(usize, usize); 9] = [
+    (7, 3),
+    (8, 3),
+    (7, 4),
+    (8, 4),
+    (7, 5),
+    (8, 5),
+    (7, 6),
+    (8, 6),
+    (7, 7),
+];
+
+fn status_board() -> Box<[[Option<PlayerRole>; 15]; 15]> {
+    let mut board = Box::new([[None; 15]; 15]);
+    board[7][7] = Some(PlayerRole::Black);
+    board[8][8] = Some(PlayerRole::White);
+    board
+}
+
+fn archived_game() -> ArchivedGame {
+    let started_at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+    ArchivedGame {
+        id: "game-1".to_string(),
+        black: "alice".to_string(),
+        white: "bob".to_string(),
+        winner: Some(PlayerRole::Black),
+        reason: Some(GameOverReason::FiveInARow),
+        moves: SCRIPT
+            .iter()
+            .enumerate()
+            .map(|(i, &(row, col))| MoveRecord {
+                player: if i % 2 == 0 {
+                    PlayerRole::Black
+                } else {
+                    PlayerRole::White
+                },
+                row,
+                col,
+                timestamp: started_at + chrono::Duration::seconds(i as i64),
+            })
+            .collect(),
+        started_at,
+        ended_at: started_at + chrono::Duration::minutes(5),
+        seed: Some(42),
+        rules: RulesConfig::default(),
+        position_hash: None,
+    }
+}
+
+fn game_summary() -> GameSummary {
+    GameSummary::from(&archived_game())
+}
+
+// 序列化、反序列化再序列化，两次得到的 JSON 必须一致（GameMessage 没有实现 PartialEq）
+pub fn assert_roundtrip(msg: &GameMessage) {
+    covered(msg);
+    let json = serde_json::to_string(msg).unwrap();
+    let decoded: GameMessage =
+        serde_json::from_str(&json).unwrap_or_else(|e| panic!("无法解析 {}: {}", json, e));
+    assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+}
+
+// 在随机端口启动一个默认配置的服务器，返回连接地址
+pub async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        ServerConfig::default(),
+        archive.clone(),
+        users.clone(),
+    )));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let player = NetworkPlayer::new(stream, rooms.clone(), users.clone(), archive.clone());
+            tokio::spawn(player.play());
+        }
+    });
+    format!("ws://{}", addr)
+}
+
+pub fn connect_request(username: &str) -> GameMessage {
+    GameMessage::ConnectRequest {
+        username: username.to_string(),
+        token: None,
+        play_vs_ai: None,
+        invite: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: None,
+        resume: None,
+    }
+}
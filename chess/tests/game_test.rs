@@ -0,0 +1,1223 @@
+use chess::{
+    shared, Board, CrashDump, DowntimePolicy, Game, GameArchive, GameError, GameMessage,
+    GameOverReason, GamePhase, GameType, LineDirection, MemoryStore, MoveOutcome, PlayerRole,
+    PresenceState, Region, RegionView, RoomManager, RulesConfig, SeatRequest, ServerConfig,
+    ThreatKind, TimeControl, UserManager,
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::sync::{Mutex, RwLock};
+
+// 取出通道里已有的全部消息
+fn drain(rx: &mut mpsc::Receiver<GameMessage>) -> Vec<GameMessage> {
+    let mut messages = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        messages.push(msg);
+    }
+    messages
+}
+
+#[tokio::test]
+async fn test_presence_is_relayed_to_opponent_only() {
+    let mut game = Game::new();
+    let (black_tx, mut black_rx) = mpsc::channel(32);
+    let (white_tx, mut white_rx) = mpsc::channel(32);
+
+    game.add_player(PlayerRole::Black, "black".to_string(), black_tx)
+        .await
+        .unwrap();
+    // 只有一位玩家时不转发
+    game.relay_presence(PlayerRole::Black, PresenceState::Thinking)
+        .await;
+    assert!(drain(&mut black_rx)
+        .iter()
+        .all(|msg| !matches!(msg, GameMessage::Presence { .. })));
+
+    game.add_player(PlayerRole::White, "white".to_string(), white_tx)
+        .await
+        .unwrap();
+    drain(&mut black_rx);
+    drain(&mut white_rx);
+
+    game.relay_presence(PlayerRole::Black, PresenceState::Thinking)
+        .await;
+    assert!(drain(&mut black_rx).is_empty());
+    let white_messages = drain(&mut white_rx);
+    assert_eq!(white_messages.len(), 1);
+    assert!(matches!(
+        white_messages[0],
+        GameMessage::Presence {
+            player: PlayerRole::Black,
+            state: PresenceState::Thinking,
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_move_history_and_sgf_export() {
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+
+    game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+    game.make_move(PlayerRole::White, 0, 1).await.unwrap();
+
+    let moves = game.moves();
+    assert_eq!(moves.len(), 2);
+    assert_eq!(
+        (moves[1].player, moves[1].row, moves[1].col),
+        (PlayerRole::White, 0, 1)
+    );
+
+    let sgf = game.export_sgf();
+    assert!(sgf.starts_with("(;FF[4]GM[4]CA[UTF-8]SZ[15]PB[alice]PW[bob]"));
+    assert!(sgf.ends_with(";B[hh];W[ba])"));
+    // 对局未结束时没有结果
+    assert!(!sgf.contains("RE["));
+}
+
+#[tokio::test]
+async fn test_finished_game_is_archived_for_replay() {
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    users.write().await.login("alice".to_string());
+    users.write().await.login("bob".to_string());
+    let mut game = Game::with_config(&ServerConfig::default(), archive.clone(), users.clone());
+    // 双方都登录过，计等级分
+    game.set_authenticated(PlayerRole::Black, true);
+    game.set_authenticated(PlayerRole::White, true);
+    let (black_tx, mut black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+
+    for col in 0..4 {
+        game.make_move(PlayerRole::Black, 7, col).await.unwrap();
+        game.make_move(PlayerRole::White, 0, col).await.unwrap();
+    }
+    let outcome = game.make_move(PlayerRole::Black, 7, 4).await.unwrap();
+    let MoveOutcome::Win(line) = outcome else {
+        panic!("应该分出胜负: {:?}", outcome);
+    };
+    assert_eq!(line.player, PlayerRole::Black);
+
+    // 存档通知在 GameOver 之前发出
+    let messages = drain(&mut black_rx);
+    let archived = messages
+        .iter()
+        .position(|msg| matches!(msg, GameMessage::GameArchived { .. }))
+        .unwrap();
+    let game_over = messages
+        .iter()
+        .position(|msg| matches!(msg, GameMessage::GameOver { .. }))
+        .unwrap();
+    assert!(archived < game_over);
+
+    let archive = archive.lock().await;
+    let record = archive.get(game.id()).unwrap();
+    assert_eq!(
+        (record.black.as_str(), record.white.as_str()),
+        ("alice", "bob")
+    );
+    assert_eq!(record.winner, Some(PlayerRole::Black));
+    assert_eq!(record.moves.len(), 9);
+    assert!(record.started_at <= record.moves[0].timestamp);
+
+    // 胜者加分，败者减分
+    let users = users.read().await;
+    let board = users.leaderboard(10);
+    assert_eq!(board[0].username, "alice");
+    assert!(board[0].rating > 1500 && board[1].rating < 1500);
+    assert_eq!((board[0].games, board[1].games), (1, 1));
+
+    let alice = users.get_user_by_name("alice").unwrap();
+    assert_eq!((alice.stats.wins, alice.stats.current_streak), (1, 1));
+    assert_eq!(alice.stats.moves, 5);
+    let bob = users.get_user_by_name("bob").unwrap();
+    assert_eq!((bob.stats.losses, bob.stats.current_streak), (1, -1));
+    assert_eq!(bob.stats.moves, 4);
+}
+
+#[tokio::test]
+async fn test_narration_is_sent_only_to_subscribers() {
+    let mut game = Game::new();
+    let (black_tx, mut black_rx) = mpsc::channel(64);
+    let (white_tx, mut white_rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.set_narration(PlayerRole::Black, true);
+    game.add_player(PlayerRole::White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+
+    for col in 0..4 {
+        game.make_move(PlayerRole::Black, 7, col).await.unwrap();
+        game.make_move(PlayerRole::White, 0, col).await.unwrap();
+    }
+    game.make_move(PlayerRole::Black, 7, 4).await.unwrap();
+
+    let narration: Vec<String> = drain(&mut black_rx)
+        .into_iter()
+        .filter_map(|msg| match msg {
+            GameMessage::Narration { text } => Some(text),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(narration[0], "bob 执白方加入对局");
+    assert_eq!(narration[1], "第 1 手：黑方落子于 (7, 0)，轮到白方");
+    assert!(narration.contains(&"第 7 手：黑方落子于 (7, 3)，形成 4 连，轮到白方".to_string()));
+    assert_eq!(narration.last().unwrap(), "对局结束，黑方连成一线获胜");
+    assert!(drain(&mut white_rx)
+        .iter()
+        .all(|msg| !matches!(msg, GameMessage::Narration { .. })));
+}
+
+#[test]
+fn test_game_over_narration_covers_unknown_reasons() {
+    use chess::narrate::narrate_game_over;
+    use GameOverReason::*;
+
+    assert_eq!(
+        narrate_game_over(Some(PlayerRole::White), Timeout),
+        "对局结束，白方因对方超时获胜"
+    );
+    assert_eq!(
+        narrate_game_over(None, BoardFull),
+        "对局结束，棋盘已下满，平局"
+    );
+    // 不认识的原因不再当作连成一线
+    assert_eq!(
+        narrate_game_over(Some(PlayerRole::Black), Unknown),
+        "对局结束，黑方获胜"
+    );
+    assert_eq!(narrate_game_over(None, Unknown), "对局结束，平局");
+}
+
+#[tokio::test]
+async fn test_feature_flags_from_config_and_admin_toggle() {
+    use chess::{Feature, GameType};
+    let config: ServerConfig =
+        serde_json::from_str(r#"{"features": {"gomoku": {"replay": false}}, "admins": ["root"]}"#)
+            .unwrap();
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let mut game = Game::with_config(&config, archive, users.clone());
+
+    assert!(!game.feature_enabled(Feature::Replay));
+    assert!(game.feature_enabled(Feature::Narration));
+    game.set_feature(GameType::Gomoku, Feature::Replay, true);
+    assert!(game.feature_enabled(Feature::Replay));
+    assert_eq!(
+        game.features().enabled(GameType::Gomoku),
+        Feature::ALL.to_vec()
+    );
+
+    let mut users = users.write().await;
+    users.set_admins(config.admins.clone());
+    // 管理员用户名不能通过连接抢注，只能由运维开户
+    assert!(users.register("root", "admin password").is_err());
+    assert!(users.register_admin("alice", "player password").is_err());
+    let root = users.register_admin("root", "admin password").unwrap();
+    let alice = users.register("alice", "player password").unwrap();
+    assert!(users.is_admin(&root));
+    assert!(!users.is_admin(&alice));
+}
+
+#[tokio::test]
+async fn test_spectator_summary_rates_only_signed_in_seats() {
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    users.write().await.register("alice", "password").unwrap();
+    users.write().await.register("bob", "password").unwrap();
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let mut game = Game::with_config(&ServerConfig::default(), archive, users.clone());
+    let (tx, _rx) = mpsc::channel(32);
+    game.add_player(PlayerRole::Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "bob".to_string(), tx)
+        .await
+        .unwrap();
+    // 白方以游客身份借用了注册用户的名字，不显示该用户的等级分
+    game.set_authenticated(PlayerRole::Black, true);
+
+    let summary = game.rated_summary().await.unwrap();
+    assert!(summary.black_rating.is_some());
+    assert_eq!(
+        summary.black_rating,
+        users.read().await.rating_by_name("alice")
+    );
+    assert_eq!(summary.white_rating, None);
+}
+
+#[tokio::test]
+async fn test_chat_is_rate_limited_per_sender() {
+    let mut game = Game::new();
+    let (tx, _rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "bob".to_string(), tx)
+        .await
+        .unwrap();
+
+    for _ in 0..chess::CHAT_BURST {
+        game.relay_chat("alice", "好棋").await.unwrap();
+    }
+    assert!(matches!(
+        game.relay_chat("alice", "好棋").await,
+        Err(GameError::Unavailable(_))
+    ));
+    // 一个人刷屏不影响对手说话
+    game.relay_chat("bob", "谢谢").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_full_rooms_queue_players_in_order() {
+    let config = ServerConfig {
+        max_rooms: 1,
+        ..ServerConfig::default()
+    };
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut rooms = RoomManager::new(config, archive, users);
+
+    // 两位玩家坐进同一个房间
+    let (tx, _rx) = mpsc::channel(32);
+    let (room, game) = rooms.try_seat(None, false).await.unwrap();
+    game.lock()
+        .await
+        .add_player(PlayerRole::Black, "black".to_string(), tx.clone())
+        .await
+        .unwrap();
+    let (second, _) = rooms.try_seat(None, false).await.unwrap();
+    assert_eq!(second, room);
+    game.lock()
+        .await
+        .add_player(PlayerRole::White, "white".to_string(), tx.clone())
+        .await
+        .unwrap();
+    assert!(rooms.try_seat(None, false).await.is_none());
+    assert_eq!(rooms.room_count(), 1);
+
+    let first = rooms.enqueue();
+    let later = rooms.enqueue();
+    assert_eq!(rooms.position(first), Some(1));
+    assert_eq!(rooms.position(later), Some(2));
+    // 没有历史对局时按默认时长估算
+    assert_eq!(
+        rooms.estimated_wait(2).await,
+        std::time::Duration::from_secs(600)
+    );
+
+    // 有空位后只有队首可以入座，新来的玩家不能插队
+    game.lock().await.remove_player(PlayerRole::White).await;
+    assert!(rooms.try_seat(None, false).await.is_none());
+    assert!(rooms.try_seat(Some(later), false).await.is_none());
+    assert!(rooms.try_seat(Some(first), false).await.is_some());
+    assert_eq!(rooms.position(later), Some(1));
+
+    rooms.leave_queue(later);
+    assert_eq!(rooms.position(later), None);
+    assert!(rooms.try_seat(None, false).await.is_some());
+}
+
+#[test]
+fn test_critical_messages_jump_ahead_of_background_traffic() {
+    use chess::Outbox;
+    let mut outbox = Outbox::new();
+    // 模拟一个积压了大量朗读和状态消息的连接
+    for i in 0..100 {
+        outbox.push(GameMessage::Narration {
+            text: format!("第 {} 条", i),
+        });
+        outbox.push(GameMessage::Presence {
+            player: PlayerRole::White,
+            state: PresenceState::Thinking,
+        });
+    }
+    outbox.push(GameMessage::Move {
+        row: 7,
+        col: 7,
+        game_id: "g1".to_string(),
+        move_seq: 0,
+        client_nonce: 0,
+        second: None,
+    });
+    outbox.push(GameMessage::TurnNotification {
+        player: PlayerRole::White,
+    });
+    outbox.push(GameMessage::GameOver {
+        winner: None,
+        winning_line: Vec::new(),
+        reason: Some(GameOverReason::BoardFull),
+    });
+    assert_eq!(outbox.len(), 203);
+
+    // 关键消息按原来的顺序最先发出
+    assert!(matches!(
+        outbox.pop(),
+        Some(GameMessage::Move { row: 7, col: 7, .. })
+    ));
+    assert!(matches!(
+        outbox.pop(),
+        Some(GameMessage::TurnNotification { .. })
+    ));
+    assert!(matches!(outbox.pop(), Some(GameMessage::GameOver { .. })));
+    // 之后才是后台消息，同样保持顺序
+    match outbox.pop() {
+        Some(GameMessage::Narration { text }) => assert_eq!(text, "第 0 条"),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(outbox.pop(), Some(GameMessage::Presence { .. })));
+
+    // 后来加的分析和查询回复不能插到落子前面，存档通知跟着 GameOver 一起优先
+    use chess::Priority;
+    let hint = GameMessage::Hint {
+        row: 7,
+        col: 7,
+        score: 0,
+        hints_left: 0,
+    };
+    assert_eq!(hint.priority(), Priority::Background);
+    assert_eq!(
+        GameMessage::ServerInfoRequest.priority(),
+        Priority::Background
+    );
+    let archived = GameMessage::GameArchived {
+        game_id: "g1".to_string(),
+    };
+    assert_eq!(archived.priority(), Priority::Critical);
+}
+
+#[test]
+fn test_outbox_drops_stale_background_and_flags_critical_overflow() {
+    use chess::{Outbox, MAX_BACKGROUND_BACKLOG, MAX_CRITICAL_BACKLOG};
+    let mut outbox = Outbox::new();
+    // 后台消息超过上限时丢掉最早的
+    for i in 0..MAX_BACKGROUND_BACKLOG + 10 {
+        outbox.push(GameMessage::Narration {
+            text: format!("第 {} 条", i),
+        });
+    }
+    assert_eq!(outbox.len(), MAX_BACKGROUND_BACKLOG);
+    assert_eq!(outbox.dropped(), 10);
+    match outbox.pop() {
+        Some(GameMessage::Narration { text }) => assert_eq!(text, "第 10 条"),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // 关键消息一条不丢，超过上限时要求断开
+    let turn = || GameMessage::TurnNotification {
+        player: PlayerRole::Black,
+    };
+    for _ in 0..MAX_CRITICAL_BACKLOG {
+        outbox.push(turn());
+    }
+    assert!(!outbox.overflowed());
+    outbox.push(turn());
+    assert!(outbox.overflowed());
+}
+
+#[test]
+fn test_zobrist_hash_is_incremental_and_order_independent() {
+    let mut a = Board::new();
+    let mut b = Board::new();
+    let empty = a.hash();
+    for (row, col) in [(7, 7), (0, 0), (8, 8), (1, 1)] {
+        a.make_move(row, col).unwrap();
+    }
+    // 同一方的棋子换个顺序下，局面相同
+    for (row, col) in [(8, 8), (1, 1), (7, 7), (0, 0)] {
+        b.make_move(row, col).unwrap();
+    }
+    assert_eq!(a.hash(), b.hash());
+
+    // 增量结果和从头计算一致
+    let mut rebuilt = Board::new();
+    rebuilt.cells = a.cells;
+    rebuilt.rehash();
+    assert_eq!(rebuilt.hash(), a.hash());
+
+    // 轮到谁不同，哈希也不同
+    a.undo_move().unwrap();
+    assert_ne!(a.hash(), b.hash());
+    while a.undo_move().is_some() {}
+    assert_eq!(a.hash(), empty);
+    assert_eq!(a.current_player, PlayerRole::Black);
+}
+
+#[test]
+fn test_winning_line_lists_every_stone_from_one_end() {
+    let mut board = Board::new();
+    // 黑棋在反对角线上连成六子，白棋随便下
+    for (i, row) in (2..8).enumerate() {
+        board.make_move(row, 10 - row).unwrap();
+        if i < 5 {
+            board.make_move(14, i).unwrap();
+        }
+    }
+    let line = board.winning_line().unwrap();
+    assert_eq!(line.player, PlayerRole::Black);
+    assert_eq!(line.direction, LineDirection::AntiDiagonal);
+    assert_eq!(
+        line.cells,
+        vec![(2, 8), (3, 7), (4, 6), (5, 5), (6, 4), (7, 3)]
+    );
+    assert_eq!(board.check_winner(), Some(PlayerRole::Black));
+
+    board.undo_move();
+    board.undo_move();
+    board.undo_move();
+    assert_eq!(board.winning_line(), None);
+}
+
+#[test]
+fn test_smaller_board_with_four_in_a_row() {
+    let rules = RulesConfig {
+        board_size: 9,
+        win_length: 4,
+        ..RulesConfig::default()
+    };
+    assert!(rules.validate().is_ok());
+    assert!(RulesConfig {
+        board_size: 9,
+        win_length: 7,
+        ..RulesConfig::default()
+    }
+    .validate()
+    .is_err());
+    assert!(RulesConfig {
+        board_size: 3,
+        win_length: 4,
+        ..RulesConfig::default()
+    }
+    .validate()
+    .is_err());
+
+    let mut board = Board::with_rules(rules);
+    assert!(board.make_move(9, 0).is_err());
+    assert!(board.make_move(0, 9).is_err());
+    // 黑棋在最后一行连成四子，白棋在第一行
+    for col in 5..8 {
+        board.make_move(8, col).unwrap();
+        board.make_move(0, col).unwrap();
+        assert_eq!(board.winning_line(), None);
+    }
+    board.make_move(8, 8).unwrap();
+    let line = board.winning_line().unwrap();
+    assert_eq!(line.player, PlayerRole::Black);
+    assert_eq!(line.cells, vec![(8, 5), (8, 6), (8, 7), (8, 8)]);
+}
+
+#[test]
+fn test_region_view_forwards_window_and_summarises_outside() {
+    let stone_at = |row, col, by: PlayerRole, move_number| GameMessage::MoveApplied {
+        row,
+        col,
+        by,
+        next_player: by.other(),
+        move_number,
+    };
+    let mut view = RegionView::new();
+    let mut board = Box::new([[None; 15]; 15]);
+    board[0][0] = Some(PlayerRole::Black);
+    view.filter(GameMessage::Status {
+        board,
+        current_player: PlayerRole::White,
+        move_number: 1,
+        last_move: Some((0, 0)),
+        moves: Vec::new(),
+    });
+
+    assert!(view
+        .subscribe(Some(Region {
+            top: 10,
+            left: 10,
+            rows: 6,
+            cols: 2
+        }))
+        .is_err());
+    // 客户端发来的超大坐标不能溢出
+    let huge = Region {
+        top: usize::MAX,
+        left: 0,
+        rows: 2,
+        cols: 2,
+    };
+    assert!(huge.validate(15).is_err());
+    let corner = Region {
+        top: 5,
+        left: 5,
+        rows: 4,
+        cols: 4,
+    };
+    assert!(corner.validate(9).is_ok());
+    assert!(corner.validate(8).is_err());
+    let region = Region {
+        top: 6,
+        left: 6,
+        rows: 3,
+        cols: 3,
+    };
+    let GameMessage::RegionUpdate { cells, outside, .. } = view.subscribe(Some(region)).unwrap()
+    else {
+        panic!("订阅后应该收到窗口局面");
+    };
+    assert_eq!(cells, vec![vec![None; 3]; 3]);
+    assert_eq!(outside.black_stones, 1);
+
+    // 窗口内的落子原样转发，窗口外的只发概况
+    assert!(matches!(
+        view.filter(stone_at(7, 7, PlayerRole::White, 2)),
+        GameMessage::MoveApplied { row: 7, col: 7, .. }
+    ));
+    let GameMessage::RegionSummary {
+        current_player,
+        outside,
+    } = view.filter(stone_at(14, 14, PlayerRole::Black, 3))
+    else {
+        panic!("窗口外的落子应该只发概况");
+    };
+    assert_eq!(current_player, PlayerRole::White);
+    assert_eq!(outside.black_stones, 2);
+    assert_eq!(outside.white_stones, 0);
+    assert_eq!(outside.last_move, Some((14, 14)));
+
+    // 取消订阅后回到整盘局面
+    let GameMessage::Status { board, .. } = view.subscribe(None).unwrap() else {
+        panic!("取消订阅后应该收到整盘局面");
+    };
+    assert_eq!(board[7][7], Some(PlayerRole::White));
+}
+
+// 对局当前轮到谁
+fn turn(game: &Game) -> PlayerRole {
+    match game.status() {
+        GameMessage::Status { current_player, .. } => current_player,
+        other => panic!("应该是 Status: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_connect6_turns_and_paired_moves() {
+    use PlayerRole::*;
+    let config = ServerConfig {
+        rules: RulesConfig::connect6(),
+        ..ServerConfig::default()
+    };
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut game = Game::with_config(&config, archive, users);
+    assert_eq!(game.game_type(), GameType::Connect6);
+    let (black_tx, _black_rx) = mpsc::channel(256);
+    let (white_tx, _white_rx) = mpsc::channel(256);
+    game.add_player(Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+    let id = game.id().to_string();
+
+    // 黑方第一手只下一子
+    assert!(game
+        .submit_move(Black, &[(7, 7), (7, 8)], &id, 0, 1)
+        .await
+        .is_err());
+    game.submit_move(Black, &[(7, 7)], &id, 0, 1).await.unwrap();
+    assert_eq!(turn(&game), White);
+
+    // 两子有一子不合法时一子都不落
+    assert!(game
+        .submit_move(White, &[(0, 0), (7, 7)], &id, 1, 2)
+        .await
+        .is_err());
+    assert!(game
+        .submit_move(White, &[(0, 0), (0, 0)], &id, 1, 2)
+        .await
+        .is_err());
+    assert_eq!(game.moves().len(), 1);
+
+    // 白方分两次提交同一回合的两子，第一子之后还是白方
+    game.submit_move(White, &[(0, 0)], &id, 1, 3).await.unwrap();
+    assert_eq!(turn(&game), White);
+    game.submit_move(White, &[(0, 1)], &id, 2, 4).await.unwrap();
+    assert_eq!(turn(&game), Black);
+
+    // 黑方第 7 行连到五子还没赢
+    game.submit_move(Black, &[(7, 8), (7, 9)], &id, 3, 5)
+        .await
+        .unwrap();
+    game.submit_move(White, &[(0, 2), (0, 3)], &id, 5, 6)
+        .await
+        .unwrap();
+    let outcome = game
+        .submit_move(Black, &[(7, 10), (7, 11)], &id, 7, 7)
+        .await
+        .unwrap();
+    assert_eq!(outcome, Some(MoveOutcome::Continue));
+    game.submit_move(White, &[(14, 0), (14, 1)], &id, 9, 8)
+        .await
+        .unwrap();
+    // 第一子连成六子就结束，第二子不再落下
+    let outcome = game
+        .submit_move(Black, &[(7, 12), (14, 14)], &id, 11, 9)
+        .await
+        .unwrap();
+    let Some(MoveOutcome::Win(line)) = outcome else {
+        panic!("应该分出胜负: {:?}", outcome);
+    };
+    assert_eq!(line.cells.len(), 6);
+    assert_eq!(game.moves().len(), 12);
+
+    // 撤回时轮次跟着退回
+    let mut board = Board::with_rules(RulesConfig::connect6());
+    for (row, col) in [(7, 7), (0, 0), (0, 1)] {
+        board.make_move(row, col).unwrap();
+    }
+    assert_eq!(board.current_player, Black);
+    assert_eq!(board.stones_left(), 2);
+    board.undo_move();
+    assert_eq!(board.current_player, White);
+    assert_eq!(board.stones_left(), 1);
+}
+
+#[tokio::test]
+async fn test_preview_move_reports_threats_without_playing() {
+    use PlayerRole::*;
+    let mut board = Board::new();
+    // 黑棋第 7 行 4..=6 列，白棋堵住第 3 列
+    for (row, col) in [(7, 4), (7, 3), (7, 5), (0, 0), (7, 6), (0, 14)] {
+        board.make_move(row, col).unwrap();
+    }
+    let hash = board.hash();
+
+    let preview = board.preview_move(7, 7).unwrap();
+    assert_eq!(preview.player, Black);
+    assert!(!preview.is_win());
+    assert_eq!(preview.threats.len(), 1);
+    assert_eq!(preview.threats[0].kind, ThreatKind::Four);
+    assert_eq!(preview.threats[0].direction, LineDirection::Horizontal);
+    assert_eq!(
+        preview.threats[0].cells,
+        vec![(7, 4), (7, 5), (7, 6), (7, 7)]
+    );
+
+    // 第 6 列竖着两头都空
+    board.make_move(8, 6).unwrap();
+    board.make_move(1, 1).unwrap();
+    let preview = board.preview_move(9, 6).unwrap();
+    assert_eq!(preview.threats[0].kind, ThreatKind::OpenThree);
+    assert_eq!(preview.threats[0].direction, LineDirection::Vertical);
+
+    board.make_move(7, 7).unwrap();
+    board.make_move(2, 2).unwrap();
+    assert!(board.preview_move(7, 8).unwrap().is_win());
+    assert!(board.preview_move(7, 7).is_err());
+    assert!(board.preview_move(15, 0).is_err());
+    // 试下不改变棋盘
+    assert_eq!(board.cells[7][8], None);
+    assert_ne!(board.hash(), hash);
+    let hash = board.hash();
+    board.preview_move(7, 8).unwrap();
+    assert_eq!(board.hash(), hash);
+
+    // 对局里只能试下自己回合的一手
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.add_player(Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+    assert!(game.preview_move(White, 7, 7).is_err());
+    let preview = game.preview_move(Black, 7, 7).unwrap();
+    assert!(preview.threats.is_empty());
+    assert!(game.moves().is_empty());
+}
+
+#[tokio::test]
+async fn test_hint_quota_starts_over_in_the_next_game() {
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), black_tx.clone())
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "bob".to_string(), white_tx.clone())
+        .await
+        .unwrap();
+    while game.take_hint(PlayerRole::Black).is_ok() {}
+    assert!(game.take_hint(PlayerRole::Black).is_err());
+
+    // 双方都离开后房间重置，下一盘的提示重新计数
+    game.remove_player(PlayerRole::Black).await;
+    game.remove_player(PlayerRole::White).await;
+    game.add_player(PlayerRole::Black, "carol".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "dave".to_string(), white_tx)
+        .await
+        .unwrap();
+    let (_, _, hints_left) = game.take_hint(PlayerRole::Black).unwrap();
+    assert_eq!(hints_left, chess::DEFAULT_HINTS_PER_GAME - 1);
+}
+
+#[tokio::test]
+async fn test_hints_need_a_running_game_and_are_refunded() {
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.add_player(PlayerRole::Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    // 对手还没来，提示不扣次数
+    assert!(game.take_hint(PlayerRole::Black).is_err());
+    game.add_player(PlayerRole::White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+    let (_, _, hints_left) = game.take_hint(PlayerRole::Black).unwrap();
+    assert_eq!(hints_left, chess::DEFAULT_HINTS_PER_GAME - 1);
+
+    // 搜索没给出落点时退回
+    game.refund_hint(PlayerRole::Black);
+    let (_, _, hints_left) = game.take_hint(PlayerRole::Black).unwrap();
+    assert_eq!(hints_left, chess::DEFAULT_HINTS_PER_GAME - 1);
+
+    // 暂停时不能请求
+    game.disconnect(PlayerRole::White).await;
+    assert_eq!(game.phase(), GamePhase::Paused);
+    assert!(game.take_hint(PlayerRole::Black).is_err());
+}
+
+#[tokio::test]
+async fn test_crash_dump_records_seats_moves_and_recent_events() {
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let rooms = Mutex::new(RoomManager::new(ServerConfig::default(), archive, users));
+    let (tx, _rx) = mpsc::channel(64);
+    let (_, game) = rooms.lock().await.try_seat(None, false).await.unwrap();
+    {
+        let mut game = game.lock().await;
+        game.add_player(PlayerRole::Black, "alice".to_string(), tx.clone())
+            .await
+            .unwrap();
+        game.add_player(PlayerRole::White, "bob".to_string(), tx.clone())
+            .await
+            .unwrap();
+        game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+        game.make_move(PlayerRole::White, 7, 8).await.unwrap();
+    }
+
+    let dump = CrashDump::capture(&rooms, "收到 SIGTERM").await;
+    assert_eq!(dump.rooms.len(), 1);
+    let room = &dump.rooms[0];
+    let seats: Vec<_> = room
+        .seats
+        .iter()
+        .map(|seat| seat.username.as_str())
+        .collect();
+    assert_eq!(seats, ["alice", "bob"]);
+    assert_eq!(room.moves.len(), 2);
+    // 没有人订阅朗读，事件照样记下来
+    assert_eq!(room.events.len(), 4);
+    assert_eq!(room.events[2].text, "第 1 手：黑方落子于 (7, 7)，轮到白方");
+
+    // 出错的任务还拿着锁时不等待，只记下房间编号
+    let guard = game.lock().await;
+    let dump = CrashDump::try_capture(&rooms, "panic");
+    assert!(dump.rooms.is_empty());
+    assert_eq!(dump.unavailable, [0]);
+    drop(guard);
+
+    let dir = std::env::temp_dir().join(format!("gomoku-dump-{}", std::process::id()));
+    let path = dump.write_to(&dir).unwrap();
+    let restored = CrashDump::read_from(&path).unwrap();
+    assert_eq!(restored.reason, "panic");
+    assert_eq!(restored.unavailable, [0]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_closed_player_channel_does_not_panic_the_game() {
+    let mut game = Game::new();
+    // 还没入座连接就断了：返回错误，不占座位
+    let (gone_tx, gone_rx) = mpsc::channel(32);
+    drop(gone_rx);
+    let result = game
+        .add_player(PlayerRole::Black, "gone".to_string(), gone_tx)
+        .await;
+    assert!(matches!(
+        result,
+        Err(GameError::Disconnected(PlayerRole::Black))
+    ));
+    assert_eq!(game.player_count(), 0);
+
+    let (black_tx, black_rx) = mpsc::channel(32);
+    let (white_tx, mut white_rx) = mpsc::channel(32);
+    game.add_player(PlayerRole::Black, "black".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(PlayerRole::White, "white".to_string(), white_tx)
+        .await
+        .unwrap();
+    // 黑方的发送任务已经结束，落子照常进行，白方照常收到通知
+    drop(black_rx);
+    game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+    assert!(drain(&mut white_rx)
+        .iter()
+        .any(|msg| matches!(msg, GameMessage::MoveApplied { row: 7, col: 7, .. })));
+    game.make_move(PlayerRole::White, 8, 8).await.unwrap();
+
+    game.remove_player(PlayerRole::White).await;
+    assert_eq!(game.player_count(), 1);
+}
+
+#[tokio::test]
+async fn test_game_phase_follows_legal_transitions() {
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    assert_eq!(game.phase(), GamePhase::Waiting);
+    game.add_player(PlayerRole::Black, "black".to_string(), black_tx)
+        .await
+        .unwrap();
+    assert_eq!(game.phase(), GamePhase::Waiting);
+    game.add_player(PlayerRole::White, "white".to_string(), white_tx)
+        .await
+        .unwrap();
+    assert_eq!(game.phase(), GamePhase::Playing);
+
+    // 掉线暂停，暂停中不能落子，重连后继续
+    assert!(game.disconnect(PlayerRole::White).await.is_some());
+    assert_eq!(game.phase(), GamePhase::Paused);
+    assert!(game.make_move(PlayerRole::Black, 7, 7).await.is_err());
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    game.resume(PlayerRole::White, white_tx).await;
+    assert_eq!(game.phase(), GamePhase::Playing);
+    game.make_move(PlayerRole::Black, 7, 7).await.unwrap();
+
+    game.resign(PlayerRole::White).await.unwrap();
+    assert_eq!(game.phase(), GamePhase::Finished);
+    assert!(game.resign(PlayerRole::Black).await.is_err());
+    assert!(!GamePhase::Finished.can_enter(GamePhase::Playing));
+    assert!(!GamePhase::Waiting.can_enter(GamePhase::Paused));
+
+    // 双方都离开后回到等待，新的一盘从头开始
+    game.remove_player(PlayerRole::Black).await;
+    game.remove_player(PlayerRole::White).await;
+    assert_eq!(game.phase(), GamePhase::Waiting);
+    assert!(game.moves().is_empty());
+}
+
+#[tokio::test]
+async fn test_add_player_picks_a_free_seat_atomically() {
+    let mut game = Game::new();
+    let (black_tx, _black_rx) = mpsc::channel(64);
+    let (white_tx, _white_rx) = mpsc::channel(64);
+    let (late_tx, _late_rx) = mpsc::channel(64);
+
+    // 没有偏好时先到先坐黑方，指定的座位有人时拒绝
+    let first = game
+        .add_player(SeatRequest::Prefer(None), "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    assert_eq!(first, PlayerRole::Black);
+    assert!(game
+        .add_player(PlayerRole::Black, "bob".to_string(), white_tx.clone())
+        .await
+        .is_err());
+    // 想执的颜色有人坐了就分到另一边
+    let second = game
+        .add_player(
+            SeatRequest::Prefer(Some(PlayerRole::Black)),
+            "bob".to_string(),
+            white_tx,
+        )
+        .await
+        .unwrap();
+    assert_eq!(second, PlayerRole::White);
+    assert!(game
+        .add_player(SeatRequest::Prefer(None), "carol".to_string(), late_tx)
+        .await
+        .is_err());
+    assert_eq!(game.player_name(PlayerRole::White), Some("bob"));
+}
+
+#[tokio::test]
+async fn test_resumed_game_reservation_expires() {
+    use PlayerRole::*;
+    let mut game = Game::new();
+    let (tx, mut rx) = mpsc::channel(64);
+    game.add_player(Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.make_move(Black, 7, 7).await.unwrap();
+    let record = game.dump(0);
+
+    // 只有一方回来，期限到了坐着的一方获胜
+    let mut restored = Game::from_record(&record, DowntimePolicy::Pause).unwrap();
+    restored
+        .add_player(Black, "alice".to_string(), tx)
+        .await
+        .unwrap();
+    drain(&mut rx);
+    restored.expire_reservation("another-game").await;
+    assert!(restored.is_reserved());
+    restored.expire_reservation(&record.game_id).await;
+    assert!(!restored.is_reserved());
+    assert!(restored.is_finished());
+    assert!(drain(&mut rx).iter().any(|msg| matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: Some(Black),
+            reason: Some(GameOverReason::Disconnect),
+            ..
+        }
+    )));
+
+    // 谁都没回来，房间清空
+    let mut empty = Game::from_record(&record, DowntimePolicy::Pause).unwrap();
+    empty.expire_reservation(&record.game_id).await;
+    assert!(!empty.is_reserved());
+    assert_ne!(empty.id(), record.game_id);
+    assert_eq!(empty.phase(), GamePhase::Waiting);
+}
+
+#[tokio::test]
+async fn test_game_is_rebuilt_from_its_record_after_a_restart() {
+    use PlayerRole::*;
+    let config = ServerConfig {
+        time_control: Some(TimeControl {
+            main_time_secs: 300,
+            increment_secs: 5,
+        }),
+        ..ServerConfig::default()
+    };
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut game = Game::with_config(&config, archive, users);
+    let (tx, _rx) = mpsc::channel(64);
+    game.add_player(Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), tx).await.unwrap();
+    for (player, row, col) in [(Black, 7, 7), (White, 7, 8), (Black, 8, 8)] {
+        game.make_move(player, row, col).await.unwrap();
+    }
+    let record = game.dump(0);
+
+    let restored = Game::from_record(&record, DowntimePolicy::Pause).unwrap();
+    assert_eq!(restored.id(), game.id());
+    assert_eq!(restored.seed(), game.seed());
+    assert_eq!(restored.phase(), GamePhase::Waiting);
+    let (before, after) = (game.position(), restored.position());
+    assert_eq!(after.cells, before.cells);
+    assert_eq!(after.current_player, White);
+    assert_eq!(after.hash(), before.hash());
+    assert_eq!(restored.moves()[1].timestamp, game.moves()[1].timestamp);
+    // 时钟停在保存时的剩余时间，加秒也都算上了
+    let clock = restored.dump(0).clock.unwrap();
+    let saved = record.clock.clone().unwrap();
+    assert_eq!(
+        (clock.black_ms, clock.white_ms),
+        (saved.black_ms, saved.white_ms)
+    );
+    assert!(clock.black_ms > 300_000);
+    assert_eq!(clock.running, None);
+
+    // 配置成计入停机时间时，停机的一分钟记在轮到的白方身上
+    let mut stale = record.clone();
+    stale.clock.as_mut().unwrap().saved_at -= chrono::Duration::seconds(60);
+    let restored = Game::from_record(&stale, DowntimePolicy::Charge).unwrap();
+    let charged = restored.dump(0).clock.unwrap();
+    assert_eq!(charged.black_ms, saved.black_ms);
+    assert!(charged.white_ms <= saved.white_ms - 60_000);
+
+    // 不按轮次的记录不能重放
+    let mut board = Board::new();
+    assert!(board.apply_moves(&[(Black, 7, 7), (Black, 7, 8)]).is_err());
+    let mut tampered = record.clone();
+    tampered.moves[1].player = Black;
+    assert!(Game::from_record(&tampered, DowntimePolicy::Pause).is_err());
+}
+
+#[tokio::test]
+async fn test_records_that_do_not_replay_are_flagged_instead_of_restored() {
+    use PlayerRole::*;
+    let mut game = Game::new();
+    let (tx, _rx) = mpsc::channel(64);
+    game.add_player(Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), tx).await.unwrap();
+    for (player, row, col) in [(Black, 7, 7), (White, 0, 0), (Black, 7, 8)] {
+        game.make_move(player, row, col).await.unwrap();
+    }
+    let record = game.dump(0);
+    assert!(record.verify().is_ok());
+
+    // 存储里的一子被改了位置，校验和对不上
+    let mut moved = record.clone();
+    moved.game_id = "moved".to_string();
+    moved.moves[1].col = 1;
+    assert!(matches!(moved.verify(), Err(GameError::Corrupted(_))));
+    // 没下完的对局记了胜方
+    let mut decided = record.clone();
+    decided.game_id = "decided".to_string();
+    decided.winner = Some(Black);
+    assert!(Game::from_record(&decided, DowntimePolicy::Pause).is_err());
+
+    // 已存档的对局：棋盘上黑方连五，记录却是白胜
+    let mut five = Board::new();
+    for (row, col) in [
+        (7, 7),
+        (0, 0),
+        (7, 8),
+        (0, 2),
+        (7, 9),
+        (0, 4),
+        (7, 10),
+        (0, 6),
+        (7, 11),
+    ] {
+        five.make_move(row, col).unwrap();
+    }
+    let mut archived = game.to_archived();
+    archived.moves = five.moves.clone();
+    archived.position_hash = Some(five.hash());
+    archived.winner = Some(Black);
+    assert!(archived.verify().is_ok());
+    let mut flipped = archived.clone();
+    flipped.id = "flipped".to_string();
+    flipped.winner = Some(White);
+    let store = shared(MemoryStore::new());
+    for game in [&archived, &flipped] {
+        store.lock().unwrap().save_game(game).unwrap();
+    }
+    let archive = GameArchive::with_store(store);
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive.flagged()[0].game_id, "flipped");
+
+    let archive = Arc::new(Mutex::new(archive));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut rooms = RoomManager::new(ServerConfig::default(), archive, users);
+    rooms.load_adjourned(vec![record, moved, decided]);
+    assert_eq!(rooms.adjourned().len(), 1);
+    let flagged: Vec<_> = rooms
+        .flagged()
+        .await
+        .into_iter()
+        .map(|issue| issue.game_id)
+        .collect();
+    assert_eq!(flagged, ["moved", "decided", "flipped"]);
+}
+
+#[tokio::test]
+async fn test_draw_conditions_and_draw_agreement() {
+    use PlayerRole::*;
+    // 步数上限
+    let mut board = Board::with_rules(RulesConfig {
+        move_limit: 4,
+        ..RulesConfig::default()
+    });
+    for (row, col) in [(0, 0), (1, 1), (2, 2)] {
+        board.make_move(row, col).unwrap();
+    }
+    assert_eq!(board.draw_reason(), None);
+    board.make_move(3, 3).unwrap();
+    assert_eq!(board.draw_reason(), Some(GameOverReason::MoveLimit));
+
+    // 4 路棋盘连 4 子：每行、每列、两条对角线都有双方的棋子后提前判和
+    let config = ServerConfig {
+        rules: RulesConfig {
+            board_size: 4,
+            win_length: 4,
+            draw_when_blocked: true,
+            ..RulesConfig::default()
+        },
+        ..ServerConfig::default()
+    };
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut game = Game::with_config(&config, archive.clone(), users.clone());
+    let (black_tx, mut black_rx) = mpsc::channel(256);
+    let (white_tx, _white_rx) = mpsc::channel(256);
+    game.add_player(Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+    let script = [(0, 0), (1, 1), (1, 2), (0, 3), (2, 1), (2, 0), (3, 3)];
+    for (i, &(row, col)) in script.iter().enumerate() {
+        let player = if i % 2 == 0 { Black } else { White };
+        let outcome = game.make_move(player, row, col).await.unwrap();
+        assert_eq!(outcome, MoveOutcome::Continue);
+    }
+    let outcome = game.make_move(White, 3, 2).await.unwrap();
+    assert_eq!(outcome, MoveOutcome::Draw(GameOverReason::Blocked));
+    assert!(drain(&mut black_rx).iter().any(|msg| matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: None,
+            reason: Some(GameOverReason::Blocked),
+            ..
+        }
+    )));
+
+    // 提和：拒绝后对局继续，落子让提议作废，同意后以和棋结束
+    let mut game = Game::with_config(&ServerConfig::default(), archive, users);
+    let (black_tx, mut black_rx) = mpsc::channel(256);
+    let (white_tx, mut white_rx) = mpsc::channel(256);
+    game.add_player(Black, "alice".to_string(), black_tx)
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), white_tx)
+        .await
+        .unwrap();
+    assert!(game.answer_draw(White, true).await.is_err());
+    game.offer_draw(Black).await.unwrap();
+    assert!(drain(&mut white_rx)
+        .iter()
+        .any(|msg| matches!(msg, GameMessage::DrawOffered { player: Black })));
+    game.answer_draw(White, false).await.unwrap();
+    assert!(drain(&mut black_rx)
+        .iter()
+        .any(|msg| matches!(msg, GameMessage::DrawDeclined { player: White })));
+    assert_eq!(game.phase(), GamePhase::Playing);
+
+    game.offer_draw(White).await.unwrap();
+    game.make_move(Black, 7, 7).await.unwrap();
+    assert_eq!(game.draw_offer(), None);
+    assert!(game.answer_draw(Black, true).await.is_err());
+
+    game.offer_draw(White).await.unwrap();
+    // 双方都提出和棋时直接成和
+    game.offer_draw(Black).await.unwrap();
+    assert_eq!(game.phase(), GamePhase::Finished);
+    assert!(drain(&mut black_rx).iter().any(|msg| matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: None,
+            reason: Some(GameOverReason::DrawAgreed),
+            ..
+        }
+    )));
+}
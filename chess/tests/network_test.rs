@@ -0,0 +1,1715 @@
+use chess::admin::{self, AdminCommand, AdminReply};
+use chess::{
+    graceful_shutdown, shared, Capability, DemoAction, Difficulty, ErrorCode, ErrorReply, Feature,
+    Game, GameArchive, GameFilter, GameMessage, GameOverReason, GameType, InviteRole, MemoryStore,
+    NetworkPlayer, PlayerRole, Region, RoomManager, RoomQuota, RoomQuotas, ServerConfig,
+    StrictMode, TimeControl, UnfinishedGame, UserManager, VariationNode, Violation,
+    PROTOCOL_VERSION,
+};
+use futures_util::{SinkExt, StreamExt};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, RwLock};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// 在随机端口启动一个服务器，返回连接地址
+async fn start_server(config: ServerConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        config,
+        archive.clone(),
+        users.clone(),
+    )));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let player = NetworkPlayer::new(stream, rooms.clone(), users.clone(), archive.clone());
+            tokio::spawn(player.play());
+        }
+    });
+    format!("ws://{}", addr)
+}
+
+async fn send(client: &mut Client, msg: &GameMessage) {
+    let json = serde_json::to_string(msg).unwrap();
+    client.send(Message::Text(json)).await.unwrap();
+}
+
+// 读到满足条件的消息为止，超时则测试失败
+async fn wait_for(client: &mut Client, matches: impl Fn(&GameMessage) -> bool) -> GameMessage {
+    let read = async {
+        while let Some(Ok(frame)) = client.next().await {
+            if let Message::Text(text) = frame {
+                let msg: GameMessage = serde_json::from_str(&text).unwrap();
+                if matches(&msg) {
+                    return msg;
+                }
+            }
+        }
+        panic!("连接已关闭");
+    };
+    tokio::time::timeout(Duration::from_secs(10), read)
+        .await
+        .expect("等待消息超时")
+}
+
+// 入座并返回对局编号
+async fn join(url: &str, username: &str) -> (Client, String) {
+    let (client, game_id, _) = join_preferring(url, username, None).await;
+    (client, game_id)
+}
+
+// 带着想执的颜色入座，返回对局编号和实际分到的座位
+async fn join_preferring(
+    url: &str,
+    username: &str,
+    preferred_role: Option<PlayerRole>,
+) -> (Client, String, PlayerRole) {
+    let (mut client, _) = connect_async(url).await.unwrap();
+    send(
+        &mut client,
+        &GameMessage::ConnectRequest {
+            username: username.to_string(),
+            token: None,
+            play_vs_ai: None,
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role,
+            resume: None,
+        },
+    )
+    .await;
+    let GameMessage::ConnectResponse {
+        game_id,
+        player_role,
+        ..
+    } = wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    (client, game_id, player_role)
+}
+
+// 第 move_seq 步落子，随机数按步数生成，重发时保持不变
+fn play(game_id: &str, move_seq: usize, row: usize, col: usize) -> GameMessage {
+    GameMessage::Move {
+        row,
+        col,
+        game_id: game_id.to_string(),
+        move_seq,
+        client_nonce: move_seq as u64 + 1,
+        second: None,
+    }
+}
+
+#[tokio::test]
+async fn test_silent_player_is_removed_after_idle_timeout() {
+    let url = start_server(ServerConfig {
+        heartbeat_interval_secs: 1,
+        idle_timeout_secs: 3,
+        ..ServerConfig::default()
+    })
+    .await;
+
+    let (mut alice, _) = join(&url, "alice").await;
+    // bob 入座后不再读取，也就不会回复 Ping
+    let _bob = join(&url, "bob").await;
+
+    // alice 一直在读，会自动回复 Pong，因此保持在线并收到对手掉线的通知
+    let msg = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GamePaused { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::GamePaused {
+            player: PlayerRole::White,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_logged_in_player_can_move_game_to_another_device() {
+    let url = start_server(ServerConfig::default()).await;
+
+    let (mut phone, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut phone,
+        &GameMessage::Register {
+            username: "alice".to_string(),
+            password: "secret-password".to_string(),
+        },
+    )
+    .await;
+    let GameMessage::AuthToken { token, .. } = wait_for(&mut phone, |msg| {
+        matches!(msg, GameMessage::AuthToken { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    let connect = GameMessage::ConnectRequest {
+        username: "alice".to_string(),
+        token: Some(token),
+        play_vs_ai: None,
+        invite: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: None,
+        resume: None,
+    };
+    send(&mut phone, &connect).await;
+    wait_for(&mut phone, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    let (mut bob, _) = join(&url, "bob").await;
+
+    // 用同一个令牌从新设备接入，接管原来的黑方座位
+    let (mut laptop, _) = connect_async(&url).await.unwrap();
+    send(&mut laptop, &connect).await;
+    let response = wait_for(&mut laptop, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    let GameMessage::ConnectResponse {
+        player_role: PlayerRole::Black,
+        game_id,
+        ..
+    } = response
+    else {
+        panic!("unexpected {:?}", response);
+    };
+    wait_for(&mut phone, |msg| {
+        matches!(msg, GameMessage::SessionTransferred)
+    })
+    .await;
+
+    // 对局在新设备上继续
+    send(&mut laptop, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::MoveApplied { row: 7, col: 7, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_disconnected_player_can_rejoin_paused_game() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (bob, _) = join(&url, "bob").await;
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+
+    drop(bob);
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GamePaused { .. })
+    })
+    .await;
+    // 暂停期间不能落子
+    send(&mut alice, &play(&game_id, 1, 0, 0)).await;
+    wait_for(&mut alice, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    let (mut bob, rejoined_id) = join(&url, "bob").await;
+    assert_eq!(rejoined_id, game_id);
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::GameResumed {
+                player: PlayerRole::White
+            }
+        )
+    })
+    .await;
+    // 重连后拿到原来的局面，轮到白方
+    wait_for(&mut bob, |msg| {
+        matches!(
+            msg,
+            GameMessage::TurnNotification {
+                player: PlayerRole::White
+            }
+        )
+    })
+    .await;
+    send(&mut bob, &play(&game_id, 1, 8, 8)).await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::MoveApplied { row: 8, col: 8, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_player_forfeits_when_grace_period_expires() {
+    let url = start_server(ServerConfig {
+        reconnect_grace_secs: 1,
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut alice, _) = join(&url, "alice").await;
+    let (bob, _) = join(&url, "bob").await;
+
+    drop(bob);
+    let msg = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GameOver { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: Some(PlayerRole::Black),
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_resign_and_goodbye_end_the_game_without_a_grace_period() {
+    let url = start_server(ServerConfig {
+        reconnect_grace_secs: 60,
+        ..ServerConfig::default()
+    })
+    .await;
+    let game_over = |msg: &GameMessage| {
+        matches!(
+            msg,
+            GameMessage::GameOver { .. } | GameMessage::GamePaused { .. }
+        )
+    };
+
+    let (mut alice, _) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    send(&mut alice, &GameMessage::Resign).await;
+    let msg = wait_for(&mut bob, game_over).await;
+    assert!(matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: Some(PlayerRole::White),
+            reason: Some(GameOverReason::Resignation),
+            ..
+        }
+    ));
+
+    // 不认输直接离开：对手马上获胜，不会进入掉线暂停
+    let (mut carol, _) = join(&url, "carol").await;
+    let (mut dave, _) = join(&url, "dave").await;
+    send(&mut dave, &GameMessage::Goodbye).await;
+    dave.close(None).await.unwrap();
+    let msg = wait_for(&mut carol, game_over).await;
+    assert!(matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: Some(PlayerRole::Black),
+            reason: Some(GameOverReason::Disconnect),
+            ..
+        }
+    ));
+    // 对局已经结束，不能再认输
+    send(&mut carol, &GameMessage::Resign).await;
+    wait_for(&mut carol, |msg| matches!(msg, GameMessage::Error(_))).await;
+}
+
+#[tokio::test]
+async fn test_server_ai_fills_second_seat_and_replies() {
+    let url = start_server(ServerConfig::default()).await;
+    // 已经有人在等对手，和电脑对弈的玩家也不会坐进这个房间
+    let _bob = join(&url, "bob").await;
+
+    let (mut alice, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut alice,
+        &GameMessage::ConnectRequest {
+            username: "alice".to_string(),
+            token: None,
+            play_vs_ai: Some(Difficulty::Easy),
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        },
+    )
+    .await;
+    let response = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    let GameMessage::ConnectResponse {
+        player_role: PlayerRole::Black,
+        game_id,
+        ..
+    } = response
+    else {
+        panic!("unexpected {:?}", response);
+    };
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::TurnNotification {
+                player: PlayerRole::Black
+            }
+        )
+    })
+    .await;
+
+    // 落子后电脑立即应对，又轮到黑方
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    wait_for(
+        &mut alice,
+        |msg| matches!(msg, GameMessage::MoveApplied { row, col, .. } if (*row, *col) != (7, 7)),
+    )
+    .await;
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::TurnNotification {
+                player: PlayerRole::Black
+            }
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resent_move_is_acknowledged_not_rejected() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::MoveAck { move_seq: 0, .. })
+    })
+    .await;
+    // 没收到确认的客户端重发同一步：再次确认，而不是报“不是你的回合”
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    let reply = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::MoveAck { .. } | GameMessage::Error(_))
+    })
+    .await;
+    assert!(matches!(reply, GameMessage::MoveAck { move_seq: 0, .. }));
+
+    // 步数或对局编号对不上的落子被拒绝
+    send(&mut bob, &play(&game_id, 5, 8, 8)).await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Error(_))).await;
+    send(&mut bob, &play("old-game", 1, 8, 8)).await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Error(_))).await;
+    send(&mut bob, &play(&game_id, 1, 8, 8)).await;
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::MoveApplied {
+                row: 8,
+                col: 8,
+                by: PlayerRole::White,
+                move_number: 2,
+                ..
+            }
+        )
+    })
+    .await;
+
+    // 对不上时请求整盘局面
+    send(&mut bob, &GameMessage::Resync).await;
+    let GameMessage::Status {
+        board,
+        current_player,
+        move_number,
+        last_move,
+        moves,
+    } = wait_for(&mut bob, |msg| matches!(msg, GameMessage::Status { .. })).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(board[7][7], Some(PlayerRole::Black));
+    assert_eq!(board[8][8], Some(PlayerRole::White));
+    assert_eq!(current_player, PlayerRole::Black);
+    // 整盘局面带着步数、最后一手和全部落子
+    assert_eq!(move_number, 2);
+    assert_eq!(last_move, Some((8, 8)));
+    let placed: Vec<_> = moves.iter().map(|m| (m.player, m.row, m.col)).collect();
+    assert_eq!(
+        placed,
+        [(PlayerRole::Black, 7, 7), (PlayerRole::White, 8, 8)]
+    );
+}
+
+#[tokio::test]
+async fn test_spectator_follows_and_switches_live_games() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, first) = join(&url, "alice").await;
+    let _bob = join(&url, "bob").await;
+    let (mut carol, second) = join(&url, "carol").await;
+    let _dave = join(&url, "dave").await;
+
+    let (mut viewer, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut viewer,
+        &GameMessage::ListGames {
+            filter: GameFilter::default(),
+        },
+    )
+    .await;
+    let GameMessage::GameList { page } = wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::GameList { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    assert_eq!(page.total, 2);
+    // 列表带着双方当前的等级分，游客没有等级分
+    assert!(page
+        .games
+        .iter()
+        .all(|game| game.black_rating.is_none() && game.white_rating.is_none()));
+
+    // 观战第一盘，看到对局信息和之后的落子
+    send(
+        &mut viewer,
+        &GameMessage::Watch {
+            game_id: first.clone(),
+        },
+    )
+    .await;
+    wait_for(
+        &mut viewer,
+        |msg| matches!(msg, GameMessage::Watching { game } if game.black == "alice"),
+    )
+    .await;
+    send(&mut alice, &play(&first, 0, 7, 7)).await;
+    wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::MoveApplied { row: 7, col: 7, .. })
+    })
+    .await;
+    // 观战者在每步之后还会收到局面评估
+    wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::Evaluation { move_seq: 0, .. })
+    })
+    .await;
+
+    // 切换到第二盘后只收到第二盘的落子
+    send(
+        &mut viewer,
+        &GameMessage::Watch {
+            game_id: second.clone(),
+        },
+    )
+    .await;
+    wait_for(
+        &mut viewer,
+        |msg| matches!(msg, GameMessage::Watching { game } if game.black == "carol"),
+    )
+    .await;
+    send(&mut carol, &play(&second, 0, 3, 4)).await;
+    let msg = wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::MoveApplied { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::MoveApplied { row: 3, col: 4, .. }
+    ));
+
+    send(
+        &mut viewer,
+        &GameMessage::Watch {
+            game_id: "no-such-game".to_string(),
+        },
+    )
+    .await;
+    wait_for(&mut viewer, |msg| matches!(msg, GameMessage::Error(_))).await;
+}
+
+#[tokio::test]
+async fn test_spectators_share_broadcasts_and_keep_their_region() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+
+    let mut viewers = Vec::new();
+    for _ in 0..5 {
+        let (mut viewer, _) = connect_async(&url).await.unwrap();
+        let watch = GameMessage::Watch {
+            game_id: game_id.clone(),
+        };
+        send(&mut viewer, &watch).await;
+        wait_for(&mut viewer, |msg| matches!(msg, GameMessage::Status { .. })).await;
+        viewers.push(viewer);
+    }
+
+    // 第一位观战者只看左上角，订阅之前的落子也算在窗口外的概况里
+    let region = Region {
+        top: 0,
+        left: 0,
+        rows: 5,
+        cols: 5,
+    };
+    let subscribe = GameMessage::SubscribeRegion {
+        region: Some(region),
+    };
+    send(&mut viewers[0], &subscribe).await;
+    let msg = wait_for(&mut viewers[0], |msg| {
+        matches!(msg, GameMessage::RegionUpdate { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::RegionUpdate { outside, .. } if outside.black_stones == 1
+    ));
+
+    send(&mut bob, &play(&game_id, 1, 8, 8)).await;
+    let msg = wait_for(&mut viewers[0], |msg| {
+        matches!(
+            msg,
+            GameMessage::RegionSummary { .. } | GameMessage::MoveApplied { .. }
+        )
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::RegionSummary { outside, .. } if outside.white_stones == 1
+    ));
+    for viewer in &mut viewers[1..] {
+        wait_for(viewer, |msg| {
+            matches!(msg, GameMessage::MoveApplied { row: 8, col: 8, .. })
+        })
+        .await;
+    }
+
+    // 双方离开后对局重置，观战者拿回连接，回到列表
+    for player in [&mut alice, &mut bob] {
+        send(player, &GameMessage::Goodbye).await;
+        player.close(None).await.unwrap();
+    }
+    let viewer = &mut viewers[1];
+    wait_for(viewer, |msg| matches!(msg, GameMessage::GameOver { .. })).await;
+    let watch = GameMessage::Watch { game_id };
+    send(viewer, &watch).await;
+    wait_for(viewer, |msg| matches!(msg, GameMessage::Error(_))).await;
+}
+
+// 用邀请令牌连接，返回收到的第一条入座或错误消息
+async fn connect_with_invite(url: &str, username: &str, invite: &str) -> GameMessage {
+    let (mut client, _) = connect_async(url).await.unwrap();
+    send(
+        &mut client,
+        &GameMessage::ConnectRequest {
+            username: username.to_string(),
+            token: None,
+            play_vs_ai: None,
+            invite: Some(invite.to_string()),
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        },
+    )
+    .await;
+    wait_for(&mut client, |msg| {
+        matches!(
+            msg,
+            GameMessage::ConnectResponse { .. } | GameMessage::Error(_)
+        )
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_invite_seats_guest_in_the_inviters_game() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+
+    send(
+        &mut alice,
+        &GameMessage::CreateInvite {
+            role: InviteRole::Seat(PlayerRole::White),
+            ttl_secs: Some(60),
+        },
+    )
+    .await;
+    let GameMessage::InviteCreated { token, role, .. } = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::InviteCreated { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    assert_eq!(role, InviteRole::Seat(PlayerRole::White));
+
+    let msg = connect_with_invite(&url, "bob", &token).await;
+    let GameMessage::ConnectResponse {
+        player_role,
+        game_id: seated_in,
+        ..
+    } = msg
+    else {
+        panic!("邀请入座失败: {:?}", msg)
+    };
+    assert_eq!(player_role, PlayerRole::White);
+    assert_eq!(seated_in, game_id);
+
+    // 座位已被占用，同一个邀请不能再用
+    let msg = connect_with_invite(&url, "dave", &token).await;
+    assert!(matches!(msg, GameMessage::Error(_)));
+    let msg = connect_with_invite(&url, "erin", "inv.forged.token").await;
+    assert!(matches!(msg, GameMessage::Error(_)));
+}
+
+#[tokio::test]
+async fn test_oversized_and_deeply_nested_messages_are_rejected() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut client, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut client,
+        &GameMessage::ConnectRequest {
+            username: "x".repeat(100),
+            token: None,
+            play_vs_ai: None,
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        },
+    )
+    .await;
+    let msg = wait_for(&mut client, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(matches!(msg, GameMessage::Error(e) if e.message.contains("用户名")));
+
+    let nested = format!("{}{}", "[".repeat(64), "]".repeat(64));
+    client.send(Message::Text(nested)).await.unwrap();
+    let msg = wait_for(&mut client, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(matches!(msg, GameMessage::Error(e) if e.message.contains("嵌套")));
+
+    // 连接仍然可用
+    let (_client, _) = join(&url, "alice").await;
+}
+
+#[tokio::test]
+async fn test_hints_are_limited_to_own_turn_and_per_game_quota() {
+    let url = start_server(ServerConfig {
+        hints_per_game: 1,
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut alice, _) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+
+    // 还没轮到白棋
+    send(&mut bob, &GameMessage::HintRequest).await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    send(&mut alice, &GameMessage::HintRequest).await;
+    let GameMessage::Hint {
+        row,
+        col,
+        hints_left,
+        ..
+    } = wait_for(&mut alice, |msg| matches!(msg, GameMessage::Hint { .. })).await
+    else {
+        unreachable!()
+    };
+    assert!(row < 15 && col < 15);
+    assert_eq!(hints_left, 0);
+
+    send(&mut alice, &GameMessage::HintRequest).await;
+    let msg = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::Error(_) | GameMessage::Hint { .. })
+    })
+    .await;
+    assert!(matches!(msg, GameMessage::Error(_)));
+}
+
+#[tokio::test]
+async fn test_server_info_reports_rules_and_limits_before_and_during_a_game() {
+    let mut config = ServerConfig {
+        time_control: Some(TimeControl {
+            main_time_secs: 300,
+            increment_secs: 5,
+        }),
+        ..ServerConfig::default()
+    };
+    config
+        .features
+        .set(GameType::Gomoku, Feature::Replay, false);
+    let url = start_server(config).await;
+
+    let (mut client, _) = connect_async(&url).await.unwrap();
+    send(&mut client, &GameMessage::ServerInfoRequest).await;
+    let GameMessage::ServerInfo(info) =
+        wait_for(&mut client, |msg| matches!(msg, GameMessage::ServerInfo(_))).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.time_control.unwrap().increment_secs, 5);
+    let gomoku = &info.variants[0];
+    assert_eq!(gomoku.game_type, GameType::Gomoku);
+    assert_eq!((gomoku.board_size, gomoku.win_length), (15, 5));
+    assert!(!gomoku.features.contains(&Feature::Replay));
+    assert!(gomoku.features.contains(&Feature::Analysis));
+
+    // 同一个连接查询之后还能正常入座
+    send(
+        &mut client,
+        &GameMessage::ConnectRequest {
+            username: "alice".to_string(),
+            token: None,
+            play_vs_ai: None,
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        },
+    )
+    .await;
+    wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    send(&mut client, &GameMessage::ServerInfoRequest).await;
+    let msg = wait_for(&mut client, |msg| matches!(msg, GameMessage::ServerInfo(_))).await;
+    assert!(matches!(msg, GameMessage::ServerInfo(again) if again == info));
+}
+
+#[tokio::test]
+async fn test_connect_negotiates_protocol_version_and_capabilities() {
+    let url = start_server(ServerConfig::default()).await;
+
+    let (mut client, _) = connect_async(&url).await.unwrap();
+    let too_old = r#"{"ConnectRequest":{"username":"ancient","protocol_version":0}}"#;
+    client
+        .send(Message::Text(too_old.to_string()))
+        .await
+        .unwrap();
+    let msg = wait_for(&mut client, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(matches!(msg, GameMessage::Error(e) if e.message.contains("协议版本")));
+
+    // 更新的客户端声明了服务器不认识的能力，只协商出双方都支持的
+    let newer = format!(
+        r#"{{"ConnectRequest":{{"username":"alice","protocol_version":{},"capabilities":["move_deltas","swap2"]}}}}"#,
+        PROTOCOL_VERSION + 1
+    );
+    client.send(Message::Text(newer)).await.unwrap();
+    let GameMessage::ConnectResponse {
+        game_id,
+        protocol_version,
+        capabilities,
+        ..
+    } = wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    assert_eq!(protocol_version, PROTOCOL_VERSION);
+    assert_eq!(capabilities, vec![Capability::MoveDeltas]);
+
+    // 不带版本号和能力的旧客户端收不到 MoveApplied，每步之后拿到整盘局面
+    let (mut legacy, _) = connect_async(&url).await.unwrap();
+    let old = r#"{"ConnectRequest":{"username":"bob"}}"#;
+    legacy.send(Message::Text(old.to_string())).await.unwrap();
+    wait_for(&mut legacy, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { capabilities, .. } if capabilities.is_empty())
+    })
+    .await;
+    send(&mut client, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::MoveApplied { move_number: 1, .. })
+    })
+    .await;
+    let msg = wait_for(&mut legacy, |msg| {
+        assert!(!matches!(msg, GameMessage::MoveApplied { .. }));
+        matches!(msg, GameMessage::Status { board, .. } if board[7][7].is_some())
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::Status {
+            board,
+            current_player: PlayerRole::White,
+            move_number: 1,
+            last_move: Some((7, 7)),
+            ..
+        } if board[7][7] == Some(PlayerRole::Black)
+    ));
+}
+
+// 读到下一条文本帧的原始 JSON
+async fn next_text(client: &mut Client) -> serde_json::Value {
+    let read = async {
+        while let Some(Ok(frame)) = client.next().await {
+            if let Message::Text(text) = frame {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+        panic!("连接已关闭");
+    };
+    tokio::time::timeout(Duration::from_secs(10), read)
+        .await
+        .expect("等待消息超时")
+}
+
+#[tokio::test]
+async fn test_errors_carry_codes_only_for_clients_that_declare_them() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, _) = join(&url, "alice").await;
+    wait_for(&mut alice, |msg| matches!(msg, GameMessage::Status { .. })).await;
+
+    let (mut legacy, _) = connect_async(&url).await.unwrap();
+    let old = r#"{"ConnectRequest":{"username":"bob"}}"#;
+    legacy.send(Message::Text(old.to_string())).await.unwrap();
+    wait_for(&mut legacy, |msg| matches!(msg, GameMessage::Status { .. })).await;
+
+    let lookup = GameMessage::GetStats {
+        user_id: "nobody".to_string(),
+    };
+    send(&mut alice, &lookup).await;
+    let msg = wait_for(&mut alice, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(matches!(
+        msg,
+        GameMessage::Error(ErrorReply { code: Some(ErrorCode::NotFound), message })
+            if message.contains("nobody")
+    ));
+
+    // 旧客户端只认识字符串形式的 Error
+    send(&mut legacy, &lookup).await;
+    let reply = loop {
+        let json = next_text(&mut legacy).await;
+        if json.get("Error").is_some() {
+            break json;
+        }
+    };
+    assert_eq!(reply["Error"], "找不到用户 nobody");
+
+    // 握手阶段还不知道客户端的能力，同样只发提示文字
+    let (mut stranger, _) = connect_async(&url).await.unwrap();
+    stranger
+        .send(Message::Text("not json".to_string()))
+        .await
+        .unwrap();
+    let reply = next_text(&mut stranger).await;
+    assert!(reply["Error"].as_str().unwrap().contains("解析消息失败"));
+}
+
+// 同时开启管理通道，返回对局地址、管理通道地址和管理员令牌
+async fn start_server_with_admin() -> (String, String, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let admin_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let admin_addr = admin_listener.local_addr().unwrap();
+    let mut users = UserManager::new();
+    users.set_admins(vec!["root".to_string()]);
+    let token = users.register_admin("root", "secret-password").unwrap();
+    let users = Arc::new(RwLock::new(users));
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        ServerConfig::default(),
+        archive.clone(),
+        users.clone(),
+    )));
+    tokio::spawn(admin::serve(admin_listener, rooms.clone(), users.clone()));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let player = NetworkPlayer::new(stream, rooms.clone(), users.clone(), archive.clone());
+            tokio::spawn(player.play());
+        }
+    });
+    (
+        format!("ws://{}", addr),
+        format!("ws://{}", admin_addr),
+        token,
+    )
+}
+
+async fn admin_command(admin: &mut Client, command: &AdminCommand) -> AdminReply {
+    let json = serde_json::to_string(command).unwrap();
+    admin.send(Message::Text(json)).await.unwrap();
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(10), admin.next())
+            .await
+            .expect("等待管理回复超时")
+            .expect("管理通道已关闭")
+            .unwrap();
+        if let Message::Text(text) = frame {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_admin_channel_lists_broadcasts_kicks_and_drains() {
+    let (url, admin_url, token) = start_server_with_admin().await;
+
+    // 没有认证或者不是管理员令牌时拒绝并断开
+    let forged = AdminCommand::Auth {
+        token: "forged".to_string(),
+    };
+    for command in [AdminCommand::ListGames, forged] {
+        let (mut intruder, _) = connect_async(&admin_url).await.unwrap();
+        let reply = admin_command(&mut intruder, &command).await;
+        assert!(matches!(reply, AdminReply::Error(_)));
+    }
+    let (mut admin, _) = connect_async(&admin_url).await.unwrap();
+    let reply = admin_command(&mut admin, &AdminCommand::Auth { token }).await;
+    assert_eq!(reply, AdminReply::Authenticated);
+
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    let AdminReply::Games { rooms } = admin_command(&mut admin, &AdminCommand::ListGames).await
+    else {
+        panic!("应该返回房间列表");
+    };
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].game_id, game_id);
+    assert_eq!(rooms[0].black.as_deref(), Some("alice"));
+    assert_eq!(rooms[0].white.as_deref(), Some("bob"));
+
+    let notice = AdminCommand::Broadcast {
+        message: "十分钟后维护".to_string(),
+    };
+    let reply = admin_command(&mut admin, &notice).await;
+    assert_eq!(reply, AdminReply::Broadcasted { recipients: 2 });
+    let msg = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::ServerNotice { .. })
+    })
+    .await;
+    assert!(matches!(msg, GameMessage::ServerNotice { message } if message == "十分钟后维护"));
+
+    // 被踢的一方判负并断开
+    let kick = AdminCommand::Kick {
+        username: "bob".to_string(),
+        reason: None,
+    };
+    let reply = admin_command(&mut admin, &kick).await;
+    assert_eq!(
+        reply,
+        AdminReply::Kicked {
+            username: "bob".to_string(),
+            room: 0
+        }
+    );
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Kicked { .. })).await;
+    let msg = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GameOver { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::GameOver {
+            winner: Some(PlayerRole::Black),
+            ..
+        }
+    ));
+
+    // 排空后不再开新局
+    let reply = admin_command(&mut admin, &AdminCommand::Drain).await;
+    assert_eq!(reply, AdminReply::Draining { active_games: 0 });
+    let (mut carol, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut carol,
+        &GameMessage::ConnectRequest {
+            username: "carol".to_string(),
+            token: None,
+            play_vs_ai: None,
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        },
+    )
+    .await;
+    wait_for(&mut carol, |msg| matches!(msg, GameMessage::Error(_))).await;
+}
+
+#[tokio::test]
+async fn test_strict_mode_disconnects_on_protocol_violations() {
+    let url = start_server(ServerConfig {
+        strict: StrictMode {
+            enabled: true,
+            max_out_of_turn: 1,
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+
+    // 连接前发来解析不了的消息，直接断开
+    let (mut client, _) = connect_async(&url).await.unwrap();
+    client
+        .send(Message::Text(r#"{"NoSuchMessage":{}}"#.to_string()))
+        .await
+        .unwrap();
+    let violation = wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::ProtocolViolation { .. })
+    })
+    .await;
+    assert!(matches!(
+        violation,
+        GameMessage::ProtocolViolation {
+            violation: Violation::Malformed,
+            ..
+        }
+    ));
+    assert!(closed(&mut client).await);
+
+    // 不是自己的回合落子，第一次照常回错误，超过上限才断开
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::TurnNotification { .. })
+    })
+    .await;
+    send(&mut bob, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Error(_))).await;
+    send(&mut bob, &play(&game_id, 0, 7, 8)).await;
+    let violation = wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::ProtocolViolation { .. })
+    })
+    .await;
+    assert!(matches!(
+        violation,
+        GameMessage::ProtocolViolation {
+            violation: Violation::OutOfTurn,
+            ..
+        }
+    ));
+    assert!(closed(&mut bob).await);
+    // 和掉线一样暂停对局，等宽限期
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GamePaused { .. })
+    })
+    .await;
+}
+
+// 服务器关闭了连接：读到 Close 帧或者流结束
+async fn closed(client: &mut Client) -> bool {
+    let read = async {
+        loop {
+            match client.next().await {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return true,
+                Some(Ok(_)) => {}
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), read)
+        .await
+        .unwrap_or(false)
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_flushes_connections_and_adjourns_unfinished_games() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        ServerConfig::default(),
+        archive.clone(),
+        users.clone(),
+    )));
+    let accept_rooms = rooms.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let player =
+                NetworkPlayer::new(stream, accept_rooms.clone(), users.clone(), archive.clone());
+            tokio::spawn(player.play());
+        }
+    });
+
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::MoveApplied { .. })
+    })
+    .await;
+
+    let store = shared(MemoryStore::new());
+    let report = graceful_shutdown(&rooms, &store, Duration::from_secs(5)).await;
+    assert!(report.flushed);
+    assert_eq!(report.adjourned, 1);
+
+    // 双方都收到停服通知，随后连接被关闭
+    for client in [&mut alice, &mut bob] {
+        wait_for(client, |msg| matches!(msg, GameMessage::ServerShutdown)).await;
+        let rest = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(Ok(frame)) = client.next().await {
+                if frame.is_close() {
+                    break;
+                }
+            }
+        });
+        rest.await.expect("停服后连接应该关闭");
+    }
+
+    let adjourned = store.lock().unwrap().load_adjourned().unwrap();
+    assert_eq!(adjourned.len(), 1);
+    assert_eq!(adjourned[0].game_id, game_id);
+    assert_eq!(adjourned[0].moves.len(), 1);
+    assert_eq!(adjourned[0].seats.len(), 2);
+    assert!(!adjourned[0].finished);
+}
+
+// 注册或登录，返回连接、令牌和服务器列出的未完成对局
+async fn sign_in(url: &str, msg: GameMessage) -> (Client, String, Vec<UnfinishedGame>) {
+    let (mut client, _) = connect_async(url).await.unwrap();
+    send(&mut client, &msg).await;
+    let GameMessage::AuthToken { token, unfinished } = wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::AuthToken { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    (client, token, unfinished)
+}
+
+// 注册讲解人，在同一个连接上开演示房间，返回讲解人的连接和房间编号
+async fn open_demo(url: &str, presenter: &str, title: &str) -> (Client, String) {
+    let register = GameMessage::Register {
+        username: presenter.to_string(),
+        password: "secret-password".to_string(),
+    };
+    let (mut client, token, _) = sign_in(url, register).await;
+    let title = title.to_string();
+    send(&mut client, &GameMessage::CreateDemo { title, token }).await;
+    let GameMessage::DemoCreated { demo_id } = wait_for(&mut client, |msg| {
+        matches!(msg, GameMessage::DemoCreated { .. })
+    })
+    .await
+    else {
+        unreachable!()
+    };
+    (client, demo_id)
+}
+
+#[tokio::test]
+async fn test_login_lists_adjourned_games_and_players_resume_them() {
+    use PlayerRole::*;
+    // 停服前存下的一盘：双方都是登录用户，黑白各下过，轮到白方
+    let archive = Arc::new(Mutex::new(GameArchive::new()));
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    let mut game = Game::with_config(&ServerConfig::default(), archive.clone(), users.clone());
+    let (tx, _rx) = tokio::sync::mpsc::channel(64);
+    game.add_player(Black, "alice".to_string(), tx.clone())
+        .await
+        .unwrap();
+    game.add_player(White, "bob".to_string(), tx).await.unwrap();
+    game.set_authenticated(Black, true);
+    game.set_authenticated(White, true);
+    for (player, row, col) in [(Black, 7, 7), (White, 7, 8), (Black, 8, 8)] {
+        game.make_move(player, row, col).await.unwrap();
+    }
+    let record = game.dump(0);
+    let game_id = record.game_id.clone();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let rooms = Arc::new(Mutex::new(RoomManager::new(
+        ServerConfig::default(),
+        archive.clone(),
+        users.clone(),
+    )));
+    rooms.lock().await.load_adjourned(vec![record]);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let player = NetworkPlayer::new(stream, rooms.clone(), users.clone(), archive.clone());
+            tokio::spawn(player.play());
+        }
+    });
+
+    let credentials = |username: &str| (username.to_string(), "secret-password".to_string());
+    for name in ["alice", "bob"] {
+        let (username, password) = credentials(name);
+        let (_, _, unfinished) = sign_in(&url, GameMessage::Register { username, password }).await;
+        assert!(unfinished.is_empty());
+    }
+
+    // 登录时列出这盘对局：对手、执哪一方、轮到谁
+    let (username, password) = credentials("alice");
+    let (mut alice, token, unfinished) =
+        sign_in(&url, GameMessage::Login { username, password }).await;
+    assert_eq!(unfinished.len(), 1);
+    assert_eq!(unfinished[0].game_id, game_id);
+    assert_eq!(unfinished[0].opponent, "bob");
+    assert_eq!(unfinished[0].color, Black);
+    assert_eq!(unfinished[0].to_move, White);
+    assert_eq!(unfinished[0].move_number, 3);
+    assert!(unfinished[0].adjourned);
+
+    let resume = |username: &str, token: String| GameMessage::ConnectRequest {
+        username: username.to_string(),
+        token: Some(token),
+        play_vs_ai: None,
+        invite: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: None,
+        resume: Some(game_id.clone()),
+    };
+    send(&mut alice, &resume("alice", token)).await;
+    let response = wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    assert!(matches!(
+        &response,
+        GameMessage::ConnectResponse { player_role: Black, game_id: id, .. } if *id == game_id
+    ));
+
+    // 留给 bob 的座位不分给别人
+    let (_carol, carol_game) = join(&url, "carol").await;
+    assert_ne!(carol_game, game_id);
+
+    let (username, password) = credentials("bob");
+    let (mut bob, token, unfinished) =
+        sign_in(&url, GameMessage::Login { username, password }).await;
+    assert_eq!(unfinished.len(), 1);
+    assert_eq!(unfinished[0].color, White);
+    send(&mut bob, &resume("bob", token)).await;
+    let response = wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::ConnectResponse { .. })
+    })
+    .await;
+    assert!(matches!(
+        response,
+        GameMessage::ConnectResponse {
+            player_role: White,
+            ..
+        }
+    ));
+
+    // 从记录里的第四手接着下
+    send(&mut bob, &play(&game_id, 3, 9, 9)).await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::MoveApplied { row: 9, col: 9, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_spectator_takes_over_a_seat_left_mid_game() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::TurnNotification { .. })
+    })
+    .await;
+
+    // 只有房主（先入座的 alice）能开启替补
+    send(&mut bob, &GameMessage::AllowSubstitutes { allowed: true }).await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::Error(_))).await;
+    send(&mut alice, &GameMessage::AllowSubstitutes { allowed: true }).await;
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::MoveApplied { move_number: 1, .. })
+    })
+    .await;
+
+    let (mut carol, _) = connect_async(&url).await.unwrap();
+    let watch = GameMessage::Watch {
+        game_id: game_id.clone(),
+    };
+    send(&mut carol, &watch).await;
+    wait_for(&mut carol, |msg| {
+        matches!(msg, GameMessage::Watching { .. })
+    })
+    .await;
+    // 座位还有人时不能申请
+    let claim = GameMessage::ClaimSeat {
+        role: PlayerRole::White,
+    };
+    send(&mut carol, &claim).await;
+    wait_for(&mut carol, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    // bob 中途离开：不判负，座位空出来
+    send(&mut bob, &GameMessage::Goodbye).await;
+    let seat_open = |msg: &GameMessage| {
+        matches!(
+            msg,
+            GameMessage::SeatOpen {
+                role: PlayerRole::White,
+                ..
+            }
+        )
+    };
+    wait_for(&mut alice, seat_open).await;
+    wait_for(&mut carol, seat_open).await;
+
+    // 接替的人在同一个连接上入座，拿到原来的局面，轮到白棋，计时和对局继续
+    send(&mut carol, &claim).await;
+    wait_for(&mut carol, |msg| {
+        matches!(msg, GameMessage::SeatClaimed { role: PlayerRole::White, game_id: id } if *id == game_id)
+    })
+    .await;
+    wait_for(&mut carol, |msg| {
+        matches!(
+            msg,
+            GameMessage::ConnectResponse {
+                player_role: PlayerRole::White,
+                ..
+            }
+        )
+    })
+    .await;
+    wait_for(&mut carol, |msg| {
+        matches!(msg, GameMessage::Status { board, .. } if board[7][7] == Some(PlayerRole::Black))
+    })
+    .await;
+    wait_for(&mut carol, |msg| {
+        matches!(
+            msg,
+            GameMessage::TurnNotification {
+                player: PlayerRole::White
+            }
+        )
+    })
+    .await;
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::PlayerConnected {
+                player: PlayerRole::White,
+                ..
+            }
+        )
+    })
+    .await;
+    send(&mut carol, &play(&game_id, 1, 8, 8)).await;
+    wait_for(&mut alice, |msg| {
+        matches!(
+            msg,
+            GameMessage::MoveApplied {
+                move_number: 2,
+                by: PlayerRole::White,
+                ..
+            }
+        )
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_players_get_their_preferred_color_when_it_is_free() {
+    let url = start_server(ServerConfig::default()).await;
+
+    let (_alice, alice_game, alice_role) =
+        join_preferring(&url, "alice", Some(PlayerRole::White)).await;
+    assert_eq!(alice_role, PlayerRole::White);
+    // 白方已经有人，同样想执白的 bob 分到黑方
+    let (_bob, bob_game, bob_role) = join_preferring(&url, "bob", Some(PlayerRole::White)).await;
+    assert_eq!(bob_game, alice_game);
+    assert_eq!(bob_role, PlayerRole::Black);
+}
+
+#[tokio::test]
+async fn test_demo_room_broadcasts_presenter_edits_and_variations() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut presenter, demo_id) = open_demo(&url, "teacher", "活三的攻防").await;
+
+    let (mut viewer, _) = connect_async(&url).await.unwrap();
+    send(
+        &mut viewer,
+        &GameMessage::Watch {
+            game_id: demo_id.clone(),
+        },
+    )
+    .await;
+    wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::DemoPosition { .. })
+    })
+    .await;
+
+    // 摆子不分先后，也可以拿掉
+    let edit = |action| GameMessage::DemoEdit { action };
+    for action in [
+        DemoAction::Place {
+            row: 7,
+            col: 7,
+            color: PlayerRole::White,
+        },
+        DemoAction::Place {
+            row: 7,
+            col: 8,
+            color: PlayerRole::White,
+        },
+        DemoAction::Remove { row: 7, col: 8 },
+    ] {
+        send(&mut presenter, &edit(action)).await;
+    }
+    let stones = |msg: &GameMessage| match msg {
+        GameMessage::Status { board, .. } => board.iter().flatten().flatten().count(),
+        _ => 0,
+    };
+    wait_for(&mut viewer, |msg| stones(msg) == 2).await;
+    let msg = wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::Status { .. }) && stones(msg) == 1
+    })
+    .await;
+    let GameMessage::Status { board, .. } = msg else {
+        unreachable!()
+    };
+    assert_eq!(board[7][7], Some(PlayerRole::White));
+
+    // 占用的位置不能再摆，错误只回给讲解人
+    send(
+        &mut presenter,
+        &edit(DemoAction::Place {
+            row: 7,
+            col: 7,
+            color: PlayerRole::Black,
+        }),
+    )
+    .await;
+    wait_for(&mut presenter, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    // 载入变化树后在两种下法之间切换，观众看到讲解和后续的下法
+    let node = |row, col, comment: &str, children| VariationNode {
+        player: PlayerRole::Black,
+        row,
+        col,
+        comment: comment.to_string(),
+        children,
+    };
+    let variations = vec![
+        node(6, 6, "挡住上方", vec![node(8, 8, "再挡下方", Vec::new())]),
+        node(8, 8, "挡住下方", Vec::new()),
+    ];
+    send(
+        &mut presenter,
+        &edit(DemoAction::LoadVariations { variations }),
+    )
+    .await;
+    let msg = wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::DemoPosition { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::DemoPosition { ref path, ref next, .. }
+            if path.is_empty() && next == &[(6, 6), (8, 8)]
+    ));
+    send(&mut presenter, &edit(DemoAction::Goto { path: vec![0, 0] })).await;
+    wait_for(&mut viewer, |msg| stones(msg) == 3).await;
+    let msg = wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::DemoPosition { .. })
+    })
+    .await;
+    assert!(matches!(
+        msg,
+        GameMessage::DemoPosition { ref comment, .. } if comment == "再挡下方"
+    ));
+    send(&mut presenter, &edit(DemoAction::Goto { path: vec![2] })).await;
+    wait_for(&mut presenter, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    // 讲解人离开，房间关闭
+    presenter.close(None).await.unwrap();
+    wait_for(
+        &mut viewer,
+        |msg| matches!(msg, GameMessage::DemoClosed { demo_id: id } if *id == demo_id),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_demo_rooms_require_login_and_are_capped_per_user_and_server() {
+    let url = start_server(ServerConfig {
+        max_demos: 2,
+        ..ServerConfig::default()
+    })
+    .await;
+    let refused = |msg: &GameMessage, text: &str| match msg {
+        GameMessage::Error(e) => e.message.contains(text),
+        _ => false,
+    };
+
+    // 没有有效的令牌不能开
+    let (mut guest, _) = connect_async(&url).await.unwrap();
+    let forged = GameMessage::CreateDemo {
+        title: "游客".to_string(),
+        token: "forged".to_string(),
+    };
+    send(&mut guest, &forged).await;
+    let msg = wait_for(&mut guest, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(refused(&msg, "登录"));
+
+    // 同一个用户同时只能开一个
+    let (_alice, _) = open_demo(&url, "alice", "第一场").await;
+    let login = GameMessage::Login {
+        username: "alice".to_string(),
+        password: "secret-password".to_string(),
+    };
+    let (mut alice_again, token, _) = sign_in(&url, login).await;
+    let title = "第二场".to_string();
+    send(&mut alice_again, &GameMessage::CreateDemo { title, token }).await;
+    let msg = wait_for(&mut alice_again, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(refused(&msg, "最多"));
+
+    // 整个服务器的演示房间也有上限
+    let (_bob, _) = open_demo(&url, "bob", "第三场").await;
+    let register = GameMessage::Register {
+        username: "carol".to_string(),
+        password: "secret-password".to_string(),
+    };
+    let (mut carol, token, _) = sign_in(&url, register).await;
+    let title = "第四场".to_string();
+    send(&mut carol, &GameMessage::CreateDemo { title, token }).await;
+    let msg = wait_for(&mut carol, |msg| matches!(msg, GameMessage::Error(_))).await;
+    assert!(refused(&msg, "已满"));
+}
+
+#[tokio::test]
+async fn test_room_over_quota_sends_a_summary_and_the_current_position() {
+    let url = start_server(ServerConfig {
+        room_quotas: RoomQuotas {
+            demo: RoomQuota {
+                window_ms: 300,
+                max_events: 2,
+                max_bytes: 0,
+            },
+            ..RoomQuotas::default()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut presenter, demo_id) = open_demo(&url, "teacher", "刷屏").await;
+    let (mut viewer, _) = connect_async(&url).await.unwrap();
+    send(&mut viewer, &GameMessage::Watch { game_id: demo_id }).await;
+    wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::DemoPosition { .. })
+    })
+    .await;
+
+    // 一口气发一串讲解再摆一个子，超出的部分合并成摘要，局面随后补发
+    let edit = |action| GameMessage::DemoEdit { action };
+    for i in 0..6 {
+        let text = format!("第 {} 条", i);
+        send(&mut presenter, &edit(DemoAction::Note { text })).await;
+    }
+    let place = DemoAction::Place {
+        row: 7,
+        col: 7,
+        color: PlayerRole::Black,
+    };
+    send(&mut presenter, &edit(place)).await;
+    let notes = Cell::new(0);
+    let msg = wait_for(&mut viewer, |msg| {
+        if matches!(msg, GameMessage::Narration { .. }) {
+            notes.set(notes.get() + 1);
+        }
+        matches!(msg, GameMessage::EventsSummarized { .. })
+    })
+    .await;
+    assert_eq!(notes.get(), 2);
+    assert!(matches!(msg, GameMessage::EventsSummarized { skipped: 5 }));
+    let msg = wait_for(&mut viewer, |msg| matches!(msg, GameMessage::Status { .. })).await;
+    let GameMessage::Status { board, .. } = msg else {
+        unreachable!()
+    };
+    assert_eq!(board[7][7], Some(PlayerRole::Black));
+}
+
+#[tokio::test]
+async fn test_finished_players_find_new_opponents_on_the_same_connection() {
+    let url = start_server(ServerConfig::default()).await;
+    let connected = |msg: &GameMessage| matches!(msg, GameMessage::ConnectResponse { .. });
+
+    let (mut alice, first_game) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    // 对局还在进行时不能换对手
+    send(&mut alice, &GameMessage::FindOpponent).await;
+    wait_for(&mut alice, |msg| matches!(msg, GameMessage::Error(_))).await;
+
+    send(&mut alice, &GameMessage::Resign).await;
+    wait_for(&mut alice, |msg| {
+        matches!(msg, GameMessage::GameOver { .. })
+    })
+    .await;
+    wait_for(&mut bob, |msg| matches!(msg, GameMessage::GameOver { .. })).await;
+
+    // 不用重新连接，回到队列坐进新的一盘
+    send(&mut alice, &GameMessage::FindOpponent).await;
+    let GameMessage::ConnectResponse { game_id, .. } = wait_for(&mut alice, connected).await else {
+        unreachable!()
+    };
+    assert_ne!(game_id, first_game);
+    wait_for(&mut bob, |msg| {
+        matches!(
+            msg,
+            GameMessage::PlayerDisconnected {
+                player: PlayerRole::Black
+            }
+        )
+    })
+    .await;
+
+    send(&mut bob, &GameMessage::FindOpponent).await;
+    let GameMessage::ConnectResponse {
+        game_id: bobs_game,
+        player_role,
+        ..
+    } = wait_for(&mut bob, connected).await
+    else {
+        unreachable!()
+    };
+    assert_eq!(bobs_game, game_id);
+    assert_eq!(player_role, PlayerRole::White);
+
+    send(&mut alice, &play(&game_id, 0, 7, 7)).await;
+    wait_for(&mut bob, |msg| {
+        matches!(msg, GameMessage::MoveApplied { row: 7, col: 7, .. })
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_chat_reaches_both_players_and_spectators() {
+    let url = start_server(ServerConfig::default()).await;
+    let (mut alice, game_id) = join(&url, "alice").await;
+    let (mut bob, _) = join(&url, "bob").await;
+    let (mut viewer, _) = connect_async(&url).await.unwrap();
+    send(&mut viewer, &GameMessage::Watch { game_id }).await;
+    wait_for(&mut viewer, |msg| {
+        matches!(msg, GameMessage::Watching { .. })
+    })
+    .await;
+
+    let chat = GameMessage::SendChat {
+        text: "  好棋  ".to_string(),
+    };
+    send(&mut alice, &chat).await;
+    // 说话的人自己也收到，前后的空白去掉
+    for client in [&mut alice, &mut bob, &mut viewer] {
+        wait_for(client, |msg| {
+            matches!(msg, GameMessage::Chat { from, text } if from == "alice" && text == "好棋")
+        })
+        .await;
+    }
+
+    // 空话和太长的话只给说话的人回错误
+    for text in [" ".to_string(), "长".repeat(chess::MAX_CHAT_CHARS + 1)] {
+        send(&mut bob, &GameMessage::SendChat { text }).await;
+        let reply = wait_for(&mut bob, |msg| {
+            matches!(msg, GameMessage::Error(_) | GameMessage::Chat { .. })
+        })
+        .await;
+        assert!(matches!(reply, GameMessage::Error(_)));
+    }
+}
@@ -0,0 +1,122 @@
+mod conformance;
+
+use chess::{ChunkAssembler, GameMessage, MessageLimits, PlayerRole};
+use conformance::{assert_roundtrip, connect_request, samples, start_server, SCRIPT};
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[test]
+fn test_every_message_survives_server_codec() {
+    let limits = MessageLimits::default();
+    // 出站单帧压得很小，每条消息都要走一遍分段和重组
+    let tiny = MessageLimits {
+        max_outbound_frame_bytes: 160,
+        ..MessageLimits::default()
+    };
+    let mut assembler = ChunkAssembler::new();
+    for msg in samples() {
+        assert_roundtrip(&msg);
+        let json = serde_json::to_string(&msg).unwrap();
+
+        let decoded = limits.decode(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        // 分段本身就是传输层的帧，不再拆分
+        if matches!(msg, GameMessage::Chunk { .. }) {
+            continue;
+        }
+
+        let mut reassembled = None;
+        for frame in tiny.encode(&msg) {
+            reassembled = match serde_json::from_str(&frame).unwrap() {
+                GameMessage::Chunk {
+                    id,
+                    index,
+                    total,
+                    data,
+                } => assembler.push(id, index, total, data).map(Result::unwrap),
+                whole => Some(whole),
+            };
+        }
+        assert_eq!(serde_json::to_string(&reassembled.unwrap()).unwrap(), json);
+    }
+}
+
+async fn send(client: &mut Client, msg: &GameMessage) {
+    let json = serde_json::to_string(msg).unwrap();
+    client.send(Message::Text(json)).await.unwrap();
+}
+
+async fn wait_for(client: &mut Client, matches: impl Fn(&GameMessage) -> bool) -> GameMessage {
+    let read = async {
+        while let Some(Ok(frame)) = client.next().await {
+            if let Message::Text(text) = frame {
+                let msg: GameMessage = serde_json::from_str(&text).unwrap();
+                if matches(&msg) {
+                    return msg;
+                }
+            }
+        }
+        panic!("连接已关闭");
+    };
+    tokio::time::timeout(Duration::from_secs(10), read)
+        .await
+        .expect("等待消息超时")
+}
+
+#[tokio::test]
+async fn test_scripted_game_from_server_side() {
+    let url = start_server().await;
+    let mut players = Vec::new();
+    for name in ["alice", "bob"] {
+        let (mut client, _) = connect_async(&url).await.unwrap();
+        send(&mut client, &connect_request(name)).await;
+        let GameMessage::ConnectResponse { game_id, .. } = wait_for(&mut client, |msg| {
+            matches!(msg, GameMessage::ConnectResponse { .. })
+        })
+        .await
+        else {
+            unreachable!()
+        };
+        players.push((client, game_id));
+    }
+
+    for (seq, &(row, col)) in SCRIPT.iter().enumerate() {
+        let (mover, game_id) = &mut players[seq % 2];
+        let request = GameMessage::Move {
+            row,
+            col,
+            game_id: game_id.clone(),
+            move_seq: seq,
+            client_nonce: seq as u64 + 1,
+            second: None,
+        };
+        send(mover, &request).await;
+        // 双方都收到这一步的广播
+        for (client, _) in players.iter_mut() {
+            wait_for(client, |msg| {
+                matches!(msg, GameMessage::MoveApplied { row: r, col: c, .. } if (*r, *c) == (row, col))
+            })
+            .await;
+        }
+    }
+
+    for (client, _) in players.iter_mut() {
+        let msg = wait_for(client, |msg| matches!(msg, GameMessage::GameOver { .. })).await;
+        let GameMessage::GameOver {
+            winner,
+            winning_line,
+            ..
+        } = msg
+        else {
+            unreachable!()
+        };
+        assert_eq!(winner, Some(PlayerRole::Black));
+        let black_moves: Vec<_> = SCRIPT.iter().copied().step_by(2).collect();
+        assert_eq!(winning_line, black_moves);
+    }
+}
@@ -0,0 +1,451 @@
+use chess::{
+    authenticate_user, register_user, shared, ArchivedGame, GameArchive, GameOverReason,
+    PlayerRole, RulesConfig, SqliteStore, UserManager, REPLAY_FORMAT_VERSION,
+};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[test]
+fn test_users_and_games_survive_restart() {
+    let store = shared(SqliteStore::open_in_memory().unwrap());
+
+    let mut users = UserManager::with_store(store.clone());
+    let alice = users.login("alice".to_string());
+    users
+        .assign_player(&alice.id, 0, PlayerRole::Black)
+        .unwrap();
+    users.logout(&alice.id);
+
+    let mut archive = GameArchive::with_store(store.clone());
+    let now = chrono::Utc::now();
+    archive.save(ArchivedGame {
+        id: "g1".to_string(),
+        black: "alice".to_string(),
+        white: "bob".to_string(),
+        winner: Some(PlayerRole::Black),
+        reason: None,
+        moves: Vec::new(),
+        started_at: now,
+        ended_at: now,
+        seed: None,
+        rules: RulesConfig::default(),
+        position_hash: None,
+    });
+
+    // 模拟重启：从同一个后端重新加载
+    let mut users = UserManager::with_store(store.clone());
+    let again = users.login("alice".to_string());
+    assert_eq!(again.id, alice.id);
+    assert_ne!(again.session_id, alice.session_id);
+    assert!(users.get_user_by_session(&alice.session_id).is_none());
+    assert!(users.get_user_by_session(&again.session_id).is_some());
+
+    let archive = GameArchive::with_store(store.clone());
+    assert_eq!(archive.get("g1").unwrap().winner, Some(PlayerRole::Black));
+    // 旧会话已删除，只剩新的一个
+    assert_eq!(store.lock().unwrap().load_sessions().unwrap().len(), 1);
+}
+
+#[test]
+fn test_elo_updates_are_zero_sum_for_equal_players() {
+    use chess::{rate_game, Rating};
+    let (black, white) = rate_game(
+        &Rating::default(),
+        &Rating::default(),
+        Some(PlayerRole::White),
+    );
+    assert_eq!(black.rating, 1480.0);
+    assert_eq!(white.rating, 1520.0);
+
+    let (black, white) = rate_game(&black, &white, None);
+    // 平局时分低的一方加分
+    assert!(black.rating > 1480.0 && white.rating < 1520.0);
+    assert!((black.rating + white.rating - 3000.0).abs() < 1e-9);
+    assert_eq!((black.games, white.games), (2, 2));
+}
+
+#[test]
+fn test_encrypted_store_hides_names_and_needs_the_key() {
+    use chess::{Cipher, Store};
+    let path = std::env::temp_dir().join(format!("gomoku-{}.db", uuid::Uuid::new_v4()));
+    let key = Cipher::generate_key();
+
+    {
+        let store = SqliteStore::open(&path)
+            .unwrap()
+            .with_cipher(Cipher::from_base64(&key).unwrap());
+        let mut users = UserManager::with_store(shared(store));
+        users.login("alice".to_string());
+    }
+
+    // 磁盘上看不到明文用户名
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!raw.windows(5).any(|w| w == b"alice"));
+
+    let mut store = SqliteStore::open(&path)
+        .unwrap()
+        .with_cipher(Cipher::from_base64(&key).unwrap());
+    let alice = store.load_users().unwrap().remove(0);
+    assert_eq!(alice.name, "alice");
+    // 用户名的密文每次不同，唯一约束和查找靠带密钥的 HMAC
+    assert_eq!(
+        store.user_id_by_name("alice").unwrap(),
+        Some(alice.id.clone())
+    );
+    assert_eq!(store.user_id_by_name("bob").unwrap(), None);
+    store.save_user(&alice).unwrap();
+    let mut impostor = alice.clone();
+    impostor.id = "another-id".to_string();
+    assert!(store.save_user(&impostor).is_err());
+    assert_eq!(store.load_users().unwrap().len(), 1);
+
+    let wrong = SqliteStore::open(&path)
+        .unwrap()
+        .with_cipher(Cipher::from_base64(&Cipher::generate_key()).unwrap());
+    assert!(wrong.load_users().is_err());
+
+    assert!(Cipher::from_base64("c2hvcnQ=").is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_old_database_with_duplicate_names_still_loads() {
+    use chess::Store;
+    let path = std::env::temp_dir().join(format!("gomoku-old-{}.db", uuid::Uuid::new_v4()));
+    // 加 name_key 之前的库里可能已经有同名用户
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE users (id TEXT PRIMARY KEY, name TEXT NOT NULL, session_id TEXT NOT NULL);
+         INSERT INTO users VALUES ('u1', 'alice', 's1'), ('u2', 'alice', 's2'), ('u3', 'bob', 's3');",
+    )
+    .unwrap();
+    drop(conn);
+
+    let store = SqliteStore::open(&path).unwrap();
+    assert_eq!(store.load_users().unwrap().len(), 3);
+    assert!(store.user_id_by_name("alice").unwrap().is_some());
+    assert_eq!(
+        store.user_id_by_name("bob").unwrap(),
+        Some("u3".to_string())
+    );
+    drop(store);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_backup_round_trip_and_integrity_check() {
+    use chess::{Backup, MemoryStore, Store};
+    let store = shared(SqliteStore::open_in_memory().unwrap());
+    let mut users = UserManager::with_store(store.clone());
+    users.login("alice".to_string());
+    users.login("bob".to_string());
+    users.record_result("alice", "bob", Some(PlayerRole::Black));
+    // 停服时封存的对局也要一起备份
+    let adjourned = chess::Game::new().dump(0);
+    store.lock().unwrap().save_adjourned(&adjourned).unwrap();
+
+    let backup = Backup::create(&*store.lock().unwrap()).unwrap();
+    let path = std::env::temp_dir().join(format!("gomoku-backup-{}.json", uuid::Uuid::new_v4()));
+    backup.write_to(&path).unwrap();
+
+    let mut target = MemoryStore::new();
+    let restored = Backup::read_from(&path).unwrap();
+    restored.restore(&mut target, false).unwrap();
+    assert_eq!(target.load_users().unwrap().len(), 2);
+    assert_eq!(target.load_ratings().unwrap().len(), 2);
+    let restored_games = target.load_adjourned().unwrap();
+    assert_eq!(restored_games.len(), 1);
+    assert_eq!(restored_games[0].game_id, adjourned.game_id);
+    // 目标已有数据时需要 force
+    assert!(restored.restore(&mut target, false).is_err());
+    assert!(restored.restore(&mut target, true).is_ok());
+
+    let mut tampered = restored.clone();
+    tampered.data.ratings[0].1.rating = 3000.0;
+    assert!(tampered.verify().is_err());
+    let mut future = restored;
+    future.version += 1;
+    assert!(future.verify().is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_version_one_backup_restores_without_adjourned_games() {
+    use chess::{Backup, MemoryStore, Store};
+    use sha2::{Digest, Sha256};
+    // 版本 1 的备份还没有 adjourned，校验和按当时的字段算
+    let data =
+        r#"{"users":[],"sessions":[],"ratings":[["u1",{"rating":1612.5,"games":3}]],"games":[]}"#;
+    let json = format!(
+        r#"{{"version":1,"created_at":"2024-01-01T00:00:00Z","checksum":"{:x}","data":{}}}"#,
+        Sha256::digest(data.as_bytes()),
+        data
+    );
+    let backup: Backup = serde_json::from_str(&json).unwrap();
+    backup.verify().unwrap();
+    let mut target = MemoryStore::new();
+    backup.restore(&mut target, false).unwrap();
+    assert_eq!(target.load_ratings().unwrap().len(), 1);
+    assert!(target.load_adjourned().unwrap().is_empty());
+
+    let mut tampered = backup;
+    tampered.data.ratings[0].1.games = 4;
+    assert!(tampered.verify().is_err());
+}
+
+#[test]
+fn test_backup_with_fractional_ratings_survives_a_file() {
+    use chess::{Backup, MemoryStore, Rating, Store};
+    // 普通的等级分大多是小数，写进文件再读回来必须还能通过校验
+    let mut store = MemoryStore::new();
+    for i in 0..500 {
+        let rating = Rating {
+            rating: 1500.0 + i as f64 * 0.731 + 1.0 / (i as f64 + 3.0),
+            games: i,
+        };
+        store.save_rating(&format!("u{}", i), &rating).unwrap();
+    }
+    let backup = Backup::create(&store).unwrap();
+    let path = std::env::temp_dir().join(format!("gomoku-backup-{}.json", uuid::Uuid::new_v4()));
+    backup.write_to(&path).unwrap();
+    let restored = Backup::read_from(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    restored.verify().unwrap();
+    let mut target = MemoryStore::new();
+    restored.restore(&mut target, false).unwrap();
+    assert_eq!(target.load_ratings().unwrap().len(), 500);
+}
+
+#[test]
+fn test_player_stats_streaks() {
+    use chess::{GameOutcome, PlayerStats};
+    let mut stats = PlayerStats::default();
+    assert_eq!(stats.average_move_ms(), None);
+    for outcome in [GameOutcome::Win, GameOutcome::Win, GameOutcome::Loss] {
+        stats.record(outcome, 3000, 3);
+    }
+    assert_eq!((stats.best_streak, stats.current_streak), (2, -1));
+    stats.record(GameOutcome::Draw, 0, 1);
+    assert_eq!(stats.current_streak, 0);
+    assert_eq!(
+        (stats.games, stats.wins, stats.losses, stats.draws),
+        (4, 2, 1, 1)
+    );
+    assert_eq!(stats.average_move_ms(), Some(900));
+
+    // 战绩随用户一起保存
+    let store = shared(SqliteStore::open_in_memory().unwrap());
+    let mut users = UserManager::with_store(store.clone());
+    users.login("alice".to_string());
+    users.record_stats("alice", GameOutcome::Win, 1200, 4);
+    let users = UserManager::with_store(store);
+    let alice = users.get_user_by_name("alice").unwrap();
+    assert_eq!((alice.stats.wins, alice.stats.moves), (1, 4));
+}
+
+#[test]
+fn test_register_login_and_tokens() {
+    use chess::TokenSigner;
+    let mut users = UserManager::new();
+    users.set_token_secret(b"test secret");
+
+    // 游客先下过棋，注册时认领同名账号
+    let (guest, authenticated) = users.connect("alice", None).unwrap();
+    assert!(!authenticated);
+    assert!(users.register("alice", "short").is_err());
+    let token = users.register("alice", "correct horse").unwrap();
+    assert!(users.register("alice", "another pass").is_err());
+
+    // 注册后不能再以游客身份使用这个名字
+    assert!(users.connect("alice", None).is_err());
+    assert!(users.authenticate("alice", "wrong password").is_err());
+    let token2 = users.authenticate("alice", "correct horse").unwrap();
+
+    let (user, authenticated) = users.connect("alice", Some(&token2)).unwrap();
+    assert!(authenticated);
+    assert_eq!(user.id, guest.id);
+    // 令牌不能冒用别人的名字
+    users.connect("bob", None).unwrap();
+    assert!(users.connect("bob", Some(&token)).is_err());
+
+    // 其他密钥签发或被篡改的令牌无效
+    let forged = TokenSigner::new(b"other secret").issue(&guest.id);
+    assert!(users.connect("alice", Some(&forged)).is_err());
+    let tampered = token.replacen(&guest.id, "someone-else", 1);
+    assert!(users.connect("alice", Some(&tampered)).is_err());
+}
+
+#[tokio::test]
+async fn test_concurrent_registration_and_login() {
+    let users = Arc::new(RwLock::new(UserManager::new()));
+    users.write().await.connect("guest", None).unwrap();
+
+    // 同名并发注册只有一个成功
+    let attempts: Vec<_> = (0..4)
+        .map(|_| {
+            let users = users.clone();
+            tokio::spawn(async move { register_user(&users, "carol", "correct horse").await })
+        })
+        .collect();
+    let mut registered = 0;
+    for attempt in attempts {
+        registered += attempt.await.unwrap().is_ok() as usize;
+    }
+    assert_eq!(registered, 1);
+
+    // 登录算哈希时用户表可以照常读写
+    let login = {
+        let users = users.clone();
+        tokio::spawn(async move { authenticate_user(&users, "carol", "correct horse").await })
+    };
+    users.write().await.connect("dave", None).unwrap();
+    let token = login.await.unwrap().unwrap();
+    let (_, authenticated) = users.write().await.connect("carol", Some(&token)).unwrap();
+    assert!(authenticated);
+
+    assert!(authenticate_user(&users, "carol", "wrong password")
+        .await
+        .is_err());
+    assert!(authenticate_user(&users, "guest", "correct horse")
+        .await
+        .is_err());
+    assert!(register_user(&users, "erin", "short").await.is_err());
+}
+
+#[test]
+fn test_rating_csv_import_and_export() {
+    use chess::{read_ratings_csv, write_ratings_csv};
+    let csv = "username,rating,deviation\n\"lee, sedol\",2100,45\nbob, 1650.5 ,350\n";
+    let records = read_ratings_csv(csv.as_bytes()).unwrap();
+    assert_eq!(records[0].username, "lee, sedol");
+    assert_eq!(records[1].rating, 1650.5);
+    assert!(read_ratings_csv("username,rating,deviation\nbob,high,350\n".as_bytes()).is_err());
+    for bad in ["bob,NaN,350", "bob,1500,inf", "bob,-infinity,350"] {
+        let csv = format!("username,rating,deviation\n{}\n", bad);
+        assert!(read_ratings_csv(csv.as_bytes()).is_err(), "{}", bad);
+    }
+
+    let mut users = UserManager::new();
+    users.login("bob".to_string());
+    assert_eq!(users.import_ratings(&records), 2);
+    // 偏差小的按老玩家处理，偏差 350 按新玩家处理
+    let lee = users.get_user_by_name("lee, sedol").unwrap().id.clone();
+    assert_eq!(users.rating(&lee).games, 30);
+    let bob = users.get_user_by_name("bob").unwrap().id.clone();
+    assert_eq!(users.rating(&bob).games, 0);
+
+    let exported = users.export_ratings();
+    assert_eq!(exported[0].username, "lee, sedol");
+    assert_eq!(exported[0].deviation, 50.0);
+    let mut out = Vec::new();
+    write_ratings_csv(&mut out, &exported).unwrap();
+    assert_eq!(read_ratings_csv(out.as_slice()).unwrap(), exported);
+}
+
+#[test]
+fn test_concurrent_games_and_abandon_cooldown() {
+    use chess::AbandonPolicy;
+    let store = shared(SqliteStore::open_in_memory().unwrap());
+    let policy = AbandonPolicy {
+        limit: 2,
+        window_hours: 24,
+        cooldown_secs: 600,
+    };
+
+    let mut users = UserManager::with_store(store.clone());
+    users.set_play_limits(1, policy.clone());
+    let alice = users.login("alice".to_string());
+    users
+        .assign_player(&alice.id, 0, PlayerRole::Black)
+        .unwrap();
+    assert!(users.check_can_play(&alice.id).is_err());
+    assert!(users
+        .assign_player(&alice.id, 1, PlayerRole::Black)
+        .is_err());
+    users.logout(&alice.id);
+    assert!(users.check_can_play(&alice.id).is_ok());
+
+    users.record_abandon(&alice.id);
+    assert!(users.check_can_play(&alice.id).is_ok());
+    users.record_abandon(&alice.id);
+    assert!(users.check_can_play(&alice.id).is_err());
+
+    // 重启后仍然在冷却中，冷却结束后恢复
+    let mut users = UserManager::with_store(store);
+    users.set_play_limits(1, policy);
+    assert!(users.check_can_play(&alice.id).is_err());
+    let later = chrono::Utc::now() + chrono::Duration::minutes(11);
+    assert!(users.cooldown_remaining(&alice.id, later).is_none());
+}
+
+#[test]
+fn test_guests_without_a_name_get_unique_generated_names() {
+    let mut users = UserManager::new();
+    let taken = users.connect("brave-otter-1", None).unwrap().0;
+
+    let mut names = std::collections::HashSet::from([taken.name]);
+    for _ in 0..50 {
+        let (user, authenticated) = users.connect("  ", None).unwrap();
+        assert!(!authenticated);
+        let parts: Vec<&str> = user.name.split('-').collect();
+        assert_eq!(parts.len(), 3, "{}", user.name);
+        assert!(parts[2].parse::<u32>().is_ok());
+        assert!(names.insert(user.name));
+    }
+}
+
+#[test]
+fn test_replay_records_carry_a_version_and_old_ones_are_upgraded() {
+    let game = ArchivedGame {
+        id: "g1".to_string(),
+        black: "alice".to_string(),
+        white: "bob".to_string(),
+        winner: None,
+        reason: Some(GameOverReason::DrawAgreed),
+        moves: Vec::new(),
+        started_at: chrono::Utc::now(),
+        ended_at: chrono::Utc::now(),
+        seed: Some(7),
+        rules: RulesConfig::default(),
+        position_hash: Some(0),
+    };
+    let json = serde_json::to_value(&game).unwrap();
+    assert_eq!(json["version"], REPLAY_FORMAT_VERSION);
+    let back: ArchivedGame = serde_json::from_value(json).unwrap();
+    assert_eq!(back.reason, Some(GameOverReason::DrawAgreed));
+    assert_eq!(back.seed, Some(7));
+
+    // 版本 1 没有 version 字段，也没有结束原因：连成线的补上原因，认输的留空
+    let v1 = |winner: &str, moves: &str| {
+        format!(
+            r#"{{"id":"old","black":"alice","white":"bob","winner":{},"moves":[{}],
+                "started_at":"2024-01-01T00:00:00Z","ended_at":"2024-01-01T00:10:00Z"}}"#,
+            winner, moves
+        )
+    };
+    let stone = |i: usize| {
+        let (player, row) = match i % 2 {
+            0 => ("Black", 7),
+            _ => ("White", 8),
+        };
+        format!(
+            r#"{{"player":"{}","row":{},"col":{},"timestamp":"2024-01-01T00:00:00Z"}}"#,
+            player,
+            row,
+            i / 2
+        )
+    };
+    let five: Vec<_> = (0..9).map(stone).collect();
+    let old: ArchivedGame = serde_json::from_str(&v1(r#""Black""#, &five.join(","))).unwrap();
+    assert_eq!(old.reason, Some(GameOverReason::FiveInARow));
+    assert_eq!(old.rules, RulesConfig::default());
+    assert!(old.verify().is_ok());
+    let resigned: ArchivedGame = serde_json::from_str(&v1(r#""White""#, &stone(0))).unwrap();
+    assert_eq!(resigned.reason, None);
+
+    // 更新的服务器写的记录读不懂，报错而不是丢字段
+    let mut future = serde_json::to_value(&game).unwrap();
+    future["version"] = serde_json::json!(REPLAY_FORMAT_VERSION + 1);
+    let err = serde_json::from_value::<ArchivedGame>(future).unwrap_err();
+    assert!(err.to_string().contains("版本"));
+}
@@ -0,0 +1,90 @@
+use chess::{post_json, GameType, Telemetry, TelemetryConfig, UsageReport};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[test]
+fn test_telemetry_is_opt_in_and_aggregate_only() {
+    let telemetry = Telemetry::new(&TelemetryConfig::default());
+    telemetry.record_game(GameType::Gomoku, 30);
+    assert_eq!(telemetry.take_report(), None);
+
+    telemetry.set_enabled(true);
+    telemetry.record_game(GameType::Gomoku, 10);
+    telemetry.record_game(GameType::Gomoku, 20);
+    let report = telemetry.take_report().unwrap();
+    assert_eq!(report.games_played, 2);
+    assert_eq!(report.average_game_length, 15.0);
+    assert_eq!(report.variants[&GameType::Gomoku], 2);
+    // 取出后清零
+    assert_eq!(telemetry.take_report(), None);
+
+    // 上报内容只有这几个字段
+    let json = serde_json::to_value(&report).unwrap();
+    let mut keys: Vec<&str> = json
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        ["average_game_length", "games_played", "variants", "version"]
+    );
+
+    // 关闭时丢掉还没上报的统计
+    telemetry.record_game(GameType::Gomoku, 10);
+    telemetry.set_enabled(false);
+    telemetry.set_enabled(true);
+    assert_eq!(telemetry.take_report(), None);
+}
+
+#[tokio::test]
+async fn test_report_is_posted_as_json() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}/usage", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // 读到请求头和 Content-Length 指定长度的正文为止
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if body.len() >= length {
+                    break;
+                }
+            }
+        }
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .await
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let telemetry = Telemetry::new(&TelemetryConfig {
+        enabled: true,
+        ..TelemetryConfig::default()
+    });
+    telemetry.record_game(GameType::Gomoku, 42);
+    let report = telemetry.take_report().unwrap();
+    post_json(&endpoint, &report).await.unwrap();
+
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /usage HTTP/1.1\r\n"));
+    let body = request.split("\r\n\r\n").nth(1).unwrap();
+    assert_eq!(serde_json::from_str::<UsageReport>(body).unwrap(), report);
+
+    assert!(post_json("https://stats.example.com", &report)
+        .await
+        .is_err());
+}
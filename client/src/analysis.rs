@@ -0,0 +1,117 @@
+use chess::{suggest_move, Board, Budget, GameError, Suggestion};
+
+use crate::{display_board, role_name, say, t};
+
+// 对局结束后的分析：沿着棋谱前后翻看，在任一手之后试下"如果这样下"的分支，
+// 并请本地引擎给出最佳续着。只在本地棋盘上进行，不和服务器通信
+#[derive(Clone)]
+pub struct Analysis {
+    // 结束时的整盘棋，落子记录就是主线
+    game: Board,
+    // 主线上摆到第几手
+    position: usize,
+    // 在这一手之后试下的子
+    branch: Vec<(usize, usize)>,
+}
+
+impl Analysis {
+    // 从终局开始看
+    pub fn new(game: Board) -> Self {
+        let position = game.moves.len();
+        Self {
+            game,
+            position,
+            branch: Vec::new(),
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.game.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.game.moves.is_empty()
+    }
+
+    pub fn branch(&self) -> &[(usize, usize)] {
+        &self.branch
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        self.jump(self.position + 1)
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        self.position > 0 && self.jump(self.position - 1)
+    }
+
+    // 跳到主线上的第 position 手，试下的分支随之丢弃
+    pub fn jump(&mut self, position: usize) -> bool {
+        if position > self.len() {
+            return false;
+        }
+        self.position = position;
+        self.branch.clear();
+        true
+    }
+
+    // 当前局面：主线的前 position 手，再加上试下的分支
+    pub fn board(&self) -> Board {
+        let mut board = Board::with_rules(self.game.rules);
+        let mainline = self.game.moves[..self.position]
+            .iter()
+            .map(|record| (record.row, record.col));
+        for (row, col) in mainline.chain(self.branch.iter().copied()) {
+            if board.make_move(row, col).is_err() {
+                break;
+            }
+        }
+        board
+    }
+
+    // 在当前局面试下一子，已经分出胜负的局面不能再下
+    pub fn try_move(&mut self, row: usize, col: usize) -> Result<(), GameError> {
+        let board = self.board();
+        if board.winning_line().is_some() {
+            return Err(GameError::InvalidMove(t!("analysis.already_won")));
+        }
+        board.validate_move(row, col)?;
+        self.branch.push((row, col));
+        Ok(())
+    }
+
+    // 撤回分支上的最后一子
+    pub fn undo(&mut self) -> bool {
+        self.branch.pop().is_some()
+    }
+
+    // 本地引擎替当前轮到的一方找最佳续着，已经分出胜负或者下满时返回 None
+    pub fn best_move(&self, budget: Budget) -> Option<Suggestion> {
+        let board = self.board();
+        if board.winning_line().is_some() {
+            return None;
+        }
+        suggest_move(&board, board.current_player, budget)
+    }
+
+    pub fn show(&self) {
+        let board = self.board();
+        display_board(&board);
+        say!("{}", self.summary());
+        if board.winning_line().is_none() {
+            say!("{}", t!("msg.turn", role_name(board.current_player)));
+        }
+    }
+
+    // 一行说明：主线上第几手，有分支时加上分支的步数
+    pub fn summary(&self) -> String {
+        match self.branch.len() {
+            0 => t!("analysis.position", self.position, self.len()),
+            n => t!("analysis.branch", self.position, self.len(), n),
+        }
+    }
+}
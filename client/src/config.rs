@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BoardStyle, Lang};
+
+// 配置文件路径可以通过环境变量覆盖
+pub const CLIENT_CONFIG_ENV: &str = "GOMOKU_CLIENT_CONFIG";
+pub const DEFAULT_CLIENT_CONFIG_PATH: &str = "client_config.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientConfig {
+    // 自己的时间快用完时执行的命令，例如桌面通知
+    #[serde(default)]
+    pub warning_command: Option<String>,
+    // 界面语言，lang 命令切换后会写回配置文件；没写时按系统的区域设置
+    #[serde(default)]
+    pub lang: Option<Lang>,
+    // 无障碍模式：用完整句子描述棋局，不画棋盘
+    #[serde(default)]
+    pub accessible: bool,
+    // 落子前先在本地试下，显示这一手形成的棋形，确认后才发出
+    #[serde(default)]
+    pub confirm_moves: bool,
+    // 棋盘画法：plain 或 unicode
+    #[serde(default)]
+    pub board_style: BoardStyle,
+    // 是否用颜色区分黑白子，没写时按输出是不是终端、有没有设置 NO_COLOR
+    #[serde(default)]
+    pub color: Option<bool>,
+    // 客户端脚本（Rhai）的路径，收到的每条消息都交给脚本的 on_event 处理
+    #[serde(default)]
+    pub script: Option<String>,
+    // 不响铃，轮到自己和收到聊天时也不提醒
+    #[serde(default)]
+    pub quiet: bool,
+    // 轮到自己、对局结束和收到聊天时弹出系统桌面通知
+    #[serde(default)]
+    pub desktop_notifications: bool,
+}
+
+impl ClientConfig {
+    pub fn path() -> String {
+        std::env::var(CLIENT_CONFIG_ENV).unwrap_or_else(|_| DEFAULT_CLIENT_CONFIG_PATH.to_string())
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !Path::new(&path).exists() {
+            return Self::default();
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", crate::t!("config.load_failed", path, e));
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(Self::path(), text).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,134 @@
+use chess::{DemoAction, GameMessage, PlayerRole, VariationNode};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::i18n::error_text;
+use crate::{handle_game_message, parse_frame, t, ClientConfig, ClientError, ClientState};
+
+// 讲解命令对应的操作，参数不对时返回 None。load 要读文件，单独处理
+pub fn demo_command(parts: &[&str]) -> Option<DemoAction> {
+    let action = match parts {
+        [color @ ("black" | "white"), row, col] => DemoAction::Place {
+            row: row.parse().ok()?,
+            col: col.parse().ok()?,
+            color: match *color {
+                "black" => PlayerRole::Black,
+                _ => PlayerRole::White,
+            },
+        },
+        ["remove", row, col] => DemoAction::Remove {
+            row: row.parse().ok()?,
+            col: col.parse().ok()?,
+        },
+        ["clear"] => DemoAction::Clear,
+        ["goto", path @ ..] => DemoAction::Goto {
+            path: path.iter().map(|i| i.parse().ok()).collect::<Option<_>>()?,
+        },
+        ["note", text @ ..] if !text.is_empty() => DemoAction::Note {
+            text: text.join(" "),
+        },
+        _ => return None,
+    };
+    Some(action)
+}
+
+// 变化树文件是第一手几种下法的 JSON 数组
+fn load_variations(path: &str) -> Option<DemoAction> {
+    let variations = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            serde_json::from_str::<Vec<VariationNode>>(&json).map_err(|e| e.to_string())
+        });
+    match variations {
+        Ok(variations) => Some(DemoAction::LoadVariations { variations }),
+        Err(e) => {
+            println!("{}", t!("present.load_failed", path, e));
+            None
+        }
+    }
+}
+
+// 讲解模式：先登录，再开一个演示房间，摆子、切换变化、发讲解，观众看到同样的棋盘。
+// 输入 quit 时关闭房间并返回 Ok
+pub async fn run_present(
+    ws_stream: WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+    title: String,
+    username: String,
+    password: String,
+) -> Result<(), ClientError> {
+    let (mut write, mut read) = ws_stream.split();
+    let mut state = ClientState::with_config(&ClientConfig::load());
+    let mut lines = BufReader::new(io::stdin()).lines();
+
+    let json = serde_json::to_string(&GameMessage::Login { username, password }).unwrap();
+    write
+        .send(Message::Text(json))
+        .await
+        .map_err(|_| ClientError::ServerClosed)?;
+    let token = loop {
+        let text = match read.next().await {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(_)) => continue,
+            Some(Err(_)) | None => return Err(ClientError::ServerClosed),
+        };
+        match parse_frame(&text) {
+            Some(GameMessage::AuthToken { token, .. }) => break token,
+            Some(GameMessage::Error(e)) => {
+                return Err(ClientError::Connect(t!("game.auth_failed", error_text(&e))));
+            }
+            _ => {}
+        }
+    };
+
+    let json = serde_json::to_string(&GameMessage::CreateDemo { title, token }).unwrap();
+    write
+        .send(Message::Text(json))
+        .await
+        .map_err(|_| ClientError::ServerClosed)?;
+    println!("{}", t!("present.help"));
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line.map_err(ClientError::Input)? else {
+                    return Ok(());
+                };
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                let action = match parts[..] {
+                    ["quit"] => return Ok(()),
+                    ["load", path] => load_variations(path),
+                    _ => {
+                        let action = demo_command(&parts);
+                        if action.is_none() {
+                            println!("{}", t!("present.help"));
+                        }
+                        action
+                    }
+                };
+                let Some(action) = action else {
+                    continue;
+                };
+                let json = serde_json::to_string(&GameMessage::DemoEdit { action }).unwrap();
+                write
+                    .send(Message::Text(json))
+                    .await
+                    .map_err(|_| ClientError::ServerClosed)?;
+            }
+            frame = read.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    if let Some(msg) = parse_frame(&text) {
+                        if handle_game_message(msg, &mut state) {
+                            return Ok(());
+                        }
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    return Err(ClientError::ServerClosed);
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
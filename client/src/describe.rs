@@ -0,0 +1,107 @@
+use chess::{Board, MovePreview, PlayerRole, ThreatKind};
+
+use crate::{role_name, t};
+
+// 四个方向上穿过 (row, col) 的连子数
+fn line_length(board: &Board, row: usize, col: usize, dr: i32, dc: i32) -> usize {
+    let Some(player) = board.cells[row][col] else {
+        return 0;
+    };
+    let mut count = 1;
+    for sign in [1, -1] {
+        let (mut r, mut c) = (row as i32, col as i32);
+        loop {
+            r += dr * sign;
+            c += dc * sign;
+            if !board.rules.contains(r, c) {
+                break;
+            }
+            if board.cells[r as usize][c as usize] != Some(player) {
+                break;
+            }
+            count += 1;
+        }
+    }
+    count
+}
+
+// 行列对外显示从 1 开始
+fn direction_text(dr: i32, dc: i32, row: usize, col: usize) -> String {
+    match (dr, dc) {
+        (0, 1) => t!("a11y.dir_row", row + 1),
+        (1, 0) => t!("a11y.dir_col", col + 1),
+        (1, 1) => t!("a11y.dir_diag"),
+        _ => t!("a11y.dir_anti"),
+    }
+}
+
+// 用完整的句子描述一步棋，例如
+// “黑方落子于第 8 行第 8 列；轮到白方；黑方在第 8 列形成四连”
+pub fn describe_move(board: &Board, player: PlayerRole, row: usize, col: usize) -> String {
+    let mut parts = vec![t!("a11y.placed", role_name(player), row + 1, col + 1)];
+    parts.push(t!("a11y.to_move", role_name(board.current_player)));
+
+    let longest = [(0, 1), (1, 0), (1, 1), (1, -1)]
+        .into_iter()
+        .map(|(dr, dc)| (line_length(board, row, col, dr, dc), dr, dc))
+        .max_by_key(|&(len, _, _)| len);
+    if let Some((len, dr, dc)) = longest {
+        if len >= 3 {
+            parts.push(t!(
+                "a11y.threat",
+                role_name(player),
+                len,
+                direction_text(dr, dc, row, col)
+            ));
+        }
+    }
+    parts.join(t!("a11y.separator").as_str())
+}
+
+// 试下一子的结果，例如“黑方试下第 8 行第 8 列；黑方位于第 8 列形成活四”
+pub fn describe_preview(preview: &MovePreview) -> String {
+    let (row, col) = (preview.row, preview.col);
+    let mut parts = vec![t!(
+        "preview.title",
+        role_name(preview.player),
+        row + 1,
+        col + 1
+    )];
+    if preview.is_win() {
+        parts.push(t!("preview.win"));
+    } else if preview.threats.is_empty() {
+        parts.push(t!("preview.none"));
+    }
+    for threat in &preview.threats {
+        let (dr, dc) = threat.direction.step();
+        let direction = direction_text(dr, dc, row, col);
+        match threat.kind {
+            ThreatKind::Win => {}
+            ThreatKind::OpenFour => parts.push(t!("preview.open_four", direction)),
+            ThreatKind::Four => parts.push(t!("preview.four", direction)),
+            ThreatKind::OpenThree => parts.push(t!("preview.open_three", direction)),
+        }
+    }
+    parts.join(t!("a11y.separator").as_str())
+}
+
+// 整盘局面的简要描述，用于重新同步时代替棋盘图
+pub fn describe_board(board: &Board) -> String {
+    let count = |role| {
+        board
+            .cells
+            .iter()
+            .flatten()
+            .filter(|&&cell| cell == Some(role))
+            .count()
+    };
+    [
+        t!(
+            "a11y.summary",
+            count(PlayerRole::Black),
+            count(PlayerRole::White)
+        ),
+        t!("a11y.to_move", role_name(board.current_player)),
+    ]
+    .join(t!("a11y.separator").as_str())
+}
@@ -0,0 +1,36 @@
+use crate::t;
+
+// 客户端接口返回的错误，脚本和机器人可以据此决定重连还是退出
+#[derive(Debug)]
+pub enum ClientError {
+    // 发送登录信息失败、登录被拒绝等，连接没能建立起来
+    Connect(String),
+    // 服务器发来了解析不了的消息
+    Protocol(String),
+    // 连续要了几次整盘局面，本地棋盘仍然和服务器对不上
+    Desync,
+    // 对局结束前服务器关闭了连接
+    ServerClosed,
+    // 读取标准输入失败
+    Input(std::io::Error),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClientError::Connect(msg) | ClientError::Protocol(msg) => write!(f, "{}", msg),
+            ClientError::Desync => write!(f, "{}", t!("error.desync")),
+            ClientError::ServerClosed => write!(f, "{}", t!("error.server_closed")),
+            ClientError::Input(e) => write!(f, "{}", t!("input.read_error", e)),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Input(e) => Some(e),
+            _ => None,
+        }
+    }
+}
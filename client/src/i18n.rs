@@ -0,0 +1,1024 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use chess::{ErrorCode, ErrorReply, GameOverReason, PlayerRole};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lang {
+    #[default]
+    Zh,
+    En,
+}
+
+impl Lang {
+    pub fn parse(code: &str) -> Option<Lang> {
+        match code.to_ascii_lowercase().replace('_', "-").as_str() {
+            "zh" | "zh-cn" | "cn" => Some(Lang::Zh),
+            "en" | "en-us" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    // 系统区域设置，例如 LANG=en_US.UTF-8；只看语言部分，其他英语地区也按英文显示
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let code = locale.split(['.', '@']).next()?;
+        Lang::parse(code).or_else(|| Lang::parse(code.split(['_', '-']).next()?))
+    }
+
+    // 启动时的语言：命令行 --lang 优先，其次是配置文件，都没写时按 LC_ALL、LANG 环境变量
+    pub fn detect(flag: Option<&str>, configured: Option<Lang>) -> Lang {
+        flag.and_then(Lang::parse)
+            .or(configured)
+            .or_else(|| {
+                ["LC_ALL", "LANG"]
+                    .iter()
+                    .filter_map(|var| std::env::var(var).ok())
+                    .find_map(|locale| Lang::from_locale(&locale))
+            })
+            .unwrap_or_default()
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::Zh => ZH,
+            Lang::En => EN,
+        }
+    }
+}
+
+// 当前语言，运行时可以通过 lang 命令切换
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Lang::En,
+        _ => Lang::Zh,
+    }
+}
+
+// 查找文案，当前语言缺失时退回中文，再缺失则返回键本身
+pub fn tr(key: &'static str) -> &'static str {
+    let find = |lang: Lang| {
+        lang.catalog()
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    };
+    find(lang()).or_else(|| find(Lang::Zh)).unwrap_or(key)
+}
+
+// 依次用参数替换文案中的 {}
+pub fn fill(template: &str, args: &[String]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        out.push_str(args.next().map(String::as_str).unwrap_or("{}"));
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn role_name(role: PlayerRole) -> &'static str {
+    match role {
+        PlayerRole::Black => tr("role.black"),
+        PlayerRole::White => tr("role.white"),
+    }
+}
+
+pub fn reason_name(reason: GameOverReason) -> &'static str {
+    match reason {
+        GameOverReason::FiveInARow => tr("reason.five_in_a_row"),
+        GameOverReason::Timeout => tr("reason.timeout"),
+        GameOverReason::Resignation => tr("reason.resignation"),
+        GameOverReason::Disconnect => tr("reason.disconnect"),
+        GameOverReason::DrawAgreed => tr("reason.draw_agreed"),
+        GameOverReason::BoardFull => tr("reason.board_full"),
+        GameOverReason::MoveLimit => tr("reason.move_limit"),
+        GameOverReason::Blocked => tr("reason.blocked"),
+        GameOverReason::Unknown => tr("reason.unknown"),
+    }
+}
+
+pub fn error_code_name(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::InvalidInput => tr("code.invalid_input"),
+        ErrorCode::InvalidPosition => tr("code.invalid_position"),
+        ErrorCode::PositionOccupied => tr("code.position_occupied"),
+        ErrorCode::InvalidMove => tr("code.invalid_move"),
+        ErrorCode::Disconnected => tr("code.disconnected"),
+        ErrorCode::InvalidPhase => tr("code.invalid_phase"),
+        ErrorCode::Protocol => tr("code.protocol"),
+        ErrorCode::Unauthorized => tr("code.unauthorized"),
+        ErrorCode::NotFound => tr("code.not_found"),
+        ErrorCode::Unavailable => tr("code.unavailable"),
+        ErrorCode::Internal => tr("code.internal"),
+        ErrorCode::Unknown => tr("code.unknown"),
+    }
+}
+
+// 服务器发来的错误：说明文字是服务器写的中文，其他语言按错误码显示本地的说明；
+// 旧服务器不带错误码，只能原样显示
+pub fn error_text(reply: &ErrorReply) -> String {
+    match reply.code {
+        Some(code) if lang() != Lang::Zh => error_code_name(code).to_string(),
+        _ => reply.message.clone(),
+    }
+}
+
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::tr($key).to_string()
+    };
+    ($key:expr, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::tr($key), &[$($arg.to_string()),+])
+    };
+}
+
+const ZH: &[(&str, &str)] = &[
+    ("a11y.placed", "{}落子于第 {} 行第 {} 列"),
+    ("a11y.to_move", "轮到{}"),
+    ("a11y.threat", "{}形成 {} 连，{}"),
+    ("a11y.dir_row", "位于第 {} 行"),
+    ("a11y.dir_col", "位于第 {} 列"),
+    ("a11y.dir_diag", "位于左上到右下的斜线"),
+    ("a11y.dir_anti", "位于右上到左下的斜线"),
+    ("a11y.summary", "棋盘上有黑子 {} 枚、白子 {} 枚"),
+    ("a11y.separator", "；"),
+    ("preview.title", "{}试下第 {} 行第 {} 列"),
+    ("preview.none", "没有形成威胁"),
+    ("preview.win", "连成一线获胜"),
+    ("preview.open_four", "{}形成活四"),
+    ("preview.four", "{}形成冲四"),
+    ("preview.open_three", "{}形成活三"),
+    ("role.black", "黑方"),
+    ("role.white", "白方"),
+    ("role.spectator", "观战"),
+    ("msg.connecting", "正在连接到游戏，用户名: {}..."),
+    (
+        "msg.connected",
+        "已连接到游戏，欢迎 {}! 你的角色是: {}，等级分 {}",
+    ),
+    ("msg.leaderboard", "排行榜："),
+    ("stats.title", "{} 的战绩："),
+    ("stats.record", "共 {} 盘，{} 胜 {} 负 {} 平"),
+    ("stats.win_streak", "当前 {} 连胜"),
+    ("stats.loss_streak", "当前 {} 连败"),
+    ("stats.best_streak", "最长连胜 {} 盘"),
+    ("stats.average_move", "平均每步用时 {} 秒"),
+    ("msg.leaderboard_entry", "{}. {}  {} 分  ({} 盘)"),
+    ("msg.move_failed", "移动失败: {}"),
+    ("msg.error", "错误: {}"),
+    ("msg.winner", "游戏结束！胜利者是: {}"),
+    ("msg.draw", "游戏结束！平局！"),
+    ("msg.winning_line", "连成五子: {}"),
+    ("msg.turn", "轮到玩家 {} 移动"),
+    ("msg.player_left", "玩家 {} 已断开连接"),
+    ("msg.player_joined", "玩家 {} ({}) 已加入游戏"),
+    ("msg.server_shutdown", "服务器已关闭"),
+    (
+        "msg.game_paused",
+        "玩家 {} 掉线，对局暂停，{} 秒内未重连判负",
+    ),
+    ("msg.game_resumed", "玩家 {} 已重连，对局继续"),
+    (
+        "msg.session_transferred",
+        "你已在其他设备上继续对局，本连接已关闭",
+    ),
+    ("msg.server_notice", "服务器通知: {}"),
+    ("msg.kicked", "你已被移出对局: {}"),
+    ("msg.protocol_violation", "违反协议 ({})，服务器断开了连接: {}"),
+    (
+        "msg.queue_status",
+        "房间已满，排队中：第 {} 位，预计等待约 {} 分钟",
+    ),
+    ("msg.time_left", "剩余时间 黑方 {}  白方 {}"),
+    ("msg.own_time_warning", "!! 你仅剩 {} 秒 !!"),
+    ("msg.opponent_time", "对手 {} 剩余 {} 秒"),
+    ("msg.sgf", "棋谱 (SGF):"),
+    ("msg.presence_restored", "玩家 {} 的网络已恢复"),
+    ("msg.presence_thinking", "玩家 {} 正在思考..."),
+    (
+        "msg.presence_degraded",
+        "玩家 {} 的网络连接不稳定，请耐心等待",
+    ),
+    (
+        "msg.game_archived",
+        "对局已存档，编号 {}，可输入 replay <编号> 回放",
+    ),
+    ("msg.replay_loaded", "开始回放对局 {}：{} (黑) 对 {} (白)"),
+    ("replay.start", "开局，共 {} 手"),
+    ("replay.move", "第 {}/{} 手：{} 落子于 ({}, {})，用时 {} 秒"),
+    ("msg.board_title", "当前棋盘："),
+    ("input.sending_move", "发送移动消息: {}"),
+    ("input.confirm_resign", "对局还在进行，退出就是认输，确定吗？(y/n)"),
+    ("input.quit_cancelled", "已取消退出，对局继续"),
+    ("input.confirm_move", "确定在这里落子吗？(y/n)"),
+    ("input.move_cancelled", "已取消落子"),
+    (
+        "input.bad_coords",
+        "无效的行/列。用法: move <行> <列> (0-14) 或 move H8",
+    ),
+    (
+        "input.bad_command",
+        "无效的命令。用法: move <行> <列> (0-14) 或 move H8",
+    ),
+    ("input.read_error", "输入错误: {}"),
+    ("input.lang_usage", "用法: lang <zh|en>"),
+    ("input.lang_switched", "已切换为中文"),
+    ("input.top_usage", "用法: top [人数]"),
+    (
+        "input.invite_usage",
+        "用法: invite <black|white|watch> [分钟]",
+    ),
+    ("input.explore_usage", "用法: explore <行,列> ...，从黑棋第一手写起"),
+    ("input.stats_not_connected", "尚未连接，无法查看自己的战绩"),
+    ("input.no_replay", "还没有加载回放，请先输入 replay <编号>"),
+    ("input.replay_out_of_range", "超出范围，可用手数为 0-{}"),
+    ("input.config_save_failed", "保存客户端配置失败: {}"),
+    ("game.send_username_failed", "发送用户名失败: {}"),
+    ("game.authenticated", "登录成功，对手也登录时本局计等级分"),
+    ("game.auth_failed", "登录失败: {}"),
+    ("game.welcome", "欢迎来到五子棋游戏！"),
+    ("game.waiting_role", "等待服务器分配玩家角色..."),
+    ("game.listening", "开始监听服务器消息..."),
+    ("game.parse_failed", "解析消息失败: {}"),
+    ("game.task_exit", "{} 任务结束"),
+    ("game.task_error", "{} 任务错误: {}"),
+    ("game.task_read", "读取"),
+    ("game.task_write", "写入"),
+    ("game.task_input", "输入"),
+    ("game.channel_closed", "通道关闭，写入任务退出"),
+    ("error.desync", "多次同步后本地棋盘仍和服务器不一致"),
+    ("error.server_closed", "服务器关闭了连接"),
+    (
+        "game.help_move",
+        "输入格式: move <行> <列> (例如: move 7 7) 或者坐标 move H8，六子棋一回合的两子可以一起输入: move 7 7 7 8",
+    ),
+    ("game.help_export", "输入 'export' 导出当前棋谱 (SGF)"),
+    ("game.help_lang", "输入 'lang en' 或 'lang zh' 切换语言"),
+    (
+        "game.help_replay",
+        "输入 'replay <编号>' 回放对局，用 next / prev / jump <手数> 翻看",
+    ),
+    ("game.help_top", "输入 'top [人数]' 查看排行榜"),
+    ("game.help_stats", "输入 'stats [用户ID]' 查看战绩"),
+    (
+        "game.help_invite",
+        "输入 'invite <black|white|watch> [分钟]' 生成邀请令牌",
+    ),
+    ("game.help_hint", "输入 'hint' 请电脑给出建议，每盘次数有限"),
+    ("game.help_eval", "输入 'eval <编号>' 查看已结束对局的评估曲线"),
+    (
+        "game.help_explore",
+        "输入 'explore 7,7 7,8 ...' 查找下出过这个局面的历史对局",
+    ),
+    ("game.help_info", "输入 'info' 查看服务器版本和规则"),
+    (
+        "game.help_preview",
+        "输入 'preview <行> <列>' 或 'preview H8' 查看这一手会形成的棋形，不会落子",
+    ),
+    ("game.help_quit", "输入 'quit' 退出游戏"),
+    (
+        "watch.help",
+        "观战: 'list' 刷新对局列表, 'watch <序号>' 观战, 'next' 看下一盘, \
+         'region <行> <列> <行数> <列数>' 只看一块窗口 ('region off' 恢复整盘), \
+         'claim <black|white>' 接替空出来的座位, 'stop' 停止, 'quit' 退出",
+    ),
+    ("msg.region_title", "窗口: 第 {}-{} 行, 第 {}-{} 列"),
+    ("msg.evaluation", "第 {} 手 黑 {} 白 ({})"),
+    ("msg.evaluation_graph", "对局 {} 的评估曲线 (黑方视角):"),
+    ("msg.region_outside", "窗口外: 黑棋 {} 子, 白棋 {} 子"),
+    ("msg.region_last_move", "窗口外最近一步: ({}, {})"),
+    ("msg.game_list", "进行中的对局 (共 {} 盘):"),
+    ("msg.game_list_entry", "{}. {} (黑) vs {} (白)，已下 {} 手"),
+    ("msg.no_live_games", "当前没有进行中的对局"),
+    ("msg.position_games", "下出过这个局面的历史对局 (共 {} 盘):"),
+    ("msg.position_entry", "{}. [{}] {} (黑) vs {} (白)，第 {} 手出现，{}"),
+    ("msg.position_won", "{} 胜"),
+    ("msg.position_draw", "和棋"),
+    ("msg.position_none", "没有下出过这个局面的历史对局"),
+    ("msg.server_info", "服务器版本 {}，协议版本 {}"),
+    (
+        "msg.protocol_older",
+        "服务器使用较旧的协议版本 {} (客户端为 {})，部分功能可能不可用",
+    ),
+    (
+        "msg.server_variant",
+        "{}: {}×{} 棋盘，连 {} 子获胜，每回合 {} 子，已开启: {}",
+    ),
+    ("msg.server_time_control", "时限: 每方 {}，每步加 {} 秒"),
+    ("msg.server_untimed", "时限: 不限时"),
+    ("msg.server_limits", "最多 {} 盘同时进行，每盘 {} 次提示"),
+    ("msg.watching", "正在观战: {} (黑) vs {} (白)"),
+    (
+        "msg.invite_created",
+        "已生成{}邀请，{} 分钟内有效，对方用 --invite {} 启动客户端",
+    ),
+    (
+        "msg.hint",
+        "提示: 建议下在 ({}, {})，局面评分 {}，本局还剩 {} 次",
+    ),
+    ("game.press_enter", "游戏已结束，按回车键退出..."),
+    ("main.connecting", "正在连接到服务器: {}"),
+    (
+        "main.ask_username",
+        "请输入您的用户名 (直接回车随机生成游客名):",
+    ),
+    (
+        "main.ask_password",
+        "请输入密码 (直接回车以游客身份进入，不计等级分):",
+    ),
+    ("main.connected", "已连接到服务器"),
+    ("main.connect_failed", "连接失败: {}"),
+    ("main.bye", "程序结束"),
+    (
+        "config.load_failed",
+        "读取客户端配置 {} 失败: {}，使用默认配置",
+    ),
+    ("notify.command_failed", "执行提醒命令失败: {}"),
+    ("script.loaded", "已加载脚本 {}"),
+    ("script.load_failed", "加载脚本 {} 失败: {}"),
+    ("script.error", "脚本出错: {}"),
+    ("script.note", "[脚本] {}"),
+    ("script.bad_position", "坐标不能为负: ({}, {})"),
+    ("script.bad_event", "无法转换给脚本的消息: {}"),
+    (
+        "msg.seat_open",
+        "{}的座位空出来了，等待观战者接替 (观战者输入 'claim <black|white>')",
+    ),
+    (
+        "game.help_substitutes",
+        "输入 'substitutes on|off' 允许或禁止观战者接替中途离开的玩家 (仅房主)",
+    ),
+    ("input.substitutes_usage", "用法: substitutes <on|off>"),
+    ("msg.game_over_reason", "结束原因: {}"),
+    ("reason.five_in_a_row", "连成一线"),
+    ("reason.timeout", "超时"),
+    ("reason.resignation", "认输"),
+    ("reason.disconnect", "玩家离开"),
+    ("reason.draw_agreed", "双方同意和棋"),
+    ("reason.board_full", "棋盘已下满"),
+    ("reason.move_limit", "达到步数上限"),
+    ("reason.blocked", "双方都已无法连成一线"),
+    ("reason.unknown", "未知原因"),
+    (
+        "msg.draw_offered",
+        "{}提出和棋，输入 'draw accept' 同意或 'draw decline' 拒绝",
+    ),
+    ("msg.draw_declined", "{}拒绝了和棋"),
+    (
+        "game.help_draw",
+        "输入 'draw' 提出和棋，'draw accept' 或 'draw decline' 回应对手的提议",
+    ),
+    ("input.draw_usage", "用法: draw [accept|decline]"),
+    (
+        "msg.demo_created",
+        "演示房间已开放，观众启动 --watch 后输入 'watch {}' 收看",
+    ),
+    ("msg.demo_comment", "讲解: {}"),
+    ("msg.demo_next", "接下来的变化: {}"),
+    ("msg.demo_closed", "讲解已结束"),
+    (
+        "present.help",
+        "讲解: 'black|white <行> <列>' 摆子, 'remove <行> <列>' 拿掉, 'clear' 清空, \
+         'load <文件>' 载入变化树, 'goto [序号 ...]' 切换变化, 'note <文字>' 发讲解, 'quit' 结束",
+    ),
+    ("present.load_failed", "读取变化树 {} 失败: {}"),
+    (
+        "game.play_again",
+        "输入 'n' 寻找新对手再来一盘，输入 'quit' 退出",
+    ),
+    ("input.finding_opponent", "正在寻找新对手..."),
+    ("input.not_finished", "对局还没有结束"),
+    ("tui.board", "棋盘"),
+    ("tui.status", "对局"),
+    ("tui.log", "消息"),
+    (
+        "tui.keys",
+        "方向键移动光标  回车或鼠标点击落子  ':' 输入命令  n 换对手  q 退出",
+    ),
+    ("tui.confirm_quit", "对局还在进行，再按一次 q 认输并退出"),
+    ("tui.you", "你执{}"),
+    ("tui.move", "第 {} 手"),
+    ("tui.last_move", "上一手 ({}, {})"),
+    ("tui.finished", "对局已结束"),
+    ("tui.game", "对局 {}"),
+    (
+        "msg.events_summarized",
+        "房间消息太密，{} 条合并跳过，下面是当前局面",
+    ),
+    ("game.unfinished_title", "你有 {} 盘没下完的对局:"),
+    (
+        "game.unfinished_item",
+        "{}. 对手 {}，你执{}，已下 {} 手，轮到{}  对局 {}",
+    ),
+    ("game.unfinished_adjourned", "   停服前保存，选它接着下"),
+    ("game.unfinished_live", "   进行中，连接后自动回到座位"),
+    (
+        "game.unfinished_pick",
+        "输入编号继续那盘对局，直接回车开始新的对局:",
+    ),
+    ("notation.empty", "没有给出坐标，例如 move 7 7 或 move H8"),
+    (
+        "notation.unrecognized",
+        "看不懂坐标 '{}'：写成 '<行> <列>' (例如 7 7) 或列字母加行号 (例如 H8)",
+    ),
+    ("notation.missing_column", "第 {} 行之后缺少列号"),
+    ("notation.off_board", "{} 不在棋盘上"),
+    ("local.started", "本地对局，不连服务器：你执{}，电脑难度 {}"),
+    (
+        "local.help",
+        "输入 move <行> <列> 或坐标 (例如 H8) 落子，'undo' 悔棋，'export' 导出棋谱，'quit' 退出",
+    ),
+    ("local.thinking", "电脑思考中..."),
+    ("local.hotseat_started", "本地双人对局：{} 路棋盘，{} 子连珠，两人轮流在这个终端落子"),
+    ("local.computer", "电脑"),
+    ("local.finished", "对局已结束：可以 'undo' 悔棋接着下、'export' 导出棋谱或 'quit' 退出"),
+    ("local.nothing_to_undo", "还没有可以悔的棋"),
+    ("local.undone", "已悔棋，撤回 {} 子"),
+    ("local.bad_rules", "规则设置有误: {}"),
+    ("local.rules_missing", "--rules 后面缺少 JSON"),
+    ("local.rules_range", "每回合落子 1-{} 个，连子数 {}-{}，棋盘大小在连子数到 {} 之间"),
+    ("reconnect.lost", "和服务器的连接断开了，正在重连..."),
+    ("reconnect.waiting", "{} 秒后重连 (第 {}/{} 次)"),
+    ("reconnect.failed", "重连失败: {}"),
+    ("reconnect.restored", "已重新连上服务器，正在同步棋盘"),
+    ("reconnect.gave_up", "重连 {} 次都没有成功，放弃"),
+    ("reconnect.not_sent", "连接已断开，这条消息没有发出"),
+    ("code.invalid_input", "输入有误"),
+    ("code.invalid_position", "位置不在棋盘上"),
+    ("code.position_occupied", "这个位置已经有子了"),
+    ("code.invalid_move", "这一步不合规则"),
+    ("code.disconnected", "对方的连接已断开"),
+    ("code.invalid_phase", "对局当前的阶段不能这样做"),
+    ("code.protocol", "消息不符合协议"),
+    ("code.unauthorized", "没有权限，或者令牌、密码不对"),
+    ("code.not_found", "要找的对局或用户不存在"),
+    ("code.unavailable", "暂时不可用，请稍后再试"),
+    ("code.internal", "服务器内部错误"),
+    ("code.unknown", "未知错误"),
+    ("gui.title", "五子棋"),
+    ("gui.server", "服务器"),
+    ("gui.username", "用户名"),
+    ("gui.password", "密码 (游客留空)"),
+    ("gui.connect", "连接"),
+    ("gui.connecting", "正在连接服务器..."),
+    ("gui.find_opponent", "再来一盘"),
+    ("gui.resign", "认输"),
+    ("gui.offer_draw", "提和"),
+    ("gui.accept_draw", "同意和棋"),
+    ("gui.lobby", "大厅"),
+    ("gui.refresh", "刷新对局列表"),
+    ("gui.lobby_entry", "{} 对 {}，{} 手"),
+    ("gui.watch", "观战"),
+    ("gui.chat", "聊天"),
+    ("gui.chat_hint", "说点什么，按回车发送"),
+    ("gui.send", "发送"),
+    ("gui.command_hint", "输入命令，和终端客户端一样，例如 hint、eval、move H8"),
+    ("msg.chat", "{}: {}"),
+    ("input.chat_usage", "用法: chat <内容>"),
+    ("game.help_chat", "输入 'chat <内容>' 和对手及观战者聊天"),
+    ("notify.title", "五子棋"),
+    ("notify.your_turn", "轮到你落子了"),
+    ("tui.live_games", "进行中的对局"),
+    (
+        "tui.watch_keys",
+        "数字键观战列表里的对局  n 下一盘  r 刷新列表  s 停止观战  ':' 输入命令  q 退出",
+    ),
+    ("tui.player", "{}: {}"),
+    ("tui.evaluation", "评估 {} ({})"),
+    ("gui.evaluation", "局面评估 (黑方视角): {}"),
+    ("game.analyze_hint", "输入 'analyze' 进入分析模式复盘这一局"),
+    (
+        "game.help_analyze",
+        "对局结束后输入 'analyze' 进入分析模式，'analyze off' 退出",
+    ),
+    ("input.analyze_usage", "用法: analyze 或 analyze off"),
+    (
+        "analysis.help",
+        "分析模式: 'next'/'prev'/'jump <手数>' 沿棋谱翻看, 'try <行> <列>' 试下一子, \
+         'undo' 撤回试下的子, 'best' 请电脑给出最佳续着, 'analyze off' 退出",
+    ),
+    ("analysis.closed", "已退出分析模式"),
+    ("analysis.position", "分析: 第 {}/{} 手"),
+    ("analysis.branch", "分析: 第 {}/{} 手之后试下了 {} 子"),
+    ("analysis.best", "{}的最佳续着: {} ({}, {})，局面评分 {}"),
+    ("analysis.no_best", "这个局面已经没有可下的位置"),
+    ("analysis.already_won", "这个局面已经分出胜负"),
+    ("analysis.nothing_to_undo", "没有试下的子可以撤回"),
+    ("present.login_required", "讲解需要登录，请输入注册过的用户名和密码"),
+    ("reconnect.no_reply", "服务器没有答复"),
+    ("reconnect.game_gone", "原来的对局已经不在了，没有加入新的对局"),
+    ("reconnect.queued", "连接断开了，这条消息等重新连上后再发"),
+    ("input.confirm_again", "再选一次同一个位置确认落子"),
+    ("game.message_skipped", "跳过一条无法解析的消息: {}"),
+    ("msg.seat_claimed", "已接替{}的座位，对局继续"),
+    ("msg.rules_invalid", "服务器发来的规则无效（{}），按 15 路五子棋显示"),
+    ("msg.replay_rules_invalid", "对局 {} 的规则无效（{}），无法回放"),
+];
+
+const EN: &[(&str, &str)] = &[
+    ("a11y.placed", "{} placed at row {}, column {}"),
+    ("a11y.to_move", "{} to move"),
+    ("a11y.threat", "{} threatens {} in a row {}"),
+    ("a11y.dir_row", "on row {}"),
+    ("a11y.dir_col", "on column {}"),
+    ("a11y.dir_diag", "on the diagonal running down to the right"),
+    ("a11y.dir_anti", "on the diagonal running down to the left"),
+    ("a11y.summary", "The board has {} black and {} white stones"),
+    ("a11y.separator", "; "),
+    ("preview.title", "{} trying row {}, column {}"),
+    ("preview.none", "no threats"),
+    ("preview.win", "wins the game"),
+    ("preview.open_four", "open four {}"),
+    ("preview.four", "four {}"),
+    ("preview.open_three", "open three {}"),
+    ("role.black", "Black"),
+    ("role.white", "White"),
+    ("role.spectator", "spectator"),
+    ("msg.connecting", "Connecting to the game as {}..."),
+    (
+        "msg.connected",
+        "Connected. Welcome {}! You play {}, rating {}",
+    ),
+    ("msg.leaderboard", "Leaderboard:"),
+    ("stats.title", "Profile of {}:"),
+    ("stats.record", "{} games: {} wins, {} losses, {} draws"),
+    ("stats.win_streak", "Currently on a {}-game winning streak"),
+    ("stats.loss_streak", "Currently on a {}-game losing streak"),
+    ("stats.best_streak", "Best winning streak: {} games"),
+    ("stats.average_move", "Average time per move: {} s"),
+    ("msg.leaderboard_entry", "{}. {}  {}  ({} games)"),
+    ("msg.move_failed", "Move failed: {}"),
+    ("msg.error", "Error: {}"),
+    ("msg.winner", "Game over! Winner: {}"),
+    ("msg.draw", "Game over! It's a draw!"),
+    ("msg.winning_line", "Winning line: {}"),
+    ("msg.turn", "{} to move"),
+    ("msg.player_left", "Player {} disconnected"),
+    ("msg.player_joined", "Player {} ({}) joined the game"),
+    ("msg.server_shutdown", "The server has shut down"),
+    (
+        "msg.game_paused",
+        "{} disconnected. The game is paused; they forfeit if not back within {} s",
+    ),
+    ("msg.game_resumed", "{} reconnected, the game continues"),
+    (
+        "msg.session_transferred",
+        "Your game continues on another device; this connection is closed",
+    ),
+    ("msg.server_notice", "Server notice: {}"),
+    ("msg.kicked", "You were removed from the game: {}"),
+    (
+        "msg.protocol_violation",
+        "Protocol violation ({}), the server closed the connection: {}",
+    ),
+    (
+        "msg.queue_status",
+        "All rooms are full. You are number {} in the queue, about {} min to wait",
+    ),
+    ("msg.time_left", "Time left  Black {}  White {}"),
+    ("msg.own_time_warning", "!! Only {} seconds left !!"),
+    ("msg.opponent_time", "Opponent {} has {} seconds left"),
+    ("msg.sgf", "Game record (SGF):"),
+    ("msg.presence_restored", "{}'s connection has recovered"),
+    ("msg.presence_thinking", "{} is thinking..."),
+    (
+        "msg.presence_degraded",
+        "{}'s connection is unstable, please wait",
+    ),
+    (
+        "msg.game_archived",
+        "Game archived as {}, enter 'replay <id>' to replay it",
+    ),
+    (
+        "msg.replay_loaded",
+        "Replaying game {}: {} (Black) vs {} (White)",
+    ),
+    ("replay.start", "Start position, {} moves in total"),
+    ("replay.move", "Move {}/{}: {} played ({}, {}) after {} s"),
+    ("msg.board_title", "Current board:"),
+    ("input.sending_move", "Sending move: {}"),
+    (
+        "input.confirm_resign",
+        "The game is still in progress. Quitting means resigning. Are you sure? (y/n)",
+    ),
+    ("input.quit_cancelled", "Quit cancelled, the game goes on"),
+    ("input.confirm_move", "Play this move? (y/n)"),
+    ("input.move_cancelled", "Move cancelled"),
+    (
+        "input.bad_coords",
+        "Invalid row/column. Usage: move <row> <col> (0-14) or move H8",
+    ),
+    (
+        "input.bad_command",
+        "Invalid command. Usage: move <row> <col> (0-14) or move H8",
+    ),
+    ("input.read_error", "Input error: {}"),
+    ("input.lang_usage", "Usage: lang <zh|en>"),
+    ("input.lang_switched", "Switched to English"),
+    ("input.top_usage", "Usage: top [count]"),
+    (
+        "input.invite_usage",
+        "Usage: invite <black|white|watch> [minutes]",
+    ),
+    (
+        "input.explore_usage",
+        "Usage: explore <row,col> ..., starting from Black's first move",
+    ),
+    (
+        "input.stats_not_connected",
+        "Not connected yet, cannot show your own profile",
+    ),
+    (
+        "input.no_replay",
+        "No replay loaded, enter 'replay <id>' first",
+    ),
+    ("input.replay_out_of_range", "Out of range, moves are 0-{}"),
+    (
+        "input.config_save_failed",
+        "Failed to save client config: {}",
+    ),
+    ("game.send_username_failed", "Failed to send username: {}"),
+    (
+        "game.authenticated",
+        "Signed in, the game is rated if your opponent is signed in too",
+    ),
+    ("game.auth_failed", "Sign-in failed: {}"),
+    ("game.welcome", "Welcome to Gomoku!"),
+    (
+        "game.waiting_role",
+        "Waiting for the server to assign your color...",
+    ),
+    ("game.listening", "Listening for server messages..."),
+    ("game.parse_failed", "Failed to parse message: {}"),
+    ("game.task_exit", "{} task finished"),
+    ("game.task_error", "{} task error: {}"),
+    ("game.task_read", "Read"),
+    ("game.task_write", "Write"),
+    ("game.task_input", "Input"),
+    ("game.channel_closed", "Channel closed, write task exiting"),
+    (
+        "error.desync",
+        "The local board still disagrees with the server after several resyncs",
+    ),
+    ("error.server_closed", "The server closed the connection"),
+    (
+        "game.help_move",
+        "Enter moves as: move <row> <col> (e.g. move 7 7) or as a coordinate (move H8); in Connect6 both stones of a turn: move 7 7 7 8",
+    ),
+    (
+        "game.help_export",
+        "Enter 'export' to export the game record (SGF)",
+    ),
+    (
+        "game.help_lang",
+        "Enter 'lang en' or 'lang zh' to switch language",
+    ),
+    (
+        "game.help_replay",
+        "Enter 'replay <id>' to replay a game, then next / prev / jump <n>",
+    ),
+    (
+        "game.help_top",
+        "Enter 'top [count]' to show the leaderboard",
+    ),
+    (
+        "game.help_stats",
+        "Enter 'stats [user id]' to show a profile",
+    ),
+    (
+        "game.help_invite",
+        "Enter 'invite <black|white|watch> [minutes]' to create an invitation",
+    ),
+    (
+        "game.help_hint",
+        "Enter 'hint' to ask the computer for a suggestion (limited per game)",
+    ),
+    (
+        "game.help_eval",
+        "Enter 'eval <id>' to show the evaluation graph of a finished game",
+    ),
+    (
+        "game.help_explore",
+        "Enter 'explore 7,7 7,8 ...' to find past games that reached this position",
+    ),
+    (
+        "game.help_info",
+        "Enter 'info' to show the server version and rules",
+    ),
+    (
+        "game.help_preview",
+        "Enter 'preview <row> <col>' or 'preview H8' to see what a move would threaten without playing it",
+    ),
+    ("game.help_quit", "Enter 'quit' to leave the game"),
+    (
+        "watch.help",
+        "Watching: 'list' refreshes, 'watch <n>' spectates, 'next' jumps to the next game, \
+         'region <row> <col> <rows> <cols>' follows a window ('region off' for the full board), \
+         'claim <black|white>' takes over an open seat, 'stop' stops, 'quit' exits",
+    ),
+    ("msg.region_title", "Window: rows {}-{}, columns {}-{}"),
+    ("msg.evaluation", "Move {} Black {} White ({})"),
+    (
+        "msg.evaluation_graph",
+        "Evaluation graph of game {} (from Black's side):",
+    ),
+    (
+        "msg.region_outside",
+        "Outside the window: {} black, {} white stones",
+    ),
+    (
+        "msg.region_last_move",
+        "Last move outside the window: ({}, {})",
+    ),
+    ("msg.game_list", "Live games ({} total):"),
+    (
+        "msg.game_list_entry",
+        "{}. {} (Black) vs {} (White), {} moves",
+    ),
+    ("msg.no_live_games", "No games are being played right now"),
+    (
+        "msg.position_games",
+        "Past games that reached this position ({} in total):",
+    ),
+    (
+        "msg.position_entry",
+        "{}. [{}] {} (Black) vs {} (White), reached at move {}, {}",
+    ),
+    ("msg.position_won", "{} won"),
+    ("msg.position_draw", "draw"),
+    (
+        "msg.position_none",
+        "No past game has reached this position",
+    ),
+    ("msg.server_info", "Server version {}, protocol version {}"),
+    (
+        "msg.protocol_older",
+        "The server speaks an older protocol version {} (client: {}); some features may be unavailable",
+    ),
+    (
+        "msg.server_variant",
+        "{}: {}x{} board, {} in a row wins, {} stone(s) per turn, enabled: {}",
+    ),
+    (
+        "msg.server_time_control",
+        "Time control: {} per player, {} s increment",
+    ),
+    ("msg.server_untimed", "Time control: none"),
+    (
+        "msg.server_limits",
+        "Up to {} concurrent games, {} hints per game",
+    ),
+    ("msg.watching", "Now watching {} (Black) vs {} (White)"),
+    (
+        "msg.invite_created",
+        "Created a {} invitation, valid for {} minutes; start the client with --invite {}",
+    ),
+    (
+        "msg.hint",
+        "Hint: try ({}, {}), evaluation {}, {} hints left this game",
+    ),
+    ("game.press_enter", "Game finished, press Enter to exit..."),
+    ("main.connecting", "Connecting to server: {}"),
+    (
+        "main.ask_username",
+        "Please enter your username (press Enter for a random guest name):",
+    ),
+    (
+        "main.ask_password",
+        "Please enter your password (press Enter to play as an unrated guest):",
+    ),
+    ("main.connected", "Connected to server"),
+    ("main.connect_failed", "Connection failed: {}"),
+    ("main.bye", "Goodbye"),
+    (
+        "config.load_failed",
+        "Failed to read client config {}: {}, using defaults",
+    ),
+    (
+        "notify.command_failed",
+        "Failed to run notification command: {}",
+    ),
+    ("script.loaded", "Loaded script {}"),
+    ("script.load_failed", "Failed to load script {}: {}"),
+    ("script.error", "Script error: {}"),
+    ("script.note", "[script] {}"),
+    ("script.bad_position", "Coordinates cannot be negative: ({}, {})"),
+    (
+        "script.bad_event",
+        "Cannot convert this message for the script: {}",
+    ),
+    (
+        "msg.seat_open",
+        "{}'s seat is open for a spectator to take over (spectators enter 'claim <black|white>')",
+    ),
+    (
+        "game.help_substitutes",
+        "Enter 'substitutes on|off' to let spectators replace a player who leaves (room owner only)",
+    ),
+    ("input.substitutes_usage", "Usage: substitutes <on|off>"),
+    ("msg.game_over_reason", "Reason: {}"),
+    ("reason.five_in_a_row", "line completed"),
+    ("reason.timeout", "time out"),
+    ("reason.resignation", "resignation"),
+    ("reason.disconnect", "player left"),
+    ("reason.draw_agreed", "draw agreed"),
+    ("reason.board_full", "board full"),
+    ("reason.move_limit", "move limit reached"),
+    ("reason.blocked", "no line can be completed"),
+    ("reason.unknown", "unknown reason"),
+    (
+        "msg.draw_offered",
+        "{} offers a draw. Enter 'draw accept' to accept or 'draw decline' to decline",
+    ),
+    ("msg.draw_declined", "{} declined the draw"),
+    (
+        "game.help_draw",
+        "Enter 'draw' to offer a draw, 'draw accept' or 'draw decline' to answer an offer",
+    ),
+    ("input.draw_usage", "Usage: draw [accept|decline]"),
+    (
+        "msg.demo_created",
+        "Demo board is open. Viewers start with --watch and enter 'watch {}'",
+    ),
+    ("msg.demo_comment", "Comment: {}"),
+    ("msg.demo_next", "Variations from here: {}"),
+    ("msg.demo_closed", "The presenter has ended the demo"),
+    (
+        "present.help",
+        "Presenting: 'black|white <row> <col>' places, 'remove <row> <col>' removes, 'clear' empties, \
+         'load <file>' loads a variation tree, 'goto [n ...]' jumps to a variation, \
+         'note <text>' comments, 'quit' ends",
+    ),
+    ("present.load_failed", "Failed to read variation tree {}: {}"),
+    ("game.play_again", "Enter 'n' to play again against a new opponent, or 'quit' to leave"),
+    ("input.finding_opponent", "Looking for a new opponent..."),
+    ("input.not_finished", "The game is not over yet"),
+    ("tui.board", "Board"),
+    ("tui.status", "Game"),
+    ("tui.log", "Messages"),
+    (
+        "tui.keys",
+        "Arrows move the cursor  Enter or a mouse click plays  ':' command  n new opponent  q quit",
+    ),
+    ("tui.confirm_quit", "The game is still on; press q again to resign and quit"),
+    ("tui.you", "You play {}"),
+    ("tui.move", "Move {}"),
+    ("tui.last_move", "Last move ({}, {})"),
+    ("tui.finished", "Game over"),
+    ("tui.game", "Game {}"),
+    (
+        "msg.events_summarized",
+        "The room is too busy; {} updates were merged, current position follows",
+    ),
+    ("game.unfinished_title", "You have {} unfinished games:"),
+    (
+        "game.unfinished_item",
+        "{}. vs {}, you play {}, {} moves played, {} to move  game {}",
+    ),
+    (
+        "game.unfinished_adjourned",
+        "   Saved before a server restart; pick it to continue",
+    ),
+    (
+        "game.unfinished_live",
+        "   In progress; you return to your seat when you connect",
+    ),
+    (
+        "game.unfinished_pick",
+        "Enter a number to continue that game, or press Enter for a new game:",
+    ),
+    ("notation.empty", "No coordinates given, e.g. move 7 7 or move H8"),
+    (
+        "notation.unrecognized",
+        "Cannot read '{}': use '<row> <col>' (e.g. 7 7) or a column letter and row number (e.g. H8)",
+    ),
+    ("notation.missing_column", "Row {} is missing its column"),
+    ("notation.off_board", "{} is off the board"),
+    (
+        "local.started",
+        "Local game, no server: you play {}, computer difficulty {}",
+    ),
+    (
+        "local.help",
+        "Enter move <row> <col> or a coordinate (e.g. H8) to play, 'undo' to take back, 'export' for the SGF record, 'quit' to leave",
+    ),
+    ("local.thinking", "The computer is thinking..."),
+    (
+        "local.hotseat_started",
+        "Local two-player game: {}-line board, {} in a row wins; take turns at this terminal",
+    ),
+    ("local.computer", "Computer"),
+    (
+        "local.finished",
+        "Game over: 'undo' to take back and play on, 'export' for the SGF record, or 'quit'",
+    ),
+    ("local.nothing_to_undo", "There is nothing to undo"),
+    ("local.undone", "Took back {} stone(s)"),
+    ("local.bad_rules", "Invalid rules: {}"),
+    ("local.rules_missing", "--rules needs a JSON argument"),
+    (
+        "local.rules_range",
+        "Play 1-{} stones per turn, win with {}-{} in a row, on a board from the win length up to {}",
+    ),
+    ("reconnect.lost", "Lost the connection to the server, reconnecting..."),
+    ("reconnect.waiting", "Reconnecting in {}s (attempt {}/{})"),
+    ("reconnect.failed", "Reconnect failed: {}"),
+    (
+        "reconnect.restored",
+        "Reconnected to the server, syncing the board",
+    ),
+    ("reconnect.gave_up", "Gave up after {} reconnect attempts"),
+    ("reconnect.not_sent", "Not connected, the message was not sent"),
+    ("code.invalid_input", "Invalid input"),
+    ("code.invalid_position", "That point is off the board"),
+    ("code.position_occupied", "That point is already taken"),
+    ("code.invalid_move", "That move breaks the rules"),
+    ("code.disconnected", "The other player's connection dropped"),
+    ("code.invalid_phase", "Not possible at this stage of the game"),
+    ("code.protocol", "The message broke the protocol"),
+    (
+        "code.unauthorized",
+        "Not allowed, or the token or password is wrong",
+    ),
+    ("code.not_found", "No such game or user"),
+    ("code.unavailable", "Not available right now, try again later"),
+    ("code.internal", "Internal server error"),
+    ("code.unknown", "Unknown error"),
+    ("gui.title", "Gomoku"),
+    ("gui.server", "Server"),
+    ("gui.username", "Username"),
+    ("gui.password", "Password (empty for guests)"),
+    ("gui.connect", "Connect"),
+    ("gui.connecting", "Connecting to the server..."),
+    ("gui.find_opponent", "Play again"),
+    ("gui.resign", "Resign"),
+    ("gui.offer_draw", "Offer a draw"),
+    ("gui.accept_draw", "Accept the draw"),
+    ("gui.lobby", "Lobby"),
+    ("gui.refresh", "Refresh the game list"),
+    ("gui.lobby_entry", "{} vs {}, {} moves"),
+    ("gui.watch", "Watch"),
+    ("gui.chat", "Chat"),
+    ("gui.chat_hint", "Say something and press Enter"),
+    ("gui.send", "Send"),
+    (
+        "gui.command_hint",
+        "Type a command as in the terminal client, e.g. hint, eval, move H8",
+    ),
+    ("msg.chat", "{}: {}"),
+    ("input.chat_usage", "Usage: chat <text>"),
+    (
+        "game.help_chat",
+        "Type 'chat <text>' to talk to your opponent and spectators",
+    ),
+    ("notify.title", "Gomoku"),
+    ("notify.your_turn", "Your move"),
+    ("tui.live_games", "Live games"),
+    (
+        "tui.watch_keys",
+        "Digits watch that game  n next game  r refresh  s stop watching  ':' command  q quit",
+    ),
+    ("tui.player", "{}: {}"),
+    ("tui.evaluation", "Eval {} ({})"),
+    ("gui.evaluation", "Evaluation (Black's view): {}"),
+    (
+        "game.analyze_hint",
+        "Enter 'analyze' to review this game in analysis mode",
+    ),
+    (
+        "game.help_analyze",
+        "After a game, enter 'analyze' for analysis mode and 'analyze off' to leave it",
+    ),
+    ("input.analyze_usage", "Usage: analyze or analyze off"),
+    (
+        "analysis.help",
+        "Analysis: 'next'/'prev'/'jump <move>' step through the game, 'try <row> <col>' plays a what-if move, \
+         'undo' takes it back, 'best' asks the engine for the best continuation, 'analyze off' leaves",
+    ),
+    ("analysis.closed", "Left analysis mode"),
+    ("analysis.position", "Analysis: move {}/{}"),
+    ("analysis.branch", "Analysis: move {}/{} plus {} what-if moves"),
+    ("analysis.best", "Best continuation for {}: {} ({}, {}), evaluation {}"),
+    ("analysis.no_best", "There is nothing left to play in this position"),
+    ("analysis.already_won", "This position has already been won"),
+    ("analysis.nothing_to_undo", "No what-if moves to take back"),
+    ("present.login_required", "Presenting requires a registered username and password"),
+    ("reconnect.no_reply", "The server did not answer"),
+    ("reconnect.game_gone", "The game is gone; not joining a new one"),
+    ("reconnect.queued", "Not connected, the message will be sent after reconnecting"),
+    ("input.confirm_again", "Select the same point again to play there"),
+    ("game.message_skipped", "Skipped a message that could not be parsed: {}"),
+    ("msg.seat_claimed", "You took over {}'s seat; the game goes on"),
+    ("msg.rules_invalid", "The server sent invalid rules ({}); showing a standard 15x15 board"),
+    ("msg.replay_rules_invalid", "Game {} has invalid rules ({}) and cannot be replayed"),
+];
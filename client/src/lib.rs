@@ -1,255 +1,1871 @@
-use chess::{Board, GameMessage, PlayerRole};
-use futures_util::{SinkExt, StreamExt};
-use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tokio::sync::Mutex;
-use tokio::sync::{broadcast, mpsc};
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::WebSocketStream;
-
-pub async fn handle_game_message(msg: GameMessage, board: &mut Board) -> bool {
+use std::time::Instant;
+
+use chess::{
+    Board, ChunkAssembler, GameMessage, GameResult, GameSummary, InviteRole, OutsideSummary,
+    PlayerRole, PlayerStats, PresenceState, Region, RulesConfig, ServerInfo, PROTOCOL_VERSION,
+};
+// 终端客户端自己连服务器、读标准输入；浏览器里的前端只用到下面的状态和消息处理
+#[cfg(feature = "terminal")]
+use {
+    chess::{Capability, Difficulty, GameError, MovePreview, UnfinishedGame},
+    futures_util::{SinkExt, StreamExt},
+    std::collections::VecDeque,
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::sync::Arc,
+    tokio::io::{self, AsyncBufReadExt, BufReader},
+    tokio::sync::Mutex,
+    tokio::sync::{broadcast, mpsc},
+    tokio_tungstenite::connect_async,
+    tokio_tungstenite::tungstenite::Message,
+};
+
+pub mod analysis;
+pub mod config;
+#[cfg(feature = "terminal")]
+pub mod demo;
+pub mod describe;
+pub mod error;
+pub mod i18n;
+#[cfg(feature = "terminal")]
+pub mod local;
+pub mod notation;
+pub mod notify;
+pub mod output;
+#[cfg(feature = "terminal")]
+pub mod reconnect;
+pub mod render;
+pub mod replay;
+#[cfg(feature = "terminal")]
+pub mod script;
+pub mod timer;
+#[cfg(feature = "terminal")]
+pub mod tui;
+#[cfg(feature = "terminal")]
+pub mod watch;
+
+pub use analysis::*;
+pub use config::*;
+#[cfg(feature = "terminal")]
+pub use demo::*;
+pub use describe::*;
+pub use error::*;
+pub use i18n::*;
+#[cfg(feature = "terminal")]
+pub use local::*;
+pub use notation::*;
+pub use notify::*;
+pub use output::*;
+#[cfg(feature = "terminal")]
+pub use reconnect::*;
+pub use render::*;
+pub use replay::*;
+#[cfg(feature = "terminal")]
+pub use script::*;
+pub use timer::*;
+#[cfg(feature = "terminal")]
+pub use tui::*;
+#[cfg(feature = "terminal")]
+pub use watch::*;
+
+// 连续要这么多次整盘局面仍然对不上，就不再自动修复
+pub const MAX_RESYNC_ATTEMPTS: u32 = 3;
+
+// 客户端本地状态
+pub struct ClientState {
+    pub board: Board,
+    pub player_role: Option<PlayerRole>,
+    // 服务器确认的用户名，游客没填时由服务器生成
+    pub username: Option<String>,
+    pub user_id: Option<String>,
+    pub game_id: Option<String>,
+    pub opponent_presence: Option<PresenceState>,
+    pub notifiers: Vec<Box<dyn Notifier>>,
+    pub accessible: bool,
+    pub confirm_moves: bool,
+    pub replay: Option<ReplayPlayer>,
+    // 对局结束后进入的分析模式，落子只下在本地的试验棋盘上
+    pub analysis: Option<Analysis>,
+    // 最近一次查询到的对局列表和正在观战的对局
+    pub game_list: Vec<GameSummary>,
+    pub watching: Option<GameSummary>,
+    // 观战时订阅的棋盘窗口，本地棋盘只有窗口内是准的
+    pub region: Option<Region>,
+    // 还没收齐的分段消息
+    pub chunks: ChunkAssembler,
+    // 发现漏收了落子，需要向服务器要一次整盘局面
+    pub resync: bool,
+    // 连续要了几次整盘局面还没对上
+    pub resync_attempts: u32,
+    #[cfg(feature = "terminal")]
+    pub script: Option<ScriptHost>,
+    // 对局已经结束，可以换个对手再来一盘
+    pub finished: bool,
+    // 黑白双方的剩余时间，收到 TimeUpdate 时对齐，之间由界面调用 tick_clock 走
+    pub clock: Option<LocalClock>,
+    // 观战时最近一次收到的局面评估，黑方视角
+    pub evaluation: Option<i32>,
+    // 发出去还没收到 MoveAck 的落子，重连或者重新同步之后原样重发
+    pub pending_move: Option<GameMessage>,
+}
+
+impl Default for ClientState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientState {
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(),
+            player_role: None,
+            username: None,
+            user_id: None,
+            game_id: None,
+            opponent_presence: None,
+            notifiers: Vec::new(),
+            accessible: false,
+            confirm_moves: false,
+            replay: None,
+            analysis: None,
+            game_list: Vec::new(),
+            watching: None,
+            region: None,
+            chunks: ChunkAssembler::new(),
+            resync: false,
+            resync_attempts: 0,
+            #[cfg(feature = "terminal")]
+            script: None,
+            finished: false,
+            clock: None,
+            evaluation: None,
+            pending_move: None,
+        }
+    }
+
+    // 界面每次重画前调用：给轮到的一方扣掉经过的时间，自己的时间刚跌破阈值时醒目提示并响铃
+    pub fn tick_clock(&mut self, now: Instant) {
+        if self.finished {
+            return;
+        }
+        let Some(clock) = self.clock.as_mut() else {
+            return;
+        };
+        clock.tick(self.board.current_player, now);
+        let Some(own) = self.player_role else {
+            return;
+        };
+        if let Some(remaining_secs) = clock.low_time_warning(own) {
+            say!("\n{}", t!("msg.own_time_warning", remaining_secs));
+            for notifier in &self.notifiers {
+                notifier.time_warning(remaining_secs);
+            }
+        }
+    }
+
+    // 把事件交给每个提醒钩子
+    pub fn notify(&self, event: ClientEvent) {
+        for notifier in &self.notifiers {
+            notifier.on_event(&event);
+        }
+    }
+
+    pub fn with_config(config: &ClientConfig) -> Self {
+        let mut state = Self::new();
+        state.notifiers = notifiers_from_config(config);
+        state.accessible = config.accessible;
+        state.confirm_moves = config.confirm_moves;
+        #[cfg(feature = "terminal")]
+        if let Some(path) = &config.script {
+            match ScriptHost::from_file(path) {
+                Ok(script) => {
+                    say!("{}", t!("script.loaded", path));
+                    state.script = Some(script);
+                }
+                Err(e) => eprintln!("{}", t!("script.load_failed", path, e)),
+            }
+        }
+        state
+    }
+
+    // 把刚处理完的消息交给脚本，返回脚本要发给服务器的请求；批注直接打印，脚本出错只提示不中断对局
+    #[cfg(feature = "terminal")]
+    pub fn run_script(&mut self, msg: &GameMessage) -> Vec<GameMessage> {
+        let Some(mut script) = self.script.take() else {
+            return Vec::new();
+        };
+        let actions = script.on_event(msg, self).unwrap_or_else(|e| {
+            eprintln!("{}", t!("script.error", e));
+            Vec::new()
+        });
+        self.script = Some(script);
+        actions
+            .into_iter()
+            .filter_map(|action| match action {
+                ScriptAction::Play { row, col } => Some(self.move_request(row, col)),
+                ScriptAction::Note(text) => {
+                    say!("{}", t!("script.note", text));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // 带上对局编号和步数的落子请求，步数就是棋盘上已有的棋子数。
+    // 请求记为待确认；同一步再请求时沿用上次的随机数，服务器靠它识别重发
+    pub fn move_request(&mut self, row: usize, col: usize) -> GameMessage {
+        self.request_move((row, col), None)
+    }
+
+    // 六子棋一回合的两子一起提交
+    pub fn pair_request(&mut self, first: (usize, usize), second: (usize, usize)) -> GameMessage {
+        self.request_move(first, Some(second))
+    }
+
+    fn request_move(
+        &mut self,
+        (row, col): (usize, usize),
+        second: Option<(usize, usize)>,
+    ) -> GameMessage {
+        let game_id = self.game_id.clone().unwrap_or_default();
+        let move_seq = self.board.cells.iter().flatten().flatten().count();
+        let client_nonce = match &self.pending_move {
+            Some(GameMessage::Move {
+                row: pending_row,
+                col: pending_col,
+                game_id: pending_game,
+                move_seq: pending_seq,
+                client_nonce,
+                second: pending_second,
+            }) if (*pending_row, *pending_col, *pending_seq, *pending_second)
+                == (row, col, move_seq, second)
+                && *pending_game == game_id =>
+            {
+                *client_nonce
+            }
+            _ => rand::random(),
+        };
+        let request = GameMessage::Move {
+            row,
+            col,
+            game_id,
+            move_seq,
+            client_nonce,
+            second,
+        };
+        self.pending_move = Some(request.clone());
+        request
+    }
+
+    // 重连或者重新同步之后还要重发的落子：还是这盘棋、棋盘上还没有这一步、仍然轮到自己
+    pub fn pending_resend(&self) -> Option<GameMessage> {
+        let request = self.pending_move.as_ref()?;
+        let GameMessage::Move {
+            game_id, move_seq, ..
+        } = request
+        else {
+            return None;
+        };
+        let stones = self.board.cells.iter().flatten().flatten().count();
+        let waiting = !self.finished
+            && self.game_id.as_ref() == Some(game_id)
+            && stones == *move_seq
+            && self.player_role == Some(self.board.current_player);
+        waiting.then(|| request.clone())
+    }
+
+    // 自己坐在棋盘前、对局还没结束，这时离开算认输
+    pub fn is_playing(&self) -> bool {
+        self.player_role.is_some() && self.game_id.is_some() && !self.finished
+    }
+
+    // 处理完一条消息后调用：需要向服务器要整盘局面时返回 true，
+    // 要了几次之后每一步仍然对不上时返回 Desync
+    pub fn take_resync(&mut self) -> Result<bool, ClientError> {
+        if !std::mem::take(&mut self.resync) {
+            return Ok(false);
+        }
+        self.resync_attempts += 1;
+        if self.resync_attempts > MAX_RESYNC_ATTEMPTS {
+            return Err(ClientError::Desync);
+        }
+        Ok(true)
+    }
+}
+
+// 解析服务器发来的一帧。更新的服务器可能发来这个客户端不认识的消息，提示一下跳过，不断开连接
+pub fn parse_frame(text: &str) -> Option<GameMessage> {
+    match serde_json::from_str(text) {
+        Ok(msg) => Some(msg),
+        Err(e) => {
+            say!("\n{}", t!("game.message_skipped", e));
+            None
+        }
+    }
+}
+
+pub fn handle_game_message(msg: GameMessage, state: &mut ClientState) -> bool {
+    // 大消息分段到达，收齐后按原消息处理
+    let msg = match msg {
+        GameMessage::Chunk {
+            id,
+            index,
+            total,
+            data,
+        } => match state.chunks.push(id, index, total, data) {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => {
+                eprintln!("{}", t!("game.parse_failed", e));
+                return false;
+            }
+            None => return false,
+        },
+        msg => msg,
+    };
+    let accessible = state.accessible;
+    let region = state.region;
+    let board = &mut state.board;
     match msg {
-        GameMessage::ConnectRequest { username } => {
-            println!("\n正在连接到游戏，用户名: {}...", username);
+        GameMessage::ConnectRequest { username, .. } => {
+            say!("\n{}", t!("msg.connecting", username));
             false
         }
         GameMessage::ConnectResponse {
             username,
             player_role,
+            rating,
+            user_id,
+            game_id,
+            protocol_version,
+            rules,
+            ..
         } => {
-            println!(
-                "\n已连接到游戏，欢迎 {}! 你的角色是: {:?}",
-                username, player_role
+            say!(
+                "\n{}",
+                t!("msg.connected", username, role_name(player_role), rating)
             );
+            // 服务器更旧时按它的版本通信，它不认识的命令会被拒绝
+            if protocol_version < PROTOCOL_VERSION {
+                say!(
+                    "{}",
+                    t!("msg.protocol_older", protocol_version, PROTOCOL_VERSION)
+                );
+            }
+            // 棋盘数组只有 15 路，超出范围的规则按默认规则显示，不能拿来下标
+            board.rules = match rules.validate() {
+                Ok(()) => rules,
+                Err(e) => {
+                    say!("{}", t!("msg.rules_invalid", e));
+                    RulesConfig::default()
+                }
+            };
+            state.finished = false;
+            state.analysis = None;
+            state.player_role = Some(player_role);
+            state.username = Some(username);
+            state.user_id = Some(user_id);
+            state.game_id = Some(game_id);
             false
         }
-        GameMessage::Move { row, col } => {
-            if let Err(e) = board.make_move(row, col) {
-                println!("移动失败: {}", e);
+        GameMessage::Move { .. } => false,
+        GameMessage::Resync => false,
+        GameMessage::MoveApplied {
+            row,
+            col,
+            by,
+            next_player,
+            move_number,
+        } => {
+            if !board.rules.contains(row as i32, col as i32) {
+                state.resync = true;
+                return false;
+            }
+            let stones = board.cells.iter().flatten().flatten().count();
+            // 已经下过的一步又收到一次，不重复落子
+            if move_number <= stones && board.cells[row][col] == Some(by) {
+                return false;
+            }
+            // 订阅了窗口时本地只有窗口内的棋子，数不出漏收
+            if region.is_some() {
+                board.cells[row][col] = Some(by);
+                board.current_player = next_player;
+                board.rehash();
+            } else if move_number != stones + 1
+                || board.current_player != by
+                || board.make_move(row, col).is_err()
+            {
+                // 漏收了消息：先照服务器说的摆上，再要一次整盘局面
+                board.cells[row][col] = Some(by);
+                board.current_player = next_player;
+                board.rehash();
+                state.resync = true;
             } else {
-                board.display();
+                state.resync_attempts = 0;
             }
+            if accessible {
+                say!("\n{}", describe_move(board, by, row, col));
+            } else if let Some(region) = region {
+                display_region(board, region);
+            } else {
+                display_board(board);
+            }
+            state.notify(ClientEvent::MoveMade { by, row, col });
             false
         }
         GameMessage::Error(msg) => {
-            println!("\n错误: {}", msg);
+            say!("\n{}", t!("msg.error", error_text(&msg)));
             false
         }
-        GameMessage::GameOver { winner } => {
+        GameMessage::GameOver {
+            winner,
+            winning_line,
+            reason,
+        } => {
             match winner {
-                Some(role) => println!("\n游戏结束！胜利者是: {:?}", role),
-                None => println!("\n游戏结束！平局！"),
+                Some(role) => say!("\n{}", t!("msg.winner", role_name(role))),
+                None => say!("\n{}", t!("msg.draw")),
             }
+            if let Some(reason) = reason {
+                say!("{}", t!("msg.game_over_reason", reason_name(reason)));
+            }
+            if !winning_line.is_empty() {
+                let stones: Vec<String> = winning_line
+                    .iter()
+                    .map(|(row, col)| format!("({}, {})", row + 1, col + 1))
+                    .collect();
+                say!("{}", t!("msg.winning_line", stones.join(" ")));
+                if !accessible {
+                    print_board(board, &winning_line);
+                }
+            }
+            state.finished = true;
+            state.notify(ClientEvent::GameOver { winner });
             true
         }
         GameMessage::Status {
             board: new_board,
             current_player,
+            move_number,
+            last_move,
+            moves,
         } => {
-            board.cells = new_board;
+            let changed = board.cells != *new_board || board.current_player != current_player;
+            // 服务器只在没有订阅窗口时发整盘局面
+            state.region = None;
+            board.cells = *new_board;
             board.current_player = current_player;
-            board.display();
+            // 带着落子列表时照单全收。每步之后的 Status 和窗口快照不带列表：
+            // 正好接在本地记录后面的一步补上，接不上时保留本地记录，再要一次整盘局面
+            let placed = last_move.and_then(|(row, col)| {
+                let player = (*board.cells.get(row)?.get(col)?)?;
+                Some((row, col, player))
+            });
+            if !moves.is_empty() || move_number == 0 {
+                board.moves = moves;
+            } else if let Some((row, col, player)) =
+                placed.filter(|_| board.moves.len() + 1 == move_number)
+            {
+                board.record_move(player, row, col);
+            } else if board.moves.len() != move_number || board.last_move() != last_move {
+                state.resync = true;
+            }
+            board.rehash();
+            if !accessible {
+                display_board(board);
+            } else if changed {
+                // 只在局面和本地不一致时朗读摘要，避免每步重复
+                say!("\n{}", describe_board(board));
+            }
             false
         }
         GameMessage::TurnNotification { player } => {
-            println!("\n轮到玩家 {:?} 移动", player);
+            say!("\n{}", t!("msg.turn", role_name(player)));
+            if state.player_role == Some(player) {
+                state.notify(ClientEvent::YourTurn);
+            }
             false
         }
         GameMessage::PlayerDisconnected { player } => {
-            println!("\n玩家 {:?} 已断开连接", player);
+            say!("\n{}", t!("msg.player_left", role_name(player)));
             false
         }
         GameMessage::PlayerConnected { player, username } => {
-            println!("\n玩家 {} ({:?}) 已加入游戏", username, player);
+            say!("\n{}", t!("msg.player_joined", username, role_name(player)));
             false
         }
         GameMessage::ServerShutdown => {
-            println!("\n服务器已关闭");
+            say!("\n{}", t!("msg.server_shutdown"));
+            true
+        }
+        GameMessage::GamePaused { player, grace_secs } => {
+            say!("\n{}", t!("msg.game_paused", role_name(player), grace_secs));
+            false
+        }
+        GameMessage::GameResumed { player } => {
+            say!("\n{}", t!("msg.game_resumed", role_name(player)));
+            false
+        }
+        GameMessage::SessionTransferred => {
+            say!("\n{}", t!("msg.session_transferred"));
+            true
+        }
+        GameMessage::ServerNotice { message } => {
+            say!("\n{}", t!("msg.server_notice", message));
+            false
+        }
+        GameMessage::Kicked { reason } => {
+            say!("\n{}", t!("msg.kicked", reason));
             true
         }
+        GameMessage::ProtocolViolation { violation, message } => {
+            say!(
+                "\n{}",
+                t!(
+                    "msg.protocol_violation",
+                    format!("{:?}", violation),
+                    message
+                )
+            );
+            true
+        }
+        GameMessage::TimeUpdate { black_ms, white_ms } => {
+            let now = Instant::now();
+            match state.clock.as_mut() {
+                Some(clock) => clock.sync(black_ms, white_ms, now),
+                None => state.clock = Some(LocalClock::new(black_ms, white_ms, now)),
+            }
+            say!(
+                "\n{}",
+                t!(
+                    "msg.time_left",
+                    format_clock(black_ms),
+                    format_clock(white_ms)
+                )
+            );
+            false
+        }
+        GameMessage::SetPresence { .. } => false,
+        GameMessage::ExportGame => false,
+        GameMessage::GameRecord { sgf } => {
+            say!("\n{}\n{}", t!("msg.sgf"), sgf);
+            false
+        }
+        GameMessage::GameArchived { game_id } => {
+            say!("\n{}", t!("msg.game_archived", game_id));
+            false
+        }
+        GameMessage::ReplayRequest { .. } => false,
+        GameMessage::SetNarration { .. } => false,
+        GameMessage::LeaderboardRequest { .. } => false,
+        GameMessage::Register { .. } | GameMessage::Login { .. } => false,
+        GameMessage::AuthToken { .. } => false,
+        GameMessage::SetFeature { .. } | GameMessage::Features { .. } => false,
+        GameMessage::SetTelemetry { .. } | GameMessage::TelemetryStatus { .. } => false,
+        GameMessage::GetStats { .. } => false,
+        GameMessage::MoveAck { game_id, move_seq } => {
+            // 服务器收到了，不用再重发
+            if matches!(
+                &state.pending_move,
+                Some(GameMessage::Move { game_id: pending_game, move_seq: pending_seq, .. })
+                    if *pending_game == game_id && *pending_seq == move_seq
+            ) {
+                state.pending_move = None;
+            }
+            false
+        }
+        GameMessage::ListGames { .. } | GameMessage::Watch { .. } => false,
+        GameMessage::StopWatching => false,
+        GameMessage::CreateInvite { .. } => false,
+        GameMessage::AllowSubstitutes { .. } | GameMessage::ClaimSeat { .. } => false,
+        // 接替了座位，不再观战，随后的 ConnectResponse 给出自己的执子颜色
+        GameMessage::SeatClaimed { role, .. } => {
+            state.watching = None;
+            say!("\n{}", t!("msg.seat_claimed", role_name(role)));
+            false
+        }
+        GameMessage::SeatOpen { role, .. } => {
+            say!("\n{}", t!("msg.seat_open", role_name(role)));
+            false
+        }
+        GameMessage::HintRequest => false,
+        GameMessage::Resign | GameMessage::Goodbye | GameMessage::FindOpponent => false,
+        GameMessage::OfferDraw | GameMessage::AnswerDraw { .. } => false,
+        GameMessage::DrawOffered { player } => {
+            say!("\n{}", t!("msg.draw_offered", role_name(player)));
+            false
+        }
+        GameMessage::DrawDeclined { player } => {
+            say!("\n{}", t!("msg.draw_declined", role_name(player)));
+            false
+        }
+        GameMessage::SubscribeRegion { .. } => false,
+        GameMessage::EvaluateRequest { .. } => false,
+        GameMessage::PositionSearch { .. } => false,
+        GameMessage::PositionGames(found) => {
+            if found.games.is_empty() {
+                say!("\n{}", t!("msg.position_none"));
+            } else {
+                say!("\n{}", t!("msg.position_games", found.total));
+                for (i, entry) in found.games.iter().enumerate() {
+                    let game = &entry.game;
+                    let outcome = match game.result {
+                        Some(GameResult::Black) => t!("msg.position_won", game.black),
+                        Some(GameResult::White) => t!("msg.position_won", game.white),
+                        _ => t!("msg.position_draw"),
+                    };
+                    say!(
+                        "{}",
+                        t!(
+                            "msg.position_entry",
+                            i + 1,
+                            game.id,
+                            game.black,
+                            game.white,
+                            entry.ply,
+                            outcome
+                        )
+                    );
+                }
+            }
+            false
+        }
+        GameMessage::ServerInfoRequest => false,
+        GameMessage::CreateDemo { .. } | GameMessage::DemoEdit { .. } => false,
+        GameMessage::DemoCreated { demo_id } => {
+            say!("\n{}", t!("msg.demo_created", demo_id));
+            false
+        }
+        GameMessage::DemoPosition { comment, next, .. } => {
+            if !comment.is_empty() {
+                say!("{}", t!("msg.demo_comment", comment));
+            }
+            if !next.is_empty() {
+                let next: Vec<String> = next
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (row, col))| format!("{}: ({}, {})", i, row, col))
+                    .collect();
+                say!("{}", t!("msg.demo_next", next.join(" ")));
+            }
+            false
+        }
+        GameMessage::DemoClosed { .. } => {
+            say!("\n{}", t!("msg.demo_closed"));
+            true
+        }
+        GameMessage::ServerInfo(info) => {
+            print_server_info(&info);
+            false
+        }
+        GameMessage::Evaluation { move_seq, score } => {
+            state.evaluation = Some(score);
+            say!(
+                "{}",
+                t!("msg.evaluation", move_seq + 1, evaluation_bar(score), score)
+            );
+            false
+        }
+        GameMessage::EvaluationGraph { game_id, scores } => {
+            say!("\n{}", t!("msg.evaluation_graph", game_id));
+            for (i, &score) in scores.iter().enumerate() {
+                say!(
+                    "{}",
+                    t!("msg.evaluation", i + 1, evaluation_bar(score), score)
+                );
+            }
+            false
+        }
+        GameMessage::RegionUpdate {
+            region,
+            cells,
+            current_player,
+            outside,
+        } => {
+            for (r, line) in cells.iter().enumerate() {
+                for (c, &cell) in line.iter().enumerate() {
+                    if let Some(slot) = board
+                        .cells
+                        .get_mut(region.top + r)
+                        .and_then(|row| row.get_mut(region.left + c))
+                    {
+                        *slot = cell;
+                    }
+                }
+            }
+            board.current_player = current_player;
+            board.rehash();
+            state.region = Some(region);
+            if !accessible {
+                display_region(board, region);
+            }
+            print_outside(&outside);
+            false
+        }
+        GameMessage::RegionSummary {
+            current_player,
+            outside,
+        } => {
+            board.current_player = current_player;
+            print_outside(&outside);
+            false
+        }
+        // 当前局面随后补发
+        GameMessage::EventsSummarized { skipped } => {
+            say!("\n{}", t!("msg.events_summarized", skipped));
+            false
+        }
+        GameMessage::Hint {
+            row,
+            col,
+            score,
+            hints_left,
+        } => {
+            say!("\n{}", t!("msg.hint", row + 1, col + 1, score, hints_left));
+            false
+        }
+        GameMessage::Chunk { .. } => false,
+        GameMessage::InviteCreated {
+            token,
+            role,
+            expires_at,
+        } => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default();
+            let minutes = (expires_at - now).max(0) / 60;
+            let role = match role {
+                InviteRole::Seat(role) => role_name(role),
+                InviteRole::Spectator => tr("role.spectator"),
+            };
+            say!("\n{}", t!("msg.invite_created", role, minutes, token));
+            false
+        }
+        GameMessage::GameList { page } => {
+            if page.games.is_empty() {
+                say!("\n{}", t!("msg.no_live_games"));
+            } else {
+                say!("\n{}", t!("msg.game_list", page.total));
+                for (i, game) in page.games.iter().enumerate() {
+                    say!(
+                        "{}",
+                        t!(
+                            "msg.game_list_entry",
+                            i + 1,
+                            player_label(&game.black, game.black_rating),
+                            player_label(&game.white, game.white_rating),
+                            game.move_count
+                        )
+                    );
+                }
+            }
+            state.game_list = page.games;
+            false
+        }
+        GameMessage::Watching { game } => {
+            say!(
+                "\n{}",
+                t!(
+                    "msg.watching",
+                    player_label(&game.black, game.black_rating),
+                    player_label(&game.white, game.white_rating)
+                )
+            );
+            *board = Board::new();
+            state.evaluation = None;
+            state.watching = Some(game);
+            false
+        }
+        GameMessage::Stats { username, stats } => {
+            print_stats(&username, &stats);
+            false
+        }
+        GameMessage::QueueStatus {
+            position,
+            estimated_wait_secs,
+        } => {
+            say!(
+                "\n{}",
+                t!(
+                    "msg.queue_status",
+                    position,
+                    estimated_wait_secs.div_ceil(60)
+                )
+            );
+            false
+        }
+        GameMessage::Leaderboard { entries } => {
+            say!("\n{}", t!("msg.leaderboard"));
+            for (rank, entry) in entries.iter().enumerate() {
+                say!(
+                    "{}",
+                    t!(
+                        "msg.leaderboard_entry",
+                        rank + 1,
+                        entry.username,
+                        entry.rating,
+                        entry.games
+                    )
+                );
+            }
+            false
+        }
+        GameMessage::Narration { text } => {
+            say!("\n{}", text);
+            false
+        }
+        GameMessage::Replay { game } => {
+            if let Err(e) = game.rules.validate() {
+                say!("\n{}", t!("msg.replay_rules_invalid", game.id, e));
+                return false;
+            }
+            say!(
+                "\n{}",
+                t!("msg.replay_loaded", game.id, game.black, game.white)
+            );
+            if let Some(reason) = game.reason {
+                say!("{}", t!("msg.game_over_reason", reason_name(reason)));
+            }
+            let replay = ReplayPlayer::new(game);
+            replay.show();
+            state.replay = Some(replay);
+            false
+        }
+        GameMessage::Presence {
+            player,
+            state: presence,
+        } => {
+            let previous = state.opponent_presence.replace(presence);
+            match presence {
+                PresenceState::Idle => {
+                    if previous == Some(PresenceState::ConnectionDegraded) {
+                        say!("\n{}", t!("msg.presence_restored", role_name(player)));
+                    }
+                }
+                PresenceState::Thinking => {
+                    say!("\n{}", t!("msg.presence_thinking", role_name(player)))
+                }
+                PresenceState::ConnectionDegraded => {
+                    say!("\n{}", t!("msg.presence_degraded", role_name(player)))
+                }
+            }
+            false
+        }
+        GameMessage::SendChat { .. } => false,
+        GameMessage::Chat { from, text } => {
+            say!("\n{}", t!("msg.chat", from, text));
+            if state.username.as_deref() != Some(from.as_str()) {
+                state.notify(ClientEvent::ChatReceived { from, text });
+            }
+            false
+        }
+        GameMessage::TimeWarning {
+            player,
+            remaining_secs,
+        } => {
+            if state.player_role == Some(player) {
+                // 自己的时间：醒目显示并触发提醒钩子
+                say!("\n!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+                say!("{}", t!("msg.own_time_warning", remaining_secs));
+                say!("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+                // 本地棋钟跌破阈值时已经响过铃，服务器随后在阈值以内的提醒不再重复
+                let rung = state.clock.is_some_and(|clock| clock.warned())
+                    && remaining_secs * 1000 <= LOW_TIME_MS;
+                if !rung {
+                    for notifier in &state.notifiers {
+                        notifier.time_warning(remaining_secs);
+                    }
+                }
+            } else {
+                say!(
+                    "\n{}",
+                    t!("msg.opponent_time", role_name(player), remaining_secs)
+                );
+            }
+            false
+        }
+    }
+}
+
+// 客户端自己绘制棋盘，标题随语言切换
+pub fn display_board(board: &Board) {
+    print_board(board, &[]);
+}
+
+// highlight 里的棋子是连成五子的一线，和最后一手一起标出。
+// 终端界面自己画棋盘，这时不打印
+fn print_board(board: &Board, highlight: &[(usize, usize)]) {
+    if output_captured() {
+        return;
+    }
+    say!("\n{}", t!("msg.board_title"));
+    print!("{}", renderer().render(board, highlight));
+}
+
+// 只画订阅的窗口，行列号和整盘棋一致
+fn display_region(board: &Board, region: Region) {
+    if output_captured() {
+        return;
+    }
+    say!(
+        "\n{}",
+        t!(
+            "msg.region_title",
+            region.top,
+            region.top + region.rows - 1,
+            region.left,
+            region.left + region.cols - 1
+        )
+    );
+    print!(
+        "{}",
+        renderer().render_area(
+            board,
+            (region.top, region.left),
+            (region.rows, region.cols),
+            &[]
+        )
+    );
+}
+
+// 评估条：左边 # 的多少表示黑方优势，正中间是均势
+pub fn evaluation_bar(score: i32) -> String {
+    const WIDTH: i32 = 20;
+    const RANGE: i32 = 1_000;
+    let black = (score.clamp(-RANGE, RANGE) + RANGE) * WIDTH / (2 * RANGE);
+    format!(
+        "[{}{}]",
+        "#".repeat(black as usize),
+        "-".repeat((WIDTH - black) as usize)
+    )
+}
+
+fn print_outside(outside: &OutsideSummary) {
+    say!(
+        "{}",
+        t!(
+            "msg.region_outside",
+            outside.black_stones,
+            outside.white_stones
+        )
+    );
+    if let Some((row, col)) = outside.last_move {
+        say!("{}", t!("msg.region_last_move", row, col));
+    }
+}
+
+pub fn print_stats(username: &str, stats: &PlayerStats) {
+    say!("\n{}", t!("stats.title", username));
+    say!(
+        "{}",
+        t!(
+            "stats.record",
+            stats.games,
+            stats.wins,
+            stats.losses,
+            stats.draws
+        )
+    );
+    match stats.current_streak {
+        n if n > 0 => say!("{}", t!("stats.win_streak", n)),
+        n if n < 0 => say!("{}", t!("stats.loss_streak", -n)),
+        _ => {}
+    }
+    say!("{}", t!("stats.best_streak", stats.best_streak));
+    if let Some(ms) = stats.average_move_ms() {
+        say!(
+            "{}",
+            t!("stats.average_move", format!("{:.1}", ms as f64 / 1000.0))
+        );
+    }
+}
+
+fn print_server_info(info: &ServerInfo) {
+    say!(
+        "\n{}",
+        t!("msg.server_info", info.version, info.protocol_version)
+    );
+    for variant in &info.variants {
+        let features: Vec<String> = variant
+            .features
+            .iter()
+            .map(|feature| format!("{:?}", feature).to_lowercase())
+            .collect();
+        say!(
+            "{}",
+            t!(
+                "msg.server_variant",
+                format!("{:?}", variant.game_type).to_lowercase(),
+                variant.board_size,
+                variant.win_length,
+                variant.stones_per_turn,
+                features.join(", ")
+            )
+        );
+    }
+    match info.time_control {
+        Some(tc) => say!(
+            "{}",
+            t!(
+                "msg.server_time_control",
+                format_clock(tc.main_time_secs * 1000),
+                tc.increment_secs
+            )
+        ),
+        None => say!("{}", t!("msg.server_untimed")),
+    }
+    say!(
+        "{}",
+        t!("msg.server_limits", info.max_rooms, info.hints_per_game)
+    );
+}
+
+// 运行时切换语言并写回客户端配置
+#[cfg(feature = "terminal")]
+fn switch_lang(code: &str) {
+    let Some(new_lang) = Lang::parse(code) else {
+        say!("{}", t!("input.lang_usage"));
+        return;
+    };
+    set_lang(new_lang);
+    let mut config = ClientConfig::load();
+    config.lang = Some(new_lang);
+    if let Err(e) = config.save() {
+        eprintln!("{}", t!("input.config_save_failed", e));
+    }
+    say!("{}", t!("input.lang_switched"));
+}
+
+#[cfg(feature = "terminal")]
+const DEFAULT_LEADERBOARD_SIZE: usize = 10;
+
+pub fn format_clock(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+// 对局列表里的玩家：有等级分时跟在用户名后面
+pub fn player_label(name: &str, rating: Option<i32>) -> String {
+    match rating {
+        Some(rating) => format!("{} [{}]", name, rating),
+        None => name.to_string(),
+    }
+}
+
+// 回放命令只操作本地状态，不需要和服务器通信
+#[cfg(feature = "terminal")]
+async fn handle_replay_command(parts: &[&str], state: &Arc<Mutex<ClientState>>) {
+    let mut state = state.lock().await;
+    let Some(replay) = state.replay.as_mut() else {
+        say!("{}", t!("input.no_replay"));
+        return;
+    };
+    let moved = match parts {
+        ["next"] => replay.step_forward(),
+        ["prev"] => replay.step_back(),
+        ["jump", n] => match n.parse::<usize>() {
+            Ok(n) => replay.jump(n),
+            Err(_) => false,
+        },
+        _ => false,
+    };
+    if moved {
+        replay.show();
+    } else {
+        say!("{}", t!("input.replay_out_of_range", replay.len()));
+    }
+}
+
+// 分析模式下的命令：next/prev/jump 沿棋谱翻看，try（或 move）试下一子，undo 撤回试下的子，
+// best 请本地引擎给出最佳续着
+#[cfg(feature = "terminal")]
+async fn handle_analysis_command(parts: &[&str], state: &Arc<Mutex<ClientState>>) {
+    let mut state = state.lock().await;
+    let size = state.board.rules.board_size;
+    let Some(analysis) = state.analysis.as_mut() else {
+        return;
+    };
+    let changed = match parts {
+        ["next"] => analysis.step_forward(),
+        ["prev"] => analysis.step_back(),
+        ["jump", n] => n.parse::<usize>().is_ok_and(|n| analysis.jump(n)),
+        ["undo"] if !analysis.undo() => {
+            say!("{}", t!("analysis.nothing_to_undo"));
+            return;
+        }
+        ["undo"] => true,
+        [command, args @ ..] if matches!(*command, "try" | "move") => {
+            match parse_points(args, size).as_deref() {
+                Ok(&[(row, col)]) => match analysis.try_move(row, col) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        say!("{}", t!("msg.error", e));
+                        return;
+                    }
+                },
+                Ok(_) => {
+                    say!("{}", t!("input.bad_coords"));
+                    return;
+                }
+                Err(e) => {
+                    say!("{}", e);
+                    return;
+                }
+            }
+        }
+        ["best"] => {
+            // 搜索放到阻塞线程里，不占着客户端状态
+            let analysis = analysis.clone();
+            drop(state);
+            let player = analysis.board().current_player;
+            let search =
+                tokio::task::spawn_blocking(move || analysis.best_move(Difficulty::Hard.budget()));
+            match search.await.ok().flatten() {
+                Some(best) => say!(
+                    "{}",
+                    t!(
+                        "analysis.best",
+                        role_name(player),
+                        format_point(best.row, best.col, size).unwrap_or_default(),
+                        best.row,
+                        best.col,
+                        best.score
+                    )
+                ),
+                None => say!("{}", t!("analysis.no_best")),
+            }
+            return;
+        }
+        _ => {
+            say!("{}", t!("analysis.help"));
+            return;
+        }
+    };
+    if changed {
+        analysis.show();
+    } else {
+        say!("{}", t!("input.replay_out_of_range", analysis.len()));
+    }
+}
+
+// explore 7,7 7,8 ...，从黑棋第一手开始的落子
+pub fn position_search_request(args: &[&str]) -> Option<GameMessage> {
+    let moves = args
+        .iter()
+        .map(|arg| {
+            let (row, col) = arg.split_once(',')?;
+            Some((row.parse().ok()?, col.parse().ok()?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(GameMessage::PositionSearch { moves })
+}
+
+// invite <black|white|watch> [分钟]，不写有效期时由服务器决定
+pub fn invite_request(args: &[&str]) -> Option<GameMessage> {
+    let role = match args.first()?.to_ascii_lowercase().as_str() {
+        "black" => InviteRole::Seat(PlayerRole::Black),
+        "white" => InviteRole::Seat(PlayerRole::White),
+        "watch" => InviteRole::Spectator,
+        _ => return None,
+    };
+    let ttl_secs = match args.get(1) {
+        Some(minutes) => Some(minutes.parse::<u64>().ok()?.checked_mul(60)?),
+        None => None,
+    };
+    if args.len() > 2 {
+        return None;
+    }
+    Some(GameMessage::CreateInvite { role, ttl_secs })
+}
+
+// 登录后列出没下完的对局让玩家选，返回要恢复的停服前对局。
+// 进行中的对局连接后服务器自动让玩家回到座位，不用带编号
+#[cfg(feature = "terminal")]
+async fn choose_unfinished(unfinished: &[UnfinishedGame]) -> Option<String> {
+    if unfinished.is_empty() {
+        return None;
+    }
+    say!("{}", t!("game.unfinished_title", unfinished.len()));
+    for (i, game) in unfinished.iter().enumerate() {
+        say!(
+            "{}",
+            t!(
+                "game.unfinished_item",
+                i + 1,
+                game.opponent,
+                role_name(game.color),
+                game.move_number,
+                role_name(game.to_move),
+                game.game_id
+            )
+        );
+        if let Some((black_ms, white_ms)) = game.clock {
+            say!(
+                "   {}",
+                t!(
+                    "msg.time_left",
+                    format_clock(black_ms),
+                    format_clock(white_ms)
+                )
+            );
+        }
+        if game.adjourned {
+            say!("{}", t!("game.unfinished_adjourned"));
+        } else {
+            say!("{}", t!("game.unfinished_live"));
+        }
+    }
+    say!("{}", t!("game.unfinished_pick"));
+    let mut answer = String::new();
+    let _ = BufReader::new(io::stdin()).read_line(&mut answer).await;
+    let choice: usize = answer.trim().parse().ok()?;
+    let game = unfinished.get(choice.checked_sub(1)?)?;
+    game.adjourned.then(|| game.game_id.clone())
+}
+
+// 正常退出：对局进行中先确认认输，再告诉服务器要离开并关闭连接。取消退出时返回 false。
+// 没有 reader 时调用方已经确认过
+#[cfg(feature = "terminal")]
+async fn quit(
+    tx: &mpsc::Sender<Message>,
+    state: &Arc<Mutex<ClientState>>,
+    reader: Option<&mut BufReader<io::Stdin>>,
+) -> bool {
+    if state.lock().await.is_playing() {
+        if let Some(reader) = reader {
+            say!("{}", t!("input.confirm_resign"));
+            let mut answer = String::new();
+            let _ = reader.read_line(&mut answer).await;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                say!("{}", t!("input.quit_cancelled"));
+                return false;
+            }
+        }
+        let json = serde_json::to_string(&GameMessage::Resign).unwrap();
+        let _ = tx.send(Message::Text(json)).await;
     }
+    say_goodbye(tx).await;
+    true
+}
+
+// 显示试下的结果并等用户确认；这一手本身不合法时直接取消
+#[cfg(feature = "terminal")]
+async fn confirm_move(
+    preview: Result<MovePreview, GameError>,
+    reader: &mut BufReader<io::Stdin>,
+) -> bool {
+    let preview = match preview {
+        Ok(preview) => preview,
+        Err(e) => {
+            say!("{}", t!("msg.error", e));
+            return false;
+        }
+    };
+    say!("{}", describe_preview(&preview));
+    say!("{}", t!("input.confirm_move"));
+    let mut answer = String::new();
+    let _ = reader.read_line(&mut answer).await;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        say!("{}", t!("input.move_cancelled"));
+        return false;
+    }
+    true
+}
+
+// 服务器收到 Goodbye 就知道不是掉线，不会保留座位等重连
+#[cfg(feature = "terminal")]
+async fn say_goodbye(tx: &mpsc::Sender<Message>) {
+    let json = serde_json::to_string(&GameMessage::Goodbye).unwrap();
+    let _ = tx.send(Message::Text(json)).await;
+    let _ = tx.send(Message::Close(None)).await;
+}
+
+// 发出一条请求，写入任务已经退出说明连接断了
+#[cfg(feature = "terminal")]
+async fn send_request(tx: &mpsc::Sender<Message>, msg: &GameMessage) -> Result<(), ClientError> {
+    let json = serde_json::to_string(msg).unwrap();
+    tx.send(Message::Text(json))
+        .await
+        .map_err(|_| ClientError::ServerClosed)
 }
 
-pub async fn handle_user_input(tx: &mpsc::Sender<Message>) -> bool {
+// 读一行命令并执行，返回 true 表示玩家要退出
+#[cfg(feature = "terminal")]
+pub async fn handle_user_input(
+    tx: &mpsc::Sender<Message>,
+    state: &Arc<Mutex<ClientState>>,
+) -> Result<bool, ClientError> {
     let stdin = io::stdin();
     let mut reader = BufReader::new(stdin);
     let mut line = String::new();
 
     // 等待用户输入
-    let result = reader.read_line(&mut line).await;
-
-    match result {
-        Ok(0) => return true,
-        Ok(_) => {
-            let input = line.trim();
-            if input.eq_ignore_ascii_case("quit") {
-                return true;
-            }
-
-            let parts: Vec<&str> = input.split_whitespace().collect();
-            if parts.len() == 3 && parts[0].eq_ignore_ascii_case("move") {
-                match (parts[1].parse::<usize>(), parts[2].parse::<usize>()) {
-                    (Ok(row), Ok(col)) => {
-                        let move_msg = GameMessage::Move { row, col };
-                        let json = serde_json::to_string(&move_msg).unwrap();
-                        println!("发送移动消息: {}", json);
-                        if let Err(e) = tx.send(Message::Text(json)).await {
-                            eprintln!("发送消息失败: {}", e);
-                            return true;
-                        }
-                    }
-                    _ => println!("无效的行/列。用法: move <行> <列> (0-14)"),
+    if reader
+        .read_line(&mut line)
+        .await
+        .map_err(ClientError::Input)?
+        == 0
+    {
+        say_goodbye(tx).await;
+        return Ok(true);
+    }
+    run_command(line.trim(), tx, state, Some(&mut reader)).await
+}
+
+// 执行一条命令，返回 true 表示玩家要退出。reader 用来询问认输和落子确认，
+// 终端界面在按键时已经确认过，传 None
+#[cfg(feature = "terminal")]
+pub async fn run_command(
+    input: &str,
+    tx: &mpsc::Sender<Message>,
+    state: &Arc<Mutex<ClientState>>,
+    mut reader: Option<&mut BufReader<io::Stdin>>,
+) -> Result<bool, ClientError> {
+    if input.eq_ignore_ascii_case("quit") {
+        return Ok(quit(tx, state, reader).await);
+    }
+
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    // 分析模式下落子、翻看和撤回都只动本地的试验棋盘
+    let analysing = state.lock().await.analysis.is_some();
+    if analysing
+        && matches!(
+            parts.first(),
+            Some(&"move" | &"try" | &"next" | &"prev" | &"jump" | &"undo" | &"best")
+        )
+    {
+        handle_analysis_command(&parts, state).await;
+        return Ok(false);
+    }
+    if !parts.is_empty() && parts[0].eq_ignore_ascii_case("move") {
+        let (move_msg, preview) = {
+            let mut state = state.lock().await;
+            let points = parse_points(&parts[1..], state.board.rules.board_size);
+            let move_msg = match points.as_deref() {
+                Ok(&[(row, col)]) => Ok(state.move_request(row, col)),
+                Ok(&[first, second]) => Ok(state.pair_request(first, second)),
+                Ok(_) => Err(t!("input.bad_coords")),
+                Err(e) => Err(e.to_string()),
+            };
+            // 开了落子确认时先在本地试下第一子
+            let preview = match (&move_msg, points.as_deref()) {
+                (Ok(_), Ok(&[(row, col), ..])) if state.confirm_moves => {
+                    Some(state.board.preview_move(row, col))
                 }
-            } else {
-                println!("无效的命令。用法: move <行> <列> (0-14)");
+                _ => None,
+            };
+            (move_msg, preview)
+        };
+        if let (Some(preview), Some(reader)) = (preview, &mut reader) {
+            if !confirm_move(preview, reader).await {
+                state.lock().await.pending_move = None;
+                return Ok(false);
             }
         }
-        Err(e) => {
-            eprintln!("输入错误: {}", e);
-            return true;
+        match move_msg {
+            Ok(move_msg) => {
+                say!(
+                    "{}",
+                    t!(
+                        "input.sending_move",
+                        serde_json::to_string(&move_msg).unwrap()
+                    )
+                );
+                send_request(tx, &move_msg).await?;
+            }
+            Err(e) => say!("{}", e),
+        }
+    } else if !parts.is_empty() && parts[0].eq_ignore_ascii_case("preview") {
+        let state = state.lock().await;
+        match parse_points(&parts[1..], state.board.rules.board_size).as_deref() {
+            Ok(&[(row, col)]) => match state.board.preview_move(row, col) {
+                Ok(preview) => say!("{}", describe_preview(&preview)),
+                Err(e) => say!("{}", t!("msg.error", e)),
+            },
+            Ok(_) => say!("{}", t!("input.bad_coords")),
+            Err(e) => say!("{}", e),
+        }
+    } else if !parts.is_empty() && parts[0].eq_ignore_ascii_case("chat") {
+        let text = input.trim_start()[parts[0].len()..].trim();
+        if text.is_empty() {
+            say!("{}", t!("input.chat_usage"));
+        } else {
+            let chat = GameMessage::SendChat {
+                text: text.to_string(),
+            };
+            send_request(tx, &chat).await?;
+        }
+    } else if parts.len() == 1 && parts[0].eq_ignore_ascii_case("export") {
+        send_request(tx, &GameMessage::ExportGame).await?;
+    } else if parts.len() == 2 && parts[0].eq_ignore_ascii_case("lang") {
+        switch_lang(parts[1]);
+    } else if !parts.is_empty() && parts.len() <= 2 && parts[0].eq_ignore_ascii_case("top") {
+        let limit = match parts.get(1).map(|n| n.parse::<usize>()) {
+            None => DEFAULT_LEADERBOARD_SIZE,
+            Some(Ok(n)) => n,
+            Some(Err(_)) => {
+                say!("{}", t!("input.top_usage"));
+                return Ok(false);
+            }
+        };
+        send_request(tx, &GameMessage::LeaderboardRequest { limit }).await?;
+    } else if !parts.is_empty() && parts.len() <= 2 && parts[0].eq_ignore_ascii_case("stats") {
+        // 不带参数时查看自己的战绩
+        let user_id = match parts.get(1) {
+            Some(id) => Some(id.to_string()),
+            None => state.lock().await.user_id.clone(),
+        };
+        let Some(user_id) = user_id else {
+            say!("{}", t!("input.stats_not_connected"));
+            return Ok(false);
+        };
+        send_request(tx, &GameMessage::GetStats { user_id }).await?;
+    } else if parts.len() == 2 && parts[0].eq_ignore_ascii_case("replay") {
+        let request = GameMessage::ReplayRequest {
+            game_id: parts[1].to_string(),
+        };
+        send_request(tx, &request).await?;
+    } else if parts.len() == 1 && parts[0].eq_ignore_ascii_case("n") {
+        // 上一盘的棋盘清掉，新对局的局面随后到达
+        {
+            let mut state = state.lock().await;
+            if !state.finished {
+                say!("{}", t!("input.not_finished"));
+                return Ok(false);
+            }
+            state.board = Board::with_rules(state.board.rules);
         }
+        say!("{}", t!("input.finding_opponent"));
+        send_request(tx, &GameMessage::FindOpponent).await?;
+    } else if parts.len() == 1 && parts[0].eq_ignore_ascii_case("hint") {
+        send_request(tx, &GameMessage::HintRequest).await?;
+    } else if parts.first() == Some(&"draw") {
+        let request = match parts[1..] {
+            [] => GameMessage::OfferDraw,
+            ["accept"] => GameMessage::AnswerDraw { accept: true },
+            ["decline"] => GameMessage::AnswerDraw { accept: false },
+            _ => {
+                say!("{}", t!("input.draw_usage"));
+                return Ok(false);
+            }
+        };
+        send_request(tx, &request).await?;
+    } else if parts.len() == 2 && parts[0].eq_ignore_ascii_case("eval") {
+        let request = GameMessage::EvaluateRequest {
+            game_id: parts[1].to_string(),
+        };
+        send_request(tx, &request).await?;
+    } else if parts.first() == Some(&"explore") {
+        let Some(request) = position_search_request(&parts[1..]) else {
+            say!("{}", t!("input.explore_usage"));
+            return Ok(false);
+        };
+        send_request(tx, &request).await?;
+    } else if parts.len() == 1 && parts[0].eq_ignore_ascii_case("info") {
+        send_request(tx, &GameMessage::ServerInfoRequest).await?;
+    } else if parts.first() == Some(&"invite") {
+        let Some(request) = invite_request(&parts[1..]) else {
+            say!("{}", t!("input.invite_usage"));
+            return Ok(false);
+        };
+        send_request(tx, &request).await?;
+    } else if parts.first() == Some(&"substitutes") {
+        let allowed = match parts[1..] {
+            ["on"] => true,
+            ["off"] => false,
+            _ => {
+                say!("{}", t!("input.substitutes_usage"));
+                return Ok(false);
+            }
+        };
+        send_request(tx, &GameMessage::AllowSubstitutes { allowed }).await?;
+    } else if matches!(parts.first(), Some(&"next" | &"prev" | &"jump")) {
+        handle_replay_command(&parts, state).await;
+    } else if parts.first() == Some(&"analyze") {
+        let mut state = state.lock().await;
+        match parts[1..] {
+            [] if !state.finished => say!("{}", t!("input.not_finished")),
+            [] => {
+                let analysis = Analysis::new(state.board.clone());
+                analysis.show();
+                say!("{}", t!("analysis.help"));
+                state.analysis = Some(analysis);
+            }
+            ["off"] => {
+                if state.analysis.take().is_some() {
+                    say!("{}", t!("analysis.closed"));
+                    display_board(&state.board);
+                }
+            }
+            _ => say!("{}", t!("input.analyze_usage")),
+        }
+    } else {
+        say!("{}", t!("input.bad_command"));
     }
+    Ok(false)
+}
 
-    false
+// 进入对局的身份：游客不计等级分
+#[cfg(feature = "terminal")]
+pub enum Auth {
+    Guest,
+    Login(String),
+    Register(String),
 }
 
+// 进行对局，一盘结束后可以换对手接着下，玩家退出时返回 Ok。
+// tui 为 true 时用终端界面，否则逐行读命令
+#[cfg(feature = "terminal")]
 pub async fn run_game(
-    ws_stream: WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+    url: &str,
     username: String,
-) {
+    auth: Auth,
+    play_vs_ai: Option<Difficulty>,
+    invite: Option<String>,
+    preferred_role: Option<PlayerRole>,
+    tui: bool,
+) -> Result<(), ClientError> {
+    let (ws_stream, _) = connect_async(url)
+        .await
+        .map_err(|e| ClientError::Connect(t!("main.connect_failed", e)))?;
+    say!("{}", t!("main.connected"));
     let (mut write, mut read) = ws_stream.split();
     let (tx, mut rx) = mpsc::channel::<Message>(32);
-    let board = Arc::new(Mutex::new(Board::new()));
+    // 重连后新连接的写入端交给写入任务
+    let (sinks_tx, mut sinks) = mpsc::channel(1);
+    let state = Arc::new(Mutex::new(ClientState::with_config(&ClientConfig::load())));
 
     let (game_over_sender, _) = broadcast::channel::<()>(16);
 
+    // 先注册或登录换取令牌
+    let auth_msg = match auth {
+        Auth::Guest => None,
+        Auth::Login(password) => Some(GameMessage::Login {
+            username: username.clone(),
+            password,
+        }),
+        Auth::Register(password) => Some(GameMessage::Register {
+            username: username.clone(),
+            password,
+        }),
+    };
+    let mut token = None;
+    let mut resume = None;
+    if let Some(auth_msg) = auth_msg {
+        let json = serde_json::to_string(&auth_msg).unwrap();
+        write
+            .send(Message::Text(json))
+            .await
+            .map_err(|e| ClientError::Connect(t!("game.send_username_failed", e)))?;
+        while token.is_none() {
+            let text = match read.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => return Err(ClientError::ServerClosed),
+            };
+            match parse_frame(&text) {
+                Some(GameMessage::AuthToken {
+                    token: issued,
+                    unfinished,
+                }) => {
+                    say!("{}", t!("game.authenticated"));
+                    token = Some(issued);
+                    resume = choose_unfinished(&unfinished).await;
+                }
+                Some(GameMessage::Error(e)) => {
+                    return Err(ClientError::Connect(t!("game.auth_failed", error_text(&e))));
+                }
+                _ => {}
+            }
+        }
+    }
+
     // 发送用户名到服务器
-    let connect_msg = GameMessage::ConnectRequest { username };
+    let connect_msg = GameMessage::ConnectRequest {
+        username,
+        token,
+        play_vs_ai,
+        invite,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role,
+        resume,
+    };
     let json = serde_json::to_string(&connect_msg).unwrap();
-    if let Err(e) = write.send(Message::Text(json)).await {
-        eprintln!("发送用户名失败: {}", e);
-        return;
-    }
+    write
+        .send(Message::Text(json))
+        .await
+        .map_err(|e| ClientError::Connect(t!("game.send_username_failed", e)))?;
+    let mut reconnector = Reconnector::new(url, connect_msg);
 
-    println!("欢迎来到五子棋游戏！");
-    println!("等待服务器分配玩家角色...");
+    // 从这里开始的输出都显示在界面的消息窗格里
+    let output = tui.then(capture_output);
+    say!("{}", t!("game.welcome"));
+    say!("{}", t!("game.waiting_role"));
 
     // 处理接收消息的任务
-    let board_clone = board.clone();
+    let state_clone = state.clone();
     let read_task = {
         let game_over_sender = game_over_sender.clone();
+        let tx = tx.clone();
         let mut game_over_receiver = game_over_sender.subscribe();
         tokio::spawn(async move {
-            println!("开始监听服务器消息...");
-            loop {
+            say!("{}", t!("game.listening"));
+            // 重连或者要了整盘局面之后，等局面到了再重发还没确认的落子
+            let mut resend_after_sync = false;
+            let result = loop {
                 tokio::select! {
-                    _ = game_over_receiver.recv() => {
-                        println!("游戏结束标志触发，读取任务退出");
-                        break;
-                    }
-                    result = read.next() => {
-                        match result {
-                            Some(Ok(msg)) => {
-                                if let Message::Text(text) = msg {
-                                    match serde_json::from_str::<GameMessage>(&text) {
-                                        Ok(game_msg) => {
-                                            let mut board = board_clone.lock().await;
-                                            if handle_game_message(game_msg, &mut board).await {
-                                                println!("游戏结束，关闭读取任务");
-                                                let _ = game_over_sender.send(());
-                                                break;
-                                            }
-                                        }
-                                        Err(e) => eprintln!("解析消息失败: {}", e),
+                    // 玩家退出后服务器会关闭连接，这不算错误
+                    biased;
+                    _ = game_over_receiver.recv() => break Ok(()),
+                    frame = read.next() => {
+                        let game_msg = match frame {
+                            Some(Ok(Message::Text(text))) => match parse_frame(&text) {
+                                Some(game_msg) => game_msg,
+                                None => continue,
+                            },
+                            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                                // 对局中掉线时按退避间隔重连，服务器在宽限期内保留座位
+                                let (username, game_id) = {
+                                    let state = state_clone.lock().await;
+                                    if state.player_role.is_none() || state.finished {
+                                        break Err(ClientError::ServerClosed);
                                     }
+                                    (state.username.clone(), state.game_id.clone())
+                                };
+                                let Some(game_id) = game_id else {
+                                    break Err(ClientError::ServerClosed);
+                                };
+                                if let Some(username) = username {
+                                    reconnector.set_username(&username);
                                 }
+                                say!("{}", t!("reconnect.lost"));
+                                let reconnected = tokio::select! {
+                                    biased;
+                                    _ = game_over_receiver.recv() => break Ok(()),
+                                    reconnected = reconnector.reconnect(&game_id) => reconnected,
+                                };
+                                match reconnected {
+                                    // 服务器的 ConnectResponse 照常处理，整盘局面随后就到
+                                    Ok((ws_stream, response)) => {
+                                        let (new_write, new_read) = ws_stream.split();
+                                        read = new_read;
+                                        let _ = sinks_tx.send(new_write).await;
+                                        resend_after_sync = true;
+                                        response
+                                    }
+                                    Err(e) => break Err(e),
+                                }
+                            }
+                            Some(Ok(_)) => continue,
+                        };
+                        let mut state = state_clone.lock().await;
+                        let finished = state.finished;
+                        let event = state.script.is_some().then(|| game_msg.clone());
+                        let synced = matches!(game_msg, GameMessage::Status { .. });
+                        let over = handle_game_message(game_msg, &mut state);
+                        // 脚本看到的是处理完这条消息之后的状态
+                        let requests = match &event {
+                            Some(event) => state.run_script(event),
+                            None => Vec::new(),
+                        };
+                        let sent = async {
+                            for request in &requests {
+                                send_request(&tx, request).await?;
                             }
-                            _ => {}
+                            Ok(())
+                        };
+                        if let Err(e) = sent.await {
+                            break Err(e);
+                        }
+                        // 对局结束后连接保留，玩家可以换对手再来一盘或者退出
+                        if over && state.finished && !finished {
+                            say!("{}", t!("game.play_again"));
+                            say!("{}", t!("game.analyze_hint"));
+                        } else if over {
+                            break Ok(());
+                        }
+                        // 重连或者重新同步拿到整盘局面后，还没确认的落子用同一个随机数再发一次
+                        if synced && std::mem::take(&mut resend_after_sync) {
+                            if let Some(request) = state.pending_resend() {
+                                if let Err(e) = send_request(&tx, &request).await {
+                                    break Err(e);
+                                }
+                            }
+                        }
+                        match state.take_resync() {
+                            Ok(true) => {
+                                resend_after_sync = true;
+                                if let Err(e) = send_request(&tx, &GameMessage::Resync).await {
+                                    break Err(e);
+                                }
+                            }
+                            Ok(false) => {}
+                            Err(e) => break Err(e),
                         }
                     }
                 }
-            }
-            println!("读取任务结束");
+            };
+            let _ = game_over_sender.send(());
+            say!("{}", t!("game.task_exit", t!("game.task_read")));
+            result
         })
     };
 
     // 处理写入消息的任务
     let write_task = {
+        let game_over_sender = game_over_sender.clone();
         let mut game_over_receiver = game_over_sender.subscribe();
         tokio::spawn(async move {
             let mut write = write;
-            loop {
+            // 连接断开时发不出去的消息先排着，读取任务重连成功后按顺序补发
+            let mut unsent: VecDeque<Message> = VecDeque::new();
+            let mut connected = true;
+            let result = loop {
                 tokio::select! {
+                    // 读取任务重连成功后换上新连接
+                    Some(sink) = sinks.recv() => {
+                        write = sink;
+                        connected = true;
+                        while let Some(msg) = unsent.pop_front() {
+                            if write.send(msg.clone()).await.is_err() {
+                                unsent.push_front(msg);
+                                connected = false;
+                                break;
+                            }
+                        }
+                    }
                     maybe_msg = rx.recv() => {
                         match maybe_msg {
                             Some(msg) => {
-                                if let Err(e) = write.send(msg).await {
-                                    println!("写入任务错误: {}", e);
-                                    break;
+                                if connected && write.send(msg.clone()).await.is_ok() {
+                                    continue;
                                 }
+                                connected = false;
+                                if unsent.len() >= MAX_UNSENT_MESSAGES {
+                                    unsent.pop_front();
+                                    say!("{}", t!("reconnect.not_sent"));
+                                }
+                                say!("{}", t!("reconnect.queued"));
+                                unsent.push_back(msg);
                             },
                             None => {
-                                println!("通道关闭，写入任务退出");
-                                break;
+                                say!("{}", t!("game.channel_closed"));
+                                break Ok(());
                             }
                         }
                     }
                     _ = game_over_receiver.recv() => {
-                        println!("游戏结束标志触发，写入任务退出");
-                        break;
+                        // 退出前把已经排队的消息发完，包括 Goodbye 和关闭帧
+                        while let Ok(msg) = rx.try_recv() {
+                            if write.send(msg).await.is_err() {
+                                break;
+                            }
+                        }
+                        break Ok(());
                     }
                 }
-            }
-            println!("写入任务结束");
+            };
+            let _ = game_over_sender.send(());
+            say!("{}", t!("game.task_exit", t!("game.task_write")));
+            result
         })
     };
 
-    println!("输入格式: move <行> <列> (例如: move 7 7)");
-    println!("输入 'quit' 退出游戏");
+    say!("{}", t!("game.help_move"));
+    say!("{}", t!("game.help_export"));
+    say!("{}", t!("game.help_chat"));
+    say!("{}", t!("game.help_lang"));
+    say!("{}", t!("game.help_replay"));
+    say!("{}", t!("game.help_top"));
+    say!("{}", t!("game.help_stats"));
+    say!("{}", t!("game.help_invite"));
+    say!("{}", t!("game.help_hint"));
+    say!("{}", t!("game.help_draw"));
+    say!("{}", t!("game.help_eval"));
+    say!("{}", t!("game.help_explore"));
+    say!("{}", t!("game.help_analyze"));
+    say!("{}", t!("game.help_info"));
+    say!("{}", t!("game.help_preview"));
+    say!("{}", t!("game.help_substitutes"));
+    say!("{}", t!("game.help_quit"));
 
     // 处理用户输入
     let tx_clone = tx.clone();
+    let input_state = state.clone();
 
-    let input_task = {
+    let input_task = if let Some(output) = output {
         let game_over_sender = game_over_sender.clone();
         let mut game_over_receiver = game_over_sender.subscribe();
+        let (commands_tx, mut commands) = mpsc::channel::<String>(8);
+        let done = Arc::new(AtomicBool::new(false));
+        let screen = {
+            let state = input_state.clone();
+            let done = done.clone();
+            tokio::task::spawn_blocking(move || run_tui(state, output, commands_tx, done, false))
+        };
         tokio::spawn(async move {
-            loop {
+            let result = loop {
                 tokio::select! {
-                    _ = game_over_receiver.recv() => {
-                        println!("游戏结束标志触发，输入任务退出");
-                        break;
-                    }
-                    game_over = handle_user_input(&tx_clone) => {
-                        println!("游戏结束，关闭输入任务");
-                        if game_over {
-                            let _ = game_over_sender.send(());
-                            break;
+                    biased;
+                    _ = game_over_receiver.recv() => break Ok(()),
+                    command = commands.recv() => {
+                        let Some(command) = command else {
+                            say_goodbye(&tx_clone).await;
+                            break Ok(());
+                        };
+                        match run_command(&command, &tx_clone, &input_state, None).await {
+                            Ok(false) => {}
+                            Ok(true) => break Ok(()),
+                            Err(e) => break Err(e),
                         }
                     }
                 }
-            }
-            println!("输入任务结束");
+            };
+            // 先恢复终端，之后的输出照常打印
+            done.store(true, Ordering::SeqCst);
+            let result = match screen.await {
+                Ok(Err(e)) if result.is_ok() => Err(ClientError::Input(e)),
+                _ => result,
+            };
+            let _ = game_over_sender.send(());
+            say!("{}", t!("game.task_exit", t!("game.task_input")));
+            result
+        })
+    } else {
+        let game_over_sender = game_over_sender.clone();
+        let mut game_over_receiver = game_over_sender.subscribe();
+        tokio::spawn(async move {
+            let result = loop {
+                tokio::select! {
+                    biased;
+                    _ = game_over_receiver.recv() => break Ok(()),
+                    input = handle_user_input(&tx_clone, &input_state) => match input {
+                        Ok(false) => {}
+                        Ok(true) => break Ok(()),
+                        Err(e) => break Err(e),
+                    }
+                }
+            };
+            let _ = game_over_sender.send(());
+            say!("{}", t!("game.task_exit", t!("game.task_input")));
+            result
         })
     };
 
-    // 等待所有任务完成
+    // 等待所有任务完成，返回最先列出的错误
     let (read_result, write_result, input_result) = tokio::join!(read_task, write_task, input_task);
-
-    // 检查任务结果
-    if let Err(e) = read_result {
-        println!("读取任务错误: {}", e);
-    }
-    if let Err(e) = write_result {
-        println!("写入任务错误: {}", e);
-    }
-    if let Err(e) = input_result {
-        println!("输入任务错误: {}", e);
+    let mut outcome = Ok(());
+    for (task, result) in [
+        ("game.task_read", read_result),
+        ("game.task_write", write_result),
+        ("game.task_input", input_result),
+    ] {
+        match result {
+            Ok(Err(e)) if outcome.is_ok() => outcome = Err(e),
+            Ok(_) => {}
+            Err(e) => say!("{}", t!("game.task_error", t!(task), e)),
+        }
     }
-
-    println!("\n游戏已结束，按回车键退出...");
-    let mut input = String::new();
-    let _ = std::io::stdin().read_line(&mut input);
+    outcome
 }
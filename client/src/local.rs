@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use chess::{
+    sgf::to_sgf, Board, Budget, Difficulty, Engine, EngineKind, GameError, GameMessage,
+    GameOverReason, PlayerRole, RulesConfig,
+};
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+use crate::{
+    display_board, error_text, handle_game_message, parse_points, role_name, say, t, ClientConfig,
+    ClientError, ClientState,
+};
+
+// 不连服务器的本地对局：棋盘、胜负判断和电脑引擎都和服务器用同一份代码，
+// 每一步照服务器发来的消息那样交给 handle_game_message 显示
+pub struct LocalGame {
+    pub state: ClientState,
+    // 电脑执的一方、用的引擎和每步的计算量；两人轮流在同一个终端下时没有
+    ai: Option<(PlayerRole, Arc<dyn Engine>, Budget)>,
+    // 对局结束后的结果，Some(None) 表示和棋，导出棋谱时用
+    result: Option<Option<PlayerRole>>,
+}
+
+impl LocalGame {
+    fn new(rules: RulesConfig, ai: Option<(PlayerRole, Arc<dyn Engine>, Budget)>) -> Self {
+        let mut state = ClientState::with_config(&ClientConfig::load());
+        state.board = Board::with_rules(rules);
+        Self {
+            state,
+            ai,
+            result: None,
+        }
+    }
+
+    pub fn vs_ai(human: PlayerRole, difficulty: Difficulty) -> Self {
+        let engine = Arc::from(EngineKind::default().engine());
+        let mut game = Self::new(
+            RulesConfig::default(),
+            Some((human.other(), engine, difficulty.budget())),
+        );
+        game.state.player_role = Some(human);
+        game
+    }
+
+    // 两人在同一个终端轮流落子，规则可以自己定，改了规则不用起服务器就能试
+    pub fn hotseat(rules: RulesConfig) -> Self {
+        Self::new(rules, None)
+    }
+
+    pub fn ai_role(&self) -> Option<PlayerRole> {
+        self.ai.as_ref().map(|(role, _, _)| *role)
+    }
+
+    // 替轮到的一方落下一子并显示，分出胜负或者和棋时返回 true
+    pub async fn play(&mut self, row: usize, col: usize) -> Result<bool, GameError> {
+        if self.state.finished {
+            return Err(GameError::InvalidInput("游戏已结束".to_string()));
+        }
+        let by = self.state.board.current_player;
+        let mut next = self.state.board.clone();
+        next.make_move(row, col)?;
+        let applied = GameMessage::MoveApplied {
+            row,
+            col,
+            by,
+            next_player: next.current_player,
+            move_number: next.move_number(),
+        };
+        handle_game_message(applied, &mut self.state);
+        let over = match (next.winning_line(), next.draw_reason()) {
+            (Some(line), _) => GameMessage::GameOver {
+                winner: Some(line.player),
+                winning_line: line.cells,
+                reason: Some(GameOverReason::FiveInARow),
+            },
+            (None, Some(reason)) => GameMessage::GameOver {
+                winner: None,
+                winning_line: Vec::new(),
+                reason: Some(reason),
+            },
+            (None, None) => return Ok(false),
+        };
+        if let GameMessage::GameOver { winner, .. } = &over {
+            self.result = Some(*winner);
+        }
+        Ok(handle_game_message(over, &mut self.state))
+    }
+
+    // 电脑下完它这一回合，搜索放到阻塞线程里。对局结束时返回 true
+    pub async fn ai_turn(&mut self) -> Result<bool, GameError> {
+        let Some((player, engine, budget)) = self.ai.clone() else {
+            return Ok(false);
+        };
+        let board = self.state.board.clone();
+        let stones =
+            tokio::task::spawn_blocking(move || engine.choose_turn(&board, player, budget))
+                .await
+                .map_err(|e| GameError::Unavailable(e.to_string()))?;
+        if stones.is_empty() {
+            return Err(GameError::Unavailable(
+                "电脑找不到可以落子的位置".to_string(),
+            ));
+        }
+        for (row, col) in stones {
+            if self.play(row, col).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // 悔棋：两人对下时撤回一子；和电脑下时连电脑的应手一起撤回，回到自己上一次落子之前。
+    // 已经结束的对局撤回后接着下。返回撤回了几子
+    pub fn undo(&mut self) -> usize {
+        let mut undone = 0;
+        while let Some(record) = self.state.board.undo_move() {
+            undone += 1;
+            if Some(record.player) != self.ai_role() {
+                break;
+            }
+        }
+        if undone > 0 {
+            self.state.finished = false;
+            self.result = None;
+        }
+        undone
+    }
+
+    // 当前对局的 SGF 棋谱，没下完也可以导出
+    pub fn export_sgf(&self) -> String {
+        let name = |player| match self.ai_role() {
+            Some(ai) if ai == player => t!("local.computer"),
+            _ => role_name(player).to_string(),
+        };
+        to_sgf(
+            &self.state.board.moves,
+            &name(PlayerRole::Black),
+            &name(PlayerRole::White),
+            self.state.board.rules.board_size,
+            self.result,
+        )
+    }
+}
+
+// 本地对局的命令循环：和电脑下时轮到电脑就自动应手。输入 quit 或者读完输入时返回
+pub async fn run_local(mut game: LocalGame) -> Result<(), ClientError> {
+    let mut lines = BufReader::new(io::stdin()).lines();
+    say!("{}", t!("local.help"));
+    display_board(&game.state.board);
+    loop {
+        let to_move = game.state.board.current_player;
+        if !game.state.finished && game.ai_role() == Some(to_move) {
+            say!("{}", t!("local.thinking"));
+            if let Err(e) = game.ai_turn().await {
+                say!("{}", t!("msg.error", error_text(&e.into())));
+                return Ok(());
+            }
+            continue;
+        }
+        if game.state.finished {
+            say!("\n{}", t!("local.finished"));
+        } else {
+            say!("\n{}", t!("msg.turn", role_name(to_move)));
+        }
+        let Some(line) = lines.next_line().await.map_err(ClientError::Input)? else {
+            return Ok(());
+        };
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let args = match parts.as_slice() {
+            [] => continue,
+            [command] if command.eq_ignore_ascii_case("quit") => return Ok(()),
+            [command] if command.eq_ignore_ascii_case("undo") => {
+                match game.undo() {
+                    0 => say!("{}", t!("local.nothing_to_undo")),
+                    undone => {
+                        say!("{}", t!("local.undone", undone));
+                        display_board(&game.state.board);
+                    }
+                }
+                continue;
+            }
+            [command] if command.eq_ignore_ascii_case("export") => {
+                say!("{}\n{}", t!("msg.sgf"), game.export_sgf());
+                continue;
+            }
+            [command, args @ ..] if command.eq_ignore_ascii_case("move") => args,
+            args => args,
+        };
+        let points = match parse_points(args, game.state.board.rules.board_size) {
+            Ok(points) if points.len() <= game.state.board.stones_left() => points,
+            Ok(_) => {
+                say!("{}", t!("input.bad_coords"));
+                continue;
+            }
+            Err(e) => {
+                say!("{}", e);
+                continue;
+            }
+        };
+        for (row, col) in points {
+            match game.play(row, col).await {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => {
+                    say!("{}", t!("msg.error", error_text(&e.into())));
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,26 +1,200 @@
-use client::run_game;
+use chess::{
+    Difficulty, PlayerRole, RulesConfig, BOARD_SIZE, MAX_STONES_PER_TURN, MAX_WIN_LENGTH,
+    MIN_WIN_LENGTH,
+};
+use client::{
+    role_name, run_game, run_local, run_present, run_watch, set_lang, set_renderer, t, Auth,
+    BoardRenderer, ClientConfig, Lang, LocalGame,
+};
 use std::io;
-use std::io::{stdout, Write};
+use std::io::{stdout, IsTerminal, Write};
 use tokio_tungstenite::connect_async;
 
+// --vs-ai [easy|medium|hard] 和服务器上的电脑对弈，不写难度时为 medium；
+// 同时带 --local 时在本地和电脑对弈
+fn parse_vs_ai(args: &[String]) -> Option<Difficulty> {
+    let pos = args.iter().position(|arg| arg == "--vs-ai")?;
+    let difficulty = match args.get(pos + 1).map(String::as_str) {
+        Some("easy") => Difficulty::Easy,
+        Some("hard") => Difficulty::Hard,
+        _ => Difficulty::Medium,
+    };
+    Some(difficulty)
+}
+
+// --color <black|white> 想执的颜色，对方已经选了这一边时分到另一边
+fn parse_color(args: &[String]) -> Option<PlayerRole> {
+    let pos = args.iter().position(|arg| arg == "--color")?;
+    match args.get(pos + 1).map(String::as_str) {
+        Some("black") => Some(PlayerRole::Black),
+        Some("white") => Some(PlayerRole::White),
+        _ => None,
+    }
+}
+
+// --rules <JSON> 本地双人对局用的规则，例如 '{"board_size":9,"win_length":4}'，
+// 没写的项用默认值
+fn parse_rules(args: &[String]) -> Result<RulesConfig, String> {
+    let Some(pos) = args.iter().position(|arg| arg == "--rules") else {
+        return Ok(RulesConfig::default());
+    };
+    let json = args.get(pos + 1).ok_or_else(|| t!("local.rules_missing"))?;
+    let rules: RulesConfig = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    // validate 的说明是中文的，这里按界面语言列出所有限制
+    rules.validate().map_err(|_| {
+        t!(
+            "local.rules_range",
+            MAX_STONES_PER_TURN,
+            MIN_WIN_LENGTH,
+            MAX_WIN_LENGTH,
+            BOARD_SIZE
+        )
+    })?;
+    Ok(rules)
+}
+
+// --lang <zh|en> 这次启动用的界面语言，不写回配置文件
+fn parse_lang(args: &[String]) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == "--lang")?;
+    args.get(pos + 1).cloned()
+}
+
+// --present <标题> 以讲解人身份开演示房间
+fn parse_present(args: &[String]) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == "--present")?;
+    args.get(pos + 1).cloned()
+}
+
+// --invite <令牌> 用别人发来的邀请入座或观战
+fn parse_invite(args: &[String]) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == "--invite")?;
+    args.get(pos + 1).cloned()
+}
+
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let config = ClientConfig::load();
+    set_lang(Lang::detect(parse_lang(&args).as_deref(), config.lang));
+    set_renderer(BoardRenderer::from_config(&config));
+
+    // 带 --hotseat 启动时不连服务器，两人在这个终端轮流落子
+    if args.iter().any(|arg| arg == "--hotseat") {
+        match parse_rules(&args) {
+            Ok(rules) => {
+                println!(
+                    "{}",
+                    t!("local.hotseat_started", rules.board_size, rules.win_length)
+                );
+                if let Err(e) = run_local(LocalGame::hotseat(rules)).await {
+                    eprintln!("{}", e);
+                }
+            }
+            Err(e) => eprintln!("{}", t!("local.bad_rules", e)),
+        }
+        println!("{}", t!("main.bye"));
+        return;
+    }
+
+    // 带 --local 启动时不连服务器，在本地和电脑对弈，不写颜色时执黑先行
+    if args.iter().any(|arg| arg == "--local") {
+        let difficulty = parse_vs_ai(&args).unwrap_or(Difficulty::Medium);
+        let human = parse_color(&args).unwrap_or(PlayerRole::Black);
+        println!(
+            "{}",
+            t!(
+                "local.started",
+                role_name(human),
+                format!("{:?}", difficulty)
+            )
+        );
+        if let Err(e) = run_local(LocalGame::vs_ai(human, difficulty)).await {
+            eprintln!("{}", e);
+        }
+        println!("{}", t!("main.bye"));
+        return;
+    }
+
     let url = "ws://localhost:8080";
-    println!("正在连接到服务器: {}", url);
+    println!("{}", t!("main.connecting", url));
+
+    // 默认用终端界面，带 --line 或者输入输出不是终端时逐行读命令
+    let tui = !args.iter().any(|arg| arg == "--line")
+        && io::stdin().is_terminal()
+        && stdout().is_terminal();
+
+    // 带 --watch 启动时只观战，不需要用户名
+    if args.iter().any(|arg| arg == "--watch") {
+        match connect_async(url).await {
+            Ok((ws_stream, _)) => {
+                println!("{}", t!("main.connected"));
+                if let Err(e) = run_watch(ws_stream, tui).await {
+                    eprintln!("{}", e);
+                }
+            }
+            Err(e) => eprintln!("{}", t!("main.connect_failed", e)),
+        }
+        println!("{}", t!("main.bye"));
+        return;
+    }
 
     // 获取用户名
-    println!("请输入您的用户名:");
+    println!("{}", t!("main.ask_username"));
     let mut username = String::new();
     io::stdin().read_line(&mut username).unwrap();
     let username = username.trim().to_string();
 
-    match connect_async(url).await {
-        Ok((ws_stream, _)) => {
-            println!("已连接到服务器");
-            run_game(ws_stream, username).await;
+    // 带 --register 启动时注册新账号，否则用密码登录，密码留空以游客身份进入；
+    // 用户名留空时直接以服务器生成的游客名进入
+    let register = args.iter().any(|arg| arg == "--register");
+    let mut password = String::new();
+    if !username.is_empty() {
+        println!("{}", t!("main.ask_password"));
+        io::stdin().read_line(&mut password).unwrap();
+    }
+    let password = password.trim().to_string();
+
+    // --present <标题> 开一个演示房间讲解，观众用 --watch 收看。讲解人必须登录
+    if let Some(title) = parse_present(&args) {
+        if password.is_empty() {
+            eprintln!("{}", t!("present.login_required"));
+        } else {
+            match connect_async(url).await {
+                Ok((ws_stream, _)) => {
+                    println!("{}", t!("main.connected"));
+                    if let Err(e) = run_present(ws_stream, title, username, password).await {
+                        eprintln!("{}", e);
+                    }
+                }
+                Err(e) => eprintln!("{}", t!("main.connect_failed", e)),
+            }
         }
-        Err(e) => eprintln!("连接失败: {}", e),
+        println!("{}", t!("main.bye"));
+        return;
+    }
+
+    let auth = match (password.is_empty(), register) {
+        (true, _) => Auth::Guest,
+        (false, true) => Auth::Register(password),
+        (false, false) => Auth::Login(password),
+    };
+
+    let result = run_game(
+        url,
+        username,
+        auth,
+        parse_vs_ai(&args),
+        parse_invite(&args),
+        parse_color(&args),
+        tui,
+    )
+    .await;
+    if let Err(e) = result {
+        eprintln!("\n{}", e);
     }
-    println!("程序结束");
+    println!("\n{}", t!("game.press_enter"));
+    let mut input = String::new();
+    let _ = io::stdin().read_line(&mut input);
+    println!("{}", t!("main.bye"));
     stdout().flush().unwrap(); // ensur
 }
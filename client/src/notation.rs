@@ -0,0 +1,91 @@
+use crate::t;
+
+// 五子棋常用的坐标写法：列用字母 A 起，行从下往上数 1 起，15 路棋盘的天元是 H8。
+// 和 `move <行> <列>` 的数字写法可以混用，两个数字算一个点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    // 没有给出任何坐标
+    Empty,
+    // 既不是字母加数字，也不是数字
+    Unrecognized(String),
+    // 数字写法只给了行没给列
+    MissingColumn(usize),
+    // 写法对，但不在棋盘上
+    OffBoard(String),
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NotationError::Empty => write!(f, "{}", t!("notation.empty")),
+            NotationError::Unrecognized(text) => write!(f, "{}", t!("notation.unrecognized", text)),
+            NotationError::MissingColumn(row) => {
+                write!(f, "{}", t!("notation.missing_column", row))
+            }
+            NotationError::OffBoard(text) => write!(f, "{}", t!("notation.off_board", text)),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+// 解析一个 H8 式的坐标，返回 (行, 列)，行列和 `move <行> <列>` 一样从 0 起、从上往下
+pub fn parse_point(text: &str, size: usize) -> Result<(usize, usize), NotationError> {
+    let unrecognized = || NotationError::Unrecognized(text.to_string());
+    let mut chars = text.chars();
+    let letter = chars
+        .next()
+        .filter(char::is_ascii_alphabetic)
+        .ok_or_else(unrecognized)?;
+    let digits = chars.as_str();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(unrecognized());
+    }
+    let col = (letter.to_ascii_uppercase() as u8 - b'A') as usize;
+    let rank: usize = digits
+        .parse()
+        .map_err(|_| NotationError::OffBoard(text.to_string()))?;
+    if col >= size || rank == 0 || rank > size {
+        return Err(NotationError::OffBoard(text.to_string()));
+    }
+    Ok((size - rank, col))
+}
+
+// 列号对应的字母，棋盘超过 26 路时后面的列没有字母可用
+pub fn column_letter(col: usize) -> Option<char> {
+    (col < 26).then(|| (b'A' + col as u8) as char)
+}
+
+// 把 (行, 列) 写成 H8 式的坐标
+pub fn format_point(row: usize, col: usize, size: usize) -> Option<String> {
+    if row >= size || col >= size {
+        return None;
+    }
+    Some(format!("{}{}", column_letter(col)?, size - row))
+}
+
+// 解析 move 后面的参数：每个点写成 H8 或者 "7 7"，六子棋一回合可以给两个点
+pub fn parse_points(args: &[&str], size: usize) -> Result<Vec<(usize, usize)>, NotationError> {
+    if args.is_empty() {
+        return Err(NotationError::Empty);
+    }
+    let mut points = Vec::new();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let Ok(row) = arg.parse::<usize>() else {
+            points.push(parse_point(arg, size)?);
+            continue;
+        };
+        let col = match args.next() {
+            Some(col) => col
+                .parse::<usize>()
+                .map_err(|_| NotationError::Unrecognized(col.to_string()))?,
+            None => return Err(NotationError::MissingColumn(row)),
+        };
+        if row >= size || col >= size {
+            return Err(NotationError::OffBoard(format!("{} {}", row, col)));
+        }
+        points.push((row, col));
+    }
+    Ok(points)
+}
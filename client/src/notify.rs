@@ -0,0 +1,134 @@
+use std::process::Command;
+
+use chess::PlayerRole;
+
+use crate::{role_name, t, ClientConfig};
+
+// 对局中值得提醒玩家的事件，由 handle_game_message 发给每个提醒钩子
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    MoveMade {
+        by: PlayerRole,
+        row: usize,
+        col: usize,
+    },
+    // 轮到自己落子
+    YourTurn,
+    // 平局时 winner 为 None
+    GameOver {
+        winner: Option<PlayerRole>,
+    },
+    // 别人发来的聊天，自己说的话不算
+    ChatReceived {
+        from: String,
+        text: String,
+    },
+}
+
+// 提醒钩子：其他代码可以实现该 trait 并注册到 ClientState，只关心的方法需要实现
+pub trait Notifier: Send + Sync {
+    // 本方时间快用完
+    fn time_warning(&self, _remaining_secs: u64) {}
+
+    fn on_event(&self, _event: &ClientEvent) {}
+}
+
+// 终端响铃：轮到自己、收到聊天和时间快用完时各响一声
+pub struct BellNotifier;
+
+impl Notifier for BellNotifier {
+    fn time_warning(&self, _remaining_secs: u64) {
+        print!("\x07");
+    }
+
+    fn on_event(&self, event: &ClientEvent) {
+        if matches!(
+            event,
+            ClientEvent::YourTurn | ClientEvent::ChatReceived { .. }
+        ) {
+            print!("\x07");
+        }
+    }
+}
+
+// 执行配置中的外部命令，剩余秒数通过环境变量 GOMOKU_REMAINING_SECS 传入
+pub struct CommandNotifier {
+    command: String,
+}
+
+impl CommandNotifier {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+}
+
+impl Notifier for CommandNotifier {
+    fn time_warning(&self, remaining_secs: u64) {
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("GOMOKU_REMAINING_SECS", remaining_secs.to_string())
+            .spawn();
+        if let Err(e) = result {
+            eprintln!("{}", t!("notify.command_failed", e));
+        }
+    }
+}
+
+// 系统桌面通知：Linux 上用 notify-send，macOS 上用 osascript。每一步落子不通知，太吵
+pub struct DesktopNotifier;
+
+impl DesktopNotifier {
+    fn show(&self, body: &str) {
+        let title = t!("notify.title");
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "display notification {:?} with title {:?}",
+                body, title
+            ));
+            command
+        } else {
+            let mut command = Command::new("notify-send");
+            command.arg(title).arg(body);
+            command
+        };
+        if let Err(e) = command.spawn() {
+            eprintln!("{}", t!("notify.command_failed", e));
+        }
+    }
+}
+
+impl Notifier for DesktopNotifier {
+    fn time_warning(&self, remaining_secs: u64) {
+        self.show(&t!("msg.own_time_warning", remaining_secs));
+    }
+
+    fn on_event(&self, event: &ClientEvent) {
+        let body = match event {
+            ClientEvent::MoveMade { .. } => return,
+            ClientEvent::YourTurn => t!("notify.your_turn"),
+            ClientEvent::GameOver {
+                winner: Some(winner),
+            } => t!("msg.winner", role_name(*winner)),
+            ClientEvent::GameOver { winner: None } => t!("msg.draw"),
+            ClientEvent::ChatReceived { from, text } => t!("msg.chat", from, text),
+        };
+        self.show(&body);
+    }
+}
+
+// 根据配置创建默认的提醒钩子
+pub fn notifiers_from_config(config: &ClientConfig) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if !config.quiet {
+        notifiers.push(Box::new(BellNotifier));
+    }
+    if config.desktop_notifications {
+        notifiers.push(Box::new(DesktopNotifier));
+    }
+    if let Some(command) = &config.warning_command {
+        notifiers.push(Box::new(CommandNotifier::new(command.clone())));
+    }
+    notifiers
+}
@@ -0,0 +1,36 @@
+use std::sync::{mpsc, Mutex};
+
+// 终端界面或者图形界面打开时客户端的输出进它们的消息窗格，否则照常打印
+static SINK: Mutex<Option<mpsc::Sender<String>>> = Mutex::new(None);
+
+pub fn say(text: String) {
+    match SINK.lock().unwrap().as_ref() {
+        Some(sink) => {
+            let _ = sink.send(text);
+        }
+        None => println!("{}", text),
+    }
+}
+
+// 用法和 println! 一样
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::say(format!($($arg)*))
+    };
+}
+
+// 接管输出，之后 say! 的内容从返回的通道读出，直到 release_output
+pub fn capture_output() -> mpsc::Receiver<String> {
+    let (sink, output) = mpsc::channel();
+    *SINK.lock().unwrap() = Some(sink);
+    output
+}
+
+pub fn release_output() {
+    *SINK.lock().unwrap() = None;
+}
+
+pub fn output_captured() -> bool {
+    SINK.lock().unwrap().is_some()
+}
@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use chess::GameMessage;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::i18n::error_text;
+use crate::{parse_frame, say, t, ClientError};
+
+pub type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// 第一次重连前等待的时间，之后每次翻倍，最长等 MAX_RECONNECT_DELAY
+pub const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+pub const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
+// 连续失败这么多次就放弃。总共等四十秒左右，在服务器默认的一分钟宽限期之内
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+// 发出 ConnectRequest 之后等服务器答复的时间，超时算这次重连失败
+pub const REJOIN_TIMEOUT: Duration = Duration::from_secs(5);
+// 恢复的连接用了这么久才又断开，才把退避从头算起；刚连上就断的算连续失败
+pub const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+// 断线期间最多替玩家攒这么多条消息，再多就丢掉最早的
+pub const MAX_UNSENT_MESSAGES: usize = 32;
+
+// 指数退避：每次失败后等待的时间翻倍，直到上限
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    next: Duration,
+    max: Duration,
+    attempts: u32,
+    max_attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(
+            INITIAL_RECONNECT_DELAY,
+            MAX_RECONNECT_DELAY,
+            MAX_RECONNECT_ATTEMPTS,
+        )
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration, max_attempts: u32) -> Self {
+        Self {
+            next: initial.min(max),
+            max,
+            attempts: 0,
+            max_attempts,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // 下一次重连前要等多久，次数用完时返回 None
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempts >= self.max_attempts {
+            return None;
+        }
+        self.attempts += 1;
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        Some(delay)
+    }
+}
+
+// 对局中连接断开时重新连上服务器。用第一次连接时拿到的令牌重新认证，
+// 服务器认出还在宽限期内的座位后发来整盘局面，客户端据此对齐棋盘
+pub struct Reconnector {
+    url: String,
+    request: GameMessage,
+    // 连续几次断线共用一个退避，服务器接受连接又马上拒绝时不会无休止地重连
+    backoff: Backoff,
+    restored_at: Option<Instant>,
+}
+
+// 服务器对重连请求的答复
+enum Rejoin {
+    // 回到了原来的对局，带着服务器的 ConnectResponse
+    Seated(GameMessage),
+    // 座位已经不在了，服务器把玩家当成新来的分进了别的对局或者排队
+    Elsewhere,
+    Failed(String),
+}
+
+impl Reconnector {
+    // 邀请和恢复对局只在第一次入座时有用，重连时回到原来的座位
+    pub fn new(url: &str, mut request: GameMessage) -> Self {
+        if let GameMessage::ConnectRequest { invite, resume, .. } = &mut request {
+            *invite = None;
+            *resume = None;
+        }
+        Self {
+            url: url.to_string(),
+            request,
+            backoff: Backoff::default(),
+            restored_at: None,
+        }
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    // 游客没填用户名时由服务器生成，重连要用服务器给的名字
+    pub fn set_username(&mut self, name: &str) {
+        if let GameMessage::ConnectRequest { username, .. } = &mut self.request {
+            *username = name.to_string();
+        }
+    }
+
+    // 按退避间隔反复重连，服务器把玩家放回 game_id 这盘棋之后返回新连接和它的 ConnectResponse。
+    // 原来的座位已经没了时不留在服务器分的新对局里，直接返回错误
+    pub async fn reconnect(
+        &mut self,
+        game_id: &str,
+    ) -> Result<(WsStream, GameMessage), ClientError> {
+        if self
+            .restored_at
+            .is_some_and(|at| at.elapsed() >= STABLE_CONNECTION)
+        {
+            self.backoff = Backoff::default();
+        }
+        let json = serde_json::to_string(&self.request).unwrap();
+        while let Some(delay) = self.backoff.next_delay() {
+            say!(
+                "{}",
+                t!(
+                    "reconnect.waiting",
+                    delay.as_secs_f32(),
+                    self.backoff.attempts(),
+                    MAX_RECONNECT_ATTEMPTS
+                )
+            );
+            tokio::time::sleep(delay).await;
+            let mut ws_stream = match connect_async(self.url.as_str()).await {
+                Ok((ws_stream, _)) => ws_stream,
+                Err(e) => {
+                    say!("{}", t!("reconnect.failed", e));
+                    continue;
+                }
+            };
+            if let Err(e) = ws_stream.send(Message::Text(json.clone())).await {
+                say!("{}", t!("reconnect.failed", e));
+                continue;
+            }
+            match rejoin(&mut ws_stream, game_id).await {
+                Rejoin::Seated(response) => {
+                    say!("{}", t!("reconnect.restored"));
+                    self.restored_at = Some(Instant::now());
+                    return Ok((ws_stream, response));
+                }
+                Rejoin::Elsewhere => {
+                    let goodbye = serde_json::to_string(&GameMessage::Goodbye).unwrap();
+                    let _ = ws_stream.send(Message::Text(goodbye)).await;
+                    let _ = ws_stream.close(None).await;
+                    return Err(ClientError::Connect(t!("reconnect.game_gone")));
+                }
+                Rejoin::Failed(e) => say!("{}", t!("reconnect.failed", e)),
+            }
+        }
+        say!("{}", t!("reconnect.gave_up", self.backoff.attempts()));
+        Err(ClientError::ServerClosed)
+    }
+}
+
+// 读到服务器对 ConnectRequest 的答复为止，中间解析不了的帧跳过
+async fn rejoin(ws_stream: &mut WsStream, game_id: &str) -> Rejoin {
+    let reply = async {
+        loop {
+            let text = match ws_stream.next().await {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Rejoin::Failed(e.to_string()),
+                None => return Rejoin::Failed(t!("error.server_closed")),
+            };
+            match parse_frame(&text) {
+                Some(GameMessage::ConnectResponse { game_id: id, .. }) if id != game_id => {
+                    return Rejoin::Elsewhere;
+                }
+                Some(response @ GameMessage::ConnectResponse { .. }) => {
+                    return Rejoin::Seated(response);
+                }
+                Some(GameMessage::QueueStatus { .. }) => return Rejoin::Elsewhere,
+                Some(GameMessage::Error(e)) => return Rejoin::Failed(error_text(&e)),
+                _ => {}
+            }
+        }
+    };
+    tokio::time::timeout(REJOIN_TIMEOUT, reply)
+        .await
+        .unwrap_or_else(|_| Rejoin::Failed(t!("reconnect.no_reply")))
+}
@@ -0,0 +1,245 @@
+use std::io::IsTerminal;
+use std::sync::RwLock;
+
+use chess::{Board, PlayerRole};
+use serde::{Deserialize, Serialize};
+
+use crate::{column_letter, ClientConfig};
+
+// 棋盘画法：plain 是最早的 - X O，unicode 用制表符画出棋盘线和星位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardStyle {
+    Plain,
+    #[default]
+    Unicode,
+}
+
+// 颜色和终端界面保持一致：黑子浅红、白子浅青、最后一手黄底，连成一线的绿底
+const RESET: &str = "\x1b[0m";
+const GRID: &str = "\x1b[90m";
+const BLACK: &str = "\x1b[91m";
+const WHITE: &str = "\x1b[96m";
+const LAST_MOVE: &str = "\x1b[30;43m";
+const WINNING: &str = "\x1b[30;42m";
+
+// 需要标出的棋子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Winning,
+    LastMove,
+}
+
+impl Mark {
+    fn color(self) -> &'static str {
+        match self {
+            Mark::Winning => WINNING,
+            Mark::LastMove => LAST_MOVE,
+        }
+    }
+}
+
+// 把棋盘画成文本，逐行打印。显示方式来自客户端配置，启动时设置一次
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardRenderer {
+    pub style: BoardStyle,
+    // 用 ANSI 颜色区分黑白子和标出最后一手
+    pub color: bool,
+    // 上边和左边标出 move 命令用的行列号，右边和下边标出 H8 式坐标
+    pub labels: bool,
+}
+
+impl Default for BoardRenderer {
+    fn default() -> Self {
+        Self::PLAIN
+    }
+}
+
+static RENDERER: RwLock<BoardRenderer> = RwLock::new(BoardRenderer::PLAIN);
+
+pub fn set_renderer(renderer: BoardRenderer) {
+    *RENDERER.write().unwrap() = renderer;
+}
+
+pub fn renderer() -> BoardRenderer {
+    *RENDERER.read().unwrap()
+}
+
+impl BoardRenderer {
+    // 不带颜色和坐标的老样子，输出给脚本或者重定向到文件时用
+    pub const PLAIN: BoardRenderer = BoardRenderer {
+        style: BoardStyle::Plain,
+        color: false,
+        labels: false,
+    };
+
+    // 配置文件没写 color 时，输出是终端且没有设置 NO_COLOR 才上色
+    pub fn from_config(config: &ClientConfig) -> Self {
+        let color = config.color.unwrap_or_else(|| {
+            std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+        });
+        Self {
+            style: config.board_style,
+            color,
+            labels: true,
+        }
+    }
+
+    // 整盘棋。highlight 里的棋子是连成一线的，最后一手另外标出
+    pub fn render(&self, board: &Board, highlight: &[(usize, usize)]) -> String {
+        let size = board.rules.board_size;
+        self.render_area(board, (0, 0), (size, size), highlight)
+    }
+
+    // 从 (top, left) 起 rows 行 cols 列的一块，行列号和整盘棋一致
+    pub fn render_area(
+        &self,
+        board: &Board,
+        (top, left): (usize, usize),
+        (rows, cols): (usize, usize),
+        highlight: &[(usize, usize)],
+    ) -> String {
+        let size = board.rules.board_size;
+        let bottom = (top + rows).min(size);
+        let right = (left + cols).min(size);
+        let last = board.last_move();
+        let mut out = String::new();
+        if self.labels {
+            let header: String = (left..right).map(|c| format!("{:^3}", c)).collect();
+            out.push_str(&self.paint(GRID, &format!("   {}", header)));
+            out.push('\n');
+        }
+        for r in top..bottom {
+            if self.labels {
+                out.push_str(&self.paint(GRID, &format!("{:>3}", r)));
+            }
+            for c in left..right {
+                let marked = if highlight.contains(&(r, c)) {
+                    Some(Mark::Winning)
+                } else if last == Some((r, c)) {
+                    Some(Mark::LastMove)
+                } else {
+                    None
+                };
+                out.push_str(&self.cell(board.cells[r][c], r, c, size, marked));
+            }
+            if self.labels {
+                out.push_str(&self.paint(GRID, &format!(" {:<2}", size - r)));
+            }
+            out.push('\n');
+        }
+        if self.labels {
+            let letters: String = (left..right)
+                .map(|c| format!("{:^3}", column_letter(c).unwrap_or(' ')))
+                .collect();
+            out.push_str(&self.paint(GRID, &format!("   {}", letters)));
+            out.push('\n');
+        }
+        out
+    }
+
+    // 一个交叉点占三个字符宽，和行列号对齐
+    fn cell(
+        &self,
+        cell: Option<PlayerRole>,
+        r: usize,
+        c: usize,
+        size: usize,
+        marked: Option<Mark>,
+    ) -> String {
+        match self.style {
+            BoardStyle::Plain => {
+                let stone = match cell {
+                    None => return " - ".to_string(),
+                    Some(PlayerRole::Black) => "X",
+                    Some(PlayerRole::White) => "O",
+                };
+                match marked {
+                    // 不上色时连成一线的加方括号、最后一手加圆括号
+                    Some(mark) if self.color => self.paint(mark.color(), &format!(" {} ", stone)),
+                    Some(Mark::Winning) => format!("[{}]", stone),
+                    Some(Mark::LastMove) => format!("({})", stone),
+                    None => self.paint(stone_color(cell), &format!(" {} ", stone)),
+                }
+            }
+            BoardStyle::Unicode => {
+                let before = if c == 0 { " " } else { "─" };
+                let after = if c + 1 == size { " " } else { "─" };
+                let point = match cell {
+                    None => {
+                        return self.paint(
+                            GRID,
+                            &format!("{}{}{}", before, grid_point(r, c, size), after),
+                        )
+                    }
+                    // 不上色时连成一线的画成星形，最后一手画成带圈的
+                    Some(PlayerRole::Black) => match marked {
+                        Some(Mark::Winning) if !self.color => '★',
+                        Some(Mark::LastMove) if !self.color => '◉',
+                        _ => '●',
+                    },
+                    Some(PlayerRole::White) => match marked {
+                        Some(Mark::Winning) if !self.color => '☆',
+                        Some(Mark::LastMove) if !self.color => '◎',
+                        _ => '○',
+                    },
+                };
+                let stone = match marked {
+                    Some(mark) => self.paint(mark.color(), &point.to_string()),
+                    None => self.paint(stone_color(cell), &point.to_string()),
+                };
+                format!(
+                    "{}{}{}",
+                    self.paint(GRID, before),
+                    stone,
+                    self.paint(GRID, after)
+                )
+            }
+        }
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn stone_color(cell: Option<PlayerRole>) -> &'static str {
+    match cell {
+        Some(PlayerRole::Black) => BLACK,
+        Some(PlayerRole::White) => WHITE,
+        None => GRID,
+    }
+}
+
+// 星位：13 路以上离边三路，9 到 12 路离边两路，外加天元
+pub fn is_star_point(row: usize, col: usize, size: usize) -> bool {
+    let edge = match size {
+        13.. => 3,
+        9..=12 => 2,
+        _ => return size % 2 == 1 && row == size / 2 && col == size / 2,
+    };
+    let on_line = |i: usize| i == edge || i + 1 + edge == size;
+    let center = size % 2 == 1 && row == size / 2 && col == size / 2;
+    center || (on_line(row) && on_line(col))
+}
+
+// 空交叉点的制表符：角、边和中间各不相同，星位加粗
+fn grid_point(row: usize, col: usize, size: usize) -> char {
+    let last = size - 1;
+    match (row, col) {
+        (0, 0) => '┌',
+        (0, c) if c == last => '┐',
+        (r, 0) if r == last => '└',
+        (r, c) if r == last && c == last => '┘',
+        (0, _) => '┬',
+        (r, _) if r == last => '┴',
+        (_, 0) => '├',
+        (_, c) if c == last => '┤',
+        (r, c) if is_star_point(r, c, size) => '╋',
+        _ => '┼',
+    }
+}
@@ -0,0 +1,93 @@
+use chess::{ArchivedGame, Board, MoveRecord};
+
+use crate::{display_board, role_name, say, t};
+
+// 回放一盘已存档的对局，position 为已经摆上棋盘的手数
+pub struct ReplayPlayer {
+    game: ArchivedGame,
+    position: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(game: ArchivedGame) -> Self {
+        Self { game, position: 0 }
+    }
+
+    pub fn game(&self) -> &ArchivedGame {
+        &self.game
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.game.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.game.moves.is_empty()
+    }
+
+    pub fn step_forward(&mut self) -> bool {
+        self.jump(self.position + 1)
+    }
+
+    pub fn step_back(&mut self) -> bool {
+        self.position > 0 && self.jump(self.position - 1)
+    }
+
+    pub fn jump(&mut self, position: usize) -> bool {
+        if position > self.len() {
+            return false;
+        }
+        self.position = position;
+        true
+    }
+
+    // 当前局面：依次摆上前 position 手
+    pub fn board(&self) -> Board {
+        let mut board = Board::with_rules(self.game.rules);
+        for record in &self.game.moves[..self.position] {
+            board.cells[record.row][record.col] = Some(record.player);
+        }
+        board.current_player = self.game.rules.mover(self.position);
+        board.rehash();
+        board
+    }
+
+    pub fn current_move(&self) -> Option<&MoveRecord> {
+        self.position
+            .checked_sub(1)
+            .and_then(|index| self.game.moves.get(index))
+    }
+
+    // 当前这一手的用时（秒），第一手从开局算起
+    pub fn think_secs(&self) -> Option<i64> {
+        let record = self.current_move()?;
+        let previous = match self.position {
+            1 => self.game.started_at,
+            n => self.game.moves[n - 2].timestamp,
+        };
+        Some((record.timestamp - previous).num_seconds())
+    }
+
+    pub fn show(&self) {
+        display_board(&self.board());
+        match self.current_move() {
+            Some(record) => say!(
+                "{}",
+                t!(
+                    "replay.move",
+                    self.position,
+                    self.len(),
+                    role_name(record.player),
+                    record.row,
+                    record.col,
+                    self.think_secs().unwrap_or(0)
+                )
+            ),
+            None => say!("{}", t!("replay.start", self.len())),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex};
+
+use chess::{GameMessage, PlayerRole};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use serde_json::Value;
+
+use crate::{t, ClientState};
+
+// 每次回调最多执行的操作数，脚本里写错的死循环不会卡住客户端
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+// 脚本要客户端做的事，回调返回后按顺序执行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptAction {
+    // 落子，和输入 "行 列" 一样发给服务器
+    Play { row: usize, col: usize },
+    // 在终端打一行批注
+    Note(String),
+}
+
+// 客户端脚本（Rhai），配置里的 script 指向脚本文件，不用重新编译就能写自动应答、批注或者简单的机器人。
+// 脚本定义 fn on_event(event, game)，每收到一条服务器消息调用一次：
+// event.type 是消息类型，其余字段和协议一致（只有一个值的消息放在 event.value）；
+// game 是处理完这条消息后的本地状态：role、current_player、game_id 和 board（每格 "Black"、"White" 或 ""）。
+// 回调里可以调用 play(row, col) 落子、note(text) 打批注
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    actions: Arc<Mutex<Vec<ScriptAction>>>,
+}
+
+impl ScriptHost {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        // 调试构建默认的嵌套层数只有发布构建的一半，统一成发布构建的上限
+        engine.set_max_expr_depths(64, 32);
+        let actions = Arc::new(Mutex::new(Vec::new()));
+
+        let queue = actions.clone();
+        engine.register_fn(
+            "play",
+            move |row: i64, col: i64| -> Result<(), Box<EvalAltResult>> {
+                let (Ok(row), Ok(col)) = (usize::try_from(row), usize::try_from(col)) else {
+                    return Err(t!("script.bad_position", row, col).into());
+                };
+                queue.lock().unwrap().push(ScriptAction::Play { row, col });
+                Ok(())
+            },
+        );
+        let queue = actions.clone();
+        engine.register_fn("note", move |text: &str| {
+            queue
+                .lock()
+                .unwrap()
+                .push(ScriptAction::Note(text.to_string()));
+        });
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        // 先执行一遍顶层语句，脚本可以在这里打印说明
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| e.to_string())?;
+        actions.lock().unwrap().clear();
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            actions,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::new(&source)
+    }
+
+    // 脚本没有定义 on_event 时什么都不做；出错时这次回调请求的动作全部作废
+    pub fn on_event(
+        &mut self,
+        msg: &GameMessage,
+        state: &ClientState,
+    ) -> Result<Vec<ScriptAction>, String> {
+        if !self.ast.iter_functions().any(|f| f.name == "on_event") {
+            return Ok(Vec::new());
+        }
+        let event = event_to_dynamic(msg)?;
+        let game = game_to_dynamic(state);
+        let result =
+            self.engine
+                .call_fn::<Dynamic>(&mut self.scope, &self.ast, "on_event", (event, game));
+        let actions = std::mem::take(&mut *self.actions.lock().unwrap());
+        // 回调的返回值不用
+        match result {
+            Ok(_) => Ok(actions),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// 按协议的 JSON 形式转换，脚本看到的字段名和服务器发的一致
+fn event_to_dynamic(msg: &GameMessage) -> Result<Dynamic, String> {
+    let (kind, fields) = match serde_json::to_value(msg).map_err(|e| e.to_string())? {
+        Value::String(kind) => (kind, Value::Object(Default::default())),
+        Value::Object(map) if map.len() == 1 => map.into_iter().next().unwrap(),
+        other => return Err(t!("script.bad_event", other)),
+    };
+    let to_dynamic = |value: &Value| rhai::serde::to_dynamic(value).map_err(|e| e.to_string());
+    let mut event = match fields {
+        Value::Object(_) => to_dynamic(&fields)?.cast::<Map>(),
+        value => Map::from([("value".into(), to_dynamic(&value)?)]),
+    };
+    event.insert("type".into(), kind.into());
+    Ok(event.into())
+}
+
+fn role_to_dynamic(role: Option<PlayerRole>) -> Dynamic {
+    match role {
+        Some(PlayerRole::Black) => "Black".into(),
+        Some(PlayerRole::White) => "White".into(),
+        None => Dynamic::UNIT,
+    }
+}
+
+fn game_to_dynamic(state: &ClientState) -> Dynamic {
+    let size = state.board.rules.board_size;
+    let board: Array = state.board.cells[..size]
+        .iter()
+        .map(|row| {
+            let row: Array = row[..size]
+                .iter()
+                .map(|&cell| match cell {
+                    Some(_) => role_to_dynamic(cell),
+                    None => "".into(),
+                })
+                .collect();
+            row.into()
+        })
+        .collect();
+    let mut game = Map::new();
+    game.insert("role".into(), role_to_dynamic(state.player_role));
+    game.insert(
+        "current_player".into(),
+        role_to_dynamic(Some(state.board.current_player)),
+    );
+    game.insert(
+        "game_id".into(),
+        state
+            .game_id
+            .clone()
+            .map(Dynamic::from)
+            .unwrap_or(Dynamic::UNIT),
+    );
+    game.insert("board".into(), board.into());
+    game.into()
+}
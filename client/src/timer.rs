@@ -0,0 +1,74 @@
+use std::time::Instant;
+
+use chess::PlayerRole;
+
+// 剩余时间低于这个值时界面标红，自己的时间跌破时响铃提醒
+pub const LOW_TIME_MS: u64 = 10_000;
+
+// 客户端本地走的棋钟：每次收到 TimeUpdate 按服务器的时间对齐，两次之间由界面按经过的
+// 时间给轮到的一方扣时。不读系统时间，当前时刻由调用方传入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalClock {
+    pub black_ms: u64,
+    pub white_ms: u64,
+    // 自己的时间跌破阈值后已经提醒过，回到阈值以上（例如每步加秒）后重新计
+    warned: bool,
+    // 上次对齐或扣时的时刻，下次只扣这之后经过的时间，对齐前界面空闲多久都不算
+    ticked_at: Instant,
+}
+
+impl LocalClock {
+    pub fn new(black_ms: u64, white_ms: u64, now: Instant) -> Self {
+        Self {
+            black_ms,
+            white_ms,
+            warned: false,
+            ticked_at: now,
+        }
+    }
+
+    // 以服务器发来的剩余时间为准
+    pub fn sync(&mut self, black_ms: u64, white_ms: u64, now: Instant) {
+        self.black_ms = black_ms;
+        self.white_ms = white_ms;
+        self.ticked_at = now;
+    }
+
+    pub fn remaining(&self, player: PlayerRole) -> u64 {
+        match player {
+            PlayerRole::Black => self.black_ms,
+            PlayerRole::White => self.white_ms,
+        }
+    }
+
+    pub fn is_low(&self, player: PlayerRole) -> bool {
+        self.remaining(player) < LOW_TIME_MS
+    }
+
+    // 给正在计时的一方扣掉上次对齐或扣时以来经过的时间，扣到零为止，超时由服务器判定
+    pub fn tick(&mut self, running: PlayerRole, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.ticked_at);
+        self.ticked_at = now;
+        let left = match running {
+            PlayerRole::Black => &mut self.black_ms,
+            PlayerRole::White => &mut self.white_ms,
+        };
+        *left = left.saturating_sub(elapsed.as_millis() as u64);
+    }
+
+    // 自己的时间刚跌破阈值时返回剩余秒数（向上取整），每次跌破只返回一次
+    pub fn low_time_warning(&mut self, own: PlayerRole) -> Option<u64> {
+        if !self.is_low(own) {
+            self.warned = false;
+            return None;
+        }
+        if std::mem::replace(&mut self.warned, true) {
+            return None;
+        }
+        Some(self.remaining(own).div_ceil(1000))
+    }
+
+    pub fn warned(&self) -> bool {
+        self.warned
+    }
+}
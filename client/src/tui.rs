@@ -0,0 +1,430 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use chess::{Board, PlayerRole};
+use ratatui::crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::Mutex;
+
+use crate::{
+    column_letter, describe_preview, evaluation_bar, format_clock, parse_points, player_label,
+    release_output, role_name, t, ClientState,
+};
+
+// 消息窗格最多保留的行数
+const LOG_LINES: usize = 500;
+// 没有按键时也隔这么久重画一次，新消息和时钟跟着刷新
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+// 界面自己的状态：光标、正在输入的命令和消息记录
+struct Screen {
+    cursor: (usize, usize),
+    command: Option<String>,
+    confirm_quit: bool,
+    // 开了落子确认时，预览过、等再选一次的那一格
+    armed: Option<(usize, usize)>,
+    // 刚选中、还要在消息窗格里预览的一格
+    preview: Option<(usize, usize)>,
+    confirm_moves: bool,
+    log: VecDeque<String>,
+    // 观战模式：不落子，按键换成挑选和切换对局
+    spectator: bool,
+}
+
+impl Screen {
+    fn new(size: usize, spectator: bool) -> Self {
+        Self {
+            cursor: (size / 2, size / 2),
+            command: None,
+            confirm_quit: false,
+            armed: None,
+            preview: None,
+            confirm_moves: false,
+            log: VecDeque::new(),
+            spectator,
+        }
+    }
+
+    fn push_log(&mut self, text: &str) {
+        self.log
+            .extend(text.trim_start_matches('\n').lines().map(String::from));
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    // 处理一次按键，返回要执行的命令，命令和文字模式下输入的一样
+    fn handle_key(&mut self, key: KeyEvent, size: usize, playing: bool) -> Option<String> {
+        if let Some(command) = &mut self.command {
+            match key.code {
+                KeyCode::Enter => {
+                    let command = self.command.take().unwrap_or_default();
+                    return match command.trim() {
+                        "" => None,
+                        command if command.eq_ignore_ascii_case("quit") => self.quit(playing),
+                        // 命令行里输入的落子和在棋盘上选点一样要确认
+                        command => {
+                            let parts: Vec<&str> = command.split_whitespace().collect();
+                            match parse_points(&parts[1..], size).as_deref() {
+                                Ok(&[(row, col)]) if parts[0].eq_ignore_ascii_case("move") => {
+                                    self.place(row, col)
+                                }
+                                _ => Some(command.to_string()),
+                            }
+                        }
+                    };
+                }
+                KeyCode::Esc => self.command = None,
+                KeyCode::Backspace => {
+                    command.pop();
+                }
+                KeyCode::Char(c) => command.push(c),
+                _ => {}
+            }
+            return None;
+        }
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if !matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) && !ctrl_c {
+            self.confirm_quit = false;
+        }
+        if self.spectator {
+            // 数字键观战列表里对应的那一盘，命令和观战模式里输入的一样
+            match key.code {
+                KeyCode::Char(n @ '1'..='9') => return Some(format!("watch {}", n)),
+                KeyCode::Char('n') => return Some("next".to_string()),
+                KeyCode::Char('r') => return Some("list".to_string()),
+                KeyCode::Char('s') => return Some("stop".to_string()),
+                KeyCode::Enter | KeyCode::Char(' ') => return None,
+                _ => {}
+            }
+        }
+        let (row, col) = self.cursor;
+        match key.code {
+            _ if ctrl_c => return self.quit(playing),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor.0 = row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor.0 = (row + 1).min(size - 1),
+            KeyCode::Left | KeyCode::Char('h') => self.cursor.1 = col.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.cursor.1 = (col + 1).min(size - 1),
+            KeyCode::Enter | KeyCode::Char(' ') => return self.place(row, col),
+            KeyCode::Char(':') => self.command = Some(String::new()),
+            KeyCode::Char('n') => return Some("n".to_string()),
+            KeyCode::Char('q') | KeyCode::Esc => return self.quit(playing),
+            _ => {}
+        }
+        None
+    }
+
+    // 在棋盘上单击左键就在那一格落子，光标跟过去；输入命令时不理会鼠标
+    fn handle_mouse(&mut self, mouse: MouseEvent, board: Rect, size: usize) -> Option<String> {
+        if self.spectator
+            || self.command.is_some()
+            || mouse.kind != MouseEventKind::Down(MouseButton::Left)
+        {
+            return None;
+        }
+        let (row, col) = board_cell(board, mouse.column, mouse.row, size)?;
+        self.confirm_quit = false;
+        self.cursor = (row, col);
+        self.place(row, col)
+    }
+
+    // 在一格落子。开了落子确认时第一次只预览，同一格再选一次才真的落子
+    fn place(&mut self, row: usize, col: usize) -> Option<String> {
+        if self.confirm_moves && self.armed != Some((row, col)) {
+            self.armed = Some((row, col));
+            self.preview = Some((row, col));
+            return None;
+        }
+        self.armed = None;
+        Some(format!("move {} {}", row, col))
+    }
+
+    // 对局进行中要连按两次，第二次才认输退出
+    fn quit(&mut self, playing: bool) -> Option<String> {
+        if playing && !std::mem::take(&mut self.confirm_quit) {
+            self.confirm_quit = true;
+            self.push_log(&t!("tui.confirm_quit"));
+            return None;
+        }
+        Some("quit".to_string())
+    }
+}
+
+// 终端界面：左边棋盘，右边对局信息，下面是消息窗格和命令行。
+// 按键得到的命令交给 commands 执行，done 置位后恢复终端返回。
+// 观战时右边换成进行中的对局列表，看着一盘时显示双方等级分和评估条
+pub fn run_tui(
+    state: Arc<Mutex<ClientState>>,
+    output: mpsc::Receiver<String>,
+    commands: tokio::sync::mpsc::Sender<String>,
+    done: Arc<AtomicBool>,
+    spectator: bool,
+) -> io::Result<()> {
+    let result = ratatui::try_init().and_then(|mut terminal| {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+        let screen = Screen::new(state.blocking_lock().board.rules.board_size, spectator);
+        event_loop(&mut terminal, screen, &state, &output, &commands, &done)
+    });
+    let _ = execute!(io::stdout(), DisableMouseCapture);
+    ratatui::restore();
+    release_output();
+    // 界面关闭后还没显示的消息照常打印出来
+    for text in output.try_iter() {
+        println!("{}", text);
+    }
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut screen: Screen,
+    state: &Mutex<ClientState>,
+    output: &mpsc::Receiver<String>,
+    commands: &tokio::sync::mpsc::Sender<String>,
+    done: &AtomicBool,
+) -> io::Result<()> {
+    let mut board_area = Rect::default();
+    while !done.load(Ordering::SeqCst) {
+        for text in output.try_iter() {
+            screen.push_log(&text);
+        }
+        let (size, playing) = {
+            let mut state = state.blocking_lock();
+            state.tick_clock(Instant::now());
+            // 分析模式下的落子只下在试验棋盘上，不用确认
+            screen.confirm_moves = state.confirm_moves && state.analysis.is_none();
+            // 观战时接替了空座位，之后按玩家的按键处理
+            if state.player_role.is_some() {
+                screen.spectator = false;
+            }
+            terminal.draw(|frame| board_area = draw(frame, &state, &screen))?;
+            (state.board.rules.board_size, state.is_playing())
+        };
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        let command = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                screen.handle_key(key, size, playing)
+            }
+            Event::Mouse(mouse) => screen.handle_mouse(mouse, board_area, size),
+            _ => continue,
+        };
+        if let Some((row, col)) = screen.preview.take() {
+            match state.blocking_lock().board.preview_move(row, col) {
+                Ok(preview) => {
+                    screen.push_log(&describe_preview(&preview));
+                    screen.push_log(&t!("input.confirm_again"));
+                }
+                Err(e) => {
+                    screen.armed = None;
+                    screen.push_log(&t!("msg.error", e));
+                }
+            }
+        }
+        if let Some(command) = command {
+            if commands.blocking_send(command).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+// 画出整个界面，返回棋盘所在的区域，鼠标点击按它换算成格子
+fn draw(frame: &mut Frame, state: &ClientState, screen: &Screen) -> Rect {
+    let size = state.board.rules.board_size as u16;
+    let [top, log_area, input] = Layout::vertical([
+        Constraint::Length(size + 4),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    // 分析模式下画试验棋盘
+    let board = match &state.analysis {
+        Some(analysis) => analysis.board(),
+        None => state.board.clone(),
+    };
+    let [board_area, side] =
+        Layout::horizontal([Constraint::Length(size * 3 + 8), Constraint::Min(20)]).areas(top);
+
+    frame.render_widget(
+        Paragraph::new(board_lines(&board, screen.cursor))
+            .block(Block::bordered().title(t!("tui.board"))),
+        board_area,
+    );
+    let (title, sidebar) = match (&state.watching, screen.spectator) {
+        (None, true) => (t!("tui.live_games"), game_list_lines(state)),
+        _ => (t!("tui.status"), sidebar_lines(state)),
+    };
+    frame.render_widget(
+        Paragraph::new(sidebar).block(Block::bordered().title(title)),
+        side,
+    );
+    // 只显示放得下的最后几行
+    let height = log_area.height.saturating_sub(2) as usize;
+    let log: Vec<Line> = screen
+        .log
+        .iter()
+        .skip(screen.log.len().saturating_sub(height))
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(log).block(Block::bordered().title(t!("tui.log"))),
+        log_area,
+    );
+    let prompt = match &screen.command {
+        Some(command) => format!(":{}", command),
+        None if screen.spectator => t!("tui.watch_keys"),
+        None => t!("tui.keys"),
+    };
+    frame.render_widget(Paragraph::new(prompt), input);
+    board_area
+}
+
+// 屏幕上的一点落在棋盘的哪一格：边框内第一行是列号，每行先是三格宽的行号，
+// 之后每个交叉点占三格
+fn board_cell(board: Rect, x: u16, y: u16, size: usize) -> Option<(usize, usize)> {
+    let row = y.checked_sub(board.y + 2)? as usize;
+    let col = x.checked_sub(board.x + 4)? as usize / 3;
+    (row < size && col < size).then_some((row, col))
+}
+
+// 带行列号的棋盘，光标反色，最后一手高亮。右边和下边另标 H8 式坐标的行号和列字母
+fn board_lines(board: &Board, cursor: (usize, usize)) -> Vec<Line<'static>> {
+    let size = board.rules.board_size;
+    let last = board.last_move();
+    let header: String = (0..size).map(|c| format!("{:^3}", c)).collect();
+    let mut lines = vec![Line::raw(format!("   {}", header))];
+    for (r, row) in board.cells.iter().take(size).enumerate() {
+        let mut spans = vec![Span::raw(format!("{:>3}", r))];
+        for (c, cell) in row.iter().take(size).enumerate() {
+            let (stone, mut style) = match cell {
+                None => ("·", Style::default().fg(Color::DarkGray)),
+                Some(PlayerRole::Black) => ("X", Style::default().fg(Color::LightRed)),
+                Some(PlayerRole::White) => ("O", Style::default().fg(Color::LightCyan)),
+            };
+            if last == Some((r, c)) {
+                style = style.bg(Color::Yellow).fg(Color::Black);
+            }
+            if cursor == (r, c) {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(format!("{} ", stone), style));
+        }
+        spans.push(Span::styled(
+            format!(" {:<2}", size - r),
+            Style::default().fg(Color::DarkGray),
+        ));
+        lines.push(Line::from(spans));
+    }
+    let letters: String = (0..size)
+        .map(|c| format!("{:^3}", column_letter(c).unwrap_or(' ')))
+        .collect();
+    lines.push(Line::styled(
+        format!("   {}", letters),
+        Style::default().fg(Color::DarkGray),
+    ));
+    lines
+}
+
+// 执哪一方、轮到谁、第几手和双方时钟。观战时先列出双方，最后是评估条
+fn sidebar_lines(state: &ClientState) -> Vec<Line<'static>> {
+    let board = &state.board;
+    let mut lines = match (&state.watching, state.player_role) {
+        (Some(game), None) => vec![
+            Line::raw(t!(
+                "tui.player",
+                role_name(PlayerRole::Black),
+                player_label(&game.black, game.black_rating)
+            )),
+            Line::raw(t!(
+                "tui.player",
+                role_name(PlayerRole::White),
+                player_label(&game.white, game.white_rating)
+            )),
+        ],
+        (_, Some(role)) => vec![Line::raw(t!("tui.you", role_name(role)))],
+        (None, None) => vec![Line::raw(t!("role.spectator"))],
+    };
+    lines.push(Line::raw(if state.finished {
+        t!("tui.finished")
+    } else {
+        t!("msg.turn", role_name(board.current_player))
+    }));
+    lines.push(Line::raw(t!("tui.move", board.move_number())));
+    if let Some((row, col)) = board.last_move() {
+        lines.push(Line::raw(t!("tui.last_move", row, col)));
+    }
+    // 双方时钟各占一行，轮到的一方加粗，不到 10 秒时标红
+    if let Some(clock) = state.clock {
+        for player in [PlayerRole::Black, PlayerRole::White] {
+            let mut style = Style::default();
+            if !state.finished && board.current_player == player {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if clock.is_low(player) {
+                style = style.fg(Color::LightRed);
+            }
+            lines.push(Line::styled(
+                t!(
+                    "tui.player",
+                    role_name(player),
+                    format_clock(clock.remaining(player))
+                ),
+                style,
+            ));
+        }
+    }
+    if let Some(game_id) = &state.game_id {
+        lines.push(Line::raw(t!("tui.game", game_id)));
+    }
+    if let Some(score) = state.evaluation {
+        lines.push(Line::raw(t!(
+            "tui.evaluation",
+            evaluation_bar(score),
+            score
+        )));
+    }
+    if let Some(analysis) = &state.analysis {
+        lines.push(Line::styled(
+            analysis.summary(),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+    lines
+}
+
+// 还没选定对局时的观战大厅：序号、双方和等级分、已下的手数
+fn game_list_lines(state: &ClientState) -> Vec<Line<'static>> {
+    if state.game_list.is_empty() {
+        return vec![Line::raw(t!("msg.no_live_games"))];
+    }
+    state
+        .game_list
+        .iter()
+        .enumerate()
+        .map(|(i, game)| {
+            Line::raw(t!(
+                "msg.game_list_entry",
+                i + 1,
+                player_label(&game.black, game.black_rating),
+                player_label(&game.white, game.white_rating),
+                game.move_count
+            ))
+        })
+        .collect()
+}
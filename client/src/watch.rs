@@ -0,0 +1,201 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chess::{GameFilter, GameMessage, GameStatus, PlayerRole, Region};
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::{
+    capture_output, handle_game_message, parse_frame, run_command, run_tui, say, t, ClientConfig,
+    ClientError, ClientState,
+};
+
+type WatchStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+// 只列出进行中的对局
+pub fn live_games_request() -> GameMessage {
+    GameMessage::ListGames {
+        filter: GameFilter {
+            status: Some(GameStatus::Live),
+            ..GameFilter::default()
+        },
+    }
+}
+
+// 观战命令对应的请求：watch 的参数是列表序号（从 1 开始）或对局编号，
+// next 切换到列表里的下一盘；参数不对时返回 None
+pub fn watch_command(parts: &[&str], state: &ClientState) -> Option<GameMessage> {
+    let game_id = match parts {
+        ["list"] => return Some(live_games_request()),
+        ["stop"] => return Some(GameMessage::StopWatching),
+        ["claim", "black"] => {
+            return Some(GameMessage::ClaimSeat {
+                role: PlayerRole::Black,
+            })
+        }
+        ["claim", "white"] => {
+            return Some(GameMessage::ClaimSeat {
+                role: PlayerRole::White,
+            })
+        }
+        ["region", "off"] => return Some(GameMessage::SubscribeRegion { region: None }),
+        ["region", top, left, rows, cols] => {
+            let region = Region {
+                top: top.parse().ok()?,
+                left: left.parse().ok()?,
+                rows: rows.parse().ok()?,
+                cols: cols.parse().ok()?,
+            };
+            return Some(GameMessage::SubscribeRegion {
+                region: Some(region),
+            });
+        }
+        ["watch", arg] => match arg.parse::<usize>() {
+            Ok(n) => state.game_list.get(n.checked_sub(1)?)?.id.clone(),
+            Err(_) => arg.to_string(),
+        },
+        ["next"] => {
+            let current = state.watching.as_ref().and_then(|watching| {
+                state
+                    .game_list
+                    .iter()
+                    .position(|game| game.id == watching.id)
+            });
+            let next = current.map_or(0, |i| i + 1) % state.game_list.len().max(1);
+            state.game_list.get(next)?.id.clone()
+        }
+        _ => return None,
+    };
+    Some(GameMessage::Watch { game_id })
+}
+
+// 观战模式：不入座，浏览进行中的对局并在它们之间切换。tui 为 true 时用终端界面挑选和收看，
+// 否则逐行读命令。接替到空座位后同一个连接接着下棋，命令按对局里的处理。
+// 输入 quit 或服务器停机时返回 Ok
+pub async fn run_watch(ws_stream: WatchStream, tui: bool) -> Result<(), ClientError> {
+    let (mut write, mut read) = ws_stream.split();
+    let state = Arc::new(Mutex::new(ClientState::with_config(&ClientConfig::load())));
+    let (commands_tx, mut commands) = mpsc::channel::<String>(8);
+    let done = Arc::new(AtomicBool::new(false));
+    let screen = if tui {
+        let output = capture_output();
+        let state = state.clone();
+        let done = done.clone();
+        Some(tokio::task::spawn_blocking(move || {
+            run_tui(state, output, commands_tx, done, true)
+        }))
+    } else {
+        // 标准输入读完时发送端随之丢弃，主循环按 quit 处理
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if commands_tx.send(line).await.is_err() {
+                    break;
+                }
+            }
+        });
+        None
+    };
+
+    let result = watch_loop(&mut write, &mut read, &state, &mut commands).await;
+    // 先恢复终端，之后的输出照常打印
+    done.store(true, Ordering::SeqCst);
+    match screen {
+        Some(screen) => match screen.await {
+            Ok(Err(e)) if result.is_ok() => Err(ClientError::Input(e)),
+            _ => result,
+        },
+        None => result,
+    }
+}
+
+async fn watch_loop(
+    write: &mut SplitSink<WatchStream, Message>,
+    read: &mut SplitStream<WatchStream>,
+    state: &Arc<Mutex<ClientState>>,
+    commands: &mut mpsc::Receiver<String>,
+) -> Result<(), ClientError> {
+    // 入座后 run_command 要发的消息先进这里，再由这个循环写出去
+    let (seated_tx, mut seated_rx) = mpsc::channel::<Message>(32);
+    let json = serde_json::to_string(&live_games_request()).unwrap();
+    write
+        .send(Message::Text(json))
+        .await
+        .map_err(|_| ClientError::ServerClosed)?;
+    say!("{}", t!("watch.help"));
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    return Ok(());
+                };
+                if state.lock().await.player_role.is_some() {
+                    let quit = run_command(command.trim(), &seated_tx, state, None).await?;
+                    while let Ok(msg) = seated_rx.try_recv() {
+                        write.send(msg).await.map_err(|_| ClientError::ServerClosed)?;
+                    }
+                    if quit {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                let parts: Vec<&str> = command.split_whitespace().collect();
+                if matches!(parts[..], ["quit"]) {
+                    return Ok(());
+                }
+                let mut state = state.lock().await;
+                let Some(request) = watch_command(&parts, &state) else {
+                    say!("{}", t!("watch.help"));
+                    continue;
+                };
+                // 停止观战后界面回到对局列表
+                if matches!(request, GameMessage::StopWatching) {
+                    state.watching = None;
+                    state.evaluation = None;
+                }
+                drop(state);
+                let json = serde_json::to_string(&request).unwrap();
+                write
+                    .send(Message::Text(json))
+                    .await
+                    .map_err(|_| ClientError::ServerClosed)?;
+            }
+            Some(msg) = seated_rx.recv() => {
+                write.send(msg).await.map_err(|_| ClientError::ServerClosed)?;
+            }
+            frame = read.next() => match frame {
+                Some(Ok(Message::Text(text))) => match parse_frame(&text) {
+                    // 观战的对局结束后留在观战模式，可以继续切换
+                    Some(msg @ (GameMessage::ServerShutdown | GameMessage::DemoClosed { .. })) => {
+                        handle_game_message(msg, &mut *state.lock().await);
+                        return Ok(());
+                    }
+                    Some(msg) => {
+                        let resync = {
+                            let mut state = state.lock().await;
+                            handle_game_message(msg, &mut state);
+                            state.take_resync()?
+                        };
+                        if resync {
+                            let json = serde_json::to_string(&GameMessage::Resync).unwrap();
+                            write
+                                .send(Message::Text(json))
+                                .await
+                                .map_err(|_| ClientError::ServerClosed)?;
+                        }
+                    }
+                    None => {}
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    return Err(ClientError::ServerClosed);
+                }
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
@@ -1,43 +1,975 @@
-use chess::{Board, GameMessage, PlayerRole};
+use chess::{GameMessage, GameOverReason, MessageLimits, PlayerRole};
 use client::handle_game_message;
-use client::handle_user_input;
-use futures_util::StreamExt;
-use std::sync::atomic::{AtomicBool, Ordering};
+use client::{handle_user_input, run_command};
+use client::{ClientError, ClientEvent, ClientState, Notifier, ScriptHost, MAX_RESYNC_ATTEMPTS};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc::channel;
-use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::tungstenite::Message;
 
 #[tokio::test]
 async fn test_game_over_handling() {
     // 模拟游戏结束消息
     let game_over_msg = GameMessage::GameOver {
         winner: Some(PlayerRole::Black),
+        winning_line: vec![(7, 3), (7, 4), (7, 5), (7, 6), (7, 7)],
+        reason: Some(GameOverReason::FiveInARow),
     };
-    let mut board = Board::new();
-    let game_over = Arc::new(AtomicBool::new(false));
+    let mut state = ClientState::new();
 
     // 测试处理游戏结束消息
-    let result = handle_game_message(game_over_msg, &mut board).await;
+    let result = handle_game_message(game_over_msg, &mut state);
     assert!(result); // 应该返回 true 表示游戏结束
 }
 
 #[tokio::test]
 async fn test_invalid_move() {
     // 模拟无效移动消息
-    let move_msg = GameMessage::Move { row: 3, col: 3 }; // 超出范围
-    let mut board = Board::new();
-    let game_over = Arc::new(AtomicBool::new(false));
+    let move_msg = ClientState::new().move_request(3, 3); // 超出范围
+    let mut state = ClientState::new();
 
     // 测试处理无效移动
-    let result = handle_game_message(move_msg, &mut board).await;
+    let result = handle_game_message(move_msg, &mut state);
     assert!(!result); // 应该返回 false 表示游戏继续
 }
 
 #[tokio::test]
 async fn test_player_quit() {
-    let game_over = Arc::new(AtomicBool::new(false));
-    let (tx, _rx) = tokio::sync::mpsc::channel::<Message>(32);
-    let result = handle_user_input(&tx, &game_over).await;
-    assert!(result);
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(32);
+    let state = Arc::new(tokio::sync::Mutex::new(ClientState::new()));
+    let result = handle_user_input(&tx, &state).await;
+    assert!(matches!(result, Ok(true)));
+    // 正常退出时先告诉服务器再关闭连接
+    let goodbye = serde_json::to_string(&GameMessage::Goodbye).unwrap();
+    assert_eq!(rx.recv().await, Some(Message::Text(goodbye)));
+    assert_eq!(rx.recv().await, Some(Message::Close(None)));
+}
+
+#[tokio::test]
+async fn test_confirmed_quit_resigns_without_asking() {
+    // 终端界面里已经按键确认过，不再从标准输入读回答
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(32);
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::Black);
+    state.game_id = Some("game".to_string());
+    let state = Arc::new(tokio::sync::Mutex::new(state));
+    let result = run_command("quit", &tx, &state, None).await;
+    assert!(matches!(result, Ok(true)));
+    let resign = serde_json::to_string(&GameMessage::Resign).unwrap();
+    assert_eq!(rx.recv().await, Some(Message::Text(resign)));
+    let goodbye = serde_json::to_string(&GameMessage::Goodbye).unwrap();
+    assert_eq!(rx.recv().await, Some(Message::Text(goodbye)));
+}
+
+struct CountingNotifier(Arc<AtomicU64>);
+
+impl Notifier for CountingNotifier {
+    fn time_warning(&self, remaining_secs: u64) {
+        self.0.store(remaining_secs, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn test_time_warning_only_notifies_own_clock() {
+    let last_warning = Arc::new(AtomicU64::new(0));
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::White);
+    state
+        .notifiers
+        .push(Box::new(CountingNotifier(last_warning.clone())));
+
+    // 对手的倒计时不触发提醒钩子
+    let opponent_warning = GameMessage::TimeWarning {
+        player: PlayerRole::Black,
+        remaining_secs: 10,
+    };
+    assert!(!handle_game_message(opponent_warning, &mut state));
+    assert_eq!(last_warning.load(Ordering::SeqCst), 0);
+
+    let own_warning = GameMessage::TimeWarning {
+        player: PlayerRole::White,
+        remaining_secs: 5,
+    };
+    assert!(!handle_game_message(own_warning, &mut state));
+    assert_eq!(last_warning.load(Ordering::SeqCst), 5);
+}
+
+struct RecordingNotifier(Arc<std::sync::Mutex<Vec<ClientEvent>>>);
+
+impl Notifier for RecordingNotifier {
+    fn on_event(&self, event: &ClientEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[test]
+fn test_game_events_reach_notifiers() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::White);
+    state.username = Some("bob".to_string());
+    state
+        .notifiers
+        .push(Box::new(RecordingNotifier(events.clone())));
+
+    let messages = [
+        GameMessage::MoveApplied {
+            row: 7,
+            col: 7,
+            by: PlayerRole::Black,
+            next_player: PlayerRole::White,
+            move_number: 1,
+        },
+        GameMessage::TurnNotification {
+            player: PlayerRole::White,
+        },
+        // 对手的回合和自己说的话不提醒
+        GameMessage::TurnNotification {
+            player: PlayerRole::Black,
+        },
+        GameMessage::Chat {
+            from: "bob".to_string(),
+            text: "你好".to_string(),
+        },
+        GameMessage::Chat {
+            from: "alice".to_string(),
+            text: "好棋".to_string(),
+        },
+        GameMessage::GameOver {
+            winner: None,
+            winning_line: Vec::new(),
+            reason: None,
+        },
+    ];
+    for msg in messages {
+        handle_game_message(msg, &mut state);
+    }
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            ClientEvent::MoveMade {
+                by: PlayerRole::Black,
+                row: 7,
+                col: 7,
+            },
+            ClientEvent::YourTurn,
+            ClientEvent::ChatReceived {
+                from: "alice".to_string(),
+                text: "好棋".to_string(),
+            },
+            ClientEvent::GameOver { winner: None },
+        ]
+    );
+}
+
+#[test]
+fn test_local_clock_ticks_between_updates_and_warns_once() {
+    use client::LocalClock;
+    use std::time::{Duration, Instant};
+
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut clock = LocalClock::new(12_000, 60_000, start);
+    clock.tick(PlayerRole::Black, at(1_500));
+    assert_eq!(clock.remaining(PlayerRole::Black), 10_500);
+    assert_eq!(clock.remaining(PlayerRole::White), 60_000);
+    assert!(!clock.is_low(PlayerRole::Black));
+    assert_eq!(clock.low_time_warning(PlayerRole::Black), None);
+
+    // 跌破 10 秒时只提醒一次，剩余秒数向上取整；扣到零为止
+    clock.tick(PlayerRole::Black, at(2_300));
+    assert!(clock.is_low(PlayerRole::Black));
+    assert_eq!(clock.low_time_warning(PlayerRole::Black), Some(10));
+    assert_eq!(clock.low_time_warning(PlayerRole::Black), None);
+    clock.tick(PlayerRole::Black, at(32_300));
+    assert_eq!(clock.remaining(PlayerRole::Black), 0);
+
+    // 服务器对齐后加了秒回到阈值以上，再跌破时重新提醒；对齐前空闲的一分钟不算
+    clock.sync(15_000, 60_000, at(92_300));
+    assert_eq!(clock.low_time_warning(PlayerRole::Black), None);
+    clock.tick(PlayerRole::Black, at(98_300));
+    assert_eq!(clock.low_time_warning(PlayerRole::Black), Some(9));
+}
+
+#[test]
+fn test_tick_clock_runs_the_side_to_move_and_rings_for_own_clock() {
+    use client::LocalClock;
+    use std::time::{Duration, Instant};
+
+    let last_warning = Arc::new(AtomicU64::new(0));
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::White);
+    state
+        .notifiers
+        .push(Box::new(CountingNotifier(last_warning.clone())));
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    state.clock = Some(LocalClock::new(9_000, 11_000, start));
+
+    // 黑方先走：只扣黑方的时间，对手的时间再少也不响铃
+    state.tick_clock(at(2_000));
+    let clock = state.clock.unwrap();
+    assert_eq!(clock.remaining(PlayerRole::Black), 7_000);
+    assert_eq!(clock.remaining(PlayerRole::White), 11_000);
+    assert_eq!(last_warning.load(Ordering::SeqCst), 0);
+
+    state.board.current_player = PlayerRole::White;
+    state.tick_clock(at(3_500));
+    assert_eq!(last_warning.load(Ordering::SeqCst), 10);
+    // 本地已经提醒过，服务器随后发来的 10 秒和 5 秒提醒只显示文字，不再响铃
+    last_warning.store(0, Ordering::SeqCst);
+    let warning = |remaining_secs| GameMessage::TimeWarning {
+        player: PlayerRole::White,
+        remaining_secs,
+    };
+    handle_game_message(warning(10), &mut state);
+    assert_eq!(last_warning.load(Ordering::SeqCst), 0);
+    handle_game_message(warning(5), &mut state);
+    assert_eq!(last_warning.load(Ordering::SeqCst), 0);
+
+    // 对局结束后时钟停住
+    state.finished = true;
+    state.tick_clock(at(8_500));
+    assert_eq!(state.clock.unwrap().remaining(PlayerRole::White), 9_500);
+}
+
+// 界面语言是全局状态，切换语言的测试需要串行执行
+static LANG_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[test]
+fn test_message_catalog_switching() {
+    use client::{set_lang, t, Lang};
+    let _guard = LANG_LOCK.lock().unwrap();
+
+    assert_eq!(Lang::parse("EN"), Some(Lang::En));
+    assert_eq!(Lang::parse("klingon"), None);
+
+    set_lang(Lang::En);
+    assert_eq!(t!("msg.turn", "Black"), "Black to move");
+    set_lang(Lang::Zh);
+    assert_eq!(t!("msg.turn", "黑方"), "轮到玩家 黑方 移动");
+    // 未知的键原样返回
+    assert_eq!(t!("no.such.key"), "no.such.key");
+}
+
+#[test]
+fn test_language_detection_and_localized_error_codes() {
+    use chess::{ErrorReply, GameError};
+    use client::{error_text, set_lang, Lang};
+    let _guard = LANG_LOCK.lock().unwrap();
+
+    assert_eq!(Lang::from_locale("en_US.UTF-8"), Some(Lang::En));
+    assert_eq!(Lang::from_locale("en_GB"), Some(Lang::En));
+    assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Some(Lang::Zh));
+    assert_eq!(Lang::from_locale("C"), None);
+    // 命令行优先于配置文件
+    assert_eq!(Lang::detect(Some("en"), Some(Lang::Zh)), Lang::En);
+    assert_eq!(Lang::detect(Some("klingon"), Some(Lang::En)), Lang::En);
+
+    // 服务器只发错误码和中文说明，英文界面按错误码显示
+    let reply: ErrorReply = GameError::PositionOccupied("(7, 7)".to_string()).into();
+    set_lang(Lang::En);
+    assert_eq!(error_text(&reply), "That point is already taken");
+    // 旧服务器不带错误码，只能原样显示
+    assert_eq!(error_text(&ErrorReply::from("旧消息")), "旧消息");
+    set_lang(Lang::Zh);
+    assert_eq!(error_text(&reply), "位置已被占用: (7, 7)");
+}
+
+#[test]
+fn test_accessible_move_description() {
+    use chess::Board;
+    use client::{describe_move, set_lang, Lang};
+    let _guard = LANG_LOCK.lock().unwrap();
+
+    set_lang(Lang::En);
+    let mut board = Board::new();
+    for (row, col) in [(7, 7), (0, 0), (8, 7), (0, 2), (9, 7)] {
+        board.make_move(row, col).unwrap();
+    }
+    assert_eq!(
+        describe_move(&board, PlayerRole::Black, 9, 7),
+        "Black placed at row 10, column 8; White to move; Black threatens 3 in a row on column 8"
+    );
+    set_lang(Lang::Zh);
+}
+
+#[test]
+fn test_replay_player_steps_through_moves() {
+    use chess::{ArchivedGame, Board, RulesConfig};
+    use client::ReplayPlayer;
+
+    let mut board = Board::new();
+    for (row, col) in [(7, 7), (0, 0), (8, 8)] {
+        board.make_move(row, col).unwrap();
+    }
+    let started_at = board.moves[0].timestamp;
+    let mut replay = ReplayPlayer::new(ArchivedGame {
+        id: "g1".to_string(),
+        black: "alice".to_string(),
+        white: "bob".to_string(),
+        winner: None,
+        reason: None,
+        moves: board.moves.clone(),
+        started_at,
+        ended_at: started_at,
+        seed: None,
+        rules: RulesConfig::default(),
+        position_hash: None,
+    });
+
+    assert!(!replay.step_back());
+    assert!(replay.step_forward());
+    assert_eq!(replay.board().cells[7][7], Some(PlayerRole::Black));
+    assert_eq!(replay.board().cells[0][0], None);
+
+    assert!(replay.jump(3));
+    assert_eq!(replay.board().cells, board.cells);
+    assert!(!replay.step_forward());
+    assert!(!replay.jump(4));
+
+    assert!(replay.step_back());
+    assert_eq!(replay.current_move().unwrap().player, PlayerRole::White);
+    assert_eq!(replay.board().current_player, PlayerRole::Black);
+}
+
+#[tokio::test]
+async fn test_watch_menu_picks_and_cycles_games() {
+    use chess::{GamePage, GameStatus, GameSummary};
+    use client::watch_command;
+
+    let summary = |id: &str| GameSummary {
+        id: id.to_string(),
+        black: "alice".to_string(),
+        white: "bob".to_string(),
+        status: GameStatus::Live,
+        result: None,
+        move_count: 0,
+        started_at: chrono::Utc::now(),
+        ended_at: None,
+        black_rating: Some(1500),
+        white_rating: None,
+    };
+    let mut state = ClientState::new();
+    let page = GamePage {
+        games: vec![summary("g1"), summary("g2")],
+        page: 1,
+        per_page: 20,
+        total: 2,
+    };
+    handle_game_message(GameMessage::GameList { page }, &mut state);
+
+    let watch = |state: &ClientState, input: &str| {
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        match watch_command(&parts, state) {
+            Some(GameMessage::Watch { game_id }) => Some(game_id),
+            _ => None,
+        }
+    };
+    assert_eq!(watch(&state, "watch 2").as_deref(), Some("g2"));
+    assert_eq!(watch(&state, "watch 3"), None);
+    assert_eq!(watch(&state, "watch 0"), None);
+    // 还没开始观战时 next 从第一盘开始，之后依次切换并回到开头
+    assert_eq!(watch(&state, "next").as_deref(), Some("g1"));
+    let game = summary("g2");
+    handle_game_message(GameMessage::Watching { game }, &mut state);
+    assert_eq!(watch(&state, "next").as_deref(), Some("g1"));
+
+    // 评估条跟着最近一次评估走，换一盘观战时清掉
+    let evaluation = GameMessage::Evaluation {
+        move_seq: 0,
+        score: 120,
+    };
+    handle_game_message(evaluation, &mut state);
+    assert_eq!(state.evaluation, Some(120));
+    let game = summary("g1");
+    handle_game_message(GameMessage::Watching { game }, &mut state);
+    assert_eq!(state.evaluation, None);
+    assert_eq!(client::player_label("alice", Some(1500)), "alice [1500]");
+    assert_eq!(client::player_label("bob", None), "bob");
+}
+
+#[tokio::test]
+async fn test_move_deltas_skip_duplicates_and_request_resync_on_gaps() {
+    let applied = |row, col, by: PlayerRole, move_number| GameMessage::MoveApplied {
+        row,
+        col,
+        by,
+        next_player: by.other(),
+        move_number,
+    };
+    let mut state = ClientState::new();
+    assert!(!handle_game_message(
+        applied(7, 7, PlayerRole::Black, 1),
+        &mut state
+    ));
+    // 同一步收到两次只落一次子
+    assert!(!handle_game_message(
+        applied(7, 7, PlayerRole::Black, 1),
+        &mut state
+    ));
+    assert_eq!(state.board.moves.len(), 1);
+    assert_eq!(state.board.current_player, PlayerRole::White);
+    assert!(!state.resync);
+
+    // 漏了第 2、3 步：照样摆上，并标记需要整盘局面
+    assert!(!handle_game_message(
+        applied(9, 9, PlayerRole::White, 4),
+        &mut state
+    ));
+    assert_eq!(state.board.cells[9][9], Some(PlayerRole::White));
+    assert_eq!(state.board.current_player, PlayerRole::Black);
+    assert!(state.resync);
+}
+
+#[tokio::test]
+async fn test_repeated_gaps_after_resync_surface_as_desync() {
+    let applied = |row, col, by: PlayerRole, move_number| GameMessage::MoveApplied {
+        row,
+        col,
+        by,
+        next_player: by.other(),
+        move_number,
+    };
+    let mut state = ClientState::new();
+    assert!(matches!(state.take_resync(), Ok(false)));
+
+    // 每一步都对不上：前几次要整盘局面，之后放弃自动修复
+    for attempt in 0..MAX_RESYNC_ATTEMPTS as usize {
+        let msg = applied(attempt, 0, PlayerRole::Black, 50);
+        assert!(!handle_game_message(msg, &mut state));
+        assert!(matches!(state.take_resync(), Ok(true)));
+    }
+    // 干净地接上一步后重新计数
+    let stones = state.board.cells.iter().flatten().flatten().count();
+    let current = state.board.current_player;
+    assert!(!handle_game_message(
+        applied(14, 14, current, stones + 1),
+        &mut state
+    ));
+    assert!(matches!(state.take_resync(), Ok(false)));
+    for attempt in 0..=MAX_RESYNC_ATTEMPTS as usize {
+        let msg = applied(attempt, 5, PlayerRole::Black, 50);
+        assert!(!handle_game_message(msg, &mut state));
+        let result = state.take_resync();
+        if attempt < MAX_RESYNC_ATTEMPTS as usize {
+            assert!(matches!(result, Ok(true)));
+        } else {
+            assert!(matches!(result, Err(ClientError::Desync)));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_chunked_message_is_reassembled() {
+    let mut board = Box::new([[None; 15]; 15]);
+    board[7][7] = Some(PlayerRole::Black);
+    let status = GameMessage::Status {
+        board,
+        current_player: PlayerRole::White,
+        move_number: 1,
+        last_move: Some((7, 7)),
+        moves: Vec::new(),
+    };
+    let limits = MessageLimits {
+        max_outbound_frame_bytes: 600,
+        ..MessageLimits::default()
+    };
+    let frames = limits.encode(&status);
+    assert!(frames.len() > 1);
+    assert!(frames.iter().all(|frame| frame.len() <= 600));
+
+    // 分段乱序到达也能拼回原消息
+    let mut state = ClientState::new();
+    for frame in frames.iter().rev() {
+        assert_eq!(state.board.cells[7][7], None);
+        let msg: GameMessage = serde_json::from_str(frame).unwrap();
+        assert!(!handle_game_message(msg, &mut state));
+    }
+    assert_eq!(state.board.cells[7][7], Some(PlayerRole::Black));
+    assert_eq!(state.board.current_player, PlayerRole::White);
+}
+
+#[test]
+fn test_move_preview_description() {
+    use chess::Board;
+    use client::{describe_preview, set_lang, Lang};
+    let _guard = LANG_LOCK.lock().unwrap();
+
+    set_lang(Lang::En);
+    let mut board = Board::new();
+    for (row, col) in [(7, 7), (0, 0), (8, 7), (0, 2)] {
+        board.make_move(row, col).unwrap();
+    }
+    let preview = board.preview_move(9, 7).unwrap();
+    assert_eq!(
+        describe_preview(&preview),
+        "Black trying row 10, column 8; open three on column 8"
+    );
+    let preview = board.preview_move(14, 14).unwrap();
+    assert_eq!(
+        describe_preview(&preview),
+        "Black trying row 15, column 15; no threats"
+    );
+    set_lang(Lang::Zh);
+}
+
+#[tokio::test]
+async fn test_script_sees_events_and_submits_moves() {
+    // 轮到自己时下在对手最后一子的右边，并批注对手落子
+    let script = r#"
+        fn on_event(event, game) {
+            if event.type == "MoveApplied" && event.by != game.role {
+                note(`opponent played ${event.row},${event.col}`);
+            }
+            if event.type == "TurnNotification" && event.player == game.role {
+                for row in 0..game.board.len() {
+                    for col in 0..game.board[row].len() {
+                        if game.board[row][col] == "Black" {
+                            play(row, col + 1);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    "#;
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::White);
+    state.game_id = Some("g1".to_string());
+    state.script = Some(ScriptHost::new(script).unwrap());
+
+    let applied = GameMessage::MoveApplied {
+        row: 7,
+        col: 7,
+        by: PlayerRole::Black,
+        next_player: PlayerRole::White,
+        move_number: 1,
+    };
+    handle_game_message(applied.clone(), &mut state);
+    assert!(state.run_script(&applied).is_empty());
+
+    let turn = GameMessage::TurnNotification {
+        player: PlayerRole::White,
+    };
+    handle_game_message(turn.clone(), &mut state);
+    let requests = state.run_script(&turn);
+    assert_eq!(requests.len(), 1);
+    assert!(matches!(
+        &requests[0],
+        GameMessage::Move { row: 7, col: 8, game_id, move_seq: 1, .. } if game_id == "g1"
+    ));
+
+    // 脚本出错或者死循环时不影响客户端
+    state.script = Some(ScriptHost::new("fn on_event(e, g) { loop {} }").unwrap());
+    assert!(state.run_script(&turn).is_empty());
+    assert!(ScriptHost::new("fn on_event(").is_err());
+}
+
+#[test]
+fn test_coordinate_notation_parsing() {
+    use client::{format_point, parse_point, parse_points, set_lang, Lang, NotationError};
+    let _guard = LANG_LOCK.lock().unwrap();
+
+    // 列字母从 A 起，行号从下往上数，15 路棋盘的天元是 H8
+    assert_eq!(parse_point("H8", 15), Ok((7, 7)));
+    assert_eq!(parse_point("a1", 15), Ok((14, 0)));
+    assert_eq!(parse_point("O15", 15), Ok((0, 14)));
+    assert_eq!(format_point(7, 7, 15).as_deref(), Some("H8"));
+    assert_eq!(format_point(0, 14, 15).as_deref(), Some("O15"));
+
+    // 两种写法可以混用，六子棋一回合给两个点
+    assert_eq!(parse_points(&["7", "7"], 15), Ok(vec![(7, 7)]));
+    assert_eq!(
+        parse_points(&["H8", "7", "8"], 15),
+        Ok(vec![(7, 7), (7, 8)])
+    );
+
+    assert_eq!(parse_points(&[], 15), Err(NotationError::Empty));
+    assert_eq!(
+        parse_points(&["7"], 15),
+        Err(NotationError::MissingColumn(7))
+    );
+    assert_eq!(
+        parse_point("P1", 15),
+        Err(NotationError::OffBoard("P1".to_string()))
+    );
+    assert_eq!(
+        parse_point("H0", 15),
+        Err(NotationError::OffBoard("H0".to_string()))
+    );
+    assert_eq!(
+        parse_points(&["15", "0"], 15),
+        Err(NotationError::OffBoard("15 0".to_string()))
+    );
+    assert_eq!(
+        parse_points(&["8H"], 15),
+        Err(NotationError::Unrecognized("8H".to_string()))
+    );
+
+    // 出错时说明哪里不对、该怎么写
+    set_lang(Lang::En);
+    assert_eq!(
+        NotationError::Unrecognized("8H".to_string()).to_string(),
+        "Cannot read '8H': use '<row> <col>' (e.g. 7 7) or a column letter and row number (e.g. H8)"
+    );
+    assert_eq!(
+        NotationError::OffBoard("P1".to_string()).to_string(),
+        "P1 is off the board"
+    );
+    set_lang(Lang::Zh);
+}
+
+#[tokio::test]
+async fn test_move_command_accepts_coordinate_notation() {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(32);
+    let state = Arc::new(tokio::sync::Mutex::new(ClientState::new()));
+    let result = run_command("move H8", &tx, &state, None).await;
+    assert!(matches!(result, Ok(false)));
+    let Some(Message::Text(json)) = rx.recv().await else {
+        panic!("没有发出落子请求");
+    };
+    assert!(matches!(
+        serde_json::from_str(&json).unwrap(),
+        GameMessage::Move {
+            row: 7,
+            col: 7,
+            second: None,
+            ..
+        }
+    ));
+
+    // 看不懂的坐标不发给服务器
+    let result = run_command("move Z99", &tx, &state, None).await;
+    assert!(matches!(result, Ok(false)));
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_local_game_against_engine() {
+    use chess::Difficulty;
+    use client::LocalGame;
+
+    let mut game = LocalGame::vs_ai(PlayerRole::Black, Difficulty::Easy);
+    assert_eq!(game.ai_role(), Some(PlayerRole::White));
+    assert!(!game.play(7, 7).await.unwrap());
+    // 电脑用服务器的引擎回一手，轮回到人
+    assert!(!game.ai_turn().await.unwrap());
+    assert_eq!(game.state.board.move_number(), 2);
+    assert_eq!(game.state.board.current_player, PlayerRole::Black);
+    // 占了的位置照常报错，不换手
+    assert!(game.play(7, 7).await.is_err());
+    assert_eq!(game.state.board.current_player, PlayerRole::Black);
+}
+
+#[tokio::test]
+async fn test_local_game_detects_five_in_a_row() {
+    use chess::Difficulty;
+    use client::LocalGame;
+
+    let mut game = LocalGame::vs_ai(PlayerRole::Black, Difficulty::Easy);
+    for col in 0..4 {
+        assert!(!game.play(7, col).await.unwrap());
+        assert!(!game.play(0, col).await.unwrap());
+    }
+    assert!(game.play(7, 4).await.unwrap());
+    assert!(game.state.finished);
+    assert!(game.play(8, 8).await.is_err());
+}
+
+#[tokio::test]
+async fn test_hotseat_alternates_undoes_and_exports_sgf() {
+    use chess::RulesConfig;
+    use client::LocalGame;
+
+    let rules = RulesConfig {
+        board_size: 9,
+        win_length: 4,
+        ..RulesConfig::default()
+    };
+    let mut game = LocalGame::hotseat(rules);
+    assert_eq!(game.ai_role(), None);
+    // 两个人轮流落子，没有电脑插手
+    assert!(!game.play(4, 4).await.unwrap());
+    assert_eq!(game.state.board.current_player, PlayerRole::White);
+    assert!(!game.play(0, 0).await.unwrap());
+    assert_eq!(game.state.board.current_player, PlayerRole::Black);
+    // 悔棋一次只撤回一子
+    assert_eq!(game.undo(), 1);
+    assert_eq!(game.state.board.move_number(), 1);
+    assert_eq!(game.state.board.current_player, PlayerRole::White);
+
+    // 9 路棋盘四连就赢，超出棋盘的点报错
+    assert!(game.play(8, 8).await.is_ok());
+    assert!(game.play(9, 9).await.is_err());
+    for col in 5..7 {
+        assert!(!game.play(4, col).await.unwrap());
+        assert!(!game.play(0, col).await.unwrap());
+    }
+    assert!(game.play(4, 7).await.unwrap());
+    let sgf = game.export_sgf();
+    assert!(sgf.contains("SZ[9]"));
+    assert!(sgf.contains("RE[B+]"));
+
+    // 结束后悔棋可以接着下，结果也跟着撤销
+    assert_eq!(game.undo(), 1);
+    assert!(!game.state.finished);
+    assert!(!game.export_sgf().contains("RE[B+]"));
+    assert!(!game.play(3, 3).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_local_undo_takes_back_the_computer_reply() {
+    use chess::Difficulty;
+    use client::LocalGame;
+
+    let mut game = LocalGame::vs_ai(PlayerRole::Black, Difficulty::Easy);
+    assert_eq!(game.undo(), 0);
+    game.play(7, 7).await.unwrap();
+    game.ai_turn().await.unwrap();
+    // 连电脑的应手一起撤回，又轮到人
+    assert_eq!(game.undo(), 2);
+    assert_eq!(game.state.board.move_number(), 0);
+    assert_eq!(game.state.board.current_player, PlayerRole::Black);
+    let computer = format!("PW[{}]", client::t!("local.computer"));
+    assert!(game.export_sgf().contains(&computer));
+}
+
+#[tokio::test]
+async fn test_analysis_steps_branches_and_suggests() {
+    use chess::{Board, Difficulty};
+    use client::Analysis;
+
+    // 黑棋第九手连成五子
+    let mut board = Board::new();
+    for col in 0..4 {
+        board.make_move(7, col).unwrap();
+        board.make_move(0, col).unwrap();
+    }
+    board.make_move(7, 4).unwrap();
+    let mut analysis = Analysis::new(board.clone());
+    assert_eq!((analysis.position(), analysis.len()), (9, 9));
+    assert!(!analysis.step_forward());
+    // 终局不能再试下，也没有续着
+    assert!(analysis.try_move(8, 8).is_err());
+    assert!(analysis.best_move(Difficulty::Easy.budget()).is_none());
+
+    // 退回一手，换一个点试下，撤回后分支清空
+    assert!(analysis.step_back());
+    assert_eq!(analysis.board().move_number(), 8);
+    analysis.try_move(8, 8).unwrap();
+    assert!(analysis.try_move(8, 8).is_err());
+    assert_eq!(analysis.branch(), &[(8, 8)]);
+    assert_eq!(analysis.board().cells[8][8], Some(PlayerRole::Black));
+    assert!(analysis.undo());
+    assert!(!analysis.undo());
+
+    // 跳到别的手数时丢掉分支；引擎替轮到的一方找棋
+    analysis.try_move(8, 8).unwrap();
+    assert!(analysis.jump(4));
+    assert!(analysis.branch().is_empty());
+    assert_eq!(analysis.board().move_number(), 4);
+    assert!(!analysis.jump(10));
+    assert!(analysis.best_move(Difficulty::Easy.budget()).is_some());
+
+    // analyze 命令只在结束后打开，打开后 move 只落在本地
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Message>(32);
+    let state = Arc::new(tokio::sync::Mutex::new(ClientState::new()));
+    run_command("analyze", &tx, &state, None).await.unwrap();
+    assert!(state.lock().await.analysis.is_none());
+    {
+        let mut state = state.lock().await;
+        state.board = board;
+        state.finished = true;
+    }
+    run_command("analyze", &tx, &state, None).await.unwrap();
+    run_command("prev", &tx, &state, None).await.unwrap();
+    run_command("move I9", &tx, &state, None).await.unwrap();
+    assert!(rx.try_recv().is_err());
+    let analysis = state.lock().await.analysis.clone().unwrap();
+    assert_eq!((analysis.position(), analysis.branch().len()), (8, 1));
+    run_command("analyze off", &tx, &state, None).await.unwrap();
+    assert!(state.lock().await.analysis.is_none());
+}
+
+#[test]
+fn test_board_renderers() {
+    use chess::Board;
+    use client::{is_star_point, BoardRenderer, BoardStyle};
+
+    let mut board = Board::new();
+    board.make_move(7, 7).unwrap();
+    board.make_move(0, 0).unwrap();
+
+    // 老样子的输出不变，最后一手加圆括号
+    let plain = BoardRenderer::PLAIN.render(&board, &[]);
+    let lines: Vec<&str> = plain.lines().collect();
+    assert_eq!(lines.len(), 15);
+    assert!(lines[0].starts_with("(O) - "));
+    assert_eq!(&lines[7][21..24], " X ");
+
+    // 制表符棋盘：角、边、星位和天元，带行列号
+    let unicode = BoardRenderer {
+        style: BoardStyle::Unicode,
+        color: false,
+        labels: true,
+    };
+    let text = unicode.render(&board, &[(7, 7)]);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 17);
+    assert!(lines[0].trim_start().starts_with("0  1  2"));
+    assert!(lines[1].starts_with("  0 ◎──┬─"));
+    assert!(lines[1].ends_with("──┐  15"));
+    assert!(lines[4].contains("─╋─"));
+    assert!(lines[8].contains("─★─"));
+    assert!(lines[15].starts_with(" 14 └─"));
+    assert!(lines[16].trim_start().starts_with("A  B  C"));
+    assert!(is_star_point(7, 7, 15));
+    assert!(is_star_point(2, 6, 9));
+    assert!(!is_star_point(3, 3, 9));
+
+    // 上色时不换符号，用 ANSI 颜色标出
+    let colored = BoardRenderer {
+        color: true,
+        ..unicode
+    };
+    let text = colored.render(&board, &[]);
+    assert!(text.contains("\x1b[30;43m○\x1b[0m"));
+    assert!(text.contains("\x1b[91m●\x1b[0m"));
+}
+
+#[test]
+fn test_pending_move_keeps_its_nonce_until_acknowledged() {
+    let nonce = |msg: &GameMessage| match msg {
+        GameMessage::Move { client_nonce, .. } => *client_nonce,
+        _ => unreachable!(),
+    };
+    let mut state = ClientState::new();
+    state.player_role = Some(PlayerRole::Black);
+    state.game_id = Some("game".to_string());
+
+    // 同一步再请求一次沿用原来的随机数，服务器认得出是重发
+    let first = state.move_request(7, 7);
+    assert_eq!(nonce(&state.move_request(7, 7)), nonce(&first));
+    assert_eq!(
+        state.pending_resend().as_ref().map(nonce),
+        Some(nonce(&first))
+    );
+
+    // 换一步就是新的请求
+    let other = state.move_request(7, 8);
+    assert_ne!(nonce(&other), nonce(&first));
+
+    // 确认之后不再重发
+    let ack = GameMessage::MoveAck {
+        game_id: "game".to_string(),
+        move_seq: 0,
+    };
+    handle_game_message(ack, &mut state);
+    assert!(state.pending_move.is_none());
+    assert!(state.pending_resend().is_none());
+}
+
+#[test]
+fn test_status_without_move_list_keeps_the_history() {
+    // 每步之后的 Status 和窗口快照不带落子列表
+    let without_moves = |msg: GameMessage| match msg {
+        GameMessage::Status {
+            board,
+            current_player,
+            move_number,
+            last_move,
+            ..
+        } => GameMessage::Status {
+            board,
+            current_player,
+            move_number,
+            last_move,
+            moves: Vec::new(),
+        },
+        _ => unreachable!(),
+    };
+    let mut server = chess::Board::new();
+    server.make_move(7, 7).unwrap();
+    server.make_move(7, 8).unwrap();
+    let mut state = ClientState::new();
+    handle_game_message(server.status(), &mut state);
+    assert_eq!(state.board.moves.len(), 2);
+
+    // 正好接上的一步补进记录，最近一手跟着 last_move
+    server.make_move(8, 8).unwrap();
+    handle_game_message(without_moves(server.status()), &mut state);
+    assert_eq!(state.board.moves.len(), 3);
+    assert_eq!(state.board.last_move(), Some((8, 8)));
+    assert!(!state.take_resync().unwrap());
+
+    // 接不上时保留本地记录，再要一次整盘局面
+    server.make_move(9, 9).unwrap();
+    server.make_move(10, 10).unwrap();
+    handle_game_message(without_moves(server.status()), &mut state);
+    assert_eq!(state.board.moves.len(), 3);
+    assert!(state.take_resync().unwrap());
+    handle_game_message(server.status(), &mut state);
+    assert_eq!(state.board.moves.len(), 5);
+    assert_eq!(state.board.last_move(), Some((10, 10)));
+}
+
+#[test]
+fn test_unknown_messages_are_skipped() {
+    use client::parse_frame;
+
+    // 更新的服务器加的消息和字段值不会让客户端断开
+    assert!(parse_frame(r#"{"Sparkle":{"level":3}}"#).is_none());
+    assert!(parse_frame("not json").is_none());
+
+    let over = r#"{"GameOver":{"winner":null,"winning_line":[],"reason":"comet_strike"}}"#;
+    let Some(msg) = parse_frame(over) else {
+        panic!("GameOver with a new reason should still parse");
+    };
+    assert!(matches!(
+        msg,
+        GameMessage::GameOver {
+            reason: Some(GameOverReason::Unknown),
+            ..
+        }
+    ));
+    let mut state = ClientState::new();
+    handle_game_message(msg, &mut state);
+    assert!(state.finished);
+}
+
+#[test]
+fn test_out_of_range_rules_fall_back_to_default() {
+    use chess::RulesConfig;
+
+    let connect = |board_size| GameMessage::ConnectResponse {
+        username: "alice".to_string(),
+        player_role: PlayerRole::Black,
+        rating: 1500,
+        user_id: "u1".to_string(),
+        game_id: "g1".to_string(),
+        protocol_version: chess::PROTOCOL_VERSION,
+        capabilities: Vec::new(),
+        rules: RulesConfig {
+            board_size,
+            ..RulesConfig::default()
+        },
+    };
+    let mut state = ClientState::new();
+    handle_game_message(connect(9), &mut state);
+    assert_eq!(state.board.rules.board_size, 9);
+
+    // 超过 15 路的棋盘没法画，也不能拿来下标
+    handle_game_message(connect(19), &mut state);
+    assert_eq!(state.board.rules, RulesConfig::default());
+    handle_game_message(
+        GameMessage::MoveApplied {
+            row: 16,
+            col: 16,
+            by: PlayerRole::Black,
+            next_player: PlayerRole::White,
+            move_number: 1,
+        },
+        &mut state,
+    );
+    assert!(state.resync);
 }
@@ -0,0 +1,149 @@
+// 和服务器共用同一份协议样例和对局脚本
+#[path = "../../chess/tests/conformance/mod.rs"]
+mod conformance;
+
+use chess::{GameMessage, PlayerRole};
+use client::{handle_game_message, Backoff, ClientError, ClientState, Reconnector};
+use conformance::{assert_roundtrip, connect_request, samples, start_server, SCRIPT};
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[tokio::test]
+async fn test_client_handles_every_message() {
+    for msg in samples() {
+        assert_roundtrip(&msg);
+        // 客户端按自己的依赖解析服务器发来的 JSON，并能处理每一种消息
+        let json = serde_json::to_string(&msg).unwrap();
+        let parsed: GameMessage = serde_json::from_str(&json).unwrap();
+        let mut state = ClientState::new();
+        let over = handle_game_message(parsed, &mut state);
+        let ends_game = matches!(
+            msg,
+            GameMessage::GameOver { .. }
+                | GameMessage::ServerShutdown
+                | GameMessage::SessionTransferred
+                | GameMessage::Kicked { .. }
+                | GameMessage::ProtocolViolation { .. }
+                | GameMessage::DemoClosed { .. }
+        );
+        assert_eq!(over, ends_game);
+    }
+}
+
+fn stones(state: &ClientState) -> usize {
+    state.board.cells.iter().flatten().flatten().count()
+}
+
+// 像客户端主循环一样把收到的消息交给 handle_game_message，
+// 直到满足条件或者对局结束
+async fn pump(client: &mut Client, state: &mut ClientState, done: impl Fn(&ClientState) -> bool) {
+    let read = async {
+        while !done(state) {
+            let Some(Ok(frame)) = client.next().await else {
+                panic!("连接已关闭");
+            };
+            if let Message::Text(text) = frame {
+                let msg: GameMessage = serde_json::from_str(&text).unwrap();
+                if handle_game_message(msg, state) {
+                    return;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), read)
+        .await
+        .expect("等待消息超时")
+}
+
+#[tokio::test]
+async fn test_scripted_game_through_client_code_paths() {
+    let url = start_server().await;
+    let mut players = Vec::new();
+    for name in ["alice", "bob"] {
+        let (mut client, _) = connect_async(&url).await.unwrap();
+        let json = serde_json::to_string(&connect_request(name)).unwrap();
+        client.send(Message::Text(json)).await.unwrap();
+        let mut state = ClientState::new();
+        pump(&mut client, &mut state, |state| state.game_id.is_some()).await;
+        players.push((client, state));
+    }
+    assert_eq!(players[0].1.player_role, Some(PlayerRole::Black));
+    assert_eq!(players[1].1.player_role, Some(PlayerRole::White));
+
+    for (seq, &(row, col)) in SCRIPT.iter().enumerate() {
+        let (mover, state) = &mut players[seq % 2];
+        let json = serde_json::to_string(&state.move_request(row, col)).unwrap();
+        mover.send(Message::Text(json)).await.unwrap();
+        for (client, state) in players.iter_mut() {
+            pump(client, state, |state| stones(state) > seq).await;
+        }
+    }
+
+    // 最后一步之后双方都能走到对局结束，本地棋盘和脚本一致
+    for (client, state) in players.iter_mut() {
+        pump(client, state, |_| false).await;
+        for (seq, &(row, col)) in SCRIPT.iter().enumerate() {
+            let expected = if seq % 2 == 0 {
+                PlayerRole::Black
+            } else {
+                PlayerRole::White
+            };
+            assert_eq!(state.board.cells[row][col], Some(expected));
+        }
+        assert_eq!(stones(state), SCRIPT.len());
+    }
+}
+
+#[test]
+fn test_reconnect_backoff_doubles_up_to_the_cap() {
+    let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(300), 4);
+    let delays: Vec<_> = std::iter::from_fn(|| backoff.next_delay()).collect();
+    assert_eq!(
+        delays,
+        [100, 200, 300, 300].map(Duration::from_millis).to_vec()
+    );
+    assert_eq!(backoff.attempts(), 4);
+}
+
+#[tokio::test]
+async fn test_client_reconnects_and_resyncs_from_status() {
+    let url = start_server().await;
+    let mut players = Vec::new();
+    for name in ["alice", "bob"] {
+        let (mut client, _) = connect_async(&url).await.unwrap();
+        let json = serde_json::to_string(&connect_request(name)).unwrap();
+        client.send(Message::Text(json)).await.unwrap();
+        let mut state = ClientState::new();
+        pump(&mut client, &mut state, |state| state.game_id.is_some()).await;
+        players.push((client, state));
+    }
+    let (alice, state) = &mut players[0];
+    let json = serde_json::to_string(&state.move_request(7, 7)).unwrap();
+    alice.send(Message::Text(json)).await.unwrap();
+    pump(alice, state, |state| stones(state) == 1).await;
+
+    // alice 掉线，用同一个请求重连后回到原座位，棋盘从服务器发来的整盘局面恢复
+    let (alice, state) = players.remove(0);
+    let game_id = state.game_id.unwrap();
+    drop(alice);
+    let backoff = Backoff::new(Duration::from_millis(50), Duration::from_millis(200), 5);
+    let mut reconnector = Reconnector::new(&url, connect_request("alice")).with_backoff(backoff);
+    let (mut alice, response) = reconnector.reconnect(&game_id).await.unwrap();
+    let mut state = ClientState::new();
+    handle_game_message(response, &mut state);
+    assert_eq!(state.game_id.as_deref(), Some(game_id.as_str()));
+    pump(&mut alice, &mut state, |state| stones(state) == 1).await;
+    assert_eq!(state.player_role, Some(PlayerRole::Black));
+    assert_eq!(state.board.cells[7][7], Some(PlayerRole::Black));
+    assert_eq!(state.board.current_player, PlayerRole::White);
+
+    // 服务器把重连的玩家分到了别的对局：不留下，报错返回
+    drop(alice);
+    let result = reconnector.reconnect("another-game").await;
+    assert!(matches!(result, Err(ClientError::Connect(_))));
+}
@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use chess::{GameFilter, GameMessage, GameStatus, PlayerRole};
+use client::{
+    capture_output, describe_preview, format_clock, player_label, role_name, t, ClientState,
+};
+use eframe::egui::{self, Align2, Color32, FontId, Sense, Vec2};
+use tokio::runtime::Handle;
+
+use crate::{board_view, stone_color, Connection, LoginDetails};
+
+// 消息窗格最多保留的行数
+const LOG_LINES: usize = 500;
+pub const DEFAULT_SERVER_URL: &str = "ws://localhost:8080";
+// 棋钟在走的时候隔这么久重画一次
+const CLOCK_REPAINT: Duration = Duration::from_millis(100);
+
+// 图形界面：左边是双方的面板和对局操作，右边是大厅里的对局列表和聊天，
+// 下边是消息和命令输入框，中间是可以点击落子的棋盘
+pub struct GomokuApp {
+    runtime: Handle,
+    login: LoginDetails,
+    connection: Option<Connection>,
+    // 断开后留着，回到登录界面时显示原因
+    last_error: Option<String>,
+    // say! 的输出，和终端界面的消息窗格一样
+    output: mpsc::Receiver<String>,
+    log: VecDeque<String>,
+    input: String,
+    chat_input: String,
+    // 开了落子确认时，预览过、等再点一次的那一格
+    armed: Option<(usize, usize)>,
+}
+
+impl GomokuApp {
+    pub fn new(runtime: Handle, login: LoginDetails) -> Self {
+        Self {
+            runtime,
+            login,
+            connection: None,
+            last_error: None,
+            output: capture_output(),
+            log: VecDeque::new(),
+            input: String::new(),
+            chat_input: String::new(),
+            armed: None,
+        }
+    }
+
+    fn connect(&mut self, ctx: &egui::Context) {
+        let ctx = ctx.clone();
+        self.log.clear();
+        self.last_error = None;
+        self.connection = Some(Connection::spawn(
+            &self.runtime,
+            self.login.clone(),
+            move || ctx.request_repaint(),
+        ));
+    }
+
+    fn login_view(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(t!("gui.title"));
+            egui::Grid::new("login").num_columns(2).show(ui, |ui| {
+                ui.label(t!("gui.server"));
+                ui.text_edit_singleline(&mut self.login.url);
+                ui.end_row();
+                ui.label(t!("gui.username"));
+                ui.text_edit_singleline(&mut self.login.username);
+                ui.end_row();
+                ui.label(t!("gui.password"));
+                ui.add(egui::TextEdit::singleline(&mut self.login.password).password(true));
+                ui.end_row();
+            });
+            if ui.button(t!("gui.connect")).clicked() {
+                self.connect(ctx);
+            }
+            if let Some(error) = &self.last_error {
+                ui.colored_label(Color32::RED, error);
+            }
+        });
+    }
+
+    fn game_view(&mut self, ctx: &egui::Context, connection: &Connection) {
+        let session = connection.session.lock().unwrap();
+        let names = session.names.clone();
+        let winning_line = session.winning_line.clone();
+        let chat = session.chat.clone();
+        let connected = session.connected;
+        drop(session);
+        let mut state = connection.state.blocking_lock();
+        state.tick_clock(Instant::now());
+        if state.clock.is_some() && !state.finished {
+            ctx.request_repaint_after(CLOCK_REPAINT);
+        }
+
+        egui::SidePanel::left("players")
+            .resizable(false)
+            .show(ctx, |ui| {
+                if !connected {
+                    ui.label(t!("gui.connecting"));
+                }
+                for player in [PlayerRole::Black, PlayerRole::White] {
+                    player_panel(ui, &state, player, names.get(&player));
+                    ui.separator();
+                }
+                ui.label(match state.player_role {
+                    Some(role) => t!("tui.you", role_name(role)),
+                    None => t!("role.spectator"),
+                });
+                ui.label(if state.finished {
+                    t!("tui.finished")
+                } else {
+                    t!("msg.turn", role_name(state.board.current_player))
+                });
+                ui.label(t!("tui.move", state.board.move_number()));
+                if let Some(score) = state.evaluation {
+                    evaluation_meter(ui, score);
+                }
+                ui.separator();
+                if state.finished {
+                    if ui.button(t!("gui.find_opponent")).clicked() {
+                        connection.send(GameMessage::FindOpponent);
+                    }
+                } else if state.player_role.is_some() {
+                    if ui.button(t!("gui.resign")).clicked() {
+                        connection.send(GameMessage::Resign);
+                    }
+                    if ui.button(t!("gui.offer_draw")).clicked() {
+                        connection.send(GameMessage::OfferDraw);
+                    }
+                    if ui.button(t!("gui.accept_draw")).clicked() {
+                        connection.send(GameMessage::AnswerDraw { accept: true });
+                    }
+                }
+            });
+
+        egui::SidePanel::right("lobby").show(ctx, |ui| {
+            ui.heading(t!("gui.lobby"));
+            if ui.button(t!("gui.refresh")).clicked() {
+                connection.send(GameMessage::ListGames {
+                    filter: GameFilter {
+                        status: Some(GameStatus::Live),
+                        ..GameFilter::default()
+                    },
+                });
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("lobby")
+                .max_height(ui.available_height() / 2.0)
+                .show(ui, |ui| {
+                    for game in &state.game_list {
+                        ui.horizontal(|ui| {
+                            ui.label(t!(
+                                "gui.lobby_entry",
+                                player_label(&game.black, game.black_rating),
+                                player_label(&game.white, game.white_rating),
+                                game.move_count
+                            ));
+                            if ui.small_button(t!("gui.watch")).clicked() {
+                                connection.send(GameMessage::Watch {
+                                    game_id: game.id.clone(),
+                                });
+                            }
+                        });
+                    }
+                });
+
+            // 双方和观战者都能说话，服务器转发给所有人，包括自己
+            ui.separator();
+            ui.heading(t!("gui.chat"));
+            let input = ui.add(
+                egui::TextEdit::singleline(&mut self.chat_input)
+                    .hint_text(t!("gui.chat_hint"))
+                    .desired_width(f32::INFINITY),
+            );
+            let entered = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let clicked = ui.button(t!("gui.send")).clicked();
+            if (entered || clicked) && !self.chat_input.trim().is_empty() {
+                let text = std::mem::take(&mut self.chat_input).trim().to_string();
+                connection.send(GameMessage::SendChat { text });
+                if entered {
+                    input.request_focus();
+                }
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("chat")
+                .stick_to_bottom(true)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for (from, text) in &chat {
+                        ui.label(t!("msg.chat", from, text));
+                    }
+                });
+        });
+
+        egui::TopBottomPanel::bottom("messages")
+            .resizable(true)
+            .min_height(120.0)
+            .show(ctx, |ui| {
+                let input = ui.add(
+                    egui::TextEdit::singleline(&mut self.input)
+                        .hint_text(t!("gui.command_hint"))
+                        .desired_width(f32::INFINITY),
+                );
+                if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    connection.command(std::mem::take(&mut self.input));
+                    input.request_focus();
+                }
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for line in &self.log {
+                            ui.label(line);
+                        }
+                    });
+            });
+
+        // 轮到自己时在鼠标下画出落子预览，点下去和终端里的 move 命令一样。
+        // 分析模式下画试验棋盘，双方都可以点，落子只下在本地
+        let (board, my_turn) = match &state.analysis {
+            Some(analysis) => (analysis.board(), true),
+            None => (
+                state.board.clone(),
+                !state.finished && state.player_role == Some(state.board.current_player),
+            ),
+        };
+        let clicked = egui::CentralPanel::default()
+            .show(ctx, |ui| {
+                board_view(
+                    ui,
+                    &board,
+                    &winning_line,
+                    my_turn.then_some(board.current_player),
+                )
+            })
+            .inner;
+        let Some((row, col)) = clicked.filter(|_| my_turn) else {
+            return;
+        };
+        // 开了落子确认时第一次点只预览，同一格再点一次才落子
+        if state.confirm_moves && state.analysis.is_none() && self.armed != Some((row, col)) {
+            match state.board.preview_move(row, col) {
+                Ok(preview) => {
+                    self.armed = Some((row, col));
+                    self.log.push_back(describe_preview(&preview));
+                    self.log.push_back(t!("input.confirm_again"));
+                }
+                Err(e) => self.log.push_back(t!("msg.error", e)),
+            }
+            return;
+        }
+        drop(state);
+        self.armed = None;
+        connection.command(format!("move {} {}", row, col));
+    }
+}
+
+// 一方的面板：用户名首字母的头像、用户名和剩余时间，轮到这一方时加粗
+fn player_panel(ui: &mut egui::Ui, state: &ClientState, player: PlayerRole, name: Option<&String>) {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(Vec2::splat(36.0), Sense::hover());
+        let painter = ui.painter();
+        painter.circle_filled(rect.center(), 18.0, stone_color(player));
+        let initial = name
+            .and_then(|name| name.chars().next())
+            .map(|c| c.to_uppercase().to_string())
+            .unwrap_or_default();
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            initial,
+            FontId::proportional(18.0),
+            stone_color(player.other()),
+        );
+        ui.vertical(|ui| {
+            let title = format!(
+                "{} {}",
+                role_name(player),
+                name.map(String::as_str).unwrap_or("-")
+            );
+            if !state.finished && state.board.current_player == player {
+                ui.strong(title);
+            } else {
+                ui.label(title);
+            }
+            // 不到 10 秒时标红
+            if let Some(clock) = state.clock {
+                let text = egui::RichText::new(format_clock(clock.remaining(player))).monospace();
+                if clock.is_low(player) {
+                    ui.label(text.color(Color32::RED).strong());
+                } else {
+                    ui.label(text);
+                }
+            }
+        });
+    });
+}
+
+// 观战时的评估条：黑色部分越长黑方越占优，正中间是均势，刻度和终端里的评估条一样
+fn evaluation_meter(ui: &mut egui::Ui, score: i32) {
+    const RANGE: i32 = 1_000;
+    ui.label(t!("gui.evaluation", score));
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(160.0, 12.0), Sense::hover());
+    let black = (score.clamp(-RANGE, RANGE) + RANGE) as f32 / (2 * RANGE) as f32;
+    let mut black_part = rect;
+    black_part.set_width(rect.width() * black);
+    let painter = ui.painter();
+    painter.rect_filled(rect, 2.0, stone_color(PlayerRole::White));
+    painter.rect_filled(black_part, 2.0, stone_color(PlayerRole::Black));
+}
+
+impl eframe::App for GomokuApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Ok(text) = self.output.try_recv() {
+            self.log
+                .extend(text.trim_start_matches('\n').lines().map(String::from));
+        }
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+        // 连接断开后回到登录界面，消息留着
+        if let Some(connection) = &self.connection {
+            let session = connection.session.lock().unwrap();
+            if session.closed {
+                self.last_error = session.error.clone();
+                drop(session);
+                self.connection = None;
+            }
+        }
+        match self.connection.take() {
+            Some(connection) => {
+                self.game_view(ctx, &connection);
+                self.connection = Some(connection);
+            }
+            None => self.login_view(ctx),
+        }
+    }
+}
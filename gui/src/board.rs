@@ -0,0 +1,123 @@
+use chess::{Board, PlayerRole};
+use client::{column_letter, is_star_point};
+use eframe::egui::{self, Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+
+// 棋盘底色和线条，和木质棋盘差不多
+const WOOD: Color32 = Color32::from_rgb(220, 179, 92);
+const LINE: Color32 = Color32::from_rgb(60, 40, 20);
+const LAST_MOVE: Color32 = Color32::from_rgb(220, 40, 40);
+
+// 棋盘在窗口里的位置：四周留出一格画坐标，交叉点之间等距
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardGeometry {
+    // 左上角交叉点的位置
+    pub origin: Pos2,
+    // 相邻交叉点的距离
+    pub spacing: f32,
+    pub size: usize,
+}
+
+impl BoardGeometry {
+    // 在给定的正方形区域里放下 size 路棋盘
+    pub fn fit(rect: Rect, size: usize) -> Self {
+        let side = rect.width().min(rect.height());
+        let spacing = side / (size + 1) as f32;
+        Self {
+            origin: rect.min + Vec2::splat(spacing),
+            spacing,
+            size,
+        }
+    }
+
+    // 交叉点 (行, 列) 在屏幕上的位置
+    pub fn point(&self, row: usize, col: usize) -> Pos2 {
+        self.origin + Vec2::new(col as f32, row as f32) * self.spacing
+    }
+
+    // 点到的交叉点，离最近的交叉点超过半格时不算
+    pub fn cell_at(&self, pos: Pos2) -> Option<(usize, usize)> {
+        let offset = (pos - self.origin) / self.spacing;
+        let (row, col) = (offset.y.round(), offset.x.round());
+        let max = (self.size - 1) as f32;
+        if !(0.0..=max).contains(&row) || !(0.0..=max).contains(&col) {
+            return None;
+        }
+        let snapped = self.point(row as usize, col as usize);
+        (snapped.distance(pos) <= self.spacing / 2.0).then_some((row as usize, col as usize))
+    }
+}
+
+// 画出棋盘，返回这一帧点到的交叉点。hover 为真时在鼠标下画出落子的预览
+pub fn board_view(
+    ui: &mut egui::Ui,
+    board: &Board,
+    highlight: &[(usize, usize)],
+    hover: Option<PlayerRole>,
+) -> Option<(usize, usize)> {
+    let side = ui.available_width().min(ui.available_height());
+    let (response, painter) = ui.allocate_painter(Vec2::splat(side), Sense::click());
+    let size = board.rules.board_size;
+    let geometry = BoardGeometry::fit(response.rect, size);
+    painter.rect_filled(response.rect, 4.0, WOOD);
+
+    let line = Stroke::new(1.0, LINE);
+    let last = size - 1;
+    let font = FontId::proportional(geometry.spacing * 0.35);
+    for i in 0..size {
+        painter.line_segment([geometry.point(i, 0), geometry.point(i, last)], line);
+        painter.line_segment([geometry.point(0, i), geometry.point(last, i)], line);
+        // 左边标 H8 式坐标的行号，下边标列字母
+        let rank = geometry.point(i, 0) - Vec2::new(geometry.spacing * 0.6, 0.0);
+        painter.text(rank, Align2::CENTER_CENTER, size - i, font.clone(), LINE);
+        if let Some(letter) = column_letter(i) {
+            let pos = geometry.point(last, i) + Vec2::new(0.0, geometry.spacing * 0.6);
+            painter.text(pos, Align2::CENTER_CENTER, letter, font.clone(), LINE);
+        }
+    }
+    for row in 0..size {
+        for col in 0..size {
+            if is_star_point(row, col, size) {
+                painter.circle_filled(geometry.point(row, col), geometry.spacing * 0.1, LINE);
+            }
+        }
+    }
+
+    let radius = geometry.spacing * 0.45;
+    for (row, cells) in board.cells.iter().take(size).enumerate() {
+        for (col, cell) in cells.iter().take(size).enumerate() {
+            if let Some(player) = cell {
+                let center = geometry.point(row, col);
+                painter.circle_filled(center, radius, stone_color(*player));
+                painter.circle_stroke(center, radius, Stroke::new(1.0, LINE));
+                if highlight.contains(&(row, col)) {
+                    painter.circle_stroke(center, radius * 1.1, Stroke::new(3.0, LAST_MOVE));
+                }
+            }
+        }
+    }
+    if let Some((row, col)) = board.last_move() {
+        painter.circle_filled(geometry.point(row, col), radius * 0.25, LAST_MOVE);
+    }
+
+    let hovered = response
+        .hover_pos()
+        .and_then(|pos| geometry.cell_at(pos))
+        .filter(|&(row, col)| board.cells[row][col].is_none());
+    if let (Some(player), Some((row, col))) = (hover, hovered) {
+        let color = stone_color(player).gamma_multiply(0.5);
+        painter.circle_filled(geometry.point(row, col), radius, color);
+    }
+    if response.clicked() {
+        return response
+            .interact_pointer_pos()
+            .and_then(|pos| geometry.cell_at(pos));
+    }
+    None
+}
+
+pub fn stone_color(player: PlayerRole) -> Color32 {
+    match player {
+        PlayerRole::Black => Color32::from_rgb(20, 20, 20),
+        PlayerRole::White => Color32::from_rgb(240, 240, 240),
+    }
+}
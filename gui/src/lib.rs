@@ -0,0 +1,7 @@
+pub mod app;
+pub mod board;
+pub mod net;
+
+pub use app::*;
+pub use board::*;
+pub use net::*;
@@ -0,0 +1,31 @@
+use client::{set_lang, t, ClientConfig, Lang};
+use gui::{GomokuApp, LoginDetails, DEFAULT_SERVER_URL};
+
+// --url <地址> 连接的服务器，--name <用户名> 预先填好登录界面
+fn parse_arg(args: &[String], flag: &str) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == flag)?;
+    args.get(pos + 1).cloned()
+}
+
+fn main() -> eframe::Result {
+    let args: Vec<String> = std::env::args().collect();
+    let config = ClientConfig::load();
+    set_lang(Lang::detect(
+        parse_arg(&args, "--lang").as_deref(),
+        config.lang,
+    ));
+
+    // 网络任务跑在后台的 tokio 运行时里，界面占着主线程
+    let runtime = tokio::runtime::Runtime::new().expect("无法创建 tokio 运行时");
+    let login = LoginDetails {
+        url: parse_arg(&args, "--url").unwrap_or_else(|| DEFAULT_SERVER_URL.to_string()),
+        username: parse_arg(&args, "--name").unwrap_or_default(),
+        password: String::new(),
+    };
+    let app = GomokuApp::new(runtime.handle().clone(), login);
+    let options = eframe::NativeOptions {
+        viewport: eframe::egui::ViewportBuilder::default().with_inner_size([1100.0, 760.0]),
+        ..Default::default()
+    };
+    eframe::run_native(&t!("gui.title"), options, Box::new(|_cc| Ok(Box::new(app))))
+}
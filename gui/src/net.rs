@@ -0,0 +1,251 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use chess::{Capability, GameMessage, PlayerRole, PROTOCOL_VERSION};
+use client::{
+    error_text, handle_game_message, parse_frame, player_label, run_command, t, ClientConfig,
+    ClientState,
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+// 聊天窗格最多保留的条数
+const CHAT_LINES: usize = 100;
+
+// 登录界面填的内容
+#[derive(Debug, Clone)]
+pub struct LoginDetails {
+    pub url: String,
+    pub username: String,
+    // 留空时以游客身份进入
+    pub password: String,
+}
+
+// ClientState 不记、界面又要显示的东西
+#[derive(Debug, Default)]
+pub struct Session {
+    // 双方的用户名，画在玩家面板上
+    pub names: HashMap<PlayerRole, String>,
+    // 上一盘连成一线的棋子，新的一盘开始时清空
+    pub winning_line: Vec<(usize, usize)>,
+    // 收到的聊天 (说话的人, 内容)，旧的在前
+    pub chat: VecDeque<(String, String)>,
+    pub connected: bool,
+    // 网络任务已经结束，界面回到登录
+    pub closed: bool,
+    // 连接断开或者登录失败的原因
+    pub error: Option<String>,
+}
+
+impl Session {
+    pub fn observe(&mut self, msg: &GameMessage) {
+        match msg {
+            GameMessage::ConnectResponse {
+                username,
+                player_role,
+                ..
+            } => {
+                self.names.clear();
+                self.names.insert(*player_role, username.clone());
+                self.winning_line.clear();
+            }
+            GameMessage::PlayerConnected { player, username } => {
+                self.names.insert(*player, username.clone());
+            }
+            GameMessage::Watching { game } => {
+                self.names.clear();
+                self.names.insert(
+                    PlayerRole::Black,
+                    player_label(&game.black, game.black_rating),
+                );
+                self.names.insert(
+                    PlayerRole::White,
+                    player_label(&game.white, game.white_rating),
+                );
+                self.winning_line.clear();
+            }
+            GameMessage::GameOver { winning_line, .. } => {
+                self.winning_line = winning_line.clone();
+            }
+            GameMessage::Chat { from, text } => {
+                self.chat.push_back((from.clone(), text.clone()));
+                if self.chat.len() > CHAT_LINES {
+                    self.chat.pop_front();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 一条到服务器的连接。网络任务跑在后台的 tokio 运行时里，棋盘、时钟和对局列表
+// 和终端客户端一样由 handle_game_message 更新，收到消息后请求重画界面
+pub struct Connection {
+    pub state: Arc<Mutex<ClientState>>,
+    pub session: Arc<std::sync::Mutex<Session>>,
+    outgoing: mpsc::Sender<Message>,
+    runtime: Handle,
+}
+
+impl Connection {
+    pub fn spawn(
+        runtime: &Handle,
+        details: LoginDetails,
+        repaint: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(ClientState::with_config(&ClientConfig::load())));
+        let session = Arc::new(std::sync::Mutex::new(Session::default()));
+        let (outgoing, requests) = mpsc::channel(32);
+        let (task_state, task_session) = (state.clone(), session.clone());
+        runtime.spawn(async move {
+            let result =
+                run_connection(details, &task_state, &task_session, requests, &repaint).await;
+            let mut session = task_session.lock().unwrap();
+            session.connected = false;
+            session.closed = true;
+            if let Err(e) = result {
+                session.error = Some(e);
+            }
+            drop(session);
+            repaint();
+        });
+        Self {
+            state,
+            session,
+            outgoing,
+            runtime: runtime.clone(),
+        }
+    }
+
+    // 在后台等发送队列有空位，界面线程不用等，消息也不会因为队列满了被丢掉。
+    // 网络任务已经结束时丢掉，界面上会显示断开的原因
+    pub fn send(&self, msg: GameMessage) {
+        let json = serde_json::to_string(&msg).unwrap();
+        let tx = self.outgoing.clone();
+        self.runtime.spawn(async move {
+            let _ = tx.send(Message::Text(json)).await;
+        });
+    }
+
+    // 输入框里的命令和终端客户端的一样，交给 run_command 处理
+    pub fn command(&self, input: String) {
+        let (tx, state) = (self.outgoing.clone(), self.state.clone());
+        self.runtime.spawn(async move {
+            let _ = run_command(input.trim(), &tx, &state, None).await;
+        });
+    }
+}
+
+async fn run_connection(
+    details: LoginDetails,
+    state: &Mutex<ClientState>,
+    session: &std::sync::Mutex<Session>,
+    mut requests: mpsc::Receiver<Message>,
+    repaint: &(impl Fn() + Send + Sync),
+) -> Result<(), String> {
+    let (ws_stream, _) = connect_async(details.url.as_str())
+        .await
+        .map_err(|e| t!("main.connect_failed", e))?;
+    let (mut write, mut read) = ws_stream.split();
+    let text = |msg: &GameMessage| Message::Text(serde_json::to_string(msg).unwrap());
+
+    // 填了密码时先登录换取令牌
+    let mut token = None;
+    if !details.password.is_empty() {
+        let login = GameMessage::Login {
+            username: details.username.clone(),
+            password: details.password.clone(),
+        };
+        write
+            .send(text(&login))
+            .await
+            .map_err(|e| t!("game.send_username_failed", e))?;
+        while token.is_none() {
+            let Some(Ok(frame)) = read.next().await else {
+                return Err(t!("error.server_closed"));
+            };
+            let Message::Text(frame) = frame else {
+                continue;
+            };
+            match parse_frame(&frame) {
+                Some(GameMessage::AuthToken { token: issued, .. }) => token = Some(issued),
+                Some(GameMessage::Error(e)) => return Err(t!("game.auth_failed", error_text(&e))),
+                _ => {}
+            }
+        }
+    }
+
+    let connect = GameMessage::ConnectRequest {
+        username: details.username,
+        token,
+        play_vs_ai: None,
+        invite: None,
+        protocol_version: Some(PROTOCOL_VERSION),
+        capabilities: Capability::ALL.to_vec(),
+        preferred_role: None,
+        resume: None,
+    };
+    write
+        .send(text(&connect))
+        .await
+        .map_err(|e| t!("game.send_username_failed", e))?;
+    session.lock().unwrap().connected = true;
+    repaint();
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                match request {
+                    // quit 命令发完 Goodbye 后关闭连接
+                    Some(Message::Close(frame)) => {
+                        let _ = write.send(Message::Close(frame)).await;
+                        return Ok(());
+                    }
+                    Some(request) => {
+                        write.send(request).await.map_err(|_| t!("error.server_closed"))?;
+                    }
+                    // 界面关掉了
+                    None => {
+                        let _ = write.send(text(&GameMessage::Goodbye)).await;
+                        let _ = write.close().await;
+                        return Ok(());
+                    }
+                }
+            }
+            frame = read.next() => {
+                let frame = match frame {
+                    Some(Ok(Message::Text(frame))) => frame,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        return Err(t!("error.server_closed"));
+                    }
+                    Some(Ok(_)) => continue,
+                };
+                let Some(msg) = parse_frame(&frame) else {
+                    continue;
+                };
+                session.lock().unwrap().observe(&msg);
+                let mut state = state.lock().await;
+                let finished = state.finished;
+                let over = handle_game_message(msg, &mut state);
+                let resync = state.take_resync().map_err(|e| e.to_string())?;
+                // 对局结束后连接保留，可以再找对手；被踢出、会话转移或者停服时到此为止
+                let game_ended = state.finished && !finished;
+                let closed = over && !game_ended;
+                drop(state);
+                repaint();
+                if closed {
+                    return Ok(());
+                }
+                if resync {
+                    write
+                        .send(text(&GameMessage::Resync))
+                        .await
+                        .map_err(|_| t!("error.server_closed"))?;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use chess::{GameMessage, GameOverReason, PlayerRole};
+use eframe::egui::{pos2, vec2, Rect};
+use gui::{BoardGeometry, Session};
+
+#[test]
+fn test_board_geometry_maps_clicks_to_points() {
+    // 16 格宽的正方形放 15 路棋盘，四周各留一格
+    let rect = Rect::from_min_size(pos2(100.0, 50.0), vec2(320.0, 400.0));
+    let geometry = BoardGeometry::fit(rect, 15);
+    assert_eq!(geometry.spacing, 20.0);
+    assert_eq!(geometry.point(0, 0), pos2(120.0, 70.0));
+    assert_eq!(geometry.point(7, 7), pos2(260.0, 210.0));
+
+    // 点在交叉点附近都算这一点，点在棋盘外或者两点正中间的外侧不算
+    assert_eq!(geometry.cell_at(pos2(262.0, 207.0)), Some((7, 7)));
+    assert_eq!(geometry.cell_at(pos2(400.0, 350.0)), Some((14, 14)));
+    assert_eq!(geometry.cell_at(pos2(105.0, 70.0)), None);
+    assert_eq!(geometry.cell_at(pos2(420.0, 210.0)), None);
+    assert_eq!(geometry.cell_at(pos2(269.0, 219.0)), None);
+}
+
+#[test]
+fn test_session_remembers_names_and_winning_line() {
+    let mut session = Session::default();
+    session.observe(&GameMessage::PlayerConnected {
+        player: PlayerRole::White,
+        username: "bob".to_string(),
+    });
+    session.observe(&GameMessage::GameOver {
+        winner: Some(PlayerRole::White),
+        winning_line: vec![(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)],
+        reason: Some(GameOverReason::FiveInARow),
+    });
+    assert_eq!(session.names.get(&PlayerRole::White).unwrap(), "bob");
+    assert_eq!(session.winning_line.len(), 5);
+}
+
+#[test]
+fn test_session_keeps_recent_chat() {
+    let mut session = Session::default();
+    for n in 0..150 {
+        session.observe(&GameMessage::Chat {
+            from: "alice".to_string(),
+            text: format!("hello {}", n),
+        });
+    }
+    // 只留最近的 100 条
+    assert_eq!(session.chat.len(), 100);
+    assert_eq!(session.chat.front().unwrap().1, "hello 50");
+    assert_eq!(session.chat.back().unwrap().1, "hello 149");
+}
@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chess::GameMessage;
+use client::{say, t};
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+use web_sys::{CloseEvent, MessageEvent, WebSocket};
+
+use crate::{encode, WebSession};
+
+// 给页面用的客户端：浏览器的 WebSocket 收到消息后交给 WebSession，
+// 每处理完一条就调用 on_update，页面据此重画棋盘和消息记录
+#[wasm_bindgen]
+pub struct WebClient {
+    socket: WebSocket,
+    session: Rc<RefCell<WebSession>>,
+    // 回调要和连接活得一样久
+    _onopen: Closure<dyn FnMut()>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+    _onclose: Closure<dyn FnMut(CloseEvent)>,
+}
+
+#[wasm_bindgen]
+impl WebClient {
+    #[wasm_bindgen(constructor)]
+    pub fn new(url: &str, username: &str, on_update: Function) -> Result<WebClient, JsValue> {
+        let socket = WebSocket::new(url)?;
+        let session = Rc::new(RefCell::new(WebSession::new(username)));
+        let update = move || {
+            let _ = on_update.call0(&JsValue::NULL);
+        };
+
+        let onopen = {
+            let (socket, session, update) = (socket.clone(), session.clone(), update.clone());
+            Closure::<dyn FnMut()>::new(move || {
+                say!("{}", t!("main.connected"));
+                let _ = socket.send_with_str(&session.borrow().connect_request());
+                update();
+            })
+        };
+        let onmessage = {
+            let (socket, session, update) = (socket.clone(), session.clone(), update.clone());
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let Some(frame) = event.data().as_string() else {
+                    return;
+                };
+                let result = session.borrow_mut().receive(&frame);
+                match result {
+                    Ok(replies) => {
+                        for reply in replies {
+                            let _ = socket.send_with_str(&reply);
+                        }
+                    }
+                    Err(e) => say!("{}", e),
+                }
+                if session.borrow().closed {
+                    let _ = socket.close();
+                }
+                update();
+            })
+        };
+        let onclose = {
+            let (session, update) = (session.clone(), update.clone());
+            Closure::<dyn FnMut(CloseEvent)>::new(move |_: CloseEvent| {
+                session.borrow_mut().closed = true;
+                say!("{}", t!("error.server_closed"));
+                update();
+            })
+        };
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+
+        Ok(WebClient {
+            socket,
+            session,
+            _onopen: onopen,
+            _onmessage: onmessage,
+            _onclose: onclose,
+        })
+    }
+
+    // 点击棋盘落子，不是自己的回合时返回 false
+    pub fn play(&self, row: usize, col: usize) -> bool {
+        let request = self.session.borrow_mut().play(row, col);
+        match request {
+            Some(request) => self.socket.send_with_str(&request).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn resign(&self) {
+        self.send(&GameMessage::Resign);
+    }
+
+    pub fn offer_draw(&self) {
+        self.send(&GameMessage::OfferDraw);
+    }
+
+    pub fn accept_draw(&self) {
+        self.send(&GameMessage::AnswerDraw { accept: true });
+    }
+
+    pub fn find_opponent(&self) {
+        self.send(&GameMessage::FindOpponent);
+    }
+
+    pub fn size(&self) -> usize {
+        self.session.borrow().state.board.rules.board_size
+    }
+
+    pub fn cells(&self) -> Vec<u8> {
+        self.session.borrow().cells()
+    }
+
+    pub fn last_move(&self) -> Option<Vec<usize>> {
+        let session = self.session.borrow();
+        let (row, col) = session.state.board.last_move()?;
+        Some(vec![row, col])
+    }
+
+    pub fn status(&self) -> String {
+        self.session.borrow().status()
+    }
+
+    // 新增的消息，一行一条
+    pub fn take_log(&self) -> String {
+        self.session.borrow().take_log().join("\n")
+    }
+
+    pub fn closed(&self) -> bool {
+        self.session.borrow().closed
+    }
+
+    pub fn close(&self) {
+        self.send(&GameMessage::Goodbye);
+        let _ = self.socket.close();
+    }
+
+    fn send(&self, msg: &GameMessage) {
+        let _ = self.socket.send_with_str(&encode(msg));
+    }
+}
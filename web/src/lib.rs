@@ -0,0 +1,7 @@
+#[cfg(target_arch = "wasm32")]
+pub mod bindings;
+pub mod session;
+
+#[cfg(target_arch = "wasm32")]
+pub use bindings::*;
+pub use session::*;
@@ -0,0 +1,112 @@
+use std::sync::mpsc;
+
+use chess::{Capability, GameMessage, PlayerRole, PROTOCOL_VERSION};
+use client::{capture_output, handle_game_message, parse_frame, role_name, t, ClientState};
+
+// 浏览器里的一条连接：和终端客户端共用 ClientState 和 handle_game_message，
+// 收发的都是 JSON 文本，WebSocket 本身由 bindings.rs 里的页面代码管
+pub struct WebSession {
+    pub state: ClientState,
+    username: String,
+    // say! 的输出，页面上显示成消息记录
+    output: mpsc::Receiver<String>,
+    // 被踢出、会话转移或者停服，连接到此为止
+    pub closed: bool,
+}
+
+impl WebSession {
+    pub fn new(username: &str) -> Self {
+        Self {
+            state: ClientState::new(),
+            username: username.to_string(),
+            output: capture_output(),
+            closed: false,
+        }
+    }
+
+    // 连接打开后发出的第一条消息
+    pub fn connect_request(&self) -> String {
+        encode(&GameMessage::ConnectRequest {
+            username: self.username.clone(),
+            token: None,
+            play_vs_ai: None,
+            invite: None,
+            protocol_version: Some(PROTOCOL_VERSION),
+            capabilities: Capability::ALL.to_vec(),
+            preferred_role: None,
+            resume: None,
+        })
+    }
+
+    // 处理服务器发来的一帧，返回要回给服务器的消息。本地棋盘和服务器对不上时先要一次整盘局面
+    pub fn receive(&mut self, frame: &str) -> Result<Vec<String>, String> {
+        let Some(msg) = parse_frame(frame) else {
+            return Ok(Vec::new());
+        };
+        let finished = self.state.finished;
+        let over = handle_game_message(msg, &mut self.state);
+        // 对局结束后连接保留，可以再找对手
+        let game_ended = self.state.finished && !finished;
+        self.closed = over && !game_ended;
+        if self.state.take_resync().map_err(|e| e.to_string())? {
+            return Ok(vec![encode(&GameMessage::Resync)]);
+        }
+        Ok(Vec::new())
+    }
+
+    // 点到的交叉点。不是自己的回合或者已经有子时返回 None，不发给服务器
+    pub fn play(&mut self, row: usize, col: usize) -> Option<String> {
+        let state = &mut self.state;
+        let my_turn = !state.finished && state.player_role == Some(state.board.current_player);
+        if !my_turn || state.board.validate_move(row, col).is_err() {
+            return None;
+        }
+        Some(encode(&state.move_request(row, col)))
+    }
+
+    // 棋盘逐行展开，0 是空点，1 是黑子，2 是白子
+    pub fn cells(&self) -> Vec<u8> {
+        let size = self.state.board.rules.board_size;
+        self.state.board.cells[..size]
+            .iter()
+            .flat_map(|row| row[..size].iter())
+            .map(|cell| match cell {
+                None => 0,
+                Some(PlayerRole::Black) => 1,
+                Some(PlayerRole::White) => 2,
+            })
+            .collect()
+    }
+
+    // 棋盘下面的一行状态：自己执哪一方，轮到谁
+    pub fn status(&self) -> String {
+        let state = &self.state;
+        let role = match state.player_role {
+            Some(role) => t!("tui.you", role_name(role)),
+            None => t!("role.spectator"),
+        };
+        let turn = if state.finished {
+            t!("tui.finished")
+        } else {
+            t!("msg.turn", role_name(state.board.current_player))
+        };
+        format!("{}  {}", role, turn)
+    }
+
+    // 上次取走之后新增的消息
+    pub fn take_log(&self) -> Vec<String> {
+        self.output
+            .try_iter()
+            .flat_map(|text| {
+                text.trim_start_matches('\n')
+                    .lines()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+pub fn encode(msg: &GameMessage) -> String {
+    serde_json::to_string(msg).unwrap()
+}
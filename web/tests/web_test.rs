@@ -0,0 +1,83 @@
+use chess::{GameMessage, PlayerRole, RulesConfig};
+use web::{encode, WebSession};
+
+fn connect_response(role: PlayerRole) -> String {
+    encode(&GameMessage::ConnectResponse {
+        username: "alice".to_string(),
+        player_role: role,
+        rating: 1500,
+        user_id: "u1".to_string(),
+        game_id: "g1".to_string(),
+        protocol_version: chess::PROTOCOL_VERSION,
+        capabilities: Vec::new(),
+        rules: RulesConfig::default(),
+    })
+}
+
+fn applied(row: usize, col: usize, by: PlayerRole, move_number: usize) -> String {
+    encode(&GameMessage::MoveApplied {
+        row,
+        col,
+        by,
+        next_player: by.other(),
+        move_number,
+    })
+}
+
+#[test]
+fn test_web_session_plays_from_json_frames() {
+    let mut session = WebSession::new("alice");
+    let request = serde_json::from_str::<GameMessage>(&session.connect_request()).unwrap();
+    assert!(matches!(
+        request,
+        GameMessage::ConnectRequest { ref username, .. } if username == "alice"
+    ));
+
+    assert!(session
+        .receive(&connect_response(PlayerRole::Black))
+        .unwrap()
+        .is_empty());
+    assert_eq!(session.state.player_role, Some(PlayerRole::Black));
+
+    // 轮到自己才发出落子请求，白方回合或者点到已有子的位置都不发
+    let request = serde_json::from_str::<GameMessage>(&session.play(7, 7).unwrap()).unwrap();
+    assert!(matches!(
+        request,
+        GameMessage::Move { row: 7, col: 7, ref game_id, move_seq: 0, .. } if game_id == "g1"
+    ));
+    session
+        .receive(&applied(7, 7, PlayerRole::Black, 1))
+        .unwrap();
+    assert_eq!(session.play(7, 8), None);
+    session
+        .receive(&applied(7, 8, PlayerRole::White, 2))
+        .unwrap();
+    assert_eq!(session.play(7, 7), None);
+    assert!(session.play(8, 8).is_some());
+
+    let cells = session.cells();
+    assert_eq!(cells.len(), 15 * 15);
+    assert_eq!(cells[7 * 15 + 7], 1);
+    assert_eq!(cells[7 * 15 + 8], 2);
+    assert_eq!(cells.iter().filter(|&&cell| cell != 0).count(), 2);
+    assert!(!session.take_log().is_empty());
+    assert!(session.take_log().is_empty());
+
+    // 漏收了一步时回一条 Resync，被踢出后连接结束
+    let replies = session
+        .receive(&applied(3, 3, PlayerRole::White, 5))
+        .unwrap();
+    assert!(matches!(
+        serde_json::from_str::<GameMessage>(&replies[0]).unwrap(),
+        GameMessage::Resync
+    ));
+    // 解析不了的帧跳过，连接照常
+    assert!(session.receive("not json").unwrap().is_empty());
+    assert!(!session.closed);
+    session
+        .receive(&encode(&GameMessage::Kicked {
+            reason: "bye".to_string(),
+        }))
+        .unwrap();
+    assert!(session.closed);
+}